@@ -0,0 +1,226 @@
+use crate::capture::{self, CaptureOptions, Item, Summary};
+use crate::clock::fit_line;
+use crate::sink::outln;
+use crate::value::{Style, Value};
+use crate::{EtherCATCommand, EtherNetFrame};
+use std::collections::HashMap;
+use std::fs::File;
+use std::ops::ControlFlow;
+use std::process::exit;
+
+/// Width of the bins the candidate clock offsets are counted in.
+const OFFSET_BIN_NS: i64 = 10_000;
+/// Passages of the first capture used to estimate the clock offset before matching.
+const OFFSET_SAMPLES: usize = 1000;
+/// Bins with at least this share of the votes of the best one are taken as equally likely.
+const OFFSET_TIE_RATIO: f64 = 0.9;
+/// Slack of the matching window once the clock offset and skew are fitted.
+const FITTED_MARGIN_NS: f64 = 1_000.0;
+
+/// A frame captured by one of the taps.
+#[derive(Clone, Copy)]
+struct Sighting {
+    frame_number: u64,
+    ts_ns: u64,
+    returned: bool,
+}
+
+/// Every EtherCAT frame of a capture, by key, in capture order.
+type Sightings = HashMap<Vec<u8>, Vec<Sighting>>;
+
+/// A frame seen by a tap on its way to the slaves and again on its way back.
+#[derive(Clone, Copy)]
+struct Passage {
+    out: Sighting,
+    back: Sighting,
+}
+
+/// A frame seen passing both taps in both directions.
+struct Match {
+    a: Passage,
+    b: Passage,
+}
+
+/// What identifies a frame at both taps. The slaves change the data, the working counter, the
+/// position addresses, the circulating bit and the locally administered MAC bit of a frame in
+/// flight, so these are left out.
+fn frame_key(frame: &EtherNetFrame) -> Vec<u8> {
+    use EtherCATCommand::*;
+
+    let mut key = frame.dst_mac.to_vec();
+    key.extend_from_slice(&frame.master_mac());
+    for datagram in &frame.ecat_frame.datagrams {
+        let header = &datagram.header;
+        let position = matches!(header.cmd, APRD | APWR | APRW | ARMW | BRD | BWR | BRW);
+        key.extend_from_slice(&[header.cmd as u8, header.index]);
+        key.extend_from_slice(&if position { 0 } else { header.slave_addr }.to_le_bytes());
+        key.extend_from_slice(&header.offset_addr.to_le_bytes());
+        key.extend_from_slice(&header.length.to_le_bytes());
+    }
+    key
+}
+
+fn load(file: File, options: &CaptureOptions) -> Result<(Sightings, Summary), String> {
+    let mut sightings = Sightings::new();
+    let summary = capture::read(file, options, |item| {
+        if let Item::Packet(packet) = item {
+            if let Some(frame) = packet.data.and_then(EtherNetFrame::parse_ethercat) {
+                let sighting = Sighting {
+                    frame_number: packet.info.frame_number,
+                    ts_ns: packet.info.ts_ns,
+                    returned: frame.is_returned(),
+                };
+                sightings.entry(frame_key(&frame)).or_default().push(sighting);
+            }
+        }
+        ControlFlow::Continue(())
+    })?;
+    Ok((sightings, summary))
+}
+
+/// Pairs every frame of the first capture sent to the slaves with its return, which the first
+/// slave marks in the source MAC address.
+fn passages(sightings: &Sightings) -> Vec<(&[u8], Passage)> {
+    let mut passages = Vec::new();
+    for (key, sightings) in sightings {
+        for pair in sightings.windows(2) {
+            if !pair[0].returned && pair[1].returned {
+                passages.push((key.as_slice(), Passage { out: pair[0], back: pair[1] }));
+            }
+        }
+    }
+    passages.sort_by_key(|(_, passage)| passage.out.ts_ns);
+    passages
+}
+
+/// The sightings of `key` in the second capture between two of its capture times.
+fn between<'a>(sightings: &'a Sightings, key: &[u8], from_ns: i64, to_ns: i64) -> &'a [Sighting] {
+    let Some(sightings) = sightings.get(key) else {
+        return &[];
+    };
+    let start = sightings.partition_point(|s| (s.ts_ns as i64) < from_ns);
+    let end = sightings.partition_point(|s| (s.ts_ns as i64) <= to_ns);
+    &sightings[start..end.max(start)]
+}
+
+/// Offset of the second capture clock, found by letting the first passages vote for the time
+/// differences to every sighting of the same frame. Frames repeating identically every cycle
+/// make several offsets about as likely; the smallest of them wins.
+fn coarse_offset(passages: &[(&[u8], Passage)], b: &Sightings, max_offset_ns: u64) -> Option<i64> {
+    let mut votes: HashMap<i64, usize> = HashMap::new();
+    for (key, passage) in passages.iter().take(OFFSET_SAMPLES) {
+        let t = passage.out.ts_ns as i64;
+        for sighting in between(b, key, t - max_offset_ns as i64, t + max_offset_ns as i64) {
+            let difference = sighting.ts_ns as i64 - passage.out.ts_ns as i64;
+            *votes.entry((difference as f64 / OFFSET_BIN_NS as f64).round() as i64).or_default() += 1;
+        }
+    }
+    let best = *votes.values().max()?;
+    votes
+        .into_iter()
+        .filter(|&(_, count)| count as f64 >= best as f64 * OFFSET_TIE_RATIO)
+        .map(|(bin, _)| bin * OFFSET_BIN_NS)
+        .min_by_key(|offset| offset.abs())
+}
+
+/// Matches every passage of the first capture with the two sightings of its frame in the
+/// second one, which lie between the two sightings at the first tap once the clocks agree.
+/// `offset_ns` gives the second clock minus the first one at a capture time of the first.
+fn match_passages(
+    passages: &[(&[u8], Passage)],
+    b: &Sightings,
+    offset_ns: impl Fn(u64) -> f64,
+    margin_ns: f64,
+) -> Vec<Match> {
+    let mut matches = Vec::new();
+    for &(key, a) in passages {
+        let from = a.out.ts_ns as i64 + (offset_ns(a.out.ts_ns) - margin_ns).round() as i64;
+        let to = a.back.ts_ns as i64 + (offset_ns(a.back.ts_ns) + margin_ns).round() as i64;
+        if let [out, back] = between(b, key, from, to) {
+            matches.push(Match { a, b: Passage { out: *out, back: *back } });
+        }
+    }
+    matches
+}
+
+/// Clock offset of the second capture seen by a matched frame, assuming it takes as long from
+/// the first tap to the second as back.
+fn measured_offset(m: &Match) -> f64 {
+    let there = m.b.out.ts_ns as i64 - m.a.out.ts_ns as i64;
+    let back = m.a.back.ts_ns as i64 - m.b.back.ts_ns as i64;
+    (there - back) as f64 / 2.0
+}
+
+/// Clock offset and skew of the second capture: `(t0, intercept, slope)` of the offset as a
+/// function of the first capture time.
+fn fit_offset(matches: &[Match]) -> (u64, f64, f64) {
+    let t0 = matches[0].a.out.ts_ns;
+    let points: Vec<(f64, f64)> = matches
+        .iter()
+        .map(|m| ((m.a.out.ts_ns - t0) as f64, measured_offset(m)))
+        .collect();
+    let (intercept, slope) = fit_line(&points);
+    (t0, intercept, slope)
+}
+
+/// Matches the frames of two captures taken at the same time at both ends of a segment and
+/// prints the time each frame took to cross it.
+pub fn run(file: File, options: &CaptureOptions, other: &str, max_offset_ns: u64) {
+    let (a, summary_a) = load(file, options).unwrap_or_else(|e| {
+        eprintln!("{}", e);
+        exit(1);
+    });
+    let other_file = capture::open(other).unwrap_or_else(|e| {
+        eprintln!("{}", e);
+        exit(1);
+    });
+    // the files a capture continues in are those of the first capture.
+    let other_options = CaptureOptions { next_files: Vec::new(), ..options.clone() };
+    let (b, summary_b) = load(other_file, &other_options).unwrap_or_else(|e| {
+        eprintln!("{} : {}", other, e);
+        exit(1);
+    });
+
+    let passages = passages(&a);
+    let Some(coarse) = coarse_offset(&passages, &b, max_offset_ns) else {
+        eprintln!("No frame of the capture was seen in {}", other);
+        exit(1);
+    };
+    let matches = match_passages(&passages, &b, |_| coarse as f64, 2.0 * OFFSET_BIN_NS as f64);
+    if matches.is_empty() {
+        eprintln!("No frame was seen passing both taps in both directions");
+        exit(1);
+    }
+    // match again with the fitted clocks, which also drops the pairs only the coarse offset allowed.
+    let (t0, intercept, slope) = fit_offset(&matches);
+    let offset = |ts: u64| intercept + slope * (ts as i64 - t0 as i64) as f64;
+    let residual = matches.iter().map(|m| (measured_offset(m) - offset(m.a.out.ts_ns)).abs()).fold(0.0, f64::max);
+    let matches = match_passages(&passages, &b, offset, FITTED_MARGIN_NS + 2.0 * residual);
+    if matches.is_empty() {
+        eprintln!("No frame was seen passing both taps in both directions");
+        exit(1);
+    }
+    let (t0, intercept, slope) = fit_offset(&matches);
+    let offset = |ts: u64| intercept + slope * (ts as i64 - t0 as i64) as f64;
+    let residual = matches.iter().map(|m| (measured_offset(m) - offset(m.a.out.ts_ns)).abs()).fold(0.0, f64::max);
+
+    outln!("frame_a,frame_b,capture_time,round_trip_a_ns,round_trip_b_ns,propagation_ns");
+    for m in &matches {
+        let round_trip_a = m.a.back.ts_ns - m.a.out.ts_ns;
+        let round_trip_b = m.b.back.ts_ns.saturating_sub(m.b.out.ts_ns);
+        // the round trip at the second tap, in the time of the first.
+        let round_trip_b_a = round_trip_b as f64 / (1.0 + slope);
+        let propagation = (round_trip_a as f64 - round_trip_b_a) / 2.0;
+        outln!("{},{},{},{},{},{}",
+            m.a.out.frame_number, m.b.out.frame_number, Value::Time(m.a.out.ts_ns).render(Style::Csv), round_trip_a, round_trip_b,
+            propagation.round());
+    }
+
+    eprintln!("Matched {} of {} frames sent and returned through the first tap", matches.len(), passages.len());
+    eprintln!("Offset at frame {}: second capture clock {} from the first", matches[0].a.out.frame_number,
+        Value::Offset(intercept).render(Style::Csv));
+    eprintln!("Skew: {:+.3} ppm", slope * 1e6);
+    eprintln!("Max residual: {} ns", residual.round());
+    summary_a.report();
+    summary_b.report();
+}
@@ -0,0 +1,69 @@
+use crate::clock::parse_timestamp;
+use std::fs;
+
+/// Timestamped free-text notes (`--annotate`) merged into the export as annotation rows.
+///
+/// The file has one `timestamp,note` line per note, with the timestamp either in epoch seconds
+/// or ISO 8601; a header line and blank lines are ignored.
+pub struct Annotations {
+    /// Notes sorted by time, in nanoseconds since the epoch.
+    notes: Vec<(u64, String)>,
+    next: usize,
+}
+
+impl Annotations {
+    pub fn load(path: &str) -> Result<Self, String> {
+        let text = fs::read_to_string(path).map_err(|e| format!("Cannot read {} : {}", path, e))?;
+        let mut notes = Vec::new();
+        for (i, line) in text.lines().enumerate() {
+            if line.trim().is_empty() {
+                continue;
+            }
+            let (timestamp, note) = line.split_once(',').unwrap_or((line, ""));
+            match parse_timestamp(timestamp) {
+                Ok(ts) => notes.push((ts, unquote(note.trim()))),
+                Err(_) if i == 0 => continue,
+                Err(e) => return Err(format!("{} line {} : {}", path, i + 1, e)),
+            }
+        }
+        // a stable sort keeps notes with the same time in file order.
+        notes.sort_by_key(|(ts, _)| *ts);
+        Ok(Annotations { notes, next: 0 })
+    }
+
+    /// Takes the notes that are due before a packet captured at `ts_ns`.
+    pub fn due(&mut self, ts_ns: u64) -> &[(u64, String)] {
+        let start = self.next;
+        while self.next < self.notes.len() && self.notes[self.next].0 <= ts_ns {
+            self.next += 1;
+        }
+        &self.notes[start..self.next]
+    }
+
+    /// Number of notes taken so far.
+    pub fn taken(&self) -> usize {
+        self.next
+    }
+
+    /// Drops the first `count` notes, already merged by the export being resumed.
+    pub fn skip(&mut self, count: usize) {
+        self.next = count.min(self.notes.len());
+    }
+
+    /// Takes the notes left after the last packet.
+    pub fn rest(&mut self) -> &[(u64, String)] {
+        self.due(u64::MAX)
+    }
+}
+
+fn unquote(field: &str) -> String {
+    match field.strip_prefix('"').and_then(|f| f.strip_suffix('"')) {
+        Some(inner) => inner.replace("\"\"", "\""),
+        None => field.to_string(),
+    }
+}
+
+/// Quotes a free-text field for the CSV output.
+pub fn quote(text: &str) -> String {
+    format!("\"{}\"", text.replace('"', "\"\""))
+}
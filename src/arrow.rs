@@ -0,0 +1,385 @@
+use crate::capture::CaptureOptions;
+use crate::filter::{self, Selection};
+use crate::mac::{self, MacFormat};
+use crate::sink::{self, Output};
+use crate::warnings::WarningLog;
+use crate::wkc::WkcModel;
+use std::fs::File;
+use std::io::Write;
+use std::process::exit;
+
+/// Magic of the Arrow IPC file format, padded to 8 bytes at the start of the file.
+const MAGIC: &[u8] = b"ARROW1";
+/// Rows of a record batch: the columns of one batch are held in memory until it is written.
+const BATCH_ROWS: usize = 65_536;
+/// Metadata version V5 of the Arrow format.
+const METADATA_VERSION: i16 = 4;
+/// Marks the start of an encapsulated message.
+const CONTINUATION: u32 = 0xFFFF_FFFF;
+
+/// Types of the columns, with the `Type` union of the Arrow schema they map to.
+#[derive(Clone, Copy)]
+enum ColumnType {
+    Int32,
+    Int64,
+    TimestampNanos,
+    Utf8,
+    Binary,
+}
+
+/// The columns of the export, those of the Parquet export, in file order.
+const SCHEMA: &[(&str, ColumnType)] = &[
+    ("frame_no", ColumnType::Int64),
+    ("ts", ColumnType::TimestampNanos),
+    ("src_mac", ColumnType::Utf8),
+    ("dst_mac", ColumnType::Utf8),
+    ("cmd", ColumnType::Utf8),
+    ("index", ColumnType::Int32),
+    ("adp", ColumnType::Int32),
+    ("ado", ColumnType::Int32),
+    ("length", ColumnType::Int32),
+    ("wkc", ColumnType::Int32),
+    ("data", ColumnType::Binary),
+];
+
+/// A field of a FlatBuffers table of the Arrow metadata.
+enum Field {
+    Absent,
+    Bool(bool),
+    U8(u8),
+    I16(i16),
+    I32(i32),
+    I64(i64),
+    Table(Table),
+    String(&'static str),
+    Tables(Vec<Table>),
+    /// A vector of structs of 8-byte aligned fields, as they are laid out.
+    Structs(usize, Vec<u8>),
+}
+
+/// A FlatBuffers table: its fields by ID.
+struct Table(Vec<Field>);
+
+impl Field {
+    /// Size of the field inline in its table: the scalar, or the offset to the object.
+    fn size(&self) -> usize {
+        match self {
+            Field::Absent => 0,
+            Field::Bool(_) | Field::U8(_) => 1,
+            Field::I16(_) => 2,
+            Field::I64(_) => 8,
+            _ => 4,
+        }
+    }
+}
+
+/// Lays out FlatBuffers front to back: every table is preceded by its vtable and followed by
+/// the objects it refers to, whose offsets are then filled in.
+#[derive(Default)]
+struct FlatBuilder {
+    buf: Vec<u8>,
+}
+
+impl FlatBuilder {
+    /// Pads the buffer up to a length of `remainder` modulo `align`.
+    fn pad(&mut self, align: usize, remainder: usize) {
+        while self.buf.len() % align != remainder {
+            self.buf.push(0);
+        }
+    }
+
+    fn patch_offset(&mut self, at: usize, target: usize) {
+        self.buf[at..at + 4].copy_from_slice(&((target - at) as u32).to_le_bytes());
+    }
+
+    /// Lays out a table and what it refers to; returns the position of the table. Fields are
+    /// laid out by decreasing size after the offset to the vtable, and the table starts 4 bytes
+    /// into an 8-byte boundary, so that every field is aligned.
+    fn table(&mut self, table: &Table) -> usize {
+        let mut order: Vec<usize> = (0..table.0.len()).filter(|&i| table.0[i].size() > 0).collect();
+        order.sort_by_key(|&i| std::cmp::Reverse(table.0[i].size()));
+        let mut offsets = vec![0u16; table.0.len()];
+        let mut size = 4;
+        for &i in &order {
+            offsets[i] = size as u16;
+            size += table.0[i].size();
+        }
+        let vtable_len = 4 + 2 * table.0.len();
+        self.pad(8, (12 - vtable_len % 8) % 8);
+        let vtable = self.buf.len();
+        self.buf.extend_from_slice(&(vtable_len as u16).to_le_bytes());
+        self.buf.extend_from_slice(&(size as u16).to_le_bytes());
+        for offset in &offsets {
+            self.buf.extend_from_slice(&offset.to_le_bytes());
+        }
+        let start = self.buf.len();
+        self.buf.extend_from_slice(&((start - vtable) as i32).to_le_bytes());
+        let mut children = Vec::new();
+        for &i in &order {
+            let field = &table.0[i];
+            match field {
+                Field::Absent => {}
+                Field::Bool(value) => self.buf.push(*value as u8),
+                Field::U8(value) => self.buf.push(*value),
+                Field::I16(value) => self.buf.extend_from_slice(&value.to_le_bytes()),
+                Field::I32(value) => self.buf.extend_from_slice(&value.to_le_bytes()),
+                Field::I64(value) => self.buf.extend_from_slice(&value.to_le_bytes()),
+                _ => {
+                    children.push((self.buf.len(), field));
+                    self.buf.extend_from_slice(&[0; 4]);
+                }
+            }
+        }
+        for (at, field) in children {
+            let target = match field {
+                Field::Table(table) => self.table(table),
+                Field::String(text) => {
+                    self.pad(4, 0);
+                    let target = self.buf.len();
+                    self.buf.extend_from_slice(&(text.len() as u32).to_le_bytes());
+                    self.buf.extend_from_slice(text.as_bytes());
+                    self.buf.push(0);
+                    target
+                }
+                Field::Tables(tables) => {
+                    self.pad(4, 0);
+                    let target = self.buf.len();
+                    self.buf.extend_from_slice(&(tables.len() as u32).to_le_bytes());
+                    let first = self.buf.len();
+                    self.buf.resize(first + 4 * tables.len(), 0);
+                    for (i, table) in tables.iter().enumerate() {
+                        let position = self.table(table);
+                        self.patch_offset(first + 4 * i, position);
+                    }
+                    target
+                }
+                Field::Structs(count, bytes) => {
+                    self.pad(8, 4);
+                    let target = self.buf.len();
+                    self.buf.extend_from_slice(&(*count as u32).to_le_bytes());
+                    self.buf.extend_from_slice(bytes);
+                    target
+                }
+                _ => unreachable!("scalars are inline"),
+            };
+            self.patch_offset(at, target);
+        }
+        start
+    }
+
+    /// The buffer of a root table, padded to 8 bytes.
+    fn finish(root: &Table) -> Vec<u8> {
+        let mut builder = FlatBuilder { buf: vec![0; 4] };
+        let position = builder.table(root);
+        builder.patch_offset(0, position);
+        builder.pad(8, 0);
+        builder.buf
+    }
+}
+
+/// The `Field` tables of the schema, with the `Type` union member of every column.
+fn schema() -> Table {
+    let fields = SCHEMA.iter().map(|&(name, column_type)| {
+        let (type_type, type_table) = match column_type {
+            ColumnType::Int32 => (2, Table(vec![Field::I32(32), Field::Bool(true)])),
+            ColumnType::Int64 => (2, Table(vec![Field::I32(64), Field::Bool(true)])),
+            ColumnType::Binary => (4, Table(vec![])),
+            ColumnType::Utf8 => (5, Table(vec![])),
+            // nanoseconds, in UTC.
+            ColumnType::TimestampNanos => (10, Table(vec![Field::I16(3), Field::String("UTC")])),
+        };
+        Table(vec![Field::String(name), Field::Bool(false), Field::U8(type_type), Field::Table(type_table), Field::Absent, Field::Tables(vec![])])
+    });
+    Table(vec![Field::I16(0), Field::Tables(fields.collect())])
+}
+
+/// A `Message` table of the given header type and header.
+fn message(header_type: u8, header: Table, body_len: usize) -> Vec<u8> {
+    FlatBuilder::finish(&Table(vec![Field::I16(METADATA_VERSION), Field::U8(header_type), Field::Table(header), Field::I64(body_len as i64)]))
+}
+
+/// A column of the record batch being collected: its values, and for the variable length
+/// columns the offsets of every value in them, which start with 0.
+#[derive(Default, Clone)]
+struct Column {
+    values: Vec<u8>,
+    offsets: Vec<i32>,
+}
+
+/// A `Block` of the footer: where a record batch message starts and how long its parts are.
+struct Block {
+    offset: u64,
+    metadata_len: usize,
+    body_len: usize,
+}
+
+/// Writes the datagrams as an Arrow IPC file, which pandas, Polars and DuckDB memory-map: the
+/// schema, then a record batch every `BATCH_ROWS` rows, and the footer indexing the batches.
+/// The columns are not nullable, so every validity buffer is empty.
+struct ArrowWriter {
+    out: Output,
+    written: u64,
+    columns: Vec<Column>,
+    rows: usize,
+    blocks: Vec<Block>,
+}
+
+impl ArrowWriter {
+    fn new(out: Output) -> std::io::Result<Self> {
+        let mut writer = ArrowWriter { out, written: 0, columns: Vec::new(), rows: 0, blocks: Vec::new() };
+        writer.reset();
+        writer.write(MAGIC)?;
+        writer.write(&[0; 2])?;
+        writer.write_message(1, schema(), &[])?;
+        Ok(writer)
+    }
+
+    fn reset(&mut self) {
+        self.columns = vec![Column { values: Vec::new(), offsets: vec![0] }; SCHEMA.len()];
+        self.rows = 0;
+    }
+
+    fn write(&mut self, bytes: &[u8]) -> std::io::Result<()> {
+        self.out.write_all(bytes)?;
+        self.written += bytes.len() as u64;
+        Ok(())
+    }
+
+    /// Writes an encapsulated message: the continuation marker, the metadata length, the
+    /// metadata and the body. Returns the block of the message.
+    fn write_message(&mut self, header_type: u8, header: Table, body: &[u8]) -> std::io::Result<Block> {
+        let metadata = message(header_type, header, body.len());
+        let offset = self.written;
+        self.write(&CONTINUATION.to_le_bytes())?;
+        self.write(&(metadata.len() as u32).to_le_bytes())?;
+        self.write(&metadata)?;
+        self.write(body)?;
+        Ok(Block { offset, metadata_len: 8 + metadata.len(), body_len: body.len() })
+    }
+
+    fn int32(&mut self, column: usize, value: i32) {
+        self.columns[column].values.extend_from_slice(&value.to_le_bytes());
+    }
+
+    fn int64(&mut self, column: usize, value: i64) {
+        self.columns[column].values.extend_from_slice(&value.to_le_bytes());
+    }
+
+    fn bytes(&mut self, column: usize, value: &[u8]) {
+        let column = &mut self.columns[column];
+        column.values.extend_from_slice(value);
+        column.offsets.push(column.values.len() as i32);
+    }
+
+    /// Ends a row whose every column was given a value, writing the batch when it is full.
+    fn end_row(&mut self) -> std::io::Result<()> {
+        self.rows += 1;
+        if self.rows == BATCH_ROWS {
+            self.flush_batch()?;
+        }
+        Ok(())
+    }
+
+    fn flush_batch(&mut self) -> std::io::Result<()> {
+        if self.rows == 0 {
+            return Ok(());
+        }
+        let mut body = Vec::new();
+        let mut buffers = Vec::new();
+        let mut nodes = Vec::new();
+        let mut buffer = |body: &mut Vec<u8>, bytes: &[u8]| {
+            buffers.extend_from_slice(&(body.len() as i64).to_le_bytes());
+            buffers.extend_from_slice(&(bytes.len() as i64).to_le_bytes());
+            body.extend_from_slice(bytes);
+            body.resize(body.len().next_multiple_of(8), 0);
+        };
+        for (column, &(_, column_type)) in self.columns.iter().zip(SCHEMA) {
+            nodes.extend_from_slice(&(self.rows as i64).to_le_bytes());
+            nodes.extend_from_slice(&0i64.to_le_bytes());
+            buffer(&mut body, &[]);
+            if matches!(column_type, ColumnType::Utf8 | ColumnType::Binary) {
+                let offsets: Vec<u8> = column.offsets.iter().flat_map(|offset| offset.to_le_bytes()).collect();
+                buffer(&mut body, &offsets);
+            }
+            buffer(&mut body, &column.values);
+        }
+        let buffer_count = buffers.len() / 16;
+        let batch = Table(vec![Field::I64(self.rows as i64), Field::Structs(SCHEMA.len(), nodes), Field::Structs(buffer_count, buffers)]);
+        let block = self.write_message(3, batch, &body)?;
+        self.blocks.push(block);
+        self.reset();
+        Ok(())
+    }
+
+    /// Writes the last batch and the footer: the schema again and the blocks of the batches,
+    /// its length and the magic.
+    fn finish(mut self) -> std::io::Result<()> {
+        self.flush_batch()?;
+        let mut blocks = Vec::new();
+        for block in &self.blocks {
+            blocks.extend_from_slice(&(block.offset as i64).to_le_bytes());
+            blocks.extend_from_slice(&(block.metadata_len as i32).to_le_bytes());
+            blocks.extend_from_slice(&[0; 4]);
+            blocks.extend_from_slice(&(block.body_len as i64).to_le_bytes());
+        }
+        let footer = FlatBuilder::finish(&Table(vec![
+            Field::I16(METADATA_VERSION),
+            Field::Table(schema()),
+            Field::Structs(0, Vec::new()),
+            Field::Structs(self.blocks.len(), blocks),
+        ]));
+        self.write(&footer)?;
+        self.write(&(footer.len() as u32).to_le_bytes())?;
+        self.write(MAGIC)?;
+        self.out.flush()
+    }
+}
+
+fn write_error<T>(e: std::io::Error) -> T {
+    eprintln!("Cannot write the Arrow export : {}", e);
+    exit(1);
+}
+
+/// Exports the datagrams as an Arrow IPC file, also known as Feather version 2, with the
+/// columns of the Parquet export in record batches of 65536 rows. Only the datagrams of the
+/// selection are exported. The file goes to `output`, or to stdout.
+pub fn run(
+    file: File,
+    options: &CaptureOptions,
+    selection: &Selection,
+    warnings: Option<WarningLog>,
+    wkc_model: Option<&WkcModel>,
+    output: Option<&str>,
+) {
+    let out = sink::open_output(output, false).unwrap_or_else(|e| {
+        eprintln!("{}", e);
+        exit(1);
+    });
+    let mut writer = ArrowWriter::new(out).unwrap_or_else(write_error);
+
+    let summary = filter::read_datagrams(file, options, selection, warnings, wkc_model, |info, frame, datagrams| {
+        let src_mac = mac::format(&frame.src_mac, MacFormat::Colon);
+        let dst_mac = mac::format(&frame.dst_mac, MacFormat::Colon);
+        for datagram in datagrams {
+            let header = &datagram.header;
+            writer.int64(0, info.frame_number as i64);
+            writer.int64(1, info.ts_ns as i64);
+            writer.bytes(2, src_mac.as_bytes());
+            writer.bytes(3, dst_mac.as_bytes());
+            writer.bytes(4, header.cmd.to_string().as_bytes());
+            writer.int32(5, header.index as i32);
+            writer.int32(6, header.slave_addr as i32);
+            writer.int32(7, header.offset_addr as i32);
+            writer.int32(8, header.length as i32);
+            writer.int32(9, datagram.wkc as i32);
+            writer.bytes(10, datagram.data);
+            writer.end_row().unwrap_or_else(write_error);
+        }
+    });
+    let summary = summary.unwrap_or_else(|e| {
+        eprintln!("{}", e);
+        exit(1);
+    });
+    writer.finish().unwrap_or_else(write_error);
+    summary.report();
+}
@@ -0,0 +1,214 @@
+use crate::eni::Eni;
+use crate::esi::DeviceCatalog;
+use crate::labels::Labels;
+use crate::mac::OuiTable;
+use crate::pdo_map::PdoMap;
+use crate::registers::RegisterNames;
+use crate::wkc::WkcModel;
+use crate::Args;
+use clap::Parser;
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::fs;
+use std::io::Write;
+use std::process::exit;
+use std::sync::Arc;
+
+/// The command line of a batch run: `batch jobs.toml`.
+#[derive(Parser, Debug)]
+#[command(about = "Run the exports and reports listed in a job file in one process")]
+struct BatchArgs {
+    /// Always `batch`
+    #[arg(hide = true)]
+    batch: String,
+    /// TOML file listing the jobs
+    jobs: String,
+}
+
+/// Jobs run by `batch`, loaded from a TOML file:
+///
+/// ```toml
+/// [[job]]
+/// input = "captures/line1.pcapng"
+/// output = "reports/line1.csv"
+/// args = ["--wkc-config", "wkc.toml", "--only-changes"]
+///
+/// # the arguments of an export or of a subcommand, as on the command line after --file
+/// [[job]]
+/// input = "captures/line1.pcapng"
+/// output = "reports/line1_foe.csv"
+/// args = ["--labels", "labels.toml", "foe"]
+/// ```
+///
+/// A job without an output writes to stdout. Paths are relative to the current directory, as on
+/// the command line.
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct JobsFile {
+    #[serde(default, rename = "job")]
+    jobs: Vec<Job>,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct Job {
+    input: String,
+    output: Option<String>,
+    #[serde(default)]
+    args: Vec<String>,
+}
+
+/// Whether the exporter was started as `batch jobs.toml`.
+pub fn invoked() -> bool {
+    std::env::args().nth(1).as_deref() == Some("batch")
+}
+
+/// Configuration files parsed once for all the jobs of a batch, by path. A single run starts
+/// with an empty cache.
+#[derive(Default)]
+pub struct Cache {
+    labels: HashMap<String, Labels>,
+    wkc_models: HashMap<String, WkcModel>,
+    enis: HashMap<String, Eni>,
+    oui_tables: HashMap<String, Arc<OuiTable>>,
+    register_names: HashMap<String, Arc<RegisterNames>>,
+    device_catalogs: HashMap<String, Arc<DeviceCatalog>>,
+    pdo_maps: HashMap<String, Arc<PdoMap>>,
+}
+
+impl Cache {
+    pub fn labels(&mut self, path: &str) -> Result<Labels, String> {
+        if !self.labels.contains_key(path) {
+            self.labels.insert(path.to_string(), Labels::load(path)?);
+        }
+        Ok(self.labels[path].clone())
+    }
+
+    pub fn wkc_model(&mut self, path: &str) -> Result<WkcModel, String> {
+        if !self.wkc_models.contains_key(path) {
+            self.wkc_models.insert(path.to_string(), WkcModel::load(path)?);
+        }
+        Ok(self.wkc_models[path].clone())
+    }
+
+    pub fn eni(&mut self, path: &str) -> Result<Eni, String> {
+        if !self.enis.contains_key(path) {
+            self.enis.insert(path.to_string(), Eni::load(path)?);
+        }
+        Ok(self.enis[path].clone())
+    }
+
+    pub fn oui_table(&mut self, path: &str) -> Result<Arc<OuiTable>, String> {
+        if !self.oui_tables.contains_key(path) {
+            self.oui_tables.insert(path.to_string(), Arc::new(OuiTable::load(path)?));
+        }
+        Ok(Arc::clone(&self.oui_tables[path]))
+    }
+
+    pub fn register_names(&mut self, path: &str) -> Result<Arc<RegisterNames>, String> {
+        if !self.register_names.contains_key(path) {
+            self.register_names.insert(path.to_string(), Arc::new(RegisterNames::load(path)?));
+        }
+        Ok(Arc::clone(&self.register_names[path]))
+    }
+
+    pub fn device_catalog(&mut self, path: &str) -> Result<Arc<DeviceCatalog>, String> {
+        if !self.device_catalogs.contains_key(path) {
+            self.device_catalogs.insert(path.to_string(), Arc::new(DeviceCatalog::load_dir(path)?));
+        }
+        Ok(Arc::clone(&self.device_catalogs[path]))
+    }
+
+    pub fn pdo_map(&mut self, path: &str) -> Result<Arc<PdoMap>, String> {
+        if !self.pdo_maps.contains_key(path) {
+            self.pdo_maps.insert(path.to_string(), Arc::new(PdoMap::load(path)?));
+        }
+        Ok(Arc::clone(&self.pdo_maps[path]))
+    }
+}
+
+/// Points stdout at the output file of a job, returning the descriptor to restore afterwards.
+#[cfg(unix)]
+fn redirect_stdout(path: &str) -> Result<i32, String> {
+    use std::os::fd::AsRawFd;
+
+    extern "C" {
+        fn dup(fd: i32) -> i32;
+        fn dup2(fd: i32, target: i32) -> i32;
+    }
+    let error = |e: std::io::Error| format!("Cannot write {} : {}", path, e);
+    let file = fs::File::create(path).map_err(error)?;
+    std::io::stdout().flush().map_err(error)?;
+    // SAFETY: plain descriptor calls of the C library; the file stays open until dup2 has
+    // duplicated it onto stdout, and the saved descriptor is only used by restore_stdout.
+    let saved = unsafe { dup(1) };
+    if saved < 0 || unsafe { dup2(file.as_raw_fd(), 1) } < 0 {
+        return Err(error(std::io::Error::last_os_error()));
+    }
+    Ok(saved)
+}
+
+#[cfg(unix)]
+fn restore_stdout(saved: i32) {
+    extern "C" {
+        fn dup2(fd: i32, target: i32) -> i32;
+        fn close(fd: i32) -> i32;
+    }
+    if let Err(e) = std::io::stdout().flush() {
+        eprintln!("Cannot write the output : {}", e);
+    }
+    // SAFETY: saved is the descriptor of the original stdout returned by redirect_stdout.
+    unsafe {
+        dup2(saved, 1);
+        close(saved);
+    }
+}
+
+#[cfg(not(unix))]
+fn redirect_stdout(_path: &str) -> Result<i32, String> {
+    Err("Job outputs are not supported on this platform, leave them out to write to stdout".to_string())
+}
+
+#[cfg(not(unix))]
+fn restore_stdout(_saved: i32) {}
+
+/// Runs the jobs of a job file in order. Every job is checked before the first one starts, so
+/// that a mistake in the last job of a nightly run does not only show after the others; a job
+/// failing while it runs stops the batch, as it would stop a single run.
+pub fn main() {
+    let args = BatchArgs::parse();
+    let text = fs::read_to_string(&args.jobs).unwrap_or_else(|e| {
+        eprintln!("Cannot read {} : {}", args.jobs, e);
+        exit(1);
+    });
+    let file: JobsFile = toml::from_str(&text).unwrap_or_else(|e| {
+        eprintln!("Invalid job file {} : {}", args.jobs, e);
+        exit(1);
+    });
+    let jobs: Vec<(&Job, Args)> = file.jobs.iter().enumerate().map(|(i, job)| {
+        let command_line = ["batch", "--file", &job.input].into_iter().chain(job.args.iter().map(String::as_str));
+        match Args::try_parse_from(command_line) {
+            Ok(job_args) => (job, job_args),
+            Err(e) => {
+                // the first line of the message, without the usage of the exporter.
+                let message = e.render().to_string();
+                let message = message.lines().next().unwrap_or_default().trim_start_matches("error: ").to_string();
+                eprintln!("Invalid job {} of {} : {}", i + 1, args.jobs, message);
+                exit(1);
+            }
+        }
+    }).collect();
+
+    let mut cache = Cache::default();
+    for (i, (job, job_args)) in jobs.iter().enumerate() {
+        eprintln!("Job {}/{} : {} -> {}", i + 1, jobs.len(), job.input, job.output.as_deref().unwrap_or("stdout"));
+        let saved = job.output.as_deref().map(|path| redirect_stdout(path).unwrap_or_else(|e| {
+            eprintln!("{}", e);
+            exit(1);
+        }));
+        crate::run(job_args, &mut cache);
+        if let Some(saved) = saved {
+            restore_stdout(saved);
+        }
+    }
+}
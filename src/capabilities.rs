@@ -0,0 +1,65 @@
+use crate::sink::outln;
+use crate::{capture, cycle, derive, ring, Args, Format, CSV_HEADER};
+use clap::{CommandFactory, ValueEnum};
+use serde::Serialize;
+
+/// Bumped when a key of the document changes meaning or goes away; keys and values are added
+/// without bumping it.
+const SCHEMA: u32 = 1;
+
+/// What this build of the exporter supports, for wrapper tooling to detect features from
+/// instead of parsing `--help`.
+#[derive(Serialize)]
+struct Capabilities {
+    schema: u32,
+    version: &'static str,
+    input_formats: Vec<&'static str>,
+    /// Compressions of the capture files, read through the decompressor of each.
+    input_compressions: Vec<&'static str>,
+    /// Values of `--format`.
+    output_formats: Vec<String>,
+    /// Values of `--group-by`.
+    group_by: Vec<String>,
+    subcommands: Vec<String>,
+    /// Protocols decoded below the EtherCAT datagrams.
+    decoders: Vec<&'static str>,
+    /// Columns of the CSV export without options.
+    csv_columns: Vec<&'static str>,
+    /// Datagram fields the `--derive` expressions refer to.
+    filter_keys: Vec<&'static str>,
+    /// Values of `ring --dump-on`.
+    ring_triggers: Vec<String>,
+    /// Long options of the export, without the leading `--`.
+    options: Vec<String>,
+}
+
+/// Whether the command line asks for the capabilities, which needs no capture file.
+pub fn invoked() -> bool {
+    std::env::args().skip(1).any(|arg| arg == "--capabilities")
+}
+
+fn value_names<T: ValueEnum>() -> Vec<String> {
+    T::value_variants().iter().filter_map(|value| value.to_possible_value()).map(|value| value.get_name().to_string()).collect()
+}
+
+/// Prints the capabilities as JSON.
+pub fn main() {
+    let command = Args::command();
+    let mut subcommands: Vec<String> = command.get_subcommands().map(|subcommand| subcommand.get_name().to_string()).collect();
+    subcommands.extend(["batch".to_string(), "examples".to_string()]);
+    let capabilities = Capabilities {
+        schema: SCHEMA,
+        version: env!("CARGO_PKG_VERSION"),
+        input_formats: vec!["pcapng", "pcap"],
+        input_compressions: capture::DECOMPRESSORS.iter().map(|(_, tool)| *tool).collect(),
+        output_formats: value_names::<Format>(),
+        group_by: value_names::<cycle::GroupBy>(),
+        subcommands,
+        decoders: vec!["ethercat", "mailbox", "coe", "foe", "eoe", "dc"],
+        csv_columns: CSV_HEADER.split(',').collect(),
+        filter_keys: derive::FIELDS.to_vec(),
+        ring_triggers: value_names::<ring::Trigger>(),
+        options: command.get_arguments().filter_map(|arg| arg.get_long()).map(str::to_string).collect(),
+    };
+    outln!("{}", serde_json::to_string_pretty(&capabilities).expect("capabilities are serializable"));
+}
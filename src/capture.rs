@@ -0,0 +1,627 @@
+use crate::clock::ClockMonitor;
+use crate::events::{Event, EventKind};
+use crate::interface::{DropCounters, Interface};
+use crate::link::{self, LinkErrors};
+use crate::locate::EcatLocation;
+use crate::mapped::{Mapping, MappedReader};
+use crate::recover;
+use ecat_packet_analyzer_rs::reader::{ethernet_frame, grow_for_block, BIG_ENDIAN_MAGIC, MAX_BLOCK_LEN, PCAP_HEADER_LEN, PCAP_MAGICS, READER_BUFFER_SIZE};
+use pcap_parser::traits::PcapReaderIterator;
+use pcap_parser::*;
+use serde::{Deserialize, Serialize};
+use std::fs::{self, File};
+use std::io::{Cursor, Read, Seek, SeekFrom};
+use std::ops::ControlFlow;
+use std::path::Path;
+
+/// Block type, block length and byte-order magic starting a pcapng section header block.
+const SHB_START_LEN: u64 = 12;
+/// `opt_comment`, the free-text comment option of every block type.
+const OPT_COMMENT: u16 = 1;
+/// Magic numbers of the compressed formats a capture can be archived in, with the tool
+/// decompressing each.
+pub const DECOMPRESSORS: [(&[u8], &str); 3] = [
+    (&[0x1F, 0x8B], "gzip"),
+    (&[0x28, 0xB5, 0x2F, 0xFD], "zstd"),
+    (&[0xFD, b'7', b'z', b'X', b'Z', 0x00], "xz"),
+];
+
+#[derive(Clone)]
+pub struct CaptureOptions {
+    /// Skip corrupt blocks instead of failing.
+    pub recover: bool,
+    /// Gap between two packets of an interface reported as a clock jump.
+    pub clock_jump_ns: u64,
+    /// Print no diagnostics, for a preliminary pass over a capture that is read again.
+    pub quiet: bool,
+    /// Where the EtherCAT frame is in the captured frames; packets are handed over rebuilt as
+    /// plain EtherCAT frames when it is not right after the Ethernet header.
+    pub ecat_location: EcatLocation,
+    /// The files a capture split into several continues in, such as the rotated files of a
+    /// long recording, read in turn after the one handed to the reader.
+    pub next_files: Vec<String>,
+}
+
+/// What is known about a captured packet besides its bytes.
+#[derive(Clone, Copy)]
+pub struct PacketInfo {
+    /// 1-based number of the packet in the capture.
+    pub frame_number: u64,
+    /// Capture time in nanoseconds since the epoch.
+    pub ts_ns: u64,
+    pub link_errors: LinkErrors,
+    /// The file of the packet, 0 for the one handed to the reader, then those of `next_files`.
+    pub file_index: usize,
+    /// The interface of the packet in the current section, 0 in a classic pcap file.
+    pub if_id: u32,
+}
+
+/// A captured packet, handed to the visitor of [`read`].
+pub struct Packet<'a> {
+    pub info: PacketInfo,
+    /// The Ethernet frame without its FCS, or `None` when the interface uses another link type.
+    pub data: Option<&'a [u8]>,
+    /// The bytes as captured, FCS included, the length of the packet on the wire and its
+    /// `epb_flags`, 0 in a classic pcap file.
+    pub captured: &'a [u8],
+    pub orig_len: u32,
+    pub flags: u32,
+}
+
+pub enum Item<'a> {
+    Packet(Packet<'a>),
+    /// Something noticed about the capture itself, handed over after reporting it on stderr.
+    Event(&'a Event),
+    /// A corrupt region skipped with `--recover`, handed over after reporting it on stderr.
+    CorruptBlock { offset: u64, reason: &'a str, skipped: usize },
+    /// A comment option of a block, such as a note added to a packet in Wireshark, handed
+    /// over before the packet it belongs to.
+    Comment(&'a str),
+    /// A custom block, with the Private Enterprise Number of the vendor defining it, and its
+    /// content if it is text.
+    CustomBlock { pen: u32, text: Option<&'a str> },
+    /// A block the exporter has no use for.
+    OtherBlock,
+}
+
+/// What was noticed about the capture file while reading it.
+#[derive(Default, Clone, Serialize, Deserialize)]
+pub struct Summary {
+    pub num_blocks: u64,
+    pub num_events: u64,
+    pub capture_drops: DropCounters,
+    pub corrupt_regions: u64,
+    pub skipped_bytes: usize,
+}
+
+impl Summary {
+    /// Prints the capture-level findings to stderr.
+    pub fn report(&self) {
+        if self.capture_drops.total() > 0 {
+            eprintln!(
+                "Capture host failed to record {} frames ({} dropped by the interface, {} by the OS); these are not bus losses",
+                self.capture_drops.total(), self.capture_drops.ifdrop, self.capture_drops.osdrop
+            );
+        }
+        if self.num_events > 0 {
+            eprintln!("{} capture events reported", self.num_events);
+        }
+        if self.corrupt_regions > 0 {
+            eprintln!("Recovered from {} corrupt regions, {} bytes skipped", self.corrupt_regions, self.skipped_bytes);
+        }
+    }
+}
+
+fn describe_pcap_error(e: &PcapError<&[u8]>) -> String {
+    match e {
+        PcapError::NomError(_, kind) | PcapError::OwnedNomError(_, kind) => format!("parse error ({:?})", kind),
+        e => e.to_string(),
+    }
+}
+
+/// The comment options of a block, prefixed with what they describe when that is not a packet.
+fn comments(options: &[PcapNGOption], prefix: &str) -> Vec<String> {
+    options
+        .iter()
+        .filter(|option| option.code.0 == OPT_COMMENT)
+        .filter_map(|option| option.as_bytes())
+        .map(|value| format!("{}{}", prefix, String::from_utf8_lossy(value).trim_end_matches('\0')))
+        .collect()
+}
+
+/// The content of a custom block as text, if it is printable UTF-8.
+fn custom_text(data: &[u8]) -> Option<&str> {
+    let text = std::str::from_utf8(data).ok()?.trim_end_matches('\0');
+    (!text.is_empty() && !text.chars().any(|c| c.is_control() && c != '\n' && c != '\t')).then_some(text)
+}
+
+fn report_event(summary: &mut Summary, options: &CaptureOptions, frame_number: u64, kind: EventKind) -> Event {
+    let event = Event { frame_number, kind };
+    if !options.quiet {
+        eprintln!("{}", event);
+    }
+    summary.num_events += 1;
+    event
+}
+
+/// Opens a capture. A gzip, zstd or xz compressed file, told by its magic number whatever its
+/// name, reads through the decompressor of its format as the capture is read, leaving no
+/// decompressed copy behind; a compressed capture cannot be resumed nor indexed, which both
+/// seek in the file.
+pub fn open(path: &str) -> Result<File, String> {
+    let error = |e: std::io::Error| format!("Cannot open file {} : {}", path, e);
+    let mut file = File::open(path).map_err(error)?;
+    match compression(&mut file).map_err(error)? {
+        Some(tool) => decompress(file, tool).map_err(|e| format!("Cannot decompress {} with {} : {}", path, tool, e)),
+        None => Ok(file),
+    }
+}
+
+/// Whether the capture file is compressed, and so cannot be seeked in once opened.
+pub fn is_compressed(path: &str) -> bool {
+    File::open(path).and_then(|mut file| compression(&mut file)).is_ok_and(|tool| tool.is_some())
+}
+
+/// The decompressor of the file, by its magic number, leaving the file at its start.
+fn compression(file: &mut File) -> std::io::Result<Option<&'static str>> {
+    // a pipe cannot be rewound after its magic number is read.
+    if !file.metadata()?.is_file() {
+        return Ok(None);
+    }
+    let mut magic = Vec::new();
+    file.by_ref().take(6).read_to_end(&mut magic)?;
+    file.rewind()?;
+    Ok(DECOMPRESSORS.iter().find(|(prefix, _)| magic.starts_with(prefix)).map(|(_, tool)| *tool))
+}
+
+/// The capture files of the `--file` values, in order. A value with `*` or `?` in its file name
+/// stands for the files of its directory matching it, in the order of their names, as the
+/// rotated files of a long recording are numbered.
+pub fn expand_files(patterns: &[String]) -> Result<Vec<String>, String> {
+    let mut files = Vec::new();
+    for pattern in patterns {
+        let path = Path::new(pattern);
+        let name = path.file_name().and_then(|name| name.to_str()).unwrap_or_default();
+        if !name.contains(['*', '?']) {
+            files.push(pattern.clone());
+            continue;
+        }
+        let dir = path.parent().filter(|dir| !dir.as_os_str().is_empty()).unwrap_or(Path::new("."));
+        let entries = fs::read_dir(dir).map_err(|e| format!("Cannot list {} : {}", dir.display(), e))?;
+        let mut matches: Vec<String> = entries
+            .filter_map(|entry| entry.ok()?.file_name().into_string().ok())
+            .filter(|file_name| wildcard_match(name, file_name))
+            .map(|file_name| path.with_file_name(file_name).to_string_lossy().into_owned())
+            .collect();
+        if matches.is_empty() {
+            return Err(format!("No file matches {}", pattern));
+        }
+        matches.sort();
+        files.extend(matches);
+    }
+    Ok(files)
+}
+
+/// Whether `name` matches `pattern`, where `*` stands for any characters and `?` for one.
+fn wildcard_match(pattern: &str, name: &str) -> bool {
+    let (pattern, name): (Vec<char>, Vec<char>) = (pattern.chars().collect(), name.chars().collect());
+    let (mut p, mut n) = (0, 0);
+    // the last star and the position in the name it is taken to stop at, to backtrack to.
+    let mut star = None;
+    while n < name.len() {
+        match pattern.get(p) {
+            Some('*') => {
+                star = Some((p, n));
+                p += 1;
+            }
+            Some(&c) if c == '?' || c == name[n] => {
+                p += 1;
+                n += 1;
+            }
+            _ => match star {
+                Some((star_p, star_n)) => {
+                    star = Some((star_p, star_n + 1));
+                    p = star_p + 1;
+                    n = star_n + 1;
+                }
+                None => return false,
+            },
+        }
+    }
+    pattern[p..].iter().all(|&c| c == '*')
+}
+
+/// A decompressor writing a capture into a pipe, by the device and inode of the pipe.
+struct Decompressor {
+    pipe: (u64, u64),
+    tool: &'static str,
+    child: Option<std::process::Child>,
+}
+
+/// The decompressors started by [`open`] whose output has not been read yet. `open` hands out
+/// the pipe as the file of the capture, which the read looks its decompressor up by.
+static DECOMPRESSORS_STARTED: std::sync::Mutex<Vec<Decompressor>> = std::sync::Mutex::new(Vec::new());
+
+impl Decompressor {
+    /// The decompressor writing into `file`, if it is the pipe of one.
+    fn of(file: &File) -> Option<Decompressor> {
+        let pipe = pipe_id(file)?;
+        let mut started = DECOMPRESSORS_STARTED.lock().unwrap();
+        let position = started.iter().position(|decompressor| decompressor.pipe == pipe)?;
+        Some(started.swap_remove(position))
+    }
+
+    /// Waits for the decompressor once its output is read to the end: a capture it failed to
+    /// decompress in full is an error, even if what it wrote ends with a whole block.
+    fn finish(mut self) -> Result<(), String> {
+        let status = self.child.take().expect("the decompressor is running").wait()
+            .map_err(|e| format!("Cannot wait for {} : {}", self.tool, e))?;
+        if !status.success() {
+            return Err(format!("{} failed to decompress the capture ({})", self.tool, status));
+        }
+        Ok(())
+    }
+}
+
+impl Drop for Decompressor {
+    /// A read that stops early closes the pipe, which ends the decompressor.
+    fn drop(&mut self) {
+        if let Some(mut child) = self.child.take() {
+            std::thread::spawn(move || child.wait());
+        }
+    }
+}
+
+#[cfg(unix)]
+fn pipe_id(file: &File) -> Option<(u64, u64)> {
+    use std::os::unix::fs::MetadataExt;
+
+    file.metadata().ok().map(|metadata| (metadata.dev(), metadata.ino()))
+}
+
+#[cfg(not(unix))]
+fn pipe_id(_file: &File) -> Option<(u64, u64)> {
+    None
+}
+
+/// The output of `tool` decompressing `file`, as the pipe it is written to.
+#[cfg(unix)]
+fn decompress(file: File, tool: &'static str) -> std::io::Result<File> {
+    use std::os::fd::OwnedFd;
+    use std::process::{Command, Stdio};
+
+    let mut child = Command::new(tool).arg("-dc").stdin(file).stdout(Stdio::piped()).spawn()?;
+    let output = File::from(OwnedFd::from(child.stdout.take().expect("the output is piped")));
+    let pipe = pipe_id(&output).expect("the pipe has an inode");
+    DECOMPRESSORS_STARTED.lock().unwrap().push(Decompressor { pipe, tool, child: Some(child) });
+    Ok(output)
+}
+
+#[cfg(not(unix))]
+fn decompress(_file: File, _tool: &'static str) -> std::io::Result<File> {
+    Err(std::io::Error::new(std::io::ErrorKind::Unsupported, "compressed captures are read on Unix only"))
+}
+
+/// Whether the file is a classic pcap file rather than pcapng, by its magic number, with its
+/// file header or first section header block, which the readers want in one piece. The file
+/// is not rewound, so that a capture written into a pipe by a running capture tool can be read
+/// as it grows.
+fn is_legacy_pcap(file: &mut File) -> std::io::Result<(bool, Vec<u8>)> {
+    let mut start = Vec::new();
+    file.by_ref().take(SHB_START_LEN).read_to_end(&mut start)?;
+    let magic = start.get(..4).map(|magic| u32::from_be_bytes([magic[0], magic[1], magic[2], magic[3]]));
+    let legacy = magic.is_some_and(|magic| PCAP_MAGICS.contains(&magic));
+    let len = match start.get(4..12) {
+        _ if legacy => PCAP_HEADER_LEN as u64,
+        // the byte-order magic tells the byte order of the block length.
+        Some(bytes) if bytes[4..] == BIG_ENDIAN_MAGIC => u32::from_be_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]) as u64,
+        Some(bytes) => u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]) as u64,
+        None => 0,
+    };
+    let rest = len.min(READER_BUFFER_SIZE as u64).saturating_sub(start.len() as u64);
+    file.by_ref().take(rest).read_to_end(&mut start)?;
+    Ok((legacy, start))
+}
+
+/// Hands a packet to `visit`, after the clock event its timestamp causes and its comments.
+#[allow(clippy::too_many_arguments)]
+fn visit_packet(
+    state: &mut ReaderState,
+    options: &CaptureOptions,
+    if_id: u32,
+    ts_ns: u64,
+    link_errors: LinkErrors,
+    data: Option<&[u8]>,
+    (captured, orig_len, flags): (&[u8], u32, u32),
+    comments: &[String],
+    visit: &mut impl FnMut(Item, &ReaderState) -> ControlFlow<()>,
+) -> ControlFlow<()> {
+    let rebuilt = data.and_then(|frame| options.ecat_location.rebuild(frame));
+    let data = rebuilt.as_deref().or(data);
+    state.frame_number += 1;
+    if let Some(kind) = state.clock_monitor.check(if_id, ts_ns) {
+        let event = report_event(&mut state.summary, options, state.frame_number, kind);
+        visit(Item::Event(&event), state)?;
+    }
+    comments.iter().try_for_each(|comment| visit(Item::Comment(comment), state))?;
+    let info = PacketInfo { frame_number: state.frame_number, ts_ns, link_errors, file_index: state.file_index, if_id };
+    visit(Item::Packet(Packet { info, data, captured, orig_len, flags }), state)
+}
+
+/// Reads a pcapng or classic pcap capture and hands every packet and unused block to `visit`, in file order,
+/// until the end of the file or until `visit` breaks.
+pub fn read(file: File, options: &CaptureOptions, mut visit: impl FnMut(Item) -> ControlFlow<()>) -> Result<Summary, String> {
+    read_from(file, options, None, |item, _| visit(item)).map(|state| state.summary)
+}
+
+/// Where the reader stands in a capture, enough to resume reading at the next block.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct ReaderState {
+    /// The file read, as [`PacketInfo::file_index`] counts them.
+    #[serde(default)]
+    file_index: usize,
+    /// File offset of the next block.
+    next_offset: u64,
+    /// File offset and length of the section header block of the current section.
+    section_offset: u64,
+    section_len: u64,
+    big_endian: bool,
+    frame_number: u64,
+    interfaces: Vec<Interface>,
+    clock_monitor: ClockMonitor,
+    pub summary: Summary,
+}
+
+impl ReaderState {
+    fn new(options: &CaptureOptions) -> Self {
+        ReaderState {
+            file_index: 0,
+            next_offset: 0,
+            section_offset: 0,
+            section_len: 0,
+            big_endian: false,
+            frame_number: 0,
+            interfaces: Vec::new(),
+            clock_monitor: ClockMonitor::new(options.clock_jump_ns),
+            summary: Summary::default(),
+        }
+    }
+
+    /// Number of the last packet read.
+    pub fn frame_number(&self) -> u64 {
+        self.frame_number
+    }
+
+    /// An interface of the current section.
+    pub fn interface(&self, if_id: u32) -> Option<&Interface> {
+        self.interfaces.get(if_id as usize)
+    }
+
+    /// The current section, as the file it is in and its offset there.
+    pub fn section(&self) -> (usize, u64) {
+        (self.file_index, self.section_offset)
+    }
+}
+
+/// Like [`read`], starting from a state saved by an earlier read of the same capture when
+/// `resume` is given, handing `visit` the state after each block and returning the final state.
+/// The block `visit` breaks at is left unread, to be read first by a read resumed from there.
+/// The files of `options.next_files` are read in turn after `file`, as the rest of the capture.
+pub fn read_from(
+    file: File,
+    options: &CaptureOptions,
+    resume: Option<ReaderState>,
+    mut visit: impl FnMut(Item, &ReaderState) -> ControlFlow<()>,
+) -> Result<ReaderState, String> {
+    let mut resuming = resume.is_some();
+    let mut state = match resume {
+        Some(mut state) => {
+            state.clock_monitor.set_jump_threshold(options.clock_jump_ns);
+            state
+        }
+        None => ReaderState::new(options),
+    };
+    let mut file = match state.file_index.checked_sub(1) {
+        Some(next) => {
+            let path = options.next_files.get(next)
+                .ok_or_else(|| format!("Cannot resume reading : the capture has fewer than the {} files read so far", state.file_index + 1))?;
+            open(path)?
+        }
+        None => file,
+    };
+    loop {
+        if read_file(file, options, &mut state, resuming, &mut visit)?.is_break() {
+            break;
+        }
+        let Some(path) = options.next_files.get(state.file_index) else {
+            break;
+        };
+        file = open(path)?;
+        resuming = false;
+        state.file_index += 1;
+        state.next_offset = 0;
+        state.section_offset = 0;
+        state.section_len = 0;
+    }
+    Ok(state)
+}
+
+/// Reads one file of a capture, from `state` on when `resuming`, until its end or until
+/// `visit` breaks.
+fn read_file(
+    mut file: File,
+    options: &CaptureOptions,
+    state: &mut ReaderState,
+    resuming: bool,
+    visit: &mut impl FnMut(Item, &ReaderState) -> ControlFlow<()>,
+) -> Result<ControlFlow<()>, String> {
+    let mut decompressor = Decompressor::of(&file);
+    let (legacy, start) = is_legacy_pcap(&mut file).map_err(|e| format!("error while reading: {}", e))?;
+    // file offset of the first byte handed to the reader, past the replayed header.
+    let base_offset = state.next_offset - state.section_len;
+    let mut reader: Box<dyn PcapReaderIterator> = match Mapping::of(&file) {
+        // a mapped file has its section header in view, nothing is replayed.
+        Some(mapping) if resuming => {
+            let section = (state.section_offset as usize, state.section_len as usize);
+            Box::new(MappedReader::new(mapping, legacy, section, state.next_offset as usize))
+        }
+        Some(mapping) => Box::new(MappedReader::new(mapping, legacy, (0, 0), 0)),
+        None => {
+            // the reader insists on starting with a section header, so a resumed read replays
+            // the header of the current section in front of the next block.
+            let mut section_header = vec![0; state.section_len as usize];
+            let seek_error = |e: std::io::Error| format!("Cannot resume reading : {}", e);
+            if resuming {
+                file.seek(SeekFrom::Start(state.section_offset)).map_err(seek_error)?;
+                file.read_exact(&mut section_header).map_err(seek_error)?;
+                file.seek(SeekFrom::Start(state.next_offset)).map_err(seek_error)?;
+            }
+            // a fresh read hands the reader the start of the file back in front of the rest.
+            let replayed = if resuming { section_header } else { start };
+            let input: Box<dyn Read> = Box::new(Cursor::new(replayed).chain(file));
+            if legacy {
+                Box::new(LegacyPcapReader::new(READER_BUFFER_SIZE, input).map_err(|e| e.to_string())?)
+            } else {
+                Box::new(PcapNGReader::new(READER_BUFFER_SIZE, input).map_err(|e| e.to_string())?)
+            }
+        }
+    };
+    let mut skip_section_header = resuming;
+    let mut buffer_size = READER_BUFFER_SIZE;
+
+    loop {
+        let block_offset = base_offset + reader.consumed() as u64;
+        let frame_number = state.frame_number;
+        let corrupt = match reader.next() {
+            Ok((offset, block)) => {
+                state.next_offset = block_offset + offset as u64;
+                if skip_section_header {
+                    skip_section_header = false;
+                    reader.consume(offset);
+                    continue;
+                }
+                state.summary.num_blocks += 1;
+                let flow = match block {
+                    PcapBlockOwned::NG(Block::SectionHeader(shb)) => {
+                        state.interfaces = Vec::new();
+                        state.clock_monitor.reset();
+                        state.big_endian = shb.big_endian();
+                        state.section_offset = block_offset;
+                        state.section_len = offset as u64;
+                        comments(&shb.options, "capture: ").iter().try_for_each(|comment| visit(Item::Comment(comment), state))
+                    },
+                    PcapBlockOwned::NG(Block::InterfaceDescription(idb)) => {
+                        state.interfaces.push(Interface::from_idb(&idb));
+                        let prefix = format!("interface {}: ", state.interfaces.len() - 1);
+                        comments(&idb.options, &prefix).iter().try_for_each(|comment| visit(Item::Comment(comment), state))
+                    },
+                    PcapBlockOwned::NG(Block::EnhancedPacket(epb)) => {
+                        let interface = state
+                            .interfaces
+                            .get(epb.if_id as usize)
+                            .ok_or_else(|| format!("Packet on undeclared interface {}", epb.if_id))?;
+                        let ts_ns = interface.timestamp_ns(epb.ts_high, epb.ts_low);
+                        let data = ethernet_frame(epb.data, interface.linktype, epb.caplen);
+                        let (link_errors, data) = match data {
+                            Some(data) => {
+                                let (errors, frame) = link::check(&epb, data, interface.fcs_len, state.big_endian);
+                                (errors, Some(frame))
+                            }
+                            None => (LinkErrors::default(), None),
+                        };
+                        let captured = &epb.data[..(epb.caplen as usize).min(epb.data.len())];
+                        let flags = link::packet_flags(&epb, state.big_endian);
+                        let comments = comments(&epb.options, "");
+                        visit_packet(state, options, epb.if_id, ts_ns, link_errors, data, (captured, epb.origlen, flags), &comments, visit)
+                    },
+                    PcapBlockOwned::NG(Block::InterfaceStatistics(isb)) => {
+                        let dropped = match state.interfaces.get_mut(isb.if_id as usize) {
+                            Some(interface) => interface.update_drops(&isb),
+                            None => DropCounters::default(),
+                        };
+                        state.summary.capture_drops += dropped;
+                        if dropped.total() > 0 {
+                            let kind = EventKind::CaptureDrops { interface: isb.if_id, dropped: dropped.total() };
+                            let event = report_event(&mut state.summary, options, state.frame_number, kind);
+                            visit(Item::Event(&event), state)
+                        } else {
+                            ControlFlow::Continue(())
+                        }
+                    },
+                    PcapBlockOwned::LegacyHeader(header) => {
+                        state.interfaces = vec![Interface::from_pcap_header(&header)];
+                        state.clock_monitor.reset();
+                        state.big_endian = header.is_bigendian();
+                        state.section_offset = block_offset;
+                        state.section_len = offset as u64;
+                        ControlFlow::Continue(())
+                    },
+                    PcapBlockOwned::Legacy(packet) => {
+                        let interface = &state.interfaces[0];
+                        let ts_ns = interface.pcap_timestamp_ns(packet.ts_sec, packet.ts_usec);
+                        let (link_errors, data) = match ethernet_frame(packet.data, interface.linktype, packet.caplen) {
+                            Some(data) => {
+                                let (errors, frame) = link::check_legacy(&packet, data, interface.fcs_len);
+                                (errors, Some(frame))
+                            }
+                            None => (LinkErrors::default(), None),
+                        };
+                        let captured = &packet.data[..(packet.caplen as usize).min(packet.data.len())];
+                        visit_packet(state, options, 0, ts_ns, link_errors, data, (captured, packet.origlen, 0), &[], visit)
+                    },
+                    PcapBlockOwned::NG(Block::Custom(custom)) => {
+                        visit(Item::CustomBlock { pen: custom.pen, text: custom_text(custom.data) }, state)
+                    },
+                    _ => visit(Item::OtherBlock, state),
+                };
+                reader.consume(offset);
+                if flow.is_break() {
+                    state.next_offset = block_offset;
+                    state.frame_number = frame_number;
+                    state.summary.num_blocks -= 1;
+                    return Ok(flow);
+                }
+                continue;
+            },
+            Err(PcapError::Eof) => return decompressor.map_or(Ok(()), Decompressor::finish).map(|()| ControlFlow::Continue(())),
+            Err(PcapError::Incomplete(_)) | Err(PcapError::BufferTooSmall) => None,
+            Err(PcapError::ReadError) => return Err("error while reading: Read error".to_string()),
+            Err(e) => Some(describe_pcap_error(&e)),
+        };
+        let corrupt = corrupt.or_else(|| {
+            let grown = grow_for_block(&mut *reader, &mut buffer_size, legacy, state.big_endian);
+            grown.is_err().then(|| PcapError::<&[u8]>::BufferTooSmall.to_string())
+        });
+        if let Some(reason) = corrupt {
+            // a capture cut short by its decompressor is reported as such.
+            if reader.reader_exhausted() {
+                decompressor.take().map_or(Ok(()), Decompressor::finish)?;
+            }
+            let offset = base_offset + reader.consumed() as u64;
+            if legacy {
+                // classic pcap records have no trailer to resynchronize on.
+                return Err(format!("Corrupt packet record at offset {} : {}", offset, reason));
+            }
+            if !options.recover {
+                return Err(format!("Corrupt block at offset {} : {} (use --recover to skip it)", offset, reason));
+            }
+            let skipped = recover::resync(&mut *reader, state.big_endian, MAX_BLOCK_LEN, &mut buffer_size)
+                .map_err(|e| format!("error while reading: {}", e))?;
+            if !options.quiet {
+                eprintln!("Corrupt block at offset {} : {}, skipped {} bytes", offset, reason, skipped);
+            }
+            state.summary.skipped_bytes += skipped;
+            state.summary.corrupt_regions += 1;
+            state.next_offset = base_offset + reader.consumed() as u64;
+            if visit(Item::CorruptBlock { offset, reason: &reason, skipped }, state).is_break() {
+                return Ok(ControlFlow::Break(()));
+            }
+            if reader.reader_exhausted() && reader.data().is_empty() {
+                return Ok(ControlFlow::Continue(()));
+            }
+            continue;
+        }
+        reader.refill().map_err(|e| format!("error while reading: {}", e))?;
+    }
+
+}
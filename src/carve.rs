@@ -0,0 +1,69 @@
+use crate::capture::{Packet, ReaderState};
+use crate::checkpoint;
+use crate::pcapng::Writer;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::path::Path;
+
+/// The packets an export keeps, written back out as they were captured with `--write-pcap`:
+/// their bytes, FCS included, their capture times, their flags and their interfaces, each declared once
+/// with its link type, name and FCS length.
+pub struct Carve {
+    writer: Writer,
+    /// The interface written for each interface of the capture, by file, section and number.
+    interfaces: HashMap<(usize, u64, u32), u32>,
+    pub written: u64,
+}
+
+/// How far the packets were written, saved with the checkpoint of the export.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct Progress {
+    /// Bytes of the file up to the last packet written.
+    len: u64,
+    #[serde(with = "checkpoint::pairs")]
+    interfaces: HashMap<(usize, u64, u32), u32>,
+    written: u64,
+}
+
+impl Carve {
+    pub fn create(path: &Path) -> Result<Self, String> {
+        let writer = Writer::create(path).map_err(|e| format!("Cannot create {} : {}", path.display(), e))?;
+        Ok(Carve { writer, interfaces: HashMap::new(), written: 0 })
+    }
+
+    /// Goes on writing the file of an interrupted export after the packets it wrote up to its
+    /// checkpoint, cutting those written after.
+    pub fn resume(path: &Path, progress: Progress) -> Result<Self, String> {
+        let writer = Writer::append(path, progress.len, progress.interfaces.len() as u32)
+            .map_err(|e| format!("Cannot append to {} : {}", path.display(), e))?;
+        Ok(Carve { writer, interfaces: progress.interfaces, written: progress.written })
+    }
+
+    /// Writes out the packets so far, for a checkpoint.
+    pub fn progress(&mut self) -> std::io::Result<Progress> {
+        let len = self.writer.flush()?;
+        Ok(Progress { len, interfaces: self.interfaces.clone(), written: self.written })
+    }
+
+    pub fn packet(&mut self, packet: &Packet, state: &ReaderState) -> std::io::Result<()> {
+        let (file_index, section_offset) = state.section();
+        let key = (file_index, section_offset, packet.info.if_id);
+        let interface = match self.interfaces.get(&key) {
+            Some(interface) => *interface,
+            None => {
+                let captured = state.interface(packet.info.if_id).expect("the packet has an interface");
+                let name = captured.name.as_deref().unwrap_or_default();
+                let interface = self.writer.interface(captured.linktype.0 as u16, name, captured.fcs_len)?;
+                self.interfaces.insert(key, interface);
+                interface
+            }
+        };
+        self.writer.packet(interface, packet.info.ts_ns, packet.captured, packet.orig_len, packet.flags)?;
+        self.written += 1;
+        Ok(())
+    }
+
+    pub fn finish(self) -> std::io::Result<()> {
+        self.writer.finish()
+    }
+}
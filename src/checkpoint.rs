@@ -0,0 +1,123 @@
+use crate::capture::ReaderState;
+use crate::carve;
+use crate::circulation::CirculationMonitor;
+use crate::health::replace_file;
+use crate::irq::IrqMonitor;
+use crate::mailbox::{MailboxMonitor, Sequences};
+use crate::reboot::RebootMonitor;
+use crate::slave_config::SlaveConfigs;
+use crate::throttle::Throttle;
+use crate::transaction::Transactions;
+use serde::{Deserialize, Serialize};
+use std::fs;
+
+/// Progress of an export, saved with `--checkpoint` and picked up again with `--resume`.
+#[derive(Serialize, Deserialize)]
+pub struct Checkpoint {
+    pub reader: ReaderState,
+    /// Bytes of output written up to the checkpoint.
+    pub output_bytes: u64,
+    /// Number of `--annotate` notes already merged into the output.
+    pub annotations_taken: usize,
+    #[serde(default)]
+    pub irq_monitor: IrqMonitor,
+    #[serde(default)]
+    pub reboot_monitor: RebootMonitor,
+    #[serde(default)]
+    pub circulation_monitor: CirculationMonitor,
+    /// The FMMU and SyncManager registers, which locate the mailboxes of `--mailbox`.
+    #[serde(default)]
+    pub configs: SlaveConfigs,
+    /// The open transactions of `--ids`.
+    #[serde(default)]
+    pub transactions: Transactions,
+    /// The mailbox counters behind the `--events` column, and those of the mailbox monitor.
+    #[serde(default)]
+    pub sequences: Sequences,
+    #[serde(default)]
+    pub mailbox_monitor: MailboxMonitor,
+    /// The rate limit window and the last frames of `--rate-limit` and `--only-changes`.
+    #[serde(default)]
+    pub throttle: Throttle,
+    /// The packets written with `--write-pcap`, if it was given.
+    #[serde(default)]
+    pub carve: Option<carve::Progress>,
+    /// Timestamp of the first packet, which relative timestamps count from.
+    #[serde(default)]
+    pub first_ts_ns: Option<u64>,
+}
+
+impl Checkpoint {
+    pub fn load(path: &str) -> Result<Self, String> {
+        let text = fs::read_to_string(path).map_err(|e| format!("Cannot read {} : {}", path, e))?;
+        serde_json::from_str(&text).map_err(|e| format!("Invalid checkpoint {} : {}", path, e))
+    }
+
+    pub fn save(&self, path: &str) -> Result<(), String> {
+        let json = serde_json::to_string(self).map_err(|e| e.to_string())?;
+        replace_file(path, &json)
+    }
+}
+
+/// Saves a map whose keys JSON cannot hold as a list of key and value pairs, for
+/// `#[serde(with = "checkpoint::pairs")]`.
+pub mod pairs {
+    use serde::{Deserialize, Deserializer, Serialize, Serializer};
+    use std::collections::HashMap;
+    use std::hash::Hash;
+
+    pub fn serialize<K: Serialize, V: Serialize, S: Serializer>(map: &HashMap<K, V>, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_seq(map)
+    }
+
+    pub fn deserialize<'de, K, V, D>(deserializer: D) -> Result<HashMap<K, V>, D::Error>
+    where
+        K: Deserialize<'de> + Eq + Hash,
+        V: Deserialize<'de>,
+        D: Deserializer<'de>,
+    {
+        Ok(Vec::<(K, V)>::deserialize(deserializer)?.into_iter().collect())
+    }
+}
+
+/// Cuts the output file, the one of `--output` or else the one on stdout, back to its length
+/// at the checkpoint, dropping the rows written after it by the interrupted export.
+pub fn truncate_output(path: Option<&str>, len: u64) -> Result<(), String> {
+    match path {
+        Some(path) => {
+            let file = fs::OpenOptions::new().write(true).open(path).map_err(|e| format!("Cannot open {} : {}", path, e))?;
+            cut(&file, len)
+        }
+        None => truncate_stdout(len),
+    }
+}
+
+fn cut(file: &fs::File, len: u64) -> Result<(), String> {
+    let metadata = file.metadata().map_err(|e| format!("Cannot truncate the output : {}", e))?;
+    if !metadata.is_file() {
+        return Err("The output is not a file, rows written after the checkpoint are repeated".to_string());
+    }
+    if metadata.len() < len {
+        return Err(format!("The output has {} bytes, fewer than the {} written at the checkpoint", metadata.len(), len));
+    }
+    file.set_len(len).map_err(|e| format!("Cannot truncate the output : {}", e))
+}
+
+/// Cuts the output file on stdout, and moves to its end.
+#[cfg(unix)]
+fn truncate_stdout(len: u64) -> Result<(), String> {
+    use std::io::{Seek, SeekFrom};
+    use std::os::fd::AsFd;
+
+    let error = |e: std::io::Error| format!("Cannot truncate the output : {}", e);
+    let file = fs::File::from(std::io::stdout().as_fd().try_clone_to_owned().map_err(error)?);
+    cut(&file, len)?;
+    // the duplicated descriptor shares the file position with stdout.
+    (&file).seek(SeekFrom::End(0)).map_err(error)?;
+    Ok(())
+}
+
+#[cfg(not(unix))]
+fn truncate_stdout(_len: u64) -> Result<(), String> {
+    Err("Cannot truncate the output on this platform, rows written after the checkpoint are repeated".to_string())
+}
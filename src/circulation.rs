@@ -0,0 +1,41 @@
+use crate::events::EventKind;
+use crate::EtherNetFrame;
+use serde::{Deserialize, Serialize};
+
+/// Watches the circulating bit of the datagrams (bit 14 of their length word, the `round_trip`
+/// column). A slave sets it when it processes a frame with its port 0 closed, and destroys a
+/// frame arriving with the bit already set, so that a frame cut off from the master does not
+/// circle forever. Frames carrying it in a capture usually mean a broken or misconfigured
+/// redundancy ring.
+#[derive(Clone, Default, Serialize, Deserialize)]
+pub struct CirculationMonitor {
+    /// Frames with the bit set in any of their datagrams.
+    frames: u64,
+    /// The previous outgoing and returned frame had it set.
+    circulating: [bool; 2],
+}
+
+impl CirculationMonitor {
+    /// Takes the next packet, returning an event when circulating frames appear after regular
+    /// ones.
+    pub fn check(&mut self, frame: &EtherNetFrame) -> Option<EventKind> {
+        let datagrams = frame.ecat_frame.datagrams.iter().filter(|datagram| datagram.header.round_trip != 0).count();
+        let previous = &mut self.circulating[frame.is_returned() as usize];
+        let appeared = datagrams > 0 && !*previous;
+        *previous = datagrams > 0;
+        if datagrams > 0 {
+            self.frames += 1;
+        }
+        appeared.then_some(EventKind::CirculatingFrame { datagrams, total: frame.ecat_frame.datagrams.len() })
+    }
+
+    pub fn frames(&self) -> u64 {
+        self.frames
+    }
+
+    pub fn report(&self) {
+        if self.frames > 0 {
+            eprintln!("{} circulating frames", self.frames);
+        }
+    }
+}
@@ -0,0 +1,229 @@
+use crate::events::EventKind;
+use clap::ValueEnum;
+use serde::{Deserialize, Serialize};
+
+const NANOS_PER_SEC: u64 = 1_000_000_000;
+
+/// Parses a duration such as `1.5s`, `250ms`, `20us`, `100ns`, `2m` or `1h` into nanoseconds.
+/// A bare number is taken as seconds.
+pub fn parse_duration(s: &str) -> Result<u64, String> {
+    let s = s.trim();
+    let split = s.find(|c: char| !(c.is_ascii_digit() || c == '.')).unwrap_or(s.len());
+    let (number, unit) = s.split_at(split);
+    let scale: f64 = match unit.trim() {
+        "" | "s" => 1e9,
+        "ms" => 1e6,
+        "us" => 1e3,
+        "ns" => 1.0,
+        "m" | "min" => 60e9,
+        "h" => 3600e9,
+        unit => return Err(format!("unknown duration unit '{}' in '{}'", unit, s)),
+    };
+    let value: f64 = number.parse().map_err(|_| format!("invalid duration '{}'", s))?;
+    Ok((value * scale).round() as u64)
+}
+
+/// Formats nanoseconds as seconds with nanosecond precision, e.g. `1.500000000s`.
+pub fn format_duration(ns: u64) -> String {
+    format!("{}.{:09}s", ns / NANOS_PER_SEC, ns % NANOS_PER_SEC)
+}
+
+/// Formats a signed amount of nanoseconds with an explicit sign, e.g. `-0.000002500s`.
+pub fn format_signed_duration(ns: f64) -> String {
+    let sign = if ns < 0.0 { "-" } else { "+" };
+    format!("{}{}", sign, format_duration(ns.abs().round() as u64))
+}
+
+/// Least squares line through `points`, as `(intercept, slope)`. With a single point, or all of
+/// them at the same x, the slope is 1: both clocks are assumed to run alike.
+pub fn fit_line(points: &[(f64, f64)]) -> (f64, f64) {
+    let n = points.len() as f64;
+    let mean_x = points.iter().map(|p| p.0).sum::<f64>() / n;
+    let mean_y = points.iter().map(|p| p.1).sum::<f64>() / n;
+    let sxx: f64 = points.iter().map(|p| (p.0 - mean_x).powi(2)).sum();
+    let sxy: f64 = points.iter().map(|p| (p.0 - mean_x) * (p.1 - mean_y)).sum();
+    let slope = if sxx > 0.0 { sxy / sxx } else { 1.0 };
+    (mean_y - slope * mean_x, slope)
+}
+
+/// Watches the packet timestamps of every interface for steps backwards and jumps forward.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct ClockMonitor {
+    #[serde(skip)]
+    jump_threshold_ns: u64,
+    last_ts: Vec<Option<u64>>,
+}
+
+impl ClockMonitor {
+    pub fn new(jump_threshold_ns: u64) -> Self {
+        ClockMonitor {
+            jump_threshold_ns,
+            last_ts: Vec::new(),
+        }
+    }
+
+    /// Changes the threshold of a monitor restored from a checkpoint.
+    pub fn set_jump_threshold(&mut self, jump_threshold_ns: u64) {
+        self.jump_threshold_ns = jump_threshold_ns;
+    }
+
+    /// Forgets all interfaces, for a new pcapng section.
+    pub fn reset(&mut self) {
+        self.last_ts.clear();
+    }
+
+    /// Records the timestamp of the next packet seen on `interface`.
+    pub fn check(&mut self, interface: u32, ts_ns: u64) -> Option<EventKind> {
+        let index = interface as usize;
+        if self.last_ts.len() <= index {
+            self.last_ts.resize(index + 1, None);
+        }
+        let last = self.last_ts[index].replace(ts_ns)?;
+        if ts_ns < last {
+            Some(EventKind::ClockBackwards { interface, by_ns: last - ts_ns })
+        } else if ts_ns - last > self.jump_threshold_ns {
+            Some(EventKind::ClockJump { interface, by_ns: ts_ns - last })
+        } else {
+            None
+        }
+    }
+}
+
+/// Parses an absolute time into nanoseconds since the epoch: either epoch seconds
+/// (`1700000000.25`) or ISO 8601 (`2023-11-14T22:13:20.25Z`, UTC unless an offset is given).
+pub fn parse_timestamp(s: &str) -> Result<u64, String> {
+    let s = s.trim();
+    let invalid = || format!("invalid timestamp '{}'", s);
+    if !s.contains('-') {
+        return parse_seconds_ns(s).ok_or_else(invalid);
+    }
+
+    let (date, time) = s.split_once(['T', ' ']).ok_or_else(invalid)?;
+    let mut date_parts = date.splitn(3, '-').map(|part| part.parse::<i64>());
+    let (Some(Ok(year)), Some(Ok(month)), Some(Ok(day))) = (date_parts.next(), date_parts.next(), date_parts.next()) else {
+        return Err(invalid());
+    };
+
+    // split off the UTC offset, if any
+    let (time, offset_s) = if let Some(time) = time.strip_suffix('Z') {
+        (time, 0)
+    } else if let Some(pos) = time.rfind(['+', '-']) {
+        let (time, offset) = time.split_at(pos);
+        let sign = if offset.starts_with('-') { -1 } else { 1 };
+        let (hours, minutes) = offset[1..].split_once(':').unwrap_or((&offset[1..], "0"));
+        let hours: i64 = hours.parse().map_err(|_| invalid())?;
+        let minutes: i64 = minutes.parse().map_err(|_| invalid())?;
+        (time, sign * (hours * 3600 + minutes * 60))
+    } else {
+        (time, 0)
+    };
+
+    let mut time_parts = time.splitn(3, ':');
+    let hours: i64 = time_parts.next().ok_or_else(invalid)?.parse().map_err(|_| invalid())?;
+    let minutes: i64 = time_parts.next().ok_or_else(invalid)?.parse().map_err(|_| invalid())?;
+    let seconds_ns = parse_seconds_ns(time_parts.next().unwrap_or("0")).ok_or_else(invalid)?;
+    if !(1..=12).contains(&month) || !(1..=31).contains(&day) || hours > 23 || minutes > 59 || seconds_ns >= 61 * NANOS_PER_SEC {
+        return Err(invalid());
+    }
+
+    let whole_seconds = days_from_civil(year, month, day) * 86400 + hours * 3600 + minutes * 60 - offset_s;
+    if whole_seconds < 0 {
+        return Err(invalid());
+    }
+    Ok(whole_seconds as u64 * NANOS_PER_SEC + seconds_ns)
+}
+
+/// Parses decimal seconds such as `20.000250` into nanoseconds without going through floats.
+fn parse_seconds_ns(s: &str) -> Option<u64> {
+    let (whole, fraction) = s.split_once('.').unwrap_or((s, ""));
+    if whole.is_empty() || !whole.bytes().chain(fraction.bytes()).all(|b| b.is_ascii_digit()) {
+        return None;
+    }
+    let mut fraction_ns = 0;
+    for (i, digit) in fraction.bytes().take(9).enumerate() {
+        fraction_ns += (digit - b'0') as u64 * 10u64.pow(8 - i as u32);
+    }
+    whole.parse::<u64>().ok()?.checked_mul(NANOS_PER_SEC)?.checked_add(fraction_ns)
+}
+
+/// How the timestamp column writes capture times; the absolute ones read back as timestamps
+/// on the command line, the relative ones as offsets.
+#[derive(ValueEnum, Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum TimeFormat {
+    /// Seconds since the epoch, such as 1700000000.000250000
+    #[default]
+    Epoch,
+    /// ISO 8601 in UTC, such as 2023-11-14T22:13:20.000250000Z
+    Iso8601,
+    /// Time since the first packet, such as 0.000250000s
+    Relative,
+}
+
+/// Formats a capture time, `first_ts_ns` being the timestamp of the first packet.
+pub fn format_timestamp(ts_ns: u64, first_ts_ns: u64, format: TimeFormat) -> String {
+    let (seconds, fraction_ns) = (ts_ns / NANOS_PER_SEC, ts_ns % NANOS_PER_SEC);
+    match format {
+        TimeFormat::Epoch => format!("{}.{:09}", seconds, fraction_ns),
+        TimeFormat::Iso8601 => {
+            let (year, month, day) = civil_from_days((seconds / 86400) as i64);
+            let second_of_day = seconds % 86400;
+            format!("{:04}-{:02}-{:02}T{:02}:{:02}:{:02}.{:09}Z", year, month, day,
+                    second_of_day / 3600, second_of_day / 60 % 60, second_of_day % 60, fraction_ns)
+        }
+        TimeFormat::Relative => format_duration(ts_ns.saturating_sub(first_ts_ns)),
+    }
+}
+
+/// Proleptic Gregorian date of a number of days since 1970-01-01, the inverse of
+/// `days_from_civil`.
+fn civil_from_days(days: i64) -> (i64, i64, i64) {
+    let days = days + 719468;
+    let era = days.div_euclid(146097);
+    let day_of_era = days - era * 146097;
+    let year_of_era = (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let month_index = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * month_index + 2) / 5 + 1;
+    let month = if month_index < 10 { month_index + 3 } else { month_index - 9 };
+    let year = year_of_era + era * 400 + if month <= 2 { 1 } else { 0 };
+    (year, month, day)
+}
+
+/// Days since 1970-01-01 of a proleptic Gregorian date.
+fn days_from_civil(year: i64, month: i64, day: i64) -> i64 {
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year.div_euclid(400);
+    let year_of_era = year - era * 400;
+    let day_of_year = (153 * ((month + 9) % 12) + 2) / 5 + day - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    era * 146097 + day_of_era - 719468
+}
+
+/// A point in the capture given on the command line.
+#[derive(Clone, Copy, Debug)]
+pub enum TimeSpec {
+    /// Nanoseconds since the epoch.
+    Absolute(u64),
+    /// Nanoseconds after the first packet.
+    Relative(u64),
+}
+
+impl TimeSpec {
+    /// Values with a unit (`12.5s`, `300ms`) are offsets from the first packet, plain numbers
+    /// and ISO 8601 dates are absolute.
+    pub fn parse(s: &str) -> Result<Self, String> {
+        if s.trim_end().ends_with(|c: char| c.is_ascii_alphabetic() && c != 'Z') {
+            parse_duration(s).map(TimeSpec::Relative)
+        } else {
+            parse_timestamp(s).map(TimeSpec::Absolute)
+        }
+    }
+
+    /// Resolves the time against the timestamp of the first packet.
+    pub fn resolve(self, first_ts_ns: u64) -> u64 {
+        match self {
+            TimeSpec::Absolute(ts) => ts,
+            TimeSpec::Relative(offset) => first_ts_ns + offset,
+        }
+    }
+}
@@ -0,0 +1,44 @@
+use crate::mailbox::{Mailbox, MailboxType};
+use ecat_packet_analyzer_rs::parser::coe::{self as protocol, SdoCommand};
+pub use ecat_packet_analyzer_rs::parser::coe::{abort_message, error_class, CoeService, Emergency};
+
+/// SDO transfers the PDO mapping reconstruction needs. Segmented downloads and uploads are
+/// left out.
+pub enum Sdo<'a> {
+    /// The master writes an object entry, or the whole object with complete access.
+    Download {
+        index: u16,
+        subindex: u8,
+        complete_access: bool,
+        data: &'a [u8],
+    },
+    /// The slave confirms a download.
+    DownloadResponse { index: u16, subindex: u8 },
+    /// Either side aborts the transfer of an object entry.
+    Abort { index: u16, subindex: u8 },
+}
+
+/// The SDO request or response a mailbox message carries, if it is a CoE message of one.
+pub fn sdo_message<'a>(mailbox: &Mailbox<'a>) -> Option<protocol::Sdo<'a>> {
+    (mailbox.mbx_type == MailboxType::CoE).then(|| protocol::Sdo::parse(mailbox.payload).ok()).flatten()
+}
+
+/// Decodes the SDO transfer a CoE mailbox message carries, if it is one of [`Sdo`].
+pub fn parse_sdo<'a>(mailbox: &Mailbox<'a>) -> Option<Sdo<'a>> {
+    let sdo = sdo_message(mailbox)?;
+    let (index, subindex) = (sdo.index?, sdo.subindex?);
+
+    match (sdo.service, sdo.command) {
+        (_, SdoCommand::Abort) => Some(Sdo::Abort { index, subindex }),
+        (CoeService::SdoRequest, SdoCommand::InitiateDownload) => {
+            let data = match sdo.expedited_data {
+                Some(data) => data,
+                // a normal transfer that does not fit continues in segments, which are not followed.
+                None => sdo.data.get(..sdo.size? as usize)?,
+            };
+            Some(Sdo::Download { index, subindex, complete_access: sdo.complete_access, data })
+        }
+        (CoeService::SdoResponse, SdoCommand::InitiateDownload) => Some(Sdo::DownloadResponse { index, subindex }),
+        _ => None,
+    }
+}
@@ -0,0 +1,126 @@
+use crate::csv::Delimiter;
+use crate::value::{self, Style};
+use crate::CSV_HEADER;
+use serde_json::Value;
+
+/// Fields the jsonl rows start with, ahead of the columns of the CSV export.
+const JSON_FIELDS: [&str; 3] = ["frame", "time", "time_ns"];
+
+/// A field of an exported datagram, as a CSV cell and as a JSON value.
+#[derive(Clone)]
+struct Field<'a> {
+    name: &'a str,
+    csv: String,
+    json: Value,
+}
+
+/// The fields of one exported datagram, which the columns of the export are looked up in.
+#[derive(Clone, Default)]
+pub struct Row<'a>(Vec<Field<'a>>);
+
+impl<'a> Row<'a> {
+    pub fn push(&mut self, name: &'a str, csv: impl Into<String>, json: impl Into<Value>) {
+        self.0.push(Field { name, csv: csv.into(), json: json.into() });
+    }
+
+    /// A text field, empty in CSV and null in JSON when there is none.
+    pub fn text(&mut self, name: &'a str, text: Option<impl Into<String>>) {
+        let text = text.map(Into::into);
+        let json = text.clone().map_or(Value::Null, Value::String);
+        self.push(name, text.unwrap_or_default(), json);
+    }
+
+    /// A numeric field, empty in CSV and null in JSON when there is none.
+    pub fn number<T: Into<Value> + ToString>(&mut self, name: &'a str, number: Option<T>) {
+        let csv = number.as_ref().map(ToString::to_string).unwrap_or_default();
+        self.push(name, csv, number.map_or(Value::Null, Into::into));
+    }
+
+    /// A field of a typed value, rendered in `style` in CSV and as [`value::Value::json`] in
+    /// JSON; empty and null when there is none.
+    pub fn value(&mut self, name: &'a str, value: Option<value::Value>, style: Style) {
+        let csv = value.map(|value| value.render(style)).unwrap_or_default();
+        self.push(name, csv, value.map_or(Value::Null, value::Value::json));
+    }
+
+    pub fn extend(&mut self, other: &Row<'a>) {
+        self.0.extend(other.0.iter().cloned());
+    }
+
+    fn get(&self, name: &str) -> Option<&Field<'a>> {
+        self.0.iter().find(|field| field.name == name)
+    }
+
+    /// The CSV record of the columns, without its line break; a field the row lacks is empty.
+    pub fn csv(&self, columns: &[String], delimiter: Delimiter) -> String {
+        delimiter.record(columns.iter().map(|name| self.get(name).map_or("", |field| field.csv.as_str())))
+    }
+
+    /// The JSON object of the columns, in their order; a field the row lacks is null.
+    pub fn json(&self, columns: &[String]) -> String {
+        let fields: Vec<String> = columns.iter().map(|name| {
+            let value = self.get(name).map_or(&Value::Null, |field| &field.json);
+            format!("{}:{}", Value::from(name.as_str()), value)
+        }).collect();
+        format!("{{{}}}", fields.join(","))
+    }
+}
+
+/// A column an export can write, and whether the CSV and the JSON Lines exports write it
+/// unless `--columns` picks the columns.
+struct Column {
+    name: String,
+    csv: bool,
+    json: bool,
+}
+
+/// The columns of an export: the leading ones every row has, then those of the options in use.
+pub struct Registry {
+    columns: Vec<Column>,
+    /// Columns written first, in order; the JSON fields of the options follow in the order of
+    /// their names.
+    leading: usize,
+}
+
+impl Registry {
+    pub fn new() -> Self {
+        let mut registry = Registry { columns: Vec::new(), leading: 0 };
+        for name in JSON_FIELDS {
+            registry.add_for(name, false, true);
+        }
+        for name in CSV_HEADER.split(',') {
+            registry.add(name);
+        }
+        registry.leading = registry.columns.len();
+        // the capture time in nanoseconds, as the Parquet and Arrow exports have it.
+        registry.add_for("ts", false, false);
+        registry
+    }
+
+    /// A column of both exports.
+    pub fn add(&mut self, name: impl Into<String>) {
+        self.add_for(name, true, true);
+    }
+
+    pub fn add_for(&mut self, name: impl Into<String>, csv: bool, json: bool) {
+        self.columns.push(Column { name: name.into(), csv, json });
+    }
+
+    /// The columns written: those of `selected` in its order, or the default ones of the format.
+    pub fn select(&self, selected: &[String], jsonl: bool) -> Result<Vec<String>, String> {
+        if let Some(unknown) = selected.iter().find(|name| !self.columns.iter().any(|column| &column.name == *name)) {
+            let names: Vec<&str> = self.columns.iter().map(|column| column.name.as_str()).collect();
+            return Err(format!("Unknown column {}, the columns are {}", unknown, names.join(",")));
+        }
+        if !selected.is_empty() {
+            return Ok(selected.to_vec());
+        }
+        if !jsonl {
+            return Ok(self.columns.iter().filter(|column| column.csv).map(|column| column.name.clone()).collect());
+        }
+        let (leading, options) = self.columns.split_at(self.leading);
+        let mut options: Vec<&Column> = options.iter().filter(|column| column.json).collect();
+        options.sort_by(|a, b| a.name.cmp(&b.name));
+        Ok(leading.iter().chain(options).filter(|column| column.json).map(|column| column.name.clone()).collect())
+    }
+}
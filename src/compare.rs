@@ -0,0 +1,190 @@
+use crate::capture::{self, CaptureOptions, Item};
+use crate::clock::TimeSpec;
+use crate::sink::outln;
+use crate::statistics;
+use crate::value::{Style, Value};
+use crate::wkc::WkcModel;
+use crate::{EtherCATCommand, EtherNetFrame};
+use std::collections::{BTreeMap, BTreeSet};
+use std::fs::File;
+use std::ops::ControlFlow;
+use std::process::exit;
+
+const NANOS_PER_SEC: f64 = 1e9;
+
+/// A window of the capture, given as `start..end`: `0..60s` or `3600s..3660s` are offsets from
+/// the first packet, a unit after the end applying to a bare start; two absolute times (epoch
+/// seconds or ISO 8601) are taken as they are.
+#[derive(Clone, Copy, Debug)]
+pub struct Window {
+    start: TimeSpec,
+    end: TimeSpec,
+}
+
+impl Window {
+    pub fn parse(s: &str) -> Result<Self, String> {
+        let error = |reason: String| format!("Invalid window '{}' : {}", s, reason);
+        let (start, end) = s.split_once("..").ok_or_else(|| error("expected start..end, as in 0..60s".to_string()))?;
+        let end_spec = TimeSpec::parse(end).map_err(error)?;
+        let start_spec = match end_spec {
+            TimeSpec::Relative(_) if start.trim().parse::<f64>().is_ok() => {
+                let unit = end.trim().trim_start_matches(|c: char| c.is_ascii_digit() || c == '.');
+                TimeSpec::parse(&format!("{}{}", start.trim(), unit)).map_err(error)?
+            }
+            _ => TimeSpec::parse(start).map_err(error)?,
+        };
+        Ok(Window { start: start_spec, end: end_spec })
+    }
+}
+
+/// Aggregate behaviour of the bus over one window.
+#[derive(Default)]
+struct Stats {
+    first_ts_ns: Option<u64>,
+    last_ts_ns: u64,
+    frames: u64,
+    datagrams: u64,
+    /// Intervals between the outgoing frames carrying process data (logical commands).
+    last_cycle_ts_ns: Option<u64>,
+    cycles: Vec<u64>,
+    commands: BTreeMap<String, u64>,
+    /// Datagrams by command and register, or by command for logical ones.
+    accesses: BTreeMap<String, u64>,
+    returned_datagrams: u64,
+    zero_wkc: u64,
+    wkc_mismatches: u64,
+}
+
+impl Stats {
+    fn frame(&mut self, ts_ns: u64, frame: &EtherNetFrame, wkc_model: Option<&WkcModel>) {
+        use EtherCATCommand::*;
+
+        self.first_ts_ns.get_or_insert(ts_ns);
+        self.last_ts_ns = ts_ns;
+        self.frames += 1;
+        let returned = frame.is_returned();
+        let datagrams = &frame.ecat_frame.datagrams;
+        if !returned && datagrams.iter().any(|datagram| matches!(datagram.header.cmd, LRD | LWR | LRW)) {
+            if let Some(last) = self.last_cycle_ts_ns {
+                self.cycles.push(ts_ns.saturating_sub(last));
+            }
+            self.last_cycle_ts_ns = Some(ts_ns);
+        }
+        for datagram in datagrams {
+            let header = &datagram.header;
+            self.datagrams += 1;
+            *self.commands.entry(header.cmd.to_string()).or_default() += 1;
+            let access = match header.cmd {
+                LRD | LWR | LRW => header.cmd.to_string(),
+                _ => format!("{} {}", header.cmd, Value::Address(header.offset_addr).render(Style::Csv)),
+            };
+            // outgoing and returned copies of a datagram are one access.
+            if !returned {
+                *self.accesses.entry(access).or_default() += 1;
+            } else {
+                self.returned_datagrams += 1;
+                if datagram.wkc == 0 {
+                    self.zero_wkc += 1;
+                }
+                if wkc_model.and_then(|model| model.expected(header)).is_some_and(|expected| expected != datagram.wkc) {
+                    self.wkc_mismatches += 1;
+                }
+            }
+        }
+    }
+
+    fn seconds(&self) -> f64 {
+        self.last_ts_ns.saturating_sub(self.first_ts_ns.unwrap_or(self.last_ts_ns)) as f64 / NANOS_PER_SEC
+    }
+
+    /// Occurrences per second of capture time, `None` for a window too short to tell.
+    fn rate(&self, count: u64) -> Option<f64> {
+        let seconds = self.seconds();
+        (seconds > 0.0).then(|| count as f64 / seconds)
+    }
+
+    fn share(count: u64, total: u64) -> Option<f64> {
+        (total > 0).then(|| count as f64 / total as f64 * 100.0)
+    }
+
+    fn cycle_mean_ns(&self) -> Option<f64> {
+        statistics::mean(&self.cycles)
+    }
+
+    fn cycle_stddev_ns(&self) -> Option<f64> {
+        statistics::stddev(&self.cycles)
+    }
+}
+
+fn format_value(value: Option<f64>) -> String {
+    value.map(|v| format!("{:.3}", v).trim_end_matches('0').trim_end_matches('.').to_string()).unwrap_or_default()
+}
+
+/// Relative change from a to b, in percent.
+fn change(a: Option<f64>, b: Option<f64>) -> String {
+    match (a, b) {
+        (Some(a), Some(b)) if a != 0.0 => format!("{:+.1}%", (b - a) / a * 100.0),
+        _ => String::new(),
+    }
+}
+
+/// Compares the aggregate behaviour of two windows of the capture: cycle time, traffic mix,
+/// register accesses and working counters, one metric per row. Counts are given per second so
+/// that windows of different lengths compare.
+pub fn run(file: File, options: &CaptureOptions, a: Window, b: Window, wkc_model: Option<&WkcModel>) {
+    let mut first_ts_ns = None;
+    let mut stats = [Stats::default(), Stats::default()];
+    let windows = [a, b];
+
+    let summary = capture::read(file, options, |item| {
+        let Item::Packet(packet) = item else {
+            return ControlFlow::Continue(());
+        };
+        let ts_ns = packet.info.ts_ns;
+        let first = *first_ts_ns.get_or_insert(ts_ns);
+        let Some(frame) = packet.data.and_then(EtherNetFrame::parse_ethercat) else {
+            return ControlFlow::Continue(());
+        };
+        for (window, stats) in windows.iter().zip(&mut stats) {
+            if (window.start.resolve(first)..window.end.resolve(first)).contains(&ts_ns) {
+                stats.frame(ts_ns, &frame, wkc_model);
+            }
+        }
+        ControlFlow::Continue(())
+    });
+    let summary = summary.unwrap_or_else(|e| {
+        eprintln!("{}", e);
+        exit(1);
+    });
+    let [a, b] = &stats;
+
+    let mut rows: Vec<(String, Option<f64>, Option<f64>)> = vec![
+        ("duration_s".to_string(), Some(a.seconds()), Some(b.seconds())),
+        ("frames_per_s".to_string(), a.rate(a.frames), b.rate(b.frames)),
+        ("datagrams_per_s".to_string(), a.rate(a.datagrams), b.rate(b.datagrams)),
+        ("cycle_mean_ns".to_string(), a.cycle_mean_ns(), b.cycle_mean_ns()),
+        ("cycle_stddev_ns".to_string(), a.cycle_stddev_ns(), b.cycle_stddev_ns()),
+        ("cycle_max_ns".to_string(), a.cycles.iter().max().map(|c| *c as f64), b.cycles.iter().max().map(|c| *c as f64)),
+        ("zero_wkc_pct".to_string(), Stats::share(a.zero_wkc, a.returned_datagrams), Stats::share(b.zero_wkc, b.returned_datagrams)),
+    ];
+    if wkc_model.is_some() {
+        rows.push(("wkc_mismatch_pct".to_string(),
+            Stats::share(a.wkc_mismatches, a.returned_datagrams), Stats::share(b.wkc_mismatches, b.returned_datagrams)));
+    }
+    let commands: BTreeSet<&String> = a.commands.keys().chain(b.commands.keys()).collect();
+    for command in commands {
+        let count = |stats: &Stats| stats.commands.get(command).copied().unwrap_or_default();
+        rows.push((format!("datagrams_pct.{}", command), Stats::share(count(a), a.datagrams), Stats::share(count(b), b.datagrams)));
+    }
+    let accesses: BTreeSet<&String> = a.accesses.keys().chain(b.accesses.keys()).collect();
+    for access in accesses {
+        let count = |stats: &Stats| stats.accesses.get(access).copied().unwrap_or_default();
+        rows.push((format!("accesses_per_s.{}", access), a.rate(count(a)), b.rate(count(b))));
+    }
+
+    outln!("metric,a,b,change");
+    for (metric, value_a, value_b) in rows {
+        outln!("{},{},{},{}", metric, format_value(value_a), format_value(value_b), change(value_a, value_b));
+    }
+    summary.report();
+}
@@ -0,0 +1,38 @@
+use clap::ValueEnum;
+use std::borrow::Cow;
+
+/// Separator of the fields of the CSV export, of `--delimiter`.
+#[derive(ValueEnum, Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Delimiter {
+    #[default]
+    Comma,
+    /// For the spreadsheets of locales writing decimal commas
+    Semicolon,
+    Tab,
+}
+
+impl Delimiter {
+    pub fn as_char(self) -> char {
+        match self {
+            Delimiter::Comma => ',',
+            Delimiter::Semicolon => ';',
+            Delimiter::Tab => '\t',
+        }
+    }
+
+    /// A field as written in a record: quoted, with its quotes doubled, when it holds the
+    /// delimiter, a quote or a line break, as RFC 4180 has it.
+    pub fn field(self, value: &str) -> Cow<'_, str> {
+        if value.contains([self.as_char(), '"', '\n', '\r']) {
+            Cow::Owned(format!("\"{}\"", value.replace('"', "\"\"")))
+        } else {
+            Cow::Borrowed(value)
+        }
+    }
+
+    /// A record of the given fields, without its line break.
+    pub fn record<'a>(self, fields: impl IntoIterator<Item = &'a str>) -> String {
+        let fields: Vec<Cow<str>> = fields.into_iter().map(|value| self.field(value)).collect();
+        fields.join(&self.as_char().to_string())
+    }
+}
@@ -0,0 +1,168 @@
+use crate::capture::{self, CaptureOptions, Item};
+use crate::sink::outln;
+use crate::transaction::datagram_id;
+use crate::value::{Style, Value};
+use crate::wkc::WkcModel;
+use crate::{EtherCATCommand, EtherNetFrame};
+use clap::ValueEnum;
+use std::fs::File;
+use std::ops::ControlFlow;
+use std::process::exit;
+
+const CSV_HEADER: &str =
+    "cycle,first_frame,last_frame,start,cycle_time,frames,datagrams,returned_frames,wkc,expected_wkc,wkc_status,datagram_ids";
+
+/// What a row of the export stands for.
+#[derive(ValueEnum, Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum GroupBy {
+    /// One row per datagram
+    #[default]
+    Datagram,
+    /// One row per bus cycle, aggregating the frames of the cycle
+    Cycle,
+}
+
+/// The process data datagrams of an outgoing frame, by command and logical address.
+fn pattern(frame: &EtherNetFrame) -> Vec<(EtherCATCommand, u32)> {
+    use EtherCATCommand::*;
+
+    frame.ecat_frame.datagrams
+        .iter()
+        .filter(|datagram| matches!(datagram.header.cmd, LRD | LWR | LRW))
+        .map(|datagram| (datagram.header.cmd, ((datagram.header.offset_addr as u32) << 16) | datagram.header.slave_addr as u32))
+        .collect()
+}
+
+/// The frames of one bus cycle.
+struct Cycle {
+    number: u64,
+    first_frame: u64,
+    last_frame: u64,
+    start_ns: u64,
+    cycle_time_ns: Option<u64>,
+    frames: u64,
+    outgoing_frames: u64,
+    returned_frames: u64,
+    datagram_ids: Vec<String>,
+    wkc: u64,
+    /// Sum of the working counters the model expects, `None` without a model or if it does not
+    /// know one of the returned datagrams.
+    expected_wkc: Option<u64>,
+    zero_wkc: bool,
+    wkc_mismatch: bool,
+}
+
+impl Cycle {
+    fn new(number: u64, frame_number: u64, start_ns: u64, previous_start_ns: Option<u64>, with_model: bool) -> Self {
+        Cycle {
+            number,
+            first_frame: frame_number,
+            last_frame: frame_number,
+            start_ns,
+            cycle_time_ns: previous_start_ns.map(|previous| start_ns.saturating_sub(previous)),
+            frames: 0,
+            outgoing_frames: 0,
+            returned_frames: 0,
+            datagram_ids: Vec::new(),
+            wkc: 0,
+            expected_wkc: with_model.then_some(0),
+            zero_wkc: false,
+            wkc_mismatch: false,
+        }
+    }
+
+    fn frame(&mut self, frame_number: u64, frame: &EtherNetFrame, wkc_model: Option<&WkcModel>) {
+        let returned = frame.is_returned();
+        self.last_frame = frame_number;
+        self.frames += 1;
+        if returned {
+            self.returned_frames += 1;
+        } else {
+            self.outgoing_frames += 1;
+        }
+        for (position, datagram) in frame.ecat_frame.datagrams.iter().enumerate() {
+            self.datagram_ids.push(datagram_id(frame_number, position));
+            if !returned {
+                continue;
+            }
+            self.wkc += datagram.wkc as u64;
+            self.zero_wkc |= datagram.wkc == 0;
+            let expected = wkc_model.and_then(|model| model.expected(&datagram.header));
+            self.expected_wkc = self.expected_wkc.zip(expected).map(|(sum, expected)| sum + expected as u64);
+            self.wkc_mismatch |= expected.is_some_and(|expected| expected != datagram.wkc);
+        }
+    }
+
+    /// `ok`, or the codes of what went wrong in the cycle, separated by semicolons.
+    fn wkc_status(&self) -> String {
+        let mut codes = Vec::new();
+        if self.returned_frames < self.outgoing_frames {
+            codes.push("not_returned");
+        }
+        if self.zero_wkc {
+            codes.push("zero_wkc");
+        }
+        if self.wkc_mismatch {
+            codes.push("wkc_mismatch");
+        }
+        if codes.is_empty() {
+            "ok".to_string()
+        } else {
+            codes.join(";")
+        }
+    }
+
+    fn row(&self) -> String {
+        format!("{},{},{},{},{},{},{},{},{},{},{},{}",
+            self.number, self.first_frame, self.last_frame, Value::Time(self.start_ns).render(Style::Csv),
+            self.cycle_time_ns.map(|ns| Value::Duration(ns).render(Style::Csv)).unwrap_or_default(),
+            self.frames, self.datagram_ids.len(), self.returned_frames, self.wkc,
+            self.expected_wkc.map(|wkc| wkc.to_string()).unwrap_or_default(), self.wkc_status(), self.datagram_ids.join(";"))
+    }
+}
+
+/// Exports one row per bus cycle. The master sends the same process data frame every cycle:
+/// the logical datagrams of the first outgoing frame exchanging process data make the pattern,
+/// and every outgoing frame with the same pattern starts a new cycle, which holds the frames up
+/// to the next one. Frames before the first cycle, while the master sets up the bus, are left
+/// out. The `datagram_ids` refer to the rows of the per-datagram export with `--ids`.
+pub fn run(file: File, options: &CaptureOptions, wkc_model: Option<&WkcModel>) {
+    let mut cycle_pattern = None;
+    let mut cycle: Option<Cycle> = None;
+
+    outln!("{}", CSV_HEADER);
+    let summary = capture::read(file, options, |item| {
+        let Item::Packet(packet) = item else {
+            return ControlFlow::Continue(());
+        };
+        let Some(frame) = packet.data.and_then(EtherNetFrame::parse_ethercat) else {
+            return ControlFlow::Continue(());
+        };
+        let info = packet.info;
+        if !frame.is_returned() {
+            let frame_pattern = pattern(&frame);
+            if !frame_pattern.is_empty() && *cycle_pattern.get_or_insert_with(|| frame_pattern.clone()) == frame_pattern {
+                let (number, previous_start_ns) = match &cycle {
+                    Some(previous) => {
+                        outln!("{}", previous.row());
+                        (previous.number + 1, Some(previous.start_ns))
+                    }
+                    None => (1, None),
+                };
+                cycle = Some(Cycle::new(number, info.frame_number, info.ts_ns, previous_start_ns, wkc_model.is_some()));
+            }
+        }
+        if let Some(cycle) = &mut cycle {
+            cycle.frame(info.frame_number, &frame, wkc_model);
+        }
+        ControlFlow::Continue(())
+    });
+    let summary = summary.unwrap_or_else(|e| {
+        eprintln!("{}", e);
+        exit(1);
+    });
+    if let Some(cycle) = &cycle {
+        outln!("{}", cycle.row());
+    }
+    summary.report();
+}
@@ -0,0 +1,261 @@
+use crate::capture::{self, CaptureOptions, Item, Summary};
+use crate::clock::fit_line;
+use crate::labels::Labels;
+use crate::registers::register_bytes;
+use crate::sink::outln;
+use crate::slave_config::SlaveRef;
+use crate::value::{Style, Value};
+use crate::{EtherCATCommand, EtherCATDatagram, EtherNetFrame};
+use std::collections::BTreeMap;
+use std::fs::File;
+use std::ops::ControlFlow;
+use std::process::exit;
+
+/// System time register of the DC unit, in nanoseconds since 2000-01-01.
+const SYSTEM_TIME_REGISTER: u16 = 0x0910;
+/// Receive times of ports 0 to 3, latched by a write to the register.
+const RECEIVE_TIME_REGISTER: u16 = 0x0900;
+/// Offset of the local clock from the system time, and propagation delay from the reference
+/// clock, both written by the master when it sets up DC.
+const SYSTEM_TIME_OFFSET_REGISTER: u16 = 0x0920;
+const SYSTEM_TIME_DELAY_REGISTER: u16 = 0x0928;
+/// Difference between the local copy of the system time and the system time last received
+/// from the reference clock, as a sign bit and a magnitude.
+const SYSTEM_TIME_DIFFERENCE_REGISTER: u16 = 0x092C;
+/// End of the DC register block starting at the receive times.
+const DC_BLOCK_END: u32 = 0x0A00;
+/// The DC epoch (2000-01-01T00:00:00Z) in nanoseconds since the Unix epoch.
+const DC_EPOCH_NS: u64 = 946_684_800_000_000_000;
+
+/// The 64-bit system time a datagram read from the reference clock, if it read one.
+fn system_time(datagram: &EtherCATDatagram, returned: bool) -> Option<u64> {
+    use EtherCATCommand::*;
+
+    let header = &datagram.header;
+    let reads = matches!(header.cmd, APRD | FPRD | BRD | ARMW | FRMW);
+    if !returned || !reads || datagram.wkc == 0 || header.offset_addr != SYSTEM_TIME_REGISTER || header.length < 8 {
+        return None;
+    }
+    let bytes: [u8; 8] = datagram.data.get(..8)?.try_into().ok()?;
+    Some(u64::from_le_bytes(bytes))
+}
+
+/// Linear model of the DC system time as a function of the capture time, fitted by least
+/// squares over every system time read in the capture.
+pub struct Correlation {
+    samples: usize,
+    first_frame: u64,
+    last_frame: u64,
+    /// Capture time and DC time of the first sample, the origin of the fit.
+    t0_ns: u64,
+    dc0_ns: u64,
+    intercept_ns: f64,
+    slope: f64,
+    max_residual_ns: f64,
+}
+
+impl Correlation {
+    /// Fits the model in a pass over the whole capture. Packets with link-level errors are left
+    /// out unless `include_link_errors` is set, as their content cannot be trusted.
+    pub fn from_capture(file: File, options: &CaptureOptions, include_link_errors: bool) -> Result<(Self, Summary), String> {
+        let mut samples: Vec<(u64, u64)> = Vec::new();
+        let mut frames = (0, 0);
+        let summary = capture::read(file, options, |item| {
+            if let Item::Packet(packet) = item {
+                if !include_link_errors && !packet.info.link_errors.is_empty() {
+                    return ControlFlow::Continue(());
+                }
+                if let Some(frame) = packet.data.and_then(EtherNetFrame::parse_ethercat) {
+                    let returned = frame.is_returned();
+                    for dc_ns in frame.ecat_frame.datagrams.iter().filter_map(|d| system_time(d, returned)) {
+                        if samples.is_empty() {
+                            frames.0 = packet.info.frame_number;
+                        }
+                        frames.1 = packet.info.frame_number;
+                        samples.push((packet.info.ts_ns, dc_ns));
+                    }
+                }
+            }
+            ControlFlow::Continue(())
+        })?;
+
+        let Some(&(t0_ns, dc0_ns)) = samples.first() else {
+            return Err("No DC system time reads (0x0910) in the capture".to_string());
+        };
+        let points: Vec<(f64, f64)> = samples
+            .iter()
+            .map(|&(t, dc)| ((t as i128 - t0_ns as i128) as f64, (dc as i128 - dc0_ns as i128) as f64))
+            .collect();
+        let (intercept_ns, slope) = fit_line(&points);
+        let max_residual_ns = points.iter().map(|p| (p.1 - (intercept_ns + slope * p.0)).abs()).fold(0.0, f64::max);
+
+        let correlation = Correlation {
+            samples: samples.len(),
+            first_frame: frames.0,
+            last_frame: frames.1,
+            t0_ns,
+            dc0_ns,
+            intercept_ns,
+            slope,
+            max_residual_ns,
+        };
+        Ok((correlation, summary))
+    }
+
+    /// DC system time corresponding to a capture time.
+    pub fn dc_time(&self, ts_ns: u64) -> u64 {
+        let x = (ts_ns as i128 - self.t0_ns as i128) as f64;
+        (self.dc0_ns as i128 + (self.intercept_ns + self.slope * x).round() as i128).max(0) as u64
+    }
+
+    pub fn report(&self) {
+        // DC time minus capture time, both taken from the Unix epoch.
+        let offset_ns = (self.dc_time(self.t0_ns) as i128 + DC_EPOCH_NS as i128 - self.t0_ns as i128) as f64;
+        outln!("DC samples: {} (frames {} to {})", self.samples, self.first_frame, self.last_frame);
+        outln!("Offset at frame {}: DC time {} from capture time", self.first_frame, Value::Offset(offset_ns).render(Style::Csv));
+        outln!("Drift: {:+.3} ppm", (self.slope - 1.0) * 1e6);
+        outln!("Max residual: {} ns", self.max_residual_ns.round());
+    }
+}
+
+const SLAVES_CSV_HEADER: &str = "slave,diff_samples,diff_first_ns,diff_last_ns,diff_min_ns,diff_max_ns,diff_mean_abs_ns,\
+diff_trend_ns_per_s,drift_ppm,offset_ns,delay_ns,loop_ns";
+
+/// The DC registers of one slave, as read and written in the capture.
+#[derive(Default)]
+struct SlaveClock {
+    /// System time differences read, with their capture time.
+    differences: Vec<(u64, i64)>,
+    /// System times read from the slave, with their capture time.
+    system_times: Vec<(u64, u64)>,
+    offset_ns: Option<u64>,
+    delay_ns: Option<u32>,
+    /// Time a frame took from port 0 through the rest of the chain back to port 1, at the last
+    /// read of the receive times.
+    loop_ns: Option<u32>,
+}
+
+impl SlaveClock {
+    /// Takes a datagram addressed to the slave: the registers it writes on the way out and
+    /// those it reads on the way back.
+    fn datagram(&mut self, ts_ns: u64, datagram: &EtherCATDatagram, returned: bool) {
+        use EtherCATCommand::*;
+
+        let cmd = datagram.header.cmd;
+        let writes = !returned && matches!(cmd, FPWR | FPRW);
+        let reads = returned && matches!(cmd, FPRD | FPRW | FRMW) && datagram.wkc > 0;
+        if writes || reads {
+            if let Some(offset) = register_bytes(datagram, SYSTEM_TIME_OFFSET_REGISTER) {
+                self.offset_ns = Some(u64::from_le_bytes(offset));
+            }
+            if let Some(delay) = register_bytes(datagram, SYSTEM_TIME_DELAY_REGISTER) {
+                self.delay_ns = Some(u32::from_le_bytes(delay));
+            }
+        }
+        if !reads {
+            return;
+        }
+        if let Some(time) = register_bytes(datagram, SYSTEM_TIME_REGISTER) {
+            self.system_times.push((ts_ns, u64::from_le_bytes(time)));
+        }
+        if let Some(difference) = register_bytes(datagram, SYSTEM_TIME_DIFFERENCE_REGISTER) {
+            let difference = u32::from_le_bytes(difference);
+            let magnitude = (difference & 0x7FFF_FFFF) as i64;
+            // the sign bit is set when the local copy is behind the received system time.
+            self.differences.push((ts_ns, if difference & 0x8000_0000 != 0 { -magnitude } else { magnitude }));
+        }
+        if let Some(times) = register_bytes::<8>(datagram, RECEIVE_TIME_REGISTER) {
+            let port0 = u32::from_le_bytes([times[0], times[1], times[2], times[3]]);
+            let port1 = u32::from_le_bytes([times[4], times[5], times[6], times[7]]);
+            // a port without a link latches nothing.
+            self.loop_ns = (port1 != 0).then(|| port1.wrapping_sub(port0));
+        }
+    }
+
+    fn to_row(&self, slave: u16) -> String {
+        let differences: Vec<i64> = self.differences.iter().map(|(_, difference)| *difference).collect();
+        let first = differences.first().map(i64::to_string).unwrap_or_default();
+        let last = differences.last().map(i64::to_string).unwrap_or_default();
+        let min = differences.iter().min().map(i64::to_string).unwrap_or_default();
+        let max = differences.iter().max().map(i64::to_string).unwrap_or_default();
+        let mean_abs = match differences.len() {
+            0 => String::new(),
+            n => (differences.iter().map(|difference| difference.unsigned_abs()).sum::<u64>() / n as u64).to_string(),
+        };
+        // ns per second of capture time, which a converging controller brings back toward 0.
+        let trend = (self.differences.len() > 1).then(|| {
+            let t0_ns = self.differences[0].0;
+            let points: Vec<(f64, f64)> = self.differences.iter().map(|&(t, d)| ((t - t0_ns) as f64 / 1e9, d as f64)).collect();
+            format!("{:.3}", fit_line(&points).1)
+        });
+        let drift = (self.system_times.len() > 1).then(|| {
+            let (t0_ns, dc0_ns) = self.system_times[0];
+            let points: Vec<(f64, f64)> = self.system_times.iter()
+                .map(|&(t, dc)| ((t - t0_ns) as f64, (dc as i128 - dc0_ns as i128) as f64))
+                .collect();
+            format!("{:+.3}", (fit_line(&points).1 - 1.0) * 1e6)
+        });
+        format!("{},{},{},{},{},{},{},{},{},{},{},{}",
+            Value::Address(slave).render(Style::Csv), differences.len(), first, last, min, max, mean_abs,
+            trend.unwrap_or_default(), drift.unwrap_or_default(), self.offset_ns.map(|ns| ns.to_string()).unwrap_or_default(),
+            self.delay_ns.map(|ns| ns.to_string()).unwrap_or_default(), self.loop_ns.map(|ns| ns.to_string()).unwrap_or_default())
+    }
+}
+
+/// Prints one row per slave whose DC registers are accessed through its station address: the
+/// system time differences read over the capture with the trend they follow, the drift of its
+/// system time against the capture clock, and the offset, delay and loop time of its clock.
+fn run_per_slave(file: File, options: &CaptureOptions, include_link_errors: bool, labels: &Labels) {
+    use EtherCATCommand::*;
+
+    let mut clocks: BTreeMap<u16, SlaveClock> = BTreeMap::new();
+    let summary = capture::read(file, options, |item| {
+        let Item::Packet(packet) = item else {
+            return ControlFlow::Continue(());
+        };
+        if !include_link_errors && !packet.info.link_errors.is_empty() {
+            return ControlFlow::Continue(());
+        }
+        let Some(frame) = packet.data.and_then(EtherNetFrame::parse_ethercat) else {
+            return ControlFlow::Continue(());
+        };
+        let returned = frame.is_returned();
+        for datagram in &frame.ecat_frame.datagrams {
+            let header = &datagram.header;
+            let start = header.offset_addr as u32;
+            let dc_block = start < DC_BLOCK_END && start + header.length as u32 > RECEIVE_TIME_REGISTER as u32;
+            if matches!(header.cmd, FPRD | FPWR | FPRW | FRMW) && dc_block {
+                clocks.entry(header.slave_addr).or_default().datagram(packet.info.ts_ns, datagram, returned);
+            }
+        }
+        ControlFlow::Continue(())
+    });
+    let summary = summary.unwrap_or_else(|e| {
+        eprintln!("{}", e);
+        exit(1);
+    });
+
+    outln!("{}{}", SLAVES_CSV_HEADER, labels.header());
+    for (slave, clock) in &clocks {
+        outln!("{}{}", clock.to_row(*slave), labels.cell(Some(SlaveRef::Station(*slave))));
+    }
+    summary.report();
+}
+
+/// Prints the offset and drift between the capture clock and the DC reference clock, or with
+/// `per_slave` the DC registers of every slave.
+pub fn run(file: File, options: &CaptureOptions, include_link_errors: bool, per_slave: bool, labels: &Labels) {
+    if per_slave {
+        return run_per_slave(file, options, include_link_errors, labels);
+    }
+    match Correlation::from_capture(file, options, include_link_errors) {
+        Ok((correlation, summary)) => {
+            correlation.report();
+            summary.report();
+        }
+        Err(e) => {
+            eprintln!("{}", e);
+            exit(1);
+        }
+    }
+}
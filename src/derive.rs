@@ -0,0 +1,329 @@
+use crate::EtherCATDatagram;
+
+/// A column computed for every row from the datagram fields and payload, given with
+/// `--derive name=expression`:
+///
+/// ```text
+/// vel=i32le(data,4)*0.001
+/// enabled=(u16le(data,0)>>2)&1
+/// missing=3-wkc
+/// ```
+///
+/// Expressions combine numbers (decimal or `0x` hex), the fields `cmd`, `index`, `adp`, `ado`,
+/// `length`, `round_trip`, `last_ind`, `irq`, `wkc` and `frame`, the operators `+ - * / %`,
+/// `& | << >>` (on integers) and parentheses, and reads of the payload at a byte offset:
+/// `u8`/`i8`, `u16`/`i16`/`u32`/`i32`/`u64`/`i64`/`f32`/`f64` followed by `le` or `be`. A read
+/// past the payload or a division by zero leaves the cell empty.
+pub struct DerivedColumn {
+    pub name: String,
+    expr: Expr,
+}
+
+/// What an expression can see of the row it is computed for.
+pub struct Row<'a> {
+    pub frame_number: u64,
+    pub datagram: &'a EtherCATDatagram<'a>,
+}
+
+/// Names of the datagram fields expressions refer to.
+pub const FIELDS: [&str; 10] = ["cmd", "index", "adp", "ado", "length", "round_trip", "last_ind", "irq", "wkc", "frame"];
+
+#[derive(Clone, Copy)]
+enum Field {
+    Cmd,
+    Index,
+    Adp,
+    Ado,
+    Length,
+    RoundTrip,
+    LastInd,
+    Irq,
+    Wkc,
+    Frame,
+}
+
+impl Field {
+    fn from_name(name: &str) -> Option<Self> {
+        Some(match name {
+            "cmd" => Field::Cmd,
+            "index" => Field::Index,
+            "adp" => Field::Adp,
+            "ado" => Field::Ado,
+            "length" => Field::Length,
+            "round_trip" => Field::RoundTrip,
+            "last_ind" => Field::LastInd,
+            "irq" => Field::Irq,
+            "wkc" => Field::Wkc,
+            "frame" => Field::Frame,
+            _ => return None,
+        })
+    }
+
+    fn value(self, row: &Row) -> f64 {
+        let header = &row.datagram.header;
+        match self {
+            Field::Cmd => header.cmd as u8 as f64,
+            Field::Index => header.index as f64,
+            Field::Adp => header.slave_addr as f64,
+            Field::Ado => header.offset_addr as f64,
+            Field::Length => header.length as f64,
+            Field::RoundTrip => header.round_trip as f64,
+            Field::LastInd => header.last_indicator as f64,
+            Field::Irq => header.irq as f64,
+            Field::Wkc => row.datagram.wkc as f64,
+            Field::Frame => row.frame_number as f64,
+        }
+    }
+}
+
+/// A read of the payload, such as `i32le`.
+#[derive(Clone, Copy)]
+struct Read {
+    len: usize,
+    signed: bool,
+    float: bool,
+    big_endian: bool,
+}
+
+impl Read {
+    fn from_name(name: &str) -> Option<Self> {
+        let (kind, rest) = name.split_at_checked(1)?;
+        let (bits, big_endian) = match rest {
+            "8" => ("8", false),
+            _ => match rest.len().checked_sub(2).map(|at| rest.split_at(at))? {
+                (bits, "le") => (bits, false),
+                (bits, "be") => (bits, true),
+                _ => return None,
+            },
+        };
+        let len = match bits {
+            "8" => 1,
+            "16" => 2,
+            "32" => 4,
+            "64" => 8,
+            _ => return None,
+        };
+        let (signed, float) = match kind {
+            "u" => (false, false),
+            "i" => (true, false),
+            "f" if len >= 4 => (true, true),
+            _ => return None,
+        };
+        Some(Read { len, signed, float, big_endian })
+    }
+
+    fn value(self, payload: &[u8], offset: f64) -> Option<f64> {
+        if offset < 0.0 || offset.fract() != 0.0 {
+            return None;
+        }
+        let bytes = payload.get(offset as usize..)?.get(..self.len)?;
+        let mut raw = [0u8; 8];
+        if self.big_endian {
+            raw[8 - self.len..].copy_from_slice(bytes);
+            raw.reverse();
+        } else {
+            raw[..self.len].copy_from_slice(bytes);
+        }
+        let bits = u64::from_le_bytes(raw);
+        let unused = 64 - 8 * self.len as u32;
+        Some(match (self.float, self.len) {
+            (true, 4) => f32::from_bits(bits as u32) as f64,
+            (true, _) => f64::from_bits(bits),
+            (false, _) if self.signed => ((bits << unused) as i64 >> unused) as f64,
+            (false, _) => bits as f64,
+        })
+    }
+}
+
+#[derive(Clone, Copy)]
+enum Op {
+    Add,
+    Sub,
+    Mul,
+    Div,
+    Rem,
+    And,
+    Or,
+    Shl,
+    Shr,
+}
+
+impl Op {
+    fn apply(self, a: f64, b: f64) -> Option<f64> {
+        let int = |v: f64| v as i64;
+        Some(match self {
+            Op::Add => a + b,
+            Op::Sub => a - b,
+            Op::Mul => a * b,
+            Op::Div if b == 0.0 => return None,
+            Op::Div => a / b,
+            Op::Rem if b == 0.0 => return None,
+            Op::Rem => a % b,
+            Op::And => (int(a) & int(b)) as f64,
+            Op::Or => (int(a) | int(b)) as f64,
+            Op::Shl => int(a).checked_shl(u32::try_from(int(b)).ok()?)? as f64,
+            Op::Shr => int(a).checked_shr(u32::try_from(int(b)).ok()?)? as f64,
+        })
+    }
+}
+
+enum Expr {
+    Number(f64),
+    Field(Field),
+    Read(Read, Box<Expr>),
+    Neg(Box<Expr>),
+    Binary(Op, Box<Expr>, Box<Expr>),
+}
+
+impl Expr {
+    fn eval(&self, row: &Row) -> Option<f64> {
+        match self {
+            Expr::Number(value) => Some(*value),
+            Expr::Field(field) => Some(field.value(row)),
+            Expr::Read(read, offset) => {
+                let datagram = row.datagram;
+                let payload = datagram.data;
+                read.value(payload, offset.eval(row)?)
+            }
+            Expr::Neg(expr) => Some(-expr.eval(row)?),
+            Expr::Binary(op, a, b) => op.apply(a.eval(row)?, b.eval(row)?),
+        }
+    }
+}
+
+#[derive(Clone, PartialEq)]
+enum Token {
+    Number(f64),
+    Ident(String),
+    Symbol(&'static str),
+}
+
+fn tokenize(s: &str) -> Result<Vec<Token>, String> {
+    const SYMBOLS: [&str; 12] = ["<<", ">>", "+", "-", "*", "/", "%", "&", "|", "(", ")", ","];
+    let mut tokens = Vec::new();
+    let mut rest = s.trim_start();
+    while let Some(c) = rest.chars().next() {
+        if let Some(symbol) = SYMBOLS.iter().find(|symbol| rest.starts_with(**symbol)) {
+            tokens.push(Token::Symbol(symbol));
+            rest = &rest[symbol.len()..];
+        } else if c.is_ascii_alphanumeric() || c == '_' || c == '.' {
+            let end = rest.find(|c: char| !(c.is_ascii_alphanumeric() || c == '_' || c == '.')).unwrap_or(rest.len());
+            let word = &rest[..end];
+            rest = &rest[end..];
+            if c.is_ascii_digit() || c == '.' {
+                let value = match word.strip_prefix("0x") {
+                    Some(hex) => u64::from_str_radix(hex, 16).map(|v| v as f64).ok(),
+                    None => word.parse().ok(),
+                };
+                tokens.push(Token::Number(value.ok_or_else(|| format!("invalid number '{}'", word))?));
+            } else {
+                tokens.push(Token::Ident(word.to_string()));
+            }
+        } else {
+            return Err(format!("unexpected '{}'", c));
+        }
+        rest = rest.trim_start();
+    }
+    Ok(tokens)
+}
+
+/// Operators from the loosest to the tightest binding.
+const PRECEDENCE: [&[(&str, Op)]; 5] = [
+    &[("|", Op::Or)],
+    &[("&", Op::And)],
+    &[("<<", Op::Shl), (">>", Op::Shr)],
+    &[("+", Op::Add), ("-", Op::Sub)],
+    &[("*", Op::Mul), ("/", Op::Div), ("%", Op::Rem)],
+];
+
+struct Parser {
+    tokens: Vec<Token>,
+    position: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.position)
+    }
+
+    fn next(&mut self) -> Option<Token> {
+        let token = self.tokens.get(self.position).cloned();
+        self.position += 1;
+        token
+    }
+
+    fn expect(&mut self, symbol: &str) -> Result<(), String> {
+        match self.next() {
+            Some(Token::Symbol(s)) if s == symbol => Ok(()),
+            _ => Err(format!("expected '{}'", symbol)),
+        }
+    }
+
+    fn binary(&mut self, level: usize) -> Result<Expr, String> {
+        if level == PRECEDENCE.len() {
+            return self.unary();
+        }
+        let mut expr = self.binary(level + 1)?;
+        while let Some(&(_, op)) = PRECEDENCE[level].iter().find(|(symbol, _)| self.peek() == Some(&Token::Symbol(symbol))) {
+            self.position += 1;
+            expr = Expr::Binary(op, Box::new(expr), Box::new(self.binary(level + 1)?));
+        }
+        Ok(expr)
+    }
+
+    fn unary(&mut self) -> Result<Expr, String> {
+        match self.next() {
+            Some(Token::Symbol("-")) => Ok(Expr::Neg(Box::new(self.unary()?))),
+            Some(Token::Symbol("(")) => {
+                let expr = self.binary(0)?;
+                self.expect(")")?;
+                Ok(expr)
+            }
+            Some(Token::Number(value)) => Ok(Expr::Number(value)),
+            Some(Token::Ident(name)) if self.peek() == Some(&Token::Symbol("(")) => {
+                let read = Read::from_name(&name).ok_or_else(|| format!("unknown function '{}'", name))?;
+                self.position += 1;
+                match self.next() {
+                    Some(Token::Ident(source)) if source == "data" => {}
+                    _ => return Err(format!("{} reads from data, as in {}(data,0)", name, name)),
+                }
+                self.expect(",")?;
+                let offset = self.binary(0)?;
+                self.expect(")")?;
+                Ok(Expr::Read(read, Box::new(offset)))
+            }
+            Some(Token::Ident(name)) => {
+                Field::from_name(&name).map(Expr::Field).ok_or_else(|| format!("unknown field '{}'", name))
+            }
+            _ => Err("incomplete expression".to_string()),
+        }
+    }
+}
+
+impl DerivedColumn {
+    /// Parses `name=expression`.
+    pub fn parse(s: &str) -> Result<Self, String> {
+        let error = |reason: String| format!("Invalid --derive '{}' : {}", s, reason);
+        let (name, expr) = s.split_once('=').ok_or_else(|| error("expected name=expression".to_string()))?;
+        let name = name.trim();
+        if name.is_empty() || name.contains([',', '"']) {
+            return Err(error(format!("invalid column name '{}'", name)));
+        }
+        let mut parser = Parser { tokens: tokenize(expr).map_err(error)?, position: 0 };
+        let expr = parser.binary(0).map_err(error)?;
+        if parser.peek().is_some() {
+            return Err(error("unexpected input after the expression".to_string()));
+        }
+        Ok(DerivedColumn { name: name.to_string(), expr })
+    }
+
+    /// The cell of this column for a row, empty when the expression has no value for it.
+    pub fn cell(&self, row: &Row) -> String {
+        match self.expr.eval(row) {
+            // integers print without a fraction, as long as floats hold them exactly.
+            Some(value) if value.fract() == 0.0 && value.abs() < (1u64 << 53) as f64 => format!("{}", value as i64),
+            Some(value) if value.is_finite() => format!("{}", value),
+            _ => String::new(),
+        }
+    }
+}
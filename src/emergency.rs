@@ -0,0 +1,35 @@
+use crate::coe;
+use crate::events::EventKind;
+use crate::mailbox::{self, Direction, Mailbox, MailboxType};
+use crate::slave_config::SlaveConfigs;
+use crate::EtherNetFrame;
+
+/// The emergency a mailbox message carries, if it is a CoE emergency from the slave.
+pub fn decode(mailbox: &Mailbox) -> Option<EventKind> {
+    if mailbox.mbx_type != MailboxType::CoE || mailbox.direction != Direction::FromSlave {
+        return None;
+    }
+    let emergency = coe::Emergency::parse(mailbox.payload).ok()?;
+    Some(EventKind::Emergency {
+        slave: mailbox.slave,
+        error_code: emergency.error_code,
+        error_register: emergency.error_register,
+        data: emergency.data.to_vec(),
+    })
+}
+
+/// Watches the read mailboxes of the slaves for the CoE emergencies they send, in which a slave
+/// reports an error as it detects it, before the master notices anything wrong. Takes the next
+/// packet, applying its datagrams to the SyncManager configuration that locates the mailboxes,
+/// and returns the emergencies they deliver.
+pub fn check(frame: &EtherNetFrame, configs: &mut SlaveConfigs) -> Vec<EventKind> {
+    let returned = frame.is_returned();
+    let mut events = Vec::new();
+    for datagram in &frame.ecat_frame.datagrams {
+        configs.apply(datagram, returned);
+        if let Some(event) = mailbox::extract(datagram, returned, configs).as_ref().and_then(decode) {
+            events.push(event);
+        }
+    }
+    events
+}
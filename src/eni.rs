@@ -0,0 +1,253 @@
+use crate::annotations::quote;
+use crate::capture::{self, CaptureOptions, Item};
+use crate::sink::outln;
+use crate::value::{Style, Value};
+use crate::xml::{self, Element};
+use crate::{EtherCATCommand, EtherCATDatagramHeader, EtherNetFrame};
+use std::collections::HashMap;
+use std::fs::{self, File};
+use std::ops::ControlFlow;
+use std::process::exit;
+
+const CSV_HEADER: &str = "cycle,frame,time,issue,cmd,adp,ado,wkc,expected_wkc,comment";
+
+/// One command of the cyclic frames of an ENI file.
+#[derive(Clone)]
+pub struct CyclicCommand {
+    pub cmd: EtherCATCommand,
+    /// ADP and ADO as sent by the master; logical commands split their address over both, as
+    /// in the datagram header.
+    pub adp: u16,
+    pub ado: u16,
+    pub length: Option<u16>,
+    pub expected_wkc: Option<u16>,
+    /// The comment of the configuration tool, such as the task the command belongs to.
+    pub comment: Option<String>,
+}
+
+impl CyclicCommand {
+    /// Whether an outgoing datagram is this command.
+    fn matches(&self, header: &EtherCATDatagramHeader) -> bool {
+        header.cmd == self.cmd && header.slave_addr == self.adp && header.offset_addr == self.ado
+    }
+
+    pub fn is_logical(&self) -> bool {
+        matches!(self.cmd, EtherCATCommand::LRD | EtherCATCommand::LWR | EtherCATCommand::LRW)
+    }
+
+    /// The logical address of a logical command.
+    pub fn logical_address(&self) -> u32 {
+        ((self.ado as u32) << 16) | self.adp as u32
+    }
+}
+
+/// The cyclic part of an EtherCAT network information file (ETG.2100), as exported by the
+/// configuration tool of the master, given with `--eni`: the commands of the frames the master
+/// sends every cycle, with the working counters they should come back with.
+#[derive(Clone)]
+pub struct Eni {
+    /// The commands of every cyclic frame, in order.
+    pub commands: Vec<CyclicCommand>,
+}
+
+fn command(element: &Element) -> Result<CyclicCommand, String> {
+    let field = |name: &str| -> Result<Option<u64>, String> {
+        element.child_text(name).map(|text| xml::number(text).ok_or_else(|| format!("invalid <{}> '{}'", name, text))).transpose()
+    };
+    let code = field("Cmd")?.ok_or("a <Cmd> without a command code")?;
+    let cmd = EtherCATCommand::ALL.into_iter().find(|cmd| *cmd as u64 == code).ok_or_else(|| format!("unknown command code {}", code))?;
+    let (adp, ado) = match field("Addr")? {
+        Some(address) => (address as u16, (address >> 16) as u16),
+        None => (field("Adp")?.unwrap_or(0) as u16, field("Ado")?.unwrap_or(0) as u16),
+    };
+    Ok(CyclicCommand {
+        cmd,
+        adp,
+        ado,
+        length: field("DataLength")?.map(|length| length as u16),
+        expected_wkc: field("Cnt")?.map(|wkc| wkc as u16),
+        comment: element.child_text("Comment").filter(|comment| !comment.is_empty()).map(str::to_string),
+    })
+}
+
+impl Eni {
+    pub fn load(path: &str) -> Result<Self, String> {
+        let error = |e: String| format!("Invalid ENI file {} : {}", path, e);
+        let text = fs::read_to_string(path).map_err(|e| format!("Cannot read {} : {}", path, e))?;
+        let root = xml::parse(&text).map_err(error)?;
+        let config = root.child("Config").ok_or_else(|| error("no <Config> element".to_string()))?;
+        let mut commands = Vec::new();
+        for cyclic in config.children("Cyclic") {
+            for frame in cyclic.children("Frame") {
+                for cmd in frame.children("Cmd") {
+                    commands.push(command(cmd).map_err(error)?);
+                }
+            }
+        }
+        if commands.is_empty() {
+            return Err(error("no cyclic commands".to_string()));
+        }
+        Ok(Eni { commands })
+    }
+}
+
+/// What went wrong with one command of one cycle.
+struct Issue {
+    frame: u64,
+    ts_ns: u64,
+    code: &'static str,
+    header: (EtherCATCommand, u16, u16),
+    wkc: Option<u16>,
+    command: Option<usize>,
+}
+
+/// The cyclic commands seen in one cycle.
+struct Cycle {
+    number: u64,
+    first_frame: u64,
+    start_ns: u64,
+    sent: Vec<bool>,
+    returned: Vec<bool>,
+    /// The command each datagram sent in the cycle stands for, by index and command.
+    outstanding: HashMap<(u8, EtherCATCommand), usize>,
+    issues: Vec<Issue>,
+}
+
+impl Cycle {
+    fn new(number: u64, first_frame: u64, start_ns: u64, commands: usize) -> Self {
+        Cycle {
+            number,
+            first_frame,
+            start_ns,
+            sent: vec![false; commands],
+            returned: vec![false; commands],
+            outstanding: HashMap::new(),
+            issues: Vec::new(),
+        }
+    }
+
+    /// Takes the next frame. An outgoing frame carrying a cyclic command is a cyclic frame,
+    /// whose other datagrams are unexpected; other outgoing frames are acyclic traffic.
+    fn frame(&mut self, frame_number: u64, ts_ns: u64, frame: &EtherNetFrame, eni: &Eni) {
+        let datagrams = &frame.ecat_frame.datagrams;
+        let issue = |code, header: &EtherCATDatagramHeader, wkc, command| Issue {
+            frame: frame_number, ts_ns, code, header: (header.cmd, header.slave_addr, header.offset_addr), wkc, command,
+        };
+        if !frame.is_returned() {
+            let matched: Vec<Option<usize>> = datagrams.iter()
+                .map(|datagram| {
+                    // a command sent twice in a cycle stands for the first one.
+                    (0..eni.commands.len())
+                        .find(|&i| !self.sent[i] && eni.commands[i].matches(&datagram.header))
+                        .or_else(|| eni.commands.iter().position(|command| command.matches(&datagram.header)))
+                })
+                .collect();
+            if matched.iter().all(Option::is_none) {
+                return;
+            }
+            for (datagram, command) in datagrams.iter().zip(matched) {
+                match command {
+                    Some(i) => {
+                        if eni.commands[i].length.is_some_and(|length| length != datagram.header.length) {
+                            self.issues.push(issue("length_mismatch", &datagram.header, None, Some(i)));
+                        }
+                        self.sent[i] = true;
+                        self.outstanding.insert((datagram.header.index, datagram.header.cmd), i);
+                    }
+                    None => self.issues.push(issue("unexpected", &datagram.header, None, None)),
+                }
+            }
+            return;
+        }
+        for datagram in datagrams {
+            let Some(&i) = self.outstanding.get(&(datagram.header.index, datagram.header.cmd)) else {
+                continue;
+            };
+            self.returned[i] = true;
+            let expected = eni.commands[i].expected_wkc;
+            if expected.is_some_and(|expected| expected != datagram.wkc) {
+                self.issues.push(issue("wkc_mismatch", &datagram.header, Some(datagram.wkc), Some(i)));
+            }
+        }
+    }
+
+    /// The issues of the cycle, with the commands it did not send or did not get back.
+    fn finish(mut self, eni: &Eni) -> (u64, Vec<Issue>) {
+        for (i, command) in eni.commands.iter().enumerate() {
+            let code = match (self.sent[i], self.returned[i]) {
+                (false, _) => "missing",
+                (true, false) => "not_returned",
+                (true, true) => continue,
+            };
+            self.issues.push(Issue {
+                frame: self.first_frame,
+                ts_ns: self.start_ns,
+                code,
+                header: (command.cmd, command.adp, command.ado),
+                wkc: None,
+                command: Some(i),
+            });
+        }
+        (self.number, self.issues)
+    }
+}
+
+fn print_issues(cycle: u64, issues: &[Issue], eni: &Eni) {
+    for issue in issues {
+        let (cmd, adp, ado) = issue.header;
+        let command = issue.command.map(|i| &eni.commands[i]);
+        outln!("{},{},{},{},{},{},{},{},{},{}",
+            cycle, issue.frame, Value::Time(issue.ts_ns).render(Style::Csv), issue.code, cmd,
+            Value::Address(adp).render(Style::Csv), Value::Address(ado).render(Style::Csv),
+            issue.wkc.map(|wkc| wkc.to_string()).unwrap_or_default(),
+            command.and_then(|command| command.expected_wkc).map(|wkc| wkc.to_string()).unwrap_or_default(),
+            command.and_then(|command| command.comment.as_deref()).map(quote).unwrap_or_default());
+    }
+}
+
+/// Checks every cycle of the capture against the cyclic commands of the ENI file, printing one
+/// row per command a cycle misses or does not get back, per unexpected datagram in a cyclic
+/// frame and per length or working counter differing from the ENI. A cycle starts with the outgoing frame
+/// carrying the first cyclic command; frames before the first cycle are left out.
+pub fn run(file: File, options: &CaptureOptions, eni: &Eni) {
+    let first = &eni.commands[0];
+    let mut cycle: Option<Cycle> = None;
+    let (mut cycles, mut failed) = (0u64, 0u64);
+    let mut report = |finished: Cycle| {
+        let (number, issues) = finished.finish(eni);
+        cycles += 1;
+        failed += !issues.is_empty() as u64;
+        print_issues(number, &issues, eni);
+    };
+
+    outln!("{}", CSV_HEADER);
+    let summary = capture::read(file, options, |item| {
+        let Item::Packet(packet) = item else {
+            return ControlFlow::Continue(());
+        };
+        let Some(frame) = packet.data.and_then(EtherNetFrame::parse_ethercat) else {
+            return ControlFlow::Continue(());
+        };
+        let info = packet.info;
+        let starts = !frame.is_returned() && frame.ecat_frame.datagrams.iter().any(|datagram| first.matches(&datagram.header));
+        if starts {
+            let number = cycle.as_ref().map_or(1, |previous| previous.number + 1);
+            if let Some(previous) = cycle.replace(Cycle::new(number, info.frame_number, info.ts_ns, eni.commands.len())) {
+                report(previous);
+            }
+        }
+        if let Some(cycle) = &mut cycle {
+            cycle.frame(info.frame_number, info.ts_ns, &frame, eni);
+        }
+        ControlFlow::Continue(())
+    });
+    let summary = summary.unwrap_or_else(|e| {
+        eprintln!("{}", e);
+        exit(1);
+    });
+    if let Some(cycle) = cycle {
+        report(cycle);
+    }
+    eprintln!("{} of {} cycles differ from the {} cyclic commands of the ENI file", failed, cycles, eni.commands.len());
+    summary.report();
+}
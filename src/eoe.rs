@@ -0,0 +1,161 @@
+use crate::capture::{self, CaptureOptions, Item};
+use crate::labels::Labels;
+use crate::mailbox::{self, Direction, Mailbox, MailboxType};
+use crate::sink::outln;
+use crate::slave_config::{SlaveConfigs, SlaveRef};
+use crate::value::{Style, Value};
+use crate::pcapng::{self, Frame};
+use crate::EtherNetFrame;
+use ecat_packet_analyzer_rs::parser::eoe::EoeFragment;
+use ecat_packet_analyzer_rs::ParseError;
+use std::collections::BTreeMap;
+use std::fs::File;
+use std::ops::ControlFlow;
+use std::path::Path;
+use std::process::exit;
+
+const CSV_HEADER: &str = "slave,direction,frames,bytes,fragments,reassembly_failures,services,protocols";
+
+/// The frame being reassembled from the fragments of one direction.
+struct Reassembly {
+    frame_number: u8,
+    next_fragment: u8,
+    data: Vec<u8>,
+}
+
+/// Virtual Ethernet traffic of one slave in one direction.
+#[derive(Default)]
+pub struct Stats {
+    frames: u64,
+    bytes: u64,
+    fragments: u64,
+    /// Frames lost to a missing, duplicated or misplaced fragment.
+    reassembly_failures: u64,
+    /// Set IP parameter, address filter and other non-frame requests and responses.
+    services: u64,
+    /// Reassembled frames by EtherType.
+    protocols: BTreeMap<u16, u64>,
+    reassembly: Option<Reassembly>,
+}
+
+fn protocol_name(ethertype: u16) -> String {
+    match ethertype {
+        0x0800 => "IPv4".to_string(),
+        0x0806 => "ARP".to_string(),
+        0x86DD => "IPv6".to_string(),
+        0x88A4 => "EtherCAT".to_string(),
+        0x8892 => "PROFINET".to_string(),
+        other => format!("0x{:04x}", other),
+    }
+}
+
+impl Stats {
+    /// Takes the next fragment, returning the frame it completes.
+    fn fragment(&mut self, fragment: EoeFragment) -> Option<Vec<u8>> {
+        let EoeFragment { fragment_number, offset, frame_number, data, .. } = fragment;
+        self.fragments += 1;
+
+        if fragment_number == 0 {
+            if self.reassembly.take().is_some() {
+                self.reassembly_failures += 1;
+            }
+            // the first fragment carries the frame size where the others carry their offset.
+            self.reassembly = Some(Reassembly { frame_number, next_fragment: 0, data: Vec::new() });
+        }
+        let in_sequence = self.reassembly.as_ref().is_some_and(|r| {
+            r.frame_number == frame_number
+                && r.next_fragment == fragment_number
+                && (fragment_number == 0 || r.data.len() == offset)
+        });
+        if !in_sequence {
+            if self.reassembly.take().is_some() {
+                self.reassembly_failures += 1;
+            }
+            return None;
+        }
+
+        let reassembly = self.reassembly.as_mut().unwrap();
+        reassembly.data.extend_from_slice(data);
+        reassembly.next_fragment += 1;
+        if !fragment.last_fragment {
+            return None;
+        }
+        let frame = self.reassembly.take().unwrap().data;
+        self.frames += 1;
+        self.bytes += frame.len() as u64;
+        if frame.len() >= 14 {
+            *self.protocols.entry(u16::from_be_bytes([frame[12], frame[13]])).or_default() += 1;
+        }
+        Some(frame)
+    }
+
+    /// Takes the next EoE message of the slave in this direction, returning the Ethernet
+    /// frame it completes.
+    pub fn handle(&mut self, mailbox: &Mailbox) -> Option<Vec<u8>> {
+        match EoeFragment::parse(mailbox.payload) {
+            Ok(fragment) => self.fragment(fragment),
+            Err(ParseError::NotEoeFragment(_)) => {
+                self.services += 1;
+                None
+            }
+            Err(_) => None,
+        }
+    }
+
+    fn to_row(&self, slave: u16, direction: Direction) -> String {
+        let protocols: Vec<String> = self
+            .protocols
+            .iter()
+            .map(|(ethertype, count)| format!("{}:{}", protocol_name(*ethertype), count))
+            .collect();
+        format!("{},{},{},{},{},{},{},{}",
+            Value::Address(slave).render(Style::Csv), if direction == Direction::ToSlave { "to_slave" } else { "from_slave" }, self.frames, self.bytes,
+            self.fragments, self.reassembly_failures, self.services, protocols.join(";"))
+    }
+}
+
+/// Prints the virtual Ethernet statistics of every slave exchanging EoE traffic. With
+/// `eoe_out`, also writes the reassembled frames of all slaves to that pcapng file, stamped with
+/// the capture time of their last fragment, for Wireshark to dissect the tunnelled traffic.
+pub fn run(file: File, options: &CaptureOptions, eoe_out: Option<&Path>, labels: &Labels) {
+    let mut configs = SlaveConfigs::default();
+    let mut stats: BTreeMap<(u16, Direction), Stats> = BTreeMap::new();
+    let mut frames = Vec::new();
+
+    let summary = capture::read(file, options, |item| {
+        if let Item::Packet(packet) = item {
+            if let Some(frame) = packet.data.and_then(EtherNetFrame::parse_ethercat) {
+                let returned = frame.is_returned();
+                for datagram in &frame.ecat_frame.datagrams {
+                    configs.apply(datagram, returned);
+                    match mailbox::extract(datagram, returned, &configs) {
+                        Some(mailbox) if mailbox.mbx_type == MailboxType::EoE => {
+                            let stats = stats.entry((mailbox.slave, mailbox.direction)).or_default();
+                            if let Some(data) = stats.handle(&mailbox).filter(|_| eoe_out.is_some()) {
+                                frames.push(Frame { ts_ns: packet.info.ts_ns, data });
+                            }
+                        }
+                        _ => {}
+                    }
+                }
+            }
+        }
+        ControlFlow::Continue(())
+    });
+    let summary = summary.unwrap_or_else(|e| {
+        eprintln!("{}", e);
+        exit(1);
+    });
+    if let Some(path) = eoe_out {
+        pcapng::write(path, &frames).unwrap_or_else(|e| {
+            eprintln!("Cannot write {} : {}", path.display(), e);
+            exit(1);
+        });
+    }
+
+    outln!("{}{}", CSV_HEADER, labels.header());
+    for ((slave, direction), stats) in &stats {
+        outln!("{}{}", stats.to_row(*slave, *direction), labels.cell(Some(SlaveRef::Station(*slave))));
+    }
+    summary.report();
+}
@@ -0,0 +1,230 @@
+use crate::capture::{self, CaptureOptions, Item, Summary};
+use crate::labels::addressed_slave;
+use crate::registers::{register_word, STATION_ADDRESS_REGISTER};
+use crate::slave_config::SlaveRef;
+use crate::xml::{self, Element};
+use crate::{EtherCATCommand, EtherNetFrame};
+use std::collections::{BTreeMap, HashMap};
+use std::fs::{self, File};
+use std::ops::ControlFlow;
+
+/// SII EEPROM control/status, followed by the word address of the access and the data read.
+const EEPROM_CONTROL_REGISTER: u16 = 0x0502;
+const EEPROM_ADDRESS_REGISTER: u16 = 0x0504;
+const EEPROM_DATA_REGISTER: u16 = 0x0508;
+/// The EEPROM interface is still busy; the data registers do not hold the words yet.
+const EEPROM_BUSY: u16 = 0x8000;
+/// SII word addresses of the vendor ID, the product code, the revision and the serial
+/// number, two words each.
+const SII_VENDOR_ID: u32 = 0x0008;
+const SII_PRODUCT_CODE: u32 = 0x000A;
+const SII_REVISION: u32 = 0x000C;
+const SII_SERIAL_NUMBER: u32 = 0x000E;
+
+/// Vendor ID and product code of a slave, as in its SII and in the ESI files.
+#[derive(Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Identity {
+    pub vendor_id: u32,
+    pub product_code: u32,
+}
+
+/// The device types of a folder of EtherCAT Slave Information files, given with `--esi-dir`,
+/// by identity. Every `.xml` file of the folder is read, in name order; the first file
+/// describing an identity names it.
+#[derive(Default)]
+pub struct DeviceCatalog {
+    devices: HashMap<Identity, String>,
+}
+
+/// The devices of one ESI file: its vendor, and a `Type` element per device, whose text names
+/// the device and whose `ProductCode` attribute identifies it.
+fn esi_devices(root: &Element) -> Result<Vec<(Identity, String)>, String> {
+    let vendor = root.child("Vendor").and_then(|vendor| vendor.child_text("Id")).ok_or("no <Vendor> <Id>")?;
+    let vendor_id = xml::number(vendor).ok_or_else(|| format!("invalid vendor ID '{}'", vendor))? as u32;
+    let devices = root.child("Descriptions").and_then(|descriptions| descriptions.child("Devices"));
+    let mut named = Vec::new();
+    for device in devices.into_iter().flat_map(|devices| devices.children("Device")) {
+        let Some(device_type) = device.child("Type") else {
+            continue;
+        };
+        let code = device_type.attribute("ProductCode").ok_or_else(|| format!("<Type> '{}' has no ProductCode", device_type.text))?;
+        let product_code = xml::number(code).ok_or_else(|| format!("invalid ProductCode '{}'", code))? as u32;
+        named.push((Identity { vendor_id, product_code }, device_type.text.clone()));
+    }
+    Ok(named)
+}
+
+impl DeviceCatalog {
+    pub fn load_dir(path: &str) -> Result<Self, String> {
+        let entries = fs::read_dir(path).map_err(|e| format!("Cannot read {} : {}", path, e))?;
+        let mut files: Vec<_> = entries
+            .filter_map(Result::ok)
+            .map(|entry| entry.path())
+            .filter(|file| file.extension().is_some_and(|extension| extension.eq_ignore_ascii_case("xml")))
+            .collect();
+        files.sort();
+        let mut devices = HashMap::new();
+        for file in files {
+            let bytes = fs::read(&file).map_err(|e| format!("Cannot read {} : {}", file.display(), e))?;
+            // ESI files are often ISO-8859-1; the names the catalog keeps are ASCII.
+            let named = xml::parse(&String::from_utf8_lossy(&bytes))
+                .and_then(|root| esi_devices(&root))
+                .map_err(|e| format!("Invalid ESI file {} : {}", file.display(), e))?;
+            for (identity, name) in named {
+                devices.entry(identity).or_insert(name);
+            }
+        }
+        Ok(DeviceCatalog { devices })
+    }
+
+    pub fn name(&self, identity: Identity) -> Option<&str> {
+        self.devices.get(&identity).map(String::as_str)
+    }
+}
+
+/// Learns the identity of the slaves from the SII reads of the master: the word address it
+/// writes to the EEPROM interface, and the words it reads back, by position while it scans
+/// the bus or by station address afterwards.
+#[derive(Default)]
+pub struct SiiReader {
+    /// The word address of the last EEPROM access of every slave.
+    addresses: BTreeMap<SlaveRef, u32>,
+    /// The EEPROM data reads on their way, by datagram index, with the word address they read.
+    reads: HashMap<u8, (SlaveRef, u32)>,
+    words: BTreeMap<SlaveRef, BTreeMap<u32, u16>>,
+    /// The station address the master gave each position.
+    stations: BTreeMap<u16, u16>,
+}
+
+impl SiiReader {
+    pub fn frame(&mut self, frame: &EtherNetFrame) {
+        use EtherCATCommand::*;
+
+        let returned = frame.is_returned();
+        for datagram in &frame.ecat_frame.datagrams {
+            let header = &datagram.header;
+            if returned {
+                let Some((slave, address)) = self.reads.remove(&header.index) else {
+                    continue;
+                };
+                if datagram.wkc == 0 || register_word(datagram, EEPROM_CONTROL_REGISTER).is_some_and(|status| status & EEPROM_BUSY != 0) {
+                    continue;
+                }
+                let words = self.words.entry(slave).or_default();
+                for (i, word) in (0u32..).zip((0..4).map_while(|i| register_word(datagram, EEPROM_DATA_REGISTER + 2 * i))) {
+                    words.insert(address + i, word);
+                }
+                continue;
+            }
+            let Some(slave) = addressed_slave(header, false) else {
+                continue;
+            };
+            match header.cmd {
+                APWR | FPWR => {
+                    if let (SlaveRef::Position(position), Some(station)) = (slave, register_word(datagram, STATION_ADDRESS_REGISTER)) {
+                        self.stations.insert(position, station);
+                    }
+                    let low = register_word(datagram, EEPROM_ADDRESS_REGISTER);
+                    let high = register_word(datagram, EEPROM_ADDRESS_REGISTER + 2);
+                    if let Some(low) = low {
+                        self.addresses.insert(slave, low as u32 | (high.unwrap_or(0) as u32) << 16);
+                    }
+                }
+                APRD | FPRD if register_word(datagram, EEPROM_DATA_REGISTER).is_some() => {
+                    if let Some(&address) = self.addresses.get(&slave) {
+                        self.reads.insert(header.index, (slave, address));
+                    }
+                }
+                _ => {}
+            }
+        }
+    }
+
+    /// The identity of a slave, read by its station address or by the position the master
+    /// gave that station address to.
+    pub fn identity(&self, station: u16) -> Option<Identity> {
+        self.identity_of(SlaveRef::Station(station))
+    }
+
+    /// The identity of a slave, read by the address the master used for it or by the other
+    /// one, its station address or its position.
+    pub fn identity_of(&self, slave: SlaveRef) -> Option<Identity> {
+        Some(Identity { vendor_id: self.double_word(slave, SII_VENDOR_ID)?, product_code: self.double_word(slave, SII_PRODUCT_CODE)? })
+    }
+
+    pub fn revision(&self, slave: SlaveRef) -> Option<u32> {
+        self.double_word(slave, SII_REVISION)
+    }
+
+    pub fn serial_number(&self, slave: SlaveRef) -> Option<u32> {
+        self.double_word(slave, SII_SERIAL_NUMBER)
+    }
+
+    /// The two words of the SII at `address`, read by either address of the slave.
+    fn double_word(&self, slave: SlaveRef, address: u32) -> Option<u32> {
+        let other: Vec<SlaveRef> = match slave {
+            SlaveRef::Station(station) => self.stations.iter()
+                .filter(|(_, address)| **address == station)
+                .map(|(position, _)| SlaveRef::Position(*position))
+                .collect(),
+            SlaveRef::Position(position) => self.stations.get(&position).map(|station| SlaveRef::Station(*station)).into_iter().collect(),
+        };
+        std::iter::once(slave).chain(other).find_map(|slave| {
+            let words = self.words.get(&slave)?;
+            Some(*words.get(&address)? as u32 | (*words.get(&(address + 1))? as u32) << 16)
+        })
+    }
+
+    /// The station addresses of the slaves whose identity was read.
+    fn named_stations(&self) -> Vec<u16> {
+        let mut stations: Vec<u16> = self.words.keys()
+            .filter_map(|slave| match slave {
+                SlaveRef::Station(station) => Some(*station),
+                SlaveRef::Position(position) => self.stations.get(position).copied(),
+            })
+            .collect();
+        stations.sort();
+        stations.dedup();
+        stations
+    }
+}
+
+/// The device names of the slaves of a capture, by station address, for the `slave_name`
+/// column of `--esi-dir`.
+#[derive(Default)]
+pub struct SlaveNames {
+    names: BTreeMap<u16, String>,
+    /// The station address the master gave each position, which names the position too.
+    stations: BTreeMap<u16, u16>,
+}
+
+impl SlaveNames {
+    /// Names the slaves whose identity the SII reads of the master show with the devices of
+    /// the catalog. Reads the whole capture, so that the rows before the scan are named too.
+    pub fn from_capture(file: File, options: &CaptureOptions, catalog: &DeviceCatalog) -> Result<(Self, Summary), String> {
+        let mut reader = SiiReader::default();
+        let summary = capture::read(file, options, |item| {
+            if let Item::Packet(packet) = item {
+                if let Some(frame) = packet.data.and_then(EtherNetFrame::parse_ethercat) {
+                    reader.frame(&frame);
+                }
+            }
+            ControlFlow::Continue(())
+        })?;
+        Ok((SlaveNames::new(&reader, catalog), summary))
+    }
+
+    pub fn new(reader: &SiiReader, catalog: &DeviceCatalog) -> Self {
+        let names = reader.named_stations().into_iter()
+            .filter_map(|station| Some((station, catalog.name(reader.identity(station)?)?.to_string())))
+            .collect();
+        SlaveNames { names, stations: reader.stations.clone() }
+    }
+
+    pub fn name(&self, slave: Option<SlaveRef>) -> Option<&str> {
+        match slave? {
+            SlaveRef::Station(station) => self.names.get(&station).map(String::as_str),
+            SlaveRef::Position(position) => self.stations.get(&position).and_then(|station| self.names.get(station)).map(String::as_str),
+        }
+    }
+}
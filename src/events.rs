@@ -0,0 +1,131 @@
+use crate::coe;
+use crate::mailbox::Direction;
+use ecat_packet_analyzer_rs::parser::mailbox;
+use crate::value::{Style, Value};
+use std::fmt::Display;
+
+/// Something noteworthy about the capture itself, reported next to the exported rows.
+pub struct Event {
+    /// Number of the packet at which the event was detected (0 before the first packet).
+    pub frame_number: u64,
+    pub kind: EventKind,
+}
+
+pub enum EventKind {
+    /// A packet is timestamped earlier than the previous packet of the same interface.
+    ClockBackwards { interface: u32, by_ns: u64 },
+    /// The gap to the previous packet of the same interface exceeds the jump threshold.
+    ClockJump { interface: u32, by_ns: u64 },
+    /// An interface statistics block reports packets the capture dropped since the previous one.
+    CaptureDrops { interface: u32, dropped: u64 },
+    /// IRQ event bits have been set in every returned frame for `frames` frames.
+    IrqStorm { bits: Vec<&'static str>, since_frame: u64, frames: u64 },
+    /// IRQ event bits of a storm are no longer set, after at most `frames` returned frames.
+    IrqStormEnded { bits: Vec<&'static str>, frames: u64 },
+    /// A slave shows a signature of having rebooted, after not answering for `downtime_ns` if
+    /// it stopped answering at all.
+    SlaveReboot { slave: u16, signature: &'static str, downtime_ns: Option<u64> },
+    /// A frame carries the circulating bit in `datagrams` of its `total` datagrams, after frames
+    /// that did not.
+    CirculatingFrame { datagrams: usize, total: usize },
+    /// A slave sent a CoE emergency, with its manufacturer specific `data`.
+    Emergency { slave: u16, error_code: u16, error_register: u8, data: Vec<u8> },
+    /// A slave replied with a mailbox error instead of processing a mailbox message.
+    MailboxError { slave: u16, code: u16 },
+    /// A mailbox message carries the counter of the message before it in the same direction.
+    MailboxRepeated { slave: u16, direction: Direction, counter: u8 },
+    /// The counters of `missing` mailbox messages between two in the same direction were not seen.
+    MailboxLost { slave: u16, direction: Direction, missing: u8, counter: u8 },
+    /// The master toggled the repeat request of the read mailbox, asking the slave to send its
+    /// last mailbox message again.
+    MailboxRepeatRequest { slave: u16 },
+}
+
+impl Display for Event {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Event at frame {} : {}", self.frame_number, self.kind)
+    }
+}
+
+impl Display for EventKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.render(Style::Csv))
+    }
+}
+
+impl EventKind {
+    /// Short identifier of the kind of event, as in the `--warnings-out` records.
+    pub fn code(&self) -> &'static str {
+        match self {
+            EventKind::ClockBackwards { .. } => "clock_backwards",
+            EventKind::ClockJump { .. } => "clock_jump",
+            EventKind::CaptureDrops { .. } => "capture_drops",
+            EventKind::IrqStorm { .. } => "irq_storm",
+            EventKind::IrqStormEnded { .. } => "irq_storm_ended",
+            EventKind::SlaveReboot { .. } => "slave_reboot",
+            EventKind::CirculatingFrame { .. } => "circulating_frame",
+            EventKind::Emergency { .. } => "emergency",
+            EventKind::MailboxError { .. } => "mailbox_error",
+            EventKind::MailboxRepeated { .. } => "mailbox_repeated",
+            EventKind::MailboxLost { .. } => "mailbox_lost",
+            EventKind::MailboxRepeatRequest { .. } => "mailbox_repeat_request",
+        }
+    }
+
+    pub fn render(&self, style: Style) -> String {
+        match self {
+            EventKind::ClockBackwards { interface, by_ns } => {
+                format!("timestamp of interface {} went backwards by {}", interface, Value::Duration(*by_ns).render(style))
+            }
+            EventKind::ClockJump { interface, by_ns } => {
+                format!("timestamp of interface {} jumped forward by {}", interface, Value::Duration(*by_ns).render(style))
+            }
+            EventKind::CaptureDrops { interface, dropped } => {
+                format!("interface {} dropped {} packets before this point", interface, dropped)
+            }
+            EventKind::IrqStorm { bits, since_frame, frames } => {
+                format!("IRQ {} asserted in {} consecutive returned frames since frame {}", bits.join(","), frames, since_frame)
+            }
+            EventKind::IrqStormEnded { bits, frames } => {
+                format!("IRQ {} cleared after {} returned frames", bits.join(","), frames)
+            }
+            EventKind::SlaveReboot { slave, signature, downtime_ns } => {
+                let slave = Value::Address(*slave).render(style);
+                match downtime_ns {
+                    Some(ns) => format!("slave {} rebooted ({}), down for {}", slave, signature, Value::Duration(*ns).render(style)),
+                    None => format!("slave {} rebooted ({})", slave, signature),
+                }
+            }
+            EventKind::CirculatingFrame { datagrams, total } => {
+                format!("circulating frame, the circulating bit is set in {} of its {} datagrams", datagrams, total)
+            }
+            EventKind::Emergency { slave, error_code, error_register, data } => {
+                let data: String = data.iter().map(|byte| format!("{:02x}", byte)).collect();
+                format!("slave {} sent emergency 0x{:04x} ({}), error register 0x{:02x}, data {}",
+                    Value::Address(*slave).render(style), error_code, coe::error_class(*error_code), error_register, data)
+            }
+            EventKind::MailboxError { slave, code } => {
+                format!("slave {} replied mailbox error 0x{:04x} ({})",
+                    Value::Address(*slave).render(style), code, mailbox::error_message(*code).unwrap_or("unknown error"))
+            }
+            EventKind::MailboxRepeated { slave, direction, counter } => {
+                format!("mailbox message {} {} repeated", counter, direction_of(*direction, *slave, style))
+            }
+            EventKind::MailboxLost { slave, direction, missing, counter } => {
+                format!("{} mailbox messages {} missing before message {}", missing, direction_of(*direction, *slave, style), counter)
+            }
+            EventKind::MailboxRepeatRequest { slave } => {
+                format!("master requested slave {} to repeat its last mailbox message", Value::Address(*slave).render(style))
+            }
+        }
+    }
+}
+
+/// The slave a mailbox message goes to or comes from, such as `to slave 0x1001`.
+fn direction_of(direction: Direction, slave: u16, style: Style) -> String {
+    let direction = match direction {
+        Direction::ToSlave => "to",
+        Direction::FromSlave => "from",
+    };
+    format!("{} slave {}", direction, Value::Address(slave).render(style))
+}
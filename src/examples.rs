@@ -0,0 +1,114 @@
+use crate::batch::Cache;
+use crate::sink::outln;
+use crate::Args;
+use clap::Parser;
+use std::fs;
+use std::path::PathBuf;
+use std::process::exit;
+
+/// The command line of the cookbook: `examples [name]`.
+#[derive(Parser, Debug)]
+#[command(about = "List the demonstrations run on the sample captures built into the exporter, or run one")]
+struct ExamplesArgs {
+    /// Always `examples`
+    #[arg(hide = true)]
+    examples: String,
+    /// The demonstration to run; without it, they are listed
+    name: Option<String>,
+    /// Directory the sample capture and configuration files are written to, to be reused with
+    /// the printed command line; a directory in the temporary directory by default
+    #[arg(long)]
+    output_dir: Option<String>,
+}
+
+/// A demonstration: a sample capture, the files its command line refers to, and the command
+/// line after `--file`.
+struct Example {
+    name: &'static str,
+    description: &'static str,
+    capture: (&'static str, &'static [u8]),
+    files: &'static [(&'static str, &'static [u8])],
+    args: &'static [&'static str],
+}
+
+const EXAMPLES: &[Example] = &[
+    Example {
+        name: "wkc-fault",
+        description: "show a working counter fault: a slave not answering its share of the process data",
+        capture: ("chained_datagrams.pcapng", include_bytes!("../tests/fixtures/chained_datagrams.pcapng")),
+        files: &[("wkc.toml", include_bytes!("../tests/fixtures/wkc.toml"))],
+        args: &["--wkc-config", "wkc.toml"],
+    },
+    Example {
+        name: "coe-startup",
+        description: "decode the CoE startup: the PDO mapping the master configures over SDO downloads",
+        capture: ("pdo_mapping.pcapng", include_bytes!("../tests/fixtures/pdo_mapping.pcapng")),
+        files: &[],
+        args: &["pdo"],
+    },
+    Example {
+        name: "firmware-update",
+        description: "follow a firmware download over FoE, with a repeated packet and a failed read",
+        capture: ("foe_update.pcapng", include_bytes!("../tests/fixtures/foe_update.pcapng")),
+        files: &[],
+        args: &["foe"],
+    },
+    Example {
+        name: "slave-reboot",
+        description: "report the capture of a slave rebooting in the middle of operation, for an issue tracker",
+        capture: ("slave_reboot.pcapng", include_bytes!("../tests/fixtures/slave_reboot.pcapng")),
+        files: &[],
+        args: &["--format", "markdown-report"],
+    },
+];
+
+/// Whether the exporter was started as `examples`.
+pub fn invoked() -> bool {
+    std::env::args().nth(1).as_deref() == Some("examples")
+}
+
+fn command_line(example: &Example, capture: &str) -> String {
+    let mut words = vec![env!("CARGO_PKG_NAME"), "--file", capture];
+    words.extend(example.args);
+    words.join(" ")
+}
+
+/// Lists the demonstrations, or writes the files of one and runs it, printing its command line
+/// on stderr.
+pub fn main() {
+    let args = ExamplesArgs::parse();
+    let Some(name) = &args.name else {
+        for example in EXAMPLES {
+            outln!("{}: {}", example.name, example.description);
+            outln!("    {}", command_line(example, example.capture.0));
+        }
+        return;
+    };
+    let Some(example) = EXAMPLES.iter().find(|example| example.name == name) else {
+        let names: Vec<&str> = EXAMPLES.iter().map(|example| example.name).collect();
+        eprintln!("Unknown example {}, the examples are {}", name, names.join(", "));
+        exit(1);
+    };
+
+    let dir = args.output_dir.as_ref().map_or_else(|| std::env::temp_dir().join("ecat-examples"), PathBuf::from);
+    let write = |file_name: &str, contents: &[u8]| {
+        let path = dir.join(file_name);
+        fs::create_dir_all(&dir).and_then(|_| fs::write(&path, contents)).unwrap_or_else(|e| {
+            eprintln!("Cannot write {} : {}", path.display(), e);
+            exit(1);
+        });
+        path.to_string_lossy().into_owned()
+    };
+    let capture = write(example.capture.0, example.capture.1);
+    let mut arguments = vec!["examples".to_string(), "--file".to_string(), capture];
+    for arg in example.args {
+        // the configuration files are given by name, and written next to the capture.
+        match example.files.iter().find(|(file_name, _)| file_name == arg) {
+            Some((file_name, contents)) => arguments.push(write(file_name, contents)),
+            None => arguments.push(arg.to_string()),
+        }
+    }
+    eprintln!("$ {} {}", env!("CARGO_PKG_NAME"), arguments[1..].join(" "));
+    let export_args = Args::try_parse_from(&arguments).unwrap_or_else(|e| e.exit());
+    crate::run(&export_args, &mut Cache::default());
+}
@@ -0,0 +1,191 @@
+use crate::capture::{self, CaptureOptions, Item};
+use crate::eoe;
+use crate::mailbox::{self, Direction, MailboxType};
+use crate::sink::outln;
+use crate::slave_config::SlaveConfigs;
+use crate::EtherNetFrame;
+use clap::Parser;
+use std::collections::BTreeMap;
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::ops::ControlFlow;
+use std::process::exit;
+
+/// Magic number of pcap files with nanosecond timestamps.
+const PCAP_MAGIC_NS: u32 = 0xA1B2_3C4D;
+const DLT_EN10MB: u32 = 1;
+const SNAPLEN: u32 = 65535;
+
+/// The interfaces offered to Wireshark, each replaying a capture file through a filter.
+const INTERFACES: &[(&str, &str)] = &[
+    ("ecat-mailbox", "EtherCAT mailbox frames (replayed capture)"),
+    ("ecat-eoe", "EtherCAT EoE tunnelled Ethernet (replayed capture)"),
+];
+
+/// The command line Wireshark uses to query and start an extcap capture source.
+#[derive(Parser, Debug)]
+#[command(about = "Wireshark extcap interface of the exporter")]
+struct ExtcapArgs {
+    #[arg(long)]
+    extcap_interfaces: bool,
+    #[arg(long)]
+    extcap_version: Option<String>,
+    #[arg(long)]
+    extcap_interface: Option<String>,
+    #[arg(long)]
+    extcap_dlts: bool,
+    #[arg(long)]
+    extcap_config: bool,
+    #[arg(long)]
+    capture: bool,
+    #[arg(long)]
+    fifo: Option<String>,
+    #[arg(long)]
+    extcap_capture_filter: Option<String>,
+    #[arg(long)]
+    extcap_control_in: Option<String>,
+    #[arg(long)]
+    extcap_control_out: Option<String>,
+    /// The pcapng or pcap capture to replay
+    #[arg(long)]
+    file: Option<String>,
+    /// Skip over corrupt pcapng blocks
+    #[arg(long)]
+    recover: bool,
+}
+
+/// Whether Wireshark started the exporter as an extcap program.
+pub fn invoked() -> bool {
+    std::env::args().skip(1).any(|arg| arg.starts_with("--extcap-"))
+}
+
+/// Writes a pcap file packet by packet, flushing each one to the reading Wireshark.
+struct PcapWriter {
+    output: BufWriter<File>,
+}
+
+impl PcapWriter {
+    fn new(output: File) -> std::io::Result<Self> {
+        let mut output = BufWriter::new(output);
+        output.write_all(&PCAP_MAGIC_NS.to_le_bytes())?;
+        output.write_all(&2u16.to_le_bytes())?;
+        output.write_all(&4u16.to_le_bytes())?;
+        output.write_all(&0i32.to_le_bytes())?;
+        output.write_all(&0u32.to_le_bytes())?;
+        output.write_all(&SNAPLEN.to_le_bytes())?;
+        output.write_all(&DLT_EN10MB.to_le_bytes())?;
+        output.flush()?;
+        Ok(PcapWriter { output })
+    }
+
+    fn packet(&mut self, ts_ns: u64, data: &[u8]) -> std::io::Result<()> {
+        let captured = data.len().min(SNAPLEN as usize);
+        self.output.write_all(&((ts_ns / 1_000_000_000) as u32).to_le_bytes())?;
+        self.output.write_all(&((ts_ns % 1_000_000_000) as u32).to_le_bytes())?;
+        self.output.write_all(&(captured as u32).to_le_bytes())?;
+        self.output.write_all(&(data.len() as u32).to_le_bytes())?;
+        self.output.write_all(&data[..captured])?;
+        self.output.flush()
+    }
+}
+
+fn print_interfaces() {
+    outln!("extcap {{version={}}}{{help=https://github.com/babakou/ecat_packet_exporter}}", env!("CARGO_PKG_VERSION"));
+    for (value, display) in INTERFACES {
+        outln!("interface {{value={}}}{{display={}}}", value, display);
+    }
+}
+
+fn print_config() {
+    outln!("arg {{number=0}}{{call=--file}}{{display=Capture file}}{{type=fileselect}}{{mustexist=true}}{{required=true}}{{tooltip=pcapng or pcap capture to replay}}");
+    outln!("arg {{number=1}}{{call=--recover}}{{display=Skip corrupt blocks}}{{type=boolflag}}{{tooltip=Skip over corrupt pcapng blocks instead of stopping}}");
+}
+
+/// Replays the capture into the fifo, keeping only what the interface selects.
+fn capture(interface: &str, file: File, options: &CaptureOptions, fifo: &str) -> Result<(), String> {
+    let output = File::create(fifo).map_err(|e| format!("Cannot open {} : {}", fifo, e))?;
+    let mut writer = PcapWriter::new(output).map_err(|e| e.to_string())?;
+    let mut configs = SlaveConfigs::default();
+    let mut eoe: BTreeMap<(u16, Direction), eoe::Stats> = BTreeMap::new();
+    let mut write_error = None;
+
+    let summary = capture::read(file, options, |item| {
+        let Item::Packet(packet) = item else {
+            return ControlFlow::Continue(());
+        };
+        let Some(data) = packet.data else {
+            return ControlFlow::Continue(());
+        };
+        let Some(frame) = EtherNetFrame::parse_ethercat(data) else {
+            return ControlFlow::Continue(());
+        };
+        let returned = frame.is_returned();
+        let mut selected = false;
+        let mut tunnelled: Vec<Vec<u8>> = Vec::new();
+        for datagram in &frame.ecat_frame.datagrams {
+            configs.apply(datagram, returned);
+            let Some(mailbox) = mailbox::extract(datagram, returned, &configs) else {
+                continue;
+            };
+            selected = true;
+            if mailbox.mbx_type == MailboxType::EoE {
+                tunnelled.extend(eoe.entry((mailbox.slave, mailbox.direction)).or_default().handle(&mailbox));
+            }
+        }
+        let result = match interface {
+            "ecat-eoe" => tunnelled.iter().try_for_each(|frame| writer.packet(packet.info.ts_ns, frame)),
+            _ if selected => writer.packet(packet.info.ts_ns, data),
+            _ => Ok(()),
+        };
+        match result {
+            Ok(()) => ControlFlow::Continue(()),
+            Err(e) => {
+                write_error = Some(e);
+                ControlFlow::Break(())
+            }
+        }
+    })?;
+    summary.report();
+    // a closed fifo means Wireshark stopped the capture.
+    match write_error {
+        Some(e) if e.kind() != std::io::ErrorKind::BrokenPipe => Err(format!("Cannot write to {} : {}", fifo, e)),
+        _ => Ok(()),
+    }
+}
+
+/// Answers a query of Wireshark or runs the capture it asked for.
+pub fn main() {
+    let args = ExtcapArgs::parse();
+    if args.extcap_interfaces {
+        print_interfaces();
+        return;
+    }
+    let Some(interface) = args.extcap_interface.as_deref() else {
+        eprintln!("--extcap-interface is required");
+        exit(1);
+    };
+    if !INTERFACES.iter().any(|(value, _)| *value == interface) {
+        eprintln!("Unknown interface {}", interface);
+        exit(1);
+    }
+    if args.extcap_dlts {
+        outln!("dlt {{number={}}}{{name=EN10MB}}{{display=Ethernet}}", DLT_EN10MB);
+    } else if args.extcap_config {
+        print_config();
+    } else if args.capture {
+        let (Some(path), Some(fifo)) = (args.file.as_deref(), args.fifo.as_deref()) else {
+            eprintln!("--capture needs --file and --fifo");
+            exit(1);
+        };
+        let file = capture::open(path).unwrap_or_else(|e| {
+            eprintln!("{}", e);
+            exit(1);
+        });
+        // Wireshark shows the replayed capture itself, its clock jumps need no diagnostics.
+        let options = CaptureOptions { recover: args.recover, clock_jump_ns: u64::MAX, quiet: false, ecat_location: Default::default(), next_files: Vec::new() };
+        if let Err(e) = capture(interface, file, &options, fifo) {
+            eprintln!("{}", e);
+            exit(1);
+        }
+    }
+}
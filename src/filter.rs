@@ -0,0 +1,203 @@
+use crate::capture::{self, CaptureOptions, Item, PacketInfo, Summary};
+use crate::clock::TimeSpec;
+use crate::warnings::{self, Record, WarningLog};
+use crate::wkc::WkcModel;
+use crate::{is_ethercat, EtherCATCommand, EtherCATDatagram, EtherCATDatagramHeader, EtherNetFrame};
+use std::fs::File;
+use std::ops::ControlFlow;
+
+/// The addresses a value of `--adp` or `--ado` matches: one address, or a range written
+/// `first..end`, end excluded, or `first..=last`. Addresses are decimal or hexadecimal with `0x`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct AddressRange {
+    first: u16,
+    last: u16,
+}
+
+fn parse_address(s: &str) -> Option<u32> {
+    let s = s.trim();
+    match s.strip_prefix("0x").or_else(|| s.strip_prefix("0X")) {
+        Some(hex) => u32::from_str_radix(hex, 16).ok(),
+        None => s.parse().ok(),
+    }
+}
+
+impl AddressRange {
+    pub fn parse(s: &str) -> Result<Self, String> {
+        let error = |reason: &str| format!("invalid address range '{}' : {}", s, reason);
+        let address = |s: &str| parse_address(s).ok_or_else(|| error("addresses are numbers such as 0x1001 or 4097"));
+        let (first, last) = if let Some((first, last)) = s.split_once("..=") {
+            (address(first)?, address(last)?)
+        } else if let Some((first, end)) = s.split_once("..") {
+            (address(first)?, address(end)?.checked_sub(1).ok_or_else(|| error("the range is empty"))?)
+        } else {
+            let address = address(s)?;
+            (address, address)
+        };
+        if first > last {
+            return Err(error("the range is empty"));
+        }
+        let last = u16::try_from(last).map_err(|_| error("addresses are 16 bits"))?;
+        // first <= last, so it fits as well.
+        Ok(AddressRange { first: first as u16, last })
+    }
+
+    fn contains(self, address: u16) -> bool {
+        (self.first..=self.last).contains(&address)
+    }
+}
+
+/// Which datagrams the export keeps: those of one of the given commands, addressed to one of
+/// the given ADP and ADO ranges. A criterion left empty matches every datagram. The ranges
+/// match the address fields as they are, whatever the addressing of the command: position or
+/// station address and register for device addressing, the two halves of the logical address
+/// for logical addressing.
+#[derive(Clone, Debug, Default)]
+pub struct DatagramFilter {
+    pub commands: Vec<EtherCATCommand>,
+    pub adp: Vec<AddressRange>,
+    pub ado: Vec<AddressRange>,
+}
+
+impl DatagramFilter {
+    /// Whether every datagram matches.
+    pub fn is_empty(&self) -> bool {
+        self.commands.is_empty() && self.adp.is_empty() && self.ado.is_empty()
+    }
+
+    pub fn matches(&self, header: &EtherCATDatagramHeader) -> bool {
+        let any = |ranges: &[AddressRange], address| ranges.is_empty() || ranges.iter().any(|range| range.contains(address));
+        (self.commands.is_empty() || self.commands.contains(&header.cmd))
+            && any(&self.adp, header.slave_addr)
+            && any(&self.ado, header.offset_addr)
+    }
+}
+
+/// The frames of `--frames`: one frame number, or a range written `first..end`, end excluded,
+/// or `first..=last`. Frames are numbered from 1, in capture order.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct FrameRange {
+    pub first: u64,
+    pub last: u64,
+}
+
+impl FrameRange {
+    pub fn parse(s: &str) -> Result<Self, String> {
+        let error = |reason: &str| format!("invalid frame range '{}' : {}", s, reason);
+        let number = |s: &str| s.trim().parse::<u64>().map_err(|_| error("frame numbers are positive numbers such as 1000"));
+        let (first, last) = if let Some((first, last)) = s.split_once("..=") {
+            (number(first)?, number(last)?)
+        } else if let Some((first, end)) = s.split_once("..") {
+            (number(first)?, number(end)?.checked_sub(1).ok_or_else(|| error("the range is empty"))?)
+        } else {
+            let number = number(s)?;
+            (number, number)
+        };
+        if first == 0 {
+            return Err(error("frames are numbered from 1"));
+        }
+        if first > last {
+            return Err(error("the range is empty"));
+        }
+        Ok(FrameRange { first, last })
+    }
+}
+
+/// Where a packet stands against the window of `--from`, `--to` and `--frames`.
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum Window {
+    Before,
+    In,
+    /// Past the window, and so are all the packets after it.
+    After,
+}
+
+/// What an export keeps of the capture: the packets of the window of `--from`, `--to` and
+/// `--frames`, and of them the datagrams of `--cmd`, `--adp` and `--ado`.
+#[derive(Clone, Debug, Default)]
+pub struct Selection {
+    pub from: Option<TimeSpec>,
+    pub to: Option<TimeSpec>,
+    pub frames: Option<FrameRange>,
+    pub datagrams: DatagramFilter,
+}
+
+impl Selection {
+    /// Where a packet stands against the window, times of a relative window counting from
+    /// `first_ts_ns`, that of the first packet.
+    pub fn window(&self, frame_number: u64, ts_ns: u64, first_ts_ns: u64) -> Window {
+        if self.to.is_some_and(|to| ts_ns >= to.resolve(first_ts_ns)) || self.frames.is_some_and(|frames| frame_number > frames.last) {
+            Window::After
+        } else if self.from.is_some_and(|from| ts_ns < from.resolve(first_ts_ns)) || self.frames.is_some_and(|frames| frame_number < frames.first) {
+            Window::Before
+        } else {
+            Window::In
+        }
+    }
+}
+
+/// Reads the EtherCAT frames of the window of a selection, handing each over with those of
+/// its datagrams the selection keeps; frames keeping none are left out. Reading stops past the
+/// window. The anomalies of the capture and of the frames of the window go to `warnings`, the
+/// working counters checked against `wkc_model`.
+pub fn read_datagrams(
+    file: File,
+    options: &CaptureOptions,
+    selection: &Selection,
+    mut warnings: Option<WarningLog>,
+    wkc_model: Option<&WkcModel>,
+    mut visit: impl FnMut(&PacketInfo, &EtherNetFrame, &[&EtherCATDatagram]),
+) -> Result<Summary, String> {
+    let logging = warnings.is_some();
+    let mut log = |record: Record| {
+        if let Some(Err(e)) = warnings.as_mut().map(|log| log.record(record)) {
+            eprintln!("{}", e);
+        }
+    };
+    let mut first_ts_ns = None;
+    let state = capture::read_from(file, options, None, |item, state| {
+        let packet = match item {
+            Item::Packet(packet) => packet,
+            Item::Event(event) => {
+                log(Record::event("capture", event.frame_number, &event.kind));
+                return ControlFlow::Continue(());
+            }
+            Item::CorruptBlock { offset, reason, skipped } => {
+                log(Record::corrupt_block(state.frame_number(), offset, reason, skipped));
+                return ControlFlow::Continue(());
+            }
+            _ => return ControlFlow::Continue(()),
+        };
+        let first_ts_ns = *first_ts_ns.get_or_insert(packet.info.ts_ns);
+        match selection.window(packet.info.frame_number, packet.info.ts_ns, first_ts_ns) {
+            Window::After => return ControlFlow::Break(()),
+            Window::Before => return ControlFlow::Continue(()),
+            Window::In => {}
+        }
+        let Some(data) = packet.data.filter(|data| is_ethercat(data)) else {
+            return ControlFlow::Continue(());
+        };
+        let frame = match EtherNetFrame::parse(data) {
+            Ok(frame) if frame.carries_ethercat() => frame,
+            Ok(_) => return ControlFlow::Continue(()),
+            Err(e) => {
+                log(Record::malformed_frame(packet.info.frame_number, &e));
+                return ControlFlow::Continue(());
+            }
+        };
+        if logging {
+            warnings::frame_records(&packet.info, &frame, wkc_model).into_iter().for_each(&mut log);
+        }
+        let datagrams: Vec<&EtherCATDatagram> = frame.ecat_frame.datagrams.iter()
+            .filter(|datagram| selection.datagrams.matches(&datagram.header))
+            .collect();
+        if !datagrams.is_empty() {
+            visit(&packet.info, &frame, &datagrams);
+        }
+        ControlFlow::Continue(())
+    });
+    if let Some(log) = warnings.as_mut() {
+        log.flush()?;
+    }
+    state.map(|state| state.summary)
+}
@@ -0,0 +1,237 @@
+use crate::annotations::quote;
+use crate::capture::{self, CaptureOptions, Item};
+use crate::mailbox::{self, Direction, Mailbox, MailboxType};
+use crate::labels::Labels;
+use crate::sink::outln;
+use crate::slave_config::{SlaveConfigs, SlaveRef};
+use crate::transaction::{self, Transactions};
+use crate::value::{Style, Value};
+use crate::EtherNetFrame;
+use ecat_packet_analyzer_rs::parser::foe::{self as protocol, FoeMessage, FOE_HEADER_LEN};
+use std::collections::HashMap;
+use std::fmt::Display;
+use std::fs::{self, File};
+use std::ops::ControlFlow;
+use std::path::Path;
+use std::process::exit;
+
+const CSV_HEADER: &str = "slave,filename,direction,bytes,start,duration,retries,status,transaction_id";
+const EXTRACT_CSV_HEADER: &str = "slave,filename,direction,password,bytes,status,transaction_id,path";
+
+enum Status {
+    InProgress,
+    Completed,
+    Error { code: u32, text: String },
+    /// Another request started before the transfer finished.
+    Abandoned,
+}
+
+impl Display for Status {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Status::InProgress => write!(f, "incomplete"),
+            Status::Completed => write!(f, "completed"),
+            Status::Error { code, text } if text.is_empty() => match protocol::error_message(*code) {
+                Some(message) => write!(f, "error 0x{:04x} ({})", code, message),
+                None => write!(f, "error 0x{:04x}", code),
+            },
+            Status::Error { code, text } => write!(f, "error 0x{:04x} ({})", code, text),
+            Status::Abandoned => write!(f, "abandoned"),
+        }
+    }
+}
+
+/// One file transfer, from its read or write request to its last message.
+struct Session {
+    /// ID of the transaction, that of the datagram carrying the request.
+    transaction: String,
+    slave: u16,
+    filename: String,
+    password: u32,
+    /// The master writes the file to the slave (a firmware download) rather than reading it.
+    write: bool,
+    start_ns: u64,
+    end_ns: u64,
+    bytes: u64,
+    last_packet: u32,
+    /// Number of the short packet ending the file, once it has been sent.
+    final_packet: Option<u32>,
+    retries: u32,
+    status: Status,
+    /// The file as reassembled from the data packets, when it is to be extracted.
+    data: Option<Vec<u8>>,
+}
+
+impl Session {
+    /// Data travels to the slave in a write session and from it in a read session.
+    fn data_direction(&self) -> Direction {
+        if self.write { Direction::ToSlave } else { Direction::FromSlave }
+    }
+
+    fn handle(&mut self, ts_ns: u64, mailbox: &Mailbox, message: FoeMessage) {
+        self.end_ns = ts_ns;
+        match message {
+            FoeMessage::Data { packet, data } if mailbox.direction == self.data_direction() => {
+                if packet <= self.last_packet {
+                    self.retries += 1;
+                    return;
+                }
+                self.last_packet = packet;
+                self.bytes += data.len() as u64;
+                if let Some(file) = &mut self.data {
+                    file.extend_from_slice(data);
+                }
+                // a packet leaving part of the mailbox unused is the last one of the file.
+                if FOE_HEADER_LEN + data.len() < mailbox.capacity {
+                    self.final_packet = Some(packet);
+                }
+            }
+            FoeMessage::Ack { packet } if mailbox.direction != self.data_direction() && self.final_packet == Some(packet) => {
+                self.status = Status::Completed;
+            }
+            FoeMessage::Error { code, text } => {
+                self.status = Status::Error { code, text: String::from_utf8_lossy(text).to_string() };
+            }
+            _ => {}
+        }
+    }
+
+    fn to_row(&self) -> String {
+        format!("{},{},{},{},{},{},{},{},{}",
+            Value::Address(self.slave).render(Style::Csv), quote(&self.filename), if self.write { "write" } else { "read" }, self.bytes,
+            Value::Time(self.start_ns).render(Style::Csv), Value::Duration(self.end_ns - self.start_ns).render(Style::Csv), self.retries,
+            quote(&self.status.to_string()),
+            self.transaction)
+    }
+}
+
+/// Every FoE transfer of the capture, in the order the transfers started.
+#[derive(Default)]
+struct Sessions {
+    sessions: Vec<Session>,
+    /// Index of the latest session of each slave.
+    current: HashMap<u16, usize>,
+    /// Reassemble the transferred files.
+    keep_data: bool,
+}
+
+impl Sessions {
+    fn handle(&mut self, ts_ns: u64, mailbox: &Mailbox, transaction: String) {
+        if mailbox.mbx_type != MailboxType::FoE {
+            return;
+        }
+        let Ok(message) = FoeMessage::parse(mailbox.payload) else {
+            return;
+        };
+
+        match message {
+            FoeMessage::ReadRequest { password, filename } | FoeMessage::WriteRequest { password, filename }
+                if mailbox.direction == Direction::ToSlave =>
+            {
+                if let Some(session) = self.current.get(&mailbox.slave).map(|&i| &mut self.sessions[i]) {
+                    if let Status::InProgress = session.status {
+                        session.status = Status::Abandoned;
+                    }
+                }
+                self.current.insert(mailbox.slave, self.sessions.len());
+                self.sessions.push(Session {
+                    transaction,
+                    slave: mailbox.slave,
+                    filename: String::from_utf8_lossy(filename).to_string(),
+                    password,
+                    write: matches!(message, FoeMessage::WriteRequest { .. }),
+                    start_ns: ts_ns,
+                    end_ns: ts_ns,
+                    bytes: 0,
+                    last_packet: 0,
+                    final_packet: None,
+                    retries: 0,
+                    status: Status::InProgress,
+                    data: self.keep_data.then(Vec::new),
+                });
+            }
+            _ => {
+                let Some(session) = self.current.get(&mailbox.slave).map(|&i| &mut self.sessions[i]) else {
+                    return;
+                };
+                if let Status::InProgress = session.status {
+                    session.handle(ts_ns, mailbox, message);
+                }
+            }
+        }
+    }
+}
+
+/// Follows the FoE transfers of the whole capture.
+fn read_sessions(file: File, options: &CaptureOptions, keep_data: bool) -> (Sessions, capture::Summary) {
+    let mut configs = SlaveConfigs::default();
+    let mut sessions = Sessions { keep_data, ..Sessions::default() };
+    let mut transactions = Transactions::default();
+
+    let summary = capture::read(file, options, |item| {
+        if let Item::Packet(packet) = item {
+            if let Some(frame) = packet.data.and_then(EtherNetFrame::parse_ethercat) {
+                let returned = frame.is_returned();
+                for (position, datagram) in frame.ecat_frame.datagrams.iter().enumerate() {
+                    configs.apply(datagram, returned);
+                    if let Some(mailbox) = mailbox::extract(datagram, returned, &configs) {
+                        let id = transaction::datagram_id(packet.info.frame_number, position);
+                        sessions.handle(packet.info.ts_ns, &mailbox, transactions.message(id, &mailbox));
+                    }
+                }
+            }
+        }
+        ControlFlow::Continue(())
+    });
+    let summary = summary.unwrap_or_else(|e| {
+        eprintln!("{}", e);
+        exit(1);
+    });
+    (sessions, summary)
+}
+
+/// The name a transferred file is written under: its transaction ID, which tells apart the
+/// transfers of the same file, then its name without the characters unsafe in a path.
+fn extracted_name(session: &Session) -> String {
+    let name: String = session.filename.chars()
+        .map(|c| if c.is_ascii_alphanumeric() || matches!(c, '.' | '-' | '_') { c } else { '_' })
+        .collect();
+    let name = name.trim_start_matches('.');
+    format!("{}_{}", session.transaction, if name.is_empty() { "unnamed" } else { name })
+}
+
+/// Prints one row per FoE file transfer found in the capture.
+pub fn run(file: File, options: &CaptureOptions, labels: &Labels) {
+    let (sessions, summary) = read_sessions(file, options, false);
+
+    outln!("{}{}", CSV_HEADER, labels.header());
+    for session in &sessions.sessions {
+        outln!("{}{}", session.to_row(), labels.cell(Some(SlaveRef::Station(session.slave))));
+    }
+    summary.report();
+}
+
+/// Writes the files of the FoE transfers to `output_dir`, reassembled from their data packets
+/// without the repeated ones, and prints one row per file. Transfers that did not complete are
+/// written as far as they went, their status telling so.
+pub fn extract(file: File, options: &CaptureOptions, output_dir: &Path, labels: &Labels) {
+    let (sessions, summary) = read_sessions(file, options, true);
+
+    fs::create_dir_all(output_dir).unwrap_or_else(|e| {
+        eprintln!("Cannot create {} : {}", output_dir.display(), e);
+        exit(1);
+    });
+    outln!("{}{}", EXTRACT_CSV_HEADER, labels.header());
+    for session in &sessions.sessions {
+        let path = output_dir.join(extracted_name(session));
+        fs::write(&path, session.data.as_deref().unwrap_or_default()).unwrap_or_else(|e| {
+            eprintln!("Cannot write {} : {}", path.display(), e);
+            exit(1);
+        });
+        outln!("{},{},{},0x{:08x},{},{},{},{}{}",
+            Value::Address(session.slave).render(Style::Csv), quote(&session.filename), if session.write { "write" } else { "read" },
+            session.password, session.bytes, quote(&session.status.to_string()), session.transaction,
+            quote(&path.to_string_lossy()), labels.cell(Some(SlaveRef::Station(session.slave))));
+    }
+    summary.report();
+}
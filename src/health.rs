@@ -0,0 +1,91 @@
+use crate::capture::Summary;
+use crate::value::{Style, Value};
+use serde::Serialize;
+use std::fs;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+/// Machine-readable state of the export of a capture file, for supervisory software polling
+/// a file while it is read.
+#[derive(Serialize)]
+struct Health {
+    /// Wall-clock time of this snapshot, in milliseconds since the epoch.
+    updated_unix_ms: u64,
+    /// The export has reached the end of the capture.
+    finished: bool,
+    packets: u64,
+    last_frame: u64,
+    /// Capture time of the last packet read, in seconds since the epoch.
+    last_capture_time: Option<String>,
+    /// The same, in nanoseconds since the epoch.
+    last_capture_time_ns: Option<u64>,
+    blocks: u64,
+    events: u64,
+    capture_drops: u64,
+    corrupt_regions: u64,
+}
+
+/// Rewrites the health file (`--stats-out`) whenever `--stats-interval` has elapsed.
+pub struct HealthWriter {
+    path: String,
+    interval: Duration,
+    last_write: Option<Instant>,
+    packets: u64,
+    last_frame: u64,
+    last_ts_ns: Option<u64>,
+}
+
+impl HealthWriter {
+    pub fn new(path: String, interval_ns: u64) -> Self {
+        HealthWriter {
+            path,
+            interval: Duration::from_nanos(interval_ns),
+            last_write: None,
+            packets: 0,
+            last_frame: 0,
+            last_ts_ns: None,
+        }
+    }
+
+    /// Counts a packet and rewrites the file if it is due.
+    pub fn packet(&mut self, frame_number: u64, ts_ns: u64, summary: &Summary) -> Result<(), String> {
+        self.packets += 1;
+        self.last_frame = frame_number;
+        self.last_ts_ns = Some(ts_ns);
+        if self.last_write.is_none_or(|last| last.elapsed() >= self.interval) {
+            self.write(summary, false)?;
+        }
+        Ok(())
+    }
+
+    /// Writes the final snapshot.
+    pub fn finish(&mut self, summary: &Summary) -> Result<(), String> {
+        self.write(summary, true)
+    }
+
+    fn write(&mut self, summary: &Summary, finished: bool) -> Result<(), String> {
+        let health = Health {
+            updated_unix_ms: SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |d| d.as_millis() as u64),
+            finished,
+            packets: self.packets,
+            last_frame: self.last_frame,
+            last_capture_time: self.last_ts_ns.map(|ns| Value::Time(ns).render(Style::Csv)),
+            last_capture_time_ns: self.last_ts_ns,
+            blocks: summary.num_blocks,
+            events: summary.num_events,
+            capture_drops: summary.capture_drops.total(),
+            corrupt_regions: summary.corrupt_regions,
+        };
+        let json = serde_json::to_string_pretty(&health).map_err(|e| e.to_string())?;
+        // a failing write is retried at the next interval, not at every packet.
+        self.last_write = Some(Instant::now());
+        replace_file(&self.path, &(json + "\n"))
+    }
+}
+
+/// Replaces the content of a file at once, so that readers never see it half-written.
+pub fn replace_file(path: &str, contents: &str) -> Result<(), String> {
+    let tmp_path = format!("{}.tmp", path);
+    fs::write(&tmp_path, contents)
+        .and_then(|_| fs::rename(&tmp_path, path))
+        .map_err(|e| format!("Cannot write {} : {}", path, e))
+}
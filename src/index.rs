@@ -0,0 +1,100 @@
+use crate::capture::{self, CaptureOptions, Item, ReaderState};
+use crate::clock::TimeSpec;
+use crate::filter::FrameRange;
+use crate::health::replace_file;
+use crate::sink::outln;
+use serde::{Deserialize, Serialize};
+use std::fs::{self, File};
+use std::ops::ControlFlow;
+use std::process::exit;
+
+/// A packet of the capture and the state of the reader right after it, from which reading
+/// resumes at the next packet.
+#[derive(Serialize, Deserialize)]
+struct Entry {
+    ts_ns: u64,
+    reader: ReaderState,
+}
+
+/// Sidecar index of a capture, written by the index subcommand: the reader state after every
+/// few packets, by frame number and capture time, so that an export of a window of a large
+/// capture starts reading close to it instead of at the start.
+#[derive(Serialize, Deserialize)]
+pub struct CaptureIndex {
+    /// Lengths of the files of the indexed capture, which tell an index written before the
+    /// capture changed.
+    capture_lens: Vec<u64>,
+    /// Timestamp of the first packet, which relative times count from.
+    first_ts_ns: Option<u64>,
+    entries: Vec<Entry>,
+}
+
+/// The index file of a capture unless `--index` names one.
+pub fn default_path(capture: &str) -> String {
+    format!("{}.idx", capture)
+}
+
+impl CaptureIndex {
+    pub fn load(path: &str) -> Result<Self, String> {
+        let text = fs::read_to_string(path).map_err(|e| format!("Cannot read {} : {}", path, e))?;
+        serde_json::from_str(&text).map_err(|e| format!("Invalid index {} : {}", path, e))
+    }
+
+    /// Whether the index was written for the capture as it is now, in files reading can seek in.
+    pub fn matches(&self, files: &[String]) -> bool {
+        files.len() == self.capture_lens.len() && files.iter().zip(&self.capture_lens).all(|(path, len)| {
+            fs::metadata(path).is_ok_and(|metadata| metadata.is_file() && metadata.len() == *len)
+        })
+    }
+
+    pub fn first_ts_ns(&self) -> Option<u64> {
+        self.first_ts_ns
+    }
+
+    /// The state to read from so that no packet of `frames` or from `from` on is missed: the
+    /// last entry before both, or `None` when reading has to start at the beginning.
+    pub fn start(&self, frames: Option<FrameRange>, from: Option<TimeSpec>) -> Option<ReaderState> {
+        let first_ts_ns = self.first_ts_ns?;
+        let before = |entry: &&Entry| {
+            frames.is_none_or(|frames| entry.reader.frame_number() < frames.first)
+                && from.is_none_or(|from| entry.ts_ns < from.resolve(first_ts_ns))
+        };
+        self.entries.iter().take_while(before).last().map(|entry| entry.reader.clone())
+    }
+}
+
+/// Reads the whole capture, of the given files, and writes its index to `path`, with an entry
+/// every `every` packets.
+pub fn run(file: File, files: &[String], options: &CaptureOptions, path: &str, every: u64) {
+    let capture_lens = files.iter().map(|path| match fs::metadata(path) {
+        Ok(metadata) if metadata.is_file() && !capture::is_compressed(path) => metadata.len(),
+        _ => {
+            eprintln!("The index subcommand needs uncompressed capture files, which reading can seek in");
+            exit(1);
+        }
+    }).collect();
+    let mut index = CaptureIndex { capture_lens, first_ts_ns: None, entries: Vec::new() };
+    let result = capture::read_from(file, options, None, |item, state| {
+        if let Item::Packet(packet) = item {
+            index.first_ts_ns.get_or_insert(packet.info.ts_ns);
+            if packet.info.frame_number % every == 0 {
+                index.entries.push(Entry { ts_ns: packet.info.ts_ns, reader: state.clone() });
+            }
+        }
+        ControlFlow::Continue(())
+    });
+    let state = result.unwrap_or_else(|e| {
+        eprintln!("{}", e);
+        exit(1);
+    });
+    let json = serde_json::to_string(&index).unwrap_or_else(|e| {
+        eprintln!("{}", e);
+        exit(1);
+    });
+    if let Err(e) = replace_file(path, &json) {
+        eprintln!("{}", e);
+        exit(1);
+    }
+    outln!("{} frames, {} entries written to {}", state.frame_number(), index.entries.len(), path);
+    state.summary.report();
+}
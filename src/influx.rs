@@ -0,0 +1,65 @@
+use crate::capture::CaptureOptions;
+use crate::filter::{self, Selection};
+use crate::labels::{addressed_slave, Labels};
+use crate::pair::Key;
+use crate::sink::outln;
+use crate::value::{Style, Value};
+use crate::warnings::WarningLog;
+use crate::wkc::WkcModel;
+use std::collections::HashMap;
+use std::fs::File;
+use std::process::exit;
+
+const MEASUREMENT: &str = "ecat_datagram";
+
+/// Escapes a tag value of the line protocol: commas, spaces and equal signs.
+fn tag(value: &str) -> String {
+    value.replace('\\', "\\\\").replace(',', "\\,").replace(' ', "\\ ").replace('=', "\\=")
+}
+
+/// Exports one line protocol record per datagram, for Telegraf or `influx write`: the
+/// `ecat_datagram` measurement tagged with the command, ADP, ADO, direction and the slave
+/// addressed, with the index, length and working counter as fields and, on the returned
+/// datagrams, the latency since their request was captured. Records carry the capture time,
+/// in nanoseconds since the epoch. Only the datagrams of the selection are exported.
+pub fn run(
+    file: File,
+    options: &CaptureOptions,
+    selection: &Selection,
+    warnings: Option<WarningLog>,
+    wkc_model: Option<&WkcModel>,
+    labels: &Labels,
+) {
+    let mut requests: HashMap<Key, u64> = HashMap::new();
+
+    let summary = filter::read_datagrams(file, options, selection, warnings, wkc_model, |info, frame, datagrams| {
+        let ts_ns = info.ts_ns;
+        let returned = frame.is_returned();
+        for datagram in datagrams {
+            let header = &datagram.header;
+            let mut tags = format!("{},cmd={},adp={},ado={},direction={}",
+                MEASUREMENT, header.cmd, Value::Address(header.slave_addr).render(Style::Csv),
+                Value::Address(header.offset_addr).render(Style::Csv), if returned { "returned" } else { "out" });
+            let slave = addressed_slave(header, returned);
+            if let Some(slave) = slave {
+                tags += &format!(",slave={}", tag(&slave.to_string()));
+            }
+            if let Some(label) = slave.and_then(|slave| labels.name(slave)) {
+                tags += &format!(",label={}", tag(label));
+            }
+            let mut fields = format!("index={}i,length={}i,wkc={}i", header.index, header.length, datagram.wkc);
+            let key = Key::new(frame.master_mac(), header);
+            if !returned {
+                requests.insert(key, ts_ns);
+            } else if let Some(request_ns) = requests.remove(&key) {
+                fields += &format!(",latency_ns={}i", ts_ns.saturating_sub(request_ns));
+            }
+            outln!("{} {} {}", tags, fields, ts_ns);
+        }
+    });
+    let summary = summary.unwrap_or_else(|e| {
+        eprintln!("{}", e);
+        exit(1);
+    });
+    summary.report();
+}
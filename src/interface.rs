@@ -0,0 +1,116 @@
+use ecat_packet_analyzer_rs::reader;
+use pcap_parser::*;
+use serde::{Deserialize, Serialize};
+
+/// Flag of the link type field of a classic pcap header telling that the FCS length in its top
+/// three bits, in 16-bit words, is valid.
+const PCAP_FCS_PRESENT: u32 = 0x1000_0000;
+
+/// `if_name` option of the interface description block.
+const IF_NAME: u16 = 2;
+/// `isb_ifdrop` option of the interface statistics block: packets dropped by the interface.
+const ISB_IFDROP: u16 = 5;
+/// `isb_osdrop` option of the interface statistics block: packets dropped by the OS.
+const ISB_OSDROP: u16 = 7;
+
+/// Packets the capture host failed to record, as counted by the interface statistics blocks.
+/// These are losses of the capture itself, not frames lost on the EtherCAT bus.
+#[derive(Default, Clone, Copy, Serialize, Deserialize)]
+pub struct DropCounters {
+    /// Dropped by the network interface (`isb_ifdrop`).
+    pub ifdrop: u64,
+    /// Dropped by the operating system or capture software (`isb_osdrop`).
+    pub osdrop: u64,
+}
+
+impl DropCounters {
+    pub fn total(&self) -> u64 {
+        self.ifdrop + self.osdrop
+    }
+}
+
+impl std::ops::AddAssign for DropCounters {
+    fn add_assign(&mut self, other: Self) {
+        self.ifdrop += other.ifdrop;
+        self.osdrop += other.osdrop;
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(remote = "Linktype")]
+struct LinktypeDef(i32);
+
+/// What the exporter keeps about a capture interface of the current pcapng section.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct Interface {
+    #[serde(with = "LinktypeDef")]
+    pub linktype: Linktype,
+    ts_resolution: u64,
+    ts_offset: i64,
+    /// Length of the FCS kept at the end of every packet (`if_fcslen`).
+    #[serde(default)]
+    pub fcs_len: Option<u8>,
+    /// `if_name`, the name of the interface on the capture host.
+    #[serde(default)]
+    pub name: Option<String>,
+    /// Drop counters of the latest interface statistics block. Both are cumulative.
+    drops: DropCounters,
+}
+
+impl Interface {
+    pub fn from_idb(idb: &InterfaceDescriptionBlock) -> Self {
+        Interface {
+            linktype: idb.linktype,
+            ts_resolution: reader::interface_ts_resolution(idb),
+            ts_offset: idb.ts_offset(),
+            fcs_len: crate::link::interface_fcs_len(idb),
+            name: idb.options.iter().find(|option| option.code.0 == IF_NAME)
+                .and_then(|option| option.as_bytes())
+                .map(|name| String::from_utf8_lossy(name).trim_end_matches('\0').to_string()),
+            drops: DropCounters::default(),
+        }
+    }
+
+    /// The single interface of a classic pcap file.
+    pub fn from_pcap_header(header: &PcapHeader) -> Self {
+        let network = header.network.0 as u32;
+        Interface {
+            linktype: Linktype((network & 0x0FFF_FFFF) as i32),
+            ts_resolution: reader::pcap_ts_resolution(header),
+            ts_offset: 0,
+            fcs_len: (network & PCAP_FCS_PRESENT != 0).then_some((network >> 29) as u8 * 2),
+            name: None,
+            drops: DropCounters::default(),
+        }
+    }
+
+    /// Converts a raw packet timestamp of this interface to nanoseconds since the epoch.
+    pub fn timestamp_ns(&self, ts_high: u32, ts_low: u32) -> u64 {
+        reader::timestamp_ns(ts_high, ts_low, self.ts_resolution, self.ts_offset)
+    }
+
+    /// Converts the seconds and fraction timestamp of a classic pcap packet to nanoseconds
+    /// since the epoch.
+    pub fn pcap_timestamp_ns(&self, ts_sec: u32, ts_fraction: u32) -> u64 {
+        reader::pcap_timestamp_ns(ts_sec, ts_fraction, self.ts_resolution)
+    }
+
+    /// Takes the drop counters of a statistics block for this interface and returns how many
+    /// packets were dropped since the previous statistics block.
+    pub fn update_drops(&mut self, isb: &InterfaceStatisticsBlock) -> DropCounters {
+        let counter = |code| {
+            isb.options
+                .iter()
+                .find(|option| option.code.0 == code)
+                .and_then(|option| option.as_u64_le())
+        };
+        let previous = self.drops;
+        // a counter missing from this block keeps its last known value.
+        self.drops.ifdrop = counter(ISB_IFDROP).unwrap_or(self.drops.ifdrop);
+        self.drops.osdrop = counter(ISB_OSDROP).unwrap_or(self.drops.osdrop);
+        DropCounters {
+            ifdrop: self.drops.ifdrop.saturating_sub(previous.ifdrop),
+            osdrop: self.drops.osdrop.saturating_sub(previous.osdrop),
+        }
+    }
+}
@@ -0,0 +1,79 @@
+use crate::events::EventKind;
+use crate::EtherNetFrame;
+use serde::{Deserialize, Serialize};
+
+/// Bits of the ECAT event request register (0x0210), which every slave ORs into the IRQ field
+/// of the datagrams passing it, masked by its ECAT event mask (0x0200).
+const EVENT_BITS: [&str; 16] = [
+    "dc_latch", "bit1", "dl_status_change", "al_status_change", "sm0", "sm1", "sm2", "sm3",
+    "sm4", "sm5", "sm6", "sm7", "bit12", "bit13", "bit14", "bit15",
+];
+
+/// How long an IRQ bit has been asserted without a break.
+#[derive(Clone, Copy, Serialize, Deserialize)]
+struct Run {
+    since_frame: u64,
+    /// Returned frames with the bit set.
+    frames: u64,
+}
+
+/// Watches the IRQ field of returned frames for event bits that stay asserted cycle after
+/// cycle, which usually means the master never serves the event.
+#[derive(Clone, Default, Serialize, Deserialize)]
+pub struct IrqMonitor {
+    /// Consecutive returned frames a bit must be set in to be reported, 0 to never report.
+    #[serde(skip)]
+    threshold: u64,
+    runs: [Option<Run>; 16],
+}
+
+fn names(bits: &[usize]) -> Vec<&'static str> {
+    bits.iter().map(|&bit| EVENT_BITS[bit]).collect()
+}
+
+/// The names of the event bits set in an IRQ field, lowest bit first.
+pub fn flags(irq: u16) -> Vec<&'static str> {
+    (0..16).filter(|bit| irq & (1 << bit) != 0).map(|bit| EVENT_BITS[bit]).collect()
+}
+
+impl IrqMonitor {
+    /// Changes the threshold, keeping the runs of a monitor restored from a checkpoint.
+    pub fn set_threshold(&mut self, threshold: u64) {
+        self.threshold = threshold;
+    }
+
+    /// Takes the next packet, returning the storms it starts or ends.
+    pub fn check(&mut self, frame_number: u64, frame: &EtherNetFrame) -> Vec<EventKind> {
+        if self.threshold == 0 || !frame.is_returned() {
+            return Vec::new();
+        }
+        let irq = frame.ecat_frame.datagrams.iter().fold(0, |irq, datagram| irq | datagram.header.irq);
+        let mut started = Vec::new();
+        let mut ended = Vec::new();
+        let mut ended_frames = 0;
+        for (bit, run) in self.runs.iter_mut().enumerate() {
+            if irq & (1 << bit) == 0 {
+                if let Some(run) = run.take().filter(|run| run.frames >= self.threshold) {
+                    ended.push(bit);
+                    ended_frames = ended_frames.max(run.frames);
+                }
+                continue;
+            }
+            let run = run.get_or_insert(Run { since_frame: frame_number, frames: 0 });
+            run.frames += 1;
+            if run.frames == self.threshold {
+                started.push((bit, run.since_frame));
+            }
+        }
+
+        let mut events = Vec::new();
+        if !ended.is_empty() {
+            events.push(EventKind::IrqStormEnded { bits: names(&ended), frames: ended_frames });
+        }
+        if let Some(since_frame) = started.iter().map(|&(_, since)| since).min() {
+            let bits: Vec<usize> = started.iter().map(|&(bit, _)| bit).collect();
+            events.push(EventKind::IrqStorm { bits: names(&bits), since_frame, frames: self.threshold });
+        }
+        events
+    }
+}
@@ -0,0 +1,109 @@
+use crate::annotations::quote;
+use crate::slave_config::SlaveRef;
+use crate::{EtherCATCommand, EtherCATDatagramHeader};
+use serde::Deserialize;
+use std::collections::BTreeMap;
+use std::fs;
+
+/// Human names of the slaves, loaded from a TOML file given with `--labels`:
+///
+/// ```toml
+/// [[slave]]
+/// address = 0x1001
+/// name = "Axis X drive"
+///
+/// # a slave with a station alias is addressed by the alias
+/// [[slave]]
+/// alias = 7
+/// name = "IO block 3"
+///
+/// # a slave only addressed by its position in the chain, counted from 0
+/// [[slave]]
+/// position = 2
+/// name = "Coupler"
+/// ```
+///
+/// Every output listing slaves adds a `label` column next to the address once labels are given.
+#[derive(Clone, Default)]
+pub struct Labels {
+    names: BTreeMap<SlaveRef, String>,
+    loaded: bool,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct LabelsFile {
+    #[serde(default, rename = "slave")]
+    slaves: Vec<SlaveLabel>,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct SlaveLabel {
+    address: Option<u16>,
+    alias: Option<u16>,
+    position: Option<u16>,
+    name: String,
+}
+
+impl Labels {
+    pub fn load(path: &str) -> Result<Self, String> {
+        let text = fs::read_to_string(path).map_err(|e| format!("Cannot read {} : {}", path, e))?;
+        let file: LabelsFile = toml::from_str(&text).map_err(|e| format!("Invalid labels {} : {}", path, e))?;
+        let mut names = BTreeMap::new();
+        for label in file.slaves {
+            let slave = match (label.address, label.alias, label.position) {
+                (Some(address), None, None) | (None, Some(address), None) => SlaveRef::Station(address),
+                (None, None, Some(position)) => SlaveRef::Position(position),
+                _ => {
+                    return Err(format!("Invalid labels {} : '{}' needs exactly one of address, alias or position",
+                        path, label.name));
+                }
+            };
+            names.insert(slave, label.name);
+        }
+        Ok(Labels { names, loaded: true })
+    }
+
+    pub fn name(&self, slave: SlaveRef) -> Option<&str> {
+        self.names.get(&slave).map(String::as_str)
+    }
+
+    /// The slave as shown in text output: its label followed by its address, or just the address.
+    pub fn display(&self, slave: SlaveRef) -> String {
+        match self.name(slave) {
+            Some(name) => format!("{} ({})", name, slave),
+            None => slave.to_string(),
+        }
+    }
+
+    /// The header of the `label` column, empty without `--labels`.
+    pub fn header(&self) -> &'static str {
+        if self.loaded {
+            ",label"
+        } else {
+            ""
+        }
+    }
+
+    /// The `label` cell of a slave, with its leading comma, empty without `--labels`.
+    pub fn cell(&self, slave: Option<SlaveRef>) -> String {
+        match slave.and_then(|slave| self.name(slave)) {
+            Some(name) => format!(",{}", quote(name)),
+            None if self.loaded => ",".to_string(),
+            None => String::new(),
+        }
+    }
+}
+
+/// The slave a datagram addresses, if it addresses a single one. Position addresses are only
+/// known before the first slave increments them, on the way out.
+pub fn addressed_slave(header: &EtherCATDatagramHeader, returned: bool) -> Option<SlaveRef> {
+    use EtherCATCommand::*;
+
+    match header.cmd {
+        FPRD | FPWR | FPRW | FRMW => Some(SlaveRef::Station(header.slave_addr)),
+        APRD | APWR | APRW | ARMW if !returned => Some(SlaveRef::Position(header.slave_addr.wrapping_neg())),
+        _ => None,
+    }
+}
@@ -0,0 +1,205 @@
+use crate::capture::{self, CaptureOptions, Item};
+use crate::labels::Labels;
+use crate::registers::STATION_ADDRESS_REGISTER;
+use crate::sink::outln;
+use crate::slave_config::SlaveRef;
+use crate::{EtherCATCommand, EtherCATDatagram, EtherNetFrame};
+use std::collections::{BTreeMap, HashMap};
+use std::fs::File;
+use std::ops::ControlFlow;
+use std::process::exit;
+
+/// Receive time of port 0, followed by those of ports 1 to 3. A write latches the local time
+/// at which the frame of the write passes each port of every slave it reaches.
+const RECEIVE_TIME_REGISTER: u16 = 0x0900;
+/// Slaves whose mean delay is this many times the median of the others are reported as outliers.
+const OUTLIER_FACTOR: f64 = 2.0;
+
+const CSV_HEADER: &str = "position,slave,loop_ns,delay_ns,max_delay_ns,samples,outlier";
+
+/// A slave answering a read of its receive times.
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+struct Slave {
+    /// Position in the chain, if known from the addressing.
+    position: Option<u16>,
+    station: Option<u16>,
+}
+
+impl Slave {
+    fn reference(self) -> SlaveRef {
+        match (self.station, self.position) {
+            (Some(station), _) => SlaveRef::Station(station),
+            (None, position) => SlaveRef::Position(position.unwrap_or_default()),
+        }
+    }
+
+    /// The reference a label is given for: the station address, else the position.
+    fn labelled(self, labels: &Labels) -> Option<SlaveRef> {
+        let references = [self.station.map(SlaveRef::Station), self.position.map(SlaveRef::Position)];
+        references.into_iter().flatten().find(|reference| labels.name(*reference).is_some())
+    }
+
+    /// Order along the chain: by position, slaves of unknown position after the others.
+    fn chain_order(&self) -> (u16, Option<u16>) {
+        (self.position.unwrap_or(u16::MAX), self.station)
+    }
+}
+
+/// The receive times latched by one write, as the slaves reported them.
+#[derive(Default)]
+struct Latch {
+    /// Capture time of the latching frame from the master back to the master.
+    round_trip_ns: Option<u64>,
+    /// Port 0 and port 1 receive times of every slave, in its local clock.
+    ports: BTreeMap<Slave, (u32, u32)>,
+}
+
+/// Forwarding delays of one slave over every latch.
+#[derive(Default)]
+struct Delays {
+    loop_sum_ns: i64,
+    loops: u64,
+    delay_sum_ns: i64,
+    max_delay_ns: i64,
+    delays: u64,
+}
+
+impl Delays {
+    fn mean_delay_ns(&self) -> Option<f64> {
+        (self.delays > 0).then(|| self.delay_sum_ns as f64 / self.delays as f64)
+    }
+}
+
+/// Splits the round trip of a latch among the slaves of the chain. The time a slave holds the
+/// frame before it returns through port 0 is the loop of its port 1 (port 1 receive time minus
+/// port 0 receive time), 0 for the last slave. What a slave adds to the loop of the slave in front
+/// of it, the cable to it and back plus its own forwarding, is its delay. The delay of the first
+/// slave is what it adds to the round trip seen by the master, which includes the master's link.
+fn attribute(latch: &Latch, delays: &mut BTreeMap<Slave, Delays>) {
+    let mut slaves: Vec<(&Slave, &(u32, u32))> = latch.ports.iter().collect();
+    slaves.sort_by_key(|(slave, _)| slave.chain_order());
+    let last = slaves.len().saturating_sub(1);
+    let loops: Vec<i64> = slaves
+        .iter()
+        .enumerate()
+        .map(|(i, (_, (port0, port1)))| if i == last { 0 } else { port1.wrapping_sub(*port0) as i64 })
+        .collect();
+    for (i, (slave, _)) in slaves.iter().enumerate() {
+        let in_front = match i {
+            0 => latch.round_trip_ns.map(|ns| ns as i64),
+            _ => Some(loops[i - 1]),
+        };
+        let entry = delays.entry(**slave).or_default();
+        entry.loop_sum_ns += loops[i];
+        entry.loops += 1;
+        if let Some(in_front) = in_front {
+            let delay = in_front - loops[i];
+            entry.delay_sum_ns += delay;
+            entry.max_delay_ns = if entry.delays == 0 { delay } else { entry.max_delay_ns.max(delay) };
+            entry.delays += 1;
+        }
+    }
+}
+
+/// Port 0 and port 1 receive times read by a returned datagram.
+fn receive_times(datagram: &EtherCATDatagram) -> Option<(u32, u32)> {
+    let header = &datagram.header;
+    if datagram.wkc == 0 || header.offset_addr != RECEIVE_TIME_REGISTER || header.length < 8 {
+        return None;
+    }
+    let times = datagram.data.get(..8)?;
+    Some((u32::from_le_bytes(times[..4].try_into().ok()?), u32::from_le_bytes(times[4..].try_into().ok()?)))
+}
+
+/// Prints the part of the round trip each slave adds, estimated from the port receive times the
+/// master latches and reads back, usually while it measures the DC propagation delays.
+pub fn run(file: File, options: &CaptureOptions, labels: &Labels) {
+    use EtherCATCommand::*;
+
+    let mut positions: HashMap<u16, u16> = HashMap::new();
+    // outgoing datagrams by index: capture time of a latch, position of an auto-increment read.
+    let mut latches_sent: HashMap<u8, u64> = HashMap::new();
+    let mut reads_sent: HashMap<u8, u16> = HashMap::new();
+    let mut latch: Option<Latch> = None;
+    let mut delays: BTreeMap<Slave, Delays> = BTreeMap::new();
+
+    let summary = capture::read(file, options, |item| {
+        let Item::Packet(packet) = item else {
+            return ControlFlow::Continue(());
+        };
+        let Some(frame) = packet.data.and_then(EtherNetFrame::parse_ethercat) else {
+            return ControlFlow::Continue(());
+        };
+        let returned = frame.is_returned();
+        for datagram in &frame.ecat_frame.datagrams {
+            let header = &datagram.header;
+            let writes_latch = matches!(header.cmd, BWR | BRW | APWR | FPWR) && header.offset_addr == RECEIVE_TIME_REGISTER;
+            if !returned {
+                match header.cmd {
+                    APWR | APRW if header.offset_addr == STATION_ADDRESS_REGISTER && header.length >= 2 => {
+                        if let Some(station) = datagram.data.get(..2) {
+                            positions.insert(u16::from_le_bytes([station[0], station[1]]), header.slave_addr.wrapping_neg());
+                        }
+                    }
+                    APRD if header.offset_addr == RECEIVE_TIME_REGISTER => {
+                        reads_sent.insert(header.index, header.slave_addr.wrapping_neg());
+                    }
+                    _ if writes_latch => {
+                        latches_sent.insert(header.index, packet.info.ts_ns);
+                    }
+                    _ => {}
+                }
+                continue;
+            }
+            if writes_latch && datagram.wkc > 0 {
+                if let Some(done) = latch.take() {
+                    attribute(&done, &mut delays);
+                }
+                let sent = latches_sent.remove(&header.index);
+                latch = Some(Latch {
+                    round_trip_ns: sent.and_then(|sent| packet.info.ts_ns.checked_sub(sent)),
+                    ports: BTreeMap::new(),
+                });
+                continue;
+            }
+            let slave = match header.cmd {
+                FPRD => Slave { position: positions.get(&header.slave_addr).copied(), station: Some(header.slave_addr) },
+                APRD => match reads_sent.remove(&header.index) {
+                    Some(position) => Slave { position: Some(position), station: None },
+                    None => continue,
+                },
+                _ => continue,
+            };
+            if let (Some(latch), Some(times)) = (latch.as_mut(), receive_times(datagram)) {
+                latch.ports.insert(slave, times);
+            }
+        }
+        ControlFlow::Continue(())
+    });
+    let summary = summary.unwrap_or_else(|e| {
+        eprintln!("{}", e);
+        exit(1);
+    });
+    if let Some(done) = latch.take() {
+        attribute(&done, &mut delays);
+    }
+
+    let mut slaves: Vec<(&Slave, &Delays)> = delays.iter().collect();
+    slaves.sort_by_key(|(slave, _)| slave.chain_order());
+    // the first slave carries the master's link, which is left out of the median.
+    let mut means: Vec<f64> = slaves.iter().skip(1).filter_map(|(_, d)| d.mean_delay_ns()).collect();
+    means.sort_by(f64::total_cmp);
+    let median = means.get(means.len() / 2).copied();
+
+    outln!("{}{}", CSV_HEADER, labels.header());
+    for (slave, delays) in slaves {
+        let mean = delays.mean_delay_ns();
+        let outlier = mean.zip(median).is_some_and(|(mean, median)| median > 0.0 && mean > OUTLIER_FACTOR * median);
+        outln!("{},{},{},{},{},{},{}{}",
+            slave.position.map(|p| p.to_string()).unwrap_or_default(), slave.reference(),
+            (delays.loop_sum_ns as f64 / delays.loops as f64).round(), mean.map(|m| m.round().to_string()).unwrap_or_default(),
+            if delays.delays > 0 { delays.max_delay_ns.to_string() } else { String::new() }, delays.delays, outlier,
+            labels.cell(slave.labelled(labels)));
+    }
+    summary.report();
+}
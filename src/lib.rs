@@ -0,0 +1,21 @@
+//! The EtherCAT dissector of the exporter, for tools of their own to decode captured frames
+//! with instead of running the binary.
+//!
+//! [`parse_frame`] decodes the EtherCAT frame carried by an Ethernet frame, directly or over
+//! UDP; the types of [`parser::ethernet`] and [`parser::ecat`] give access to the Ethernet
+//! header and to the header, payload and working counter of every datagram, [`parser::netvars`]
+//! the network variables of the frames of that type, [`parser::mailbox`] the header of the
+//! mailbox messages some of them carry and the error replies of the slaves, [`parser::coe`] the
+//! SDO transfers and emergencies of the CoE messages, [`parser::foe`] the file transfers of the
+//! FoE messages and [`parser::eoe`] the fragments of the Ethernet frames tunnelled by the EoE
+//! messages. [`EcatPcapReader`] reads the datagrams of a pcap or pcapng capture one at a time,
+//! with the number and capture time of their frame, and [`EcatStream`] streams them from an
+//! asynchronous input.
+
+pub mod parser;
+pub mod reader;
+pub mod stream;
+
+pub use parser::{parse_frame, ParseError};
+pub use reader::{DatagramRecord, EcatPcapReader, ReadError};
+pub use stream::EcatStream;
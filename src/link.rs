@@ -0,0 +1,104 @@
+use pcap_parser::{EnhancedPacketBlock, InterfaceDescriptionBlock, LegacyPcapBlock, PcapNGOption};
+
+/// `epb_flags` option of the enhanced packet block.
+const EPB_FLAGS: u16 = 2;
+/// `if_fcslen` option of the interface description block.
+const IF_FCSLEN: u16 = 13;
+/// Bits 5-8 of `epb_flags`: length of the FCS at the end of the packet, 0 if unknown.
+const FCS_LEN_SHIFT: u32 = 5;
+const FCS_LEN_MASK: u32 = 0xF;
+const ETHERNET_FCS_LEN: usize = 4;
+
+/// Link-layer error bits 24-31 of `epb_flags` and the row warning reported for each.
+const ERROR_BITS: [(u32, &str); 8] = [
+    (1 << 31, "link_symbol_error"),
+    (1 << 30, "link_preamble_error"),
+    (1 << 29, "link_sfd_error"),
+    (1 << 28, "link_unaligned_frame"),
+    (1 << 27, "link_wrong_ifg"),
+    (1 << 26, "link_too_short"),
+    (1 << 25, "link_too_long"),
+    (1 << 24, "link_crc_error"),
+];
+
+/// Link-level errors of a packet: the error bits the capture device set in `epb_flags`, and
+/// an FCS that does not match the frame when the capture kept it.
+#[derive(Default, Clone, Copy, PartialEq, Eq)]
+pub struct LinkErrors {
+    flags: u32,
+    fcs_mismatch: bool,
+}
+
+impl LinkErrors {
+    pub fn is_empty(&self) -> bool {
+        self.flags == 0 && !self.fcs_mismatch
+    }
+
+    /// Warning codes, in the order of the `epb_flags` bits.
+    pub fn codes(&self) -> Vec<&'static str> {
+        let mut codes: Vec<&str> = ERROR_BITS.iter().filter(|(bit, _)| self.flags & bit != 0).map(|(_, code)| *code).collect();
+        if self.fcs_mismatch {
+            codes.push("fcs_mismatch");
+        }
+        codes
+    }
+}
+
+fn option_u32(options: &[PcapNGOption], code: u16, big_endian: bool) -> Option<u32> {
+    let option = options.iter().find(|option| option.code.0 == code)?;
+    let bytes: [u8; 4] = option.as_bytes()?.get(..4)?.try_into().ok()?;
+    Some(if big_endian { u32::from_be_bytes(bytes) } else { u32::from_le_bytes(bytes) })
+}
+
+/// The `epb_flags` of a packet, 0 when it has none.
+pub fn packet_flags(epb: &EnhancedPacketBlock, big_endian: bool) -> u32 {
+    option_u32(&epb.options, EPB_FLAGS, big_endian).unwrap_or(0)
+}
+
+/// Length of the FCS the interface keeps at the end of each packet (`if_fcslen`).
+pub fn interface_fcs_len(idb: &InterfaceDescriptionBlock) -> Option<u8> {
+    let option = idb.options.iter().find(|option| option.code.0 == IF_FCSLEN)?;
+    option.as_bytes()?.first().copied()
+}
+
+/// Ethernet CRC-32 (IEEE 802.3), as carried in the FCS.
+fn crc32(data: &[u8]) -> u32 {
+    let mut crc = 0xFFFF_FFFFu32;
+    for byte in data {
+        crc ^= *byte as u32;
+        for _ in 0..8 {
+            crc = if crc & 1 != 0 { (crc >> 1) ^ 0xEDB8_8320 } else { crc >> 1 };
+        }
+    }
+    !crc
+}
+
+/// Checks a packet for link-level errors and strips the FCS from its frame, when captured.
+pub fn check<'a>(epb: &EnhancedPacketBlock<'a>, frame: &'a [u8], interface_fcs_len: Option<u8>, big_endian: bool) -> (LinkErrors, &'a [u8]) {
+    let flags = packet_flags(epb, big_endian);
+    let mut errors = LinkErrors { flags: flags & 0xFF00_0000, fcs_mismatch: false };
+
+    let fcs_len = match (flags >> FCS_LEN_SHIFT) & FCS_LEN_MASK {
+        0 => interface_fcs_len.unwrap_or(0) as usize,
+        len => len as usize,
+    };
+    let frame = strip_fcs(&mut errors, frame, fcs_len, epb.caplen == epb.origlen);
+    (errors, frame)
+}
+
+/// Like [`check`] for a packet of a classic pcap file, which only has the FCS to check.
+pub fn check_legacy<'a>(packet: &LegacyPcapBlock<'a>, frame: &'a [u8], fcs_len: Option<u8>) -> (LinkErrors, &'a [u8]) {
+    let mut errors = LinkErrors::default();
+    let frame = strip_fcs(&mut errors, frame, fcs_len.unwrap_or(0) as usize, packet.caplen == packet.origlen);
+    (errors, frame)
+}
+
+fn strip_fcs<'a>(errors: &mut LinkErrors, frame: &'a [u8], fcs_len: usize, complete: bool) -> &'a [u8] {
+    // the FCS is only there if the whole frame was captured.
+    if fcs_len != ETHERNET_FCS_LEN || !complete || frame.len() < fcs_len {
+        return frame;
+    }
+    let (frame, fcs) = frame.split_at(frame.len() - fcs_len);
+    errors.fcs_mismatch = crc32(frame) != u32::from_le_bytes([fcs[0], fcs[1], fcs[2], fcs[3]]);
+    frame
+}
@@ -0,0 +1,178 @@
+use crate::capture::{self, CaptureOptions, Item};
+use crate::labels::Labels;
+use crate::registers::{register_byte, AL_CONTROL_REGISTER};
+use crate::sink::outln;
+use crate::slave_config::{SlaveConfigs, SlaveRef};
+use crate::{EtherCATCommand, EtherCATDatagram, EtherNetFrame};
+use std::collections::BTreeMap;
+use std::fs::File;
+use std::ops::ControlFlow;
+use std::process::exit;
+
+/// First SyncManager; each takes 8 bytes, the status at offset 5.
+const SM_BASE: u16 = 0x0800;
+const SM_STATUS_OFFSET: u16 = 5;
+/// Cyclic unit and SYNC0/SYNC1 activation.
+const DC_ACTIVATION_REGISTER: u16 = 0x0981;
+/// System time, which the master keeps distributing from the reference clock to compensate drift.
+const SYSTEM_TIME_REGISTER: u16 = 0x0910;
+/// AL state SAFE-OP, the first that exchanges process data through the SyncManagers.
+const SAFEOP: u8 = 4;
+
+const CSV_HEADER: &str = "frame,rule,slave,detail,reference";
+
+/// A rule the master is checked against.
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+enum Rule {
+    /// The master reads the status of a read mailbox SyncManager with physical datagrams,
+    /// instead of an FMMU mapping it into the cyclic logical datagrams.
+    MailboxPolledWithoutFmmu,
+    /// SAFE-OP or OP requested from a slave whose SyncManagers were never configured.
+    StateBeforeSmConfig,
+    /// Cyclic operation activated without the system time ever being distributed.
+    DcWithoutDriftCompensation,
+}
+
+impl Rule {
+    fn name(self) -> &'static str {
+        match self {
+            Rule::MailboxPolledWithoutFmmu => "mailbox-polled-without-fmmu",
+            Rule::StateBeforeSmConfig => "state-before-sm-config",
+            Rule::DcWithoutDriftCompensation => "dc-without-drift-compensation",
+        }
+    }
+
+    /// The specification stating the rule.
+    fn reference(self) -> &'static str {
+        match self {
+            Rule::MailboxPolledWithoutFmmu => "ETG.1500 (mailbox polling)",
+            Rule::StateBeforeSmConfig => "ETG.1000.6 (AL state machine)",
+            Rule::DcWithoutDriftCompensation => "ETG.1000.4 (distributed clocks)",
+        }
+    }
+}
+
+struct Violation {
+    frame_number: u64,
+    rule: Rule,
+    slave: Option<SlaveRef>,
+    detail: String,
+}
+
+/// The slave a physical datagram addresses, `None` for a broadcast.
+fn addressed_slave(datagram: &EtherCATDatagram) -> Option<SlaveRef> {
+    use EtherCATCommand::*;
+
+    let header = &datagram.header;
+    match header.cmd {
+        FPRD | FPWR | FPRW | FRMW => Some(SlaveRef::Station(header.slave_addr)),
+        APRD | APWR | APRW | ARMW => Some(SlaveRef::Position(header.slave_addr.wrapping_neg())),
+        _ => None,
+    }
+}
+
+/// The first byte a write to `register` sets, if the datagram writes it.
+fn written_byte(datagram: &EtherCATDatagram, register: u16) -> Option<u8> {
+    use EtherCATCommand::*;
+
+    let header = &datagram.header;
+    if !matches!(header.cmd, APWR | APRW | FPWR | FPRW | BWR | BRW) {
+        return None;
+    }
+    register_byte(datagram, register)
+}
+
+/// Checks the master against the rules, from the outgoing frames and the configuration they
+/// write, and prints a row per violation.
+pub fn run(file: File, options: &CaptureOptions, labels: &Labels) {
+    use EtherCATCommand::*;
+
+    let mut configs = SlaveConfigs::default();
+    let mut violations: Vec<Violation> = Vec::new();
+    // per slave: frame of the first poll, number of polls and the status register polled.
+    let mut polls: BTreeMap<SlaveRef, (u64, u64, u16)> = BTreeMap::new();
+    let mut activations: Vec<(u64, Option<SlaveRef>)> = Vec::new();
+    let mut drift_compensated = false;
+
+    let summary = capture::read(file, options, |item| {
+        let Item::Packet(packet) = item else {
+            return ControlFlow::Continue(());
+        };
+        let Some(frame) = packet.data.and_then(EtherNetFrame::parse_ethercat) else {
+            return ControlFlow::Continue(());
+        };
+        let returned = frame.is_returned();
+        let frame_number = packet.info.frame_number;
+        for datagram in &frame.ecat_frame.datagrams {
+            configs.apply(datagram, returned);
+            if returned {
+                continue;
+            }
+            let header = &datagram.header;
+            let slave = addressed_slave(datagram);
+            if matches!(header.cmd, ARMW | FRMW) && header.offset_addr == SYSTEM_TIME_REGISTER {
+                drift_compensated = true;
+            }
+            if let Some(activation) = written_byte(datagram, DC_ACTIVATION_REGISTER) {
+                if activation & 0x1 != 0 {
+                    activations.push((frame_number, slave));
+                }
+            }
+            if let (Some(state), Some(slave)) = (written_byte(datagram, AL_CONTROL_REGISTER), slave) {
+                if state & 0x0F >= SAFEOP && configs.sync_managers(slave).is_none() {
+                    violations.push(Violation {
+                        frame_number,
+                        rule: Rule::StateBeforeSmConfig,
+                        slave: Some(slave),
+                        detail: format!("AL control 0x{:02x} written before any SyncManager", state),
+                    });
+                }
+            }
+            if let (APRD | FPRD, Some(slave)) = (header.cmd, slave) {
+                let read = header.offset_addr..header.offset_addr.saturating_add(header.length);
+                let sync_managers = configs.sync_managers(slave).unwrap_or_default();
+                let polled = sync_managers.iter().find_map(|sm| {
+                    let status = SM_BASE + sm.index as u16 * 8 + SM_STATUS_OFFSET;
+                    (sm.mailbox && !sm.master_writes && read.contains(&status)).then_some(status)
+                });
+                let mapped = |status: u16| configs.fmmus().any(|(s, fmmu)| s == slave && fmmu.physical_start == status);
+                if let Some(status) = polled.filter(|status| !mapped(*status)) {
+                    polls.entry(slave).or_insert((frame_number, 0, status)).1 += 1;
+                }
+            }
+        }
+        ControlFlow::Continue(())
+    });
+    let summary = summary.unwrap_or_else(|e| {
+        eprintln!("{}", e);
+        exit(1);
+    });
+
+    for (slave, (frame_number, count, status)) in polls {
+        violations.push(Violation {
+            frame_number,
+            rule: Rule::MailboxPolledWithoutFmmu,
+            slave: Some(slave),
+            detail: format!("SyncManager status 0x{:04x} read {} times by physical datagrams", status, count),
+        });
+    }
+    if !drift_compensated {
+        for (frame_number, slave) in activations {
+            violations.push(Violation {
+                frame_number,
+                rule: Rule::DcWithoutDriftCompensation,
+                slave,
+                detail: "no ARMW or FRMW of the system time in the capture".to_string(),
+            });
+        }
+    }
+    violations.sort_by_key(|v| (v.frame_number, v.rule));
+
+    outln!("{}{}", CSV_HEADER, labels.header());
+    for violation in &violations {
+        outln!("{},{},{},{},{}{}",
+            violation.frame_number, violation.rule.name(), violation.slave.map(|s| s.to_string()).unwrap_or_else(|| "all".to_string()),
+            violation.detail, violation.rule.reference(), labels.cell(violation.slave));
+    }
+    summary.report();
+}
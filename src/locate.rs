@@ -0,0 +1,85 @@
+use crate::{ECAT_HEADER_LEN, ETHERNET_HEADER_LEN, ETHERTYPE_ETHERCAT};
+use ecat_packet_analyzer_rs::parser::ethernet::ether_type;
+
+/// Offsets tried by the scan when none are given: right after the Ethernet header, behind one
+/// and two VLAN tags, and in a UDP datagram over IPv4 without options.
+pub const DEFAULT_SCAN_OFFSETS: &str = "14,18,22,42";
+/// Frame type of the EtherCAT header for datagrams (the others carry network variables and
+/// mailbox gateway traffic).
+const FRAME_TYPE_DATAGRAMS: u16 = 1;
+/// Command byte, index, address, length word and IRQ of a datagram header.
+const DATAGRAM_HEADER_LEN: usize = 10;
+const WKC_LEN: usize = 2;
+const LAST_COMMAND: u8 = 14;
+
+/// Where the EtherCAT header of the captured frames is.
+#[derive(Clone, Debug, Default)]
+pub enum EcatLocation {
+    /// Right after the Ethernet header, in frames of the EtherCAT EtherType, or in the UDP
+    /// datagrams of IPv4 frames from or to the EtherCAT port.
+    #[default]
+    Standard,
+    /// At this offset of every frame, whatever its EtherType (`--assume-ecat-offset`).
+    At(usize),
+    /// At the first of these offsets that holds a plausible EtherCAT header, in frames of other
+    /// EtherTypes (`--scan-ecat`).
+    Scan(Vec<usize>),
+}
+
+/// Whether the bytes at `offset` look like an EtherCAT header for datagrams followed by the
+/// chain of datagrams it announces, with known commands and clear reserved bits.
+fn plausible(frame: &[u8], offset: usize) -> bool {
+    let Some(header) = frame.get(offset..offset + ECAT_HEADER_LEN) else {
+        return false;
+    };
+    let header = u16::from_le_bytes([header[0], header[1]]);
+    let length = (header & 0x07FF) as usize;
+    if header >> 12 != FRAME_TYPE_DATAGRAMS || header & 0x0800 != 0 || length < DATAGRAM_HEADER_LEN + WKC_LEN {
+        return false;
+    }
+    let Some(datagrams) = frame.get(offset + ECAT_HEADER_LEN..offset + ECAT_HEADER_LEN + length) else {
+        return false;
+    };
+    let mut at = 0;
+    loop {
+        let Some(datagram) = datagrams.get(at..at + DATAGRAM_HEADER_LEN) else {
+            return false;
+        };
+        let length_word = u16::from_le_bytes([datagram[6], datagram[7]]);
+        if datagram[0] > LAST_COMMAND || length_word & 0x3800 != 0 {
+            return false;
+        }
+        at += DATAGRAM_HEADER_LEN + (length_word & 0x07FF) as usize + WKC_LEN;
+        if length_word & 0x8000 == 0 {
+            return at == length;
+        }
+    }
+}
+
+impl EcatLocation {
+    /// The frame as the decoders expect it, when its EtherCAT header is elsewhere than right
+    /// after the Ethernet header: the MAC addresses, the EtherCAT EtherType and the EtherCAT
+    /// frame. `None` leaves the frame as captured.
+    pub fn rebuild(&self, frame: &[u8]) -> Option<Vec<u8>> {
+        let ethertype = frame.get(12..ETHERNET_HEADER_LEN).map(|bytes| u16::from_be_bytes([bytes[0], bytes[1]]));
+        let offset = match self {
+            EcatLocation::Standard => return None,
+            EcatLocation::At(offset) => *offset,
+            EcatLocation::Scan(offsets) => {
+                // the decoders skip the VLAN tags of EtherCAT frames themselves.
+                if ether_type(frame).is_some_and(|(ethertype, _)| ethertype == ETHERTYPE_ETHERCAT) {
+                    return None;
+                }
+                *offsets.iter().find(|&&offset| plausible(frame, offset))?
+            }
+        };
+        if (offset == ETHERNET_HEADER_LEN && ethertype == Some(ETHERTYPE_ETHERCAT)) || offset > frame.len() || frame.len() < 12 {
+            return None;
+        }
+        let mut rebuilt = Vec::with_capacity(ETHERNET_HEADER_LEN + frame.len() - offset);
+        rebuilt.extend_from_slice(&frame[..12]);
+        rebuilt.extend_from_slice(&ETHERTYPE_ETHERCAT.to_be_bytes());
+        rebuilt.extend_from_slice(&frame[offset..]);
+        Some(rebuilt)
+    }
+}
@@ -0,0 +1,90 @@
+use crate::capture::{self, CaptureOptions, Item};
+use crate::mac::{self, MacFormat};
+use crate::pair::Key;
+use crate::sink::outln;
+use crate::value::{Style, Value};
+use crate::EtherNetFrame;
+use std::collections::{BTreeMap, VecDeque};
+use std::fs::File;
+use std::ops::ControlFlow;
+use std::process::exit;
+
+const CSV_HEADER: &str = "frame,time,issue,master,cmd,index";
+
+/// Frames of a master on their way at a time; beyond, the oldest is forgotten, as a capture
+/// taken where the frames do not come back would otherwise keep them all.
+const MAX_IN_FLIGHT: usize = 1024;
+
+/// An outgoing frame waiting for its return: the key of its first datagram, and its row
+/// should it be lost.
+struct Sent {
+    key: Key,
+    row: String,
+}
+
+#[derive(Default)]
+struct Counts {
+    lost: u64,
+    duplicated: u64,
+    circulating: u64,
+}
+
+/// Lists the frames lost or duplicated on the wire and those that circulated. Frames come back
+/// in the order the master sent them, so an outgoing frame is lost when a frame it sent later
+/// returns first; a returned frame no outgoing frame waits for, by master, index and command of
+/// its first datagram, is a duplicate. Circulating frames carry the circulating bit in any of
+/// their datagrams. The frames still on their way at the end of the capture are not counted.
+pub fn run(file: File, options: &CaptureOptions, mac_format: MacFormat) {
+    let mut in_flight: BTreeMap<[u8; 6], VecDeque<Sent>> = BTreeMap::new();
+    let mut counts = Counts::default();
+
+    outln!("{}", CSV_HEADER);
+    let summary = capture::read(file, options, |item| {
+        let Item::Packet(packet) = item else {
+            return ControlFlow::Continue(());
+        };
+        let Some(frame) = packet.data.and_then(EtherNetFrame::parse_ethercat) else {
+            return ControlFlow::Continue(());
+        };
+        let Some(first) = frame.ecat_frame.datagrams.first() else {
+            return ControlFlow::Continue(());
+        };
+        let master = frame.master_mac();
+        let row = |frame_number: u64, ts_ns: u64, issue: &str| format!("{},{},{},{},{},{}",
+            frame_number, Value::Time(ts_ns).render(Style::Csv), issue, mac::format(&master, mac_format), first.header.cmd, first.header.index);
+        let (frame_number, ts_ns) = (packet.info.frame_number, packet.info.ts_ns);
+        let key = Key::new(master, &first.header);
+        let sent = in_flight.entry(master).or_default();
+        if !frame.is_returned() {
+            if sent.len() == MAX_IN_FLIGHT {
+                sent.pop_front();
+            }
+            sent.push_back(Sent { key, row: row(frame_number, ts_ns, "lost") });
+            return ControlFlow::Continue(());
+        }
+        match sent.iter().position(|sent| sent.key == key) {
+            Some(position) => {
+                for lost in sent.drain(..position) {
+                    outln!("{}", lost.row);
+                    counts.lost += 1;
+                }
+                sent.pop_front();
+            }
+            None => {
+                outln!("{}", row(frame_number, ts_ns, "duplicated"));
+                counts.duplicated += 1;
+            }
+        }
+        if frame.ecat_frame.datagrams.iter().any(|datagram| datagram.header.round_trip != 0) {
+            outln!("{}", row(frame_number, ts_ns, "circulating"));
+            counts.circulating += 1;
+        }
+        ControlFlow::Continue(())
+    });
+    let summary = summary.unwrap_or_else(|e| {
+        eprintln!("{}", e);
+        exit(1);
+    });
+    eprintln!("{} frames lost, {} duplicated, {} circulating", counts.lost, counts.duplicated, counts.circulating);
+    summary.report();
+}
@@ -0,0 +1,110 @@
+use clap::ValueEnum;
+use std::fs;
+
+/// How MAC addresses are written in the `dst_mac` and `src_mac` columns.
+#[derive(ValueEnum, Clone, Copy, Debug, Default)]
+pub enum MacFormat {
+    /// aa:bb:cc:dd:ee:ff
+    #[default]
+    Colon,
+    /// aa-bb-cc-dd-ee-ff
+    Hyphen,
+    /// aabb.ccdd.eeff
+    Dot,
+    /// aabbccddeeff
+    Bare,
+    /// Unpadded and space-separated (a bb cc d ee ff), as written before --mac-format
+    Legacy,
+}
+
+pub fn format(mac: &[u8; 6], format: MacFormat) -> String {
+    let bytes = |separator: &str| mac.iter().map(|byte| format!("{:02x}", byte)).collect::<Vec<_>>().join(separator);
+    match format {
+        MacFormat::Colon => bytes(":"),
+        MacFormat::Hyphen => bytes("-"),
+        MacFormat::Bare => bytes(""),
+        MacFormat::Dot => mac.chunks(2).map(|pair| format!("{:02x}{:02x}", pair[0], pair[1])).collect::<Vec<_>>().join("."),
+        MacFormat::Legacy => mac.iter().map(|byte| format!("{:x}", byte)).collect::<Vec<_>>().join(" "),
+    }
+}
+
+/// A block of addresses assigned to a vendor: the first `bits` bits of `prefix`.
+struct Assignment {
+    prefix: u64,
+    bits: u32,
+    vendor: String,
+}
+
+/// NIC vendors by address block, loaded from a file given with `--oui`, either Wireshark's
+/// `manuf` file, with the block, a short and a full vendor name separated by tabs:
+///
+/// ```text
+/// 00:1B:21    Intel    Intel Corporate
+/// 00:1B:C5:00:00:00/36    Converge    Converging Systems Inc.
+/// ```
+///
+/// or the IEEE `oui.txt` registry, of which only the `(hex)` lines are read:
+///
+/// ```text
+/// 00-1B-21   (hex)    Intel Corporate
+/// ```
+///
+/// The longest matching block wins, and the full vendor name is used where the file has one.
+pub struct OuiTable {
+    assignments: Vec<Assignment>,
+}
+
+fn parse_prefix(s: &str) -> Option<(u64, u32)> {
+    let (address, bits) = match s.split_once('/') {
+        Some((address, bits)) => (address, Some(bits.parse().ok()?)),
+        None => (s, None),
+    };
+    let bytes: Vec<u8> = address.split([':', '-', '.']).map(|byte| u8::from_str_radix(byte, 16).ok()).collect::<Option<_>>()?;
+    if bytes.is_empty() || bytes.len() > 6 {
+        return None;
+    }
+    let bits = bits.unwrap_or(bytes.len() as u32 * 8);
+    if bits == 0 || bits > 48 {
+        return None;
+    }
+    let prefix = bytes.iter().chain(std::iter::repeat(&0)).take(6).fold(0u64, |prefix, byte| prefix << 8 | *byte as u64);
+    Some((prefix, bits))
+}
+
+impl OuiTable {
+    pub fn load(path: &str) -> Result<Self, String> {
+        let text = fs::read_to_string(path).map_err(|e| format!("Cannot read {} : {}", path, e))?;
+        let mut assignments = Vec::new();
+        for (number, line) in text.lines().enumerate() {
+            let line = line.trim_end();
+            if line.trim_start().is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let (prefix, vendor) = if let Some((prefix, vendor)) = line.split_once("(hex)") {
+                (prefix.trim(), vendor.trim())
+            } else if line.starts_with(|c: char| c.is_ascii_hexdigit()) && line.contains('\t') {
+                let mut fields = line.split('\t').filter(|field| !field.is_empty());
+                let prefix = fields.next().unwrap_or_default();
+                let short = fields.next().unwrap_or_default();
+                (prefix, fields.next().unwrap_or(short))
+            } else {
+                // the other lines of oui.txt: addresses in base 16, company addresses.
+                continue;
+            };
+            let (prefix, bits) = parse_prefix(prefix)
+                .ok_or_else(|| format!("Invalid OUI file {} : bad address block on line {}", path, number + 1))?;
+            assignments.push(Assignment { prefix, bits, vendor: vendor.to_string() });
+        }
+        assignments.sort_by_key(|assignment| std::cmp::Reverse(assignment.bits));
+        Ok(OuiTable { assignments })
+    }
+
+    /// Vendor of the NIC owning a (universally administered) address.
+    pub fn vendor(&self, mac: &[u8; 6]) -> Option<&str> {
+        let address = mac.iter().fold(0u64, |address, byte| address << 8 | *byte as u64);
+        self.assignments
+            .iter()
+            .find(|assignment| (address ^ assignment.prefix) >> (48 - assignment.bits) == 0)
+            .map(|assignment| assignment.vendor.as_str())
+    }
+}
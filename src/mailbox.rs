@@ -0,0 +1,175 @@
+use crate::checkpoint;
+use crate::events::EventKind;
+use crate::slave_config::{SlaveConfigs, SlaveRef};
+use crate::{EtherCATCommand, EtherCATDatagram, EtherNetFrame};
+use ecat_packet_analyzer_rs::parser::mailbox::{MailboxError, MailboxHeader, MAILBOX_HEADER_LEN};
+pub use ecat_packet_analyzer_rs::parser::mailbox::MailboxType;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+/// Lowest address of the process memory, where mailbox buffers live.
+const PROCESS_MEMORY_START: u16 = 0x1000;
+/// First SyncManager; each takes 8 bytes, the activate register at offset 6.
+const SM_BASE: u16 = 0x0800;
+const SM_ACTIVATE_OFFSET: u16 = 6;
+/// Bit of the activate register the master toggles to have the slave repeat its last message.
+const REPEAT_REQUEST: u8 = 0x02;
+/// Highest mailbox counter, after which the sequence starts over at 1.
+const MAX_COUNTER: u8 = 7;
+
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Debug, Serialize, Deserialize)]
+pub enum Direction {
+    /// Written by the master into the write mailbox of the slave.
+    ToSlave,
+    /// Read by the master from the read mailbox of the slave.
+    FromSlave,
+}
+
+/// A mailbox message exchanged with one slave.
+pub struct Mailbox<'a> {
+    /// Configured station address of the slave.
+    pub slave: u16,
+    pub direction: Direction,
+    pub mbx_type: MailboxType,
+    /// Counter of the message, 1 to 7, 0 for a message outside the counter sequence.
+    pub counter: u8,
+    /// Size of the mailbox buffer available to the protocol payload.
+    pub capacity: usize,
+    pub payload: &'a [u8],
+}
+
+/// Extracts the mailbox message a datagram delivers, if any.
+///
+/// Only datagrams that have passed the slave and were acknowledged count: an unacknowledged
+/// write did not reach the mailbox and an unacknowledged read found it empty. Mailbox buffers
+/// are located from the SyncManager configuration when the capture contains it, otherwise any
+/// acknowledged FPWR/FPRD in the process memory carrying a valid mailbox header is taken.
+pub fn extract<'a>(datagram: &EtherCATDatagram<'a>, returned: bool, configs: &SlaveConfigs) -> Option<Mailbox<'a>> {
+    let header = &datagram.header;
+    if !returned || datagram.wkc == 0 {
+        return None;
+    }
+    let direction = match header.cmd {
+        EtherCATCommand::FPWR => Direction::ToSlave,
+        EtherCATCommand::FPRD => Direction::FromSlave,
+        _ => return None,
+    };
+
+    match configs.sync_managers(SlaveRef::Station(header.slave_addr)) {
+        Some(sync_managers) => {
+            let is_mailbox = sync_managers.iter().any(|sm| {
+                sm.mailbox && sm.start == header.offset_addr && sm.master_writes == (direction == Direction::ToSlave)
+            });
+            if !is_mailbox {
+                return None;
+            }
+        }
+        None if header.offset_addr < PROCESS_MEMORY_START => return None,
+        None => {}
+    }
+
+    let data = datagram.data;
+    let mbx_header = MailboxHeader::parse(data).ok()?;
+    let length = mbx_header.length as usize;
+    if MAILBOX_HEADER_LEN + length > data.len() {
+        return None;
+    }
+
+    Some(Mailbox {
+        slave: header.slave_addr,
+        direction,
+        mbx_type: mbx_header.mbx_type,
+        counter: mbx_header.counter,
+        capacity: data.len() - MAILBOX_HEADER_LEN,
+        payload: &data[MAILBOX_HEADER_LEN..MAILBOX_HEADER_LEN + length],
+    })
+}
+
+/// The mailbox error reply a message carries, if it is one from the slave.
+pub fn decode_error(mailbox: &Mailbox) -> Option<EventKind> {
+    if mailbox.mbx_type != MailboxType::Error || mailbox.direction != Direction::FromSlave {
+        return None;
+    }
+    let error = MailboxError::parse(mailbox.payload).ok()?;
+    Some(EventKind::MailboxError { slave: mailbox.slave, code: error.code })
+}
+
+/// Follows the mailbox traffic of every slave: the counter of the last message in each
+/// direction, and the repeat request last written to its read mailbox.
+#[derive(Clone, Default, Serialize, Deserialize)]
+pub struct Sequences {
+    #[serde(with = "checkpoint::pairs")]
+    counters: HashMap<(u16, Direction), u8>,
+    repeat_requests: HashMap<u16, bool>,
+}
+
+impl Sequences {
+    /// Takes the next datagram, returning the mailbox error reply it delivers, whether its
+    /// message repeats the counter of the one before or skips some, and whether it toggles the
+    /// repeat request of a read mailbox.
+    pub fn datagram(&mut self, datagram: &EtherCATDatagram, returned: bool, configs: &SlaveConfigs) -> Vec<EventKind> {
+        let mut events = Vec::new();
+        if let Some(mailbox) = extract(datagram, returned, configs) {
+            events.extend(decode_error(&mailbox));
+            events.extend(self.message(&mailbox));
+        }
+        events.extend(self.repeat_request(datagram, returned, configs));
+        events
+    }
+
+    fn message(&mut self, mailbox: &Mailbox) -> Option<EventKind> {
+        // counter 0 is outside the sequence.
+        if mailbox.counter == 0 {
+            return None;
+        }
+        let (slave, direction, counter) = (mailbox.slave, mailbox.direction, mailbox.counter);
+        let previous = self.counters.insert((slave, direction), counter)?;
+        let expected = previous % MAX_COUNTER + 1;
+        if counter == previous {
+            Some(EventKind::MailboxRepeated { slave, direction, counter })
+        } else if counter != expected {
+            let missing = (counter + MAX_COUNTER - expected) % MAX_COUNTER;
+            Some(EventKind::MailboxLost { slave, direction, missing, counter })
+        } else {
+            None
+        }
+    }
+
+    /// The first write of the activate register of a read mailbox is where its repeat request
+    /// starts from; a write with the other value of the bit toggles it.
+    fn repeat_request(&mut self, datagram: &EtherCATDatagram, returned: bool, configs: &SlaveConfigs) -> Option<EventKind> {
+        let header = &datagram.header;
+        if !returned || datagram.wkc == 0 || !matches!(header.cmd, EtherCATCommand::FPWR | EtherCATCommand::FPRW) {
+            return None;
+        }
+        let slave = header.slave_addr;
+        let read_mailbox = configs.sync_managers(SlaveRef::Station(slave))?.into_iter().find(|sm| sm.mailbox && !sm.master_writes)?;
+        let register = SM_BASE + read_mailbox.index as u16 * 8 + SM_ACTIVATE_OFFSET;
+        let offset = register.checked_sub(header.offset_addr)? as usize;
+        let requested = datagram.data.get(offset)? & REPEAT_REQUEST != 0;
+        let previous = self.repeat_requests.insert(slave, requested)?;
+        (requested != previous).then_some(EventKind::MailboxRepeatRequest { slave })
+    }
+}
+
+/// Watches the mailbox traffic of the slaves for the error replies they send and for messages
+/// repeated or lost, which stall the mailbox protocols the master configures the slaves with
+/// before SAFE-OP.
+#[derive(Clone, Default, Serialize, Deserialize)]
+pub struct MailboxMonitor {
+    configs: SlaveConfigs,
+    sequences: Sequences,
+}
+
+impl MailboxMonitor {
+    /// Takes the next packet, returning the mailbox events of its datagrams.
+    pub fn check(&mut self, frame: &EtherNetFrame) -> Vec<EventKind> {
+        let returned = frame.is_returned();
+        let mut events = Vec::new();
+        for datagram in &frame.ecat_frame.datagrams {
+            self.configs.apply(datagram, returned);
+            events.extend(self.sequences.datagram(datagram, returned, &self.configs));
+        }
+        events
+    }
+}
@@ -1,292 +1,1677 @@
-use clap::Parser;
-use pcap_parser::*;
-use pcap_parser::traits::PcapReaderIterator;
-use std::fmt::Display;
+use clap::{Parser, Subcommand, ValueEnum};
+use ecat_packet_analyzer_rs::parser::ecat::{EtherCATCommand, EtherCATDatagram, EtherCATDatagramHeader, FrameContent, FrameType, ECAT_HEADER_LEN};
+use ecat_packet_analyzer_rs::parser::ethernet::{is_ethercat, EtherNetFrame, ETHERNET_HEADER_LEN, ETHERTYPE_ETHERCAT};
 use std::fs::File;
+use std::num::{NonZeroU64, NonZeroUsize};
+use std::ops::ControlFlow;
+use std::path::PathBuf;
 use std::process::exit;
+use std::sync::{Arc, Mutex, OnceLock};
+use std::time::{Duration, Instant};
+
+mod align;
+mod annotations;
+mod arrow;
+mod batch;
+mod capabilities;
+mod capture;
+mod carve;
+mod checkpoint;
+mod circulation;
+mod clock;
+mod compare;
+mod coe;
+mod columns;
+mod csv;
+mod cycle;
+mod dc;
+mod derive;
+mod emergency;
+mod eni;
+mod eoe;
+mod esi;
+mod events;
+mod examples;
+mod extcap;
+mod filter;
+mod foe;
+mod health;
+mod index;
+mod influx;
+mod interface;
+mod irq;
+mod labels;
+mod latency;
+mod link;
+mod lint;
+mod losses;
+mod locate;
+mod mac;
+mod mailbox;
+mod mapped;
+mod merge;
+mod metrics;
+mod other;
+mod pair;
+mod parquet;
+mod pcapng;
+mod pdo;
+mod pdo_map;
+mod pipeline;
+mod port_errors;
+mod reboot;
+mod recover;
+mod registers;
+mod report;
+mod ring;
+mod signal;
+mod sink;
+mod slave_config;
+mod slaves;
+mod snapshot;
+mod state;
+mod states;
+mod statistics;
+mod stats;
+mod throttle;
+mod top;
+mod topology;
+mod transaction;
+mod validate;
+mod value;
+mod warnings;
+mod wkc;
+mod xml;
+
+const CSV_HEADER: &str = "dst_mac,src_mac,cmd,index,adp,ado,length,round_trip,last_ind,irq,wkc,data,warnings";
+
+/// Columns of --sdo in the CSV export, which the jsonl rows carry as one `sdo` object.
+const SDO_COLUMNS: [&str; 7] = ["sdo_service", "sdo_command", "sdo_index", "sdo_subindex", "sdo_data", "sdo_abort_code", "sdo_abort"];
 
 #[derive(Parser, Debug)]
-#[command(version, about, long_about = None)]
+#[command(version, about, long_about = None, after_help = "Run `batch jobs.toml` to run the exports listed in a job file in one process, \
+`examples` for demonstrations on sample captures built into the exporter, \
+and `--capabilities` for a JSON description of the supported formats, subcommands, decoders and options.")]
 struct Args {
+    /// The pcapng or pcap capture, possibly gzip, zstd or xz compressed; repeat for a capture
+    /// split into several files, or give a pattern such as 'captures/*.pcapng', the files
+    /// being read in turn as one capture with their frames numbered on
+    #[arg(short, long, required = true)]
+    file: Vec<String>,
+
+    #[command(subcommand)]
+    command: Option<Command>,
+
+    /// Output of the export: one CSV row, JSON object, InfluxDB line protocol record, Parquet or
+    /// Arrow row per datagram, or a Markdown report of the whole capture
+    #[arg(long, value_enum, default_value_t = Format::Csv)]
+    format: Format,
+
+    /// Separator of the fields of the CSV export of the datagrams; fields holding it, a quote or
+    /// a line break are quoted
+    #[arg(long, value_enum, default_value_t = csv::Delimiter::Comma)]
+    delimiter: csv::Delimiter,
+
+    /// Columns of the CSV export and fields of the JSON Lines export, in this order, instead of
+    /// the default ones: any of those the options in use add, frame, time, time_ns and ts, the
+    /// capture time in nanoseconds
+    #[arg(long, value_delimiter = ',')]
+    columns: Vec<String>,
+
+    /// Write the CSV, JSON Lines, Parquet or Arrow export to this file instead of stdout, which
+    /// is left to diagnostics such as the unknown blocks and the block count
     #[arg(short, long)]
-    file: String,
+    output: Option<String>,
+
+    /// What a row of the CSV export stands for: a datagram, or a whole bus cycle with its cycle
+    /// time, working counters and the IDs of its datagrams
+    #[arg(long, value_enum, default_value_t = cycle::GroupBy::Datagram)]
+    group_by: cycle::GroupBy,
+
+    /// Pair each returned datagram with the outgoing one by master, index, command and address,
+    /// and export one row per pair with the round-trip time and the working counter change
+    #[arg(long, conflicts_with = "group_by")]
+    pair: bool,
+
+    /// Skip over corrupt pcapng blocks to the next valid block instead of aborting
+    #[arg(long)]
+    recover: bool,
+
+    /// Decode every frame as carrying its EtherCAT frame at this offset, whatever its EtherType,
+    /// for captures of tools rewriting the EtherType or wrapping EtherCAT in a vendor tunnel
+    #[arg(long, conflicts_with = "scan_ecat")]
+    assume_ecat_offset: Option<usize>,
+
+    /// Look for a plausible EtherCAT frame at the --scan-offsets of the frames of other
+    /// EtherTypes, and decode it where one is found
+    #[arg(long)]
+    scan_ecat: bool,
+
+    /// Offsets tried by --scan-ecat, in order
+    #[arg(long, value_delimiter = ',', default_value = locate::DEFAULT_SCAN_OFFSETS)]
+    scan_offsets: Vec<usize>,
+
+    /// Report the frames of other protocols the export skips, such as the ARP, LLDP and IP
+    /// traffic of the NIC of the master, beyond their count
+    #[arg(long, value_enum)]
+    include_other: Option<other::IncludeOther>,
+
+    /// Export only the packets captured from this time on: epoch seconds, ISO 8601, or an offset
+    /// from the first packet such as 12.5s
+    #[arg(long, value_parser = clock::TimeSpec::parse)]
+    from: Option<clock::TimeSpec>,
+
+    /// Export only the packets captured before this time, given as for --from; reading stops at
+    /// the first packet past it
+    #[arg(long, value_parser = clock::TimeSpec::parse)]
+    to: Option<clock::TimeSpec>,
+
+    /// Export only these frames, numbered from 1: one frame, first..end (end excluded) or
+    /// first..=last; reading stops past the last
+    #[arg(long, value_parser = filter::FrameRange::parse)]
+    frames: Option<filter::FrameRange>,
+
+    /// Index written by the index subcommand, which --from and --frames start reading from
+    /// instead of the start of the capture; <file>.idx by default, when it exists
+    #[arg(long)]
+    index: Option<String>,
+
+    /// Export only the datagrams of these commands, such as LRW or BRD,FPRD; the other
+    /// datagrams of the same frames are left out
+    #[arg(long, value_delimiter = ',')]
+    cmd: Vec<EtherCATCommand>,
+
+    /// Export only the datagrams of these slave addresses (ADP), such as 0x1001, 0x1001..0x1003
+    /// (end excluded) or 0x1001..=0x1002, comma separated or repeated
+    #[arg(long, value_delimiter = ',', value_parser = filter::AddressRange::parse)]
+    adp: Vec<filter::AddressRange>,
+
+    /// Export only the datagrams of these register offsets (ADO), such as 0x0130..0x0140
+    #[arg(long, value_delimiter = ',', value_parser = filter::AddressRange::parse)]
+    ado: Vec<filter::AddressRange>,
+
+    /// Also write the packets exported to this pcapng file, as they were captured, with their
+    /// capture times and interfaces: those of --from, --to and --frames, and with --cmd, --adp
+    /// or --ado only the frames with a datagram of them
+    #[arg(long)]
+    write_pcap: Option<PathBuf>,
+
+    /// Abort on the first inconsistent frame instead of flagging it in the warnings column
+    #[arg(long)]
+    strict: bool,
+
+    /// Number of threads parsing and formatting packets, or auto for one per CPU, while the
+    /// capture is read and the rows written on threads of their own; output keeps capture order
+    /// unless --unordered is given
+    #[arg(long, default_value = "1", value_parser = pipeline::parse_jobs)]
+    jobs: NonZeroUsize,
+
+    /// With --jobs, write rows as soon as they are ready instead of in capture order
+    #[arg(long)]
+    unordered: bool,
+
+    /// What to do when the consumer of the output, such as a pipe or a socket, falls behind by
+    /// the whole --output-buffer: wait for it, drop the oldest buffered rows and report how much
+    /// was lost, or write the excess to a file in --spill-dir until it catches up
+    #[arg(long, value_enum, default_value_t = sink::Backpressure::Block)]
+    backpressure: sink::Backpressure,
+
+    /// Output held in memory for a slow consumer, such as 512KiB or 16MiB (16MiB unless given;
+    /// without it, block writes straight to stdout)
+    #[arg(long, value_parser = sink::parse_size)]
+    output_buffer: Option<usize>,
+
+    /// Directory of the spill file of --backpressure spill, the temporary directory by default
+    #[arg(long)]
+    spill_dir: Option<String>,
+
+    /// Report a clock jump when consecutive packets of an interface are further apart than this
+    #[arg(long, default_value = "1s", value_parser = clock::parse_duration)]
+    clock_jump: u64,
+
+    /// TOML file describing the expected working counters; returning datagrams that differ
+    /// are flagged as wkc_mismatch in the warnings column
+    #[arg(long)]
+    wkc_config: Option<String>,
+
+    /// Expected working counter of every returning datagram of a command, such as LRW=3; wins
+    /// over --wkc-config
+    #[arg(long, value_parser = wkc::parse_expectation)]
+    expected_wkc: Vec<(EtherCATCommand, u16)>,
+
+    /// ENI file (EtherCAT network information, as exported by TwinCAT and other configuration
+    /// tools) whose cyclic commands check-eni checks the cycles against; the working counters
+    /// it expects win over --wkc-config
+    #[arg(long)]
+    eni: Option<String>,
+
+    /// Add frame_number and datagram_index columns with the number of the packet in the capture,
+    /// the one Wireshark shows, and the position of the datagram in its frame
+    #[arg(long)]
+    frame_number: bool,
+
+    /// Add a timestamp column with the capture time of each packet, written as --time-format says
+    #[arg(long)]
+    timestamp: bool,
+
+    /// Format of the timestamp column
+    #[arg(long, value_enum, default_value_t = clock::TimeFormat::Epoch, requires = "timestamp")]
+    time_format: clock::TimeFormat,
+
+    /// Add a dc_time column with the capture time of each packet converted to the DC time of the reference clock
+    #[arg(long)]
+    dc_time: bool,
+
+    /// Add datagrams_in_frame and frame_composition columns with the number and the commands
+    /// (such as LRW+BRD+FPRD) of the datagrams in the frame of each row
+    #[arg(long)]
+    frame_composition: bool,
+
+    /// Add datagram_id and transaction_id columns: the frame number and position of each datagram,
+    /// such as 17.1, and for mailbox datagrams the ID of the first datagram of their FoE, SDO or
+    /// EoE transaction, as in the transaction_id of the foe subcommand
+    #[arg(long)]
+    ids: bool,
+
+    /// Add mbx_type and mbx_counter columns with the protocol (CoE, FoE, EoE, ...) and the
+    /// counter of the mailbox message each datagram delivers, found from the SyncManager
+    /// configuration in the capture
+    #[arg(long)]
+    mailbox: bool,
+
+    /// Add sdo_service, sdo_command, sdo_index, sdo_subindex, sdo_data, sdo_abort_code and
+    /// sdo_abort columns decoding the CoE SDO request or response of each mailbox datagram,
+    /// with the data of expedited transfers and the meaning of abort codes; a nested sdo object
+    /// in JSON Lines
+    #[arg(long)]
+    sdo: bool,
+
+    /// Add an event column flagging the datagrams that deliver a CoE emergency, with the
+    /// slave, error code, error register and manufacturer data, or a mailbox error reply with
+    /// its code, a mailbox message repeating or skipping counters, or the repeat request of a
+    /// read mailbox; every such event is also reported on stderr
+    #[arg(long)]
+    events: bool,
+
+    /// Add an irq_flags column with the names of the event bits set in the IRQ field, such as
+    /// al_status_change or sm0, separated by semicolons, as a list in jsonl
+    #[arg(long)]
+    irq_flags: bool,
+
+    /// Add a frame_hex column with the bytes of the whole captured frame of each row
+    #[arg(long)]
+    frame_hex: bool,
+
+    /// Add a datagram_offset_in_frame column with the offset of each datagram header from the
+    /// start of the captured frame, in bytes
+    #[arg(long)]
+    datagram_offset: bool,
+
+    /// Add a vlan_id column with the VLAN IDs of the tagged frames, outermost first and
+    /// separated by dots for QinQ
+    #[arg(long)]
+    vlan: bool,
+
+    /// Add a row for every network variable of the frames of that type, with its length and
+    /// data in the columns of the datagrams and publisher_id, nv_index, nv_hash and nv_quality
+    /// columns; the frames are skipped otherwise
+    #[arg(long)]
+    network_variables: bool,
+
+    /// Add a file column with the name of the capture file of each packet, for a capture
+    /// split into several files
+    #[arg(long)]
+    file_column: bool,
+
+    /// Add a position column with the position in the chain of the slave each datagram
+    /// addresses, from the station addresses the master assigns by position while it scans
+    #[arg(long)]
+    position: bool,
+
+    /// Add a register_name column with the name of the ESC register, such as AL Status or
+    /// DC System Time, that each datagram addressing slave registers starts at
+    #[arg(long)]
+    register_names: bool,
+
+    /// TOML file naming registers for --register-names, such as vendor registers in the user
+    /// RAM; its names win over the built-in ones
+    #[arg(long, requires = "register_names")]
+    registers: Option<String>,
+
+    /// How MAC addresses are written
+    #[arg(long, value_enum, default_value_t = mac::MacFormat::Colon)]
+    mac_format: mac::MacFormat,
+
+    /// Wireshark manuf or IEEE oui.txt file; adds a master_vendor column with the NIC vendor
+    /// of the master sending each frame
+    #[arg(long)]
+    oui: Option<String>,
+
+    /// CSV file receiving a record for every anomaly, validation failure, link error and
+    /// corrupt region of the export, with the frame it concerns
+    #[arg(long)]
+    warnings_out: Option<String>,
+
+    /// Write the records of --warnings-out into the JSON Lines export as well, as `warning`
+    /// objects ahead of the datagrams of the frame they concern
+    #[arg(long)]
+    inline_warnings: bool,
+
+    /// JSON file with the health of the CSV or JSON Lines export of a capture file, rewritten
+    /// periodically while the file is read and once at its end
+    #[arg(long)]
+    stats_out: Option<String>,
+
+    /// How often the --stats-out file is rewritten while the capture file is read
+    #[arg(long, default_value = "10s", value_parser = clock::parse_duration)]
+    stats_interval: u64,
+
+    /// Serve Prometheus metrics of the CSV or JSON Lines export at /metrics of this address,
+    /// such as 0.0.0.0:9464, while it reads the capture, which a capture tool may still be
+    /// writing into a pipe (--file /dev/stdin): frames by command, working counter mismatches
+    /// by slave, mailbox errors and cycle jitter
+    #[arg(long)]
+    metrics_listen: Option<String>,
+
+    /// Keep packets flagged with link-level errors (capture error flags, FCS mismatch) in timing statistics
+    #[arg(long)]
+    timing_include_errors: bool,
+
+    /// Save the progress of the export to this file periodically, for --resume
+    #[arg(long)]
+    checkpoint: Option<String>,
+
+    /// How often the --checkpoint file is rewritten
+    #[arg(long, default_value = "60s", value_parser = clock::parse_duration)]
+    checkpoint_interval: u64,
+
+    /// Continue an interrupted export from its checkpoint, with the same options, appending
+    /// to its output (>>); rows written after the checkpoint are cut from the output file, and
+    /// packets from the file of --write-pcap
+    #[arg(long)]
+    resume: Option<String>,
+
+    /// TOML file naming the slaves by station address, alias or position; outputs listing
+    /// slaves gain a label column
+    #[arg(long)]
+    labels: Option<String>,
+
+    /// Folder of ESI (EtherCAT slave information) files naming the slaves by the vendor ID and
+    /// product code the master reads from their SII; the export gains a slave_name column and
+    /// the report names the slaves of its topology
+    #[arg(long)]
+    esi_dir: Option<String>,
+
+    /// Add a column computed for each datagram from its fields and payload, as name=expression
+    /// (e.g. vel=i32le(data,4)*0.001); may be repeated
+    #[arg(long)]
+    derive: Vec<String>,
+
+    /// TOML file laying out the process image as named signals, with their logical address,
+    /// bits, type and scaling; each signal becomes a column decoding the LRD, LRW and LWR rows
+    #[arg(long)]
+    pdo_map: Option<String>,
+
+    /// Report an IRQ storm when an event bit is set in this many consecutive returned frames (0 disables)
+    #[arg(long, default_value = "100")]
+    irq_storm: u64,
+
+    /// Write at most this many packets per period of capture time, such as 100/s, for a human
+    /// tailing the output
+    #[arg(long, value_parser = throttle::RateLimit::parse)]
+    rate_limit: Option<throttle::RateLimit>,
+
+    /// Leave out frames whose datagrams carry the same data and working counters as the
+    /// previous frame with the same datagrams
+    #[arg(long)]
+    only_changes: bool,
+
+    /// CSV file of timestamped notes (timestamp,note) merged into the output as annotation rows
+    #[arg(long)]
+    annotate: Option<String>,
+
+    /// Merge the comments of the capture (pcapng comment options, such as packet comments added
+    /// in Wireshark) and the text of its custom blocks into the output as annotation rows
+    #[arg(long)]
+    capture_comments: bool,
+}
+
+#[derive(ValueEnum, Clone, Copy, Debug)]
+enum Format {
+    Csv,
+    /// One JSON object per line and datagram, with the fields of the CSV columns, for jq and
+    /// log pipelines
+    Jsonl,
+    /// Summary, topology, timeline of events and anomaly tables, for issue trackers and wikis
+    MarkdownReport,
+    /// InfluxDB line protocol, one record per datagram with the capture time, for Telegraf and
+    /// `influx write`
+    Influx,
+    /// Parquet file with a stable schema and Snappy compressed columns, for the captures whose
+    /// CSV is slow to load
+    Parquet,
+    /// Arrow IPC file (Feather version 2) with the columns of the Parquet export in record
+    /// batches, for pandas, Polars and DuckDB to memory-map
+    Arrow,
+}
+
+#[derive(Subcommand, Debug)]
+enum Command {
+    /// Reconstruct the process image as of a point in time
+    Snapshot {
+        /// Absolute time (epoch seconds or ISO 8601) or offset from the first packet (e.g. 12.5s)
+        #[arg(long)]
+        at: String,
+    },
+    /// Print the state of every slave as of a point in time: AL state, position, SyncManager
+    /// and FMMU configuration and working counters
+    State {
+        /// Absolute time (epoch seconds or ISO 8601) or offset from the first packet (e.g. 12.5s)
+        #[arg(long)]
+        at: String,
+    },
+    /// Print the SyncManager and FMMU setup of every slave at the end of the capture: the buffer,
+    /// direction and mode of each SyncManager and the logical range each FMMU maps
+    Config,
+    /// Print the AL state timeline: the states the master requests and every change of the AL
+    /// status of each slave, with error flags, AL status codes and the time taken to follow
+    States,
+    /// Report FoE file transfers (firmware updates) with their outcome
+    Foe,
+    /// Write the files of the FoE transfers to disk, reassembled from their data packets
+    ExtractFoe {
+        /// Directory to write the files to, created if missing
+        #[arg(long, default_value = ".")]
+        output_dir: PathBuf,
+    },
+    /// Write the signals of --pdo-map sampled once per cycle, as one wide CSV or as one CSV per
+    /// signal
+    ExtractSignals {
+        /// A signal of the PDO map to write; repeat for more, all of them by default
+        #[arg(long = "signal")]
+        signals: Vec<String>,
+        /// Directory to write one CSV per signal to, created if missing, instead of the wide
+        /// CSV on stdout
+        #[arg(long)]
+        output_dir: Option<PathBuf>,
+    },
+    /// Report the virtual Ethernet traffic each slave exchanges over EoE
+    Eoe {
+        /// Write the reassembled Ethernet frames to this pcapng file
+        #[arg(long)]
+        eoe_out: Option<PathBuf>,
+    },
+    /// Report the PDO mapping of each slave, rebuilt from the SDO downloads in the capture
+    Pdo,
+    /// Estimate the offset and drift between the capture clock and the DC reference clock
+    Dc {
+        /// Report the DC registers of every slave instead: the trend of its system time
+        /// difference, its drift, and the offset, delay and loop time of its clock
+        #[arg(long)]
+        per_slave: bool,
+    },
+    /// Show the most accessed registers, busiest slaves and largest payloads over a sliding window
+    Top {
+        /// Length of the sliding window, in capture time
+        #[arg(long, default_value = "1s", value_parser = clock::parse_duration)]
+        window: u64,
+        /// Entries shown per table
+        #[arg(long, default_value = "10")]
+        count: usize,
+    },
+    /// Match the frames of a second capture taken at the far end of the segment at the same
+    /// time and report the time each frame took to cross it
+    Align {
+        /// The capture of the second tap
+        other: String,
+        /// Largest clock offset between the two captures considered
+        #[arg(long, default_value = "1s", value_parser = clock::parse_duration)]
+        max_offset: u64,
+    },
+    /// Estimate the part of the round trip each slave adds, from the port receive times the
+    /// master latches and reads back
+    Latency,
+    /// Report the error counters the master reads from every slave, by port: how much the
+    /// invalid frame, RX error, forwarded RX error and lost link counters rose over the capture,
+    /// and the invalid frames that arrived corrupted on the cable into the port
+    Errors {
+        /// List every read a counter rose in instead, with its value and rise
+        #[arg(long)]
+        changes: bool,
+    },
+    /// Compare cycle time, traffic mix, register accesses and working counters between two
+    /// windows of the capture
+    DiffWindows {
+        /// First window, as start..end: offsets from the first packet such as 0..60s, or
+        /// absolute times
+        #[arg(long, value_parser = compare::Window::parse)]
+        a: compare::Window,
+        /// Second window, compared to the first
+        #[arg(long, value_parser = compare::Window::parse)]
+        b: compare::Window,
+    },
+    /// Check the master against rules of the EtherCAT specifications, such as the SyncManagers
+    /// being configured before SAFE-OP is requested
+    Lint,
+    /// List the returning datagrams whose working counter differs from the one expected by
+    /// --wkc-config, --eni or --expected-wkc, with their frame and capture time
+    Wkc,
+    /// Check every cycle against the cyclic commands of --eni, listing the commands a cycle
+    /// misses or does not get back, the datagrams the ENI does not have and the lengths and
+    /// working counters differing from it
+    CheckEni,
+    /// Summarize the traffic of every slave addressed by station address or position: reads,
+    /// writes, registers accessed, working counter failures, mailbox messages, the times it
+    /// was first and last addressed and the vendor ID, product code, revision and serial number
+    /// the master read from its SII
+    Slaves,
+    /// List the slaves in the order of the chain, from the scan of the master: the positions
+    /// answering auto-increment datagrams and the station address written to each
+    Topology,
+    /// List the frames lost on the wire, those that came back twice and those that circulated,
+    /// with their capture time, from the index and command of the frames of each master
+    Losses,
+    /// Report the cycle time of the cyclic traffic of each master, with its spread and the
+    /// 99th and 99.9th percentiles of the jitter
+    Stats,
+    /// Print the time series of process data signals
+    Signals {
+        /// A signal as source@address[.bit]:type, such as lrw@0x0012.3:bool or
+        /// 0x1001@0x1100.5:bool for a physical address of a slave; repeat for more signals
+        #[arg(long = "extract-signal", required = true)]
+        signals: Vec<String>,
+        /// Only print the samples where a signal changes
+        #[arg(long)]
+        changes: bool,
+    },
+    /// Write an index of the capture to --index or <file>.idx, for --from and --frames to
+    /// start reading close to the packets they export
+    Index {
+        /// Packets between two entries of the index
+        #[arg(long, default_value = "10000")]
+        every: NonZeroU64,
+    },
+    /// Interleave the packets of the capture and of other captures by capture time into one
+    /// pcapng file, with an interface for each capture: the two ports of a cable-redundant
+    /// master, or the directions of a tap captured on separate NICs
+    Merge {
+        /// The captures merged with the one of --file
+        #[arg(required = true)]
+        others: Vec<String>,
+        /// The pcapng file to write
+        #[arg(long)]
+        out: PathBuf,
+    },
+    /// Keep the last frames in memory and write them, with the frames following, to a pcapng
+    /// file whenever a trigger fires
+    Ring {
+        /// Frames kept in memory in front of a trigger
+        #[arg(long, default_value = "10000")]
+        frames: NonZeroUsize,
+        /// Frames written after the triggering one
+        #[arg(long, default_value = "100")]
+        after: usize,
+        /// Events triggering a dump, separated by commas
+        #[arg(long, value_enum, value_delimiter = ',', required = true)]
+        dump_on: Vec<ring::Trigger>,
+        /// Directory the ring_<frame>.pcapng dumps are written to
+        #[arg(long, default_value = ".")]
+        output_dir: String,
+    },
 }
 
-#[derive(Default)]
-struct EtherNetFrame<'a> {
-    frame_type: u16, //should be 0x88a4(EtherCAT Frame) in this application.
-    ecat_frame: EtherCATFrame<'a>
+/// Settings shared by everything that turns a packet into output rows.
+struct ExportOptions {
+    strict: bool,
+    /// Datagrams exported, of --cmd, --adp and --ado.
+    filter: filter::DatagramFilter,
+    wkc_model: Option<wkc::WkcModel>,
+    /// Rows carry the frame number and the datagram index.
+    frame_number: bool,
+    /// Format of the timestamp column, if there is one.
+    timestamp: Option<clock::TimeFormat>,
+    /// Timestamp of the first packet of the export, which relative timestamps count from.
+    first_ts_ns: OnceLock<u64>,
+    /// Converts capture times for the dc_time column.
+    dc_correlation: Option<dc::Correlation>,
+    frame_composition: bool,
+    irq_flags: bool,
+    frame_hex: bool,
+    datagram_offset: bool,
+    vlan: bool,
+    /// Rows for the network variables, of --network-variables.
+    network_variables: bool,
+    register_names: Option<Arc<registers::RegisterNames>>,
+    mac_format: mac::MacFormat,
+    oui: Option<Arc<mac::OuiTable>>,
+    /// Columns added after the regular ones, in command line order.
+    derived: Vec<derive::DerivedColumn>,
+    /// Signal columns of --pdo-map, after the derived ones.
+    pdo_map: Option<Arc<pdo_map::PdoMap>>,
+    labels: labels::Labels,
+    /// Names of the slave_name column, of --esi-dir.
+    slave_names: Option<esi::SlaveNames>,
+    /// Positions of the position column, of --position.
+    topology: Option<topology::Topology>,
+    /// Names of the capture files of the file column, of --file-column, by file index.
+    file_names: Option<Vec<String>>,
+    /// Rows carry an extra, empty, annotation column.
+    annotate: bool,
+    /// Rows are JSON objects instead of CSV rows.
+    jsonl: bool,
+    delimiter: csv::Delimiter,
+    /// The columns of the rows, in order.
+    columns: Vec<String>,
 }
 
-impl<'a> EtherNetFrame<'a> {
-    fn parse(data: &'a [u8]) -> Self {
-        let mut dst_mac: [u8; 6] = [0; 6];
-        dst_mac.copy_from_slice(&data[0..6]);
+fn main(){
+    if extcap::invoked() {
+        extcap::main();
+        return;
+    }
+    if capabilities::invoked() {
+        capabilities::main();
+        return;
+    }
+    if batch::invoked() {
+        batch::main();
+        return;
+    }
+    if examples::invoked() {
+        examples::main();
+        return;
+    }
+    let args = Args::parse();
+    run(&args, &mut batch::Cache::default());
+}
 
-        let mut src_mac: [u8; 6] = [0; 6];
-        src_mac.copy_from_slice(&data[6..12]);
+/// Runs the export or subcommand of a command line, loading configuration files through the
+/// cache.
+fn run(args: &Args, cache: &mut batch::Cache) {
+    let files = capture::expand_files(&args.file).unwrap_or_else(|e| {
+        eprintln!("{}", e);
+        exit(1);
+    });
+    let file = capture::open(&files[0]).unwrap_or_else(|e| {
+        eprintln!("{}", e);
+        exit(1);
+    });
+    if args.output.is_some() && (args.command.is_some() || matches!(args.format, Format::MarkdownReport | Format::Influx) || args.group_by == cycle::GroupBy::Cycle || args.pair) {
+        eprintln!("--output applies to the csv, jsonl, parquet and arrow exports only");
+        exit(1);
+    }
+    if args.write_pcap.is_some() && (args.command.is_some() || !matches!(args.format, Format::Csv | Format::Jsonl) || args.group_by == cycle::GroupBy::Cycle || args.pair) {
+        eprintln!("--write-pcap applies to the csv and jsonl exports only");
+        exit(1);
+    }
+    if args.warnings_out.is_some() && (args.command.is_some() || matches!(args.format, Format::MarkdownReport) || args.group_by == cycle::GroupBy::Cycle || args.pair) {
+        eprintln!("--warnings-out applies to the csv, jsonl, influx, parquet and arrow exports only");
+        exit(1);
+    }
+    if args.inline_warnings && (args.command.is_some() || !matches!(args.format, Format::Jsonl) || args.group_by == cycle::GroupBy::Cycle || args.pair) {
+        eprintln!("--inline-warnings applies to the jsonl export only, the others take --warnings-out");
+        exit(1);
+    }
+    if !args.columns.is_empty() && (args.command.is_some() || !matches!(args.format, Format::Csv | Format::Jsonl) || args.group_by == cycle::GroupBy::Cycle || args.pair) {
+        eprintln!("--columns applies to the csv and jsonl exports only");
+        exit(1);
+    }
+    if args.stats_out.is_some() && (args.command.is_some() || !matches!(args.format, Format::Csv | Format::Jsonl) || args.group_by == cycle::GroupBy::Cycle || args.pair) {
+        eprintln!("--stats-out applies to the csv and jsonl exports only");
+        exit(1);
+    }
+    if args.metrics_listen.is_some() && (args.command.is_some() || !matches!(args.format, Format::Csv | Format::Jsonl) || args.group_by == cycle::GroupBy::Cycle || args.pair) {
+        eprintln!("--metrics-listen applies to the csv and jsonl exports only");
+        exit(1);
+    }
+    let capture_options = capture::CaptureOptions {
+        recover: args.recover,
+        clock_jump_ns: args.clock_jump,
+        quiet: false,
+        ecat_location: match args.assume_ecat_offset {
+            Some(offset) => locate::EcatLocation::At(offset),
+            None if args.scan_ecat => locate::EcatLocation::Scan(args.scan_offsets.clone()),
+            None => locate::EcatLocation::Standard,
+        },
+        next_files: files[1..].to_vec(),
+    };
 
-        let frame_type = u16::from_be_bytes([data[12], data[13]]);
+    let labels = args.labels.as_deref().map(|path| cache.labels(path).unwrap_or_else(|e| {
+        eprintln!("{}", e);
+        exit(1);
+    })).unwrap_or_default();
 
-        EtherNetFrame {
-            frame_type,
-            ecat_frame: EtherCATFrame::parse(dst_mac, src_mac, &data[14..])
+    match &args.command {
+        Some(Command::Snapshot { at }) => snapshot::run(file, &capture_options, at, &labels),
+        Some(Command::State { at }) => state::run(file, &capture_options, at, &labels),
+        Some(Command::States) => states::run(file, &capture_options, &labels),
+        Some(Command::Config) => slave_config::run(file, &capture_options, &labels),
+        Some(Command::Foe) => foe::run(file, &capture_options, &labels),
+        Some(Command::ExtractFoe { output_dir }) => foe::extract(file, &capture_options, output_dir, &labels),
+        Some(Command::ExtractSignals { signals, output_dir }) => {
+            let Some(path) = args.pdo_map.as_deref() else {
+                eprintln!("The extract-signals subcommand needs --pdo-map");
+                exit(1);
+            };
+            let map = cache.pdo_map(path).unwrap_or_else(|e| {
+                eprintln!("{}", e);
+                exit(1);
+            });
+            pdo_map::extract(file, &capture_options, &map, signals, output_dir.as_deref())
+        }
+        Some(Command::Eoe { eoe_out }) => eoe::run(file, &capture_options, eoe_out.as_deref(), &labels),
+        Some(Command::Top { window, count }) => top::run(file, &capture_options, *window, *count, &labels),
+        Some(Command::Align { other, max_offset }) => align::run(file, &capture_options, other, *max_offset),
+        Some(Command::Ring { frames, after, dump_on, output_dir }) => {
+            let ring_options = ring::RingOptions {
+                frames: frames.get(),
+                after: *after,
+                triggers: dump_on,
+                output_dir: std::path::Path::new(output_dir),
+                irq_storm: args.irq_storm,
+            };
+            ring::run(file, &capture_options, &ring_options)
+        }
+        Some(Command::Signals { signals, changes }) => {
+            let signals: Vec<signal::Signal> = signals.iter().map(|s| signal::Signal::parse(s).unwrap_or_else(|e| {
+                eprintln!("{}", e);
+                exit(1);
+            })).collect();
+            signal::run(file, &capture_options, &signals, *changes)
+        }
+        Some(Command::Latency) => latency::run(file, &capture_options, &labels),
+        Some(Command::Errors { changes }) => port_errors::run(file, &capture_options, *changes, &labels),
+        Some(Command::DiffWindows { a, b }) => {
+            let wkc_model = wkc_model(args, cache);
+            compare::run(file, &capture_options, *a, *b, wkc_model.as_ref())
+        }
+        Some(Command::Lint) => lint::run(file, &capture_options, &labels),
+        Some(Command::Slaves) => {
+            let wkc_model = wkc_model(args, cache);
+            slaves::run(file, &capture_options, wkc_model.as_ref(), &labels)
+        }
+        Some(Command::Topology) => topology::run(file, &capture_options, &labels),
+        Some(Command::Index { every }) => {
+            let path = args.index.clone().unwrap_or_else(|| index::default_path(&files[0]));
+            index::run(file, &files, &capture_options, &path, every.get())
+        }
+        Some(Command::Merge { others, out }) => {
+            let names: Vec<&str> = files[..1].iter().chain(others).map(|path| {
+                std::path::Path::new(path).file_name().and_then(|name| name.to_str()).unwrap_or(path)
+            }).collect();
+            merge::run(file, &capture_options, &names, others, out)
         }
+        Some(Command::Losses) => losses::run(file, &capture_options, args.mac_format),
+        Some(Command::Stats) => stats::run(file, &capture_options, args.mac_format),
+        Some(Command::CheckEni) => {
+            let eni = eni(args, cache).unwrap_or_else(|| {
+                eprintln!("The check-eni subcommand needs --eni");
+                exit(1);
+            });
+            eni::run(file, &capture_options, &eni)
+        }
+        Some(Command::Wkc) => {
+            let wkc_model = wkc_model(args, cache).unwrap_or_else(|| {
+                eprintln!("The wkc subcommand needs --wkc-config, --eni or --expected-wkc");
+                exit(1);
+            });
+            wkc::run(file, &capture_options, &wkc_model, &labels)
+        }
+        Some(Command::Pdo) => pdo::run(file, &capture_options, &labels),
+        Some(Command::Dc { per_slave }) => dc::run(file, &capture_options, args.timing_include_errors, *per_slave, &labels),
+        None => match args.format {
+            Format::Csv if args.group_by == cycle::GroupBy::Cycle => {
+                let wkc_model = wkc_model(args, cache);
+                cycle::run(file, &capture_options, wkc_model.as_ref())
+            }
+            Format::Csv if args.pair => pair::run(file, &capture_options),
+            Format::Csv | Format::Jsonl => export(args, &files, file, &capture_options, labels, cache),
+            Format::MarkdownReport => markdown_report(args, &files, file, &capture_options, labels, cache),
+            Format::Influx => {
+                let wkc_model = wkc_model(args, cache);
+                influx::run(file, &capture_options, &selection(args), warning_log(args, false), wkc_model.as_ref(), &labels)
+            }
+            Format::Parquet => {
+                let wkc_model = wkc_model(args, cache);
+                parquet::run(file, &capture_options, &selection(args), warning_log(args, false), wkc_model.as_ref(), args.output.as_deref())
+            }
+            Format::Arrow => {
+                let wkc_model = wkc_model(args, cache);
+                arrow::run(file, &capture_options, &selection(args), warning_log(args, false), wkc_model.as_ref(), args.output.as_deref())
+            }
+        },
     }
 }
 
-#[derive(Default)]
-struct EtherCATFrame<'a> {
-    header: EtherCATFrameHeader,
-    datagrams: Vec<EtherCATDatagram<'a>>,
+/// The packets and datagrams the export keeps.
+fn selection(args: &Args) -> filter::Selection {
+    filter::Selection {
+        from: args.from,
+        to: args.to,
+        frames: args.frames,
+        datagrams: filter::DatagramFilter { commands: args.cmd.clone(), adp: args.adp.clone(), ado: args.ado.clone() },
+    }
 }
 
-impl<'a> EtherCATFrame<'a> {
-    fn parse(dst_mac: [u8; 6], src_mac: [u8; 6], data: &'a [u8]) -> Self {
-        let len_rsv_type = u16::from_le_bytes([data[0], data[1]]);
-        let length = len_rsv_type & 0x07_FF;
-        let reserved: u8 = (len_rsv_type >> 11) as u8 & 0x1;
-        let ecat_frame_type = (len_rsv_type >> 12) as u8 & 0xF;
-        EtherCATFrame {
-            header: EtherCATFrameHeader {
-                length,
-                reserved,
-                ecat_frame_type
-            },
-            datagrams: EtherCATDatagram::parse_datagrams(dst_mac, src_mac, &data[2..])
+fn export(args: &Args, files: &[String], file: File, capture_options: &capture::CaptureOptions, labels: labels::Labels,
+          cache: &mut batch::Cache) {
+    let wkc_model = wkc_model(args, cache);
+    let mut annotations = args.annotate.as_deref().map(|path| annotations::Annotations::load(path).unwrap_or_else(|e| {
+        eprintln!("{}", e);
+        exit(1);
+    }));
+    let dc_correlation = args.dc_time.then(|| {
+        let correlation = capture::open(&files[0])
+            .and_then(|file| {
+                let options = capture::CaptureOptions { quiet: true, ..capture_options.clone() };
+                dc::Correlation::from_capture(file, &options, args.timing_include_errors)
+            });
+        correlation.map(|(correlation, _)| correlation).unwrap_or_else(|e| {
+            eprintln!("{}", e);
+            exit(1);
+        })
+    });
+
+    let slave_names = args.esi_dir.as_deref().map(|path| {
+        let slave_names = cache.device_catalog(path).and_then(|catalog| {
+            let file = capture::open(&files[0])?;
+            let options = capture::CaptureOptions { quiet: true, ..capture_options.clone() };
+            esi::SlaveNames::from_capture(file, &options, &catalog)
+        });
+        slave_names.map(|(slave_names, _)| slave_names).unwrap_or_else(|e| {
+            eprintln!("{}", e);
+            exit(1);
+        })
+    });
+
+    let topology = args.position.then(|| {
+        let topology = capture::open(&files[0])
+            .and_then(|file| {
+                let options = capture::CaptureOptions { quiet: true, ..capture_options.clone() };
+                topology::Topology::from_capture(file, &options)
+            });
+        topology.map(|(topology, _)| topology).unwrap_or_else(|e| {
+            eprintln!("{}", e);
+            exit(1);
+        })
+    });
+
+    let derived: Vec<derive::DerivedColumn> = args.derive.iter().map(|s| derive::DerivedColumn::parse(s).unwrap_or_else(|e| {
+        eprintln!("{}", e);
+        exit(1);
+    })).collect();
+
+    let resume = args.resume.as_deref().map(|path| checkpoint::Checkpoint::load(path).unwrap_or_else(|e| {
+        eprintln!("{}", e);
+        exit(1);
+    }));
+
+    // without a checkpoint to resume from, the index of the capture skips the packets before
+    // the frames or times exported.
+    let index_path = args.index.clone()
+        .or_else(|| Some(index::default_path(&files[0])).filter(|path| std::path::Path::new(path).exists()));
+    let seek = index_path.filter(|_| resume.is_none() && (args.frames.is_some() || args.from.is_some())).map(|path| {
+        index::CaptureIndex::load(&path).unwrap_or_else(|e| {
+            eprintln!("{}", e);
+            exit(1);
+        })
+    });
+    let seek = seek.filter(|index| {
+        let matches = index.matches(files);
+        if !matches {
+            eprintln!("Ignoring the index, written before the capture changed; run the index subcommand again");
         }
+        matches
+    });
+    let start = seek.as_ref().and_then(|index| index.start(args.frames, args.from));
+    if let Some(start) = &start {
+        eprintln!("Starting after frame {} with the index", start.frame_number());
     }
-}
 
-#[derive(Default)]
-struct EtherCATFrameHeader {
-    length: u16,
-    reserved: u8,
-    ecat_frame_type: u8,
-}
+    let mut registry = columns::Registry::new();
+    if args.frame_number {
+        registry.add("frame_number");
+        registry.add("datagram_index");
+    }
+    if args.timestamp {
+        registry.add("timestamp");
+    }
+    if dc_correlation.is_some() {
+        registry.add("dc_time");
+    }
+    if args.frame_composition {
+        registry.add("datagrams_in_frame");
+        registry.add("frame_composition");
+    }
+    if args.ids {
+        registry.add("datagram_id");
+        registry.add("transaction_id");
+    }
+    if args.mailbox {
+        registry.add("mbx_type");
+        registry.add("mbx_counter");
+    }
+    if args.sdo {
+        // the JSON rows carry the SDO as one object.
+        for name in SDO_COLUMNS {
+            registry.add_for(name, true, false);
+        }
+        registry.add_for("sdo", false, true);
+    }
+    if args.events {
+        registry.add("event");
+    }
+    if args.irq_flags {
+        registry.add("irq_flags");
+    }
+    if args.frame_hex {
+        registry.add("frame_hex");
+    }
+    if args.datagram_offset {
+        registry.add("datagram_offset_in_frame");
+    }
+    if args.vlan {
+        registry.add("vlan_id");
+    }
+    if args.network_variables {
+        for name in ["publisher_id", "nv_index", "nv_hash", "nv_quality"] {
+            registry.add(name);
+        }
+    }
+    let register_names = args.register_names.then(|| match args.registers.as_deref() {
+        Some(path) => cache.register_names(path).unwrap_or_else(|e| {
+            eprintln!("{}", e);
+            exit(1);
+        }),
+        None => Arc::new(registers::RegisterNames::default()),
+    });
+    if register_names.is_some() {
+        registry.add("register_name");
+    }
+    let oui = args.oui.as_deref().map(|path| cache.oui_table(path).unwrap_or_else(|e| {
+        eprintln!("{}", e);
+        exit(1);
+    }));
+    if oui.is_some() {
+        registry.add("master_vendor");
+    }
+    for column in &derived {
+        registry.add(column.name.as_str());
+    }
+    let pdo_map = args.pdo_map.as_deref().map(|path| cache.pdo_map(path).unwrap_or_else(|e| {
+        eprintln!("{}", e);
+        exit(1);
+    }));
+    for name in pdo_map.iter().flat_map(|map| map.names()) {
+        registry.add(name);
+    }
+    if labels.header() != "" {
+        registry.add("label");
+    }
+    if slave_names.is_some() {
+        registry.add("slave_name");
+    }
+    if topology.is_some() {
+        registry.add("position");
+    }
+    if args.file_column {
+        registry.add("file");
+    }
+    let jsonl = matches!(args.format, Format::Jsonl);
+    let columns = registry.select(&args.columns, jsonl).unwrap_or_else(|e| {
+        eprintln!("{}", e);
+        exit(1);
+    });
+    // annotation rows leave every regular column empty.
+    let annotation_prefix = args.delimiter.as_char().to_string().repeat(columns.len());
+    let annotate = annotations.is_some() || args.capture_comments;
+    let header = args.delimiter.record(columns.iter().map(String::as_str).chain(annotate.then_some("annotation")));
+    let annotation_row = |note: &str| {
+        if jsonl {
+            format!("{}\n", serde_json::json!({ "annotation": note }))
+        } else {
+            format!("{}{}\n", annotation_prefix, args.delimiter.field(note))
+        }
+    };
+    let output = args.output.as_deref();
+    // diagnostics go to stdout once the export has a file of its own, JSON Lines carry records only.
+    let unknown_block = |pipeline: &mut pipeline::Pipeline| match output {
+        Some(_) => sink::outln!("unknown block"),
+        None if jsonl => {}
+        None => pipeline.text("unknown block\n".to_string()),
+    };
+    if let Some(resume) = &resume {
+        eprintln!("Resuming after frame {}", resume.reader.frame_number());
+        if let Err(e) = checkpoint::truncate_output(output, resume.output_bytes) {
+            eprintln!("{}", e);
+        }
+        if let Some(annotations) = &mut annotations {
+            annotations.skip(resume.annotations_taken);
+        }
+    }
+    let out = sink::open_output(output, resume.is_some()).unwrap_or_else(|e| {
+        eprintln!("{}", e);
+        exit(1);
+    });
+    let sink = match (args.backpressure, args.output_buffer) {
+        (sink::Backpressure::Block, None) => sink::Sink::direct(out),
+        (policy, buffer) => {
+            let buffer = buffer.unwrap_or_else(|| sink::parse_size(sink::DEFAULT_BUFFER).unwrap());
+            sink::Sink::buffered(out, policy, buffer, args.spill_dir.as_deref())
+        }
+    };
+    if resume.is_none() && !jsonl {
+        sink.write(format!("{}\n", header));
+    }
+    // output of the interrupted export, which the sink does not count.
+    let output_base = resume.as_ref().map_or(0, |resume| resume.output_bytes);
 
-#[derive(Default, Debug)]
-struct EtherCATDatagram<'a> {
-    header: EtherCATDatagramHeader,
-    data: &'a [u8],
-    wkc: u16,
-}
+    let selection = selection(args);
+    let options = Arc::new(ExportOptions {
+        strict: args.strict,
+        filter: selection.datagrams.clone(),
+        wkc_model,
+        dc_correlation,
+        frame_composition: args.frame_composition,
+        irq_flags: args.irq_flags,
+        frame_hex: args.frame_hex,
+        datagram_offset: args.datagram_offset,
+        vlan: args.vlan,
+        network_variables: args.network_variables,
+        register_names,
+        mac_format: args.mac_format,
+        oui,
+        derived,
+        pdo_map,
+        labels,
+        slave_names,
+        topology,
+        file_names: args.file_column.then(|| files.iter().map(|path| {
+            std::path::Path::new(path).file_name().map_or_else(|| path.clone(), |name| name.to_string_lossy().into_owned())
+        }).collect()),
+        annotate,
+        jsonl,
+        delimiter: args.delimiter,
+        columns,
+        frame_number: args.frame_number,
+        timestamp: args.timestamp.then_some(args.time_format),
+        first_ts_ns: resume.as_ref().and_then(|resume| resume.first_ts_ns)
+            .or_else(|| seek.as_ref().and_then(|index| index.first_ts_ns()))
+            .map_or_else(OnceLock::new, OnceLock::from),
+    });
+    let export_options = Arc::clone(&options);
+    let export = Arc::new(move |info: &capture::PacketInfo, packet_data: &[u8], cells: &[columns::Row<'static>]| {
+        export_packet(info, packet_data, cells, &export_options)
+    });
+    let mut pipeline = pipeline::Pipeline::new(args.jobs.get(), !args.unordered, export, sink);
 
-impl<'a> EtherCATDatagram<'a> {
-    fn parse_datagrams(dst_mac: [u8; 6], src_mac: [u8; 6], data_buf: &'a[u8]) -> Vec<Self> {
-        let mut datagrams = Vec::new();
-        let mut next_datagram_offset = 0;
-        loop {
-            let datagram = EtherCATDatagram::parse_one_datagram(&data_buf[next_datagram_offset..]);
-            let is_last_datagram = datagram.is_last_datagram();
-            next_datagram_offset += datagram.size();
-
-            //datagrams.push(datagram);
-            let dst_mac_str = format!("{:x} {:x} {:x} {:x} {:x} {:x}", dst_mac[0], dst_mac[1], dst_mac[2], dst_mac[3], dst_mac[4], dst_mac[5]);
-            let src_mac_str = format!("{:x} {:x} {:x} {:x} {:x} {:x}", src_mac[0], src_mac[1], src_mac[2], src_mac[3], src_mac[4], src_mac[5]);
-            println!("{},{},{}",dst_mac_str, src_mac_str, datagram);
-
-            if is_last_datagram {
-                break;
+    let mut irq_monitor = match &resume {
+        Some(resume) => resume.irq_monitor.clone(),
+        None => irq::IrqMonitor::default(),
+    };
+    irq_monitor.set_threshold(args.irq_storm);
+    let mut reboot_monitor = match &resume {
+        Some(resume) => resume.reboot_monitor.clone(),
+        None => reboot::RebootMonitor::default(),
+    };
+    let mut circulation_monitor = match &resume {
+        Some(resume) => resume.circulation_monitor.clone(),
+        None => circulation::CirculationMonitor::default(),
+    };
+    let mut mailbox_monitor = match &resume {
+        Some(resume) => resume.mailbox_monitor.clone(),
+        None => mailbox::MailboxMonitor::default(),
+    };
+    let mut throttle = match &resume {
+        Some(resume) => resume.throttle.clone(),
+        None => throttle::Throttle::default(),
+    };
+    throttle.set_limits(args.rate_limit, args.only_changes);
+    let mut malformed_frames = 0;
+    let mut other_frames = other::OtherFrames::default();
+    let mut configs = match &resume {
+        Some(resume) => resume.configs.clone(),
+        None => slave_config::SlaveConfigs::default(),
+    };
+    let mut transactions = match &resume {
+        Some(resume) => resume.transactions.clone(),
+        None => transaction::Transactions::default(),
+    };
+    let mut sequences = match &resume {
+        Some(resume) => resume.sequences.clone(),
+        None => mailbox::Sequences::default(),
+    };
+    let mut health = args.stats_out.clone().map(|path| health::HealthWriter::new(path, args.stats_interval));
+    let metrics = args.metrics_listen.as_deref().map(|address| {
+        let metrics = Arc::new(Mutex::new(metrics::Metrics::default()));
+        metrics::serve(address, metrics.clone()).unwrap_or_else(|e| {
+            eprintln!("{}", e);
+            exit(1);
+        });
+        metrics
+    });
+    let mut warning_log = warning_log(args, resume.is_some());
+    let mut carve = args.write_pcap.as_deref().map(|path| {
+        let carve = match resume.as_ref().map(|resume| resume.carve.clone()) {
+            Some(Some(progress)) => carve::Carve::resume(path, progress),
+            Some(None) => Err(format!("The interrupted export did not write {}", path.display())),
+            None => carve::Carve::create(path),
+        };
+        carve.unwrap_or_else(|e| {
+            eprintln!("{}", e);
+            exit(1);
+        })
+    });
+    let checkpoint_interval = Duration::from_nanos(args.checkpoint_interval);
+    let mut last_checkpoint = Instant::now();
+    let save_checkpoint = |path: &str, state: &capture::ReaderState, output_bytes, annotations_taken,
+                           irq_monitor: &irq::IrqMonitor, reboot_monitor: &reboot::RebootMonitor,
+                           circulation_monitor: &circulation::CirculationMonitor, configs: &slave_config::SlaveConfigs,
+                           transactions: &transaction::Transactions, sequences: &mailbox::Sequences,
+                           mailbox_monitor: &mailbox::MailboxMonitor, throttle: &throttle::Throttle,
+                           carve: Option<&mut carve::Carve>| {
+        // the checkpoint covers the packets carved up to it, once they are in the file.
+        let carve = match carve.map(carve::Carve::progress).transpose() {
+            Ok(carve) => carve,
+            Err(e) => {
+                eprintln!("Cannot write {} : {}", args.write_pcap.as_ref().unwrap().display(), e);
+                return;
             }
-        }
-        datagrams
-    }
-    fn parse_one_datagram(data_buf: &'a[u8]) -> Self {
-        let cmd: EtherCATCommand = match data_buf[0] {
-            0 => EtherCATCommand::NOP,
-            1 => EtherCATCommand::APRD,
-            2 => EtherCATCommand::APWR,
-            3 => EtherCATCommand::APRW,
-            4 => EtherCATCommand::FPRD,
-            5 => EtherCATCommand::FPWR,
-            6 => EtherCATCommand::FPRW,
-            7 => EtherCATCommand::BRD,
-            8 => EtherCATCommand::BWR,
-            9 => EtherCATCommand::BRW,
-            10 => EtherCATCommand::LRD,
-            11 => EtherCATCommand::LWR,
-            12 => EtherCATCommand::LRW,
-            13 => EtherCATCommand::ARMW,
-            14 => EtherCATCommand::FRMW,
-            _ => EtherCATCommand::UNKNOWN,
         };
-        let index = data_buf[1];
-        let slave_addr = u16::from_le_bytes([data_buf[2], data_buf[3]]);
-        let offset_addr = u16::from_le_bytes([data_buf[4], data_buf[5]]);
-        let len_rtr_last = u16::from_le_bytes([data_buf[6], data_buf[7]]);
-        let length = len_rtr_last & 0x07_FF;
-        let round_trip = (len_rtr_last >> 14) as u8 & 0x1;
-        let last_indicator = (len_rtr_last >> 15) as u8 & 0x1;
-        let irq = u16::from_le_bytes([data_buf[8], data_buf[9]]);
-        let data = &data_buf[10..];
-        let wkc = u16::from_le_bytes([data_buf[10 + length as usize], data_buf[11 + length as usize]]);
-
-        let datagram = EtherCATDatagram {
-            header: EtherCATDatagramHeader {
-                cmd,
-                index,
-                slave_addr,
-                offset_addr,
-                length,
-                round_trip,
-                last_indicator,
-                irq
-            },
-            data,
-            wkc
+        let checkpoint = checkpoint::Checkpoint {
+            reader: state.clone(),
+            output_bytes,
+            annotations_taken,
+            irq_monitor: irq_monitor.clone(),
+            reboot_monitor: reboot_monitor.clone(),
+            circulation_monitor: circulation_monitor.clone(),
+            configs: configs.clone(),
+            transactions: transactions.clone(),
+            sequences: sequences.clone(),
+            mailbox_monitor: mailbox_monitor.clone(),
+            throttle: throttle.clone(),
+            carve,
+            first_ts_ns: options.first_ts_ns.get().copied(),
         };
+        if let Err(e) = checkpoint.save(path) {
+            eprintln!("{}", e);
+        }
+    };
 
-        //println!("{:x?}", datagram);
-
-        datagram
+    let result = capture::read_from(file, capture_options, resume.map(|resume| resume.reader).or(start), |item, state| {
+        match item {
+            capture::Item::Packet(packet) => {
+                let first_ts_ns = *options.first_ts_ns.get_or_init(|| packet.info.ts_ns);
+                if let Some(health) = &mut health {
+                    if let Err(e) = health.packet(packet.info.frame_number, packet.info.ts_ns, &state.summary) {
+                        eprintln!("{}", e);
+                    }
+                }
+                let frame_number = packet.info.frame_number;
+                let window = selection.window(frame_number, packet.info.ts_ns, first_ts_ns);
+                if window == filter::Window::After {
+                    return ControlFlow::Break(());
+                }
+                if window == filter::Window::Before {
+                    // the notes before the window are left out with its packets.
+                    if let Some(annotations) = &mut annotations {
+                        annotations.due(packet.info.ts_ns);
+                    }
+                    return ControlFlow::Continue(());
+                }
+                if let Some(annotations) = &mut annotations {
+                    for (_, note) in annotations.due(packet.info.ts_ns) {
+                        pipeline.text(annotation_row(note));
+                    }
+                }
+                if let Some(carve) = &mut carve {
+                    let datagrams = || packet.data.and_then(EtherNetFrame::parse_ethercat).map(|frame| frame.ecat_frame.datagrams);
+                    if options.filter.is_empty() || datagrams().is_some_and(|datagrams| datagrams.iter().any(|d| options.filter.matches(&d.header))) {
+                        if let Err(e) = carve.packet(&packet, state) {
+                            pipeline.flush();
+                            eprintln!("Cannot write {} : {}", args.write_pcap.as_ref().unwrap().display(), e);
+                            exit(1);
+                        }
+                    }
+                }
+                if let Some(data) = packet.data.filter(|data| !is_ethercat(data)) {
+                    other_frames.skip(data);
+                    return ControlFlow::Continue(());
+                }
+                let parsed = match packet.data.map(EtherNetFrame::parse) {
+                    Some(Err(e)) => {
+                        let record = warnings::Record::malformed_frame(packet.info.frame_number, &e);
+                        log_warning(&mut warning_log, args.inline_warnings.then_some(&mut pipeline), record);
+                        if args.strict {
+                            pipeline.flush();
+                            eprintln!("Frame {} : {}", packet.info.frame_number, e);
+                            exit(1);
+                        }
+                        eprintln!("Frame {} : {}, skipped", packet.info.frame_number, e);
+                        malformed_frames += 1;
+                        return ControlFlow::Continue(());
+                    }
+                    parsed => parsed.and_then(Result::ok),
+                };
+                let frame = parsed.as_ref().filter(|frame| frame.carries_ethercat());
+                if let Some(frame) = frame.filter(|frame| frame.ecat_frame.content.is_some()) {
+                    let frame_type = frame.ecat_frame.header.frame_type();
+                    if !(args.network_variables && frame_type == FrameType::NetworkVariables) {
+                        other_frames.skip_frame_type(frame_type);
+                    }
+                }
+                if let (Some(metrics), Some(frame)) = (&metrics, frame) {
+                    metrics.lock().unwrap().frame(packet.info.ts_ns, frame, options.wkc_model.as_ref());
+                }
+                let mut cells = Vec::new();
+                if let Some(frame) = frame {
+                    let frame_number = packet.info.frame_number;
+                    let events = irq_monitor.check(frame_number, frame).into_iter()
+                        .chain(reboot_monitor.check(packet.info.ts_ns, frame))
+                        .chain(circulation_monitor.check(frame))
+                        .chain(emergency::check(frame, &mut configs))
+                        .chain(mailbox_monitor.check(frame));
+                    for kind in events {
+                        log_warning(&mut warning_log, args.inline_warnings.then_some(&mut pipeline), warnings::Record::event("monitor", frame_number, &kind));
+                        eprintln!("{}", events::Event { frame_number, kind });
+                    }
+                    if args.ids || args.mailbox || args.sdo || args.events {
+                        cells = mailbox_cells(frame_number, frame, args, &configs, &mut transactions, &mut sequences);
+                    }
+                }
+                if let Some(parsed) = parsed.as_ref().filter(|_| warning_log.is_some() || args.inline_warnings) {
+                    for record in warnings::frame_records(&packet.info, parsed, options.wkc_model.as_ref()) {
+                        log_warning(&mut warning_log, args.inline_warnings.then_some(&mut pipeline), record);
+                    }
+                }
+                match packet.data {
+                    Some(packet_data) if throttle.pass(packet.info.ts_ns, frame) => {
+                        pipeline.packet(&packet.info, packet_data, cells)
+                    }
+                    Some(_) => {}
+                    None => unknown_block(&mut pipeline),
+                }
+            },
+            capture::Item::Event(event) => {
+                let record = warnings::Record::event("capture", event.frame_number, &event.kind);
+                log_warning(&mut warning_log, args.inline_warnings.then_some(&mut pipeline), record)
+            }
+            capture::Item::CorruptBlock { offset, reason, skipped } => {
+                let record = warnings::Record::corrupt_block(state.frame_number(), offset, reason, skipped);
+                log_warning(&mut warning_log, args.inline_warnings.then_some(&mut pipeline), record)
+            }
+            capture::Item::Comment(note) if args.capture_comments => {
+                pipeline.text(annotation_row(note))
+            }
+            capture::Item::CustomBlock { pen, text } if args.capture_comments => {
+                let note = match text {
+                    Some(text) => format!("custom block of PEN {}: {}", pen, text),
+                    None => format!("custom block of PEN {}", pen),
+                };
+                pipeline.text(annotation_row(&note))
+            }
+            capture::Item::Comment(_) => {}
+            capture::Item::CustomBlock { .. } | capture::Item::OtherBlock => unknown_block(&mut pipeline),
+        }
+        if let Some(path) = &args.checkpoint {
+            if last_checkpoint.elapsed() >= checkpoint_interval {
+                let annotations_taken = annotations.as_ref().map_or(0, |annotations| annotations.taken());
+                if let Some(Err(e)) = warning_log.as_mut().map(|log| log.flush()) {
+                    eprintln!("{}", e);
+                }
+                save_checkpoint(path, state, output_base + pipeline.flush(), annotations_taken, &irq_monitor, &reboot_monitor,
+                                &circulation_monitor, &configs, &transactions, &sequences, &mailbox_monitor, &throttle, carve.as_mut());
+                last_checkpoint = Instant::now();
+            }
+        }
+        ControlFlow::Continue(())
+    });
+    let state = match result {
+        Ok(state) => state,
+        Err(e) => {
+            pipeline.finish();
+            eprintln!("{}", e);
+            exit(1);
+        }
+    };
+    // the final checkpoint lets an export of a capture that has grown since pick up the rest.
+    if let Some(path) = &args.checkpoint {
+        let annotations_taken = annotations.as_ref().map_or(0, |annotations| annotations.taken());
+        save_checkpoint(path, &state, output_base + pipeline.flush(), annotations_taken, &irq_monitor, &reboot_monitor,
+                        &circulation_monitor, &configs, &transactions, &sequences, &mailbox_monitor, &throttle, carve.as_mut());
     }
-
-    fn is_last_datagram(&self) -> bool {
-        self.header.last_indicator == 0
+    let summary = state.summary;
+    if let Some(Err(e)) = warning_log.as_mut().map(|log| log.flush()) {
+        eprintln!("{}", e);
     }
 
-    fn size(&self) -> usize {
-        10 + self.header.length as usize + 2 // 10 indicates header, 2 indicates wkc.
+    if let Some(annotations) = annotations.as_mut().filter(|_| args.to.is_none() && args.frames.is_none()) {
+        for (_, note) in annotations.rest() {
+            pipeline.text(annotation_row(note));
+        }
+    }
+    pipeline.finish();
+    if let (Some(carve), Some(path)) = (carve, &args.write_pcap) {
+        let written = carve.written;
+        match carve.finish() {
+            Ok(()) => eprintln!("{} packets written to {}", written, path.display()),
+            Err(e) => eprintln!("Cannot write {} : {}", path.display(), e),
+        }
+    }
+    if let Some(Err(e)) = health.as_mut().map(|health| health.finish(&summary)) {
+        eprintln!("{}", e);
+    }
+    if jsonl && output.is_none() {
+        eprintln!("num_blocks: {}", summary.num_blocks);
+    } else {
+        sink::outln!("num_blocks: {}", summary.num_blocks);
+    }
+    if malformed_frames > 0 {
+        eprintln!("{} malformed frames skipped", malformed_frames);
     }
+    other_frames.report(args.include_other);
+    summary.report();
+    circulation_monitor.report();
+    throttle.report();
 }
 
-impl Display for EtherCATDatagram<'_> {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        write!(f, "{},{},{:x?}", self.header, self.wkc, self.data)
+/// The expected working counters of --wkc-config, --eni and --expected-wkc, if any were given.
+fn wkc_model(args: &Args, cache: &mut batch::Cache) -> Option<wkc::WkcModel> {
+    let mut model = args.wkc_config.as_deref().map(|path| cache.wkc_model(path).unwrap_or_else(|e| {
+        eprintln!("{}", e);
+        exit(1);
+    }));
+    if let Some(eni) = eni(args, cache) {
+        model = Some(model.unwrap_or_default().with_eni(&eni));
     }
+    if args.expected_wkc.is_empty() {
+        return model;
+    }
+    Some(model.unwrap_or_default().with_expectations(&args.expected_wkc))
 }
 
-#[derive(Default, Debug)]
-struct EtherCATDatagramHeader {
-    cmd: EtherCATCommand,
-    index: u8,
-    slave_addr: u16,
-    offset_addr: u16,
-    length: u16,
-    round_trip: u8,
-    last_indicator: u8,
-    irq: u16,
+fn eni(args: &Args, cache: &mut batch::Cache) -> Option<eni::Eni> {
+    args.eni.as_deref().map(|path| cache.eni(path).unwrap_or_else(|e| {
+        eprintln!("{}", e);
+        exit(1);
+    }))
 }
 
-impl Display for EtherCATDatagramHeader {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        write!(f, "{},{},{},{:x},{},{},{},{:x}", self.cmd, self.index, self.slave_addr, self.offset_addr, self.length, self.round_trip, self.last_indicator, self.irq)
-    }
+fn markdown_report(args: &Args, files: &[String], file: File, capture_options: &capture::CaptureOptions, labels: labels::Labels,
+                   cache: &mut batch::Cache) {
+    let wkc_model = wkc_model(args, cache);
+    let first_name = std::path::Path::new(&files[0]).file_name().and_then(|name| name.to_str()).unwrap_or(&files[0]);
+    let capture_name = match files.len() {
+        1 => first_name.to_string(),
+        count => format!("{} and {} more files", first_name, count - 1),
+    };
+    let device_catalog = args.esi_dir.as_deref().map(|path| cache.device_catalog(path).unwrap_or_else(|e| {
+        eprintln!("{}", e);
+        exit(1);
+    }));
+    let options = report::ReportOptions { capture_name: &capture_name, irq_storm: args.irq_storm, wkc_model, labels, device_catalog };
+    report::run(file, capture_options, &options);
 }
 
-#[derive(Default, Debug)]
-enum EtherCATCommand {
-    NOP = 0,
-    APRD,
-    APWR,
-    APRW,
-    FPRD,
-    FPWR,
-    FPRW,
-    BRD,
-    BWR,
-    BRW,
-    LRD,
-    LWR,
-    LRW,
-    ARMW,
-    FRMW,
-    #[default] UNKNOWN,
+/// The `--warnings-out` sidecar, if any, appended to when `resume`.
+fn warning_log(args: &Args, resume: bool) -> Option<warnings::WarningLog> {
+    args.warnings_out.as_deref().map(|path| warnings::WarningLog::open(path, resume).unwrap_or_else(|e| {
+        eprintln!("{}", e);
+        exit(1);
+    }))
 }
 
-impl Display for EtherCATCommand{
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        let cmd_str = match self {
-            Self::NOP => "NOP",
-            Self::APRD => "APRD",
-            Self::APWR => "APWR",
-            Self::APRW => "APRW",
-            Self::FPRD => "FPRD",
-            Self::FPWR => "FPWR",
-            Self::FPRW => "FPRW",
-            Self::BRD => "BRD",
-            Self::BWR => "BWR",
-            Self::BRW => "BRW",
-            Self::LRD => "LRD",
-            Self::LWR => "LWR",
-            Self::LRW => "LRW",
-            Self::ARMW => "ARMW",
-            Self::FRMW => "FRMW",
-            Self::UNKNOWN => "UNKNOWN"
-        };
+/// Writes a record to the `--warnings-out` sidecar, if any, and into the output through
+/// `pipeline` for `--inline-warnings`.
+fn log_warning(log: &mut Option<warnings::WarningLog>, pipeline: Option<&mut pipeline::Pipeline>, record: warnings::Record) {
+    if let Some(pipeline) = pipeline {
+        pipeline.text(record.json_line());
+    }
+    if let Some(Err(e)) = log.as_mut().map(|log| log.record(record)) {
+        eprintln!("{}", e);
+    }
+}
+
+/// The cells of --ids, --mailbox, --sdo and --events of every datagram of a frame, which follow the
+/// SyncManager configuration, once the frame is applied to it, and the mailbox transactions from
+/// frame to frame: CSV cells, or for JSON Lines an object with the fields of the columns.
+fn mailbox_cells(
+    frame_number: u64,
+    frame: &EtherNetFrame,
+    args: &Args,
+    configs: &slave_config::SlaveConfigs,
+    transactions: &mut transaction::Transactions,
+    sequences: &mut mailbox::Sequences,
+) -> Vec<columns::Row<'static>> {
+    use serde_json::json;
 
-        write!(f, "{}", cmd_str)
+    let returned = frame.is_returned();
+    let mut cells = Vec::new();
+    for (position, datagram) in frame.ecat_frame.datagrams.iter().enumerate() {
+        let message = mailbox::extract(datagram, returned, configs);
+        let mut row = columns::Row::default();
+        if args.ids {
+            let id = transaction::datagram_id(frame_number, position);
+            let transaction = message.as_ref().map(|mailbox| transactions.message(id.clone(), mailbox));
+            row.text("datagram_id", Some(id));
+            row.text("transaction_id", transaction);
+        }
+        if args.mailbox {
+            row.text("mbx_type", message.as_ref().map(|mailbox| mailbox.mbx_type.to_string()));
+            row.number("mbx_counter", message.as_ref().map(|mailbox| mailbox.counter));
+        }
+        if args.sdo {
+            let sdo = message.as_ref().and_then(coe::sdo_message);
+            let service = sdo.map(|sdo| if sdo.service == coe::CoeService::SdoRequest { "request" } else { "response" });
+            let data = sdo.and_then(|sdo| sdo.expedited_data).map(hex);
+            let abort = sdo.and_then(|sdo| sdo.abort_code).and_then(coe::abort_message);
+            let sdo_cells = [
+                service.map(str::to_string), sdo.map(|sdo| sdo.command.to_string()),
+                sdo.and_then(|sdo| sdo.index).map(|index| format!("0x{:04x}", index)),
+                sdo.and_then(|sdo| sdo.subindex).map(|subindex| subindex.to_string()),
+                data.clone(),
+                sdo.and_then(|sdo| sdo.abort_code).map(|code| format!("0x{:08x}", code)),
+                abort.map(str::to_string),
+            ];
+            for (name, cell) in SDO_COLUMNS.into_iter().zip(sdo_cells) {
+                row.text(name, cell);
+            }
+            let object = sdo.map(|sdo| json!({
+                "service": service,
+                "command": sdo.command.to_string(),
+                "index": sdo.index,
+                "subindex": sdo.subindex,
+                "complete_access": sdo.complete_access,
+                "data": data,
+                "abort_code": sdo.abort_code,
+                "abort": abort,
+            }));
+            row.push("sdo", object.as_ref().map(|object| object.to_string()).unwrap_or_default(), object);
+        }
+        if args.events {
+            let events: Vec<String> = message.as_ref().and_then(emergency::decode).into_iter()
+                .chain(sequences.datagram(datagram, returned, configs))
+                .map(|kind| kind.to_string())
+                .collect();
+            row.text("event", (!events.is_empty()).then(|| events.join("; ")));
+        }
+        cells.push(row);
     }
+    cells
 }
 
-fn main(){
-    let args = Args::parse();
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{:02x}", byte)).collect()
+}
 
-    let file = match File::open(&args.file) {
-        Ok(file) => file,
-        Err(e) => {
-            println!("Cannot open file {} : {}", args.file, e);
-            exit(1);
+/// A numeric cell as a JSON number, null if it is empty or not a number.
+fn json_number(cell: &str) -> serde_json::Value {
+    match (cell.parse::<i64>(), cell.parse::<f64>()) {
+        (Ok(integer), _) => integer.into(),
+        (_, Ok(float)) => serde_json::Number::from_f64(float).map_or(serde_json::Value::Null, serde_json::Value::Number),
+        _ => serde_json::Value::Null,
+    }
+}
+
+/// Formats the CSV rows of one captured packet. In strict mode, an inconsistent frame is an error.
+fn export_packet(info: &capture::PacketInfo, packet_data: &[u8], cells: &[columns::Row], options: &ExportOptions) -> Result<String, String> {
+    let ethernet_frame = EtherNetFrame::parse(packet_data).map_err(|e| format!("Frame {} : {}", info.frame_number, e))?;
+    let issues = validate::check_frame(&ethernet_frame);
+    if options.strict && !issues.is_empty() {
+        let errors: Vec<String> = issues.iter().map(|issue| format!("Frame {} : {}", info.frame_number, issue)).collect();
+        return Err(errors.join("\n"));
+    }
+    let dst_mac_str = mac::format(&ethernet_frame.dst_mac, options.mac_format);
+    let src_mac_str = mac::format(&ethernet_frame.src_mac, options.mac_format);
+    let master_vendor = options.oui.as_ref().map(|oui| oui.vendor(&ethernet_frame.master_mac()));
+    let datagrams = &ethernet_frame.ecat_frame.datagrams;
+    let composition = options.frame_composition.then(|| {
+        let commands: Vec<String> = datagrams.iter().map(|datagram| datagram.header.cmd.to_string()).collect();
+        commands.join("+")
+    });
+    let frame_hex = options.frame_hex.then(|| hex(packet_data));
+    let vlan_ids = options.vlan.then(|| {
+        let ids: Vec<String> = ethernet_frame.vlan_ids.iter().map(u16::to_string).collect();
+        ids.join(".")
+    });
+    let datagram_offsets = ethernet_frame.ecat_frame.datagram_offsets();
+    let time = value::Value::Time(info.ts_ns).render(value::Style::Csv);
+    // the fields of the frame, which every row of it has.
+    let mut frame_row = columns::Row::default();
+    frame_row.number("frame", Some(info.frame_number));
+    frame_row.text("time", Some(time.as_str()));
+    frame_row.number("time_ns", Some(info.ts_ns));
+    frame_row.number("ts", Some(info.ts_ns));
+    frame_row.text("dst_mac", Some(dst_mac_str.as_str()));
+    frame_row.text("src_mac", Some(src_mac_str.as_str()));
+    if options.frame_number {
+        frame_row.number("frame_number", Some(info.frame_number));
+    }
+    if let Some(format) = options.timestamp {
+        let first_ts_ns = options.first_ts_ns.get().copied().unwrap_or(info.ts_ns);
+        frame_row.text("timestamp", Some(clock::format_timestamp(info.ts_ns, first_ts_ns, format)));
+    }
+    if let Some(correlation) = &options.dc_correlation {
+        frame_row.value("dc_time", Some(value::Value::Time(correlation.dc_time(info.ts_ns))), value::Style::Csv);
+    }
+    if let Some(frame_hex) = &frame_hex {
+        frame_row.text("frame_hex", Some(frame_hex.as_str()));
+    }
+    if let Some(vlan_ids) = &vlan_ids {
+        frame_row.push("vlan_id", vlan_ids.as_str(), ethernet_frame.vlan_ids.clone());
+    }
+    if let Some(vendor) = master_vendor {
+        frame_row.text("master_vendor", vendor);
+    }
+    if let Some(names) = &options.file_names {
+        frame_row.text("file", names.get(info.file_index).map(String::as_str));
+    }
+    let mut rows = String::new();
+    let mut write_row = |row: &columns::Row| {
+        if options.jsonl {
+            rows += &row.json(&options.columns);
+        } else {
+            rows += &row.csv(&options.columns, options.delimiter);
+            if options.annotate {
+                rows.push(options.delimiter.as_char());
+            }
         }
+        rows.push('\n');
     };
+    for (i, datagram) in datagrams.iter().enumerate() {
+        if !options.filter.matches(&datagram.header) {
+            continue;
+        }
+        let header = &datagram.header;
+        let datagram_offset = ethernet_frame.ecat_offset + datagram_offsets[i];
+        let mut warnings = validate::warnings_for_datagram(&issues, i);
+        let mut push_warning = |code: &str| {
+            if !warnings.is_empty() {
+                warnings.push(';');
+            }
+            warnings.push_str(code);
+        };
+        let expected_wkc = options.wkc_model.as_ref().filter(|_| ethernet_frame.is_returned()).and_then(|model| model.expected(header));
+        if expected_wkc.is_some_and(|expected| expected != datagram.wkc) {
+            push_warning("wkc_mismatch");
+        }
+        info.link_errors.codes().into_iter().for_each(push_warning);
+        let slave = labels::addressed_slave(header, ethernet_frame.is_returned());
 
-    let mut num_blocks = 0;
-    let mut reader = PcapNGReader::new(65536, file).expect("PcapNGReader");
-    let mut if_linktypes = Vec::new();
-
-    fn get_ethernet_packetdata(raw_data: &[u8], linktype: Linktype, len: u32) -> Option<&[u8]> {
-        match pcap_parser::data::get_packetdata(raw_data, linktype, len as usize) {
-            Some(packet_data) => match packet_data {
-                data::PacketData::L2(packet_data) => Some(packet_data),
-                _ => None,
-            },
-            None => None,
-        }
-    }
-
-    println!("dst_mac,src_mac,cmd,index,adp,ado,length,round_trip,last_ind,irq,wkc,data");
-
-    loop {
-        match reader.next() {
-            Ok((offset, block)) => {
-                match block {
-                    PcapBlockOwned::NG(Block::SectionHeader(_shb)) => {
-                        //println!("got SHB");
-                        if_linktypes = Vec::new();
-                    },
-                    PcapBlockOwned::NG(Block::InterfaceDescription(idb)) => {
-                        //println!("got IDB");
-                        if_linktypes.push(idb.linktype);
-                    },
-                    PcapBlockOwned::NG(Block::EnhancedPacket(epb)) => {
-                        //println!("got EPB");
-                        assert!((epb.if_id as usize) < if_linktypes.len());
-                        let linktype = if_linktypes[epb.if_id as usize];
-                        match get_ethernet_packetdata(epb.data, linktype, epb.caplen) {
-                            Some(packet_data) => {
-                                let ethernet_frame = EtherNetFrame::parse(packet_data);
-                                // println!("dst_mac = {:x?}", ethernet_frame.dst_mac);
-                                // println!("src_mac = {:x?}", ethernet_frame.src_mac);
-                                // println!("frame_type = {:x?}", ethernet_frame.frame_type);
-                                // println!("len = {}", ethernet_frame.ecat_frame.header.length);
-                            },
-                            None => println!("unknown block"),
-                        }
-                    },
-                    _ => {
-                        println!("unknown block");
-                    },
-                }
-                num_blocks += 1;
-                reader.consume(offset);
-            },
-            Err(PcapError::Eof) => break,
-            Err(PcapError::Incomplete(_)) => {
-                reader.refill().unwrap();
-            },
-            Err(e) => panic!("error while reading: {:?}", e),
+        let mut row = frame_row.clone();
+        row.text("cmd", Some(header.cmd.to_string()));
+        row.number("index", Some(header.index));
+        row.value("adp", Some(value::Value::DatagramAddress(header.slave_addr)), value::Style::Columns);
+        row.value("ado", Some(value::Value::Address(header.offset_addr)), value::Style::Columns);
+        row.number("length", Some(header.length));
+        row.number("round_trip", Some(header.round_trip));
+        row.number("last_ind", Some(header.last_indicator));
+        row.push("irq", format!("{:x}", header.irq), header.irq);
+        row.number("wkc", Some(datagram.wkc));
+        row.text("data", Some(hex(datagram.data)));
+        let codes: Vec<&str> = warnings.split(';').filter(|code| !code.is_empty()).collect();
+        row.push("warnings", warnings.as_str(), codes);
+        if options.frame_number {
+            row.number("datagram_index", Some(i));
         }
+        if let Some(composition) = &composition {
+            row.number("datagrams_in_frame", Some(datagrams.len()));
+            row.text("frame_composition", Some(composition.as_str()));
+        }
+        // the fields of --ids, --mailbox, --sdo and --events.
+        if let Some(cells) = cells.get(i) {
+            row.extend(cells);
+        }
+        if options.irq_flags {
+            let flags = irq::flags(header.irq);
+            row.push("irq_flags", flags.join(";"), flags);
+        }
+        if options.datagram_offset {
+            row.number("datagram_offset_in_frame", Some(datagram_offset));
+        }
+        if let Some(names) = &options.register_names {
+            row.text("register_name", names.datagram_register(header));
+        }
+        for column in &options.derived {
+            let cell = column.cell(&derive::Row { frame_number: info.frame_number, datagram });
+            let json = json_number(&cell);
+            row.push(&column.name, cell, json);
+        }
+        if let Some(map) = &options.pdo_map {
+            for (name, cell) in map.names().zip(map.cells(datagram, ethernet_frame.is_returned())) {
+                let json = cell.as_deref().map_or(serde_json::Value::Null, json_number);
+                row.push(name, cell.unwrap_or_default(), json);
+            }
+        }
+        if options.labels.header() != "" {
+            row.text("label", slave.and_then(|slave| options.labels.name(slave)));
+        }
+        if let Some(names) = &options.slave_names {
+            row.text("slave_name", names.name(slave));
+        }
+        if let Some(topology) = &options.topology {
+            row.number("position", topology.position(slave));
+        }
+        write_row(&row);
     }
-    println!("num_blocks: {}", num_blocks);
-}
\ No newline at end of file
+    // the network variables have rows of their own, which the filters of datagrams leave out.
+    if let Some(FrameContent::NetworkVariables(content)) = ethernet_frame.ecat_frame.content.as_ref().filter(|_| options.network_variables && options.filter.is_empty()) {
+        let publisher_id = mac::format(&content.publisher_id, options.mac_format);
+        let warnings = info.link_errors.codes();
+        for (i, variable) in content.variables.iter().enumerate() {
+            let mut row = frame_row.clone();
+            row.number("length", Some(variable.data.len()));
+            row.text("data", Some(hex(variable.data)));
+            row.push("warnings", warnings.join(";"), warnings.clone());
+            if options.frame_number {
+                row.number("datagram_index", Some(i));
+            }
+            row.text("publisher_id", Some(publisher_id.as_str()));
+            row.number("nv_index", Some(variable.index));
+            row.push("nv_hash", format!("{:04x}", variable.hash), variable.hash);
+            row.number("nv_quality", Some(variable.quality));
+            write_row(&row);
+        }
+    }
+    Ok(rows)
+}
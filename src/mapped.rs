@@ -0,0 +1,164 @@
+//! Memory-mapped reading of capture files, handing the blocks to the export without copying
+//! them into a read buffer. Pipes and compressed captures are read through the buffered
+//! readers of pcap-parser instead.
+
+use ecat_packet_analyzer_rs::reader::{block_len, MAX_BLOCK_LEN};
+use pcap_parser::nom::Offset;
+use pcap_parser::pcapng::{parse_block_be, parse_block_le};
+use pcap_parser::traits::PcapReaderIterator;
+use pcap_parser::*;
+use std::fs::File;
+
+/// A read-only mapping of a whole file.
+pub struct Mapping {
+    ptr: *mut u8,
+    len: usize,
+}
+
+impl Mapping {
+    /// Maps `file` if it is a regular, non-empty file on a platform with mmap, `None` otherwise.
+    #[cfg(all(unix, target_pointer_width = "64"))]
+    pub fn of(file: &File) -> Option<Mapping> {
+        use std::os::fd::AsRawFd;
+
+        extern "C" {
+            fn mmap(addr: *mut u8, len: usize, prot: i32, flags: i32, fd: i32, offset: i64) -> *mut u8;
+        }
+        const PROT_READ: i32 = 1;
+        const MAP_PRIVATE: i32 = 2;
+        let metadata = file.metadata().ok()?;
+        let len = usize::try_from(metadata.len()).ok().filter(|&len| metadata.is_file() && len > 0)?;
+        // SAFETY: a private read-only mapping of an open descriptor, unmapped by drop; the
+        // file is not written through the mapping.
+        let ptr = unsafe { mmap(std::ptr::null_mut(), len, PROT_READ, MAP_PRIVATE, file.as_raw_fd(), 0) };
+        // MAP_FAILED is the all-ones address.
+        (ptr as usize != usize::MAX).then_some(Mapping { ptr, len })
+    }
+
+    #[cfg(not(all(unix, target_pointer_width = "64")))]
+    pub fn of(_file: &File) -> Option<Mapping> {
+        None
+    }
+
+    pub fn bytes(&self) -> &[u8] {
+        // SAFETY: the mapping is readable for len bytes until drop. A capture truncated by
+        // another process while it is read is beyond what a reader can guard against.
+        unsafe { std::slice::from_raw_parts(self.ptr, self.len) }
+    }
+}
+
+impl Drop for Mapping {
+    fn drop(&mut self) {
+        #[cfg(all(unix, target_pointer_width = "64"))]
+        {
+            extern "C" {
+                fn munmap(addr: *mut u8, len: usize) -> i32;
+            }
+            // SAFETY: ptr and len are those of the mapping, which nothing borrows any more.
+            unsafe {
+                munmap(self.ptr, self.len);
+            }
+        }
+    }
+}
+
+/// Reads the blocks of a mapped pcap or pcapng file, as the buffered readers of pcap-parser
+/// would: every block is in view, so there is nothing to refill.
+pub struct MappedReader {
+    mapping: Mapping,
+    /// Offset of the next block in the mapping.
+    position: usize,
+    /// Bytes handed out so far, counting a replayed section header.
+    consumed: usize,
+    /// Where the replayed section header ends and where the read goes on, when resuming.
+    jump: Option<(usize, usize)>,
+    legacy: bool,
+    /// Whether the file header of a classic pcap file has been read, and its byte order or
+    /// that of the current pcapng section.
+    header_read: bool,
+    big_endian: bool,
+}
+
+impl MappedReader {
+    /// A reader of the section header at `section` (offset and length) of the mapping,
+    /// followed by the blocks from `next_offset` on.
+    pub fn new(mapping: Mapping, legacy: bool, section: (usize, usize), next_offset: usize) -> Self {
+        let (offset, len) = section;
+        let jump = (offset + len != next_offset).then_some((offset + len, next_offset));
+        MappedReader { mapping, position: offset, consumed: 0, jump, legacy, header_read: false, big_endian: false }
+    }
+}
+
+impl PcapReaderIterator for MappedReader {
+    fn next(&mut self) -> Result<(usize, PcapBlockOwned<'_>), PcapError<&[u8]>> {
+        let data = self.mapping.bytes().get(self.position..).unwrap_or_default();
+        if data.is_empty() {
+            return Err(PcapError::Eof);
+        }
+        let parsed = if self.legacy && !self.header_read {
+            parse_pcap_header(data).map(|(rem, header)| {
+                self.header_read = true;
+                self.big_endian = header.is_bigendian();
+                (rem, PcapBlockOwned::from(header))
+            })
+        } else if self.legacy {
+            let parse = if self.big_endian { parse_pcap_frame_be } else { parse_pcap_frame };
+            parse(data).map(|(rem, packet)| (rem, PcapBlockOwned::from(packet)))
+        } else {
+            let parse = if self.big_endian { parse_block_be } else { parse_block_le };
+            parse(data).map(|(rem, block)| {
+                if let Block::SectionHeader(ref shb) = block {
+                    self.big_endian = shb.big_endian();
+                }
+                (rem, PcapBlockOwned::from(block))
+            })
+        };
+        match parsed {
+            Ok((rem, block)) => Ok((data.offset(rem), block)),
+            Err(nom::Err::Error(e)) | Err(nom::Err::Failure(e)) => Err(e),
+            // a block declaring more bytes than any read buffer would hold is reported as the
+            // buffered readers report it.
+            Err(nom::Err::Incomplete(_)) if block_len(data, self.legacy, self.big_endian).is_some_and(|len| len > MAX_BLOCK_LEN) => {
+                Err(PcapError::BufferTooSmall)
+            }
+            Err(nom::Err::Incomplete(_)) => Err(PcapError::UnexpectedEof),
+        }
+    }
+
+    fn consume(&mut self, offset: usize) {
+        self.consumed += offset;
+        self.position += offset;
+        if let Some((_, next)) = self.jump.filter(|&(end, _)| self.position == end) {
+            self.jump = None;
+            self.position = next;
+        }
+    }
+
+    fn consume_noshift(&mut self, offset: usize) {
+        self.consume(offset);
+    }
+
+    fn consumed(&self) -> usize {
+        self.consumed
+    }
+
+    fn refill(&mut self) -> Result<(), PcapError<&[u8]>> {
+        Ok(())
+    }
+
+    fn position(&self) -> usize {
+        self.position
+    }
+
+    fn grow(&mut self, _new_size: usize) -> bool {
+        true
+    }
+
+    fn data(&self) -> &[u8] {
+        self.mapping.bytes().get(self.position..).unwrap_or_default()
+    }
+
+    fn reader_exhausted(&self) -> bool {
+        true
+    }
+}
@@ -0,0 +1,103 @@
+use crate::capture::{self, CaptureOptions, Item, Summary};
+use crate::locate::EcatLocation;
+use crate::pcapng::{Frame, Writer};
+use crate::sink::outln;
+use std::fs::File;
+use std::ops::ControlFlow;
+use std::path::Path;
+use std::process::exit;
+use std::sync::mpsc::{self, Receiver};
+use std::thread::{self, JoinHandle};
+
+/// Frames of a capture read ahead of the merge.
+const READ_AHEAD: usize = 1024;
+
+/// A capture read on a thread of its own, handing its Ethernet frames over in file order.
+struct Input {
+    frames: Receiver<Frame>,
+    reader: JoinHandle<Result<(Summary, u64), String>>,
+    /// The next frame, which the merge compares to those of the other captures.
+    next: Option<Frame>,
+}
+
+impl Input {
+    /// Starts reading `file`, counting the packets of other link types it leaves out.
+    fn start(file: File, options: CaptureOptions) -> Self {
+        let (sender, frames) = mpsc::sync_channel(READ_AHEAD);
+        let reader = thread::spawn(move || {
+            let mut other_link_types = 0;
+            let summary = capture::read(file, &options, |item| {
+                let Item::Packet(packet) = item else {
+                    return ControlFlow::Continue(());
+                };
+                let Some(data) = packet.data else {
+                    other_link_types += 1;
+                    return ControlFlow::Continue(());
+                };
+                // the merge stops reading when it fails to write.
+                match sender.send(Frame { ts_ns: packet.info.ts_ns, data: data.to_vec() }) {
+                    Ok(()) => ControlFlow::Continue(()),
+                    Err(_) => ControlFlow::Break(()),
+                }
+            })?;
+            Ok((summary, other_link_types))
+        });
+        let mut input = Input { frames, reader, next: None };
+        input.next = input.frames.recv().ok();
+        input
+    }
+}
+
+/// Interleaves the packets of several captures by capture time into one pcapng file, with an
+/// interface for each capture named after its file, such as the captures of the primary and
+/// the redundant port of a cable-redundant master or of the two directions of a tap taken on
+/// separate NICs. Packets of the same time keep the order of the captures on the command
+/// line. The captures are read side by side, so they can be of any size.
+pub fn run(file: File, options: &CaptureOptions, names: &[&str], others: &[String], out: &Path) {
+    // the frames are written as captured, not rebuilt from where the EtherCAT frame is.
+    let options = &CaptureOptions { ecat_location: EcatLocation::Standard, ..options.clone() };
+    let mut inputs = vec![Input::start(file, options.clone())];
+    for other in others {
+        let file = capture::open(other).unwrap_or_else(|e| {
+            eprintln!("{}", e);
+            exit(1);
+        });
+        // the files a capture continues in are those of the first capture.
+        inputs.push(Input::start(file, CaptureOptions { next_files: Vec::new(), ..options.clone() }));
+    }
+    let merged = write(&mut inputs, names, out).unwrap_or_else(|e| {
+        eprintln!("Cannot write {} : {}", out.display(), e);
+        exit(1);
+    });
+
+    outln!("{} packets of {} captures merged into {}", merged, inputs.len(), out.display());
+    for (input, name) in inputs.into_iter().zip(names) {
+        let (summary, other_link_types) = input.reader.join().expect("the reader panicked").unwrap_or_else(|e| {
+            eprintln!("{} : {}", name, e);
+            exit(1);
+        });
+        if other_link_types > 0 {
+            outln!("{} : {} packets of other link types than Ethernet left out", name, other_link_types);
+        }
+        summary.report();
+    }
+}
+
+/// Writes the frames of the inputs in the order of their capture times, returning their count.
+fn write(inputs: &mut [Input], names: &[&str], out: &Path) -> std::io::Result<u64> {
+    let mut writer = Writer::create(out)?;
+    for name in names {
+        writer.ethernet_interface(name)?;
+    }
+    let mut merged = 0;
+    // the first of the captures wins a tie.
+    let next_ts = |input: &Input| input.next.as_ref().map(|frame| frame.ts_ns);
+    while let Some((index, input)) = inputs.iter_mut().enumerate().filter(|(_, input)| input.next.is_some()).min_by_key(|(_, input)| next_ts(input)) {
+        let next = input.frames.recv().ok();
+        let frame = std::mem::replace(&mut input.next, next).expect("the input has a frame");
+        writer.frame(index as u32, &frame)?;
+        merged += 1;
+    }
+    writer.finish()?;
+    Ok(merged)
+}
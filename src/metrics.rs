@@ -0,0 +1,173 @@
+use crate::coe;
+use crate::labels::addressed_slave;
+use crate::mailbox::{self, MailboxType};
+use crate::slave_config::SlaveConfigs;
+use crate::value::{Style, Value};
+use crate::wkc::WkcModel;
+use crate::{EtherCATCommand, EtherCATDatagramHeader, EtherNetFrame};
+use std::collections::{BTreeMap, HashMap};
+use std::io::{BufRead, BufReader, Write};
+use std::net::{TcpListener, TcpStream};
+use std::sync::{Arc, Mutex};
+use std::thread;
+
+/// Upper bounds of the buckets of the cycle jitter histogram, in nanoseconds.
+const JITTER_BUCKETS_NS: [u64; 8] = [1_000, 5_000, 10_000, 50_000, 100_000, 500_000, 1_000_000, 5_000_000];
+
+/// The slave label of the counters of a datagram: the station address it addresses, or the
+/// kind of addressing that reaches several slaves or none in particular.
+fn slave_label(header: &EtherCATDatagramHeader, returned: bool) -> String {
+    use EtherCATCommand::*;
+
+    match (addressed_slave(header, returned), header.cmd) {
+        (Some(slave), _) => slave.to_string(),
+        (None, LRD | LWR | LRW) => "logical".to_string(),
+        (None, BRD | BWR | BRW) => "broadcast".to_string(),
+        (None, _) => "position".to_string(),
+    }
+}
+
+/// The cycle times of the cyclic frames of one master so far.
+#[derive(Default)]
+struct Master {
+    last_ns: Option<u64>,
+    cycles: u64,
+    total_ns: u64,
+}
+
+/// Counters and histograms of the traffic exported so far, in the Prometheus text format.
+#[derive(Default)]
+pub struct Metrics {
+    frames: [u64; 2],
+    datagrams: BTreeMap<String, u64>,
+    wkc_mismatches: BTreeMap<String, u64>,
+    /// Mailbox error replies and SDO aborts, by slave and kind.
+    mailbox_errors: BTreeMap<(String, &'static str), u64>,
+    masters: HashMap<[u8; 6], Master>,
+    jitter_buckets: [u64; JITTER_BUCKETS_NS.len()],
+    jitter_count: u64,
+    jitter_sum_ns: u64,
+    configs: SlaveConfigs,
+}
+
+impl Metrics {
+    /// Counts an EtherCAT frame: its datagrams by command on the way out, the working counters
+    /// differing from the model and the mailbox errors on the way back, and the jitter of the
+    /// cyclic frames, the distance of every cycle time of a master to its mean so far.
+    pub fn frame(&mut self, ts_ns: u64, frame: &EtherNetFrame, wkc_model: Option<&WkcModel>) {
+        use EtherCATCommand::*;
+
+        let returned = frame.is_returned();
+        self.frames[returned as usize] += 1;
+        let datagrams = &frame.ecat_frame.datagrams;
+        for datagram in datagrams {
+            let header = &datagram.header;
+            if !returned {
+                *self.datagrams.entry(header.cmd.to_string()).or_default() += 1;
+                continue;
+            }
+            if wkc_model.and_then(|model| model.expected(header)).is_some_and(|expected| expected != datagram.wkc) {
+                *self.wkc_mismatches.entry(slave_label(header, returned)).or_default() += 1;
+            }
+            self.configs.apply(datagram, returned);
+            let Some(message) = mailbox::extract(datagram, returned, &self.configs) else {
+                continue;
+            };
+            // an error reply carries the mailbox error service, 0x0001, and its detail code.
+            let kind = if message.mbx_type == MailboxType::Error && message.payload.starts_with(&[0x01, 0x00]) && message.payload.len() >= 4 {
+                "error_reply"
+            } else if coe::sdo_message(&message).is_some_and(|sdo| sdo.abort_code.is_some()) {
+                "sdo_abort"
+            } else {
+                continue;
+            };
+            *self.mailbox_errors.entry((Value::Address(message.slave).render(Style::Csv), kind)).or_default() += 1;
+        }
+
+        let cyclic = datagrams.iter().any(|datagram| matches!(datagram.header.cmd, LRD | LWR | LRW));
+        if returned || !cyclic {
+            return;
+        }
+        let master = self.masters.entry(frame.src_mac).or_default();
+        if let Some(last_ns) = master.last_ns.replace(ts_ns) {
+            let cycle_ns = ts_ns.saturating_sub(last_ns);
+            if master.cycles > 0 {
+                let mean_ns = master.total_ns as f64 / master.cycles as f64;
+                let jitter_ns = (cycle_ns as f64 - mean_ns).abs().round() as u64;
+                for (bucket, bound) in self.jitter_buckets.iter_mut().zip(JITTER_BUCKETS_NS) {
+                    *bucket += (jitter_ns <= bound) as u64;
+                }
+                self.jitter_count += 1;
+                self.jitter_sum_ns += jitter_ns;
+            }
+            master.cycles += 1;
+            master.total_ns += cycle_ns;
+        }
+    }
+
+    /// The metrics in the Prometheus text exposition format.
+    pub fn render(&self) -> String {
+        let seconds = |ns: u64| ns as f64 / 1e9;
+        let family = |name: &str, kind: &str, help: &str| format!("# HELP {} {}\n# TYPE {} {}\n", name, help, name, kind);
+        let mut text = family("ecat_frames_total", "counter", "EtherCAT frames exported, by direction.");
+        text += &format!("ecat_frames_total{{direction=\"out\"}} {}\n", self.frames[0]);
+        text += &format!("ecat_frames_total{{direction=\"returned\"}} {}\n", self.frames[1]);
+        text += &family("ecat_datagrams_total", "counter", "Datagrams sent by the master, by command.");
+        for (cmd, count) in &self.datagrams {
+            text += &format!("ecat_datagrams_total{{cmd=\"{}\"}} {}\n", cmd, count);
+        }
+        text += &family("ecat_wkc_mismatches_total", "counter", "Returned datagrams whose working counter differs from the expected one, by slave.");
+        for (slave, count) in &self.wkc_mismatches {
+            text += &format!("ecat_wkc_mismatches_total{{slave=\"{}\"}} {}\n", slave, count);
+        }
+        text += &family("ecat_mailbox_errors_total", "counter", "Mailbox error replies and SDO aborts, by slave.");
+        for ((slave, kind), count) in &self.mailbox_errors {
+            text += &format!("ecat_mailbox_errors_total{{slave=\"{}\",kind=\"{}\"}} {}\n", slave, kind, count);
+        }
+        text += &family("ecat_cycle_jitter_seconds", "histogram", "Distance of the cycle times of the cyclic frames to their mean so far.");
+        for (bucket, bound) in self.jitter_buckets.iter().zip(JITTER_BUCKETS_NS) {
+            text += &format!("ecat_cycle_jitter_seconds_bucket{{le=\"{}\"}} {}\n", seconds(bound), bucket);
+        }
+        text += &format!("ecat_cycle_jitter_seconds_bucket{{le=\"+Inf\"}} {}\n", self.jitter_count);
+        text += &format!("ecat_cycle_jitter_seconds_sum {}\n", seconds(self.jitter_sum_ns));
+        text += &format!("ecat_cycle_jitter_seconds_count {}\n", self.jitter_count);
+        text
+    }
+}
+
+/// Answers one HTTP request: the metrics for `GET /metrics`, 404 for anything else.
+fn answer(stream: TcpStream, metrics: &Mutex<Metrics>) -> std::io::Result<()> {
+    let mut reader = BufReader::new(stream);
+    let mut request = String::new();
+    reader.read_line(&mut request)?;
+    // the headers of the request are of no use.
+    let mut line = String::new();
+    while reader.read_line(&mut line)? > 2 {
+        line.clear();
+    }
+    let mut words = request.split_whitespace();
+    let (status, body) = match (words.next(), words.next()) {
+        (Some("GET"), Some("/metrics")) => ("200 OK", metrics.lock().unwrap().render()),
+        _ => ("404 Not Found", "Not found, the metrics are at /metrics\n".to_string()),
+    };
+    let mut stream = reader.into_inner();
+    write!(stream, "HTTP/1.1 {}\r\nContent-Type: text/plain; version=0.0.4\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+        status, body.len(), body)
+}
+
+/// Serves the metrics over HTTP at `/metrics` of `address` from a thread of its own, for as
+/// long as the export runs. Prints the address it listens on, which tells the port picked for
+/// port 0.
+pub fn serve(address: &str, metrics: Arc<Mutex<Metrics>>) -> Result<(), String> {
+    let listener = TcpListener::bind(address).map_err(|e| format!("Cannot listen on {} : {}", address, e))?;
+    let local = listener.local_addr().map_err(|e| format!("Cannot listen on {} : {}", address, e))?;
+    eprintln!("Serving metrics on http://{}/metrics", local);
+    thread::spawn(move || {
+        for stream in listener.incoming().flatten() {
+            if let Err(e) = answer(stream, &metrics) {
+                eprintln!("Metrics request failed : {}", e);
+            }
+        }
+    });
+    Ok(())
+}
@@ -0,0 +1,69 @@
+use clap::ValueEnum;
+use ecat_packet_analyzer_rs::parser::ecat::FrameType;
+use ecat_packet_analyzer_rs::parser::ethernet::ether_type;
+use std::collections::BTreeMap;
+
+/// What is reported of the frames of other protocols the export skips.
+#[derive(ValueEnum, Clone, Copy, Debug)]
+pub enum IncludeOther {
+    /// The skipped frames by EtherType
+    Summary,
+}
+
+/// Name of the protocols seen most on the NIC of the master, by EtherType.
+fn protocol(ether_type: u16) -> Option<&'static str> {
+    Some(match ether_type {
+        0x0800 => "IPv4",
+        0x0806 => "ARP",
+        0x86DD => "IPv6",
+        0x8892 => "PROFINET",
+        0x88B5 => "local experimental",
+        0x88CC => "LLDP",
+        0x88F7 => "PTP",
+        0x88E3 => "MRP",
+        _ => return None,
+    })
+}
+
+/// Counts the frames the export skips because they do not carry EtherCAT, such as the ARP,
+/// LLDP and IP traffic of the NIC of the master, by EtherType behind their VLAN tags, and the
+/// EtherCAT frames without datagrams, by frame type.
+#[derive(Default)]
+pub struct OtherFrames {
+    counts: BTreeMap<u16, u64>,
+    frame_types: BTreeMap<FrameType, u64>,
+}
+
+impl OtherFrames {
+    pub fn skip(&mut self, frame: &[u8]) {
+        let ether_type = ether_type(frame).map_or(0, |(ether_type, _)| ether_type);
+        *self.counts.entry(ether_type).or_default() += 1;
+    }
+
+    /// Counts an EtherCAT frame of another type than datagrams, such as network variables.
+    pub fn skip_frame_type(&mut self, frame_type: FrameType) {
+        *self.frame_types.entry(frame_type).or_default() += 1;
+    }
+
+    /// Prints the count of the skipped frames to stderr, by EtherType with `--include-other
+    /// summary`.
+    pub fn report(&self, include: Option<IncludeOther>) {
+        for (frame_type, count) in &self.frame_types {
+            eprintln!("{} EtherCAT frames of {} skipped", count, frame_type);
+        }
+        let total: u64 = self.counts.values().sum();
+        if total == 0 {
+            return;
+        }
+        eprintln!("{} frames of other protocols skipped", total);
+        if include.is_none() {
+            return;
+        }
+        for (&ether_type, count) in &self.counts {
+            match protocol(ether_type) {
+                Some(name) => eprintln!("  0x{:04x} ({}) : {}", ether_type, name, count),
+                None => eprintln!("  0x{:04x} : {}", ether_type, count),
+            }
+        }
+    }
+}
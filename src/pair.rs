@@ -0,0 +1,110 @@
+use crate::capture::{self, CaptureOptions, Item};
+use crate::sink::outln;
+use crate::transaction::datagram_id;
+use crate::value::{Style, Value};
+use crate::{EtherCATCommand, EtherCATDatagramHeader, EtherNetFrame};
+use std::collections::HashMap;
+use std::fs::File;
+use std::ops::ControlFlow;
+use std::process::exit;
+
+const CSV_HEADER: &str =
+    "request_id,response_id,time,cmd,index,adp,ado,length,round_trip_ns,request_wkc,response_wkc,wkc_delta";
+
+/// What a response is matched to its request on: the master, the index, the command and the
+/// addresses. Each slave increments the ADP of the auto-increment and broadcast commands on the
+/// way, so for those the ADP is left out.
+#[derive(PartialEq, Eq, Hash)]
+pub struct Key {
+    master: [u8; 6],
+    index: u8,
+    cmd: EtherCATCommand,
+    adp: Option<u16>,
+    ado: u16,
+}
+
+impl Key {
+    pub fn new(master: [u8; 6], header: &EtherCATDatagramHeader) -> Self {
+        use EtherCATCommand::*;
+
+        let adp = (!matches!(header.cmd, APRD | APWR | APRW | ARMW | BRD | BWR | BRW)).then_some(header.slave_addr);
+        Key { master, index: header.index, cmd: header.cmd, adp, ado: header.offset_addr }
+    }
+}
+
+/// One side of a transaction.
+struct Side {
+    id: String,
+    ts_ns: u64,
+    cmd: EtherCATCommand,
+    index: u8,
+    adp: u16,
+    ado: u16,
+    length: u16,
+    wkc: u16,
+}
+
+/// The row of a request and its response; either may be missing, for a lost frame or a
+/// capture started while a frame was on its way.
+fn row(request: Option<&Side>, response: Option<&Side>) -> String {
+    let side = request.or(response).expect("a transaction has a request or a response");
+    let round_trip = request.zip(response).map(|(request, response)| response.ts_ns.saturating_sub(request.ts_ns));
+    let wkc_delta = request.zip(response).map(|(request, response)| response.wkc as i32 - request.wkc as i32);
+    let cell = |value: Option<String>| value.unwrap_or_default();
+    format!("{},{},{},{},{},{},{},{},{},{},{},{}",
+        cell(request.map(|side| side.id.clone())), cell(response.map(|side| side.id.clone())),
+        Value::Time(side.ts_ns).render(Style::Csv), side.cmd, side.index,
+        Value::Address(side.adp).render(Style::Csv), Value::Address(side.ado).render(Style::Csv), side.length,
+        cell(round_trip.map(|ns| ns.to_string())), cell(request.map(|side| side.wkc.to_string())),
+        cell(response.map(|side| side.wkc.to_string())), cell(wkc_delta.map(|delta| delta.to_string())))
+}
+
+/// Exports one row per transaction instead of a row for the outgoing datagram and another for
+/// the returned one: the round trip from the capture of the request to that of its response, and
+/// the change of the working counter on the way. Rows come in the order the responses arrive; a
+/// request without a response is written when its key is reused, or at the end of the capture.
+pub fn run(file: File, options: &CaptureOptions) {
+    let mut pending: HashMap<Key, Side> = HashMap::new();
+
+    outln!("{}", CSV_HEADER);
+    let summary = capture::read(file, options, |item| {
+        let Item::Packet(packet) = item else {
+            return ControlFlow::Continue(());
+        };
+        let Some(frame) = packet.data.and_then(EtherNetFrame::parse_ethercat) else {
+            return ControlFlow::Continue(());
+        };
+        let returned = frame.is_returned();
+        for (position, datagram) in frame.ecat_frame.datagrams.iter().enumerate() {
+            let header = &datagram.header;
+            let side = Side {
+                id: datagram_id(packet.info.frame_number, position),
+                ts_ns: packet.info.ts_ns,
+                cmd: header.cmd,
+                index: header.index,
+                adp: header.slave_addr,
+                ado: header.offset_addr,
+                length: header.length,
+                wkc: datagram.wkc,
+            };
+            let key = Key::new(frame.master_mac(), header);
+            if returned {
+                let request = pending.remove(&key);
+                outln!("{}", row(request.as_ref(), Some(&side)));
+            } else if let Some(unanswered) = pending.insert(key, side) {
+                outln!("{}", row(Some(&unanswered), None));
+            }
+        }
+        ControlFlow::Continue(())
+    });
+    let summary = summary.unwrap_or_else(|e| {
+        eprintln!("{}", e);
+        exit(1);
+    });
+    let mut unanswered: Vec<Side> = pending.into_values().collect();
+    unanswered.sort_by_key(|side| side.ts_ns);
+    for side in &unanswered {
+        outln!("{}", row(Some(side), None));
+    }
+    summary.report();
+}
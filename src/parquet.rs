@@ -0,0 +1,397 @@
+use crate::capture::CaptureOptions;
+use crate::filter::{self, Selection};
+use crate::mac::{self, MacFormat};
+use crate::sink::{self, Output};
+use crate::warnings::WarningLog;
+use crate::wkc::WkcModel;
+use std::io::Write;
+use std::fs::File;
+use std::process::exit;
+
+const MAGIC: &[u8] = b"PAR1";
+/// Rows of a row group: the columns of one group are held in memory until it is written.
+const ROW_GROUP_ROWS: usize = 65_536;
+const CREATED_BY: &str = concat!("ecat-packet-analyzer-rs version ", env!("CARGO_PKG_VERSION"));
+
+/// Physical types of the Parquet format.
+#[derive(Clone, Copy)]
+enum PhysicalType {
+    Int32 = 1,
+    Int64 = 2,
+    ByteArray = 6,
+}
+
+/// How the values of a column are read: as they are stored, as UTF-8 text, or as a time in
+/// nanoseconds since the epoch.
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Annotation {
+    None,
+    String,
+    TimestampNanos,
+}
+
+/// The columns of the export, in file order. The schema is stable: columns are only ever added
+/// at the end.
+const SCHEMA: &[(&str, PhysicalType, Annotation)] = &[
+    ("frame_no", PhysicalType::Int64, Annotation::None),
+    ("ts", PhysicalType::Int64, Annotation::TimestampNanos),
+    ("src_mac", PhysicalType::ByteArray, Annotation::String),
+    ("dst_mac", PhysicalType::ByteArray, Annotation::String),
+    ("cmd", PhysicalType::ByteArray, Annotation::String),
+    ("index", PhysicalType::Int32, Annotation::None),
+    ("adp", PhysicalType::Int32, Annotation::None),
+    ("ado", PhysicalType::Int32, Annotation::None),
+    ("length", PhysicalType::Int32, Annotation::None),
+    ("wkc", PhysicalType::Int32, Annotation::None),
+    ("data", PhysicalType::ByteArray, Annotation::None),
+];
+
+// Thrift compact protocol: the field types, and the encoding of the Parquet metadata with them.
+const BOOL_TRUE: u8 = 1;
+const I32: u8 = 5;
+const I64: u8 = 6;
+const BINARY: u8 = 8;
+const LIST: u8 = 9;
+const STRUCT: u8 = 12;
+
+/// A Thrift compact protocol encoder of the structs of the Parquet metadata.
+#[derive(Default)]
+struct Thrift {
+    out: Vec<u8>,
+    /// The last field ID of every struct being written, innermost last.
+    last_ids: Vec<i16>,
+}
+
+impl Thrift {
+    fn varint(&mut self, mut value: u64) {
+        while value >= 0x80 {
+            self.out.push(value as u8 | 0x80);
+            value >>= 7;
+        }
+        self.out.push(value as u8);
+    }
+
+    fn zigzag(&mut self, value: i64) {
+        self.varint(((value << 1) ^ (value >> 63)) as u64);
+    }
+
+    fn field(&mut self, id: i16, field_type: u8) {
+        let last = self.last_ids.last_mut().expect("field outside of a struct");
+        let delta = id - std::mem::replace(last, id);
+        if (1..=15).contains(&delta) {
+            self.out.push((delta as u8) << 4 | field_type);
+        } else {
+            self.out.push(field_type);
+            self.zigzag(id as i64);
+        }
+    }
+
+    fn begin(&mut self) {
+        self.last_ids.push(0);
+    }
+
+    fn end(&mut self) {
+        self.out.push(0);
+        self.last_ids.pop();
+    }
+
+    fn i32(&mut self, id: i16, value: i32) {
+        self.field(id, I32);
+        self.zigzag(value as i64);
+    }
+
+    fn i64(&mut self, id: i16, value: i64) {
+        self.field(id, I64);
+        self.zigzag(value);
+    }
+
+    fn binary(&mut self, id: i16, value: &[u8]) {
+        self.field(id, BINARY);
+        self.varint(value.len() as u64);
+        self.out.extend_from_slice(value);
+    }
+
+    fn list(&mut self, id: i16, element_type: u8, len: usize) {
+        self.field(id, LIST);
+        if len < 15 {
+            self.out.push((len as u8) << 4 | element_type);
+        } else {
+            self.out.push(0xF0 | element_type);
+            self.varint(len as u64);
+        }
+    }
+
+    /// Opens a struct field; its fields follow up to `end`.
+    fn struct_field(&mut self, id: i16) {
+        self.field(id, STRUCT);
+        self.begin();
+    }
+}
+
+/// Appends a literal of the Snappy format.
+fn snappy_literal(out: &mut Vec<u8>, literal: &[u8]) {
+    let Some(n) = literal.len().checked_sub(1) else {
+        return;
+    };
+    if n < 60 {
+        out.push((n as u8) << 2);
+    } else {
+        let bytes = (usize::BITS - n.leading_zeros()).div_ceil(8) as usize;
+        out.push((59 + bytes as u8) << 2);
+        out.extend_from_slice(&n.to_le_bytes()[..bytes]);
+    }
+    out.extend_from_slice(literal);
+}
+
+/// Compresses a page in the Snappy block format, the codec of the columns: repeated runs of 4
+/// bytes or more within 64 KiB become copies of the earlier occurrence, the rest literals.
+fn snappy(input: &[u8]) -> Vec<u8> {
+    const HASH_BITS: u32 = 14;
+
+    let mut out = Vec::with_capacity(input.len() / 2 + 8);
+    let mut length = input.len() as u64;
+    while length >= 0x80 {
+        out.push(length as u8 | 0x80);
+        length >>= 7;
+    }
+    out.push(length as u8);
+    // the position after the last run of 4 bytes seen with every hash, 0 for none.
+    let mut table = vec![0usize; 1 << HASH_BITS];
+    let (mut i, mut literal) = (0, 0);
+    while i + 4 <= input.len() {
+        let word = u32::from_le_bytes([input[i], input[i + 1], input[i + 2], input[i + 3]]);
+        let slot = (word.wrapping_mul(0x1E35_A7BD) >> (32 - HASH_BITS)) as usize;
+        let candidate = std::mem::replace(&mut table[slot], i + 1);
+        let Some(start) = candidate.checked_sub(1).filter(|&start| i - start <= 0xFFFF && input[start..start + 4] == input[i..i + 4]) else {
+            i += 1;
+            continue;
+        };
+        let mut len = 4;
+        while i + len < input.len() && input[start + len] == input[i + len] {
+            len += 1;
+        }
+        snappy_literal(&mut out, &input[literal..i]);
+        let offset = (i - start) as u16;
+        let mut remaining = len;
+        while remaining > 0 {
+            let n = remaining.min(64);
+            out.push(((n - 1) as u8) << 2 | 2);
+            out.extend_from_slice(&offset.to_le_bytes());
+            remaining -= n;
+        }
+        i += len;
+        literal = i;
+    }
+    snappy_literal(&mut out, &input[literal..]);
+    out
+}
+
+/// The location and sizes of a column chunk written, for the metadata of its row group.
+struct ChunkMeta {
+    offset: u64,
+    uncompressed: usize,
+    compressed: usize,
+}
+
+struct RowGroupMeta {
+    rows: usize,
+    chunks: Vec<ChunkMeta>,
+}
+
+/// Writes the datagrams as a Parquet file, column by column in row groups: every column of a
+/// group is one PLAIN encoded data page, compressed with Snappy. The columns are required, so
+/// the pages carry no definition levels.
+struct ParquetWriter {
+    out: Output,
+    written: u64,
+    /// The PLAIN encoded values of the row group being collected, by column.
+    columns: Vec<Vec<u8>>,
+    rows: usize,
+    total_rows: u64,
+    row_groups: Vec<RowGroupMeta>,
+}
+
+impl ParquetWriter {
+    fn new(mut out: Output) -> std::io::Result<Self> {
+        out.write_all(MAGIC)?;
+        Ok(ParquetWriter { out, written: MAGIC.len() as u64, columns: vec![Vec::new(); SCHEMA.len()], rows: 0, total_rows: 0, row_groups: Vec::new() })
+    }
+
+    fn write(&mut self, bytes: &[u8]) -> std::io::Result<()> {
+        self.out.write_all(bytes)?;
+        self.written += bytes.len() as u64;
+        Ok(())
+    }
+
+    fn int32(&mut self, column: usize, value: i32) {
+        self.columns[column].extend_from_slice(&value.to_le_bytes());
+    }
+
+    fn int64(&mut self, column: usize, value: i64) {
+        self.columns[column].extend_from_slice(&value.to_le_bytes());
+    }
+
+    fn bytes(&mut self, column: usize, value: &[u8]) {
+        self.columns[column].extend_from_slice(&(value.len() as u32).to_le_bytes());
+        self.columns[column].extend_from_slice(value);
+    }
+
+    /// Ends a row whose every column was given a value, writing the row group when it is full.
+    fn end_row(&mut self) -> std::io::Result<()> {
+        self.rows += 1;
+        if self.rows == ROW_GROUP_ROWS {
+            self.flush_row_group()?;
+        }
+        Ok(())
+    }
+
+    fn flush_row_group(&mut self) -> std::io::Result<()> {
+        if self.rows == 0 {
+            return Ok(());
+        }
+        let mut chunks = Vec::new();
+        for i in 0..self.columns.len() {
+            let values = std::mem::take(&mut self.columns[i]);
+            let page = snappy(&values);
+            let mut header = Thrift::default();
+            header.begin();
+            header.i32(1, 0); // DATA_PAGE
+            header.i32(2, values.len() as i32);
+            header.i32(3, page.len() as i32);
+            header.struct_field(5);
+            header.i32(1, self.rows as i32);
+            header.i32(2, 0); // PLAIN
+            header.i32(3, 3); // RLE
+            header.i32(4, 3);
+            header.end();
+            header.end();
+            let offset = self.written;
+            self.write(&header.out)?;
+            self.write(&page)?;
+            chunks.push(ChunkMeta { offset, uncompressed: header.out.len() + values.len(), compressed: header.out.len() + page.len() });
+        }
+        self.row_groups.push(RowGroupMeta { rows: self.rows, chunks });
+        self.total_rows += self.rows as u64;
+        self.rows = 0;
+        Ok(())
+    }
+
+    /// Writes the last row group and the footer: the file metadata, its length and the magic.
+    fn finish(mut self) -> std::io::Result<()> {
+        self.flush_row_group()?;
+        let mut meta = Thrift::default();
+        meta.begin();
+        meta.i32(1, 1);
+        meta.list(2, STRUCT, SCHEMA.len() + 1);
+        meta.begin();
+        meta.binary(4, b"schema");
+        meta.i32(5, SCHEMA.len() as i32);
+        meta.end();
+        for &(name, physical_type, annotation) in SCHEMA {
+            meta.begin();
+            meta.i32(1, physical_type as i32);
+            meta.i32(3, 0); // REQUIRED
+            meta.binary(4, name.as_bytes());
+            match annotation {
+                Annotation::None => {}
+                Annotation::String => meta.i32(6, 0), // UTF8
+                Annotation::TimestampNanos => {
+                    meta.struct_field(10);
+                    meta.struct_field(8);
+                    meta.field(1, BOOL_TRUE); // adjusted to UTC
+                    meta.struct_field(2);
+                    meta.struct_field(3); // NANOS
+                    meta.end();
+                    meta.end();
+                    meta.end();
+                    meta.end();
+                }
+            }
+            meta.end();
+        }
+        meta.i64(3, self.total_rows as i64);
+        meta.list(4, STRUCT, self.row_groups.len());
+        for group in &self.row_groups {
+            meta.begin();
+            meta.list(1, STRUCT, group.chunks.len());
+            for (chunk, &(name, physical_type, _)) in group.chunks.iter().zip(SCHEMA) {
+                meta.begin();
+                meta.i64(2, chunk.offset as i64);
+                meta.struct_field(3);
+                meta.i32(1, physical_type as i32);
+                meta.list(2, I32, 1);
+                meta.zigzag(0); // PLAIN
+                meta.list(3, BINARY, 1);
+                meta.varint(name.len() as u64);
+                meta.out.extend_from_slice(name.as_bytes());
+                meta.i32(4, 1); // SNAPPY
+                meta.i64(5, group.rows as i64);
+                meta.i64(6, chunk.uncompressed as i64);
+                meta.i64(7, chunk.compressed as i64);
+                meta.i64(9, chunk.offset as i64);
+                meta.end();
+                meta.end();
+            }
+            meta.i64(2, group.chunks.iter().map(|chunk| chunk.uncompressed as i64).sum());
+            meta.i64(3, group.rows as i64);
+            meta.end();
+        }
+        meta.binary(6, CREATED_BY.as_bytes());
+        meta.end();
+        self.write(&meta.out)?;
+        self.write(&(meta.out.len() as u32).to_le_bytes())?;
+        self.write(MAGIC)?;
+        self.out.flush()
+    }
+}
+
+fn write_error<T>(e: std::io::Error) -> T {
+    eprintln!("Cannot write the Parquet export : {}", e);
+    exit(1);
+}
+
+/// Exports the datagrams as a Parquet file, for the captures too large for the CSV to load
+/// quickly: one row per datagram with the frame number, the capture time, the MAC addresses,
+/// the command, index, ADP, ADO, length, working counter and the data, in row groups of
+/// 65536 rows. Only the datagrams of the selection are exported. The file goes to `output`,
+/// or to stdout.
+pub fn run(
+    file: File,
+    options: &CaptureOptions,
+    selection: &Selection,
+    warnings: Option<WarningLog>,
+    wkc_model: Option<&WkcModel>,
+    output: Option<&str>,
+) {
+    let out = sink::open_output(output, false).unwrap_or_else(|e| {
+        eprintln!("{}", e);
+        exit(1);
+    });
+    let mut writer = ParquetWriter::new(out).unwrap_or_else(write_error);
+
+    let summary = filter::read_datagrams(file, options, selection, warnings, wkc_model, |info, frame, datagrams| {
+        let src_mac = mac::format(&frame.src_mac, MacFormat::Colon);
+        let dst_mac = mac::format(&frame.dst_mac, MacFormat::Colon);
+        for datagram in datagrams {
+            let header = &datagram.header;
+            writer.int64(0, info.frame_number as i64);
+            writer.int64(1, info.ts_ns as i64);
+            writer.bytes(2, src_mac.as_bytes());
+            writer.bytes(3, dst_mac.as_bytes());
+            writer.bytes(4, header.cmd.to_string().as_bytes());
+            writer.int32(5, header.index as i32);
+            writer.int32(6, header.slave_addr as i32);
+            writer.int32(7, header.offset_addr as i32);
+            writer.int32(8, header.length as i32);
+            writer.int32(9, datagram.wkc as i32);
+            writer.bytes(10, datagram.data);
+            writer.end_row().unwrap_or_else(write_error);
+        }
+    });
+    let summary = summary.unwrap_or_else(|e| {
+        eprintln!("{}", e);
+        exit(1);
+    });
+    writer.finish().unwrap_or_else(write_error);
+    summary.report();
+}
@@ -0,0 +1,76 @@
+//! Decoding of Ethernet frames carrying EtherCAT.
+
+pub mod coe;
+pub mod ecat;
+pub mod eoe;
+pub mod ethernet;
+pub mod foe;
+pub mod mailbox;
+pub mod netvars;
+
+use ecat::EtherCATFrame;
+use ethernet::{EtherNetFrame, ETHERTYPE_ETHERCAT, ETHERTYPE_IPV4};
+use thiserror::Error;
+
+/// Why a frame does not parse.
+#[derive(Debug, Clone, PartialEq, Eq, Error)]
+pub enum ParseError {
+    #[error("the frame has {len} bytes, fewer than an Ethernet header")]
+    TruncatedEthernetHeader { len: usize },
+    #[error("EtherType 0x{0:04x} is not EtherCAT")]
+    NotEtherCAT(u16),
+    #[error("the frame ends inside the EtherCAT header")]
+    TruncatedEtherCATHeader,
+    /// The frame ends inside the datagram at position `datagram`, which needs `needed` bytes
+    /// from its start.
+    #[error("datagram {datagram} needs {needed} bytes, the frame ends after {available}")]
+    TruncatedDatagram { datagram: usize, needed: usize, available: usize },
+    #[error("the payload has {len} bytes, fewer than a mailbox header")]
+    TruncatedMailboxHeader { len: usize },
+    #[error("mailbox type {0} is not a known protocol")]
+    UnknownMailboxType(u8),
+    #[error("the mailbox error reply has {len} bytes, fewer than its type and code")]
+    TruncatedMailboxError { len: usize },
+    #[error("mailbox error reply type {0} is not an error")]
+    NotMailboxError(u16),
+    #[error("the CoE payload has {len} bytes, fewer than an SDO header")]
+    TruncatedSdo { len: usize },
+    #[error("CoE service {0} is not an SDO request or response")]
+    NotSdo(u8),
+    #[error("SDO command specifier {0} is not known")]
+    UnknownSdoCommand(u8),
+    #[error("the CoE payload has {len} bytes, fewer than an emergency message")]
+    TruncatedEmergency { len: usize },
+    #[error("CoE service {0} is not an emergency")]
+    NotEmergency(u8),
+    #[error("the FoE payload has {len} bytes, fewer than an FoE header")]
+    TruncatedFoeHeader { len: usize },
+    #[error("FoE opcode {0} is not known")]
+    UnknownFoeOpcode(u8),
+    #[error("the EoE payload has {len} bytes, fewer than an EoE header")]
+    TruncatedEoeHeader { len: usize },
+    #[error("EoE type {0} is not a frame fragment")]
+    NotEoeFragment(u8),
+    /// The network variables frame ends before the `needed` bytes its headers announce.
+    #[error("the network variables need {needed} bytes, the frame ends after {available}")]
+    TruncatedNetworkVariables { needed: usize, available: usize },
+    #[error("the IPv4 frame has {len} bytes, fewer than its IPv4 and UDP headers")]
+    TruncatedUdpHeader { len: usize },
+}
+
+/// Parses the EtherCAT frame carried by `frame`, an Ethernet frame with the EtherCAT EtherType
+/// or an IPv4 frame carrying EtherCAT over UDP, such as a packet of a capture, with or without
+/// VLAN tags and without a frame check sequence.
+pub fn parse_frame(frame: &[u8]) -> Result<EtherCATFrame<'_>, ParseError> {
+    let Some((frame_type, _)) = ethernet::ether_type(frame) else {
+        return Err(ParseError::TruncatedEthernetHeader { len: frame.len() });
+    };
+    if frame_type != ETHERTYPE_ETHERCAT && frame_type != ETHERTYPE_IPV4 {
+        return Err(ParseError::NotEtherCAT(frame_type));
+    }
+    let parsed = EtherNetFrame::parse(frame)?;
+    if !parsed.carries_ethercat() {
+        return Err(ParseError::NotEtherCAT(frame_type));
+    }
+    Ok(parsed.ecat_frame)
+}
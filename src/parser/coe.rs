@@ -0,0 +1,243 @@
+//! CANopen over EtherCAT: the SDO services carried by CoE mailbox messages.
+
+use super::ParseError;
+use std::fmt::Display;
+
+/// Bytes of the CoE header in front of every CoE service.
+pub const COE_HEADER_LEN: usize = 2;
+/// Bytes of the SDO header following the CoE header: the command, the index, the subindex and
+/// the data or size field.
+pub const SDO_HEADER_LEN: usize = 8;
+
+/// The CoE service, in the upper 4 bits of the CoE header.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum CoeService {
+    Emergency,
+    SdoRequest,
+    SdoResponse,
+    TxPdo,
+    RxPdo,
+    TxPdoRemoteRequest,
+    RxPdoRemoteRequest,
+    SdoInformation,
+}
+
+impl CoeService {
+    pub fn from_u8(value: u8) -> Option<Self> {
+        match value {
+            1 => Some(CoeService::Emergency),
+            2 => Some(CoeService::SdoRequest),
+            3 => Some(CoeService::SdoResponse),
+            4 => Some(CoeService::TxPdo),
+            5 => Some(CoeService::RxPdo),
+            6 => Some(CoeService::TxPdoRemoteRequest),
+            7 => Some(CoeService::RxPdoRemoteRequest),
+            8 => Some(CoeService::SdoInformation),
+            _ => None,
+        }
+    }
+}
+
+/// The command of an SDO, from the client command specifier of a request or the server command
+/// specifier of a response, in bits 5-7 of the command byte.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum SdoCommand {
+    InitiateDownload,
+    DownloadSegment,
+    InitiateUpload,
+    UploadSegment,
+    Abort,
+}
+
+impl Display for SdoCommand {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let command_str = match self {
+            Self::InitiateDownload => "initiate download",
+            Self::DownloadSegment => "download segment",
+            Self::InitiateUpload => "initiate upload",
+            Self::UploadSegment => "upload segment",
+            Self::Abort => "abort",
+        };
+
+        write!(f, "{}", command_str)
+    }
+}
+
+/// An SDO request or response.
+#[derive(Clone, Copy, Debug)]
+pub struct Sdo<'a> {
+    /// [`CoeService::SdoRequest`] from the master, or [`CoeService::SdoResponse`] from the slave.
+    pub service: CoeService,
+    pub command: SdoCommand,
+    /// Index of the object entry; segments do not carry it.
+    pub index: Option<u16>,
+    pub subindex: Option<u8>,
+    /// The whole object is transferred, from subindex 0 or 1.
+    pub complete_access: bool,
+    /// The data of an expedited transfer, carried in the header.
+    pub expedited_data: Option<&'a [u8]>,
+    /// Size of the data of a normal transfer, which follows the header.
+    pub size: Option<u32>,
+    pub abort_code: Option<u32>,
+    /// The data following the header of a normal transfer, or carried by a segment.
+    pub data: &'a [u8],
+}
+
+impl<'a> Sdo<'a> {
+    /// Parses the SDO service of `payload`, the payload of a CoE mailbox message.
+    pub fn parse(payload: &'a [u8]) -> Result<Self, ParseError> {
+        if payload.len() < COE_HEADER_LEN + SDO_HEADER_LEN {
+            return Err(ParseError::TruncatedSdo { len: payload.len() });
+        }
+        let service_number = (u16::from_le_bytes([payload[0], payload[1]]) >> 12) as u8;
+        let service = match CoeService::from_u8(service_number) {
+            Some(service @ (CoeService::SdoRequest | CoeService::SdoResponse)) => service,
+            _ => return Err(ParseError::NotSdo(service_number)),
+        };
+        let sdo = &payload[COE_HEADER_LEN..];
+        let command_byte = sdo[0];
+        let command = match (service, command_byte >> 5) {
+            (_, 4) => SdoCommand::Abort,
+            (CoeService::SdoRequest, 0) | (CoeService::SdoResponse, 1) => SdoCommand::DownloadSegment,
+            (CoeService::SdoRequest, 1) | (CoeService::SdoResponse, 3) => SdoCommand::InitiateDownload,
+            (_, 2) => SdoCommand::InitiateUpload,
+            (CoeService::SdoRequest, 3) | (CoeService::SdoResponse, 0) => SdoCommand::UploadSegment,
+            (_, specifier) => return Err(ParseError::UnknownSdoCommand(specifier)),
+        };
+        let field = u32::from_le_bytes([sdo[4], sdo[5], sdo[6], sdo[7]]);
+
+        let mut parsed = Sdo {
+            service,
+            command,
+            index: None,
+            subindex: None,
+            complete_access: false,
+            expedited_data: None,
+            size: None,
+            abort_code: None,
+            data: &[],
+        };
+        if matches!(command, SdoCommand::DownloadSegment | SdoCommand::UploadSegment) {
+            // a segment of the minimum size tells how many of its 7 data bytes are unused.
+            let unused = if sdo.len() == SDO_HEADER_LEN { ((command_byte >> 1) & 0x7) as usize } else { 0 };
+            parsed.data = &sdo[1..sdo.len() - unused];
+            return Ok(parsed);
+        }
+        parsed.index = Some(u16::from_le_bytes([sdo[1], sdo[2]]));
+        parsed.subindex = Some(sdo[3]);
+        parsed.complete_access = command_byte & 0x10 != 0;
+        match command {
+            SdoCommand::Abort => parsed.abort_code = Some(field),
+            // the initiating direction of the data: the download request and the upload response.
+            SdoCommand::InitiateDownload | SdoCommand::InitiateUpload
+                if (command == SdoCommand::InitiateDownload) == (service == CoeService::SdoRequest) =>
+            {
+                let size_indicated = command_byte & 0x01 != 0;
+                if command_byte & 0x02 != 0 {
+                    let unused = if size_indicated { ((command_byte >> 2) & 0x3) as usize } else { 0 };
+                    parsed.expedited_data = Some(&sdo[4..8 - unused]);
+                } else {
+                    parsed.size = Some(field);
+                    parsed.data = &sdo[SDO_HEADER_LEN..];
+                }
+            }
+            _ => {}
+        }
+        Ok(parsed)
+    }
+}
+
+/// An emergency message: an error the slave raises, or its reset.
+#[derive(Clone, Copy, Debug)]
+pub struct Emergency<'a> {
+    pub error_code: u16,
+    /// The error register, object 0x1001.
+    pub error_register: u8,
+    /// Manufacturer specific error data, 5 bytes.
+    pub data: &'a [u8],
+}
+
+/// Bytes of an emergency message after the CoE header.
+pub const EMERGENCY_LEN: usize = 8;
+
+impl<'a> Emergency<'a> {
+    /// Parses the emergency message of `payload`, the payload of a CoE mailbox message.
+    pub fn parse(payload: &'a [u8]) -> Result<Self, ParseError> {
+        if payload.len() < COE_HEADER_LEN {
+            return Err(ParseError::TruncatedEmergency { len: payload.len() });
+        }
+        let service_number = (u16::from_le_bytes([payload[0], payload[1]]) >> 12) as u8;
+        if CoeService::from_u8(service_number) != Some(CoeService::Emergency) {
+            return Err(ParseError::NotEmergency(service_number));
+        }
+        if payload.len() < COE_HEADER_LEN + EMERGENCY_LEN {
+            return Err(ParseError::TruncatedEmergency { len: payload.len() });
+        }
+        let emergency = &payload[COE_HEADER_LEN..COE_HEADER_LEN + EMERGENCY_LEN];
+        Ok(Emergency {
+            error_code: u16::from_le_bytes([emergency[0], emergency[1]]),
+            error_register: emergency[2],
+            data: &emergency[3..],
+        })
+    }
+}
+
+/// The class of an emergency error code, from its upper byte or bytes as listed by CiA 301.
+pub fn error_class(error_code: u16) -> &'static str {
+    match error_code >> 8 {
+        0x00 => "error reset",
+        0x10 => "generic error",
+        0x20..=0x2F => "current",
+        0x30..=0x3F => "voltage",
+        0x40..=0x4F => "temperature",
+        0x50 => "device hardware",
+        0x60..=0x6F => "device software",
+        0x70 => "additional modules",
+        0x81 => "communication",
+        0x82 => "protocol error",
+        0x80..=0x8F => "monitoring",
+        0x90 => "external error",
+        0xA0 => "EtherCAT state machine transition error",
+        0xF0 => "additional functions",
+        0xFF => "device specific",
+        _ => "reserved",
+    }
+}
+
+/// The meaning of an SDO abort code, as listed by ETG.1000.6.
+pub fn abort_message(code: u32) -> Option<&'static str> {
+    let message = match code {
+        0x0503_0000 => "Toggle bit not changed",
+        0x0504_0000 => "SDO protocol timeout",
+        0x0504_0001 => "Client/Server command specifier not valid or unknown",
+        0x0504_0005 => "Out of memory",
+        0x0601_0000 => "Unsupported access to an object",
+        0x0601_0001 => "Attempt to read a write only object",
+        0x0601_0002 => "Attempt to write a read only object",
+        0x0601_0003 => "Subindex cannot be written, SI0 must be 0 for write access",
+        0x0601_0004 => "SDO Complete access not supported for objects of variable length",
+        0x0601_0005 => "Object length exceeds mailbox size",
+        0x0601_0006 => "Object mapped to RxPDO, SDO Download blocked",
+        0x0602_0000 => "The object does not exist in the object directory",
+        0x0604_0041 => "The object can not be mapped into the PDO",
+        0x0604_0042 => "The number and length of the objects to be mapped would exceed the PDO length",
+        0x0604_0043 => "General parameter incompatibility reason",
+        0x0604_0047 => "General internal incompatibility in the device",
+        0x0606_0000 => "Access failed due to a hardware error",
+        0x0607_0010 => "Data type does not match, length of service parameter does not match",
+        0x0607_0012 => "Data type does not match, length of service parameter too high",
+        0x0607_0013 => "Data type does not match, length of service parameter too low",
+        0x0609_0011 => "Subindex does not exist",
+        0x0609_0030 => "Value range of parameter exceeded",
+        0x0609_0031 => "Value of parameter written too high",
+        0x0609_0032 => "Value of parameter written too low",
+        0x0609_0036 => "Maximum value is less than minimum value",
+        0x0800_0000 => "General error",
+        0x0800_0020 => "Data cannot be transferred or stored to the application",
+        0x0800_0021 => "Data cannot be transferred or stored to the application because of local control",
+        0x0800_0022 => "Data cannot be transferred or stored to the application because of the present device state",
+        0x0800_0023 => "Object dictionary dynamic generation fails or no object dictionary is present",
+        _ => return None,
+    };
+    Some(message)
+}
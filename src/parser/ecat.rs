@@ -0,0 +1,348 @@
+//! The EtherCAT frame and its datagrams.
+
+use super::netvars::NetworkVariables;
+use super::ParseError;
+use std::fmt::Display;
+use std::str::FromStr;
+
+pub const ECAT_HEADER_LEN: usize = 2;
+/// Bytes of a datagram header, in front of the payload.
+const DATAGRAM_HEADER_LEN: usize = 10;
+/// Bytes of the working counter, behind the payload.
+const WKC_LEN: usize = 2;
+
+/// Frame types of the EtherCAT header (ETG.1000.4): datagrams, network variables and the
+/// mailbox messages of a mailbox gateway.
+const FRAME_TYPE_DATAGRAMS: u8 = 1;
+const FRAME_TYPE_NETWORK_VARIABLES: u8 = 4;
+const FRAME_TYPE_MAILBOX: u8 = 5;
+
+/// An EtherCAT frame: its header and the chain of datagrams following it, with the datagrams
+/// of the EtherCAT frames following it in the same Ethernet frame. A frame of another type
+/// has no datagrams, its content is in `content`.
+#[derive(Default)]
+pub struct EtherCATFrame<'a> {
+    pub header: EtherCATFrameHeader,
+    pub datagrams: Vec<EtherCATDatagram<'a>>,
+    /// What a frame of another type than datagrams carries.
+    pub content: Option<FrameContent<'a>>,
+    /// The headers of the EtherCAT frames following the first one, each with the index in
+    /// `datagrams` of its first datagram.
+    pub following: Vec<(usize, EtherCATFrameHeader)>,
+    /// Bytes behind the last EtherCAT frame, such as the padding of a short Ethernet frame.
+    pub padding: usize,
+}
+
+fn parse_header(data: &[u8]) -> Result<EtherCATFrameHeader, ParseError> {
+    if data.len() < ECAT_HEADER_LEN {
+        return Err(ParseError::TruncatedEtherCATHeader);
+    }
+    let len_rsv_type = u16::from_le_bytes([data[0], data[1]]);
+    Ok(EtherCATFrameHeader {
+        length: len_rsv_type & 0x07_FF,
+        reserved: (len_rsv_type >> 11) as u8 & 0x1,
+        ecat_frame_type: (len_rsv_type >> 12) as u8 & 0xF,
+    })
+}
+
+impl<'a> EtherCATFrame<'a> {
+    /// Parses the EtherCAT frame at the start of `data`. The chain of datagrams ends at the
+    /// length of the EtherCAT header, when the frame holds it, even if the last datagram says
+    /// more follow; behind it, another EtherCAT header whose datagrams fill its length starts a
+    /// following frame, and anything else is padding.
+    pub fn parse(data: &'a [u8]) -> Result<Self, ParseError> {
+        let header = parse_header(data)?;
+        let length = header.length as usize;
+        if header.ecat_frame_type != FRAME_TYPE_DATAGRAMS {
+            let end = (ECAT_HEADER_LEN + length).min(data.len());
+            let content = FrameContent::parse(header.frame_type(), &data[ECAT_HEADER_LEN..end])?;
+            return Ok(EtherCATFrame { header, content: Some(content), padding: data.len() - end, ..Default::default() });
+        }
+        let limit = (length > 0 && ECAT_HEADER_LEN + length <= data.len()).then_some(length);
+        let mut datagrams = EtherCATDatagram::parse_chain(&data[ECAT_HEADER_LEN..], limit)?;
+        let mut end = ECAT_HEADER_LEN + datagrams.iter().map(EtherCATDatagram::size).sum::<usize>();
+        let mut following = Vec::new();
+        if end == ECAT_HEADER_LEN + length {
+            while let Some((header, more)) = Self::parse_following(&data[end..]) {
+                end += ECAT_HEADER_LEN + header.length as usize;
+                following.push((datagrams.len(), header));
+                datagrams.extend(more);
+            }
+        }
+        Ok(EtherCATFrame {
+            header,
+            datagrams,
+            content: None,
+            following,
+            padding: data.len().saturating_sub(end),
+        })
+    }
+
+    /// The EtherCAT frame at the start of `data`, if its header announces datagrams whose
+    /// chain ends exactly at its length, as a frame following another must.
+    fn parse_following(data: &'a [u8]) -> Option<(EtherCATFrameHeader, Vec<EtherCATDatagram<'a>>)> {
+        let header = parse_header(data).ok()?;
+        let length = header.length as usize;
+        if header.ecat_frame_type != FRAME_TYPE_DATAGRAMS || header.reserved != 0 || length == 0 || ECAT_HEADER_LEN + length > data.len() {
+            return None;
+        }
+        let datagrams = EtherCATDatagram::parse_chain(&data[ECAT_HEADER_LEN..], Some(length)).ok()?;
+        let chain = datagrams.iter().map(EtherCATDatagram::size).sum::<usize>();
+        let ends = datagrams.last().is_some_and(EtherCATDatagram::is_last_datagram);
+        (chain == length && ends).then_some((header, datagrams))
+    }
+
+    /// The offset of every datagram header from the start of the EtherCAT frame, past the
+    /// headers of the frames following it.
+    pub fn datagram_offsets(&self) -> Vec<usize> {
+        let mut offset = ECAT_HEADER_LEN;
+        let mut following = self.following.iter().peekable();
+        self.datagrams.iter().enumerate().map(|(i, datagram)| {
+            if following.next_if(|(first, _)| *first == i).is_some() {
+                offset += ECAT_HEADER_LEN;
+            }
+            let at = offset;
+            offset += datagram.size();
+            at
+        }).collect()
+    }
+
+    /// The datagrams of the first EtherCAT frame, which its header length covers.
+    pub fn first_frame_datagrams(&self) -> &[EtherCATDatagram<'a>] {
+        let end = self.following.first().map_or(self.datagrams.len(), |(first, _)| *first);
+        &self.datagrams[..end]
+    }
+}
+
+#[derive(Default)]
+pub struct EtherCATFrameHeader {
+    pub length: u16,
+    pub reserved: u8,
+    pub ecat_frame_type: u8,
+}
+
+impl EtherCATFrameHeader {
+    pub fn frame_type(&self) -> FrameType {
+        match self.ecat_frame_type {
+            FRAME_TYPE_DATAGRAMS => FrameType::Datagrams,
+            FRAME_TYPE_NETWORK_VARIABLES => FrameType::NetworkVariables,
+            FRAME_TYPE_MAILBOX => FrameType::Mailbox,
+            other => FrameType::Unknown(other),
+        }
+    }
+}
+
+/// The type of an EtherCAT frame, which tells what follows its header.
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Debug)]
+pub enum FrameType {
+    Datagrams,
+    NetworkVariables,
+    /// Mailbox messages of a mailbox gateway.
+    Mailbox,
+    Unknown(u8),
+}
+
+impl Display for FrameType {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Datagrams => write!(f, "datagrams"),
+            Self::NetworkVariables => write!(f, "network variables"),
+            Self::Mailbox => write!(f, "mailbox messages"),
+            Self::Unknown(frame_type) => write!(f, "type {}", frame_type),
+        }
+    }
+}
+
+/// The content of an EtherCAT frame of another type than datagrams, up to its length.
+pub enum FrameContent<'a> {
+    NetworkVariables(NetworkVariables<'a>),
+    /// A mailbox message, its mailbox header first.
+    Mailbox(&'a [u8]),
+    Unknown(&'a [u8]),
+}
+
+impl<'a> FrameContent<'a> {
+    fn parse(frame_type: FrameType, data: &'a [u8]) -> Result<Self, ParseError> {
+        Ok(match frame_type {
+            FrameType::NetworkVariables => FrameContent::NetworkVariables(NetworkVariables::parse(data)?),
+            FrameType::Mailbox => FrameContent::Mailbox(data),
+            _ => FrameContent::Unknown(data),
+        })
+    }
+}
+
+#[derive(Default, Debug)]
+pub struct EtherCATDatagram<'a> {
+    pub header: EtherCATDatagramHeader,
+    /// The `length` bytes of data between the header and the working counter.
+    pub data: &'a [u8],
+    pub wkc: u16,
+}
+
+impl<'a> EtherCATDatagram<'a> {
+    /// Parses the chain of datagrams up to the one without the "more datagrams follow" bit.
+    pub fn parse_datagrams(data_buf: &'a[u8]) -> Result<Vec<Self>, ParseError> {
+        Self::parse_chain(data_buf, None)
+    }
+
+    /// Parses the chain of datagrams up to the one without the "more datagrams follow" bit,
+    /// or up to the first one ending at or past `limit` bytes.
+    fn parse_chain(data_buf: &'a[u8], limit: Option<usize>) -> Result<Vec<Self>, ParseError> {
+        let mut datagrams = Vec::new();
+        let mut next_datagram_offset = 0;
+        loop {
+            let datagram = EtherCATDatagram::parse_one_datagram(&data_buf[next_datagram_offset..], datagrams.len())?;
+            let is_last_datagram = datagram.is_last_datagram();
+            next_datagram_offset += datagram.size();
+
+            datagrams.push(datagram);
+
+            if is_last_datagram || limit.is_some_and(|limit| next_datagram_offset >= limit) {
+                break;
+            }
+        }
+        Ok(datagrams)
+    }
+
+    /// Parses the datagram at the start of `data_buf`, the one at position `datagram` in its frame.
+    fn parse_one_datagram(data_buf: &'a[u8], datagram: usize) -> Result<Self, ParseError> {
+        let truncated = |needed| ParseError::TruncatedDatagram { datagram, needed, available: data_buf.len() };
+        if data_buf.len() < DATAGRAM_HEADER_LEN {
+            return Err(truncated(DATAGRAM_HEADER_LEN));
+        }
+        let cmd: EtherCATCommand = match data_buf[0] {
+            0 => EtherCATCommand::NOP,
+            1 => EtherCATCommand::APRD,
+            2 => EtherCATCommand::APWR,
+            3 => EtherCATCommand::APRW,
+            4 => EtherCATCommand::FPRD,
+            5 => EtherCATCommand::FPWR,
+            6 => EtherCATCommand::FPRW,
+            7 => EtherCATCommand::BRD,
+            8 => EtherCATCommand::BWR,
+            9 => EtherCATCommand::BRW,
+            10 => EtherCATCommand::LRD,
+            11 => EtherCATCommand::LWR,
+            12 => EtherCATCommand::LRW,
+            13 => EtherCATCommand::ARMW,
+            14 => EtherCATCommand::FRMW,
+            _ => EtherCATCommand::UNKNOWN,
+        };
+        let index = data_buf[1];
+        let slave_addr = u16::from_le_bytes([data_buf[2], data_buf[3]]);
+        let offset_addr = u16::from_le_bytes([data_buf[4], data_buf[5]]);
+        let len_rtr_last = u16::from_le_bytes([data_buf[6], data_buf[7]]);
+        let length = len_rtr_last & 0x07_FF;
+        let reserved = (len_rtr_last >> 11) as u8 & 0x7;
+        let round_trip = (len_rtr_last >> 14) as u8 & 0x1;
+        let last_indicator = (len_rtr_last >> 15) as u8 & 0x1;
+        let irq = u16::from_le_bytes([data_buf[8], data_buf[9]]);
+        let wkc_offset = DATAGRAM_HEADER_LEN + length as usize;
+        if data_buf.len() < wkc_offset + WKC_LEN {
+            return Err(truncated(wkc_offset + WKC_LEN));
+        }
+        let data = &data_buf[DATAGRAM_HEADER_LEN..wkc_offset];
+        let wkc = u16::from_le_bytes([data_buf[wkc_offset], data_buf[wkc_offset + 1]]);
+
+        Ok(EtherCATDatagram {
+            header: EtherCATDatagramHeader {
+                cmd,
+                index,
+                slave_addr,
+                offset_addr,
+                length,
+                reserved,
+                round_trip,
+                last_indicator,
+                irq
+            },
+            data,
+            wkc
+        })
+    }
+
+    pub fn is_last_datagram(&self) -> bool {
+        self.header.last_indicator == 0
+    }
+
+    pub fn size(&self) -> usize {
+        DATAGRAM_HEADER_LEN + self.header.length as usize + WKC_LEN
+    }
+}
+
+#[derive(Default, Debug)]
+pub struct EtherCATDatagramHeader {
+    pub cmd: EtherCATCommand,
+    pub index: u8,
+    pub slave_addr: u16,
+    pub offset_addr: u16,
+    pub length: u16,
+    pub reserved: u8,
+    pub round_trip: u8,
+    pub last_indicator: u8,
+    pub irq: u16,
+}
+
+#[derive(Default, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[allow(clippy::upper_case_acronyms)]
+pub enum EtherCATCommand {
+    NOP = 0,
+    APRD,
+    APWR,
+    APRW,
+    FPRD,
+    FPWR,
+    FPRW,
+    BRD,
+    BWR,
+    BRW,
+    LRD,
+    LWR,
+    LRW,
+    ARMW,
+    FRMW,
+    #[default] UNKNOWN,
+}
+
+impl Display for EtherCATCommand{
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let cmd_str = match self {
+            Self::NOP => "NOP",
+            Self::APRD => "APRD",
+            Self::APWR => "APWR",
+            Self::APRW => "APRW",
+            Self::FPRD => "FPRD",
+            Self::FPWR => "FPWR",
+            Self::FPRW => "FPRW",
+            Self::BRD => "BRD",
+            Self::BWR => "BWR",
+            Self::BRW => "BRW",
+            Self::LRD => "LRD",
+            Self::LWR => "LWR",
+            Self::LRW => "LRW",
+            Self::ARMW => "ARMW",
+            Self::FRMW => "FRMW",
+            Self::UNKNOWN => "UNKNOWN"
+        };
+
+        write!(f, "{}", cmd_str)
+    }
+}
+
+impl EtherCATCommand {
+    pub const ALL: [EtherCATCommand; 15] = [
+        Self::NOP, Self::APRD, Self::APWR, Self::APRW, Self::FPRD, Self::FPWR, Self::FPRW, Self::BRD,
+        Self::BWR, Self::BRW, Self::LRD, Self::LWR, Self::LRW, Self::ARMW, Self::FRMW,
+    ];
+}
+
+impl FromStr for EtherCATCommand {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::ALL
+            .into_iter()
+            .find(|cmd| cmd.to_string().eq_ignore_ascii_case(s))
+            .ok_or_else(|| format!("unknown EtherCAT command '{}'", s))
+    }
+}
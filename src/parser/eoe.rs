@@ -0,0 +1,63 @@
+//! Ethernet over EtherCAT: the fragments of the EoE mailbox protocol, which tunnels Ethernet
+//! frames between the master and the slaves.
+
+use super::ParseError;
+
+/// Bytes of the EoE header in front of every fragment.
+pub const EOE_HEADER_LEN: usize = 4;
+/// Fragment offsets and frame sizes are given in units of 32 bytes.
+pub const OFFSET_UNIT: usize = 32;
+/// EoE type of the fragments carrying Ethernet frames; the others are IP and filter services.
+pub const FRAGMENT_DATA: u8 = 0;
+/// Bytes of the time stamp some slaves append to the last fragment of a frame.
+const TIMESTAMP_LEN: usize = 4;
+
+/// A fragment of a tunnelled Ethernet frame.
+#[derive(Clone, Copy, Debug)]
+pub struct EoeFragment<'a> {
+    /// The switch port of the slave the frame goes to or comes from.
+    pub port: u8,
+    pub last_fragment: bool,
+    /// Number of the fragment in its frame, from 0.
+    pub fragment_number: u8,
+    /// Offset of the fragment in its frame, in bytes; the first fragment carries the size of
+    /// the whole frame, rounded up to 32 bytes, where the others carry their offset.
+    pub offset: usize,
+    /// Number of the frame, telling the fragments of consecutive frames apart.
+    pub frame_number: u8,
+    pub data: &'a [u8],
+    /// Time stamp of the frame, in nanoseconds, appended to its last fragment.
+    pub timestamp: Option<u32>,
+}
+
+impl<'a> EoeFragment<'a> {
+    /// Parses `payload`, the payload of an EoE mailbox message.
+    pub fn parse(payload: &'a [u8]) -> Result<Self, ParseError> {
+        if payload.len() < EOE_HEADER_LEN {
+            return Err(ParseError::TruncatedEoeHeader { len: payload.len() });
+        }
+        let frame_type = payload[0] & 0x0F;
+        if frame_type != FRAGMENT_DATA {
+            return Err(ParseError::NotEoeFragment(frame_type));
+        }
+        let last_fragment = payload[1] & 0x1 != 0;
+        let time_appended = payload[1] & 0x2 != 0;
+        let info = u16::from_le_bytes([payload[2], payload[3]]);
+        let mut data = &payload[EOE_HEADER_LEN..];
+        let mut timestamp = None;
+        if last_fragment && time_appended {
+            let (frame, time) = data.split_at(data.len().saturating_sub(TIMESTAMP_LEN));
+            data = frame;
+            timestamp = time.try_into().ok().map(u32::from_le_bytes);
+        }
+        Ok(EoeFragment {
+            port: payload[0] >> 4,
+            last_fragment,
+            fragment_number: (info & 0x3F) as u8,
+            offset: ((info >> 6) & 0x3F) as usize * OFFSET_UNIT,
+            frame_number: (info >> 12) as u8,
+            data,
+            timestamp,
+        })
+    }
+}
@@ -0,0 +1,147 @@
+//! The Ethernet header in front of the EtherCAT frame.
+
+use super::ecat::EtherCATFrame;
+use super::ParseError;
+
+pub const ETHERNET_HEADER_LEN: usize = 14;
+pub const ETHERTYPE_ETHERCAT: u16 = 0x88A4;
+pub const ETHERTYPE_IPV4: u16 = 0x0800;
+/// Tag protocol identifiers of 802.1Q VLAN tags, 802.1ad service tags (QinQ) and the service
+/// tags of the switches predating 802.1ad.
+const VLAN_TPIDS: [u16; 3] = [0x8100, 0x88A8, 0x9100];
+const VLAN_TAG_LEN: usize = 4;
+/// UDP port of EtherCAT carried in UDP datagrams, the EtherCAT EtherType.
+pub const UDP_PORT_ETHERCAT: u16 = 0x88A4;
+const IPV4_HEADER_MIN_LEN: usize = 20;
+const IP_PROTOCOL_UDP: u8 = 17;
+const UDP_HEADER_LEN: usize = 8;
+
+/// An Ethernet frame and the EtherCAT frame it carries.
+#[derive(Default)]
+pub struct EtherNetFrame<'a> {
+    pub dst_mac: [u8; 6],
+    pub src_mac: [u8; 6],
+    pub frame_type: u16, //should be 0x88a4(EtherCAT Frame) in this application.
+    /// The VLAN IDs of the VLAN tags in front of the EtherType, outermost first.
+    pub vlan_ids: Vec<u16>,
+    /// Offset of the EtherCAT header in the frame: right after the Ethernet header and its VLAN
+    /// tags, or behind the IPv4 and UDP headers of EtherCAT over UDP.
+    pub ecat_offset: usize,
+    pub ecat_frame: EtherCATFrame<'a>
+}
+
+/// The EtherType of a frame, behind its VLAN tags, with the length of the Ethernet header and
+/// the tags; `None` if the frame ends before.
+pub fn ether_type(data: &[u8]) -> Option<(u16, usize)> {
+    let mut header_len = ETHERNET_HEADER_LEN;
+    loop {
+        let bytes = data.get(header_len - 2..header_len)?;
+        let ether_type = u16::from_be_bytes([bytes[0], bytes[1]]);
+        if !VLAN_TPIDS.contains(&ether_type) {
+            return Some((ether_type, header_len));
+        }
+        header_len += VLAN_TAG_LEN;
+    }
+}
+
+/// Where the EtherCAT frame of an IPv4 frame is, if it is a UDP datagram from or to the
+/// EtherCAT port: its offset, and its bytes up to the end of the UDP datagram.
+fn udp_payload(data: &[u8], ip_start: usize) -> Result<Option<(usize, &[u8])>, ParseError> {
+    let truncated = Err(ParseError::TruncatedUdpHeader { len: data.len() });
+    let Some(ip) = data.get(ip_start..ip_start + IPV4_HEADER_MIN_LEN) else {
+        return truncated;
+    };
+    let ip_header_len = (ip[0] & 0x0F) as usize * 4;
+    if ip[0] >> 4 != 4 || ip_header_len < IPV4_HEADER_MIN_LEN || ip[9] != IP_PROTOCOL_UDP {
+        return Ok(None);
+    }
+    let udp_start = ip_start + ip_header_len;
+    let Some(udp) = data.get(udp_start..udp_start + UDP_HEADER_LEN) else {
+        return truncated;
+    };
+    let source_port = u16::from_be_bytes([udp[0], udp[1]]);
+    let destination_port = u16::from_be_bytes([udp[2], udp[3]]);
+    if source_port != UDP_PORT_ETHERCAT && destination_port != UDP_PORT_ETHERCAT {
+        return Ok(None);
+    }
+    // the UDP length leaves out the padding of short Ethernet frames.
+    let udp_len = (u16::from_be_bytes([udp[4], udp[5]]) as usize).max(UDP_HEADER_LEN);
+    let offset = udp_start + UDP_HEADER_LEN;
+    Ok(Some((offset, &data[offset..(udp_start + udp_len).min(data.len())])))
+}
+
+/// Whether a frame carries EtherCAT, by its EtherType behind the VLAN tags or in a UDP
+/// datagram of the EtherCAT port, without parsing it. The frames too short to tell are taken
+/// for EtherCAT, for the parser to report.
+pub fn is_ethercat(data: &[u8]) -> bool {
+    match ether_type(data) {
+        None | Some((ETHERTYPE_ETHERCAT, _)) => true,
+        Some((ETHERTYPE_IPV4, header_len)) => !matches!(udp_payload(data, header_len), Ok(None)),
+        Some(_) => false,
+    }
+}
+
+impl<'a> EtherNetFrame<'a> {
+    /// Parses the frame as EtherCAT whatever its EtherType, skipping its VLAN tags; in an IPv4
+    /// frame, EtherCAT over UDP starts behind the IPv4 and UDP headers.
+    pub fn parse(data: &'a [u8]) -> Result<Self, ParseError> {
+        let Some((frame_type, header_len)) = ether_type(data) else {
+            return Err(ParseError::TruncatedEthernetHeader { len: data.len() });
+        };
+        let mut dst_mac: [u8; 6] = [0; 6];
+        dst_mac.copy_from_slice(&data[0..6]);
+
+        let mut src_mac: [u8; 6] = [0; 6];
+        src_mac.copy_from_slice(&data[6..12]);
+
+        // the VLAN ID is the low 12 bits of the tag control information.
+        let vlan_ids = data[12..header_len - 2].chunks(VLAN_TAG_LEN).map(|tag| u16::from_be_bytes([tag[2], tag[3]]) & 0x0FFF).collect();
+        let udp = if frame_type == ETHERTYPE_IPV4 { udp_payload(data, header_len)? } else { None };
+        let (ecat_offset, payload) = udp.unwrap_or((header_len, &data[header_len..]));
+
+        Ok(EtherNetFrame {
+            dst_mac,
+            src_mac,
+            frame_type,
+            vlan_ids,
+            ecat_offset,
+            ecat_frame: EtherCATFrame::parse(payload)?
+        })
+    }
+}
+
+impl<'a> EtherNetFrame<'a> {
+    /// Parses the frame if it carries EtherCAT, directly or over UDP, for the analyses that skip
+    /// other traffic and the frames that do not parse.
+    pub fn parse_ethercat(data: &'a [u8]) -> Option<Self> {
+        if !is_ethercat(data) {
+            return None;
+        }
+        Self::parse(data).ok().filter(Self::carries_ethercat)
+    }
+
+    /// Whether the frame carries EtherCAT: by its EtherType, or in a UDP datagram of the
+    /// EtherCAT port.
+    pub fn carries_ethercat(&self) -> bool {
+        self.frame_type == ETHERTYPE_ETHERCAT || self.ecat_offset != self.header_len()
+    }
+
+    /// The length of the Ethernet header with its VLAN tags.
+    pub fn header_len(&self) -> usize {
+        ETHERNET_HEADER_LEN + VLAN_TAG_LEN * self.vlan_ids.len()
+    }
+
+    /// Whether the frame has passed the slaves: the first slave sets the locally administered
+    /// bit of the source MAC address on the way back to the master.
+    pub fn is_returned(&self) -> bool {
+        self.src_mac[0] & 0x02 != 0
+    }
+
+    /// The MAC address of the master that sent the frame: the source address, without the
+    /// locally administered bit of the returned frames.
+    pub fn master_mac(&self) -> [u8; 6] {
+        let mut master = self.src_mac;
+        master[0] &= !0x02;
+        master
+    }
+}
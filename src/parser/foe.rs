@@ -0,0 +1,68 @@
+//! File access over EtherCAT: the messages of the FoE mailbox protocol, which masters use to
+//! download firmware to slaves and to read files from them.
+
+use super::ParseError;
+
+/// Bytes of the FoE header: the opcode, a reserved byte and the 32-bit field after it.
+pub const FOE_HEADER_LEN: usize = 6;
+
+/// An FoE message.
+#[derive(Clone, Copy, Debug)]
+pub enum FoeMessage<'a> {
+    /// The master asks for a file of the slave.
+    ReadRequest { password: u32, filename: &'a [u8] },
+    /// The master announces a file it sends to the slave.
+    WriteRequest { password: u32, filename: &'a [u8] },
+    /// A part of the file, numbered from 1.
+    Data { packet: u32, data: &'a [u8] },
+    /// The receiver acknowledges a packet, or the write request with packet 0.
+    Ack { packet: u32 },
+    Error { code: u32, text: &'a [u8] },
+    /// The slave needs time before the next packet, `done` out of `entire`.
+    Busy { done: u16, entire: u16, text: &'a [u8] },
+}
+
+impl<'a> FoeMessage<'a> {
+    /// Parses `payload`, the payload of an FoE mailbox message.
+    pub fn parse(payload: &'a [u8]) -> Result<Self, ParseError> {
+        if payload.len() < FOE_HEADER_LEN {
+            return Err(ParseError::TruncatedFoeHeader { len: payload.len() });
+        }
+        let field = u32::from_le_bytes([payload[2], payload[3], payload[4], payload[5]]);
+        let rest = &payload[FOE_HEADER_LEN..];
+        match payload[0] {
+            1 => Ok(FoeMessage::ReadRequest { password: field, filename: trim_nul(rest) }),
+            2 => Ok(FoeMessage::WriteRequest { password: field, filename: trim_nul(rest) }),
+            3 => Ok(FoeMessage::Data { packet: field, data: rest }),
+            4 => Ok(FoeMessage::Ack { packet: field }),
+            5 => Ok(FoeMessage::Error { code: field, text: trim_nul(rest) }),
+            6 => Ok(FoeMessage::Busy { done: field as u16, entire: (field >> 16) as u16, text: trim_nul(rest) }),
+            opcode => Err(ParseError::UnknownFoeOpcode(opcode)),
+        }
+    }
+}
+
+/// Strings end at their first NUL, the rest of the mailbox being padding.
+fn trim_nul(text: &[u8]) -> &[u8] {
+    text.iter().position(|byte| *byte == 0).map_or(text, |end| &text[..end])
+}
+
+/// The meaning of an FoE error code, as listed by ETG.1000.6.
+pub fn error_message(code: u32) -> Option<&'static str> {
+    let message = match code {
+        0x8000 => "Not defined",
+        0x8001 => "Not found",
+        0x8002 => "Access denied",
+        0x8003 => "Disk full",
+        0x8004 => "Illegal",
+        0x8005 => "Packet number wrong",
+        0x8006 => "Already exists",
+        0x8007 => "No user",
+        0x8008 => "Bootstrap only",
+        0x8009 => "Not Bootstrap",
+        0x800A => "No rights",
+        0x800B => "Program Error",
+        _ => return None,
+    };
+    Some(message)
+}
@@ -0,0 +1,124 @@
+//! The mailbox header in front of the protocol payload of the datagrams exchanged with the
+//! mailbox SyncManagers of a slave.
+
+use super::ParseError;
+use std::fmt::Display;
+
+pub const MAILBOX_HEADER_LEN: usize = 6;
+
+/// The protocol of a mailbox message.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum MailboxType {
+    /// A mailbox error reply of the slave.
+    Error,
+    AoE,
+    EoE,
+    CoE,
+    FoE,
+    SoE,
+    VoE,
+}
+
+impl MailboxType {
+    pub fn from_u8(value: u8) -> Option<Self> {
+        match value {
+            0 => Some(MailboxType::Error),
+            1 => Some(MailboxType::AoE),
+            2 => Some(MailboxType::EoE),
+            3 => Some(MailboxType::CoE),
+            4 => Some(MailboxType::FoE),
+            5 => Some(MailboxType::SoE),
+            15 => Some(MailboxType::VoE),
+            _ => None,
+        }
+    }
+}
+
+impl Display for MailboxType {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let type_str = match self {
+            Self::Error => "ERR",
+            Self::AoE => "AoE",
+            Self::EoE => "EoE",
+            Self::CoE => "CoE",
+            Self::FoE => "FoE",
+            Self::SoE => "SoE",
+            Self::VoE => "VoE",
+        };
+
+        write!(f, "{}", type_str)
+    }
+}
+
+/// The header of a mailbox message.
+#[derive(Clone, Copy, Debug)]
+pub struct MailboxHeader {
+    /// Bytes of the protocol payload following the header.
+    pub length: u16,
+    /// Station address of the source of the message, 0 for the master.
+    pub address: u16,
+    pub channel: u8,
+    pub priority: u8,
+    pub mbx_type: MailboxType,
+    /// Counter of the message, 1 to 7, 0 for a message outside the counter sequence.
+    pub counter: u8,
+}
+
+impl MailboxHeader {
+    /// Parses the header at the start of `data`, the payload of a mailbox datagram.
+    pub fn parse(data: &[u8]) -> Result<Self, ParseError> {
+        if data.len() < MAILBOX_HEADER_LEN {
+            return Err(ParseError::TruncatedMailboxHeader { len: data.len() });
+        }
+        let mbx_type = MailboxType::from_u8(data[5] & 0x0F).ok_or(ParseError::UnknownMailboxType(data[5] & 0x0F))?;
+        Ok(MailboxHeader {
+            length: u16::from_le_bytes([data[0], data[1]]),
+            address: u16::from_le_bytes([data[2], data[3]]),
+            channel: data[4] & 0x3F,
+            priority: data[4] >> 6,
+            mbx_type,
+            counter: (data[5] >> 4) & 0x7,
+        })
+    }
+}
+
+/// Type of the mailbox error replies, in the first word of their payload.
+const MAILBOX_ERROR_TYPE: u16 = 0x0001;
+const MAILBOX_ERROR_LEN: usize = 4;
+
+/// The reply of type `Error` a slave sends instead of processing a mailbox message it cannot.
+#[derive(Clone, Copy, Debug)]
+pub struct MailboxError {
+    pub code: u16,
+}
+
+impl MailboxError {
+    /// Parses the error reply of `payload`, the payload of a mailbox message of type `Error`.
+    pub fn parse(payload: &[u8]) -> Result<Self, ParseError> {
+        if payload.len() < MAILBOX_ERROR_LEN {
+            return Err(ParseError::TruncatedMailboxError { len: payload.len() });
+        }
+        let reply_type = u16::from_le_bytes([payload[0], payload[1]]);
+        if reply_type != MAILBOX_ERROR_TYPE {
+            return Err(ParseError::NotMailboxError(reply_type));
+        }
+        Ok(MailboxError { code: u16::from_le_bytes([payload[2], payload[3]]) })
+    }
+}
+
+/// The meaning of a mailbox error code, as listed by ETG.1000.6.
+pub fn error_message(code: u16) -> Option<&'static str> {
+    let message = match code {
+        0x0001 => "Syntax of the mailbox header is wrong",
+        0x0002 => "Mailbox protocol not supported",
+        0x0003 => "Channel field contains a wrong value",
+        0x0004 => "Service not supported",
+        0x0005 => "Header of the mailbox protocol is wrong",
+        0x0006 => "Mailbox data too short",
+        0x0007 => "Not enough memory to process the mailbox protocol",
+        0x0008 => "Length of the data is inconsistent",
+        0x0009 => "Mailbox service already in use",
+        _ => return None,
+    };
+    Some(message)
+}
@@ -0,0 +1,48 @@
+//! The network variables of the EtherCAT frames a publisher sends to its subscribers.
+
+use super::ParseError;
+
+/// Bytes of the publisher header, and of the header of each network variable.
+const PUBLISHER_HEADER_LEN: usize = 12;
+const NETWORK_VARIABLE_HEADER_LEN: usize = 8;
+
+/// The network variables of one frame, behind the header of their publisher.
+pub struct NetworkVariables<'a> {
+    /// The ID of the publisher, usually its MAC address.
+    pub publisher_id: [u8; 6],
+    /// The cycle counter of the publisher.
+    pub cycle: u16,
+    pub variables: Vec<NetworkVariable<'a>>,
+}
+
+pub struct NetworkVariable<'a> {
+    pub index: u16,
+    /// Hash of the description of the variable, which its subscribers check.
+    pub hash: u16,
+    pub quality: u16,
+    pub data: &'a [u8],
+}
+
+impl<'a> NetworkVariables<'a> {
+    /// Parses the publisher header and the number of network variables it announces, from the
+    /// bytes behind the EtherCAT header.
+    pub fn parse(data: &'a [u8]) -> Result<Self, ParseError> {
+        let truncated = |needed| ParseError::TruncatedNetworkVariables { needed, available: data.len() };
+        let header = data.get(..PUBLISHER_HEADER_LEN).ok_or(truncated(PUBLISHER_HEADER_LEN))?;
+        let publisher_id = header[0..6].try_into().expect("six bytes");
+        let count = u16::from_le_bytes([header[6], header[7]]);
+        let cycle = u16::from_le_bytes([header[8], header[9]]);
+        let mut variables = Vec::new();
+        let mut offset = PUBLISHER_HEADER_LEN;
+        for _ in 0..count {
+            let header = data.get(offset..offset + NETWORK_VARIABLE_HEADER_LEN).ok_or(truncated(offset + NETWORK_VARIABLE_HEADER_LEN))?;
+            let word = |at: usize| u16::from_le_bytes([header[at], header[at + 1]]);
+            let length = word(4) as usize;
+            let start = offset + NETWORK_VARIABLE_HEADER_LEN;
+            let data = data.get(start..start + length).ok_or(truncated(start + length))?;
+            variables.push(NetworkVariable { index: word(0), hash: word(2), quality: word(6), data });
+            offset = start + length;
+        }
+        Ok(NetworkVariables { publisher_id, cycle, variables })
+    }
+}
@@ -0,0 +1,135 @@
+use std::fs::{File, OpenOptions};
+use std::io::{BufWriter, Seek, SeekFrom, Write};
+use std::path::Path;
+
+const LINKTYPE_ETHERNET: u16 = 1;
+/// `if_tsresol` of the written interfaces: nanoseconds.
+const TS_RESOLUTION_NS: u8 = 9;
+const IF_NAME: u16 = 2;
+const IF_TSRESOL: u16 = 9;
+const IF_FCSLEN: u16 = 13;
+const EPB_FLAGS: u16 = 2;
+
+/// An Ethernet frame to write, with its capture time.
+pub struct Frame {
+    pub ts_ns: u64,
+    pub data: Vec<u8>,
+}
+
+fn block(output: &mut impl Write, block_type: u32, body: &[u8]) -> std::io::Result<()> {
+    let padding = (4 - body.len() % 4) % 4;
+    let length = (12 + body.len() + padding) as u32;
+    output.write_all(&block_type.to_le_bytes())?;
+    output.write_all(&length.to_le_bytes())?;
+    output.write_all(body)?;
+    output.write_all(&[0; 3][..padding])?;
+    output.write_all(&length.to_le_bytes())
+}
+
+fn option(body: &mut Vec<u8>, code: u16, value: &[u8]) {
+    body.extend(code.to_le_bytes());
+    body.extend((value.len() as u16).to_le_bytes());
+    body.extend(value);
+    body.resize(body.len().next_multiple_of(4), 0);
+}
+
+/// Writes one pcapng section, a packet at a time, declaring its interfaces as they come.
+pub struct Writer {
+    output: BufWriter<File>,
+    interfaces: u32,
+}
+
+impl Writer {
+    pub fn create(path: &Path) -> std::io::Result<Self> {
+        let mut output = BufWriter::new(File::create(path)?);
+        let mut shb = Vec::new();
+        shb.extend(0x1A2B_3C4Du32.to_le_bytes());
+        shb.extend(1u16.to_le_bytes());
+        shb.extend(0u16.to_le_bytes());
+        shb.extend((-1i64).to_le_bytes());
+        block(&mut output, 0x0A0D_0D0A, &shb)?;
+        Ok(Writer { output, interfaces: 0 })
+    }
+
+    /// Reopens a section of `len` bytes declaring `interfaces` interfaces to write on at its
+    /// end, cutting whatever follows.
+    pub fn append(path: &Path, len: u64, interfaces: u32) -> std::io::Result<Self> {
+        let mut file = OpenOptions::new().write(true).open(path)?;
+        let found = file.metadata()?.len();
+        if found < len {
+            return Err(std::io::Error::other(format!("{} bytes, fewer than the {} written before", found, len)));
+        }
+        file.set_len(len)?;
+        file.seek(SeekFrom::End(0))?;
+        Ok(Writer { output: BufWriter::new(file), interfaces })
+    }
+
+    /// Writes out the blocks buffered so far, returning the length of the section.
+    pub fn flush(&mut self) -> std::io::Result<u64> {
+        self.output.flush()?;
+        self.output.get_mut().stream_position()
+    }
+
+    /// Declares the next interface, returning its number: its link type, its name unless
+    /// empty, and the length of the FCS its packets keep, if known.
+    pub fn interface(&mut self, linktype: u16, name: &str, fcs_len: Option<u8>) -> std::io::Result<u32> {
+        let mut idb = Vec::new();
+        idb.extend(linktype.to_le_bytes());
+        idb.extend(0u16.to_le_bytes());
+        idb.extend(0u32.to_le_bytes());
+        if !name.is_empty() {
+            option(&mut idb, IF_NAME, name.as_bytes());
+        }
+        option(&mut idb, IF_TSRESOL, &[TS_RESOLUTION_NS]);
+        if let Some(fcs_len) = fcs_len {
+            option(&mut idb, IF_FCSLEN, &[fcs_len]);
+        }
+        // the end of the options.
+        idb.extend([0; 4]);
+        block(&mut self.output, 1, &idb)?;
+        self.interfaces += 1;
+        Ok(self.interfaces - 1)
+    }
+
+    /// Declares an Ethernet interface without FCS, as the frames of [`Frame`] are.
+    pub fn ethernet_interface(&mut self, name: &str) -> std::io::Result<u32> {
+        self.interface(LINKTYPE_ETHERNET, name, None)
+    }
+
+    /// Writes a packet of `interface`, of `orig_len` bytes on the wire, with its `epb_flags`
+    /// unless 0.
+    pub fn packet(&mut self, interface: u32, ts_ns: u64, data: &[u8], orig_len: u32, flags: u32) -> std::io::Result<()> {
+        let mut epb = Vec::with_capacity(20 + data.len());
+        epb.extend(interface.to_le_bytes());
+        epb.extend(((ts_ns >> 32) as u32).to_le_bytes());
+        epb.extend((ts_ns as u32).to_le_bytes());
+        epb.extend((data.len() as u32).to_le_bytes());
+        epb.extend(orig_len.to_le_bytes());
+        epb.extend(data);
+        epb.resize(epb.len().next_multiple_of(4), 0);
+        if flags != 0 {
+            option(&mut epb, EPB_FLAGS, &flags.to_le_bytes());
+            // the end of the options.
+            epb.extend([0; 4]);
+        }
+        block(&mut self.output, 6, &epb)
+    }
+
+    pub fn frame(&mut self, interface: u32, frame: &Frame) -> std::io::Result<()> {
+        self.packet(interface, frame.ts_ns, &frame.data, frame.data.len() as u32, 0)
+    }
+
+    pub fn finish(mut self) -> std::io::Result<()> {
+        self.output.flush()
+    }
+}
+
+/// Writes one pcapng section with a single Ethernet interface.
+pub fn write(path: &Path, frames: &[Frame]) -> std::io::Result<()> {
+    let mut writer = Writer::create(path)?;
+    let interface = writer.ethernet_interface("")?;
+    for frame in frames {
+        writer.frame(interface, frame)?;
+    }
+    writer.finish()
+}
@@ -0,0 +1,205 @@
+use crate::capture::{self, CaptureOptions, Item};
+use crate::coe::{self, Sdo};
+use crate::labels::Labels;
+use crate::mailbox::{self, Direction, Mailbox};
+use crate::sink::outln;
+use crate::slave_config::{SlaveConfigs, SlaveRef};
+use crate::value::{Style, Value};
+use crate::EtherNetFrame;
+use std::collections::{BTreeMap, HashMap};
+use std::fs::File;
+use std::ops::ControlFlow;
+use std::process::exit;
+
+/// Objects assigning the RxPDOs (outputs) and TxPDOs (inputs) to SyncManagers 2 and 3.
+const RXPDO_ASSIGN: u16 = 0x1C12;
+const TXPDO_ASSIGN: u16 = 0x1C13;
+
+/// Mapping objects of RxPDOs and TxPDOs.
+fn is_mapping_object(index: u16) -> bool {
+    (0x1600..=0x17FF).contains(&index) || (0x1A00..=0x1BFF).contains(&index)
+}
+
+/// Names and signedness of the CiA 402 drive objects commonly mapped to process data.
+const KNOWN_OBJECTS: &[(u16, &str, bool)] = &[
+    (0x603F, "error_code", false),
+    (0x6040, "controlword", false),
+    (0x6041, "statusword", false),
+    (0x6060, "modes_of_operation", true),
+    (0x6061, "modes_of_operation_display", true),
+    (0x6064, "position_actual_value", true),
+    (0x606C, "velocity_actual_value", true),
+    (0x6071, "target_torque", true),
+    (0x6077, "torque_actual_value", true),
+    (0x607A, "target_position", true),
+    (0x60B8, "touch_probe_function", false),
+    (0x60B9, "touch_probe_status", false),
+    (0x60FF, "target_velocity", true),
+];
+
+/// One object entry mapped into the process data of a slave.
+pub struct PdoEntry {
+    pub pdo: u16,
+    pub index: u16,
+    pub subindex: u8,
+    /// Position in the process data of the SyncManager, in bits.
+    pub bit_offset: u32,
+    pub bit_length: u8,
+}
+
+impl PdoEntry {
+    pub fn name(&self) -> String {
+        match KNOWN_OBJECTS.iter().find(|(index, ..)| *index == self.index) {
+            Some((_, name, _)) if self.subindex == 0 => name.to_string(),
+            _ => format!("0x{:04x}:{:02x}", self.index, self.subindex),
+        }
+    }
+
+    /// Whether the value is a signed integer. Unknown objects are decoded as unsigned.
+    pub fn signed(&self) -> bool {
+        KNOWN_OBJECTS.iter().any(|(index, _, signed)| *index == self.index && *signed)
+    }
+
+    /// Padding entries (index 0) only reserve bits.
+    pub fn is_padding(&self) -> bool {
+        self.index == 0
+    }
+}
+
+/// A download waiting for the slave to confirm it.
+struct PendingDownload {
+    index: u16,
+    subindex: u8,
+    complete_access: bool,
+    data: Vec<u8>,
+}
+
+/// PDO mapping and assignment objects of every slave, rebuilt from the SDO downloads the
+/// slaves confirmed.
+#[derive(Default)]
+pub struct PdoMappings {
+    /// Entries of the mapping and assignment objects, by slave and object index.
+    objects: BTreeMap<u16, BTreeMap<u16, BTreeMap<u8, u32>>>,
+    pending: HashMap<u16, PendingDownload>,
+}
+
+impl PdoMappings {
+    pub fn handle(&mut self, mailbox: &Mailbox) {
+        match coe::parse_sdo(mailbox) {
+            Some(Sdo::Download { index, subindex, complete_access, data })
+                if mailbox.direction == Direction::ToSlave
+                    && (is_mapping_object(index) || index == RXPDO_ASSIGN || index == TXPDO_ASSIGN) =>
+            {
+                let download = PendingDownload { index, subindex, complete_access, data: data.to_vec() };
+                self.pending.insert(mailbox.slave, download);
+            }
+            Some(Sdo::DownloadResponse { index, subindex })
+                if mailbox.direction == Direction::FromSlave && self.pending(mailbox.slave, index, subindex) =>
+            {
+                let download = self.pending.remove(&mailbox.slave).unwrap();
+                self.apply(mailbox.slave, &download);
+            }
+            Some(Sdo::Abort { index, subindex }) if self.pending(mailbox.slave, index, subindex) => {
+                self.pending.remove(&mailbox.slave);
+            }
+            _ => {}
+        }
+    }
+
+    /// Whether the download of this object entry waits for the slave to confirm it.
+    fn pending(&self, slave: u16, index: u16, subindex: u8) -> bool {
+        self.pending.get(&slave).is_some_and(|pending| pending.index == index && pending.subindex == subindex)
+    }
+
+    fn apply(&mut self, slave: u16, download: &PendingDownload) {
+        let object = self.objects.entry(slave).or_default().entry(download.index).or_default();
+        let data = &download.data;
+        if !download.complete_access {
+            let mut value = [0u8; 4];
+            let len = data.len().min(4);
+            value[..len].copy_from_slice(&data[..len]);
+            object.insert(download.subindex, u32::from_le_bytes(value));
+            return;
+        }
+        // complete access: the entry count, a padding byte, then every entry.
+        let Some(&count) = data.first() else {
+            return;
+        };
+        let entry_len = if is_mapping_object(download.index) { 4 } else { 2 };
+        object.clear();
+        object.insert(0, count as u32);
+        for (i, entry) in data.get(2..).unwrap_or_default().chunks_exact(entry_len).take(count as usize).enumerate() {
+            let mut value = [0u8; 4];
+            value[..entry_len].copy_from_slice(entry);
+            object.insert(i as u8 + 1, u32::from_le_bytes(value));
+        }
+    }
+
+    /// Slaves with a reconstructed mapping.
+    pub fn slaves(&self) -> impl Iterator<Item = u16> + '_ {
+        self.objects.keys().copied()
+    }
+
+    /// The process data entries of a slave, laid out in the order of the assigned PDOs, for
+    /// its outputs (RxPDOs) or inputs (TxPDOs). `None` if the assignment was not seen.
+    pub fn entries(&self, slave: u16, outputs: bool) -> Option<Vec<PdoEntry>> {
+        let objects = self.objects.get(&slave)?;
+        let assign = objects.get(&if outputs { RXPDO_ASSIGN } else { TXPDO_ASSIGN })?;
+        let mut entries = Vec::new();
+        let mut bit_offset = 0;
+        for i in 1..=*assign.get(&0)? as u8 {
+            let pdo = *assign.get(&i)? as u16;
+            let mapping = objects.get(&pdo)?;
+            for j in 1..=*mapping.get(&0)? as u8 {
+                let entry = *mapping.get(&j)?;
+                let bit_length = (entry & 0xFF) as u8;
+                entries.push(PdoEntry {
+                    pdo,
+                    index: (entry >> 16) as u16,
+                    subindex: (entry >> 8) as u8,
+                    bit_offset,
+                    bit_length,
+                });
+                bit_offset += bit_length as u32;
+            }
+        }
+        Some(entries)
+    }
+}
+
+/// Prints the PDO mapping of every slave whose mapping was downloaded in the capture.
+pub fn run(file: File, options: &CaptureOptions, labels: &Labels) {
+    let mut configs = SlaveConfigs::default();
+    let mut mappings = PdoMappings::default();
+
+    let summary = capture::read(file, options, |item| {
+        if let Item::Packet(packet) = item {
+            if let Some(frame) = packet.data.and_then(EtherNetFrame::parse_ethercat) {
+                let returned = frame.is_returned();
+                for datagram in &frame.ecat_frame.datagrams {
+                    configs.apply(datagram, returned);
+                    if let Some(mailbox) = mailbox::extract(datagram, returned, &configs) {
+                        mappings.handle(&mailbox);
+                    }
+                }
+            }
+        }
+        ControlFlow::Continue(())
+    });
+    let summary = summary.unwrap_or_else(|e| {
+        eprintln!("{}", e);
+        exit(1);
+    });
+
+    outln!("slave,direction,pdo,entry,name,bit_offset,bit_length{}", labels.header());
+    for slave in mappings.slaves() {
+        for (outputs, direction) in [(true, "outputs"), (false, "inputs")] {
+            for entry in mappings.entries(slave, outputs).unwrap_or_default() {
+                outln!("{},{},0x{:04x},0x{:04x}:{:02x},{},{},{}{}",
+                    Value::Address(slave).render(Style::Csv), direction, entry.pdo, entry.index, entry.subindex, entry.name(), entry.bit_offset, entry.bit_length,
+                    labels.cell(Some(SlaveRef::Station(slave))));
+            }
+        }
+    }
+    summary.report();
+}
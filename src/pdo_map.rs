@@ -0,0 +1,271 @@
+use crate::annotations::quote;
+use crate::capture::{self, CaptureOptions, Item};
+use crate::signal::{logical_bits, SignalType};
+use crate::sink::outln;
+use crate::value::{Style, Value};
+use crate::{EtherCATCommand, EtherCATDatagram, EtherNetFrame};
+use serde::Deserialize;
+use std::collections::HashSet;
+use std::fs::{self, File};
+use std::ops::ControlFlow;
+use std::path::Path;
+use std::process::exit;
+
+const FILES_CSV_HEADER: &str = "signal,samples,path";
+
+/// The layout of the process image, loaded from a TOML file given with `--pdo-map`, decoding
+/// the process data of the logical datagrams into named signals:
+///
+/// ```toml
+/// [[signal]]
+/// name = "x_position"
+/// address = 0x0004      # logical address of the first byte
+/// type = "i32"
+/// scale = 0.001         # value = raw * scale + offset
+/// offset = -12.5
+///
+/// # the master writes outputs; inputs are read from the returned frames
+/// [[signal]]
+/// name = "x_enable"
+/// address = 0x0000
+/// bit = 3
+/// type = "bool"
+/// direction = "output"
+///
+/// # an integer narrower than its type
+/// [[signal]]
+/// name = "x_analog"
+/// address = 0x0008
+/// bit = 4
+/// type = "u16"
+/// bits = 12
+/// ```
+///
+/// Types are those of `--extract-signal`. Every signal adds a column after the derived ones,
+/// filled in the rows of the LRD, LRW and LWR datagrams carrying all of its bits.
+pub struct PdoMap {
+    signals: Vec<PdoSignal>,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct PdoMapFile {
+    #[serde(default, rename = "signal")]
+    signals: Vec<SignalEntry>,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct SignalEntry {
+    name: String,
+    address: u32,
+    #[serde(default)]
+    bit: u8,
+    #[serde(rename = "type")]
+    signal_type: String,
+    bits: Option<u8>,
+    scale: Option<f64>,
+    offset: Option<f64>,
+    #[serde(default)]
+    direction: Direction,
+}
+
+#[derive(Deserialize, Default, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+enum Direction {
+    /// Read by the master, from the returned frames.
+    #[default]
+    Input,
+    /// Written by the master, from the outgoing frames.
+    Output,
+}
+
+struct PdoSignal {
+    name: String,
+    bit_address: u64,
+    signal_type: SignalType,
+    /// Scale and offset, if the raw value is scaled.
+    scaling: Option<(f64, f64)>,
+    direction: Direction,
+}
+
+impl PdoSignal {
+    fn new(entry: SignalEntry) -> Result<Self, String> {
+        if entry.bit > 7 {
+            return Err(format!("'{}' : the bit is 0 to 7", entry.name));
+        }
+        let signal_type = SignalType::from_name(&entry.signal_type)
+            .ok_or_else(|| format!("'{}' : unknown type '{}'", entry.name, entry.signal_type))?;
+        let signal_type = match (signal_type, entry.bits) {
+            (_, None) => signal_type,
+            (SignalType::Unsigned(width), Some(bits)) if (1..=width).contains(&bits) => SignalType::Unsigned(bits),
+            (SignalType::Signed(width), Some(bits)) if (2..=width).contains(&bits) => SignalType::Signed(bits),
+            _ => return Err(format!("'{}' : {} bits do not fit type {}", entry.name, entry.bits.unwrap_or(0), entry.signal_type)),
+        };
+        let scaling = (entry.scale.is_some() || entry.offset.is_some())
+            .then(|| (entry.scale.unwrap_or(1.0), entry.offset.unwrap_or(0.0)));
+        Ok(PdoSignal {
+            name: entry.name,
+            bit_address: entry.address as u64 * 8 + entry.bit as u64,
+            signal_type,
+            scaling,
+            direction: entry.direction,
+        })
+    }
+
+    /// The value of the signal in a datagram, if the datagram carries it in its direction.
+    fn cell(&self, datagram: &EtherCATDatagram, returned: bool) -> Option<String> {
+        use EtherCATCommand::*;
+
+        let carried = match datagram.header.cmd {
+            LRW => true,
+            LRD => returned,
+            LWR => !returned,
+            _ => false,
+        };
+        if !carried || returned != (self.direction == Direction::Input) {
+            return None;
+        }
+        let raw = logical_bits(datagram, self.bit_address, self.signal_type.bits())?;
+        Some(match self.scaling {
+            Some((scale, offset)) => (self.signal_type.number(raw) * scale + offset).to_string(),
+            None => self.signal_type.format(raw),
+        })
+    }
+}
+
+impl PdoMap {
+    pub fn load(path: &str) -> Result<Self, String> {
+        let error = |e: String| format!("Invalid PDO map {} : {}", path, e);
+        let text = fs::read_to_string(path).map_err(|e| format!("Cannot read {} : {}", path, e))?;
+        let file: PdoMapFile = toml::from_str(&text).map_err(|e| error(e.to_string()))?;
+        let mut names = HashSet::new();
+        let mut signals = Vec::new();
+        for entry in file.signals {
+            if !names.insert(entry.name.clone()) {
+                return Err(error(format!("'{}' is defined twice", entry.name)));
+            }
+            signals.push(PdoSignal::new(entry).map_err(error)?);
+        }
+        Ok(PdoMap { signals })
+    }
+
+    /// The names of the signal columns, in file order.
+    pub fn names(&self) -> impl Iterator<Item = &str> {
+        self.signals.iter().map(|signal| signal.name.as_str())
+    }
+
+    /// The cell of every signal for one datagram, in file order.
+    pub fn cells(&self, datagram: &EtherCATDatagram, returned: bool) -> Vec<Option<String>> {
+        self.signals.iter().map(|signal| signal.cell(datagram, returned)).collect()
+    }
+}
+
+/// The values of the signals in one cycle: the outgoing frame carrying logical commands and
+/// the frames up to the next one, which bring its datagrams back.
+struct Cycle {
+    number: u64,
+    frame_number: u64,
+    ts_ns: u64,
+    values: Vec<Option<String>>,
+}
+
+/// Samples the selected signals, by index in the map, once per cycle of the capture.
+fn sample(file: File, options: &CaptureOptions, map: &PdoMap, selected: &[usize], mut take: impl FnMut(Cycle)) {
+    use EtherCATCommand::*;
+
+    let mut cycle: Option<Cycle> = None;
+    let summary = capture::read(file, options, |item| {
+        let Item::Packet(packet) = item else {
+            return ControlFlow::Continue(());
+        };
+        let Some(frame) = packet.data.and_then(EtherNetFrame::parse_ethercat) else {
+            return ControlFlow::Continue(());
+        };
+        let returned = frame.is_returned();
+        let datagrams = &frame.ecat_frame.datagrams;
+        if !returned && datagrams.iter().any(|datagram| matches!(datagram.header.cmd, LRD | LWR | LRW)) {
+            let number = cycle.as_ref().map_or(1, |previous| previous.number + 1);
+            let next = Cycle { number, frame_number: packet.info.frame_number, ts_ns: packet.info.ts_ns, values: vec![None; selected.len()] };
+            if let Some(previous) = cycle.replace(next) {
+                take(previous);
+            }
+        }
+        if let Some(cycle) = &mut cycle {
+            for datagram in datagrams {
+                for (value, &i) in cycle.values.iter_mut().zip(selected) {
+                    if let Some(cell) = map.signals[i].cell(datagram, returned) {
+                        *value = Some(cell);
+                    }
+                }
+            }
+        }
+        ControlFlow::Continue(())
+    });
+    let summary = summary.unwrap_or_else(|e| {
+        eprintln!("{}", e);
+        exit(1);
+    });
+    if let Some(cycle) = cycle {
+        take(cycle);
+    }
+    summary.report();
+}
+
+/// The file a signal is written to: its name, with the characters a file name should not
+/// carry replaced.
+fn file_name(name: &str) -> String {
+    let name: String = name.chars().map(|c| if c.is_ascii_alphanumeric() || matches!(c, '-' | '_') { c } else { '_' }).collect();
+    format!("{}.csv", name)
+}
+
+/// Writes the time series of the signals, sampled once per cycle: a cycle starts with an
+/// outgoing frame carrying logical commands and takes the outputs it writes and the inputs
+/// its datagrams bring back. Without `output_dir`, prints one wide CSV with a column per
+/// signal; with it, writes one CSV per signal with the cycles carrying it and prints the
+/// files written. `names` selects signals of the map, all of them when empty.
+pub fn extract(file: File, options: &CaptureOptions, map: &PdoMap, names: &[String], output_dir: Option<&Path>) {
+    let selected: Vec<usize> = if names.is_empty() {
+        (0..map.signals.len()).collect()
+    } else {
+        names.iter().map(|name| map.signals.iter().position(|signal| signal.name == *name).unwrap_or_else(|| {
+            eprintln!("Signal '{}' is not in the PDO map", name);
+            exit(1);
+        })).collect()
+    };
+    let time = |cycle: &Cycle| format!("{},{},{},{}", cycle.number, cycle.frame_number, Value::Time(cycle.ts_ns).render(Style::Csv), cycle.ts_ns);
+
+    let Some(output_dir) = output_dir else {
+        let names: Vec<&str> = selected.iter().map(|&i| map.signals[i].name.as_str()).collect();
+        outln!("cycle,frame,time,time_ns,{}", names.join(","));
+        sample(file, options, map, &selected, |cycle| {
+            let values: Vec<&str> = cycle.values.iter().map(|value| value.as_deref().unwrap_or_default()).collect();
+            outln!("{},{}", time(&cycle), values.join(","));
+        });
+        return;
+    };
+    let mut series = vec![String::new(); selected.len()];
+    let mut samples = vec![0u64; selected.len()];
+    sample(file, options, map, &selected, |cycle| {
+        for (i, value) in cycle.values.iter().enumerate() {
+            if let Some(value) = value {
+                series[i] += &format!("{},{}\n", time(&cycle), value);
+                samples[i] += 1;
+            }
+        }
+    });
+    fs::create_dir_all(output_dir).unwrap_or_else(|e| {
+        eprintln!("Cannot create {} : {}", output_dir.display(), e);
+        exit(1);
+    });
+    outln!("{}", FILES_CSV_HEADER);
+    for ((&i, series), samples) in selected.iter().zip(series).zip(samples) {
+        let name = &map.signals[i].name;
+        let path = output_dir.join(file_name(name));
+        fs::write(&path, format!("cycle,frame,time,time_ns,value\n{}", series)).unwrap_or_else(|e| {
+            eprintln!("Cannot write {} : {}", path.display(), e);
+            exit(1);
+        });
+        outln!("{},{},{}", quote(name), samples, quote(&path.to_string_lossy()));
+    }
+}
@@ -0,0 +1,235 @@
+use crate::capture::PacketInfo;
+use crate::columns::Row;
+use crate::sink::Sink;
+use std::collections::BTreeMap;
+use std::num::NonZeroUsize;
+use std::panic::{self, AssertUnwindSafe};
+use std::process::exit;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::mpsc::{self, Receiver, Sender, SyncSender};
+use std::sync::{Arc, Mutex};
+use std::thread::{self, JoinHandle};
+
+/// Jobs queued per worker before the reader blocks, bounding memory on large captures.
+const QUEUE_DEPTH_PER_WORKER: usize = 64;
+
+/// Exit code of a process aborted by a panic, used when a worker panics.
+const PANIC_EXIT_CODE: i32 = 101;
+
+/// Parses the number of worker threads of `--jobs`: a positive number, or `auto` for one per
+/// CPU the process may use.
+pub fn parse_jobs(s: &str) -> Result<NonZeroUsize, String> {
+    if s == "auto" {
+        return Ok(thread::available_parallelism().unwrap_or(NonZeroUsize::MIN));
+    }
+    s.parse().map_err(|_| format!("invalid number of jobs '{}', expected a positive number or auto", s))
+}
+
+/// Turns the bytes of one packet into its output rows, or into an error aborting the export.
+/// The rows are fields computed in capture order beforehand, one per datagram, if any.
+pub type ExportFn = dyn Fn(&PacketInfo, &[u8], &[Row<'static>]) -> Result<String, String> + Send + Sync;
+
+enum Outcome {
+    Rows(String),
+    Error(String),
+    Panicked,
+    /// Everything before has been written; acknowledged once it is flushed.
+    Flush(Sender<()>),
+}
+
+struct Job {
+    seq: u64,
+    info: PacketInfo,
+    data: Vec<u8>,
+    cells: Vec<Row<'static>>,
+}
+
+/// Runs the per-packet export either inline or on a pool of worker threads.
+///
+/// In threaded mode the output is written in capture order unless `ordered` is false, in
+/// which case rows are written as soon as a worker finishes them.
+pub struct Pipeline {
+    mode: Mode,
+    /// Bytes written to stdout so far.
+    written: Arc<AtomicU64>,
+}
+
+enum Mode {
+    Inline {
+        export: Arc<ExportFn>,
+        sink: Sink,
+    },
+    Threaded {
+        jobs: Option<SyncSender<Job>>,
+        results: Option<Sender<(u64, Outcome)>>,
+        workers: Vec<JoinHandle<()>>,
+        writer: JoinHandle<Sink>,
+        next_seq: u64,
+    },
+}
+
+impl Pipeline {
+    pub fn new(threads: usize, ordered: bool, export: Arc<ExportFn>, sink: Sink) -> Self {
+        let written = sink.written();
+        if threads <= 1 {
+            return Pipeline { mode: Mode::Inline { export, sink }, written };
+        }
+
+        let (job_tx, job_rx) = mpsc::sync_channel::<Job>(threads * QUEUE_DEPTH_PER_WORKER);
+        let (result_tx, result_rx) = mpsc::channel();
+        let job_rx = Arc::new(Mutex::new(job_rx));
+        let workers = (0..threads)
+            .map(|_| {
+                let job_rx = Arc::clone(&job_rx);
+                let result_tx = result_tx.clone();
+                let export = Arc::clone(&export);
+                thread::spawn(move || worker(&job_rx, &result_tx, &*export))
+            })
+            .collect();
+        let writer = thread::spawn(move || writer(result_rx, ordered, sink));
+
+        Pipeline {
+            mode: Mode::Threaded {
+                jobs: Some(job_tx),
+                results: Some(result_tx),
+                workers,
+                writer,
+                next_seq: 0,
+            },
+            written,
+        }
+    }
+
+    /// Queues a captured packet for export.
+    pub fn packet(&mut self, info: &PacketInfo, data: &[u8], cells: Vec<Row<'static>>) {
+        match &mut self.mode {
+            Mode::Inline { export, sink } => match export(info, data, &cells) {
+                Ok(rows) => sink.write(rows),
+                Err(e) => {
+                    sink.flush();
+                    eprintln!("{}", e);
+                    exit(1);
+                }
+            },
+            Mode::Threaded { jobs, next_seq, .. } => {
+                let job = Job {
+                    seq: *next_seq,
+                    info: *info,
+                    data: data.to_vec(),
+                    cells,
+                };
+                *next_seq += 1;
+                jobs.as_ref().unwrap().send(job).expect("worker threads stopped");
+            }
+        }
+    }
+
+    /// Writes text that does not need any processing, keeping its place in the output order.
+    pub fn text(&mut self, text: String) {
+        match &mut self.mode {
+            Mode::Inline { sink, .. } => sink.write(text),
+            Mode::Threaded { results, next_seq, .. } => {
+                results.as_ref().unwrap().send((*next_seq, Outcome::Rows(text))).expect("writer thread stopped");
+                *next_seq += 1;
+            }
+        }
+    }
+
+    /// Waits until everything queued so far is on stdout and returns the number of bytes
+    /// written since the pipeline started.
+    pub fn flush(&mut self) -> u64 {
+        match &mut self.mode {
+            Mode::Inline { sink, .. } => sink.flush(),
+            Mode::Threaded { results, next_seq, .. } => {
+                let (ack_tx, ack_rx) = mpsc::channel();
+                results.as_ref().unwrap().send((*next_seq, Outcome::Flush(ack_tx))).expect("writer thread stopped");
+                *next_seq += 1;
+                ack_rx.recv().expect("writer thread stopped");
+            }
+        }
+        self.written.load(Ordering::Relaxed)
+    }
+
+    /// Waits until everything queued so far has been written.
+    pub fn finish(self) {
+        match self.mode {
+            Mode::Inline { sink, .. } => sink.finish(),
+            Mode::Threaded { mut jobs, mut results, workers, writer, .. } => {
+                jobs.take();
+                for worker in workers {
+                    worker.join().expect("worker thread panicked");
+                }
+                results.take();
+                writer.join().expect("writer thread panicked").finish();
+            }
+        }
+    }
+}
+
+fn worker(jobs: &Mutex<Receiver<Job>>, results: &Sender<(u64, Outcome)>, export: &ExportFn) {
+    loop {
+        // hold the lock only while waiting for the next job, not while processing it.
+        let job = match jobs.lock().unwrap().recv() {
+            Ok(job) => job,
+            Err(_) => return,
+        };
+        let outcome = match panic::catch_unwind(AssertUnwindSafe(|| export(&job.info, &job.data, &job.cells))) {
+            Ok(Ok(rows)) => Outcome::Rows(rows),
+            Ok(Err(e)) => Outcome::Error(e),
+            Err(_) => Outcome::Panicked,
+        };
+        if results.send((job.seq, outcome)).is_err() {
+            return;
+        }
+    }
+}
+
+fn writer(results: Receiver<(u64, Outcome)>, ordered: bool, sink: Sink) -> Sink {
+    let mut pending = BTreeMap::new();
+    let mut next_seq = 0;
+    // unordered: a flush waits until everything queued before it has been written.
+    let mut held_flush = None;
+    for (seq, outcome) in results {
+        if !ordered {
+            match outcome {
+                Outcome::Flush(ack) => held_flush = Some((seq, ack)),
+                outcome => {
+                    write_outcome(&sink, outcome);
+                    next_seq += 1;
+                }
+            }
+            if held_flush.as_ref().is_some_and(|(flush_seq, _)| *flush_seq == next_seq) {
+                write_outcome(&sink, Outcome::Flush(held_flush.take().unwrap().1));
+                next_seq += 1;
+            }
+            continue;
+        }
+        pending.insert(seq, outcome);
+        while let Some(outcome) = pending.remove(&next_seq) {
+            write_outcome(&sink, outcome);
+            next_seq += 1;
+        }
+    }
+    sink.flush();
+    sink
+}
+
+fn write_outcome(sink: &Sink, outcome: Outcome) {
+    match outcome {
+        Outcome::Rows(rows) => sink.write(rows),
+        Outcome::Error(e) => {
+            sink.flush();
+            eprintln!("{}", e);
+            exit(1);
+        }
+        Outcome::Panicked => {
+            sink.flush();
+            exit(PANIC_EXIT_CODE);
+        }
+        Outcome::Flush(ack) => {
+            sink.flush();
+            // the pipeline waits for the acknowledgement, so it cannot be gone.
+            ack.send(()).ok();
+        }
+    }
+}
@@ -0,0 +1,143 @@
+use crate::capture::{self, CaptureOptions, Item};
+use crate::labels::Labels;
+use crate::registers::register_byte;
+use crate::sink::outln;
+use crate::slave_config::SlaveRef;
+use crate::value::{Style, Value};
+use crate::{EtherCATCommand, EtherCATDatagram, EtherNetFrame};
+use std::collections::BTreeMap;
+use std::fs::File;
+use std::ops::ControlFlow;
+use std::process::exit;
+
+/// Invalid frame and RX error counters of port 0, a byte each, followed by those of ports 1 to 3.
+const RX_ERROR_COUNTER_REGISTER: u16 = 0x0300;
+/// Forwarded RX error counter of port 0, followed by those of ports 1 to 3.
+const FORWARDED_ERROR_COUNTER_REGISTER: u16 = 0x0308;
+/// Lost link counter of port 0, followed by those of ports 1 to 3.
+const LOST_LINK_COUNTER_REGISTER: u16 = 0x0310;
+/// End of the error counters; a write to any of them clears them.
+const ERROR_COUNTERS_END: u32 = 0x0314;
+const PORTS: u16 = 4;
+
+const CSV_HEADER: &str = "slave,port,reads,invalid_frames,rx_errors,forwarded_errors,lost_links,segment_errors,first_rise,last_rise";
+const CHANGES_CSV_HEADER: &str = "time,frame,slave,port,counter,value,rise";
+
+/// Names of the counters of a port, in the order they are kept in.
+const COUNTERS: [&str; 4] = ["invalid_frames", "rx_errors", "forwarded_errors", "lost_links"];
+
+/// The error counters of one port, as last read, and how much each rose over the capture.
+#[derive(Default)]
+struct PortCounters {
+    reads: u64,
+    last: [Option<u8>; 4],
+    rises: [u64; 4],
+    first_rise_ns: Option<u64>,
+    last_rise_ns: Option<u64>,
+}
+
+impl PortCounters {
+    /// Takes the counters a read returned, returning those that rose with their value and rise.
+    /// The first read of a counter is where it starts from; a counter lower than at the read
+    /// before was cleared by the master in between, and rose by its value since.
+    fn read(&mut self, ts_ns: u64, counters: [Option<u8>; 4]) -> Vec<(usize, u8, u64)> {
+        self.reads += 1;
+        let mut risen = Vec::new();
+        for (counter, value) in counters.into_iter().enumerate() {
+            let Some(value) = value else {
+                continue;
+            };
+            let rise = match self.last[counter] {
+                Some(last) if value >= last => (value - last) as u64,
+                Some(_) => value as u64,
+                None => 0,
+            };
+            self.last[counter] = Some(value);
+            if rise > 0 {
+                self.rises[counter] += rise;
+                self.first_rise_ns.get_or_insert(ts_ns);
+                self.last_rise_ns = Some(ts_ns);
+                risen.push((counter, value, rise));
+            }
+        }
+        risen
+    }
+
+    fn to_row(&self, slave: u16, port: u16) -> String {
+        let [invalid_frames, rx_errors, forwarded_errors, lost_links] = self.rises;
+        let time = |ts_ns: Option<u64>| ts_ns.map(|ts_ns| Value::Time(ts_ns).render(Style::Csv)).unwrap_or_default();
+        format!("{},{},{},{},{},{},{},{},{},{}",
+            Value::Address(slave).render(Style::Csv), port, self.reads, invalid_frames, rx_errors, forwarded_errors, lost_links,
+            invalid_frames.saturating_sub(forwarded_errors), time(self.first_rise_ns), time(self.last_rise_ns))
+    }
+}
+
+/// The counters of each port a returned read carries: invalid frames, RX errors, forwarded
+/// errors and lost links, `None` for those it does not cover.
+fn port_counters(datagram: &EtherCATDatagram) -> Vec<(u16, [Option<u8>; 4])> {
+    (0..PORTS).filter_map(|port| {
+        let counters = [
+            register_byte(datagram, RX_ERROR_COUNTER_REGISTER + 2 * port),
+            register_byte(datagram, RX_ERROR_COUNTER_REGISTER + 2 * port + 1),
+            register_byte(datagram, FORWARDED_ERROR_COUNTER_REGISTER + port),
+            register_byte(datagram, LOST_LINK_COUNTER_REGISTER + port),
+        ];
+        counters.iter().any(Option::is_some).then_some((port, counters))
+    }).collect()
+}
+
+/// Prints one row per port of every slave whose error counters are read through its station
+/// address: how much its invalid frame, RX error, forwarded RX error and lost link counters
+/// rose over the capture, and when they first and last rose. The invalid frames a slave in
+/// front did not already mark as forwarded errors, in segment_errors, were corrupted on the
+/// cable into that port, which localizes the faulty segment. With `changes`, lists every read
+/// a counter rose in instead.
+pub fn run(file: File, options: &CaptureOptions, changes: bool, labels: &Labels) {
+    use EtherCATCommand::*;
+
+    let mut ports: BTreeMap<(u16, u16), PortCounters> = BTreeMap::new();
+    if changes {
+        outln!("{}{}", CHANGES_CSV_HEADER, labels.header());
+    }
+    let summary = capture::read(file, options, |item| {
+        let Item::Packet(packet) = item else {
+            return ControlFlow::Continue(());
+        };
+        let Some(frame) = packet.data.and_then(EtherNetFrame::parse_ethercat) else {
+            return ControlFlow::Continue(());
+        };
+        if !frame.is_returned() {
+            return ControlFlow::Continue(());
+        }
+        for datagram in &frame.ecat_frame.datagrams {
+            let header = &datagram.header;
+            let start = header.offset_addr as u32;
+            let counters = start < ERROR_COUNTERS_END && start + header.length as u32 > RX_ERROR_COUNTER_REGISTER as u32;
+            if !matches!(header.cmd, FPRD | FPRW) || !counters || datagram.wkc == 0 {
+                continue;
+            }
+            let slave = header.slave_addr;
+            for (port, counters) in port_counters(datagram) {
+                let risen = ports.entry((slave, port)).or_default().read(packet.info.ts_ns, counters);
+                for (counter, value, rise) in risen.into_iter().filter(|_| changes) {
+                    outln!("{},{},{},{},{},{},{}{}",
+                        Value::Time(packet.info.ts_ns).render(Style::Csv), packet.info.frame_number, Value::Address(slave).render(Style::Csv),
+                        port, COUNTERS[counter], value, rise, labels.cell(Some(SlaveRef::Station(slave))));
+                }
+            }
+        }
+        ControlFlow::Continue(())
+    });
+    let summary = summary.unwrap_or_else(|e| {
+        eprintln!("{}", e);
+        exit(1);
+    });
+
+    if !changes {
+        outln!("{}{}", CSV_HEADER, labels.header());
+        for (&(slave, port), counters) in &ports {
+            outln!("{}{}", counters.to_row(slave, port), labels.cell(Some(SlaveRef::Station(slave))));
+        }
+    }
+    summary.report();
+}
@@ -0,0 +1,325 @@
+//! Reading of the datagrams of a capture, one at a time.
+
+use crate::parser::ecat::EtherCATDatagramHeader;
+use crate::parser::ethernet::{is_ethercat, EtherNetFrame};
+use crate::parser::ParseError;
+use pcap_parser::nom::Offset;
+use pcap_parser::traits::PcapReaderIterator;
+use pcap_parser::*;
+use std::collections::VecDeque;
+use std::fmt;
+use std::io::Read;
+use std::ops::Range;
+use std::sync::Arc;
+use thiserror::Error;
+
+/// Size of the read buffer a capture is read with at first.
+pub const READER_BUFFER_SIZE: usize = 65536;
+/// Largest block the read buffer grows to; a longer block is taken for a corrupt one.
+pub const MAX_BLOCK_LEN: usize = 64 << 20;
+/// Timestamp resolution of pcapng interfaces without an `if_tsresol` option.
+pub const DEFAULT_TS_RESOLUTION: u64 = 1_000_000;
+/// Magic numbers starting a classic pcap file, with microsecond and nanosecond timestamps, in
+/// either byte order, read as big-endian.
+pub const PCAP_MAGICS: [u32; 4] = [0xA1B2_C3D4, 0xD4C3_B2A1, 0xA1B2_3C4D, 0x4D3C_B2A1];
+/// Length of the file header of a classic pcap file.
+pub const PCAP_HEADER_LEN: usize = 24;
+/// Type of the section header block of pcapng, the same in both byte orders.
+pub const SECTION_HEADER_TYPE: [u8; 4] = [0x0A, 0x0D, 0x0D, 0x0A];
+/// Byte-order magic of a big-endian section header block.
+pub const BIG_ENDIAN_MAGIC: [u8; 4] = [0x1A, 0x2B, 0x3C, 0x4D];
+/// Shortest pcapng block: type, length and trailing length.
+pub const MIN_BLOCK_LEN: usize = 12;
+
+/// Why a capture, or a frame of it, cannot be read.
+#[derive(Debug, Clone, PartialEq, Eq, Error)]
+pub enum ReadError {
+    /// The capture is not a pcap or pcapng file, or is corrupt; nothing follows.
+    #[error("error while reading the capture: {0}")]
+    Capture(String),
+    /// A frame with the EtherCAT EtherType does not parse; the next frames follow.
+    #[error("frame {frame_number}: {error}")]
+    Frame { frame_number: u64, error: ParseError },
+}
+
+/// A datagram of a capture, with what is known about the frame carrying it.
+pub struct DatagramRecord {
+    /// 1-based number of the packet in the capture.
+    pub frame_number: u64,
+    /// Capture time in nanoseconds since the epoch.
+    pub ts_ns: u64,
+    pub dst_mac: [u8; 6],
+    pub src_mac: [u8; 6],
+    /// Whether the frame has passed the slaves, by the locally administered bit of `src_mac`.
+    pub returned: bool,
+    /// Position of the datagram in its frame.
+    pub position: usize,
+    pub header: EtherCATDatagramHeader,
+    pub wkc: u16,
+    /// The Ethernet frame, shared by the records of its datagrams rather than copied for each.
+    frame: Arc<[u8]>,
+    /// Where the payload is in `frame`.
+    payload: Range<usize>,
+}
+
+impl DatagramRecord {
+    /// The payload, of the length of the header.
+    pub fn data(&self) -> &[u8] {
+        &self.frame[self.payload.clone()]
+    }
+}
+
+impl fmt::Debug for DatagramRecord {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("DatagramRecord")
+            .field("frame_number", &self.frame_number)
+            .field("ts_ns", &self.ts_ns)
+            .field("dst_mac", &self.dst_mac)
+            .field("src_mac", &self.src_mac)
+            .field("returned", &self.returned)
+            .field("position", &self.position)
+            .field("header", &self.header)
+            .field("data", &self.data())
+            .field("wkc", &self.wkc)
+            .finish()
+    }
+}
+
+/// What the timestamps of an interface count.
+struct Interface {
+    linktype: Linktype,
+    ts_resolution: u64,
+    ts_offset: i64,
+}
+
+/// What is known of a capture from the blocks read so far, which the synchronous reader and
+/// the asynchronous stream hand every block to.
+#[derive(Default)]
+pub(crate) struct Decoder {
+    /// Whether the capture is a classic pcap file, and the byte order of its current section.
+    pub(crate) legacy: bool,
+    pub(crate) big_endian: bool,
+    /// The interfaces of the current section.
+    interfaces: Vec<Interface>,
+    frame_number: u64,
+    /// The datagrams of the current packet not yet handed over.
+    pub(crate) pending: VecDeque<DatagramRecord>,
+}
+
+impl Decoder {
+    /// Takes the next block of the capture, queueing the datagrams of the packet it carries.
+    /// A `ReadError::Capture` ends the capture.
+    pub(crate) fn block(&mut self, block: PcapBlockOwned) -> Result<(), ReadError> {
+        let packet = match block {
+            PcapBlockOwned::LegacyHeader(header) => {
+                self.legacy = true;
+                self.big_endian = header.is_bigendian();
+                // the top bits of the link type field tell the FCS length.
+                let linktype = Linktype(header.network.0 & 0x0FFF_FFFF);
+                self.interfaces = vec![Interface { linktype, ts_resolution: pcap_ts_resolution(&header), ts_offset: 0 }];
+                None
+            }
+            PcapBlockOwned::Legacy(packet) => {
+                self.frame_number += 1;
+                let interface = &self.interfaces[0];
+                let ts_ns = pcap_timestamp_ns(packet.ts_sec, packet.ts_usec, interface.ts_resolution);
+                ethernet_frame(packet.data, interface.linktype, packet.caplen).map(|data| (ts_ns, data))
+            }
+            PcapBlockOwned::NG(Block::SectionHeader(shb)) => {
+                self.big_endian = shb.big_endian();
+                self.interfaces.clear();
+                None
+            }
+            PcapBlockOwned::NG(Block::InterfaceDescription(idb)) => {
+                self.interfaces.push(Interface {
+                    linktype: idb.linktype,
+                    ts_resolution: interface_ts_resolution(&idb),
+                    ts_offset: idb.ts_offset(),
+                });
+                None
+            }
+            PcapBlockOwned::NG(Block::EnhancedPacket(epb)) => {
+                let interface = self.interfaces.get(epb.if_id as usize)
+                    .ok_or_else(|| ReadError::Capture(format!("packet on undeclared interface {}", epb.if_id)))?;
+                self.frame_number += 1;
+                let ts_ns = timestamp_ns(epb.ts_high, epb.ts_low, interface.ts_resolution, interface.ts_offset);
+                ethernet_frame(epb.data, interface.linktype, epb.caplen).map(|data| (ts_ns, data))
+            }
+            _ => None,
+        };
+        match packet {
+            Some((ts_ns, data)) => decode(self.frame_number, ts_ns, data, &mut self.pending),
+            None => Ok(()),
+        }
+    }
+}
+
+/// Iterates over the datagrams of a pcap or pcapng capture, decoding one packet at a time,
+/// so that tools of their own aggregate captures of any size. Packets that do not carry
+/// EtherCAT and those of other link types than Ethernet are skipped; a frame check sequence
+/// the capture kept is left behind the datagrams, as padding.
+pub struct EcatPcapReader<'r> {
+    /// The capture until the first call to `next` tells pcap from pcapng.
+    input: Option<Box<dyn Read + 'r>>,
+    /// `None` once the capture has ended or cannot be read further.
+    reader: Option<Box<dyn PcapReaderIterator + 'r>>,
+    /// Size of the read buffer, which grows for blocks longer than it.
+    buffer_size: usize,
+    decoder: Decoder,
+}
+
+impl<'r> EcatPcapReader<'r> {
+    /// Reads the capture from `reader`, from the first call to `next` on.
+    pub fn new(reader: impl Read + 'r) -> Self {
+        EcatPcapReader {
+            input: Some(Box::new(reader)),
+            reader: None,
+            buffer_size: READER_BUFFER_SIZE,
+            decoder: Decoder::default(),
+        }
+    }
+}
+
+impl Iterator for EcatPcapReader<'_> {
+    type Item = Result<DatagramRecord, ReadError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if let Some(record) = self.decoder.pending.pop_front() {
+                return Some(Ok(record));
+            }
+            if let Some(input) = self.input.take() {
+                match create_reader(READER_BUFFER_SIZE, input) {
+                    Ok(reader) => self.reader = Some(reader),
+                    Err(PcapError::Eof) => return None,
+                    Err(e) => return Some(Err(ReadError::Capture(e.to_string()))),
+                }
+            }
+            let reader = self.reader.as_mut()?;
+            match reader.next() {
+                Ok((offset, block)) => {
+                    let decoded = self.decoder.block(block);
+                    reader.consume(offset);
+                    match decoded {
+                        Err(error @ ReadError::Frame { .. }) => return Some(Err(error)),
+                        Err(error) => {
+                            self.reader = None;
+                            return Some(Err(error));
+                        }
+                        Ok(()) => continue,
+                    }
+                }
+                Err(PcapError::Eof) => {
+                    self.reader = None;
+                    return None;
+                }
+                Err(PcapError::Incomplete(_)) | Err(PcapError::BufferTooSmall) => {}
+                Err(e) => {
+                    let error = ReadError::Capture(e.to_string());
+                    self.reader = None;
+                    return Some(Err(error));
+                }
+            }
+            let result = match grow_for_block(&mut **reader, &mut self.buffer_size, self.decoder.legacy, self.decoder.big_endian) {
+                Ok(()) => reader.refill().map_err(|e| e.to_string()),
+                Err(needed) => Err(too_long(needed)),
+            };
+            if let Err(e) = result {
+                self.reader = None;
+                return Some(Err(ReadError::Capture(e)));
+            }
+        }
+    }
+}
+
+/// The length of the block, or of the packet record of a classic pcap file, at the start of
+/// `data`, as its header tells.
+pub fn block_len(data: &[u8], legacy: bool, big_endian: bool) -> Option<usize> {
+    let field = if legacy { data.get(8..12)? } else { data.get(4..8)? };
+    let bytes = [field[0], field[1], field[2], field[3]];
+    let len = if big_endian { u32::from_be_bytes(bytes) } else { u32::from_le_bytes(bytes) } as usize;
+    // the header of a packet record is not counted in its captured length.
+    Some(if legacy { 16 + len } else { len })
+}
+
+/// Makes room in the buffer of `reader`, of `buffer_size` bytes, for the block at the start of
+/// its data before it is refilled: a block longer than the buffer needs a larger one, refilling
+/// it would read nothing. A block declared longer than [`MAX_BLOCK_LEN`] is taken for a corrupt
+/// one, its length returned as the error.
+pub fn grow_for_block(reader: &mut dyn PcapReaderIterator, buffer_size: &mut usize, legacy: bool, big_endian: bool) -> Result<(), usize> {
+    let needed = block_len(reader.data(), legacy, big_endian).unwrap_or(0);
+    if needed > MAX_BLOCK_LEN {
+        return Err(needed);
+    }
+    if needed >= *buffer_size {
+        *buffer_size = (needed + 1).next_power_of_two();
+        reader.grow(*buffer_size);
+    }
+    Ok(())
+}
+
+/// The error of a block declared longer than [`MAX_BLOCK_LEN`].
+pub(crate) fn too_long(len: usize) -> String {
+    format!("a block of {} bytes is longer than {} bytes", len, MAX_BLOCK_LEN)
+}
+
+/// Timestamp resolution of a pcapng interface, in units per second.
+pub fn interface_ts_resolution(idb: &InterfaceDescriptionBlock) -> u64 {
+    idb.ts_resolution().unwrap_or(DEFAULT_TS_RESOLUTION)
+}
+
+/// Timestamp resolution of the packets of a classic pcap file, in units per second.
+pub fn pcap_ts_resolution(header: &PcapHeader) -> u64 {
+    if header.is_nanosecond_precision() { 1_000_000_000 } else { 1_000_000 }
+}
+
+/// Converts a raw pcapng packet timestamp, in `ts_resolution` units per second from
+/// `ts_offset` seconds, to nanoseconds since the epoch.
+pub fn timestamp_ns(ts_high: u32, ts_low: u32, ts_resolution: u64, ts_offset: i64) -> u64 {
+    let ts = ((ts_high as u64) << 32) | ts_low as u64;
+    let ns = ts as u128 * 1_000_000_000 / ts_resolution as u128;
+    (ns as i128 + ts_offset as i128 * 1_000_000_000) as u64
+}
+
+/// Converts the seconds and fraction timestamp of a classic pcap packet to nanoseconds since
+/// the epoch.
+pub fn pcap_timestamp_ns(ts_sec: u32, ts_fraction: u32, ts_resolution: u64) -> u64 {
+    ts_sec as u64 * 1_000_000_000 + ts_fraction as u64 * 1_000_000_000 / ts_resolution
+}
+
+/// The Ethernet frame of a packet of `linktype`, `None` for other link types.
+pub fn ethernet_frame(data: &[u8], linktype: Linktype, caplen: u32) -> Option<&[u8]> {
+    match pcap_parser::data::get_packetdata(data, linktype, caplen as usize) {
+        Some(data::PacketData::L2(frame)) => Some(frame),
+        _ => None,
+    }
+}
+
+/// Queues the datagrams of a packet, if it carries EtherCAT.
+fn decode(frame_number: u64, ts_ns: u64, data: &[u8], pending: &mut VecDeque<DatagramRecord>) -> Result<(), ReadError> {
+    if !is_ethercat(data) {
+        return Ok(());
+    }
+    let frame = EtherNetFrame::parse(data).map_err(|error| ReadError::Frame { frame_number, error })?;
+    if !frame.carries_ethercat() {
+        return Ok(());
+    }
+    let returned = frame.is_returned();
+    let shared: Arc<[u8]> = data.into();
+    for (position, datagram) in frame.ecat_frame.datagrams.into_iter().enumerate() {
+        let start = data.offset(datagram.data);
+        pending.push_back(DatagramRecord {
+            frame_number,
+            ts_ns,
+            dst_mac: frame.dst_mac,
+            src_mac: frame.src_mac,
+            returned,
+            position,
+            header: datagram.header,
+            wkc: datagram.wkc,
+            frame: Arc::clone(&shared),
+            payload: start..start + datagram.data.len(),
+        });
+    }
+    Ok(())
+}
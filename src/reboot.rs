@@ -0,0 +1,165 @@
+use crate::events::EventKind;
+use crate::mailbox::{self, Direction};
+use crate::registers::{register_byte, AL_CONTROL_REGISTER};
+use crate::report::AL_STATUS_REGISTER;
+use crate::slave_config::SlaveConfigs;
+use crate::{EtherCATCommand, EtherCATDatagram, EtherNetFrame};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+/// SII EEPROM control/status, followed by the word address of the access at 0x0504.
+const EEPROM_CONTROL_REGISTER: u16 = 0x0502;
+const EEPROM_READ_COMMAND: u16 = 0x0100;
+/// SII word address of the vendor ID, the first word of the identity the master reads when it
+/// scans the bus.
+const SII_VENDOR_ID: u16 = 0x0008;
+const AL_STATE_INIT: u8 = 1;
+/// The last mailbox counter before the sequence wraps back to 1.
+const MAILBOX_COUNTER_MAX: u8 = 7;
+
+const BACK_TO_INIT: &str = "AL state back to INIT";
+const MAILBOX_COUNTER_RESET: &str = "mailbox counter reset";
+const IDENTITY_READ_AGAIN: &str = "identity read again";
+
+/// What a slave has shown of itself since it last came up.
+#[derive(Clone, Default, Serialize, Deserialize)]
+struct Slave {
+    /// AL state of the last AL status read.
+    state: Option<u8>,
+    /// The last state the master requested through AL control.
+    requested: Option<u8>,
+    /// Counter of the last message from the read mailbox.
+    mailbox_counter: Option<u8>,
+    identity_read: bool,
+    /// Capture time of the last datagram the slave answered.
+    last_answer_ns: Option<u64>,
+    /// Capture time of the first datagram it left unanswered after that.
+    silent_since_ns: Option<u64>,
+    /// The last time the slave stopped answering, from its last answer to its next one.
+    outage_ns: Option<u64>,
+}
+
+/// Watches every slave addressed by its station address for the signatures of a reboot in the
+/// middle of the capture:
+///
+/// - its AL status is back to INIT although the master did not request INIT,
+/// - the counter of its read mailbox restarts at 1 in the middle of the sequence,
+/// - the master reads its identity from the SII again, as it does for a slave it rescans.
+///
+/// A slave is reported once per reboot: its history starts over at the first signature. The
+/// downtime is the time it did not answer before, if it stopped answering at all.
+#[derive(Clone, Default, Serialize, Deserialize)]
+pub struct RebootMonitor {
+    slaves: BTreeMap<u16, Slave>,
+    #[serde(default)]
+    configs: SlaveConfigs,
+}
+
+/// The state requested by an outgoing datagram writing AL control, and whether it was
+/// broadcast to every slave.
+fn requested_state(datagram: &EtherCATDatagram) -> Option<(u8, bool)> {
+    use EtherCATCommand::*;
+
+    let header = &datagram.header;
+    let broadcast = match header.cmd {
+        FPWR | FPRW => false,
+        BWR | BRW => true,
+        _ => return None,
+    };
+    let state = register_byte(datagram, AL_CONTROL_REGISTER)?;
+    Some((state & 0x0F, broadcast))
+}
+
+/// Whether an outgoing datagram starts an SII read of the vendor ID.
+fn reads_identity(datagram: &EtherCATDatagram) -> bool {
+    let header = &datagram.header;
+    if header.cmd != EtherCATCommand::FPWR || header.offset_addr != EEPROM_CONTROL_REGISTER || header.length < 4 {
+        return false;
+    }
+    let word = |at: usize| datagram.data.get(at..at + 2).map(|bytes| u16::from_le_bytes([bytes[0], bytes[1]]));
+    word(0).is_some_and(|control| control & EEPROM_READ_COMMAND != 0) && word(2) == Some(SII_VENDOR_ID)
+}
+
+impl RebootMonitor {
+    /// Takes the next packet, returning the reboots it reveals.
+    pub fn check(&mut self, ts_ns: u64, frame: &EtherNetFrame) -> Vec<EventKind> {
+        use EtherCATCommand::*;
+
+        let returned = frame.is_returned();
+        let mut signatures = Vec::new();
+        for datagram in &frame.ecat_frame.datagrams {
+            self.configs.apply(datagram, returned);
+            let header = &datagram.header;
+            if !returned {
+                if let Some((state, broadcast)) = requested_state(datagram) {
+                    if broadcast {
+                        self.slaves.values_mut().for_each(|slave| slave.requested = Some(state));
+                    } else {
+                        self.slaves.entry(header.slave_addr).or_default().requested = Some(state);
+                    }
+                }
+                if reads_identity(datagram) {
+                    let slave = self.slaves.entry(header.slave_addr).or_default();
+                    if slave.identity_read {
+                        signatures.push((header.slave_addr, IDENTITY_READ_AGAIN));
+                    }
+                    slave.identity_read = true;
+                }
+                continue;
+            }
+            if !matches!(header.cmd, FPRD | FPWR | FPRW) {
+                continue;
+            }
+            let slave = self.slaves.entry(header.slave_addr).or_default();
+            if datagram.wkc == 0 {
+                if slave.last_answer_ns.is_some() {
+                    slave.silent_since_ns.get_or_insert(ts_ns);
+                }
+                continue;
+            }
+            if let (Some(_), Some(last)) = (slave.silent_since_ns.take(), slave.last_answer_ns) {
+                slave.outage_ns = Some(ts_ns.saturating_sub(last));
+            }
+            slave.last_answer_ns = Some(ts_ns);
+            if header.cmd == FPRD && header.offset_addr == AL_STATUS_REGISTER && header.length >= 1 {
+                if let Some(status) = datagram.data.first() {
+                    let state = status & 0x0F;
+                    let was_up = slave.state.is_some_and(|state| state > AL_STATE_INIT);
+                    if state == AL_STATE_INIT && was_up && slave.requested != Some(AL_STATE_INIT) {
+                        signatures.push((header.slave_addr, BACK_TO_INIT));
+                    }
+                    slave.state = Some(state);
+                }
+            }
+            if let Some(mailbox) = mailbox::extract(datagram, returned, &self.configs) {
+                if mailbox.direction == Direction::FromSlave && mailbox.counter != 0 {
+                    let previous = slave.mailbox_counter.replace(mailbox.counter);
+                    if mailbox.counter == 1 && previous.is_some_and(|previous| previous > 1 && previous < MAILBOX_COUNTER_MAX) {
+                        signatures.push((header.slave_addr, MAILBOX_COUNTER_RESET));
+                    }
+                }
+            }
+        }
+
+        let mut events = Vec::new();
+        for (station, signature) in signatures {
+            let Some(slave) = self.slaves.get_mut(&station) else {
+                continue;
+            };
+            // a frame showing several signatures of the same reboot reports it once.
+            if events.iter().any(|event| matches!(event, EventKind::SlaveReboot { slave, .. } if *slave == station)) {
+                continue;
+            }
+            events.push(EventKind::SlaveReboot { slave: station, signature, downtime_ns: slave.outage_ns });
+            // the state the slave comes up to, and the identity read revealing the reboot, are
+            // the first of its new life.
+            *slave = Slave {
+                requested: slave.requested,
+                last_answer_ns: slave.last_answer_ns,
+                identity_read: signature == IDENTITY_READ_AGAIN,
+                ..Slave::default()
+            };
+        }
+        events
+    }
+}
@@ -0,0 +1,94 @@
+use ecat_packet_analyzer_rs::reader::grow_for_block;
+use pcap_parser::traits::PcapReaderIterator;
+use pcap_parser::*;
+
+const KNOWN_BLOCK_TYPES: [u32; 11] = [
+    SHB_MAGIC, IDB_MAGIC, SPB_MAGIC, NRB_MAGIC, ISB_MAGIC, EPB_MAGIC,
+    SJE_MAGIC, DSB_MAGIC, CB_MAGIC, DCB_MAGIC, PIB_MAGIC,
+];
+
+enum Scan {
+    /// A plausible block starts at this offset.
+    Found(usize),
+    /// Nothing plausible before this offset; drop it and look at more data.
+    Skip(usize),
+    /// A plausible block starts at this offset, but does not end in the data yet.
+    Partial(usize),
+}
+
+fn read_u32(data: &[u8], big_endian: bool) -> u32 {
+    let bytes = [data[0], data[1], data[2], data[3]];
+    if big_endian {
+        u32::from_be_bytes(bytes)
+    } else {
+        u32::from_le_bytes(bytes)
+    }
+}
+
+/// Looks for the next offset in `data` (from `start`) where a pcapng block with a known type,
+/// a sane length and a matching trailing length begins.
+fn scan(data: &[u8], start: usize, big_endian: bool, max_block_len: usize, at_eof: bool) -> Scan {
+    let mut pos = start;
+    while pos + 12 <= data.len() {
+        let block_type = read_u32(&data[pos..], big_endian);
+        if KNOWN_BLOCK_TYPES.contains(&block_type) {
+            // A section header carries its own byte order in the byte-order magic.
+            let big_endian = if block_type == SHB_MAGIC {
+                read_u32(&data[pos + 8..], true) == BOM_MAGIC
+            } else {
+                big_endian
+            };
+            let block_len = read_u32(&data[pos + 4..], big_endian) as usize;
+            if block_len >= 12 && block_len.is_multiple_of(4) && block_len <= max_block_len {
+                if pos + block_len <= data.len() {
+                    if read_u32(&data[pos + block_len - 4..], big_endian) as usize == block_len {
+                        return Scan::Found(pos);
+                    }
+                } else if !at_eof {
+                    return Scan::Partial(pos);
+                }
+            }
+        }
+        pos += 1;
+    }
+    if at_eof {
+        Scan::Skip(data.len())
+    } else {
+        Scan::Skip(pos)
+    }
+}
+
+/// Skips forward from a corrupt block to the next valid block boundary.
+/// Returns the number of bytes skipped; if no valid block follows, the rest of the input is consumed.
+/// The buffer of the reader, of `buffer_size` bytes, grows for a block that it cannot hold.
+pub fn resync(reader: &mut dyn PcapReaderIterator, big_endian: bool, max_block_len: usize, buffer_size: &mut usize) -> Result<usize, String> {
+    let mut skipped = 0;
+    // the block at the current position is the corrupt one, so never stop there again.
+    let mut start = 1;
+    loop {
+        let at_eof = reader.reader_exhausted();
+        match scan(reader.data(), start, big_endian, max_block_len, at_eof) {
+            Scan::Found(pos) => {
+                reader.consume(pos);
+                return Ok(skipped + pos);
+            }
+            Scan::Skip(pos) => {
+                reader.consume(pos);
+                skipped += pos;
+                if at_eof {
+                    return Ok(skipped);
+                }
+                reader.refill().map_err(|e| e.to_string())?;
+                start = 0;
+            }
+            Scan::Partial(pos) => {
+                reader.consume(pos);
+                skipped += pos;
+                // the length of the block was checked against `max_block_len` already.
+                let _ = grow_for_block(reader, buffer_size, false, big_endian);
+                reader.refill().map_err(|e| e.to_string())?;
+                start = 0;
+            }
+        }
+    }
+}
@@ -0,0 +1,212 @@
+use crate::{EtherCATCommand, EtherCATDatagram, EtherCATDatagramHeader};
+use serde::Deserialize;
+use std::fs;
+
+/// The well-known registers of the ESC address space, as start, length and name, from the
+/// EtherCAT slave controller documentation. FMMUs and SyncManagers are named by `channel_name`.
+const REGISTERS: &[(u16, u16, &str)] = &[
+    (0x0000, 1, "Type"),
+    (0x0001, 1, "Revision"),
+    (0x0002, 2, "Build"),
+    (0x0004, 1, "FMMUs Supported"),
+    (0x0005, 1, "SyncManagers Supported"),
+    (0x0006, 1, "RAM Size"),
+    (0x0007, 1, "Port Descriptor"),
+    (0x0008, 2, "ESC Features Supported"),
+    (0x0010, 2, "Configured Station Address"),
+    (0x0012, 2, "Configured Station Alias"),
+    (0x0020, 1, "Register Write Enable"),
+    (0x0021, 1, "Register Write Protection"),
+    (0x0030, 1, "ESC Write Enable"),
+    (0x0031, 1, "ESC Write Protection"),
+    (0x0040, 1, "ESC Reset ECAT"),
+    (0x0041, 1, "ESC Reset PDI"),
+    (0x0100, 4, "ESC DL Control"),
+    (0x0108, 2, "Physical Read/Write Offset"),
+    (0x0110, 2, "ESC DL Status"),
+    (0x0120, 2, "AL Control"),
+    (0x0130, 2, "AL Status"),
+    (0x0134, 2, "AL Status Code"),
+    (0x0138, 1, "RUN LED Override"),
+    (0x0139, 1, "ERR LED Override"),
+    (0x0140, 1, "PDI Control"),
+    (0x0141, 1, "ESC Configuration"),
+    (0x014E, 2, "PDI Information"),
+    (0x0150, 4, "PDI Configuration"),
+    (0x0200, 2, "ECAT Event Mask"),
+    (0x0204, 4, "AL Event Mask"),
+    (0x0210, 2, "ECAT Event Request"),
+    (0x0220, 4, "AL Event Request"),
+    (0x0300, 8, "RX Error Counters"),
+    (0x0308, 4, "Forwarded RX Error Counters"),
+    (0x030C, 1, "ECAT Processing Unit Error Counter"),
+    (0x030D, 1, "PDI Error Counter"),
+    (0x030E, 1, "PDI Error Code"),
+    (0x0310, 4, "Lost Link Counters"),
+    (0x0400, 2, "Watchdog Divider"),
+    (0x0410, 2, "Watchdog Time PDI"),
+    (0x0420, 2, "Watchdog Time Process Data"),
+    (0x0440, 2, "Watchdog Status Process Data"),
+    (0x0442, 1, "Watchdog Counter Process Data"),
+    (0x0443, 1, "Watchdog Counter PDI"),
+    (0x0500, 1, "SII EEPROM Configuration"),
+    (0x0501, 1, "SII EEPROM PDI Access State"),
+    (0x0502, 2, "SII EEPROM Control/Status"),
+    (0x0504, 4, "SII EEPROM Address"),
+    (0x0508, 8, "SII EEPROM Data"),
+    (0x0510, 2, "MII Management Control/Status"),
+    (0x0512, 1, "PHY Address"),
+    (0x0513, 1, "PHY Register Address"),
+    (0x0514, 2, "PHY Data"),
+    (0x0516, 1, "MII Management ECAT Access State"),
+    (0x0517, 1, "MII Management PDI Access State"),
+    (0x0900, 4, "DC Receive Time Port 0"),
+    (0x0904, 4, "DC Receive Time Port 1"),
+    (0x0908, 4, "DC Receive Time Port 2"),
+    (0x090C, 4, "DC Receive Time Port 3"),
+    (0x0910, 8, "DC System Time"),
+    (0x0918, 8, "DC Receive Time ECAT Processing Unit"),
+    (0x0920, 8, "DC System Time Offset"),
+    (0x0928, 4, "DC System Time Delay"),
+    (0x092C, 4, "DC System Time Difference"),
+    (0x0930, 2, "DC Speed Counter Start"),
+    (0x0932, 2, "DC Speed Counter Diff"),
+    (0x0934, 1, "DC System Time Difference Filter Depth"),
+    (0x0935, 1, "DC Speed Counter Filter Depth"),
+    (0x0980, 1, "DC Cyclic Unit Control"),
+    (0x0981, 1, "DC Activation"),
+    (0x0982, 2, "DC Pulse Length of SyncSignals"),
+    (0x0984, 1, "DC Activation Status"),
+    (0x098E, 1, "DC SYNC0 Status"),
+    (0x098F, 1, "DC SYNC1 Status"),
+    (0x0990, 8, "DC Start Time Cyclic Operation"),
+    (0x0998, 8, "DC Next SYNC1 Pulse"),
+    (0x09A0, 4, "DC SYNC0 Cycle Time"),
+    (0x09A4, 4, "DC SYNC1 Cycle Time"),
+    (0x0F00, 4, "Digital I/O Output Data"),
+    (0x0F10, 8, "General Purpose Outputs"),
+    (0x0F18, 8, "General Purpose Inputs"),
+    (0x0F80, 128, "User RAM"),
+];
+const FMMU_BASE: u16 = 0x0600;
+const FMMU_LEN: u16 = 16;
+const SM_BASE: u16 = 0x0800;
+const SM_LEN: u16 = 8;
+/// Both the FMMU and the SyncManager areas hold 16 channels.
+const CHANNELS: u16 = 16;
+/// Start of the process data RAM, which holds the mailboxes and the process data.
+const PROCESS_RAM: u16 = 0x1000;
+
+/// Configured station address, assigned by the master to the slave at a position while it
+/// scans the bus.
+pub const STATION_ADDRESS_REGISTER: u16 = 0x0010;
+/// AL control register, through which the master requests a state of the slave.
+pub const AL_CONTROL_REGISTER: u16 = 0x0120;
+
+/// Names of the ESC registers for the `register_name` column: the built-in table of the
+/// well-known registers, and the registers of a TOML file given with `--registers`, which win
+/// over the table where they overlap:
+///
+/// ```toml
+/// [[register]]
+/// address = 0x0F80
+/// length = 4
+/// name = "Heartbeat counter"
+///
+/// # one byte unless a length is given
+/// [[register]]
+/// address = 0x0F84
+/// name = "Vendor diagnosis"
+/// ```
+#[derive(Default)]
+pub struct RegisterNames {
+    overrides: Vec<Register>,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct RegistersFile {
+    #[serde(default, rename = "register")]
+    registers: Vec<Register>,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct Register {
+    address: u16,
+    length: Option<u16>,
+    name: String,
+}
+
+impl Register {
+    fn contains(&self, address: u16) -> bool {
+        (self.address as u32..self.address as u32 + self.length.unwrap_or(1) as u32).contains(&(address as u32))
+    }
+}
+
+/// FMMU and SyncManager channels are named with their number, such as "SyncManager 1".
+fn channel_name(address: u16) -> Option<String> {
+    if (FMMU_BASE..FMMU_BASE + CHANNELS * FMMU_LEN).contains(&address) {
+        Some(format!("FMMU {}", (address - FMMU_BASE) / FMMU_LEN))
+    } else if (SM_BASE..SM_BASE + CHANNELS * SM_LEN).contains(&address) {
+        Some(format!("SyncManager {}", (address - SM_BASE) / SM_LEN))
+    } else {
+        None
+    }
+}
+
+impl RegisterNames {
+    pub fn load(path: &str) -> Result<Self, String> {
+        let text = fs::read_to_string(path).map_err(|e| format!("Cannot read {} : {}", path, e))?;
+        let file: RegistersFile = toml::from_str(&text).map_err(|e| format!("Invalid registers {} : {}", path, e))?;
+        if let Some(register) = file.registers.iter().find(|register| register.length == Some(0)) {
+            return Err(format!("Invalid registers {} : '{}' has a length of 0", path, register.name));
+        }
+        Ok(RegisterNames { overrides: file.registers })
+    }
+
+    /// Name of the register at `address`, or of the register `address` falls inside.
+    pub fn name(&self, address: u16) -> Option<String> {
+        if let Some(register) = self.overrides.iter().find(|register| register.contains(address)) {
+            return Some(register.name.clone());
+        }
+        if let Some(name) = channel_name(address) {
+            return Some(name);
+        }
+        if address >= PROCESS_RAM {
+            return Some("Process Data RAM".to_string());
+        }
+        REGISTERS
+            .iter()
+            .find(|(start, length, _)| (*start as u32..*start as u32 + *length as u32).contains(&(address as u32)))
+            .map(|(_, _, name)| name.to_string())
+    }
+
+    /// Name of the register a datagram starts at, for the commands addressing the registers of
+    /// slaves by position, station address or broadcast. Logical commands address the process
+    /// image instead and get none.
+    pub fn datagram_register(&self, header: &EtherCATDatagramHeader) -> Option<String> {
+        use EtherCATCommand::*;
+
+        match header.cmd {
+            APRD | APWR | APRW | ARMW | FPRD | FPWR | FPRW | FRMW | BRD | BWR | BRW => self.name(header.offset_addr),
+            LRD | LWR | LRW | NOP | UNKNOWN => None,
+        }
+    }
+}
+
+/// The `N` bytes at `register` carried by a datagram, if it covers all of them.
+pub fn register_bytes<const N: usize>(datagram: &EtherCATDatagram, register: u16) -> Option<[u8; N]> {
+    let offset = register.checked_sub(datagram.header.offset_addr)? as usize;
+    datagram.data.get(offset..offset + N)?.try_into().ok()
+}
+
+/// The byte at `register` carried by a datagram, if it covers it.
+pub fn register_byte(datagram: &EtherCATDatagram, register: u16) -> Option<u8> {
+    register_bytes(datagram, register).map(|[byte]| byte)
+}
+
+/// The 16-bit register at `register` carried by a datagram, if it covers it.
+pub fn register_word(datagram: &EtherCATDatagram, register: u16) -> Option<u16> {
+    register_bytes(datagram, register).map(u16::from_le_bytes)
+}
@@ -0,0 +1,230 @@
+use crate::capture::{self, CaptureOptions, Item};
+use crate::circulation::CirculationMonitor;
+use crate::emergency;
+use crate::esi::{DeviceCatalog, SiiReader};
+use crate::irq::IrqMonitor;
+use crate::labels::Labels;
+use crate::reboot::RebootMonitor;
+use crate::sink::outln;
+use crate::slave_config::{SlaveConfigs, SlaveRef};
+use crate::state::{al_state, SlaveState, SlaveStates};
+use crate::value::{Style, Value};
+use crate::wkc::WkcModel;
+use crate::{validate, EtherCATCommand, EtherNetFrame};
+use ecat_packet_analyzer_rs::parser::ecat::FrameType;
+use std::collections::BTreeMap;
+use std::fs::File;
+use std::ops::ControlFlow;
+use std::process::exit;
+use std::sync::Arc;
+
+/// AL status register of every slave.
+pub const AL_STATUS_REGISTER: u16 = 0x0130;
+
+/// What the report needs besides the capture.
+pub struct ReportOptions<'a> {
+    /// Name of the capture, for the title.
+    pub capture_name: &'a str,
+    pub irq_storm: u64,
+    pub wkc_model: Option<WkcModel>,
+    pub labels: Labels,
+    /// Devices of --esi-dir, naming the slaves of the topology.
+    pub device_catalog: Option<Arc<DeviceCatalog>>,
+}
+
+/// How often an anomaly was flagged, and where.
+struct Anomaly {
+    count: u64,
+    first_frame: u64,
+    last_frame: u64,
+}
+
+#[derive(Default)]
+struct Report {
+    packets: u64,
+    ethercat_frames: u64,
+    returned_frames: u64,
+    /// EtherCAT frames of other types than datagrams.
+    frame_types: BTreeMap<FrameType, u64>,
+    datagrams: u64,
+    first_ts_ns: Option<u64>,
+    last_ts_ns: u64,
+    /// Slaves counted by returned broadcast reads.
+    broadcast_slaves: Option<u16>,
+    slaves: SlaveStates,
+    sii: SiiReader,
+    timeline: Vec<(u64, String)>,
+    anomalies: BTreeMap<&'static str, Anomaly>,
+}
+
+impl Report {
+    fn flag(&mut self, code: &'static str, frame_number: u64) {
+        let anomaly = self.anomalies.entry(code).or_insert(Anomaly { count: 0, first_frame: frame_number, last_frame: 0 });
+        anomaly.count += 1;
+        anomaly.last_frame = frame_number;
+    }
+
+    fn frame(&mut self, ts_ns: u64, frame_number: u64, frame: &EtherNetFrame, wkc_model: Option<&WkcModel>) {
+        let returned = frame.is_returned();
+        self.ethercat_frames += 1;
+        self.returned_frames += returned as u64;
+        if frame.ecat_frame.content.is_some() {
+            *self.frame_types.entry(frame.ecat_frame.header.frame_type()).or_default() += 1;
+        }
+        for issue in validate::check_frame(frame) {
+            self.flag(issue.kind.code(), frame_number);
+        }
+        for datagram in &frame.ecat_frame.datagrams {
+            let header = &datagram.header;
+            self.datagrams += 1;
+            if !returned {
+                continue;
+            }
+            if wkc_model.and_then(|model| model.expected(header)).is_some_and(|expected| expected != datagram.wkc) {
+                self.flag("wkc_mismatch", frame_number);
+            }
+            if header.cmd == EtherCATCommand::BRD {
+                self.broadcast_slaves = Some(self.broadcast_slaves.unwrap_or(0).max(datagram.wkc));
+            }
+        }
+        self.slaves.frame(ts_ns, frame_number, frame);
+        self.sii.frame(frame);
+    }
+}
+
+/// Escapes the characters that would break a Markdown table cell.
+fn cell(text: &str) -> String {
+    text.replace('|', "\\|")
+}
+
+/// Reads the whole capture and prints a Markdown report of it: summary, topology, timeline of
+/// the events and a table of the anomalies flagged on the way.
+pub fn run(file: File, options: &CaptureOptions, report_options: &ReportOptions) {
+    let mut report = Report::default();
+    let mut irq_monitor = IrqMonitor::default();
+    irq_monitor.set_threshold(report_options.irq_storm);
+    let mut reboot_monitor = RebootMonitor::default();
+    let mut circulation_monitor = CirculationMonitor::default();
+    let mut configs = SlaveConfigs::default();
+
+    let summary = capture::read(file, options, |item| {
+        match item {
+            Item::Packet(packet) => {
+                let info = packet.info;
+                report.packets += 1;
+                report.first_ts_ns.get_or_insert(info.ts_ns);
+                report.last_ts_ns = info.ts_ns;
+                for code in info.link_errors.codes() {
+                    report.flag(code, info.frame_number);
+                }
+                if let Some(frame) = packet.data.and_then(EtherNetFrame::parse_ethercat) {
+                    report.frame(info.ts_ns, info.frame_number, &frame, report_options.wkc_model.as_ref());
+                    let events = irq_monitor.check(info.frame_number, &frame).into_iter()
+                        .chain(reboot_monitor.check(info.ts_ns, &frame))
+                        .chain(circulation_monitor.check(&frame))
+                        .chain(emergency::check(&frame, &mut configs));
+                    for kind in events {
+                        report.timeline.push((info.frame_number, kind.render(Style::Report)));
+                    }
+                }
+            }
+            Item::Event(event) => report.timeline.push((event.frame_number, event.kind.render(Style::Report))),
+            Item::CorruptBlock { .. } | Item::Comment(_) | Item::CustomBlock { .. } | Item::OtherBlock => {}
+        }
+        ControlFlow::Continue(())
+    });
+    let summary = summary.unwrap_or_else(|e| {
+        eprintln!("{}", e);
+        exit(1);
+    });
+
+    outln!("# EtherCAT capture report: {}", cell(report_options.capture_name));
+    outln!();
+    outln!("## Summary");
+    outln!();
+    outln!("| | |");
+    outln!("|---|---|");
+    if let Some(first_ts_ns) = report.first_ts_ns {
+        outln!("| First packet | {} |", Value::Time(first_ts_ns).render(Style::Report));
+        outln!("| Duration | {} |", Value::Duration(report.last_ts_ns.saturating_sub(first_ts_ns)).render(Style::Report));
+    }
+    outln!("| Packets | {} |", Value::Count(report.packets).render(Style::Report));
+    outln!("| EtherCAT frames | {} ({} returned from the slaves) |", report.ethercat_frames, report.returned_frames);
+    for (frame_type, count) in &report.frame_types {
+        outln!("| EtherCAT frames of {} | {} |", frame_type, Value::Count(*count).render(Style::Report));
+    }
+    outln!("| Datagrams | {} |", Value::Count(report.datagrams).render(Style::Report));
+    outln!("| Events | {} |", report.timeline.len());
+    outln!("| Frames the capture host dropped | {} |", summary.capture_drops.total());
+    if circulation_monitor.frames() > 0 {
+        outln!("| Circulating frames | {} |", Value::Count(circulation_monitor.frames()).render(Style::Report));
+    }
+    if summary.corrupt_regions > 0 {
+        outln!("| Corrupt regions skipped | {} ({} bytes) |", summary.corrupt_regions, summary.skipped_bytes);
+    }
+    outln!();
+
+    outln!("## Topology");
+    outln!();
+    match report.broadcast_slaves {
+        Some(count) => outln!("Slaves answering broadcast reads: {}", count),
+        None => outln!("No broadcast read in the capture, the slave count is unknown."),
+    }
+    outln!();
+    // the slaves addressed by their station address that answered at least once.
+    let stations: Vec<(u16, &SlaveState)> = report.slaves.slaves().filter(|(_, slave)| slave.datagrams > 0).collect();
+    let catalog = report_options.device_catalog.as_deref();
+    if !stations.is_empty() {
+        match catalog {
+            Some(_) => {
+                outln!("| Station address | Device | Datagrams | First frame | AL state |");
+                outln!("|---|---|---|---|---|");
+            }
+            None => {
+                outln!("| Station address | Datagrams | First frame | AL state |");
+                outln!("|---|---|---|---|");
+            }
+        }
+        for (address, station) in stations {
+            let state = station.al_status.map_or_else(|| "not read".to_string(), al_state);
+            let slave = report_options.labels.display(SlaveRef::Station(address));
+            let first_frame = station.first_frame.unwrap_or_default();
+            let device = catalog.map(|catalog| {
+                let identity = report.sii.identity(address);
+                let name = identity.and_then(|identity| catalog.name(identity));
+                match (identity, name) {
+                    (_, Some(name)) => format!(" {} |", cell(name)),
+                    (Some(identity), None) => format!(" vendor 0x{:08x}, product 0x{:08x} |", identity.vendor_id, identity.product_code),
+                    (None, None) => " |".to_string(),
+                }
+            });
+            outln!("| {} |{} {} | {} | {} |", cell(&slave), device.unwrap_or_default(), station.datagrams, first_frame, state);
+        }
+        outln!();
+    }
+
+    outln!("## Timeline");
+    outln!();
+    if report.timeline.is_empty() {
+        outln!("No events.");
+    } else {
+        outln!("| Frame | Event |");
+        outln!("|---|---|");
+        for (frame_number, event) in &report.timeline {
+            outln!("| {} | {} |", frame_number, cell(event));
+        }
+    }
+    outln!();
+
+    outln!("## Anomalies");
+    outln!();
+    if report.anomalies.is_empty() {
+        outln!("No anomalies.");
+    } else {
+        outln!("| Anomaly | Occurrences | First frame | Last frame |");
+        outln!("|---|---|---|---|");
+        for (code, anomaly) in &report.anomalies {
+            outln!("| {} | {} | {} | {} |", code, anomaly.count, anomaly.first_frame, anomaly.last_frame);
+        }
+    }
+}
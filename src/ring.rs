@@ -0,0 +1,210 @@
+use crate::capture::{self, CaptureOptions, Item};
+use crate::circulation::CirculationMonitor;
+use crate::emergency;
+use crate::events::EventKind;
+use crate::irq::IrqMonitor;
+use crate::mailbox::MailboxMonitor;
+use crate::pcapng::{self, Frame};
+use crate::report::AL_STATUS_REGISTER;
+use crate::slave_config::SlaveConfigs;
+use crate::{EtherCATCommand, EtherNetFrame};
+use clap::ValueEnum;
+use std::collections::{BTreeMap, VecDeque};
+use std::fs::File;
+use std::ops::ControlFlow;
+use std::path::{Path, PathBuf};
+use std::process::exit;
+
+/// Error bit of the AL status register.
+const AL_ERROR: u8 = 0x10;
+
+/// What makes the ring write out the frames it holds.
+#[derive(Clone, Copy, PartialEq, Eq, Debug, ValueEnum)]
+pub enum Trigger {
+    /// A slave sets the error bit of its AL status
+    AlError,
+    /// A frame carries link-level errors (capture error flags, FCS mismatch)
+    LinkError,
+    /// The capture host reports dropped frames
+    CaptureDrop,
+    /// The capture clock jumps forward or goes backwards
+    ClockJump,
+    /// IRQ event bits stay asserted, see --irq-storm
+    IrqStorm,
+    /// Frames marked as circulating appear
+    Circulating,
+    /// A slave sends a CoE emergency
+    Emergency,
+    /// A slave replies with a mailbox error, or a mailbox message is repeated or lost
+    MailboxError,
+}
+
+impl Trigger {
+    fn name(self) -> &'static str {
+        match self {
+            Trigger::AlError => "al-error",
+            Trigger::LinkError => "link-error",
+            Trigger::CaptureDrop => "capture-drop",
+            Trigger::ClockJump => "clock-jump",
+            Trigger::IrqStorm => "irq-storm",
+            Trigger::Circulating => "circulating",
+            Trigger::Emergency => "emergency",
+            Trigger::MailboxError => "mailbox-error",
+        }
+    }
+
+    fn of_event(kind: &EventKind) -> Option<Self> {
+        match kind {
+            EventKind::ClockBackwards { .. } | EventKind::ClockJump { .. } => Some(Trigger::ClockJump),
+            EventKind::CaptureDrops { .. } => Some(Trigger::CaptureDrop),
+            EventKind::IrqStorm { .. } => Some(Trigger::IrqStorm),
+            EventKind::CirculatingFrame { .. } => Some(Trigger::Circulating),
+            EventKind::Emergency { .. } => Some(Trigger::Emergency),
+            EventKind::MailboxError { .. } | EventKind::MailboxRepeated { .. } | EventKind::MailboxLost { .. } => {
+                Some(Trigger::MailboxError)
+            }
+            EventKind::IrqStormEnded { .. } | EventKind::SlaveReboot { .. } | EventKind::MailboxRepeatRequest { .. } => None,
+        }
+    }
+}
+
+/// How the ring is sized and when it is written out.
+pub struct RingOptions<'a> {
+    /// Frames kept in memory in front of a trigger.
+    pub frames: usize,
+    /// Frames written after the triggering one.
+    pub after: usize,
+    pub triggers: &'a [Trigger],
+    pub output_dir: &'a Path,
+    pub irq_storm: u64,
+}
+
+/// A dump waiting for the frames following its trigger.
+struct Dump {
+    path: PathBuf,
+    trigger: Trigger,
+    frames: Vec<Frame>,
+    remaining: usize,
+}
+
+/// Last error bit seen by every reader of the AL status, so that a slave staying in error
+/// triggers once instead of every cycle.
+#[derive(Default)]
+struct AlErrors {
+    asserted: BTreeMap<(u8, u16), bool>,
+}
+
+impl AlErrors {
+    /// Whether a returned frame shows an AL error that was not there at the previous read.
+    fn check(&mut self, frame: &EtherNetFrame) -> bool {
+        use EtherCATCommand::*;
+
+        let mut raised = false;
+        for datagram in &frame.ecat_frame.datagrams {
+            let header = &datagram.header;
+            if !matches!(header.cmd, APRD | FPRD | BRD) || datagram.wkc == 0 {
+                continue;
+            }
+            let Some(at) = AL_STATUS_REGISTER.checked_sub(header.offset_addr) else {
+                continue;
+            };
+            let Some(status) = datagram.data.get(at as usize) else {
+                continue;
+            };
+            // auto-increment addresses have been incremented by the slaves on the way back,
+            // which still tells the readers of different slaves apart.
+            let error = status & AL_ERROR != 0;
+            let was = self.asserted.insert((header.cmd as u8, header.slave_addr), error).unwrap_or(false);
+            raised |= error && !was;
+        }
+        raised
+    }
+}
+
+fn finish(dump: Dump) {
+    match pcapng::write(&dump.path, &dump.frames) {
+        Ok(()) => eprintln!("{} : dumped {} frames to {}", dump.trigger.name(), dump.frames.len(), dump.path.display()),
+        Err(e) => {
+            eprintln!("Cannot write {} : {}", dump.path.display(), e);
+            exit(1);
+        }
+    }
+}
+
+/// Keeps the last frames of the capture in memory and writes them, with the frames following,
+/// to a pcapng file named after the frame of the trigger whenever one fires. Triggers firing
+/// while a dump still collects its following frames are part of that dump.
+pub fn run(file: File, options: &CaptureOptions, ring_options: &RingOptions) {
+    let mut ring: VecDeque<Frame> = VecDeque::with_capacity(ring_options.frames);
+    let mut dump: Option<Dump> = None;
+    let mut al_errors = AlErrors::default();
+    let mut irq_monitor = IrqMonitor::default();
+    irq_monitor.set_threshold(ring_options.irq_storm);
+    let mut circulation_monitor = CirculationMonitor::default();
+    let mut configs = SlaveConfigs::default();
+    let mut mailbox_monitor = MailboxMonitor::default();
+    let mut pending_trigger = None;
+
+    let summary = capture::read(file, options, |item| {
+        let packet = match item {
+            Item::Packet(packet) => packet,
+            Item::Event(event) => {
+                pending_trigger = pending_trigger.or(Trigger::of_event(&event.kind));
+                return ControlFlow::Continue(());
+            }
+            Item::CorruptBlock { .. } | Item::Comment(_) | Item::CustomBlock { .. } | Item::OtherBlock => {
+                return ControlFlow::Continue(())
+            }
+        };
+        let Some(data) = packet.data else {
+            return ControlFlow::Continue(());
+        };
+        let mut fired: Vec<Trigger> = pending_trigger.take().into_iter().collect();
+        if !packet.info.link_errors.is_empty() {
+            fired.push(Trigger::LinkError);
+        }
+        if let Some(frame) = EtherNetFrame::parse_ethercat(data) {
+            if frame.is_returned() && al_errors.check(&frame) {
+                fired.push(Trigger::AlError);
+            }
+            fired.extend(irq_monitor.check(packet.info.frame_number, &frame).iter().filter_map(Trigger::of_event));
+            fired.extend(circulation_monitor.check(&frame).iter().filter_map(Trigger::of_event));
+            fired.extend(emergency::check(&frame, &mut configs).iter().filter_map(Trigger::of_event));
+            fired.extend(mailbox_monitor.check(&frame).iter().filter_map(Trigger::of_event));
+        }
+        let trigger = fired.into_iter().find(|trigger| ring_options.triggers.contains(trigger));
+
+        let frame = Frame { ts_ns: packet.info.ts_ns, data: data.to_vec() };
+        if let Some(active) = dump.as_mut() {
+            active.frames.push(frame);
+            active.remaining -= 1;
+            if active.remaining == 0 {
+                finish(dump.take().unwrap());
+            }
+            return ControlFlow::Continue(());
+        }
+        if ring.len() == ring_options.frames {
+            ring.pop_front();
+        }
+        ring.push_back(frame);
+        if let Some(trigger) = trigger {
+            let path = ring_options.output_dir.join(format!("ring_{}.pcapng", packet.info.frame_number));
+            let started = Dump { path, trigger, frames: ring.drain(..).collect(), remaining: ring_options.after };
+            if ring_options.after == 0 {
+                finish(started);
+            } else {
+                dump = Some(started);
+            }
+        }
+        ControlFlow::Continue(())
+    });
+    let summary = summary.unwrap_or_else(|e| {
+        eprintln!("{}", e);
+        exit(1);
+    });
+    // the capture ended before all the following frames of the last dump.
+    if let Some(dump) = dump {
+        finish(dump);
+    }
+    summary.report();
+}
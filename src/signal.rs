@@ -0,0 +1,217 @@
+use crate::capture::{self, CaptureOptions, Item};
+use crate::sink::outln;
+use crate::slave_config::{SlaveConfigs, SlaveRef};
+use crate::value::{Style, Value};
+use crate::{EtherCATCommand, EtherCATDatagram, EtherNetFrame};
+use std::fs::File;
+use std::ops::ControlFlow;
+use std::process::exit;
+
+/// How the bits of a signal are decoded. Process data is little-endian.
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum SignalType {
+    Bool,
+    Unsigned(u8),
+    Signed(u8),
+    F32,
+    F64,
+}
+
+impl SignalType {
+    pub fn from_name(name: &str) -> Option<Self> {
+        Some(match name {
+            "bool" => SignalType::Bool,
+            "u8" => SignalType::Unsigned(8),
+            "u16" => SignalType::Unsigned(16),
+            "u32" => SignalType::Unsigned(32),
+            "u64" => SignalType::Unsigned(64),
+            "i8" => SignalType::Signed(8),
+            "i16" => SignalType::Signed(16),
+            "i32" => SignalType::Signed(32),
+            "i64" => SignalType::Signed(64),
+            "f32" => SignalType::F32,
+            "f64" => SignalType::F64,
+            _ => return None,
+        })
+    }
+
+    pub fn bits(self) -> u64 {
+        match self {
+            SignalType::Bool => 1,
+            SignalType::Unsigned(bits) | SignalType::Signed(bits) => bits as u64,
+            SignalType::F32 => 32,
+            SignalType::F64 => 64,
+        }
+    }
+
+    pub fn format(self, raw: u64) -> String {
+        match self {
+            SignalType::Bool | SignalType::Unsigned(_) => raw.to_string(),
+            SignalType::Signed(bits) => sign_extend(raw, bits).to_string(),
+            SignalType::F32 => f32::from_bits(raw as u32).to_string(),
+            SignalType::F64 => f64::from_bits(raw).to_string(),
+        }
+    }
+
+    /// The value of the raw bits as a number, for scaling.
+    pub fn number(self, raw: u64) -> f64 {
+        match self {
+            SignalType::Bool | SignalType::Unsigned(_) => raw as f64,
+            SignalType::Signed(bits) => sign_extend(raw, bits) as f64,
+            SignalType::F32 => f32::from_bits(raw as u32) as f64,
+            SignalType::F64 => f64::from_bits(raw),
+        }
+    }
+}
+
+fn sign_extend(raw: u64, bits: u8) -> i64 {
+    let unused = 64 - bits as u32;
+    ((raw << unused) as i64) >> unused
+}
+
+/// The `bits` bits at logical bit address `bit_address` carried by a logical datagram, if it
+/// carries all of them. Bit addresses count from bit 0 of logical byte 0.
+pub fn logical_bits(datagram: &EtherCATDatagram, bit_address: u64, bits: u64) -> Option<u64> {
+    let header = &datagram.header;
+    let start = (((header.offset_addr as u32) << 16) | header.slave_addr as u32) as u64 * 8;
+    let payload = datagram.data;
+    let offset = bit_address.checked_sub(start)?;
+    if offset + bits > payload.len() as u64 * 8 {
+        return None;
+    }
+    Some((0..bits).fold(0u64, |raw, i| {
+        let bit = offset + i;
+        raw | (((payload[(bit / 8) as usize] >> (bit % 8)) & 1) as u64) << i
+    }))
+}
+
+/// Where a signal lives.
+enum Source {
+    /// A logical address, in the datagrams of this logical command.
+    Logical(EtherCATCommand),
+    /// A physical address in the memory of a slave, found in the process image through its FMMUs.
+    Slave(u16),
+}
+
+/// A signal traced through the capture, given with `--extract-signal source@address[.bit]:type`:
+///
+/// ```text
+/// lrw@0x0012.3:bool      bit 3 of logical byte 0x12, as returned in LRW datagrams
+/// lwr@0x0000:u16         the word at logical address 0, as written by the master
+/// 0x1001@0x1100.5:bool   bit 5 of physical byte 0x1100 of the slave at station address 0x1001
+/// ```
+///
+/// Logical sources are `lrd` and `lrw`, read from the returned frames, and `lwr`, read from
+/// the outgoing ones. A slave source follows the FMMU of the slave mapping the address,
+/// including its start bits, to a logical address, and reads it from the returned frames for
+/// an input (read) FMMU or from the outgoing ones for an output. Types are `bool`, `u8` to
+/// `u64`, `i8` to `i64`, `f32` and `f64`; with a bit, the value starts at that bit.
+pub struct Signal {
+    name: String,
+    source: Source,
+    address: u32,
+    bit: u8,
+    signal_type: SignalType,
+}
+
+fn parse_number(s: &str) -> Option<u32> {
+    match s.strip_prefix("0x") {
+        Some(hex) => u32::from_str_radix(hex, 16).ok(),
+        None => s.parse().ok(),
+    }
+}
+
+impl Signal {
+    pub fn parse(s: &str) -> Result<Self, String> {
+        let error = |reason: &str| format!("Invalid --extract-signal '{}' : {}", s, reason);
+        let (location, type_name) = s.rsplit_once(':').ok_or_else(|| error("expected source@address:type"))?;
+        let (source, address) = location.split_once('@').ok_or_else(|| error("expected source@address:type"))?;
+        let signal_type = SignalType::from_name(type_name).ok_or_else(|| error("unknown type"))?;
+        let source = match source.to_ascii_lowercase().as_str() {
+            "lrd" => Source::Logical(EtherCATCommand::LRD),
+            "lrw" => Source::Logical(EtherCATCommand::LRW),
+            "lwr" => Source::Logical(EtherCATCommand::LWR),
+            station => match parse_number(station).and_then(|address| u16::try_from(address).ok()) {
+                Some(address) => Source::Slave(address),
+                None => return Err(error("the source is lrd, lrw, lwr or a station address")),
+            },
+        };
+        let (byte, bit) = match address.split_once('.') {
+            Some((byte, bit)) => (byte, bit.parse().ok().filter(|bit| *bit < 8).ok_or_else(|| error("the bit is 0 to 7"))?),
+            None => (address, 0),
+        };
+        let address = parse_number(byte).ok_or_else(|| error("invalid address"))?;
+        if matches!(source, Source::Slave(_)) && address > u16::MAX as u32 {
+            return Err(error("physical addresses are 16 bits"));
+        }
+        Ok(Signal { name: s.to_string(), source, address, bit, signal_type })
+    }
+
+    /// Logical bit address of the signal and whether it is read from the returned frames.
+    fn locate(&self, configs: &SlaveConfigs) -> Option<(u64, bool)> {
+        let bit_address = self.address as u64 * 8 + self.bit as u64;
+        match self.source {
+            Source::Logical(cmd) => Some((bit_address, cmd != EtherCATCommand::LWR)),
+            Source::Slave(station) => configs
+                .fmmus()
+                .filter(|(slave, _)| *slave == SlaveRef::Station(station))
+                .find_map(|(_, fmmu)| Some((fmmu.logical_bit(bit_address, self.signal_type.bits())?, fmmu.read))),
+        }
+    }
+
+    /// The value of the signal in a datagram, if the datagram carries all of its bits.
+    fn sample(&self, datagram: &EtherCATDatagram, returned: bool, configs: &SlaveConfigs) -> Option<String> {
+        use EtherCATCommand::*;
+
+        let header = &datagram.header;
+        let carried = match self.source {
+            Source::Logical(cmd) => header.cmd == cmd,
+            Source::Slave(_) => matches!(header.cmd, LRD | LRW | LWR),
+        };
+        if !carried {
+            return None;
+        }
+        let (bit_address, from_returned) = self.locate(configs)?;
+        if returned != from_returned || (header.cmd == LRD && !returned) || (header.cmd == LWR && returned) {
+            return None;
+        }
+        let raw = logical_bits(datagram, bit_address, self.signal_type.bits())?;
+        Some(self.signal_type.format(raw))
+    }
+}
+
+/// Prints the time series of the signals, one row per sample, or per change with `changes_only`.
+pub fn run(file: File, options: &CaptureOptions, signals: &[Signal], changes_only: bool) {
+    let mut configs = SlaveConfigs::default();
+    let mut last_values: Vec<Option<String>> = vec![None; signals.len()];
+
+    outln!("frame,time,signal,value");
+    let summary = capture::read(file, options, |item| {
+        let Item::Packet(packet) = item else {
+            return ControlFlow::Continue(());
+        };
+        let Some(frame) = packet.data.and_then(EtherNetFrame::parse_ethercat) else {
+            return ControlFlow::Continue(());
+        };
+        let returned = frame.is_returned();
+        for datagram in &frame.ecat_frame.datagrams {
+            configs.apply(datagram, returned);
+            for (signal, last_value) in signals.iter().zip(&mut last_values) {
+                let Some(value) = signal.sample(datagram, returned, &configs) else {
+                    continue;
+                };
+                if changes_only && last_value.as_ref() == Some(&value) {
+                    continue;
+                }
+                outln!("{},{},{},{}", packet.info.frame_number, Value::Time(packet.info.ts_ns).render(Style::Csv), signal.name, value);
+                *last_value = Some(value);
+            }
+        }
+        ControlFlow::Continue(())
+    });
+    let summary = summary.unwrap_or_else(|e| {
+        eprintln!("{}", e);
+        exit(1);
+    });
+    summary.report();
+}
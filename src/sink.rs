@@ -0,0 +1,321 @@
+use clap::ValueEnum;
+use std::collections::VecDeque;
+use std::fmt::Arguments;
+use std::fs::{self, File, OpenOptions};
+use std::io::{BufWriter, ErrorKind, Read, Seek, SeekFrom, Write};
+use std::path::PathBuf;
+use std::process::exit;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Condvar, Mutex};
+use std::thread::{self, JoinHandle};
+
+/// Output buffered in memory for a slow consumer when `--output-buffer` is not given.
+pub const DEFAULT_BUFFER: &str = "16MiB";
+
+/// Bytes read back from the spill file at a time.
+const SPILL_READ_LEN: usize = 64 * 1024;
+
+/// What happens to the output when its consumer falls behind by the whole buffer.
+#[derive(ValueEnum, Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Backpressure {
+    /// Wait for the consumer, holding up the export
+    #[default]
+    Block,
+    /// Drop the oldest buffered output, counting what is lost
+    DropOldest,
+    /// Write the excess to a file and send it on once the consumer catches up
+    Spill,
+}
+
+/// Parses a size such as `16MiB`, `512KiB`, `1GiB` or a bare number of bytes.
+pub fn parse_size(s: &str) -> Result<usize, String> {
+    let s = s.trim();
+    let split = s.find(|c: char| !c.is_ascii_digit()).unwrap_or(s.len());
+    let (number, unit) = s.split_at(split);
+    let scale = match unit.trim() {
+        "" | "B" => 1,
+        "KiB" => 1 << 10,
+        "MiB" => 1 << 20,
+        "GiB" => 1 << 30,
+        unit => return Err(format!("unknown size unit '{}' in '{}'", unit, s)),
+    };
+    let value: usize = number.parse().map_err(|_| format!("invalid size '{}'", s))?;
+    value.checked_mul(scale).ok_or_else(|| format!("size '{}' is too large", s))
+}
+
+/// Where the output ends up: stdout, or the file of `--output`.
+pub type Output = Box<dyn Write + Send>;
+
+/// Opens the file of `--output`, or stdout without one. A resumed export appends to the file
+/// cut back to its length at the checkpoint.
+pub fn open_output(path: Option<&str>, append: bool) -> Result<Output, String> {
+    let Some(path) = path else {
+        return Ok(Box::new(std::io::stdout()));
+    };
+    let file = OpenOptions::new().write(true).create(true).append(append).truncate(!append).open(path)
+        .map_err(|e| format!("Cannot create {} : {}", path, e))?;
+    Ok(Box::new(BufWriter::new(file)))
+}
+
+/// Where the output writes go: straight to the output, or into a bounded buffer a thread of
+/// its own empties into the output, so that a stalling pipe or socket does not hold up the
+/// export until the buffer is full, and then only as the policy says.
+pub struct Sink {
+    mode: Mode,
+    out: Arc<Mutex<Output>>,
+    /// Bytes that reached the output so far.
+    written: Arc<AtomicU64>,
+}
+
+enum Mode {
+    Direct,
+    Buffered {
+        shared: Arc<Shared>,
+        writer: JoinHandle<()>,
+    },
+}
+
+struct Shared {
+    queue: Mutex<Queue>,
+    /// Signalled whenever output is queued, written or the sink is closed.
+    changed: Condvar,
+    policy: Backpressure,
+    capacity: usize,
+    spill_path: PathBuf,
+}
+
+#[derive(Default)]
+struct Queue {
+    chunks: VecDeque<String>,
+    bytes: usize,
+    /// Output written after everything in `chunks`, once the buffer has overflowed.
+    spill: Option<Spill>,
+    /// The writer thread is writing a chunk it took off the queue.
+    writing: bool,
+    closed: bool,
+    dropped_writes: u64,
+    dropped_bytes: u64,
+    spilled_bytes: u64,
+}
+
+struct Spill {
+    file: File,
+    reader: File,
+    unread: u64,
+}
+
+impl Sink {
+    /// Writes straight to the output, blocking while the consumer does not read.
+    pub fn direct(out: Output) -> Self {
+        Sink { mode: Mode::Direct, out: Arc::new(Mutex::new(out)), written: Arc::new(AtomicU64::new(0)) }
+    }
+
+    /// Buffers up to `capacity` bytes of output in memory, the spill file of the `spill` policy
+    /// going to `spill_dir`.
+    pub fn buffered(out: Output, policy: Backpressure, capacity: usize, spill_dir: Option<&str>) -> Self {
+        let spill_dir = spill_dir.map_or_else(std::env::temp_dir, PathBuf::from);
+        let shared = Arc::new(Shared {
+            queue: Mutex::new(Queue::default()),
+            changed: Condvar::new(),
+            policy,
+            capacity,
+            spill_path: spill_dir.join(format!("ecat-export-{}.spill", std::process::id())),
+        });
+        let out = Arc::new(Mutex::new(out));
+        let written = Arc::new(AtomicU64::new(0));
+        let writer_shared = Arc::clone(&shared);
+        let writer_out = Arc::clone(&out);
+        let writer_written = Arc::clone(&written);
+        let writer = thread::spawn(move || writer(&writer_shared, &writer_out, &writer_written));
+        Sink { mode: Mode::Buffered { shared, writer }, out, written }
+    }
+
+    /// The count of bytes that reached the output, kept up to date as the sink writes.
+    pub fn written(&self) -> Arc<AtomicU64> {
+        Arc::clone(&self.written)
+    }
+
+    pub fn write(&self, text: String) {
+        let Mode::Buffered { shared, .. } = &self.mode else {
+            write_output(&mut **self.out.lock().unwrap(), text.as_bytes(), false);
+            self.written.fetch_add(text.len() as u64, Ordering::Relaxed);
+            return;
+        };
+        let mut queue = shared.queue.lock().unwrap();
+        loop {
+            if queue.spill.as_ref().is_some_and(|spill| spill.unread > 0) {
+                // the consumer has not caught up with the spill file yet, which comes after the buffer.
+                spill(shared, &mut queue, &text);
+                break;
+            }
+            if queue.chunks.is_empty() || queue.bytes + text.len() <= shared.capacity {
+                queue.bytes += text.len();
+                queue.chunks.push_back(text);
+                break;
+            }
+            match shared.policy {
+                Backpressure::Block => queue = shared.changed.wait(queue).unwrap(),
+                Backpressure::DropOldest => {
+                    let oldest = queue.chunks.pop_front().unwrap();
+                    if queue.dropped_writes == 0 {
+                        eprintln!("The consumer of the output is too slow, dropping the oldest buffered output");
+                    }
+                    queue.bytes -= oldest.len();
+                    queue.dropped_writes += 1;
+                    queue.dropped_bytes += oldest.len() as u64;
+                }
+                Backpressure::Spill => {
+                    spill(shared, &mut queue, &text);
+                    break;
+                }
+            }
+        }
+        shared.changed.notify_all();
+    }
+
+    /// Waits until everything written so far is in the output.
+    pub fn flush(&self) {
+        if let Mode::Buffered { shared, .. } = &self.mode {
+            let mut queue = shared.queue.lock().unwrap();
+            while queue.writing || !queue.chunks.is_empty() || queue.spill.as_ref().is_some_and(|spill| spill.unread > 0) {
+                queue = shared.changed.wait(queue).unwrap();
+            }
+        }
+        write_output(&mut **self.out.lock().unwrap(), &[], true);
+    }
+
+    /// Writes out the rest of the buffer and reports the output dropped or spilled on the way.
+    pub fn finish(self) {
+        let Mode::Buffered { shared, writer } = self.mode else {
+            write_output(&mut **self.out.lock().unwrap(), &[], true);
+            return;
+        };
+        shared.queue.lock().unwrap().closed = true;
+        shared.changed.notify_all();
+        writer.join().expect("output writer thread panicked");
+        let queue = shared.queue.lock().unwrap();
+        if queue.dropped_writes > 0 {
+            eprintln!("Dropped {} bytes of output in {} writes while the consumer was slow", queue.dropped_bytes, queue.dropped_writes);
+        }
+        if queue.spilled_bytes > 0 {
+            eprintln!("Spilled {} bytes of output to disk while the consumer was slow", queue.spilled_bytes);
+        }
+        if queue.spill.is_some() {
+            fs::remove_file(&shared.spill_path).ok();
+        }
+    }
+}
+
+/// Appends output to the spill file, creating it on the first overflow.
+fn spill(shared: &Shared, queue: &mut Queue, text: &str) {
+    let open = || -> std::io::Result<Spill> {
+        let file = OpenOptions::new().read(true).write(true).create(true).truncate(true).open(&shared.spill_path)?;
+        Ok(Spill { file, reader: File::open(&shared.spill_path)?, unread: 0 })
+    };
+    if queue.spill.is_none() {
+        queue.spill = Some(open().unwrap_or_else(|e| {
+            eprintln!("Cannot create the spill file {} : {}", shared.spill_path.display(), e);
+            exit(1);
+        }));
+    }
+    let spill = queue.spill.as_mut().unwrap();
+    if let Err(e) = spill.file.write_all(text.as_bytes()) {
+        eprintln!("Cannot write the spill file {} : {}", shared.spill_path.display(), e);
+        exit(1);
+    }
+    spill.unread += text.len() as u64;
+    queue.spilled_bytes += text.len() as u64;
+}
+
+/// The next output to write: the oldest buffered chunk, then what was spilled after the
+/// buffer. `None` when everything queued has been written.
+fn next_output(queue: &mut Queue) -> std::io::Result<Option<Vec<u8>>> {
+    if let Some(chunk) = queue.chunks.pop_front() {
+        queue.bytes -= chunk.len();
+        return Ok(Some(chunk.into_bytes()));
+    }
+    if let Some(spill) = queue.spill.as_mut().filter(|spill| spill.unread > 0) {
+        let mut buf = vec![0; SPILL_READ_LEN.min(spill.unread as usize)];
+        spill.reader.read_exact(&mut buf)?;
+        spill.unread -= buf.len() as u64;
+        if spill.unread == 0 {
+            // caught up: start the file over rather than let it grow for the whole export.
+            spill.file.set_len(0)?;
+            spill.file.seek(SeekFrom::Start(0))?;
+            spill.reader.seek(SeekFrom::Start(0))?;
+        }
+        return Ok(Some(buf));
+    }
+    Ok(None)
+}
+
+/// Writes to the output, flushing it if asked; the export cannot go on without its output.
+fn write_output(out: &mut dyn Write, bytes: &[u8], flush: bool) {
+    let result = out.write_all(bytes).and_then(|_| if flush { out.flush() } else { Ok(()) });
+    if let Err(e) = result {
+        write_failed(e);
+    }
+}
+
+/// Ends the process once the output cannot be written: quietly when the reader of a pipe has
+/// gone, as `head` leaves it, with an error otherwise.
+fn write_failed(e: std::io::Error) -> ! {
+    if e.kind() == ErrorKind::BrokenPipe {
+        exit(0);
+    }
+    eprintln!("Cannot write the output : {}", e);
+    exit(1);
+}
+
+/// Writes the output of a report subcommand to stdout, with the error handling of the export
+/// output; see [`outln`].
+pub fn print(text: Arguments) {
+    if let Err(e) = std::io::stdout().lock().write_fmt(text) {
+        write_failed(e);
+    }
+}
+
+/// `print!` for the report subcommands, which does not panic on a closed pipe.
+macro_rules! out {
+    ($($arg:tt)*) => {
+        $crate::sink::print(format_args!($($arg)*))
+    };
+}
+
+/// `println!` for the report subcommands, which does not panic on a closed pipe.
+macro_rules! outln {
+    () => {
+        $crate::sink::print(format_args!("\n"))
+    };
+    ($($arg:tt)*) => {
+        $crate::sink::print(format_args!("{}\n", format_args!($($arg)*)))
+    };
+}
+
+pub(crate) use {out, outln};
+
+fn writer(shared: &Shared, out: &Mutex<Output>, written: &AtomicU64) {
+    let mut queue = shared.queue.lock().unwrap();
+    loop {
+        let output = match next_output(&mut queue) {
+            Ok(Some(output)) => output,
+            Ok(None) if queue.closed => break,
+            Ok(None) => {
+                queue = shared.changed.wait(queue).unwrap();
+                continue;
+            }
+            Err(e) => {
+                eprintln!("Cannot read the spill file {} : {}", shared.spill_path.display(), e);
+                exit(1);
+            }
+        };
+        queue.writing = true;
+        shared.changed.notify_all();
+        drop(queue);
+        write_output(&mut **out.lock().unwrap(), &output, true);
+        written.fetch_add(output.len() as u64, Ordering::Relaxed);
+        queue = shared.queue.lock().unwrap();
+        queue.writing = false;
+        shared.changed.notify_all();
+    }
+}
@@ -0,0 +1,250 @@
+use crate::capture::{self, CaptureOptions, Item};
+use crate::labels::Labels;
+use crate::sink::outln;
+use crate::state::SlaveStates;
+use crate::value::{Style, Value};
+use crate::{EtherCATCommand, EtherCATDatagram, EtherNetFrame};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fmt::Display;
+use std::fs::File;
+use std::ops::ControlFlow;
+use std::process::exit;
+
+/// First register of the FMMU block; each FMMU takes 16 bytes.
+const FMMU_BASE: usize = 0x0600;
+const FMMU_ENTRY_LEN: usize = 16;
+/// First register of the SyncManager block; each SyncManager takes 8 bytes.
+const SM_BASE: usize = 0x0800;
+const SM_ENTRY_LEN: usize = 8;
+/// The mirrored registers, from the first FMMU to the last SyncManager.
+const MIRROR_LEN: usize = SM_BASE + 0x80 - FMMU_BASE;
+
+const CSV_HEADER: &str = "slave,position,channel,direction,mode,logical_start,length,physical_start,enabled";
+
+/// How a datagram addresses the slave it configures.
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+pub enum SlaveRef {
+    /// Configured station address (FPxx commands).
+    Station(u16),
+    /// Position in the chain (APxx commands).
+    Position(u16),
+}
+
+impl Display for SlaveRef {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SlaveRef::Station(address) => write!(f, "{}", Value::Address(*address).render(Style::Csv)),
+            SlaveRef::Position(position) => write!(f, "#{}", position),
+        }
+    }
+}
+
+/// One FMMU, mapping a logical range onto the physical memory of a slave.
+pub struct Fmmu {
+    pub index: usize,
+    pub logical_start: u32,
+    pub length: u16,
+    /// First and last bit of the logical range, within its first and last byte.
+    pub logical_start_bit: u8,
+    pub logical_end_bit: u8,
+    pub physical_start: u16,
+    pub physical_start_bit: u8,
+    /// The master reads this range (slave inputs).
+    pub read: bool,
+    /// The master writes this range (slave outputs).
+    pub write: bool,
+    /// The FMMU is activated; an FMMU left deactivated maps nothing.
+    pub enabled: bool,
+}
+
+impl Fmmu {
+    fn decode(index: usize, entry: &[u8]) -> Self {
+        let fmmu_type = entry[0xB];
+        Fmmu {
+            index,
+            logical_start: u32::from_le_bytes([entry[0], entry[1], entry[2], entry[3]]),
+            length: u16::from_le_bytes([entry[4], entry[5]]),
+            logical_start_bit: entry[6] & 0x7,
+            logical_end_bit: entry[7] & 0x7,
+            physical_start: u16::from_le_bytes([entry[8], entry[9]]),
+            physical_start_bit: entry[0xA] & 0x7,
+            read: fmmu_type & 0x1 != 0,
+            write: fmmu_type & 0x2 != 0,
+            enabled: entry[0xC] & 0x1 != 0,
+        }
+    }
+
+    /// Logical bit address of a range of physical bits, if the FMMU maps all of them. Bit
+    /// addresses count from bit 0 of byte 0.
+    pub fn logical_bit(&self, physical_bit: u64, bit_length: u64) -> Option<u64> {
+        let logical_start = self.logical_start as u64 * 8 + self.logical_start_bit as u64;
+        let logical_end = (self.logical_start as u64 + self.length as u64).checked_sub(1)? * 8 + self.logical_end_bit as u64;
+        let mapped_bits = (logical_end + 1).checked_sub(logical_start)?;
+        let offset = physical_bit.checked_sub(self.physical_start as u64 * 8 + self.physical_start_bit as u64)?;
+        (offset + bit_length <= mapped_bits).then_some(logical_start + offset)
+    }
+}
+
+/// One SyncManager, guarding a buffer in the physical memory of a slave.
+pub struct SyncManager {
+    pub index: usize,
+    pub start: u16,
+    pub length: u16,
+    /// A mailbox (one-buffer handshake) rather than a buffered process data SyncManager.
+    pub mailbox: bool,
+    /// The master writes the buffer, as opposed to reading it.
+    pub master_writes: bool,
+    /// The SyncManager is activated; a deactivated one leaves its buffer unguarded.
+    pub enabled: bool,
+}
+
+impl SyncManager {
+    fn decode(index: usize, entry: &[u8]) -> Self {
+        let control = entry[4];
+        SyncManager {
+            index,
+            start: u16::from_le_bytes([entry[0], entry[1]]),
+            length: u16::from_le_bytes([entry[2], entry[3]]),
+            mailbox: control & 0x3 == 0x2,
+            master_writes: (control >> 2) & 0x3 == 0x1,
+            enabled: entry[6] & 0x1 != 0,
+        }
+    }
+}
+
+/// Mirrors the FMMU and SyncManager registers of every slave from the register writes seen in
+/// the capture. A checkpoint keeps the registers as a list, slave by slave.
+#[derive(Clone, Default, Serialize, Deserialize)]
+#[serde(into = "Vec<(SlaveRef, Vec<u8>)>", try_from = "Vec<(SlaveRef, Vec<u8>)>")]
+pub struct SlaveConfigs {
+    registers: BTreeMap<SlaveRef, [u8; MIRROR_LEN]>,
+}
+
+impl From<SlaveConfigs> for Vec<(SlaveRef, Vec<u8>)> {
+    fn from(configs: SlaveConfigs) -> Self {
+        configs.registers.into_iter().map(|(slave, registers)| (slave, registers.to_vec())).collect()
+    }
+}
+
+impl TryFrom<Vec<(SlaveRef, Vec<u8>)>> for SlaveConfigs {
+    type Error = String;
+
+    fn try_from(slaves: Vec<(SlaveRef, Vec<u8>)>) -> Result<Self, String> {
+        let registers = slaves.into_iter().map(|(slave, registers)| {
+            let len = registers.len();
+            let registers = registers.try_into().map_err(|_| format!("{} bytes of registers for slave {}, not {}", len, slave, MIRROR_LEN))?;
+            Ok((slave, registers))
+        });
+        Ok(SlaveConfigs { registers: registers.collect::<Result<_, String>>()? })
+    }
+}
+
+impl SlaveConfigs {
+    /// Applies a datagram if it writes FMMU or SyncManager registers. Writes a slave did not acknowledge
+    /// (returning with a zero working counter) are ignored.
+    pub fn apply(&mut self, datagram: &EtherCATDatagram, returned: bool) {
+        use EtherCATCommand::*;
+
+        let header = &datagram.header;
+        if returned && datagram.wkc == 0 {
+            return;
+        }
+        let slave = match header.cmd {
+            FPWR | FPRW => Some(SlaveRef::Station(header.slave_addr)),
+            APWR | APRW => Some(SlaveRef::Position(header.slave_addr.wrapping_neg())),
+            BWR | BRW => None,
+            _ => return,
+        };
+
+        let start = header.offset_addr as usize;
+        let end = start + header.length as usize;
+        let mirror_end = FMMU_BASE + MIRROR_LEN;
+        if end <= FMMU_BASE || start >= mirror_end {
+            return;
+        }
+        let data = datagram.data;
+        let write = |registers: &mut [u8; MIRROR_LEN]| {
+            for (i, byte) in data.iter().enumerate() {
+                let address = start + i;
+                if (FMMU_BASE..mirror_end).contains(&address) {
+                    registers[address - FMMU_BASE] = *byte;
+                }
+            }
+        };
+
+        match slave {
+            Some(slave) => write(self.registers.entry(slave).or_insert([0; MIRROR_LEN])),
+            // a broadcast write reaches every slave seen so far
+            None => self.registers.values_mut().for_each(write),
+        }
+    }
+
+    /// The configured (non-empty) FMMUs of every slave.
+    pub fn fmmus(&self) -> impl Iterator<Item = (SlaveRef, Fmmu)> + '_ {
+        self.registers.iter().flat_map(|(slave, registers)| {
+            registers[..SM_BASE - FMMU_BASE]
+                .chunks(FMMU_ENTRY_LEN)
+                .enumerate()
+                .map(|(index, entry)| (*slave, Fmmu::decode(index, entry)))
+                .filter(|(_, fmmu)| fmmu.length > 0 && (fmmu.read || fmmu.write))
+        })
+    }
+
+    /// The configured SyncManagers of a slave, `None` if none were seen being written.
+    pub fn sync_managers(&self, slave: SlaveRef) -> Option<Vec<SyncManager>> {
+        let registers = self.registers.get(&slave)?;
+        let sync_managers: Vec<SyncManager> = registers[SM_BASE - FMMU_BASE..]
+            .chunks(SM_ENTRY_LEN)
+            .enumerate()
+            .map(|(index, entry)| SyncManager::decode(index, entry))
+            .filter(|sm| sm.length > 0)
+            .collect();
+        (!sync_managers.is_empty()).then_some(sync_managers)
+    }
+}
+
+/// Prints the SyncManager and FMMU setup every slave was left with at the end of the capture,
+/// one row per channel: the physical buffer each SyncManager guards and how, and the logical
+/// range each FMMU maps onto the physical memory. Comparing it with the configuration of the
+/// ENI shows whether the master configured the slaves as intended.
+pub fn run(file: File, options: &CaptureOptions, labels: &Labels) {
+    let mut states = SlaveStates::default();
+    let summary = capture::read(file, options, |item| {
+        let Item::Packet(packet) = item else {
+            return ControlFlow::Continue(());
+        };
+        if let Some(frame) = packet.data.and_then(EtherNetFrame::parse_ethercat) {
+            states.frame(packet.info.ts_ns, packet.info.frame_number, &frame);
+        }
+        ControlFlow::Continue(())
+    });
+    let summary = summary.unwrap_or_else(|e| {
+        eprintln!("{}", e);
+        exit(1);
+    });
+
+    outln!("{}{}", CSV_HEADER, labels.header());
+    for (address, slave) in states.slaves() {
+        let slave_cells = format!("{},{}", Value::Address(address).render(Style::Csv),
+            slave.position.map(|position| position.to_string()).unwrap_or_default());
+        let label = labels.cell(Some(SlaveRef::Station(address)));
+        for sm in states.sync_managers(address).unwrap_or_default() {
+            let direction = if sm.master_writes { "outputs" } else { "inputs" };
+            let mode = if sm.mailbox { "mailbox" } else { "buffered" };
+            outln!("{},sm{},{},{},,{},{},{}{}",
+                slave_cells, sm.index, direction, mode, sm.length, Value::Address(sm.start).render(Style::Csv), sm.enabled, label);
+        }
+        for fmmu in states.fmmus(address) {
+            let direction = match (fmmu.read, fmmu.write) {
+                (true, true) => "inputs+outputs",
+                (true, false) => "inputs",
+                _ => "outputs",
+            };
+            outln!("{},fmmu{},{},,{},{},{},{}{}",
+                slave_cells, fmmu.index, direction, Value::LogicalAddress(fmmu.logical_start).render(Style::Csv), fmmu.length,
+                Value::Address(fmmu.physical_start).render(Style::Csv), fmmu.enabled, label);
+        }
+    }
+    summary.report();
+}
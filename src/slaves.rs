@@ -0,0 +1,121 @@
+use crate::capture::{self, CaptureOptions, Item};
+use crate::esi::SiiReader;
+use crate::labels::{addressed_slave, Labels};
+use crate::mailbox::{self, Direction};
+use crate::pair::Key;
+use crate::sink::outln;
+use crate::slave_config::{SlaveConfigs, SlaveRef};
+use crate::value::{Style, Value};
+use crate::wkc::WkcModel;
+use crate::{EtherCATCommand, EtherNetFrame};
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::fs::File;
+use std::ops::ControlFlow;
+use std::process::exit;
+
+const CSV_HEADER: &str = "slave,reads,writes,registers,wkc_failures,mailbox_out,mailbox_in,first_seen,last_seen,\
+    vendor_id,product_code,revision,serial_number";
+
+/// The traffic of one slave.
+#[derive(Default)]
+struct SlaveStats {
+    reads: u64,
+    writes: u64,
+    /// The registers the datagrams addressing the slave start at.
+    registers: BTreeSet<u16>,
+    wkc_failures: u64,
+    mailbox_out: u64,
+    mailbox_in: u64,
+    first_ns: Option<u64>,
+    last_ns: u64,
+}
+
+impl SlaveStats {
+    fn seen(&mut self, ts_ns: u64) {
+        self.first_ns.get_or_insert(ts_ns);
+        self.last_ns = ts_ns;
+    }
+
+    fn to_row(&self, slave: SlaveRef, sii: &SiiReader) -> String {
+        let registers: Vec<String> = self.registers.iter().map(|register| Value::Address(*register).render(Style::Csv)).collect();
+        let hex = |value: Option<u32>| value.map(|value| format!("0x{:08x}", value)).unwrap_or_default();
+        let identity = sii.identity_of(slave);
+        format!("{},{},{},{},{},{},{},{},{},{},{},{},{}",
+            slave, self.reads, self.writes, registers.join(" "), self.wkc_failures, self.mailbox_out, self.mailbox_in,
+            Value::Time(self.first_ns.unwrap_or_default()).render(Style::Csv), Value::Time(self.last_ns).render(Style::Csv),
+            hex(identity.map(|identity| identity.vendor_id)), hex(identity.map(|identity| identity.product_code)),
+            hex(sii.revision(slave)), sii.serial_number(slave).map(|serial| serial.to_string()).unwrap_or_default())
+    }
+}
+
+/// Prints one row per slave the master addresses by station address or by position: the
+/// reads and writes it sends it (a read-write counts as both), the registers they start at,
+/// the returned datagrams whose working counter differs from the one expected by `wkc_model`,
+/// or is 0 without it, the mailbox messages written to and read from it, and the times it was
+/// first and last addressed, with the vendor ID, product code, revision and serial number the
+/// master read from its SII.
+pub fn run(file: File, options: &CaptureOptions, wkc_model: Option<&WkcModel>, labels: &Labels) {
+    use EtherCATCommand::*;
+
+    let mut configs = SlaveConfigs::default();
+    let mut sii = SiiReader::default();
+    let mut slaves: BTreeMap<SlaveRef, SlaveStats> = BTreeMap::new();
+    // the slave of every datagram on its way, which position addresses do not tell on the way back.
+    let mut pending: HashMap<Key, SlaveRef> = HashMap::new();
+
+    let summary = capture::read(file, options, |item| {
+        let Item::Packet(packet) = item else {
+            return ControlFlow::Continue(());
+        };
+        let Some(frame) = packet.data.and_then(EtherNetFrame::parse_ethercat) else {
+            return ControlFlow::Continue(());
+        };
+        let ts_ns = packet.info.ts_ns;
+        let returned = frame.is_returned();
+        sii.frame(&frame);
+        for datagram in &frame.ecat_frame.datagrams {
+            let header = &datagram.header;
+            configs.apply(datagram, returned);
+            if let Some(mailbox) = mailbox::extract(datagram, returned, &configs) {
+                let stats = slaves.entry(SlaveRef::Station(mailbox.slave)).or_default();
+                match mailbox.direction {
+                    Direction::ToSlave => stats.mailbox_out += 1,
+                    Direction::FromSlave => stats.mailbox_in += 1,
+                }
+            }
+            let key = Key::new(frame.master_mac(), header);
+            if returned {
+                let Some(slave) = pending.remove(&key) else {
+                    continue;
+                };
+                let stats = slaves.entry(slave).or_default();
+                stats.seen(ts_ns);
+                let expected = wkc_model.and_then(|model| model.expected(header));
+                if expected.map_or(datagram.wkc == 0, |expected| expected != datagram.wkc) {
+                    stats.wkc_failures += 1;
+                }
+                continue;
+            }
+            let Some(slave) = addressed_slave(header, returned) else {
+                continue;
+            };
+            pending.insert(key, slave);
+            let stats = slaves.entry(slave).or_default();
+            stats.seen(ts_ns);
+            stats.reads += matches!(header.cmd, APRD | FPRD | APRW | FPRW | ARMW | FRMW) as u64;
+            stats.writes += matches!(header.cmd, APWR | FPWR | APRW | FPRW) as u64;
+            stats.registers.insert(header.offset_addr);
+        }
+        ControlFlow::Continue(())
+    });
+    let summary = summary.unwrap_or_else(|e| {
+        eprintln!("{}", e);
+        exit(1);
+    });
+
+    outln!("{}{}", CSV_HEADER, labels.header());
+    for (&slave, stats) in &slaves {
+        outln!("{}{}", stats.to_row(slave, &sii), labels.cell(Some(slave)));
+    }
+    summary.report();
+}
@@ -0,0 +1,222 @@
+use crate::capture::{self, CaptureOptions, Item};
+use crate::clock::TimeSpec;
+use crate::labels::Labels;
+use crate::mailbox;
+use crate::pdo::{PdoEntry, PdoMappings};
+use crate::sink::outln;
+use crate::slave_config::{Fmmu, SlaveConfigs, SlaveRef};
+use crate::value::{Style, Value};
+use crate::{EtherCATCommand, EtherNetFrame};
+use std::collections::BTreeMap;
+use std::fs::File;
+use std::ops::ControlFlow;
+use std::process::exit;
+
+/// The logical process image as last seen on the bus, byte by byte.
+#[derive(Default)]
+struct ProcessImage {
+    /// Written by the master with LWR/LRW.
+    outputs: BTreeMap<u32, u8>,
+    /// Returned by the slaves with LRD/LRW.
+    inputs: BTreeMap<u32, u8>,
+}
+
+impl ProcessImage {
+    fn store(image: &mut BTreeMap<u32, u8>, start: u32, data: &[u8]) {
+        for (i, byte) in data.iter().enumerate() {
+            image.insert(start.wrapping_add(i as u32), *byte);
+        }
+    }
+
+    /// Bytes of a logical range, `None` for those never seen.
+    fn read(image: &BTreeMap<u32, u8>, start: u32, length: u16) -> Vec<Option<u8>> {
+        (0..length as u32).map(|i| image.get(&start.wrapping_add(i)).copied()).collect()
+    }
+
+    /// A little-endian bit field of the image, `None` if any of its bytes was never seen.
+    fn read_bits(image: &BTreeMap<u32, u8>, bit_address: u64, bit_length: u8) -> Option<u64> {
+        let mut value = 0;
+        for i in 0..bit_length.min(64) as u64 {
+            let bit = bit_address + i;
+            let byte = image.get(&((bit / 8) as u32))?;
+            value |= (((byte >> (bit % 8)) & 1) as u64) << i;
+        }
+        Some(value)
+    }
+}
+
+/// Logical bit address of a PDO entry, when an FMMU maps the process data holding it.
+fn logical_bit_address(entry: &PdoEntry, fmmu: &Fmmu, pdo_start: u16) -> Option<u64> {
+    fmmu.logical_bit(pdo_start as u64 * 8 + entry.bit_offset as u64, entry.bit_length as u64)
+}
+
+fn format_value(entry: &PdoEntry, value: u64) -> String {
+    let bits = entry.bit_length.clamp(1, 64) as u32;
+    let decoded = if entry.signed() && bits < 64 && value >> (bits - 1) & 1 != 0 {
+        (value as i64 - (1i64 << bits)).to_string()
+    } else if entry.signed() {
+        (value as i64).to_string()
+    } else {
+        value.to_string()
+    };
+    format!("0x{:0width$x},{}", value, decoded, width = bits.div_ceil(4) as usize)
+}
+
+/// Prints every mapped PDO entry of the slaves whose PDO mapping was seen being downloaded.
+fn print_variables(configs: &SlaveConfigs, mappings: &PdoMappings, image: &ProcessImage, labels: &Labels) {
+    let mut rows = Vec::new();
+    for slave in mappings.slaves() {
+        let slave_ref = SlaveRef::Station(slave);
+        let sync_managers = configs.sync_managers(slave_ref).unwrap_or_default();
+        for (outputs, direction, bytes) in [(true, "outputs", &image.outputs), (false, "inputs", &image.inputs)] {
+            let Some(entries) = mappings.entries(slave, outputs) else {
+                continue;
+            };
+            let fmmus: Vec<Fmmu> = configs
+                .fmmus()
+                .filter(|(fmmu_slave, fmmu)| *fmmu_slave == slave_ref && if outputs { fmmu.write } else { fmmu.read })
+                .map(|(_, fmmu)| fmmu)
+                .collect();
+            // the process data starts with the buffered SyncManager of its direction.
+            let pdo_start = sync_managers.iter().find(|sm| !sm.mailbox && sm.master_writes == outputs).map(|sm| sm.start);
+            for entry in entries.iter().filter(|entry| !entry.is_padding()) {
+                let address = fmmus
+                    .iter()
+                    .find_map(|fmmu| logical_bit_address(entry, fmmu, pdo_start.unwrap_or(fmmu.physical_start)));
+                let Some(address) = address else {
+                    continue;
+                };
+                let value = ProcessImage::read_bits(bytes, address, entry.bit_length)
+                    .map_or_else(|| "--,--".to_string(), |value| format_value(entry, value));
+                let slave = Value::Address(slave).render(Style::Csv);
+                rows.push(format!("slave_{}.{},{},{},0x{:04x}:{:02x},{},{},{},{}{}",
+                    slave, entry.name(), slave, direction, entry.index, entry.subindex, Value::LogicalAddress((address / 8) as u32).render(Style::Csv), address % 8,
+                    entry.bit_length, value, labels.cell(Some(slave_ref))));
+            }
+        }
+    }
+    if !rows.is_empty() {
+        outln!();
+        outln!("variable,slave,direction,entry,logical_address,bit,bit_length,value,decoded{}", labels.header());
+        for row in rows {
+            outln!("{}", row);
+        }
+    }
+}
+
+fn format_bytes(bytes: &[Option<u8>]) -> String {
+    let bytes: Vec<String> = bytes
+        .iter()
+        .map(|byte| byte.map_or_else(|| "--".to_string(), |byte| format!("{:02x}", byte)))
+        .collect();
+    bytes.join(" ")
+}
+
+/// Contiguous runs of the image, for captures without any FMMU configuration.
+fn segments(image: &BTreeMap<u32, u8>) -> Vec<(u32, Vec<u8>)> {
+    let mut segments: Vec<(u32, Vec<u8>)> = Vec::new();
+    for (&address, &byte) in image {
+        match segments.last_mut() {
+            Some((start, bytes)) if start.wrapping_add(bytes.len() as u32) == address => bytes.push(byte),
+            _ => segments.push((address, vec![byte])),
+        }
+    }
+    segments
+}
+
+/// Replays the capture up to `at` and prints the process image of every slave at that time.
+pub fn run(file: File, options: &CaptureOptions, at: &str, labels: &Labels) {
+    let at = TimeSpec::parse(at).unwrap_or_else(|e| {
+        eprintln!("Invalid --at : {}", e);
+        exit(1);
+    });
+
+    let mut image = ProcessImage::default();
+    let mut configs = SlaveConfigs::default();
+    let mut mappings = PdoMappings::default();
+    let mut at_ns = None;
+    let mut first_ts_ns = None;
+    let mut last_frame = 0;
+
+    let summary = capture::read(file, options, |item| {
+        let Item::Packet(packet) = item else {
+            return ControlFlow::Continue(());
+        };
+        let at_ns = *at_ns.get_or_insert_with(|| at.resolve(packet.info.ts_ns));
+        first_ts_ns.get_or_insert(packet.info.ts_ns);
+        if packet.info.ts_ns > at_ns {
+            return ControlFlow::Break(());
+        }
+        last_frame = packet.info.frame_number;
+
+        let Some(frame) = packet.data.and_then(EtherNetFrame::parse_ethercat) else {
+            return ControlFlow::Continue(());
+        };
+        let returned = frame.is_returned();
+        for datagram in &frame.ecat_frame.datagrams {
+            let header = &datagram.header;
+            let start = ((header.offset_addr as u32) << 16) | header.slave_addr as u32;
+            let payload = datagram.data;
+            match header.cmd {
+                EtherCATCommand::LWR => ProcessImage::store(&mut image.outputs, start, payload),
+                EtherCATCommand::LRD if returned => ProcessImage::store(&mut image.inputs, start, payload),
+                EtherCATCommand::LRW => {
+                    ProcessImage::store(&mut image.outputs, start, payload);
+                    if returned {
+                        ProcessImage::store(&mut image.inputs, start, payload);
+                    }
+                }
+                _ => {
+                    configs.apply(datagram, returned);
+                    if let Some(mailbox) = mailbox::extract(datagram, returned, &configs) {
+                        mappings.handle(&mailbox);
+                    }
+                }
+            }
+        }
+        ControlFlow::Continue(())
+    });
+
+    let summary = summary.unwrap_or_else(|e| {
+        eprintln!("{}", e);
+        exit(1);
+    });
+
+    let (Some(at_ns), Some(first_ts_ns)) = (at_ns, first_ts_ns) else {
+        eprintln!("No packets in the capture");
+        exit(1);
+    };
+    if at_ns < first_ts_ns {
+        eprintln!("--at is before the first packet of the capture");
+        exit(1);
+    }
+
+    outln!("Process image at {} (frame {}, {} after the first packet)",
+        Value::Time(at_ns).render(Style::Csv), last_frame, Value::Duration(at_ns - first_ts_ns).render(Style::Csv));
+
+    let mut fmmus = configs.fmmus().peekable();
+    if fmmus.peek().is_some() {
+        outln!("variable,slave,fmmu,direction,logical_start,length,physical_start,value{}", labels.header());
+        for (slave, fmmu) in fmmus {
+            // a read/write FMMU appears once per direction
+            let directions = [(fmmu.write, "outputs", &image.outputs), (fmmu.read, "inputs", &image.inputs)];
+            for (direction, bytes) in directions.into_iter().filter(|(mapped, ..)| *mapped).map(|(_, d, b)| (d, b)) {
+                outln!("slave_{}.fmmu{}.{},{},{},{},{},{},{},{}{}",
+                    slave, fmmu.index, direction, slave, fmmu.index, direction, Value::LogicalAddress(fmmu.logical_start).render(Style::Csv),
+                    fmmu.length, Value::Address(fmmu.physical_start).render(Style::Csv), format_bytes(&ProcessImage::read(bytes, fmmu.logical_start, fmmu.length)),
+                    labels.cell(Some(slave)));
+            }
+        }
+    } else {
+        outln!("No FMMU configuration before this point, raw logical image:");
+        for (direction, bytes) in [("outputs", &image.outputs), ("inputs", &image.inputs)] {
+            for (start, bytes) in segments(bytes) {
+                let bytes: Vec<Option<u8>> = bytes.into_iter().map(Some).collect();
+                outln!("{} {} ({} bytes): {}", direction, Value::LogicalAddress(start).render(Style::Csv), bytes.len(), format_bytes(&bytes));
+            }
+        }
+    }
+    print_variables(&configs, &mappings, &image, labels);
+
+    summary.report();
+}
@@ -0,0 +1,290 @@
+use crate::capture::{self, CaptureOptions, Item};
+use crate::clock::TimeSpec;
+use crate::labels::Labels;
+use crate::registers::{register_byte, AL_CONTROL_REGISTER, STATION_ADDRESS_REGISTER};
+use crate::report::AL_STATUS_REGISTER;
+use crate::sink::outln;
+use crate::slave_config::{Fmmu, SlaveConfigs, SlaveRef, SyncManager};
+use crate::value::{Style, Value};
+use crate::{EtherCATCommand, EtherNetFrame};
+use std::collections::BTreeMap;
+use std::fs::File;
+use std::ops::ControlFlow;
+use std::process::exit;
+
+/// What is known of one slave, addressed by its station address, at a point of the capture.
+#[derive(Clone, Default)]
+pub struct SlaveState {
+    /// Position in the chain the master assigned the station address to.
+    pub position: Option<u16>,
+    /// AL status last read from the slave.
+    pub al_status: Option<u16>,
+    /// The state the master last requested through AL control.
+    pub requested_state: Option<u8>,
+    /// Datagrams the slave answered, and the frame of the first one.
+    pub datagrams: u64,
+    pub first_frame: Option<u64>,
+    /// Working counter of the last returned datagram addressed to the slave.
+    pub last_wkc: Option<u16>,
+    /// Returned datagrams left unanswered (working counter 0) since the last answer.
+    pub unanswered: u64,
+    /// Capture time of the last answered datagram.
+    pub last_answer_ns: Option<u64>,
+}
+
+/// The state of every slave as the capture is processed: AL state, station address, SyncManager
+/// and FMMU configuration and working counters, updated frame by frame. The reports showing
+/// slaves take it from here, so that they agree about them.
+#[derive(Clone, Default)]
+pub struct SlaveStates {
+    slaves: BTreeMap<u16, SlaveState>,
+    configs: SlaveConfigs,
+}
+
+/// Name of an AL state, with the error flag of an AL status.
+pub fn al_state(status: u16) -> String {
+    let state = match status & 0x0F {
+        1 => "INIT",
+        2 => "PREOP",
+        3 => "BOOT",
+        4 => "SAFEOP",
+        8 => "OP",
+        _ => "unknown",
+    };
+    if status & 0x10 != 0 {
+        format!("{} (error)", state)
+    } else {
+        state.to_string()
+    }
+}
+
+/// The meaning of an AL status code, as listed by ETG.1000.6.
+pub fn al_status_message(code: u16) -> Option<&'static str> {
+    let message = match code {
+        0x0000 => "No error",
+        0x0001 => "Unspecified error",
+        0x0002 => "No memory",
+        0x0004 => "Invalid device setup",
+        0x0011 => "Invalid requested state change",
+        0x0012 => "Unknown requested state",
+        0x0013 => "Bootstrap not supported",
+        0x0014 => "No valid firmware",
+        0x0015 => "Invalid mailbox configuration (BOOT)",
+        0x0016 => "Invalid mailbox configuration (PREOP)",
+        0x0017 => "Invalid sync manager configuration",
+        0x0018 => "No valid inputs available",
+        0x0019 => "No valid outputs",
+        0x001A => "Synchronization error",
+        0x001B => "Sync manager watchdog",
+        0x001C => "Invalid sync manager types",
+        0x001D => "Invalid output configuration",
+        0x001E => "Invalid input configuration",
+        0x001F => "Invalid watchdog configuration",
+        0x0020 => "Slave needs cold start",
+        0x0021 => "Slave needs INIT",
+        0x0022 => "Slave needs PREOP",
+        0x0023 => "Slave needs SAFEOP",
+        0x0024 => "Invalid input mapping",
+        0x0025 => "Invalid output mapping",
+        0x0026 => "Inconsistent settings",
+        0x0027 => "Freerun not supported",
+        0x0028 => "Synchronization not supported",
+        0x0029 => "Freerun needs 3 buffer mode",
+        0x002A => "Background watchdog",
+        0x002B => "No valid inputs and outputs",
+        0x002C => "Fatal sync error",
+        0x002D => "No sync error",
+        0x0030 => "Invalid DC SYNC configuration",
+        0x0031 => "Invalid DC latch configuration",
+        0x0032 => "PLL error",
+        0x0033 => "DC sync IO error",
+        0x0034 => "DC sync timeout error",
+        0x0035 => "DC invalid sync cycle time",
+        0x0036 => "DC SYNC0 cycle time",
+        0x0037 => "DC SYNC1 cycle time",
+        0x0041 => "Mailbox AoE",
+        0x0042 => "Mailbox EoE",
+        0x0043 => "Mailbox CoE",
+        0x0044 => "Mailbox FoE",
+        0x0045 => "Mailbox SoE",
+        0x004F => "Mailbox VoE",
+        0x0050 => "EEPROM no access",
+        0x0051 => "EEPROM error",
+        0x0060 => "Slave restarted locally",
+        0x0061 => "Device identification value updated",
+        0x00F0 => "Application controller available",
+        _ => return None,
+    };
+    Some(message)
+}
+
+impl SlaveStates {
+    /// Takes the next EtherCAT frame.
+    pub fn frame(&mut self, ts_ns: u64, frame_number: u64, frame: &EtherNetFrame) {
+        use EtherCATCommand::*;
+
+        let returned = frame.is_returned();
+        for datagram in &frame.ecat_frame.datagrams {
+            self.configs.apply(datagram, returned);
+            let header = &datagram.header;
+            if !returned {
+                // the returned copy of a position addressed datagram no longer carries the position.
+                if matches!(header.cmd, APWR | APRW) && header.offset_addr == STATION_ADDRESS_REGISTER && header.length >= 2 {
+                    if let Some(address) = datagram.data.get(..2) {
+                        let position = header.slave_addr.wrapping_neg();
+                        self.slaves.entry(u16::from_le_bytes([address[0], address[1]])).or_default().position = Some(position);
+                    }
+                }
+                let Some(state) = register_byte(datagram, AL_CONTROL_REGISTER) else {
+                    continue;
+                };
+                match header.cmd {
+                    FPWR | FPRW => self.slaves.entry(header.slave_addr).or_default().requested_state = Some(state & 0x0F),
+                    BWR | BRW => self.slaves.values_mut().for_each(|slave| slave.requested_state = Some(state & 0x0F)),
+                    _ => {}
+                }
+                continue;
+            }
+            if !matches!(header.cmd, FPRD | FPWR | FPRW | FRMW) {
+                continue;
+            }
+            let slave = self.slaves.entry(header.slave_addr).or_default();
+            slave.last_wkc = Some(datagram.wkc);
+            if datagram.wkc == 0 {
+                slave.unanswered += 1;
+                continue;
+            }
+            slave.unanswered = 0;
+            slave.datagrams += 1;
+            slave.first_frame.get_or_insert(frame_number);
+            slave.last_answer_ns = Some(ts_ns);
+            if header.cmd == FPRD && header.offset_addr == AL_STATUS_REGISTER && header.length >= 2 {
+                if let Some(status) = datagram.data.get(..2) {
+                    slave.al_status = Some(u16::from_le_bytes([status[0], status[1]]));
+                }
+            }
+        }
+    }
+
+    /// Every slave seen so far, by station address.
+    pub fn slaves(&self) -> impl Iterator<Item = (u16, &SlaveState)> + '_ {
+        self.slaves.iter().map(|(address, slave)| (*address, slave))
+    }
+
+    /// The configured SyncManagers of a slave, written through its station address or, before
+    /// that, its position: those written through the station address replace those of the same
+    /// number written through the position.
+    pub fn sync_managers(&self, address: u16) -> Option<Vec<SyncManager>> {
+        let position = self.slaves.get(&address).and_then(|slave| slave.position);
+        let by_station = self.configs.sync_managers(SlaveRef::Station(address));
+        let by_position = position.and_then(|position| self.configs.sync_managers(SlaveRef::Position(position)));
+        let Some(by_position) = by_position else {
+            return by_station;
+        };
+        let mut sync_managers = by_station.unwrap_or_default();
+        let written: Vec<usize> = sync_managers.iter().map(|sm| sm.index).collect();
+        sync_managers.extend(by_position.into_iter().filter(|sm| !written.contains(&sm.index)));
+        sync_managers.sort_by_key(|sm| sm.index);
+        Some(sync_managers)
+    }
+
+    /// The configured FMMUs of a slave, written through its station address or its position.
+    pub fn fmmus(&self, address: u16) -> Vec<Fmmu> {
+        let position = self.slaves.get(&address).and_then(|slave| slave.position);
+        self.configs
+            .fmmus()
+            .filter(|(slave, _)| *slave == SlaveRef::Station(address) || Some(*slave) == position.map(SlaveRef::Position))
+            .map(|(_, fmmu)| fmmu)
+            .collect()
+    }
+}
+
+fn format_sync_managers(sync_managers: &[SyncManager]) -> String {
+    let sync_managers: Vec<String> = sync_managers.iter().map(|sm| {
+        let kind = match (sm.mailbox, sm.master_writes) {
+            (true, true) => "mailbox_out",
+            (true, false) => "mailbox_in",
+            (false, true) => "outputs",
+            (false, false) => "inputs",
+        };
+        format!("{}/{} {}", Value::Address(sm.start).render(Style::Csv), sm.length, kind)
+    }).collect();
+    sync_managers.join(";")
+}
+
+fn format_fmmus(fmmus: &[Fmmu]) -> String {
+    let fmmus: Vec<String> = fmmus.iter().map(|fmmu| {
+        let direction = match (fmmu.read, fmmu.write) {
+            (true, true) => "inputs+outputs",
+            (true, false) => "inputs",
+            _ => "outputs",
+        };
+        format!("{}/{} at {} {}", Value::LogicalAddress(fmmu.logical_start).render(Style::Csv), fmmu.length,
+            Value::Address(fmmu.physical_start).render(Style::Csv), direction)
+    }).collect();
+    fmmus.join(";")
+}
+
+/// Replays the capture up to `at` and prints the state of every slave at that time.
+pub fn run(file: File, options: &CaptureOptions, at: &str, labels: &Labels) {
+    let at = TimeSpec::parse(at).unwrap_or_else(|e| {
+        eprintln!("Invalid --at : {}", e);
+        exit(1);
+    });
+
+    let mut states = SlaveStates::default();
+    let mut at_ns = None;
+    let mut first_ts_ns = None;
+    let mut last_frame = 0;
+
+    let summary = capture::read(file, options, |item| {
+        let Item::Packet(packet) = item else {
+            return ControlFlow::Continue(());
+        };
+        let at_ns = *at_ns.get_or_insert_with(|| at.resolve(packet.info.ts_ns));
+        first_ts_ns.get_or_insert(packet.info.ts_ns);
+        if packet.info.ts_ns > at_ns {
+            return ControlFlow::Break(());
+        }
+        last_frame = packet.info.frame_number;
+        if let Some(frame) = packet.data.and_then(EtherNetFrame::parse_ethercat) {
+            states.frame(packet.info.ts_ns, packet.info.frame_number, &frame);
+        }
+        ControlFlow::Continue(())
+    });
+    let summary = summary.unwrap_or_else(|e| {
+        eprintln!("{}", e);
+        exit(1);
+    });
+
+    let (Some(at_ns), Some(first_ts_ns)) = (at_ns, first_ts_ns) else {
+        eprintln!("No packets in the capture");
+        exit(1);
+    };
+    if at_ns < first_ts_ns {
+        eprintln!("--at is before the first packet of the capture");
+        exit(1);
+    }
+
+    outln!("Slave state at {} (frame {}, {} after the first packet)",
+        Value::Time(at_ns).render(Style::Csv), last_frame, Value::Duration(at_ns - first_ts_ns).render(Style::Csv));
+    outln!("slave,position,al_state,requested_state,datagrams,first_frame,last_wkc,unanswered,last_answer,sync_managers,fmmus{}",
+        labels.header());
+    for (address, slave) in states.slaves() {
+        let optional = |value: Option<String>| value.unwrap_or_default();
+        outln!("{},{},{},{},{},{},{},{},{},{},{}{}",
+            Value::Address(address).render(Style::Csv),
+            optional(slave.position.map(|position| position.to_string())),
+            optional(slave.al_status.map(al_state)),
+            optional(slave.requested_state.map(|state| al_state(state as u16))),
+            slave.datagrams,
+            optional(slave.first_frame.map(|frame| frame.to_string())),
+            optional(slave.last_wkc.map(|wkc| wkc.to_string())),
+            slave.unanswered,
+            optional(slave.last_answer_ns.map(|ts| Value::Time(ts).render(Style::Csv))),
+            format_sync_managers(&states.sync_managers(address).unwrap_or_default()),
+            format_fmmus(&states.fmmus(address)),
+            labels.cell(Some(SlaveRef::Station(address))));
+    }
+    summary.report();
+}
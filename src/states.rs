@@ -0,0 +1,166 @@
+use crate::annotations::quote;
+use crate::capture::{self, CaptureOptions, Item};
+use crate::labels::Labels;
+use crate::registers::{register_word, AL_CONTROL_REGISTER};
+use crate::report::AL_STATUS_REGISTER;
+use crate::sink::outln;
+use crate::slave_config::SlaveRef;
+use crate::state::{al_state, al_status_message};
+use crate::value::{Style, Value};
+use crate::{EtherCATCommand, EtherNetFrame};
+use std::collections::BTreeMap;
+use std::fs::File;
+use std::ops::ControlFlow;
+use std::process::exit;
+
+/// AL status code register, telling why a slave refused a state or fell out of one.
+const AL_STATUS_CODE_REGISTER: u16 = 0x0134;
+/// Error flag of the AL status, and error acknowledge bit of AL control.
+const AL_ERROR: u16 = 0x10;
+
+const CSV_HEADER: &str = "time,frame,slave,event,from,to,al_status_code,al_status_message,elapsed";
+
+/// Name of a state requested through AL control, with the error acknowledge bit.
+fn requested(control: u16) -> String {
+    let state = al_state(control & 0x0F);
+    if control & AL_ERROR != 0 {
+        format!("{} (error ack)", state)
+    } else {
+        state
+    }
+}
+
+/// What is known of the AL state machine of one slave.
+#[derive(Clone, Copy, Default)]
+struct Slave {
+    /// The last AL control written, and its capture time.
+    request: Option<(u16, u64)>,
+    /// The last AL status read, with its AL status code while the error flag is set.
+    status: Option<(u16, Option<u16>)>,
+}
+
+/// One row of the timeline.
+struct Transition {
+    ts_ns: u64,
+    frame_number: u64,
+    /// None for a request broadcast to every slave.
+    slave: Option<u16>,
+    event: &'static str,
+    from: Option<String>,
+    to: String,
+    code: Option<u16>,
+    /// Time since the request of the state the slave has now reached.
+    elapsed_ns: Option<u64>,
+}
+
+impl Transition {
+    fn to_row(&self, labels: &Labels) -> String {
+        let slave = self.slave.map_or("all".to_string(), |slave| Value::Address(slave).render(Style::Csv));
+        let message = self.code.and_then(al_status_message).map(quote).unwrap_or_default();
+        format!("{},{},{},{},{},{},{},{},{}{}",
+            Value::Time(self.ts_ns).render(Style::Csv), self.frame_number, slave, self.event,
+            self.from.as_deref().unwrap_or_default(), self.to, self.code.map(|code| format!("0x{:04x}", code)).unwrap_or_default(),
+            message, self.elapsed_ns.map(|ns| Value::Duration(ns).render(Style::Csv)).unwrap_or_default(),
+            labels.cell(self.slave.map(SlaveRef::Station)))
+    }
+}
+
+/// Follows the AL state machine of every slave addressed by its station address: the states
+/// the master requests through AL control, and the states, error flags and AL status codes the
+/// slaves report in their AL status.
+#[derive(Default)]
+struct Timeline {
+    slaves: BTreeMap<u16, Slave>,
+    /// The last AL control broadcast, which also applies to the slaves not seen yet.
+    broadcast: Option<(u16, u64)>,
+}
+
+impl Timeline {
+    fn slave(&mut self, address: u16) -> &mut Slave {
+        let broadcast = self.broadcast;
+        self.slaves.entry(address).or_insert_with(|| Slave { request: broadcast, status: None })
+    }
+
+    /// Takes the next EtherCAT frame, returning the transitions it shows.
+    fn frame(&mut self, ts_ns: u64, frame_number: u64, frame: &EtherNetFrame) -> Vec<Transition> {
+        use EtherCATCommand::*;
+
+        let returned = frame.is_returned();
+        let mut transitions = Vec::new();
+        for datagram in &frame.ecat_frame.datagrams {
+            let header = &datagram.header;
+            let transition = |slave, event, from, to, code, elapsed_ns| Transition {
+                ts_ns, frame_number, slave, event, from, to, code, elapsed_ns,
+            };
+            if !returned {
+                let Some(control) = register_word(datagram, AL_CONTROL_REGISTER) else {
+                    continue;
+                };
+                match header.cmd {
+                    FPWR | FPRW => {
+                        let slave = self.slave(header.slave_addr);
+                        let from = slave.request.map(|(control, _)| requested(control));
+                        slave.request = Some((control, ts_ns));
+                        transitions.push(transition(Some(header.slave_addr), "request", from, requested(control), None, None));
+                    }
+                    BWR | BRW => {
+                        self.broadcast = Some((control, ts_ns));
+                        self.slaves.values_mut().for_each(|slave| slave.request = Some((control, ts_ns)));
+                        transitions.push(transition(None, "request", None, requested(control), None, None));
+                    }
+                    _ => {}
+                }
+                continue;
+            }
+            if header.cmd != FPRD || datagram.wkc == 0 {
+                continue;
+            }
+            let Some(status) = register_word(datagram, AL_STATUS_REGISTER) else {
+                continue;
+            };
+            let slave = self.slave(header.slave_addr);
+            let previous_code = slave.status.and_then(|(_, code)| code);
+            // a slave in error keeps its AL status code until acknowledged, so a read of the
+            // status alone leaves the code of an earlier read in place.
+            let code = (status & AL_ERROR != 0)
+                .then(|| register_word(datagram, AL_STATUS_CODE_REGISTER).or(previous_code))
+                .flatten();
+            if slave.status == Some((status, code)) {
+                continue;
+            }
+            let from = slave.status.map(|(status, _)| al_state(status));
+            slave.status = Some((status, code));
+            let elapsed_ns = slave.request
+                .filter(|(control, _)| status & AL_ERROR == 0 && control & 0x0F == status & 0x0F)
+                .map(|(_, requested_ns)| ts_ns - requested_ns);
+            transitions.push(transition(Some(header.slave_addr), "status", from, al_state(status), code, elapsed_ns));
+        }
+        transitions
+    }
+}
+
+/// Prints the AL state timeline of the capture: every AL control write of the master and every
+/// change of the AL status of a slave, with the AL status code of the slaves in error and, for
+/// the states reached as requested, the time the slave took since the request.
+pub fn run(file: File, options: &CaptureOptions, labels: &Labels) {
+    let mut timeline = Timeline::default();
+
+    outln!("{}{}", CSV_HEADER, labels.header());
+    let summary = capture::read(file, options, |item| {
+        let Item::Packet(packet) = item else {
+            return ControlFlow::Continue(());
+        };
+        let Some(frame) = packet.data.and_then(EtherNetFrame::parse_ethercat) else {
+            return ControlFlow::Continue(());
+        };
+        for transition in timeline.frame(packet.info.ts_ns, packet.info.frame_number, &frame) {
+            outln!("{}", transition.to_row(labels));
+        }
+        ControlFlow::Continue(())
+    });
+    let summary = summary.unwrap_or_else(|e| {
+        eprintln!("{}", e);
+        exit(1);
+    });
+    summary.report();
+}
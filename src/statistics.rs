@@ -0,0 +1,56 @@
+/// Summary statistics of a set of samples, such as the intervals between cycles.
+pub struct Distribution {
+    /// The samples, in ascending order.
+    sorted: Vec<u64>,
+}
+
+impl Distribution {
+    pub fn new(mut samples: Vec<u64>) -> Self {
+        samples.sort_unstable();
+        Distribution { sorted: samples }
+    }
+
+    /// The samples, in ascending order.
+    pub fn samples(&self) -> &[u64] {
+        &self.sorted
+    }
+
+    pub fn len(&self) -> usize {
+        self.sorted.len()
+    }
+
+    pub fn min(&self) -> Option<u64> {
+        self.sorted.first().copied()
+    }
+
+    pub fn max(&self) -> Option<u64> {
+        self.sorted.last().copied()
+    }
+
+    pub fn mean(&self) -> Option<f64> {
+        mean(&self.sorted)
+    }
+
+    /// Population standard deviation.
+    pub fn stddev(&self) -> Option<f64> {
+        stddev(&self.sorted)
+    }
+
+    /// The nearest-rank percentile: the smallest sample at least `percent` of the samples are
+    /// lower than or equal to.
+    pub fn percentile(&self, percent: f64) -> Option<u64> {
+        let rank = (percent / 100.0 * self.sorted.len() as f64).ceil() as usize;
+        self.sorted.get(rank.clamp(1, self.sorted.len().max(1)) - 1).copied()
+    }
+}
+
+pub fn mean(samples: &[u64]) -> Option<f64> {
+    (!samples.is_empty()).then(|| samples.iter().sum::<u64>() as f64 / samples.len() as f64)
+}
+
+/// Population standard deviation.
+pub fn stddev(samples: &[u64]) -> Option<f64> {
+    let mean = mean(samples)?;
+    let variance = samples.iter().map(|sample| (*sample as f64 - mean).powi(2)).sum::<f64>() / samples.len() as f64;
+    Some(variance.sqrt())
+}
@@ -0,0 +1,68 @@
+use crate::capture::{self, CaptureOptions, Item};
+use crate::mac::{self, MacFormat};
+use crate::sink::outln;
+use crate::statistics::Distribution;
+use crate::{EtherCATCommand, EtherNetFrame};
+use std::collections::BTreeMap;
+use std::fs::File;
+use std::ops::ControlFlow;
+use std::process::exit;
+
+const CSV_HEADER: &str = "master,frames,cycles,min_ns,avg_ns,max_ns,stddev_ns,jitter_p99_ns,jitter_p99_9_ns";
+
+/// Cyclic frames sent by one master, and the intervals between them.
+#[derive(Default)]
+struct Master {
+    frames: u64,
+    last_ns: Option<u64>,
+    cycles: Vec<u64>,
+}
+
+fn cell<T: ToString>(value: Option<T>) -> String {
+    value.map(|value| value.to_string()).unwrap_or_default()
+}
+
+/// Prints the cycle time and jitter of the cyclic traffic of each master: the frames carrying
+/// logical process data it sends, by source address.
+pub fn run(file: File, options: &CaptureOptions, mac_format: MacFormat) {
+    use EtherCATCommand::*;
+
+    let mut masters: BTreeMap<[u8; 6], Master> = BTreeMap::new();
+    let summary = capture::read(file, options, |item| {
+        let Item::Packet(packet) = item else {
+            return ControlFlow::Continue(());
+        };
+        let Some(frame) = packet.data.and_then(EtherNetFrame::parse_ethercat) else {
+            return ControlFlow::Continue(());
+        };
+        let cyclic = frame.ecat_frame.datagrams.iter().any(|datagram| matches!(datagram.header.cmd, LRD | LWR | LRW));
+        if frame.is_returned() || !cyclic {
+            return ControlFlow::Continue(());
+        }
+        let master = masters.entry(frame.src_mac).or_default();
+        master.frames += 1;
+        if let Some(last) = master.last_ns.replace(packet.info.ts_ns) {
+            master.cycles.push(packet.info.ts_ns.saturating_sub(last));
+        }
+        ControlFlow::Continue(())
+    });
+    let summary = summary.unwrap_or_else(|e| {
+        eprintln!("{}", e);
+        exit(1);
+    });
+
+    outln!("{}", CSV_HEADER);
+    for (address, master) in masters {
+        let cycles = Distribution::new(master.cycles);
+        // the jitter of a cycle is its distance to the mean cycle time.
+        let jitter = cycles.mean().map(|mean| {
+            Distribution::new(cycles.samples().iter().map(|cycle| (*cycle as f64 - mean).abs().round() as u64).collect())
+        });
+        let jitter_percentile = |percent| jitter.as_ref().and_then(|jitter| jitter.percentile(percent));
+        outln!("{},{},{},{},{},{},{},{},{}",
+            mac::format(&address, mac_format), master.frames, cycles.len(),
+            cell(cycles.min()), cell(cycles.mean().map(f64::round)), cell(cycles.max()), cell(cycles.stddev().map(f64::round)),
+            cell(jitter_percentile(99.0)), cell(jitter_percentile(99.9)));
+    }
+    summary.report();
+}
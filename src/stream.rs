@@ -0,0 +1,251 @@
+//! Asynchronous reading of the datagrams of a capture, for async capture daemons and servers.
+//!
+//! [`AsyncRead`] and [`Stream`] have the signatures of the traits of the `futures` crate, so
+//! that an adapter of a few lines connects [`EcatStream`] to any runtime. The stream hands the
+//! blocks it reads to the same decoder as [`EcatPcapReader`](crate::EcatPcapReader).
+//!
+//! Neither `futures` nor `tokio` is a dependency, so the caller wraps their reader in a type
+//! implementing [`AsyncRead`] and the stream in one implementing the `Stream` of `futures`,
+//! which then takes the combinators of `StreamExt`:
+//!
+//! ```ignore
+//! use ecat_packet_analyzer_rs::stream::{self, EcatStream};
+//! use ecat_packet_analyzer_rs::{DatagramRecord, ReadError};
+//! use std::pin::Pin;
+//! use std::task::{Context, Poll};
+//!
+//! struct Input<R>(R);
+//!
+//! impl<R: futures::io::AsyncRead + Unpin> stream::AsyncRead for Input<R> {
+//!     fn poll_read(mut self: Pin<&mut Self>, cx: &mut Context<'_>, buf: &mut [u8]) -> Poll<std::io::Result<usize>> {
+//!         Pin::new(&mut self.0).poll_read(cx, buf)
+//!     }
+//! }
+//!
+//! struct Records<R>(EcatStream<R>);
+//!
+//! impl<R: stream::AsyncRead + Unpin> futures::Stream for Records<R> {
+//!     type Item = Result<DatagramRecord, ReadError>;
+//!
+//!     fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+//!         stream::Stream::poll_next(Pin::new(&mut self.0), cx)
+//!     }
+//! }
+//!
+//! let mut records = Records(EcatStream::new(Input(reader)));
+//! while let Some(record) = futures::StreamExt::next(&mut records).await { /* ... */ }
+//! ```
+//!
+//! A tokio reader fills a `ReadBuf` instead:
+//!
+//! ```ignore
+//! impl<R: tokio::io::AsyncRead + Unpin> stream::AsyncRead for Input<R> {
+//!     fn poll_read(mut self: Pin<&mut Self>, cx: &mut Context<'_>, buf: &mut [u8]) -> Poll<std::io::Result<usize>> {
+//!         let mut buf = tokio::io::ReadBuf::new(buf);
+//!         std::task::ready!(Pin::new(&mut self.0).poll_read(cx, &mut buf))?;
+//!         Poll::Ready(Ok(buf.filled().len()))
+//!     }
+//! }
+//! ```
+
+use crate::reader::{block_len, too_long, DatagramRecord, Decoder, ReadError, BIG_ENDIAN_MAGIC, MAX_BLOCK_LEN, MIN_BLOCK_LEN, PCAP_HEADER_LEN, PCAP_MAGICS, READER_BUFFER_SIZE, SECTION_HEADER_TYPE};
+use pcap_parser::pcapng::{parse_block_be, parse_block_le, parse_sectionheaderblock};
+use pcap_parser::{parse_pcap_frame, parse_pcap_frame_be, parse_pcap_header, PcapBlockOwned};
+use std::future::Future;
+use std::io;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+
+/// A source of bytes that may not have them yet, as `futures::io::AsyncRead`.
+pub trait AsyncRead {
+    /// Reads into `buf`, returning 0 at the end of the input, or `Poll::Pending` after
+    /// arranging for the task of `cx` to be woken once there is more to read.
+    fn poll_read(self: Pin<&mut Self>, cx: &mut Context<'_>, buf: &mut [u8]) -> Poll<io::Result<usize>>;
+}
+
+impl AsyncRead for &[u8] {
+    fn poll_read(mut self: Pin<&mut Self>, _cx: &mut Context<'_>, buf: &mut [u8]) -> Poll<io::Result<usize>> {
+        let len = buf.len().min(self.len());
+        buf[..len].copy_from_slice(&self[..len]);
+        *self = &self[len..];
+        Poll::Ready(Ok(len))
+    }
+}
+
+impl<R: AsyncRead + Unpin + ?Sized> AsyncRead for &mut R {
+    fn poll_read(mut self: Pin<&mut Self>, cx: &mut Context<'_>, buf: &mut [u8]) -> Poll<io::Result<usize>> {
+        Pin::new(&mut **self).poll_read(cx, buf)
+    }
+}
+
+impl<R: AsyncRead + Unpin + ?Sized> AsyncRead for Box<R> {
+    fn poll_read(mut self: Pin<&mut Self>, cx: &mut Context<'_>, buf: &mut [u8]) -> Poll<io::Result<usize>> {
+        Pin::new(&mut **self).poll_read(cx, buf)
+    }
+}
+
+/// A series of values produced asynchronously, as `futures::Stream`.
+pub trait Stream {
+    type Item;
+
+    /// The next value, `None` once there are no more, or `Poll::Pending` until the task of
+    /// `cx` is woken.
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>>;
+}
+
+#[derive(Clone, Copy)]
+enum Format {
+    Pcap,
+    Pcapng,
+}
+
+/// Streams the datagrams of a pcap or pcapng capture read from an [`AsyncRead`], with the
+/// records and errors of [`EcatPcapReader`](crate::EcatPcapReader). Only the block being
+/// decoded is kept in memory.
+pub struct EcatStream<R> {
+    input: R,
+    /// What the input was last read into, before it joins the bytes not yet decoded.
+    chunk: Box<[u8]>,
+    buffer: Vec<u8>,
+    /// Bytes of `buffer` already decoded.
+    start: usize,
+    /// `None` until the first bytes tell pcap from pcapng.
+    format: Option<Format>,
+    /// Whether the input has ended, and whether the stream has.
+    eof: bool,
+    done: bool,
+    decoder: Decoder,
+}
+
+impl<R: AsyncRead + Unpin> EcatStream<R> {
+    pub fn new(input: R) -> Self {
+        EcatStream { input, chunk: vec![0; READER_BUFFER_SIZE].into(), buffer: Vec::new(), start: 0, format: None, eof: false, done: false, decoder: Decoder::default() }
+    }
+
+    /// The next datagram: `while let Some(record) = stream.next_record().await`.
+    pub fn next_record(&mut self) -> Next<'_, R> {
+        Next { stream: self }
+    }
+
+    /// Decodes the block at the start of the unread bytes, returning whether it has been read
+    /// in full. A block is left behind even if a frame of it does not parse.
+    fn decode_block(&mut self) -> Result<bool, ReadError> {
+        let data = &self.buffer[self.start..];
+        let corrupt = |what: &str| ReadError::Capture(format!("corrupt {}", what));
+        let format = match self.format {
+            Some(format) => format,
+            None if data.len() < 4 => return Ok(false),
+            None => {
+                let magic = u32::from_be_bytes(data[..4].try_into().expect("four bytes"));
+                let format = if PCAP_MAGICS.contains(&magic) {
+                    Format::Pcap
+                } else if data.starts_with(&SECTION_HEADER_TYPE) {
+                    Format::Pcapng
+                } else {
+                    return Err(ReadError::Capture("not a pcap or pcapng capture".to_string()));
+                };
+                self.format = Some(format);
+                format
+            }
+        };
+        let len = match format {
+            Format::Pcap if !self.decoder.legacy => PCAP_HEADER_LEN,
+            Format::Pcap => match block_len(data, true, self.decoder.big_endian) {
+                Some(len) => len,
+                None => return Ok(false),
+            },
+            // the byte order of a section is that of its header.
+            Format::Pcapng if data.starts_with(&SECTION_HEADER_TYPE) => match data.get(4..12) {
+                Some(header) if header[4..] == BIG_ENDIAN_MAGIC => u32::from_be_bytes(header[..4].try_into().expect("four bytes")) as usize,
+                Some(header) => u32::from_le_bytes(header[..4].try_into().expect("four bytes")) as usize,
+                None => return Ok(false),
+            },
+            Format::Pcapng => match block_len(data, false, self.decoder.big_endian) {
+                Some(len) => len,
+                None => return Ok(false),
+            },
+        };
+        if len > MAX_BLOCK_LEN {
+            return Err(ReadError::Capture(too_long(len)));
+        }
+        if matches!(format, Format::Pcapng) && len < MIN_BLOCK_LEN {
+            return Err(corrupt("block length"));
+        }
+        let Some(block) = data.get(..len) else {
+            return Ok(false);
+        };
+        let block: PcapBlockOwned = match format {
+            Format::Pcap if !self.decoder.legacy => parse_pcap_header(block).map_err(|_| corrupt("pcap header"))?.1.into(),
+            Format::Pcap if self.decoder.big_endian => parse_pcap_frame_be(block).map_err(|_| corrupt("packet record"))?.1.into(),
+            Format::Pcap => parse_pcap_frame(block).map_err(|_| corrupt("packet record"))?.1.into(),
+            Format::Pcapng if block.starts_with(&SECTION_HEADER_TYPE) => {
+                let (_, shb) = parse_sectionheaderblock(block).map_err(|_| corrupt("section header block"))?;
+                PcapBlockOwned::NG(pcap_parser::Block::SectionHeader(shb))
+            }
+            Format::Pcapng if self.decoder.big_endian => parse_block_be(block).map_err(|_| corrupt("block"))?.1.into(),
+            Format::Pcapng => parse_block_le(block).map_err(|_| corrupt("block"))?.1.into(),
+        };
+        self.start += len;
+        self.decoder.block(block)?;
+        Ok(true)
+    }
+}
+
+impl<R: AsyncRead + Unpin> Stream for EcatStream<R> {
+    type Item = Result<DatagramRecord, ReadError>;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let stream = self.get_mut();
+        loop {
+            if let Some(record) = stream.decoder.pending.pop_front() {
+                return Poll::Ready(Some(Ok(record)));
+            }
+            if stream.done {
+                return Poll::Ready(None);
+            }
+            match stream.decode_block() {
+                Ok(true) => continue,
+                Ok(false) => {}
+                Err(error) => {
+                    stream.done = !matches!(error, ReadError::Frame { .. });
+                    return Poll::Ready(Some(Err(error)));
+                }
+            }
+            if stream.eof {
+                stream.done = true;
+                if stream.start < stream.buffer.len() {
+                    return Poll::Ready(Some(Err(ReadError::Capture("the capture ends inside a block".to_string()))));
+                }
+                continue;
+            }
+            let read = match Pin::new(&mut stream.input).poll_read(cx, &mut stream.chunk) {
+                Poll::Ready(Ok(read)) => read,
+                Poll::Ready(Err(e)) => {
+                    stream.done = true;
+                    return Poll::Ready(Some(Err(ReadError::Capture(e.to_string()))));
+                }
+                Poll::Pending => return Poll::Pending,
+            };
+            // the decoded blocks make room once they take most of the buffer.
+            if stream.start > stream.buffer.len() / 2 {
+                stream.buffer.drain(..stream.start);
+                stream.start = 0;
+            }
+            stream.buffer.extend_from_slice(&stream.chunk[..read]);
+            stream.eof = read == 0;
+        }
+    }
+}
+
+/// The future of [`EcatStream::next_record`].
+pub struct Next<'s, R> {
+    stream: &'s mut EcatStream<R>,
+}
+
+impl<R: AsyncRead + Unpin> Future for Next<'_, R> {
+    type Output = Option<Result<DatagramRecord, ReadError>>;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        Pin::new(&mut *self.stream).poll_next(cx)
+    }
+}
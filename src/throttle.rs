@@ -0,0 +1,104 @@
+use crate::checkpoint;
+use crate::clock::parse_duration;
+use crate::EtherNetFrame;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+/// At most `packets` packets in every `period_ns` of capture time, given as `100/s`,
+/// `10/100ms` or `5/2s`.
+#[derive(Clone, Copy, Debug)]
+pub struct RateLimit {
+    packets: u64,
+    period_ns: u64,
+}
+
+impl RateLimit {
+    pub fn parse(s: &str) -> Result<Self, String> {
+        let (packets, period) = s.split_once('/').ok_or_else(|| format!("expected packets/period, as in 100/s, not '{}'", s))?;
+        let packets = packets.trim().parse().map_err(|_| format!("invalid packet count '{}'", packets))?;
+        // a bare unit is one of it.
+        let period = period.trim();
+        let period_ns = if period.starts_with(|c: char| c.is_ascii_digit() || c == '.') {
+            parse_duration(period)?
+        } else {
+            parse_duration(&format!("1{}", period))?
+        };
+        if period_ns == 0 {
+            return Err(format!("the period of '{}' is empty", s));
+        }
+        Ok(RateLimit { packets, period_ns })
+    }
+}
+
+/// Thins out the exported packets for a human watching the output: packets beyond the rate
+/// limit and, with `only_changes`, frames repeating the previous frame of the same layout are
+/// left out. Decided packet by packet in capture order, in front of the export.
+#[derive(Clone, Default, Serialize, Deserialize)]
+pub struct Throttle {
+    #[serde(skip)]
+    rate_limit: Option<RateLimit>,
+    #[serde(skip)]
+    only_changes: bool,
+    window_start_ns: u64,
+    window_packets: u64,
+    /// Payload of the last frame of every layout (direction and datagram headers, indexes aside).
+    #[serde(with = "checkpoint::pairs")]
+    last_frames: HashMap<Vec<u8>, Vec<u8>>,
+    rate_limited: u64,
+    unchanged: u64,
+}
+
+impl Throttle {
+    /// Changes the limits, keeping the window and the last frames of a throttle restored from a
+    /// checkpoint.
+    pub fn set_limits(&mut self, rate_limit: Option<RateLimit>, only_changes: bool) {
+        self.rate_limit = rate_limit;
+        self.only_changes = only_changes;
+    }
+
+    /// Whether the packet is written. Packets that are not EtherCAT frames are never unchanged.
+    pub fn pass(&mut self, ts_ns: u64, frame: Option<&EtherNetFrame>) -> bool {
+        if self.only_changes {
+            if let Some(frame) = frame {
+                let mut layout = vec![frame.is_returned() as u8];
+                let mut payload = Vec::new();
+                for datagram in &frame.ecat_frame.datagrams {
+                    let header = &datagram.header;
+                    layout.push(header.cmd as u8);
+                    layout.extend(header.slave_addr.to_le_bytes());
+                    layout.extend(header.offset_addr.to_le_bytes());
+                    layout.extend(header.length.to_le_bytes());
+                    payload.extend(datagram.data);
+                    payload.extend(datagram.wkc.to_le_bytes());
+                }
+                if self.last_frames.get(&layout) == Some(&payload) {
+                    self.unchanged += 1;
+                    return false;
+                }
+                self.last_frames.insert(layout, payload);
+            }
+        }
+        if let Some(limit) = self.rate_limit {
+            if ts_ns.saturating_sub(self.window_start_ns) >= limit.period_ns {
+                self.window_start_ns = ts_ns;
+                self.window_packets = 0;
+            }
+            if self.window_packets >= limit.packets {
+                self.rate_limited += 1;
+                return false;
+            }
+            self.window_packets += 1;
+        }
+        true
+    }
+
+    /// Prints what was left out to stderr.
+    pub fn report(&self) {
+        if self.unchanged > 0 {
+            eprintln!("{} unchanged frames not written (--only-changes)", self.unchanged);
+        }
+        if self.rate_limited > 0 {
+            eprintln!("{} packets not written (--rate-limit)", self.rate_limited);
+        }
+    }
+}
@@ -0,0 +1,157 @@
+use crate::capture::{self, CaptureOptions, Item};
+use crate::labels::Labels;
+use crate::sink::out;
+use crate::slave_config::SlaveRef;
+use crate::value::{Style, Value};
+use crate::{EtherCATCommand, EtherCATDatagramHeader, EtherNetFrame};
+use std::collections::{HashMap, VecDeque};
+use std::fs::File;
+use std::io::IsTerminal;
+use std::ops::ControlFlow;
+use std::process::exit;
+
+/// Moves the cursor home and clears the terminal, to redraw the view in place.
+const CLEAR_SCREEN: &str = "\x1b[H\x1b[2J";
+
+/// One datagram as counted by the view.
+struct Access {
+    ts_ns: u64,
+    register: String,
+    slave: String,
+    bytes: u64,
+}
+
+fn access(ts_ns: u64, header: &EtherCATDatagramHeader, labels: &Labels) -> Access {
+    use EtherCATCommand::*;
+
+    let (register, slave) = match header.cmd {
+        LRD | LWR | LRW => {
+            let address = ((header.offset_addr as u32) << 16) | header.slave_addr as u32;
+            let address = Value::LogicalAddress(address).render(Style::Report);
+            (format!("{} {}", header.cmd, address), format!("logical {}", address))
+        }
+        APRD | APWR | APRW | ARMW => {
+            let slave = SlaveRef::Position(header.slave_addr.wrapping_neg());
+            (format!("{} {}", header.cmd, Value::Address(header.offset_addr).render(Style::Report)), labels.display(slave))
+        }
+        FPRD | FPWR | FPRW | FRMW => {
+            (format!("{} {}", header.cmd, Value::Address(header.offset_addr).render(Style::Report)), labels.display(SlaveRef::Station(header.slave_addr)))
+        }
+        BRD | BWR | BRW => (format!("{} {}", header.cmd, Value::Address(header.offset_addr).render(Style::Report)), "all".to_string()),
+        NOP | UNKNOWN => (header.cmd.to_string(), "none".to_string()),
+    };
+    Access { ts_ns, register, slave, bytes: header.length as u64 }
+}
+
+/// Counters over the accesses of the last `window_ns` of capture time.
+struct Window {
+    window_ns: u64,
+    accesses: VecDeque<Access>,
+    registers: HashMap<String, u64>,
+    slaves: HashMap<String, u64>,
+    payload: HashMap<String, u64>,
+}
+
+fn add(counters: &mut HashMap<String, u64>, key: &str, amount: u64) {
+    *counters.entry(key.to_string()).or_default() += amount;
+}
+
+fn remove(counters: &mut HashMap<String, u64>, key: &str, amount: u64) {
+    if let Some(count) = counters.get_mut(key) {
+        *count -= amount;
+        if *count == 0 {
+            counters.remove(key);
+        }
+    }
+}
+
+impl Window {
+    fn new(window_ns: u64) -> Self {
+        Window {
+            window_ns,
+            accesses: VecDeque::new(),
+            registers: HashMap::new(),
+            slaves: HashMap::new(),
+            payload: HashMap::new(),
+        }
+    }
+
+    fn push(&mut self, access: Access) {
+        add(&mut self.registers, &access.register, 1);
+        add(&mut self.slaves, &access.slave, 1);
+        add(&mut self.payload, &access.slave, access.bytes);
+        self.accesses.push_back(access);
+    }
+
+    /// Forgets the accesses that fell out of the window ending at `now_ns`.
+    fn expire(&mut self, now_ns: u64) {
+        while let Some(access) = self.accesses.front() {
+            if access.ts_ns + self.window_ns > now_ns {
+                break;
+            }
+            let access = self.accesses.pop_front().unwrap();
+            remove(&mut self.registers, &access.register, 1);
+            remove(&mut self.slaves, &access.slave, 1);
+            remove(&mut self.payload, &access.slave, access.bytes);
+        }
+    }
+
+    fn render(&self, now_ns: u64, frame_number: u64, count: usize) -> String {
+        let mut view = format!("Top talkers over {} up to {} (frame {})\n", Value::Duration(self.window_ns).render(Style::Report), Value::Time(now_ns).render(Style::Report), frame_number);
+        for (title, counters) in [("Registers (accesses)", &self.registers), ("Slaves (datagrams)", &self.slaves), ("Payload (bytes)", &self.payload)] {
+            let mut entries: Vec<(&String, &u64)> = counters.iter().collect();
+            // most first, ties by name so that the view does not flicker
+            entries.sort_by(|a, b| b.1.cmp(a.1).then(a.0.cmp(b.0)));
+            view += &format!("{}:\n", title);
+            for (key, value) in entries.into_iter().take(count) {
+                view += &format!("{:>10}  {}\n", value, key);
+            }
+        }
+        view
+    }
+}
+
+/// Shows the most accessed registers, busiest slaves and largest payloads over a sliding
+/// window of capture time. On a terminal the view is redrawn in place every `window_ns`,
+/// otherwise only the view at the end of the capture is printed.
+pub fn run(file: File, options: &CaptureOptions, window_ns: u64, count: usize, labels: &Labels) {
+    let interactive = std::io::stdout().is_terminal();
+    let mut window = Window::new(window_ns);
+    let mut next_refresh_ns = None;
+    let mut last = (0, 0);
+    // each datagram is counted once, on its way out, unless only returning frames were captured.
+    let mut seen_outgoing = false;
+
+    let summary = capture::read(file, options, |item| {
+        let Item::Packet(packet) = item else {
+            return ControlFlow::Continue(());
+        };
+        let ts_ns = packet.info.ts_ns;
+        last = (ts_ns, packet.info.frame_number);
+        window.expire(ts_ns);
+        if let Some(frame) = packet.data.and_then(EtherNetFrame::parse_ethercat) {
+            seen_outgoing |= !frame.is_returned();
+            if !frame.is_returned() || !seen_outgoing {
+                for datagram in &frame.ecat_frame.datagrams {
+                    window.push(access(ts_ns, &datagram.header, labels));
+                }
+            }
+        }
+        let refresh_ns = *next_refresh_ns.get_or_insert(ts_ns + window_ns);
+        if interactive && ts_ns >= refresh_ns {
+            out!("{}{}", CLEAR_SCREEN, window.render(ts_ns, packet.info.frame_number, count));
+            next_refresh_ns = Some(ts_ns + window_ns);
+        }
+        ControlFlow::Continue(())
+    });
+    let summary = summary.unwrap_or_else(|e| {
+        eprintln!("{}", e);
+        exit(1);
+    });
+
+    if interactive {
+        out!("{}", CLEAR_SCREEN);
+    }
+    out!("{}", window.render(last.0, last.1, count));
+    summary.report();
+}
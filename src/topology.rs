@@ -0,0 +1,114 @@
+use crate::capture::{self, CaptureOptions, Item, Summary};
+use crate::labels::Labels;
+use crate::pair::Key;
+use crate::registers::STATION_ADDRESS_REGISTER;
+use crate::sink::outln;
+use crate::slave_config::SlaveRef;
+use crate::{EtherCATCommand, EtherNetFrame};
+use std::collections::{BTreeMap, HashMap};
+use std::fs::File;
+use std::ops::ControlFlow;
+use std::process::exit;
+
+const CSV_HEADER: &str = "position,station,assigned_frame,answered";
+
+/// The physical order of the slaves in the chain, rebuilt from the scan of the master: the
+/// positions answering auto-increment datagrams, the station address written to each of them
+/// and the slave count of the broadcast reads.
+#[derive(Default)]
+pub struct Topology {
+    /// The station address assigned to every position, with the frame of the assignment.
+    stations: BTreeMap<u16, (u16, u64)>,
+    /// The auto-increment datagrams every position answered.
+    answered: BTreeMap<u16, u64>,
+    /// The position of every auto-increment datagram on its way, which the returned datagram
+    /// no longer tells.
+    pending: HashMap<Key, u16>,
+    /// Slaves counted by returned broadcast reads.
+    broadcast_slaves: Option<u16>,
+}
+
+impl Topology {
+    /// Reads the whole capture, so that the rows before the scan get their position too.
+    pub fn from_capture(file: File, options: &CaptureOptions) -> Result<(Self, Summary), String> {
+        let mut topology = Topology::default();
+        let summary = capture::read(file, options, |item| {
+            if let Item::Packet(packet) = item {
+                if let Some(frame) = packet.data.and_then(EtherNetFrame::parse_ethercat) {
+                    topology.frame(packet.info.frame_number, &frame);
+                }
+            }
+            ControlFlow::Continue(())
+        })?;
+        Ok((topology, summary))
+    }
+
+    pub fn frame(&mut self, frame_number: u64, frame: &EtherNetFrame) {
+        use EtherCATCommand::*;
+
+        let returned = frame.is_returned();
+        for datagram in &frame.ecat_frame.datagrams {
+            let header = &datagram.header;
+            let key = Key::new(frame.master_mac(), header);
+            if returned {
+                if header.cmd == BRD {
+                    self.broadcast_slaves = Some(self.broadcast_slaves.unwrap_or(0).max(datagram.wkc));
+                }
+                if let Some(position) = self.pending.remove(&key).filter(|_| datagram.wkc > 0) {
+                    *self.answered.entry(position).or_default() += 1;
+                }
+                continue;
+            }
+            if !matches!(header.cmd, APRD | APWR | APRW | ARMW) {
+                continue;
+            }
+            let position = header.slave_addr.wrapping_neg();
+            self.pending.insert(key, position);
+            if matches!(header.cmd, APWR | APRW) && header.offset_addr == STATION_ADDRESS_REGISTER && header.length >= 2 {
+                if let Some(station) = datagram.data.get(..2) {
+                    self.stations.insert(position, (u16::from_le_bytes([station[0], station[1]]), frame_number));
+                }
+            }
+        }
+    }
+
+    /// The position of the slave a datagram addresses: its own by auto-increment addressing,
+    /// the last position given its address by station addressing.
+    pub fn position(&self, slave: Option<SlaveRef>) -> Option<u16> {
+        match slave? {
+            SlaveRef::Position(position) => Some(position),
+            SlaveRef::Station(address) => self.stations.iter()
+                .filter(|(_, (station, _))| *station == address)
+                .max_by_key(|(_, (_, frame_number))| *frame_number)
+                .map(|(position, _)| *position),
+        }
+    }
+
+    /// The positions of the chain in order: every position that answered or was given an
+    /// address, and up to the slave count of the broadcast reads.
+    fn positions(&self) -> impl Iterator<Item = u16> {
+        let last = self.stations.keys().chain(self.answered.keys()).max().copied();
+        let counted = self.broadcast_slaves.and_then(|count| count.checked_sub(1));
+        let end = last.max(counted);
+        end.into_iter().flat_map(|end| 0..=end)
+    }
+}
+
+/// Prints the slaves in the order of the chain: their position, the station address the master
+/// assigned them and in which frame, and how many auto-increment datagrams they answered.
+pub fn run(file: File, options: &CaptureOptions, labels: &Labels) {
+    let (topology, summary) = Topology::from_capture(file, options).unwrap_or_else(|e| {
+        eprintln!("{}", e);
+        exit(1);
+    });
+
+    outln!("{}{}", CSV_HEADER, labels.header());
+    for position in topology.positions() {
+        let assigned = topology.stations.get(&position);
+        let station = assigned.map(|(station, _)| SlaveRef::Station(*station));
+        outln!("{},{},{},{}{}", position, station.map(|station| station.to_string()).unwrap_or_default(),
+            assigned.map(|(_, frame_number)| frame_number.to_string()).unwrap_or_default(),
+            topology.answered.get(&position).copied().unwrap_or(0), labels.cell(station));
+    }
+    summary.report();
+}
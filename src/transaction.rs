@@ -0,0 +1,71 @@
+use crate::coe::{self, Sdo};
+use crate::checkpoint;
+use crate::mailbox::{Direction, Mailbox, MailboxType};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+/// EoE type of the fragments carrying Ethernet frames.
+const EOE_FRAGMENT_DATA: u8 = 0;
+
+/// The ID of a datagram, unique within the capture: the number of its frame and its position in
+/// the frame, counted from 0, such as `17.1`.
+pub fn datagram_id(frame_number: u64, position: usize) -> String {
+    format!("{}.{}", frame_number, position)
+}
+
+/// Groups the mailbox messages into transactions and names every transaction after the datagram
+/// of its first message, so that rows of every output can be joined on it:
+///
+/// - an FoE transfer runs from its read or write request to the next request to the slave,
+/// - an SDO transfer from its request to the response or abort,
+/// - a tunnelled EoE frame from its first fragment to its last,
+/// - any other message is a transaction of its own.
+///
+/// Every output feeding the same capture through a tracker gets the same IDs.
+#[derive(Clone, Default, Serialize, Deserialize)]
+pub struct Transactions {
+    foe: HashMap<u16, String>,
+    sdo: HashMap<u16, String>,
+    #[serde(with = "checkpoint::pairs")]
+    eoe: HashMap<(u16, Direction), String>,
+}
+
+impl Transactions {
+    /// Takes the next mailbox message, delivered by the datagram `id`, and returns the ID of its
+    /// transaction.
+    pub fn message(&mut self, id: String, mailbox: &Mailbox) -> String {
+        let payload = mailbox.payload;
+        match mailbox.mbx_type {
+            MailboxType::FoE if !payload.is_empty() => {
+                // read and write requests open a transfer.
+                if mailbox.direction == Direction::ToSlave && matches!(payload[0], 1 | 2) {
+                    self.foe.insert(mailbox.slave, id.clone());
+                    return id;
+                }
+                self.foe.get(&mailbox.slave).cloned().unwrap_or(id)
+            }
+            MailboxType::CoE => match (mailbox.direction, coe::parse_sdo(mailbox)) {
+                (Direction::ToSlave, Some(Sdo::Download { .. })) => {
+                    self.sdo.insert(mailbox.slave, id.clone());
+                    id
+                }
+                (_, Some(Sdo::DownloadResponse { .. } | Sdo::Abort { .. })) => self.sdo.remove(&mailbox.slave).unwrap_or(id),
+                _ => id,
+            },
+            MailboxType::EoE if payload.len() >= 4 && payload[0] & 0x0F == EOE_FRAGMENT_DATA => {
+                let key = (mailbox.slave, mailbox.direction);
+                let fragment_number = u16::from_le_bytes([payload[2], payload[3]]) & 0x3F;
+                let last = payload[1] & 0x1 != 0;
+                let transaction = match fragment_number {
+                    0 => id,
+                    _ => self.eoe.remove(&key).unwrap_or(id),
+                };
+                if !last {
+                    self.eoe.insert(key, transaction.clone());
+                }
+                transaction
+            }
+            _ => id,
+        }
+    }
+}
@@ -0,0 +1,120 @@
+use crate::{EtherCATCommand, EtherNetFrame};
+use std::fmt::Display;
+
+/// Offset of the length/R/C/M word inside a datagram header.
+const DATAGRAM_LENGTH_WORD_OFFSET: usize = 6;
+
+/// An inconsistency found in a parsed frame.
+/// Issues are shared by both checking modes: lenient mode lists them in the `warnings` column,
+/// strict mode reports them as errors.
+pub struct Issue {
+    /// Index of the offending datagram, or `None` when the issue concerns the whole frame.
+    pub datagram: Option<usize>,
+    /// Offset of the offending field from the start of the Ethernet frame.
+    pub offset: usize,
+    pub kind: IssueKind,
+}
+
+pub enum IssueKind {
+    /// The datagram sizes do not add up to the length in the EtherCAT header.
+    LengthMismatch { header_length: u16, datagrams_length: usize },
+    /// The reserved bit of the EtherCAT header is set.
+    FrameReservedBit,
+    /// Some of the reserved bits 11-13 of a datagram length word are set.
+    DatagramReservedBits(u8),
+    /// The datagram command byte is not a known EtherCAT command.
+    UnknownCommand,
+}
+
+impl IssueKind {
+    /// Short identifier used in the `warnings` column.
+    pub fn code(&self) -> &'static str {
+        match self {
+            Self::LengthMismatch { .. } => "length_mismatch",
+            Self::FrameReservedBit => "frame_reserved_bit",
+            Self::DatagramReservedBits(_) => "datagram_reserved_bits",
+            Self::UnknownCommand => "unknown_command",
+        }
+    }
+}
+
+impl Display for Issue {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "offset 0x{:x}", self.offset)?;
+        if let Some(datagram) = self.datagram {
+            write!(f, " (datagram {})", datagram)?;
+        }
+        match &self.kind {
+            IssueKind::LengthMismatch { header_length, datagrams_length } => write!(
+                f,
+                ": datagrams occupy {} bytes but the EtherCAT header announces {}",
+                datagrams_length, header_length
+            ),
+            IssueKind::FrameReservedBit => write!(f, ": reserved bit of the EtherCAT header is set"),
+            IssueKind::DatagramReservedBits(bits) => write!(f, ": reserved length bits set ({:#05b})", bits),
+            IssueKind::UnknownCommand => write!(f, ": unknown command"),
+        }
+    }
+}
+
+/// Checks a parsed frame for inconsistencies.
+pub fn check_frame(frame: &EtherNetFrame) -> Vec<Issue> {
+    let mut issues = Vec::new();
+    let ecat_frame = &frame.ecat_frame;
+
+    if ecat_frame.header.reserved != 0 {
+        issues.push(Issue {
+            datagram: None,
+            offset: frame.ecat_offset,
+            kind: IssueKind::FrameReservedBit,
+        });
+    }
+
+    let offsets = ecat_frame.datagram_offsets();
+    for (i, datagram) in ecat_frame.datagrams.iter().enumerate() {
+        let offset = frame.ecat_offset + offsets[i];
+        if matches!(datagram.header.cmd, EtherCATCommand::UNKNOWN) {
+            issues.push(Issue {
+                datagram: Some(i),
+                offset,
+                kind: IssueKind::UnknownCommand,
+            });
+        }
+        if datagram.header.reserved != 0 {
+            issues.push(Issue {
+                datagram: Some(i),
+                offset: offset + DATAGRAM_LENGTH_WORD_OFFSET,
+                kind: IssueKind::DatagramReservedBits(datagram.header.reserved),
+            });
+        }
+    }
+
+    // the frames following the first one fill their length by construction, and those of
+    // other types have none.
+    if ecat_frame.content.is_some() {
+        return issues;
+    }
+    let datagrams_length: usize = ecat_frame.first_frame_datagrams().iter().map(|datagram| datagram.size()).sum();
+    if datagrams_length != ecat_frame.header.length as usize {
+        issues.push(Issue {
+            datagram: None,
+            offset: frame.ecat_offset,
+            kind: IssueKind::LengthMismatch {
+                header_length: ecat_frame.header.length,
+                datagrams_length,
+            },
+        });
+    }
+
+    issues
+}
+
+/// Formats the `warnings` column of one datagram row: the datagram's own issues plus the frame-level ones.
+pub fn warnings_for_datagram(issues: &[Issue], datagram: usize) -> String {
+    issues
+        .iter()
+        .filter(|issue| issue.datagram.is_none_or(|i| i == datagram))
+        .map(|issue| issue.kind.code())
+        .collect::<Vec<_>>()
+        .join(";")
+}
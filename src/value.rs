@@ -0,0 +1,71 @@
+use crate::clock::{format_duration, format_signed_duration};
+
+/// How values are written: as the CSV outputs have always written them, for scripts, or for a
+/// human reading a report.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Style {
+    /// Hexadecimal addresses, times and durations in seconds with nanosecond precision.
+    Csv,
+    /// Hexadecimal addresses, durations in engineering notation such as `2.5 ms`.
+    Report,
+    /// The datagram header columns of the export, as existing consumers parse them: addresses
+    /// in bare hexadecimal, the address field of the header in decimal.
+    Columns,
+}
+
+/// A decoded number together with what it measures, so that every output renders the same kind
+/// of value the same way.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Value {
+    /// A station address or an address in the memory of a slave.
+    Address(u16),
+    /// An address in the logical process image.
+    LogicalAddress(u32),
+    /// The address field of a datagram header: a position for the auto-increment commands, a
+    /// station address for the configured ones.
+    DatagramAddress(u16),
+    /// A capture time, in nanoseconds since the epoch.
+    Time(u64),
+    /// A length of time, in nanoseconds.
+    Duration(u64),
+    /// The difference of two times, such as between two clocks, in nanoseconds.
+    Offset(f64),
+    Count(u64),
+}
+
+impl Value {
+    pub fn render(self, style: Style) -> String {
+        match (self, style) {
+            (Value::Address(address), Style::Columns) => format!("{:x}", address),
+            (Value::Address(address) | Value::DatagramAddress(address), Style::Csv | Style::Report) => format!("0x{:04x}", address),
+            (Value::DatagramAddress(address), Style::Columns) => address.to_string(),
+            (Value::LogicalAddress(address), _) => format!("0x{:08x}", address),
+            (Value::Time(ns), _) | (Value::Duration(ns), Style::Csv | Style::Columns) => format_duration(ns),
+            (Value::Duration(ns), Style::Report) => engineering(ns),
+            (Value::Offset(ns), _) => format_signed_duration(ns),
+            (Value::Count(count), _) => count.to_string(),
+        }
+    }
+
+    /// The value in JSON: addresses and counts as numbers, times and durations in nanoseconds.
+    pub fn json(self) -> serde_json::Value {
+        match self {
+            Value::Address(address) | Value::DatagramAddress(address) => address.into(),
+            Value::LogicalAddress(address) => address.into(),
+            Value::Time(ns) | Value::Duration(ns) | Value::Count(ns) => ns.into(),
+            Value::Offset(ns) => serde_json::Number::from_f64(ns.round()).map_or(serde_json::Value::Null, serde_json::Value::Number),
+        }
+    }
+}
+
+/// Nanoseconds in the largest unit of which there is at least one, with up to three decimals:
+/// `18 ms`, `2.5 us`, `0 s`.
+fn engineering(ns: u64) -> String {
+    const UNITS: [(f64, &str); 4] = [(1e9, "s"), (1e6, "ms"), (1e3, "us"), (1.0, "ns")];
+    if ns == 0 {
+        return "0 s".to_string();
+    }
+    let (scale, unit) = UNITS.into_iter().find(|(scale, _)| ns as f64 >= *scale).unwrap_or(UNITS[3]);
+    let number = format!("{:.3}", ns as f64 / scale);
+    format!("{} {}", number.trim_end_matches('0').trim_end_matches('.'), unit)
+}
@@ -0,0 +1,153 @@
+use crate::annotations::quote;
+use crate::capture::PacketInfo;
+use crate::columns::Row;
+use crate::events::EventKind;
+use crate::validate;
+use crate::wkc::WkcModel;
+use crate::EtherNetFrame;
+use std::fmt::Display;
+use std::fs::{File, OpenOptions};
+use std::io::{BufWriter, Write};
+
+const CSV_HEADER: &str = "frame,datagram,severity,source,code,detail";
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum Severity {
+    /// Something is off, but every byte of the capture made it into the output.
+    Warning,
+    /// Part of the capture is missing from the output.
+    Error,
+}
+
+impl Display for Severity {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Severity::Warning => write!(f, "warning"),
+            Severity::Error => write!(f, "error"),
+        }
+    }
+}
+
+/// One finding of the exporter, with the frame it concerns.
+pub struct Record<'a> {
+    /// Number of the packet concerned, or of the last packet before it for findings between
+    /// packets (0 before the first packet).
+    pub frame_number: u64,
+    /// Position of the datagram concerned in its frame, if the finding is about one datagram.
+    pub datagram: Option<usize>,
+    pub severity: Severity,
+    /// What found it: `capture` for the reader, `validation`, `link` or `monitor`.
+    pub source: &'static str,
+    /// Short identifier, the same as in the `warnings` column where there is one.
+    pub code: &'a str,
+    pub detail: String,
+}
+
+impl<'a> Record<'a> {
+    /// An event found by the reader (`capture`) or by a monitor of the export (`monitor`).
+    pub fn event(source: &'static str, frame_number: u64, kind: &'a EventKind) -> Self {
+        Record { frame_number, datagram: None, severity: Severity::Warning, source, code: kind.code(), detail: kind.to_string() }
+    }
+
+    /// A region of the capture skipped by `--recover`, after the packet `frame_number`.
+    pub fn corrupt_block(frame_number: u64, offset: u64, reason: &str, skipped: usize) -> Self {
+        Record {
+            frame_number,
+            datagram: None,
+            severity: Severity::Error,
+            source: "capture",
+            code: "corrupt_block",
+            detail: format!("offset {} : {}, skipped {} bytes", offset, reason, skipped),
+        }
+    }
+
+    /// A frame with the EtherCAT EtherType that does not parse, left out of the output.
+    pub fn malformed_frame(frame_number: u64, error: &impl Display) -> Self {
+        Record { frame_number, datagram: None, severity: Severity::Error, source: "parser", code: "malformed_frame", detail: error.to_string() }
+    }
+
+    /// The record as a line of JSON Lines, an object of the fields of the CSV columns under
+    /// `warning`, for `--inline-warnings`.
+    pub fn json_line(&self) -> String {
+        let mut row = Row::default();
+        row.number("frame", Some(self.frame_number));
+        row.number("datagram", self.datagram);
+        row.text("severity", Some(self.severity.to_string()));
+        row.text("source", Some(self.source));
+        row.text("code", Some(self.code));
+        row.text("detail", Some(self.detail.as_str()));
+        let columns: Vec<String> = CSV_HEADER.split(',').map(str::to_string).collect();
+        format!("{{\"warning\":{}}}\n", row.json(&columns))
+    }
+}
+
+/// The findings of the warnings column of a frame (validation failures, working counter
+/// mismatches and link errors) as records.
+pub fn frame_records(info: &PacketInfo, frame: &EtherNetFrame, wkc_model: Option<&WkcModel>) -> Vec<Record<'static>> {
+    let mut records: Vec<Record> = validate::check_frame(frame)
+        .into_iter()
+        .map(|issue| Record {
+            frame_number: info.frame_number,
+            datagram: issue.datagram,
+            severity: Severity::Warning,
+            source: "validation",
+            code: issue.kind.code(),
+            detail: issue.to_string(),
+        })
+        .collect();
+    for (i, datagram) in frame.ecat_frame.datagrams.iter().enumerate() {
+        let expected = wkc_model.filter(|_| frame.is_returned()).and_then(|model| model.expected(&datagram.header));
+        if let Some(expected) = expected.filter(|expected| *expected != datagram.wkc) {
+            records.push(Record {
+                frame_number: info.frame_number,
+                datagram: Some(i),
+                severity: Severity::Warning,
+                source: "validation",
+                code: "wkc_mismatch",
+                detail: format!("working counter {}, expected {}", datagram.wkc, expected),
+            });
+        }
+    }
+    records.extend(info.link_errors.codes().into_iter().map(|code| Record {
+        frame_number: info.frame_number,
+        datagram: None,
+        severity: Severity::Warning,
+        source: "link",
+        code,
+        detail: String::new(),
+    }));
+    records
+}
+
+/// The `--warnings-out` sidecar: every anomaly, validation failure and skipped region of the
+/// export as a CSV row, so that consumers do not have to scrape stderr for them. Records are
+/// written in capture order.
+pub struct WarningLog {
+    path: String,
+    out: BufWriter<File>,
+}
+
+impl WarningLog {
+    /// Starts a new sidecar, or appends to the one of the export being resumed.
+    pub fn open(path: &str, resume: bool) -> Result<Self, String> {
+        let error = |e: std::io::Error| format!("Cannot write {} : {}", path, e);
+        let file = OpenOptions::new().create(true).append(resume).write(true).truncate(!resume).open(path).map_err(error)?;
+        let mut log = WarningLog { path: path.to_string(), out: BufWriter::new(file) };
+        if !resume {
+            writeln!(log.out, "{}", CSV_HEADER).map_err(error)?;
+        }
+        Ok(log)
+    }
+
+    pub fn record(&mut self, record: Record) -> Result<(), String> {
+        writeln!(self.out, "{},{},{},{},{},{}",
+            record.frame_number, record.datagram.map(|d| d.to_string()).unwrap_or_default(), record.severity,
+            record.source, record.code, quote(&record.detail))
+            .map_err(|e| format!("Cannot write {} : {}", self.path, e))
+    }
+
+    /// Writes out what is buffered, before a checkpoint and at the end of the export.
+    pub fn flush(&mut self) -> Result<(), String> {
+        self.out.flush().map_err(|e| format!("Cannot write {} : {}", self.path, e))
+    }
+}
@@ -0,0 +1,229 @@
+use crate::capture::{self, CaptureOptions, Item};
+use crate::eni::Eni;
+use crate::labels::{self, Labels};
+use crate::sink::outln;
+use crate::value::{Style, Value};
+use crate::{EtherCATCommand, EtherCATDatagramHeader, EtherNetFrame};
+use serde::Deserialize;
+use std::fs::{self, File};
+use std::ops::ControlFlow;
+use std::process::exit;
+
+const CSV_HEADER: &str = "frame,time,cmd,index,adp,ado,wkc,expected";
+
+/// Expected working counters, loaded from a TOML file given with `--wkc-config`:
+///
+/// ```toml
+/// # slaves on the segment, for broadcast and read multiple write commands
+/// slave_count = 2
+///
+/// # logical process data each slave takes part in, for LRD/LWR/LRW
+/// [[slave]]
+/// address = 0x1001
+/// outputs = { start = 0x0000, length = 4 }
+/// inputs = { start = 0x0004, length = 4 }
+///
+/// # an explicit expectation wins over the values derived from the slaves
+/// [[datagram]]
+/// cmd = "FPRD"
+/// adp = 0x1002
+/// ado = 0x0130
+/// expected = 1
+/// ```
+///
+/// Each addressed slave adds 1 when it is read or written, except that an xRW command adds 2
+/// for the write, so a slave both read and written by it accounts for 3. Auto-increment commands
+/// are only checked against an explicit expectation: the returned frame no longer tells which
+/// slave they addressed.
+#[derive(Clone, Deserialize, Default)]
+#[serde(deny_unknown_fields)]
+pub struct WkcModel {
+    slave_count: Option<u16>,
+    #[serde(default, rename = "slave")]
+    slaves: Vec<SlaveWkc>,
+    #[serde(default, rename = "datagram")]
+    datagrams: Vec<DatagramWkc>,
+}
+
+#[derive(Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct SlaveWkc {
+    /// Configured station address.
+    address: u16,
+    /// Logical range the slave's outputs are mapped to.
+    outputs: Option<LogicalRange>,
+    /// Logical range the slave's inputs are mapped to.
+    inputs: Option<LogicalRange>,
+}
+
+#[derive(Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct LogicalRange {
+    start: u32,
+    length: u32,
+}
+
+impl LogicalRange {
+    fn overlaps(&self, start: u32, length: u32) -> bool {
+        let (start, end) = (start as u64, start as u64 + length as u64);
+        let (own_start, own_end) = (self.start as u64, self.start as u64 + self.length as u64);
+        own_start < end && start < own_end
+    }
+}
+
+#[derive(Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct DatagramWkc {
+    cmd: String,
+    adp: Option<u16>,
+    ado: Option<u16>,
+    /// 32-bit logical address, instead of `adp`/`ado`, for logical commands.
+    address: Option<u32>,
+    expected: u16,
+}
+
+impl DatagramWkc {
+    fn matches(&self, header: &EtherCATDatagramHeader) -> bool {
+        self.cmd.parse::<EtherCATCommand>().is_ok_and(|cmd| cmd == header.cmd)
+            && self.adp.is_none_or(|adp| adp == header.slave_addr)
+            && self.ado.is_none_or(|ado| ado == header.offset_addr)
+            && self.address.is_none_or(|address| address == logical_address(header))
+    }
+}
+
+/// Parses an expectation of `--expected-wkc`, such as `LRW=3`, for every datagram of a command.
+pub fn parse_expectation(s: &str) -> Result<(EtherCATCommand, u16), String> {
+    let (cmd, expected) = s.split_once('=').ok_or_else(|| format!("invalid expected working counter '{}', expected CMD=N", s))?;
+    let expected = expected.trim().parse().map_err(|_| format!("invalid working counter '{}' in '{}'", expected, s))?;
+    Ok((cmd.trim().parse()?, expected))
+}
+
+/// Logical commands carry a 32-bit address in place of the ADP/ADO pair.
+fn logical_address(header: &EtherCATDatagramHeader) -> u32 {
+    ((header.offset_addr as u32) << 16) | header.slave_addr as u32
+}
+
+impl WkcModel {
+    pub fn load(path: &str) -> Result<Self, String> {
+        let text = fs::read_to_string(path).map_err(|e| format!("Cannot read {} : {}", path, e))?;
+        let model: WkcModel = toml::from_str(&text).map_err(|e| format!("Invalid WKC config {} : {}", path, e))?;
+        if let Some(datagram) = model.datagrams.iter().find(|d| d.cmd.parse::<EtherCATCommand>().is_err()) {
+            return Err(format!("Invalid WKC config {} : unknown command '{}'", path, datagram.cmd));
+        }
+        Ok(model)
+    }
+
+    /// Adds the working counters of the cyclic commands of an ENI file, which win over those
+    /// of the WKC config.
+    pub fn with_eni(mut self, eni: &Eni) -> Self {
+        use EtherCATCommand::*;
+
+        let cyclic = eni.commands.iter().filter_map(|command| {
+            let expected = command.expected_wkc?;
+            let (adp, ado, address) = match command.cmd {
+                _ if command.is_logical() => (None, None, Some(command.logical_address())),
+                // the slaves increment the position and broadcast addresses on the way.
+                APRD | APWR | APRW | ARMW | BRD | BWR | BRW => (None, Some(command.ado), None),
+                _ => (Some(command.adp), Some(command.ado), None),
+            };
+            Some(DatagramWkc { cmd: command.cmd.to_string(), adp, ado, address, expected })
+        });
+        self.datagrams.splice(0..0, cyclic);
+        self
+    }
+
+    /// Adds expectations given on the command line, which win over those of the file.
+    pub fn with_expectations(mut self, expectations: &[(EtherCATCommand, u16)]) -> Self {
+        let explicit = expectations.iter().map(|(cmd, expected)| DatagramWkc {
+            cmd: cmd.to_string(),
+            adp: None,
+            ado: None,
+            address: None,
+            expected: *expected,
+        });
+        self.datagrams.splice(0..0, explicit);
+        self
+    }
+
+    /// The working counter a returning datagram should carry, if the model knows it.
+    pub fn expected(&self, header: &EtherCATDatagramHeader) -> Option<u16> {
+        use EtherCATCommand::*;
+
+        if let Some(datagram) = self.datagrams.iter().find(|d| d.matches(header)) {
+            return Some(datagram.expected);
+        }
+
+        let (reads, writes) = match header.cmd {
+            APRD | FPRD | BRD | LRD => (true, false),
+            APWR | FPWR | BWR | LWR => (false, true),
+            APRW | FPRW | BRW | LRW => (true, true),
+            // one slave reads, all others write: every slave counts once.
+            ARMW | FRMW => return self.slave_count,
+            NOP | UNKNOWN => return None,
+        };
+        let write_wkc = if reads { 2 } else { 1 };
+        let per_slave = reads as u16 + writes as u16 * write_wkc;
+
+        match header.cmd {
+            BRD | BWR | BRW => self.slave_count.map(|count| count * per_slave),
+            // every slave has incremented the auto-increment address by the time the frame returns.
+            APRD | APWR | APRW => None,
+            FPRD | FPWR | FPRW => {
+                if self.slaves.is_empty() {
+                    return None;
+                }
+                let present = self.slaves.iter().any(|slave| slave.address == header.slave_addr);
+                Some(if present { per_slave } else { 0 })
+            }
+            _ => {
+                if self.slaves.is_empty() {
+                    return None;
+                }
+                let (start, length) = (logical_address(header), header.length as u32);
+                let wkc = self.slaves.iter().map(|slave| {
+                    let read = reads && slave.inputs.as_ref().is_some_and(|r| r.overlaps(start, length));
+                    let written = writes && slave.outputs.as_ref().is_some_and(|r| r.overlaps(start, length));
+                    read as u16 + written as u16 * write_wkc
+                });
+                Some(wkc.sum())
+            }
+        }
+    }
+}
+
+/// Lists every returning datagram whose working counter differs from the model, with its
+/// frame and capture time, as the capture goes.
+pub fn run(file: File, options: &CaptureOptions, model: &WkcModel, labels: &Labels) {
+    let mut checked = 0;
+    let mut mismatches = 0;
+    outln!("{}{}", CSV_HEADER, labels.header());
+    let summary = capture::read(file, options, |item| {
+        let Item::Packet(packet) = item else {
+            return ControlFlow::Continue(());
+        };
+        let Some(frame) = packet.data.and_then(EtherNetFrame::parse_ethercat).filter(|frame| frame.is_returned()) else {
+            return ControlFlow::Continue(());
+        };
+        for datagram in &frame.ecat_frame.datagrams {
+            let header = &datagram.header;
+            let Some(expected) = model.expected(header) else {
+                continue;
+            };
+            checked += 1;
+            if datagram.wkc != expected {
+                mismatches += 1;
+                outln!("{},{},{},{},{},{},{},{}{}",
+                    packet.info.frame_number, Value::Time(packet.info.ts_ns).render(Style::Csv), header.cmd, header.index,
+                    Value::Address(header.slave_addr).render(Style::Csv), Value::Address(header.offset_addr).render(Style::Csv),
+                    datagram.wkc, expected, labels.cell(labels::addressed_slave(header, true)));
+            }
+        }
+        ControlFlow::Continue(())
+    });
+    let summary = summary.unwrap_or_else(|e| {
+        eprintln!("{}", e);
+        exit(1);
+    });
+    eprintln!("{} of {} datagrams with an expected working counter differ from it", mismatches, checked);
+    summary.report();
+}
@@ -0,0 +1,228 @@
+/// An element of an XML document, with its attributes, child elements and text. Comments,
+/// processing instructions and the document type declaration are skipped; the text of an
+/// element is the concatenation of its text and CDATA sections, trimmed.
+#[derive(Debug, Default)]
+pub struct Element {
+    pub name: String,
+    pub attributes: Vec<(String, String)>,
+    pub children: Vec<Element>,
+    pub text: String,
+}
+
+impl Element {
+    /// The first child element named `name`.
+    pub fn child(&self, name: &str) -> Option<&Element> {
+        self.children.iter().find(|child| child.name == name)
+    }
+
+    /// Every child element named `name`, in document order.
+    pub fn children<'a>(&'a self, name: &'a str) -> impl Iterator<Item = &'a Element> + 'a {
+        self.children.iter().filter(move |child| child.name == name)
+    }
+
+    /// The text of the first child element named `name`.
+    pub fn child_text(&self, name: &str) -> Option<&str> {
+        self.child(name).map(|child| child.text.as_str())
+    }
+
+    pub fn attribute(&self, name: &str) -> Option<&str> {
+        self.attributes.iter().find(|(key, _)| key == name).map(|(_, value)| value.as_str())
+    }
+}
+
+/// Reads a document, such as an ENI file, into its root element.
+struct Parser<'a> {
+    text: &'a str,
+    position: usize,
+}
+
+impl<'a> Parser<'a> {
+    fn rest(&self) -> &'a str {
+        &self.text[self.position..]
+    }
+
+    fn error(&self, message: &str) -> String {
+        let line = self.text[..self.position].matches('\n').count() + 1;
+        format!("line {} : {}", line, message)
+    }
+
+    /// Skips past the next `end`, which must follow.
+    fn skip_past(&mut self, end: &str) -> Result<(), String> {
+        match self.rest().find(end) {
+            Some(at) => {
+                self.position += at + end.len();
+                Ok(())
+            }
+            None => Err(self.error(&format!("missing '{}'", end))),
+        }
+    }
+
+    fn skip_whitespace(&mut self) {
+        self.position = self.text.len() - self.rest().trim_start().len();
+    }
+
+    /// Skips the markup that carries no content: comments, processing instructions and
+    /// declarations. Returns whether there was any.
+    fn skip_misc(&mut self) -> Result<bool, String> {
+        let rest = self.rest();
+        if rest.starts_with("<!--") {
+            self.skip_past("-->")?;
+        } else if rest.starts_with("<?") {
+            self.skip_past("?>")?;
+        } else if rest.starts_with("<!") && !rest.starts_with("<![CDATA[") {
+            self.skip_past(">")?;
+        } else {
+            return Ok(false);
+        }
+        Ok(true)
+    }
+
+    fn name(&mut self) -> Result<String, String> {
+        let rest = self.rest();
+        let end = rest.find(|c: char| c.is_whitespace() || matches!(c, '/' | '>' | '=')).unwrap_or(rest.len());
+        if end == 0 {
+            return Err(self.error("expected a name"));
+        }
+        self.position += end;
+        Ok(rest[..end].to_string())
+    }
+
+    fn element(&mut self) -> Result<Element, String> {
+        if !self.rest().starts_with('<') {
+            return Err(self.error("expected an element"));
+        }
+        self.position += 1;
+        let mut element = Element { name: self.name()?, ..Element::default() };
+        loop {
+            self.skip_whitespace();
+            let rest = self.rest();
+            if rest.starts_with("/>") {
+                self.position += 2;
+                return Ok(element);
+            }
+            if rest.starts_with('>') {
+                self.position += 1;
+                break;
+            }
+            let key = self.name()?;
+            self.skip_whitespace();
+            if !self.rest().starts_with('=') {
+                return Err(self.error(&format!("attribute '{}' has no value", key)));
+            }
+            self.position += 1;
+            self.skip_whitespace();
+            let Some(quote) = self.rest().chars().next().filter(|c| matches!(c, '"' | '\'')) else {
+                return Err(self.error(&format!("the value of attribute '{}' is not quoted", key)));
+            };
+            self.position += 1;
+            let Some(end) = self.rest().find(quote) else {
+                return Err(self.error(&format!("the value of attribute '{}' is not closed", key)));
+            };
+            let value = unescape(&self.rest()[..end]);
+            self.position += end + 1;
+            element.attributes.push((key, value));
+        }
+
+        let mut text = String::new();
+        loop {
+            if self.skip_misc()? {
+                continue;
+            }
+            let rest = self.rest();
+            if let Some(cdata) = rest.strip_prefix("<![CDATA[") {
+                let Some(end) = cdata.find("]]>") else {
+                    return Err(self.error("CDATA section is not closed"));
+                };
+                text.push_str(&cdata[..end]);
+                self.position += "<![CDATA[".len() + end + "]]>".len();
+            } else if let Some(closing) = rest.strip_prefix("</") {
+                let end = closing.find('>').ok_or_else(|| self.error("closing tag is not closed"))?;
+                if closing[..end].trim() != element.name {
+                    return Err(self.error(&format!("'{}' closed by '{}'", element.name, closing[..end].trim())));
+                }
+                self.position += 2 + end + 1;
+                element.text = text.trim().to_string();
+                return Ok(element);
+            } else if rest.starts_with('<') {
+                element.children.push(self.element()?);
+            } else if rest.is_empty() {
+                return Err(self.error(&format!("'{}' is not closed", element.name)));
+            } else {
+                let end = rest.find('<').unwrap_or(rest.len());
+                text.push_str(&unescape(&rest[..end]));
+                self.position += end;
+            }
+        }
+    }
+}
+
+/// Reads a number as the EtherCAT XML files write them (ENI, ESI): decimal, or hexadecimal
+/// written as `#x1001` or `0x1001`.
+pub fn number(text: &str) -> Option<u64> {
+    let text = text.trim();
+    match text.strip_prefix("#x").or_else(|| text.strip_prefix("0x")) {
+        Some(hex) => u64::from_str_radix(hex, 16).ok(),
+        None => text.parse().ok(),
+    }
+}
+
+/// Replaces the predefined entities and character references.
+fn unescape(text: &str) -> String {
+    let mut unescaped = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(at) = rest.find('&') {
+        unescaped.push_str(&rest[..at]);
+        rest = &rest[at..];
+        let Some(end) = rest.find(';') else {
+            break;
+        };
+        let entity = &rest[1..end];
+        let character = match entity {
+            "lt" => Some('<'),
+            "gt" => Some('>'),
+            "amp" => Some('&'),
+            "quot" => Some('"'),
+            "apos" => Some('\''),
+            _ => entity
+                .strip_prefix("#x")
+                .map(|hex| u32::from_str_radix(hex, 16).ok())
+                .unwrap_or_else(|| entity.strip_prefix('#').and_then(|decimal| decimal.parse().ok()))
+                .and_then(char::from_u32),
+        };
+        match character {
+            Some(character) => {
+                unescaped.push(character);
+                rest = &rest[end + 1..];
+            }
+            // an unknown entity is kept as it is.
+            None => {
+                unescaped.push('&');
+                rest = &rest[1..];
+            }
+        }
+    }
+    unescaped.push_str(rest);
+    unescaped
+}
+
+/// Parses an XML document into its root element.
+pub fn parse(text: &str) -> Result<Element, String> {
+    let mut parser = Parser { text: text.strip_prefix('\u{feff}').unwrap_or(text), position: 0 };
+    loop {
+        parser.skip_whitespace();
+        if !parser.skip_misc()? {
+            break;
+        }
+    }
+    let root = parser.element()?;
+    loop {
+        parser.skip_whitespace();
+        if !parser.skip_misc()? {
+            break;
+        }
+    }
+    if !parser.rest().is_empty() {
+        return Err(parser.error("content after the root element"));
+    }
+    Ok(root)
+}
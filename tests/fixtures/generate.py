@@ -0,0 +1,732 @@
+#!/usr/bin/env python3
+"""Regenerates the pcapng and classic pcap fixtures used by the golden-file tests.
+
+Run from this directory: ./generate.py
+"""
+import gzip
+import lzma
+import struct
+import zlib
+
+TS_BASE = 1_700_000_000_000_000  # microseconds, default if_tsresol
+MASTER_MAC = bytes.fromhex('001b21aabb01')
+RETURN_MAC = bytes.fromhex('021b21aabb01')  # the first slave sets the local bit on the way back
+BROADCAST = b'\xff' * 6
+
+NOP, APRD, APWR, APRW, FPRD, FPWR, FPRW, BRD, BWR, BRW, LRD, LWR, LRW, ARMW, FRMW = range(15)
+
+
+def block(block_type, body):
+    body += b'\0' * ((-len(body)) % 4)
+    length = 12 + len(body)
+    return struct.pack('<II', block_type, length) + body + struct.pack('<I', length)
+
+
+def shb(options=b''):
+    return block(0x0A0D0D0A, struct.pack('<IHHq', 0x1A2B3C4D, 1, 0, -1) + options)
+
+
+def idb(linktype=1, snaplen=65535, options=b''):
+    return block(1, struct.pack('<HHI', linktype, 0, snaplen) + options)
+
+
+def epb(frame, ts, caplen=None, if_id=0, options=b''):
+    caplen = len(frame) if caplen is None else caplen
+    data = frame[:caplen] + b'\0' * ((-caplen) % 4)
+    return block(6, struct.pack('<IIIII', if_id, ts >> 32, ts & 0xFFFFFFFF, caplen, len(frame)) + data + options)
+
+
+def datagram(cmd, index, adp, ado, data, wkc, irq=0, circulating=False, more=False):
+    length_word = len(data) | (0x4000 if circulating else 0) | (0x8000 if more else 0)
+    return struct.pack('<BBHHHH', cmd, index, adp, ado, length_word, irq) + data + struct.pack('<H', wkc)
+
+
+def ecat_payload(datagrams):
+    body = b''.join(datagram(*d, more=i < len(datagrams) - 1) for i, d in enumerate(datagrams))
+    return struct.pack('<H', len(body) | 0x1000) + body
+
+
+def ethernet(ethertype, payload, src=MASTER_MAC, dst=BROADCAST, vlans=()):
+    header = dst + src
+    for tpid, vid in vlans:
+        header += struct.pack('>HH', tpid, vid)
+    frame = header + struct.pack('>H', ethertype) + payload
+    return frame + b'\0' * max(0, 60 - len(frame))
+
+
+def ecat(datagrams, **kwargs):
+    return ethernet(0x88A4, ecat_payload(datagrams), **kwargs)
+
+
+def udp_ecat(datagrams, **kwargs):
+    payload = ecat_payload(datagrams)
+    udp = struct.pack('>HHHH', 0x88A4, 0x88A4, 8 + len(payload), 0) + payload
+    ip = struct.pack('>BBHHHBBH4s4s', 0x45, 0, 20 + len(udp), 0, 0, 64, 17, 0,
+                     bytes([192, 168, 0, 1]), bytes([192, 168, 0, 255])) + udp
+    return ethernet(0x0800, ip, **kwargs)
+
+
+def option(code, value):
+    return struct.pack('<HH', code, len(value)) + value + b'\0' * ((-len(value)) % 4)
+
+
+def isb(ts, ifdrop, osdrop=None, if_id=0):
+    options = option(5, struct.pack('<Q', ifdrop))
+    if osdrop is not None:
+        options += option(7, struct.pack('<Q', osdrop))
+    options += option(0, b'')
+    return block(5, struct.pack('<III', if_id, ts >> 32, ts & 0xFFFFFFFF) + options)
+
+
+def capture(name, packets, ts=TS_BASE):
+    with open(name + '.pcapng', 'wb') as f:
+        f.write(shb() + idb())
+        for i, packet in enumerate(packets):
+            frame, caplen = packet if isinstance(packet, tuple) else (packet, None)
+            f.write(epb(frame, ts + i * 1000, caplen))
+
+
+def pcap(name, packets, nanoseconds=False, big_endian=False, fcs=False, ts=TS_BASE):
+    """Writes a classic pcap file, with an Ethernet FCS appended to each frame when `fcs`."""
+    order = '>' if big_endian else '<'
+    magic = 0xA1B23C4D if nanoseconds else 0xA1B2C3D4
+    # the FCS length of the link type field is in 16-bit words, flagged by bit 28
+    linktype = 1 | ((0x1000_0000 | (2 << 29)) if fcs else 0)
+    with open(name + '.pcap', 'wb') as f:
+        f.write(struct.pack(order + 'IHHiIII', magic, 2, 4, 0, 0, 65535, linktype))
+        for i, frame in enumerate(packets):
+            if fcs:
+                frame += struct.pack('<I', zlib.crc32(frame))
+            ts_us = ts + i * 1000
+            fraction = ts_us % 1_000_000 * (1000 if nanoseconds else 1)
+            f.write(struct.pack(order + 'IIII', ts_us // 1_000_000, fraction, len(frame), len(frame)) + frame)
+
+
+def mailbox(length, address, channel, priority, mbx_type, counter, payload):
+    return struct.pack('<HHBB', length, address, channel | (priority << 6), mbx_type | (counter << 4)) + payload
+
+
+def fmmu(logical_start, length, physical_start, fmmu_type, start_bit=0, end_bit=7, physical_bit=0, activate=1):
+    return struct.pack('<IHBBHBBBxxx', logical_start, length, start_bit, end_bit, physical_start, physical_bit,
+                       fmmu_type, activate)
+
+
+def sync_manager(start, length, control, activate=1):
+    return struct.pack('<HHBBBB', start, length, control, 0, activate, 0)
+
+
+MAILBOX_SIZE = 128
+# SM0 write mailbox (master to slave), SM1 read mailbox (slave to master)
+MAILBOX_SMS = sync_manager(0x1000, MAILBOX_SIZE, 0x26) + sync_manager(0x1080, MAILBOX_SIZE, 0x22)
+
+
+def mailbox_write(slave, message, index=0x30):
+    data = message + bytes(MAILBOX_SIZE - len(message))
+    return [ecat([(FPWR, index, slave, 0x1000, data, 0)]), ecat([(FPWR, index, slave, 0x1000, data, 1)], src=RETURN_MAC)]
+
+
+def mailbox_read(slave, message, index=0x31):
+    data = message + bytes(MAILBOX_SIZE - len(message))
+    return [ecat([(FPRD, index, slave, 0x1080, bytes(MAILBOX_SIZE), 0)]),
+            ecat([(FPRD, index, slave, 0x1080, data, 1)], src=RETURN_MAC)]
+
+
+def foe(opcode, field, payload=b''):
+    body = struct.pack('<BBI', opcode, 0, field) + payload
+    return mailbox(len(body), 0, 0, 0, 4, 1, body)
+
+
+def eoe_fragment(frame, fragment, offset_units, data, last):
+    info = fragment | (offset_units << 6) | (frame << 12)
+    body = struct.pack('<BBH', 0, 0x01 if last else 0, info) + data
+    return mailbox(len(body), 0, 0, 0, 2, 1, body)
+
+
+def sdo_download(index, subindex, value=None, complete=None):
+    if complete is None:
+        body = struct.pack('<HBHBI', 0x2000, 0x23, index, subindex, value)
+    else:
+        body = struct.pack('<HBHBI', 0x2000, 0x31, index, subindex, len(complete)) + complete
+    response = struct.pack('<HBHBI', 0x3000, 0x60, index, subindex, 0)
+    return mailbox(len(body), 0, 0, 0, 3, 1, body), mailbox(len(response), 0, 0, 0, 3, 1, response)
+
+
+def main():
+    lrw = (LRW, 0x01, 0x0000, 0x0000, bytes([0x01, 0x02, 0x03, 0x04]), 0x0003)
+    capture('single_datagram', [ecat([lrw[:5] + (0,)]), ecat([lrw], src=RETURN_MAC)])
+
+    cyclic_tx = [(LRW, 0x02, 0x0000, 0x0000, bytes(8), 0),
+                 (BRD, 0x03, 0x0000, 0x0130, bytes(2), 0),
+                 (FPRD, 0x04, 0x1001, 0x0130, bytes(2), 0)]
+    cyclic_rx = [(LRW, 0x02, 0x0000, 0x0000, bytes(range(8)), 3),
+                 (BRD, 0x03, 0x0000, 0x0130, bytes([0x08, 0x00]), 2),
+                 (FPRD, 0x04, 0x1001, 0x0130, bytes([0x08, 0x00]), 1)]
+    capture('chained_datagrams', [ecat(cyclic_tx), ecat(cyclic_rx, src=RETURN_MAC)])
+
+    # CoE SDO upload of 0x1018:01 (vendor id) through SM0 (0x1000) / SM1 (0x1080) of slave 0x1001
+    sdo_request = mailbox(10, 0, 0, 0, 3, 1, struct.pack('<HBHBI', 0x2000, 0x40, 0x1018, 0x01, 0))
+    sdo_response = mailbox(10, 0, 0, 0, 3, 1, struct.pack('<HBHBI', 0x3000, 0x43, 0x1018, 0x01, 0x00000002))
+    capture('mailbox', [
+        ecat([(FPWR, 0x10, 0x1001, 0x1000, sdo_request + bytes(128 - len(sdo_request)), 0)]),
+        ecat([(FPWR, 0x10, 0x1001, 0x1000, sdo_request + bytes(128 - len(sdo_request)), 1)], src=RETURN_MAC),
+        ecat([(FPRD, 0x11, 0x1001, 0x1080, bytes(128), 0)]),
+        ecat([(FPRD, 0x11, 0x1001, 0x1080, sdo_response + bytes(128 - len(sdo_response)), 1)], src=RETURN_MAC),
+    ])
+
+    # process data read between the messages, from a buffer that would pass for a mailbox of
+    # the slave without its SyncManagers
+    packets = [ecat([(FPWR, 0x01, 0x1001, 0x0800, MAILBOX_SMS + sync_manager(0x1100, 8, 0x20), 1)], src=RETURN_MAC)]
+    packets += mailbox_write(0x1001, sdo_request)
+    for i in range(4):
+        packets.append(ecat([(FPRD, 0x20 + i, 0x1001, 0x1100, bytes(8), 1)], src=RETURN_MAC))
+    packets += mailbox_read(0x1001, sdo_response)
+    capture('process_data_mailbox', packets)
+
+    # enough cycles to keep several worker threads busy in the parallel ordering tests
+    cycles = []
+    for i in range(200):
+        cycles.append(ecat([(LRW, i & 0xFF, 0x0000, 0x0000, bytes(8), 0)]))
+        cycles.append(ecat([(LRW, i & 0xFF, 0x0000, 0x0000, struct.pack('<Q', i), 3)], src=RETURN_MAC))
+    capture('cyclic', cycles)
+
+    # an NTP step backwards, a five second gap and capture drops reported by statistics blocks
+    frame = ecat([lrw], src=RETURN_MAC)
+    with open('clock_events.pcapng', 'wb') as f:
+        f.write(shb() + idb() + epb(frame, TS_BASE) + epb(frame, TS_BASE + 1000) + epb(frame, TS_BASE + 500)
+                + isb(TS_BASE + 600, 0) + epb(frame, TS_BASE + 5_000_500) + isb(TS_BASE + 5_000_600, 3)
+                + epb(frame, TS_BASE + 5_001_500) + isb(TS_BASE + 5_001_600, 3, 2))
+
+    # the second packet was captured with a 40 byte snap length
+    capture('truncated', [ecat(cyclic_rx, src=RETURN_MAC), (ecat(cyclic_rx, src=RETURN_MAC), 40)])
+
+    capture('vlan_tagged', [
+        ecat([lrw], src=RETURN_MAC, vlans=[(0x8100, 0x0064)]),
+        ecat([lrw], src=RETURN_MAC, vlans=[(0x88A8, 0x00C8), (0x8100, 0x0064)]),
+    ])
+
+    capture('udp_encapsulated', [udp_ecat([lrw]), udp_ecat(cyclic_rx, src=RETURN_MAC)])
+
+    # two EtherCAT frames in one Ethernet frame, then a short frame whose last datagram
+    # wrongly announces another one, followed by the Ethernet padding
+    brd = (BRD, 0x03, 0x0000, 0x0130, bytes([0x08, 0x00]), 0x0002)
+    fprd = (FPRD, 0x04, 0x1001, 0x0130, bytes([0x08, 0x00]), 0x0001)
+    bogus_chain = datagram(*fprd, more=True)
+    capture('chained_frames', [
+        ethernet(0x88A4, ecat_payload([lrw]) + ecat_payload([brd, fprd]), src=RETURN_MAC),
+        ethernet(0x88A4, struct.pack('<H', len(bogus_chain) | 0x1000) + bogus_chain, src=RETURN_MAC),
+    ])
+
+    # the NIC of the master also carries ARP, LLDP, other UDP traffic and a tagged ARP request
+    arp = struct.pack('>HHBBH', 1, 0x0800, 6, 4, 1) + MASTER_MAC + bytes([192, 168, 0, 1]) + bytes(6) + bytes([192, 168, 0, 2])
+    lldp = struct.pack('>HB6s', 0x0207, 0x04, MASTER_MAC) + struct.pack('>H', 0)
+    dns = struct.pack('>HHHH', 53000, 53, 8, 0)
+    dns_ip = struct.pack('>BBHHHBBH4s4s', 0x45, 0, 20 + len(dns), 0, 0, 64, 17, 0,
+                         bytes([192, 168, 0, 1]), bytes([192, 168, 0, 2])) + dns
+    capture('mixed_traffic', [
+        ethernet(0x0806, arp),
+        ecat(cyclic_tx),
+        ethernet(0x88CC, lldp, dst=bytes([0x01, 0x80, 0xC2, 0x00, 0x00, 0x0E])),
+        ecat(cyclic_rx, src=RETURN_MAC),
+        ethernet(0x0800, dns_ip),
+        ethernet(0x0806, arp, vlans=[(0x8100, 0x0064)]),
+    ])
+
+    # FMMU setup of two slaves followed by cyclic LRW exchanging 2 output and 2 input bytes each
+    fmmu_setup = [(FPWR, 0x20, 0x1001, 0x0600, fmmu(0x0000, 2, 0x1100, 2) + fmmu(0x0002, 2, 0x1180, 1), 1),
+                  (FPWR, 0x21, 0x1002, 0x0600, fmmu(0x0004, 2, 0x1100, 2), 1)]
+    packets = [ecat(fmmu_setup), ecat(fmmu_setup, src=RETURN_MAC)]
+    for i in range(4):
+        outputs = bytes([0x10 + i, 0x00, 0, 0, 0x20 + i, 0x00])
+        inputs = bytes([0x10 + i, 0x00, 0x30 + i, 0x01, 0x20 + i, 0x00])
+        packets.append(ecat([(LRW, i, 0x0000, 0x0000, outputs, 0)]))
+        packets.append(ecat([(LRW, i, 0x0000, 0x0000, inputs, 5)], src=RETURN_MAC))
+    capture('process_image', packets)
+
+    # a firmware download with one repeated data packet, then a read of a missing file
+    packets = [ecat([(FPWR, 0x01, 0x1001, 0x0800, MAILBOX_SMS, 1)], src=RETURN_MAC)]
+    packets += mailbox_write(0x1001, foe(2, 0, b'firmware.efw'))
+    packets += mailbox_read(0x1001, foe(4, 0))
+    for number, length in [(1, 116), (2, 116), (2, 116), (3, 10)]:
+        packets += mailbox_write(0x1001, foe(3, number, bytes(range(length))))
+        packets += mailbox_read(0x1001, foe(4, number))
+    packets += mailbox_write(0x1001, foe(1, 0, b'missing.txt'))
+    packets += mailbox_read(0x1001, foe(5, 0x8001, b'not found'))
+    capture('foe_update', packets)
+
+    # an ARP request tunnelled to the slave, a fragmented IPv4 reply and a frame missing its
+    # second fragment
+    arp = bytes(6) + MASTER_MAC + struct.pack('>H', 0x0806) + bytes(28)
+    ipv4 = BROADCAST + RETURN_MAC + struct.pack('>H', 0x0800) + bytes(range(186))
+    packets = [ecat([(FPWR, 0x01, 0x1001, 0x0800, MAILBOX_SMS, 1)], src=RETURN_MAC)]
+    packets += mailbox_write(0x1001, eoe_fragment(1, 0, 2, arp, True))
+    packets += mailbox_read(0x1001, eoe_fragment(2, 0, 7, ipv4[:96], False))
+    packets += mailbox_read(0x1001, eoe_fragment(2, 1, 3, ipv4[96:], True))
+    packets += mailbox_read(0x1001, eoe_fragment(3, 0, 7, ipv4[:96], False))
+    packets += mailbox_read(0x1001, eoe_fragment(4, 0, 2, arp, True))
+    capture('eoe', packets)
+
+    # the reference clock is 2.5 ms ahead of the capture clock and runs 20 ppm fast
+    dc_epoch_us = 946_684_800_000_000
+    packets = []
+    for i in range(10):
+        dc_time = (TS_BASE - dc_epoch_us) * 1000 + 2_500_000 + i * 1_000_020
+        packets.append(ecat([(FRMW, i, 0x1001, 0x0910, struct.pack('<Q', dc_time), 3)], src=RETURN_MAC))
+    capture('dc_sync', packets)
+
+    # the master sets up the clocks of three slaves, then reads their system time differences
+    # every cycle: slave 0x1002 converges, slave 0x1003 falls further behind the reference
+    setup = []
+    for k, (offset, delay) in enumerate([(0x1_0000_0000, 0), (0x2_0000_0000, 250), (0x3_0000_0000, 520)]):
+        setup += [(FPWR, 0x01, 0x1001 + k, 0x0920, struct.pack('<Q', offset), 0),
+                  (FPWR, 0x02, 0x1001 + k, 0x0928, struct.pack('<I', delay), 0)]
+    packets = [ecat(setup), ecat([d[:5] + (1,) for d in setup], src=RETURN_MAC)]
+    for i in range(10):
+        dc_time = 700_000_000_000_000_000 + i * 2_000_040
+        differences = [5 | (0x8000_0000 if i % 2 else 0), 800 >> i, 0x8000_0000 | (50 + 40 * i)]
+        reads = [(FRMW, i, 0x1001, 0x0910, bytes(8), 0)]
+        reads += [(FPRD, i, 0x1001 + k, 0x092C, bytes(4), 0) for k in range(3)]
+        answers = [(FRMW, i, 0x1001, 0x0910, struct.pack('<Q', dc_time), 3)]
+        answers += [(FPRD, i, 0x1001 + k, 0x092C, struct.pack('<I', d), 1) for k, d in enumerate(differences)]
+        packets += [ecat(reads), ecat(answers, src=RETURN_MAC)]
+    capture('dc_difference', packets)
+
+    # frames captured with their FCS: one FCS does not match and the device flagged a CRC error
+    # on another, which carries a bogus DC time
+    with_fcs = lambda frame: frame + struct.pack('<I', zlib.crc32(frame))
+    dc_frame = lambda i, dc: ecat([(FRMW, i, 0x1001, 0x0910, struct.pack('<Q', dc), 3)], src=RETURN_MAC)
+    dc_base = (TS_BASE - dc_epoch_us) * 1000
+    crc_error_flag = option(2, struct.pack('<I', 1 << 24)) + option(0, b'')
+    with open('link_errors.pcapng', 'wb') as f:
+        f.write(shb() + idb(options=option(13, b'\x04') + option(0, b'')))
+        f.write(epb(with_fcs(dc_frame(0, dc_base)), TS_BASE))
+        f.write(epb(with_fcs(dc_frame(1, dc_base + 1_000_000)), TS_BASE + 1000))
+        f.write(epb(with_fcs(dc_frame(2, dc_base + 2_000_000))[:-1] + b'\0', TS_BASE + 2000))
+        f.write(epb(with_fcs(dc_frame(3, dc_base)), TS_BASE + 3000, options=crc_error_flag))
+        f.write(epb(with_fcs(dc_frame(4, dc_base + 4_000_000)), TS_BASE + 4000))
+
+    # a drive gets its PDO mapping from expedited downloads (RxPDO) and complete access
+    # downloads (TxPDO), then exchanges controlword, statusword and actual position cyclically
+    rx_sms = MAILBOX_SMS + sync_manager(0x1100, 2, 0x64) + sync_manager(0x1180, 6, 0x20)
+    packets = [ecat([(FPWR, 0x01, 0x1001, 0x0800, rx_sms, 1)], src=RETURN_MAC)]
+    downloads = [sdo_download(0x1C12, 0, 0), sdo_download(0x1600, 0, 0), sdo_download(0x1600, 1, 0x60400010),
+                 sdo_download(0x1600, 0, 1), sdo_download(0x1C12, 1, 0x1600), sdo_download(0x1C12, 0, 1),
+                 sdo_download(0x1C13, 0, complete=struct.pack('<BxH', 1, 0x1A00)),
+                 sdo_download(0x1A00, 0, complete=struct.pack('<BxII', 2, 0x60410010, 0x60640020))]
+    for request, response in downloads:
+        packets += mailbox_write(0x1001, request)
+        packets += mailbox_read(0x1001, response)
+    fmmu_setup = [(FPWR, 0x02, 0x1001, 0x0600, fmmu(0x0000, 2, 0x1100, 2) + fmmu(0x0002, 6, 0x1180, 1), 1)]
+    packets.append(ecat(fmmu_setup, src=RETURN_MAC))
+    for i in range(3):
+        outputs = struct.pack('<H', 0x000F) + bytes(6)
+        inputs = struct.pack('<HHi', 0x000F, 0x0237, -1000 - i)
+        packets.append(ecat([(LRW, i, 0x0000, 0x0000, outputs, 0)]))
+        packets.append(ecat([(LRW, i, 0x0000, 0x0000, inputs, 3)], src=RETURN_MAC))
+    capture('pdo_mapping', packets)
+
+    # a configuration tool reads the device type, then gets SDO aborts for a missing object and
+    # for a write to a read-only entry
+    sdo = lambda command, index, subindex, field: mailbox(10, 0, 0, 0, 3, 1, struct.pack('<HBHBI', *command, index, subindex, field))
+    exchanges = [(sdo((0x2000, 0x40), 0x1000, 0, 0), sdo((0x3000, 0x43), 0x1000, 0, 0x00020192)),
+                 (sdo((0x2000, 0x40), 0x2100, 1, 0), sdo((0x3000, 0x80), 0x2100, 1, 0x06020000)),
+                 (sdo((0x2000, 0x2B), 0x1018, 1, 0x0002), sdo((0x3000, 0x80), 0x1018, 1, 0x06010002))]
+    packets = [ecat([(FPWR, 0x01, 0x1001, 0x0800, MAILBOX_SMS, 1)], src=RETURN_MAC)]
+    for request, response in exchanges:
+        packets += mailbox_write(0x1001, request)
+        packets += mailbox_read(0x1001, response)
+    capture('sdo_abort', packets)
+
+    # two drives in OP: 0x1002 reports an overcurrent by emergency, then 0x1001 a heartbeat
+    # timeout, and 0x1002 the reset of its error
+    emergency = lambda code, register, data, counter=1: mailbox(10, 0, 0, 0, 3, counter,
+                                                               struct.pack('<HHB', 0x1000, code, register) + data)
+    packets = [ecat([(FPWR, 0x01, 0x1001, 0x0800, MAILBOX_SMS, 1), (FPWR, 0x02, 0x1002, 0x0800, MAILBOX_SMS, 1)],
+                    src=RETURN_MAC)]
+    emergencies = {2: (0x1002, emergency(0x2310, 0x03, bytes([0x01, 0x00, 0x00, 0x00, 0x00]))),
+                   4: (0x1001, emergency(0x8130, 0x11, bytes(5))),
+                   6: (0x1002, emergency(0x0000, 0x00, bytes(5), counter=2))}
+    for i in range(8):
+        packets.append(ecat([(LRW, i, 0x0000, 0x0000, bytes(8), 0)]))
+        packets.append(ecat([(LRW, i, 0x0000, 0x0000, bytes(8), 6)], src=RETURN_MAC))
+        if i in emergencies:
+            packets += mailbox_read(*emergencies[i])
+    capture('emergency', packets)
+
+    # an input image that would pass for an emergency of the slave without its SyncManagers,
+    # read before and after the emergency it sends
+    image = emergency(0x5000, 0x01, bytes(5))
+    packets = [ecat([(FPWR, 0x01, 0x1001, 0x0800, MAILBOX_SMS + sync_manager(0x1100, len(image), 0x20), 1)],
+                    src=RETURN_MAC)]
+    for i in range(4):
+        packets.append(ecat([(FPRD, 0x20 + i, 0x1001, 0x1100, image, 1)], src=RETURN_MAC))
+        if i == 1:
+            packets += mailbox_read(0x1001, emergency(0x8130, 0x11, bytes(5)))
+    capture('process_data_emergency', packets)
+
+    # a slave keeps AL status change pending for 120 cycles, and SM2 for 100 of them
+    packets = []
+    for i in range(150):
+        irq = (0x0008 if 10 <= i < 130 else 0) | (0x0040 if 20 <= i < 120 else 0)
+        packets.append(ecat([(LRW, i, 0x0000, 0x0000, bytes(4), 0)]))
+        packets.append(ecat([(LRW, i, 0x0000, 0x0000, bytes(4), 1, irq)], src=RETURN_MAC))
+    capture('irq_storm', packets)
+
+    # a slave drops from OP to SAFEOP with the error bit set at cycle 25 and stays there
+    packets = []
+    for i in range(40):
+        status = 0x0008 if i < 25 else 0x0014
+        packets.append(ecat([(LRW, i, 0x0000, 0x0000, bytes(4), 0), (FPRD, i, 0x1001, 0x0130, bytes(2), 0)]))
+        packets.append(ecat([(LRW, i, 0x0000, 0x0000, bytes(4), 3),
+                             (FPRD, i, 0x1001, 0x0130, struct.pack('<H', status), 1)], src=RETURN_MAC))
+    capture('al_error', packets)
+
+    # a 4-channel digital input terminal mapped to the high nibble of logical byte 0x10, next to
+    # another terminal holding the low nibble at 0xF; channel 0 toggles every cycle
+    packets = [ecat([(FPWR, 0x01, 0x1002, 0x0600, fmmu(0x0010, 1, 0x1000, 1, start_bit=4), 1)], src=RETURN_MAC)]
+    for i in range(8):
+        packets.append(ecat([(LRD, i, 0x0010, 0x0000, bytes(1), 0)]))
+        packets.append(ecat([(LRD, i, 0x0010, 0x0000, bytes([(i % 4) << 4 | 0x0F]), 2)], src=RETURN_MAC))
+    capture('digital_io', packets)
+
+    # two taps recording at nanosecond resolution, the second one in the middle of the segment
+    # with a clock 2.5 ms ahead running 30 ppm fast; it started a cycle late and missed a frame
+    ns_idb = idb(options=option(9, b'\x09') + option(0, b''))
+    t0 = TS_BASE * 1000
+    tap_b_clock = lambda t: t + 2_500_000 + (t - t0) * 30 // 1_000_000
+    with open('align_a.pcapng', 'wb') as a, open('align_b.pcapng', 'wb') as b:
+        a.write(shb() + ns_idb)
+        b.write(shb() + ns_idb)
+        for i in range(20):
+            t = t0 + i * 1_000_000
+            out = ecat([(LRW, i, 0x0000, 0x0000, bytes(8), 0)])
+            processed = ecat([(LRW, i, 0x0000, 0x0000, struct.pack('<Q', i), 3)])
+            a.write(epb(out, t) + epb(ecat([(LRW, i, 0x0000, 0x0000, struct.pack('<Q', i), 3)], src=RETURN_MAC),
+                                         t + 20_000 + (i % 4) * 50))
+            if i > 0:
+                b.write(epb(out, tap_b_clock(t + 5_000 + (i % 3) * 100)))
+            if i > 0 and i != 7:
+                b.write(epb(processed, tap_b_clock(t + 15_000)))
+
+    # four slaves get their station addresses, then the master latches and reads their port
+    # receive times three times; the last slave takes 1.8 us more than the others to turn a frame
+    delays = [900, 600, 650, 2400]  # ns each slave adds, the first one including the master's link
+    loops = [sum(delays[k + 1:]) for k in range(4)]
+    clocks = [0x10000000, 0x7FFFF000, 0xFFFFFF00, 0x30000000]  # free-running local clocks
+    t0 = TS_BASE * 1000
+    with open('dc_latency.pcapng', 'wb') as f:
+        f.write(shb() + ns_idb)
+        for k in range(4):
+            assign = [(APWR, k, (-k) & 0xFFFF, 0x0010, struct.pack('<H', 0x1001 + k), 0)]
+            f.write(epb(ecat(assign), t0 + k * 10_000))
+            assign = [(APWR, k, (4 - k) & 0xFFFF, 0x0010, struct.pack('<H', 0x1001 + k), 1)]
+            f.write(epb(ecat(assign, src=RETURN_MAC), t0 + k * 10_000 + 5_000))
+        for i in range(3):
+            t = t0 + 1_000_000 * (i + 1)
+            jitter = [0, 10, -10][i]
+            f.write(epb(ecat([(BWR, i, 0x0000, 0x0900, bytes(4), 0)]), t))
+            f.write(epb(ecat([(BWR, i, 0x0004, 0x0900, bytes(4), 4)], src=RETURN_MAC), t + sum(delays) + jitter))
+            for k in range(4):
+                port0 = (clocks[k] + i * 1_000_000) & 0xFFFFFFFF
+                port1 = (port0 + loops[k] + (jitter if k == 2 else 0)) & 0xFFFFFFFF if k < 3 else 0
+                read = lambda times, wkc: [(FPRD, 0x10 + k, 0x1001 + k, 0x0900, times, wkc)]
+                f.write(epb(ecat(read(bytes(16), 0)), t + 100_000 + k * 10_000))
+                f.write(epb(ecat(read(struct.pack('<IIII', port0, port1, 0, 0), 1), src=RETURN_MAC),
+                            t + 105_000 + k * 10_000))
+
+    # slave 0x1001 stops answering for 5 ms and comes back in INIT; slave 0x1002 restarts its
+    # mailbox counter in the middle of the sequence, and is then sent to INIT by the master, which
+    # is no reboot; the master reads the identity of slave 0x1003 twice
+    def al_status(slave, index, state, wkc=1):
+        return [ecat([(FPRD, index, slave, 0x0130, bytes(2), 0)]),
+                ecat([(FPRD, index, slave, 0x0130, struct.pack('<H', state), wkc)], src=RETURN_MAC)]
+
+    def coe_reply(counter):
+        return mailbox(10, 0, 0, 0, 3, counter, struct.pack('<HBHBI', 0x3000, 0x60, 0x1018, 1, 0))
+
+    identity = struct.pack('<HHH', 0x0100, 0x0008, 0)
+    packets = [ecat([(FPWR, 0x01, 0x1002, 0x0800, MAILBOX_SMS, 1)], src=RETURN_MAC)]
+    for slave in [0x1001, 0x1003]:
+        packets.append(ecat([(FPWR, 0x02, slave, 0x0502, identity, 0)]))
+    for i in range(3):
+        packets += al_status(0x1001, 0x10 + i, 0x0008)
+        packets += mailbox_read(0x1002, coe_reply(i + 1))
+    packets += sum((al_status(0x1001, 0x20 + i, 0x0008, wkc=0) for i in range(5)), [])
+    packets += al_status(0x1001, 0x30, 0x0001)
+    packets += mailbox_read(0x1002, coe_reply(1))
+    packets.append(ecat([(FPWR, 0x03, 0x1003, 0x0502, identity, 0)]))
+    packets += al_status(0x1002, 0x31, 0x0004)
+    packets.append(ecat([(FPWR, 0x04, 0x1002, 0x0120, struct.pack('<H', 0x01), 0)]))
+    packets += al_status(0x1002, 0x32, 0x0001)
+    capture('slave_reboot', packets)
+
+    # the master brings two slaves up from INIT to OP; slave 0x1002 refuses SAFEOP with AL status
+    # code 0x001D, is acknowledged back to PREOP and stays there
+    def al_control(slave, index, control):
+        return ecat([(BWR if slave is None else FPWR, index, slave or 0, 0x0120, struct.pack('<H', control), 0)])
+
+    def al_status_code(slave, index, status, code=0):
+        return [ecat([(FPRD, index, slave, 0x0130, bytes(6), 0)]),
+                ecat([(FPRD, index, slave, 0x0130, struct.pack('<HHH', status, 0, code), 1)], src=RETURN_MAC)]
+
+    packets = [al_control(None, 0x01, 0x0011)]
+    packets += al_status_code(0x1001, 0x02, 0x0001) + al_status_code(0x1002, 0x03, 0x0001)
+    for index, state in [(0x10, 0x02), (0x20, 0x04)]:
+        packets += [al_control(0x1001, index, state), al_control(0x1002, index + 1, state)]
+        packets += al_status_code(0x1001, index + 2, 0x0001 if state == 0x02 else 0x0002)
+        packets += al_status_code(0x1001, index + 3, state)
+        packets += al_status_code(0x1002, index + 4, state if state == 0x02 else 0x0012, 0 if state == 0x02 else 0x001D)
+    packets += [al_control(0x1002, 0x30, 0x0012)]
+    packets += al_status_code(0x1002, 0x31, 0x0002)
+    packets += [al_control(0x1001, 0x40, 0x0008)]
+    packets += al_status_code(0x1001, 0x41, 0x0008)
+    capture('startup', packets)
+
+    # cycles of the network of network.xml: the second loses slave 0x1002 in the LRW, the third
+    # skips the AL status read of slave 0x1001, the fourth reads the AL status of a slave the ENI
+    # does not have and the fifth does not come back; a mailbox read runs between the cycles
+    packets = []
+    for i in range(6):
+        tx = [(LRW, 0x10 + i, 0x0000, 0x0000, bytes(8), 0), (BRD, 0x20 + i, 0x0000, 0x0130, bytes(2), 0)]
+        rx = [(LRW, 0x10 + i, 0x0000, 0x0000, bytes(range(8)), 2 if i == 1 else 3),
+              (BRD, 0x20 + i, 0x0000, 0x0130, bytes([0x08, 0x00]), 2)]
+        if i != 2:
+            tx.append((FPRD, 0x30 + i, 0x1001, 0x0130, bytes(2), 0))
+            rx.append((FPRD, 0x30 + i, 0x1001, 0x0130, bytes([0x08, 0x00]), 1))
+        if i == 3:
+            tx.append((FPRD, 0x40 + i, 0x1003, 0x0130, bytes(2), 0))
+            rx.append((FPRD, 0x40 + i, 0x1003, 0x0130, bytes([0x08, 0x00]), 1))
+        packets.append(ecat(tx))
+        if i != 4:
+            packets.append(ecat(rx, src=RETURN_MAC))
+        packets += [ecat([(FPRD, 0x50 + i, 0x1002, 0x1080, bytes(16), 0)]),
+                    ecat([(FPRD, 0x50 + i, 0x1002, 0x1080, bytes(16), 1)], src=RETURN_MAC)]
+    capture('eni_cycles', packets)
+
+    # the master gives positions 0 and 1 their station addresses and reads their identity from
+    # the SII by position, then reads the identity of slave 0x1003 by station address in one
+    # 8 byte read, and its revision and serial number once it is up; the product code of 0x1003
+    # is in none of the ESI files of esi/
+    def sii_read(command, slave, index, word, data):
+        read = APRD if command == APWR else FPRD
+        return [ecat([(command, index, slave, 0x0502, struct.pack('<HI', 0x0100, word), 0)]),
+                ecat([(command, index, slave, 0x0502, struct.pack('<HI', 0x0100, word), 1)], src=RETURN_MAC),
+                ecat([(read, index + 1, slave, 0x0502, bytes(6 + len(data)), 0)]),
+                ecat([(read, index + 1, slave, 0x0502, struct.pack('<HI', 0x0000, word) + data, 1)], src=RETURN_MAC)]
+
+    packets = []
+    identities = [(0x0000, 0x00000002, 0x03f03052), (0xFFFF, 0x00000002, 0x07d43052)]
+    for position, (adp, vendor, product) in enumerate(identities):
+        station = struct.pack('<H', 0x1001 + position)
+        packets += [ecat([(APWR, 0x01 + position, adp, 0x0010, station, 0)]),
+                    ecat([(APWR, 0x01 + position, adp + 1 & 0xFFFF, 0x0010, station, 1)], src=RETURN_MAC)]
+        packets += sii_read(APWR, adp, 0x10 + 4 * position, 0x0008, struct.pack('<I', vendor))
+        packets += sii_read(APWR, adp, 0x12 + 4 * position, 0x000A, struct.pack('<I', product))
+    packets += sii_read(FPWR, 0x1003, 0x20, 0x0008, struct.pack('<II', 0x00000002, 0x0c1e3052))
+    for i in range(2):
+        packets.append(ecat([(FPRD, 0x30 + i, 0x1001 + i, 0x0130, bytes(2), 0)]))
+        packets.append(ecat([(FPRD, 0x30 + i, 0x1001 + i, 0x0130, bytes([0x08, 0x00]), 1)], src=RETURN_MAC))
+    packets += sii_read(FPWR, 0x1003, 0x22, 0x000C, struct.pack('<II', 0x00110000, 123456))
+    capture('esi_scan', packets)
+
+    # 1 ms cycles, steady for the first 50 ms; then the cycle jitters, every tenth LRW comes back
+    # unanswered and the master adds an AL status read to every fifth cycle
+    packets = []
+    for i in range(100):
+        late = i >= 50
+        t = TS_BASE + i * 1000 + ([0, 200, 100][i % 3] if late else 0)
+        datagrams = [(LRW, i, 0x0000, 0x0000, bytes(4), 0)]
+        answered = [(LRW, i, 0x0000, 0x0000, bytes(4), 0 if late and i % 10 == 0 else 3)]
+        if late and i % 5 == 0:
+            datagrams.append((FPRD, i, 0x1001, 0x0130, bytes(2), 0))
+            answered.append((FPRD, i, 0x1001, 0x0130, struct.pack('<H', 0x0008), 1))
+        packets.append((ecat(datagrams), t))
+        packets.append((ecat(answered, src=RETURN_MAC), t + 20))
+    with open('long_run.pcapng', 'wb') as f:
+        f.write(shb() + idb() + b''.join(epb(frame, ts) for frame, ts in packets))
+
+    # slave 0x1001 is asked for OP before its SyncManagers are set up, slave 0x1002 has its read
+    # mailbox status polled with FPRD and gets SYNC0 activated, nobody distributing the system
+    # time; slave 0x1003 maps the status of its read mailbox through an FMMU and is not reported
+    mailbox_status = fmmu(0x0100, 1, 0x080D, 1, start_bit=3, end_bit=3, physical_bit=3)
+    setup = [[(FPWR, 0x01, 0x1001, 0x0120, struct.pack('<H', 0x08), 1)],
+             [(FPWR, 0x02, 0x1002, 0x0800, MAILBOX_SMS, 1)],
+             [(FPWR, 0x03, 0x1003, 0x0800, MAILBOX_SMS, 1), (FPWR, 0x04, 0x1003, 0x0600, mailbox_status, 1)]]
+    packets = []
+    for datagrams in setup:
+        packets += [ecat(datagrams), ecat(datagrams, src=RETURN_MAC)]
+    for i in range(3):
+        poll = [(FPRD, 0x10 + i, 0x1002, 0x0808, bytes(8), 0), (LRD, 0x20 + i, 0x0100, 0x0000, bytes(1), 0)]
+        packets += [ecat(poll), ecat(poll, src=RETURN_MAC)]
+    packets.append(ecat([(FPWR, 0x05, 0x1002, 0x0980, bytes([0x00, 0x03]), 0)]))
+    packets.append(ecat([(FPWR, 0x06, 0x1003, 0x0120, struct.pack('<H', 0x02), 0)]))
+    capture('lint', packets)
+
+    # the ring loses its link at cycle 3: frames come back marked as circulating for three
+    # cycles, and once more at cycle 8
+    packets = []
+    for i in range(10):
+        circulating = 3 <= i < 6 or i == 8
+        packets.append(ecat([(LRW, i, 0x0000, 0x0000, bytes(4), 0)]))
+        packets.append(ecat([(LRW, i, 0x0000, 0x0000, bytes(4), 2, 0, circulating),
+                             (BRD, i, 0x0000, 0x0130, bytes(2), 2)], src=RETURN_MAC))
+    capture('circulating', packets)
+
+    # a flaky cable: the frame of cycle 3 never comes back, cycle 6 comes back twice and cycle 8
+    # comes back circulating
+    packets = []
+    for i in range(10):
+        packets.append(ecat([(LRW, i, 0x0000, 0x0000, bytes(4), 0), (BRD, i, 0x0000, 0x0130, bytes(2), 0)]))
+        back = ecat([(LRW, i, 0x0000, 0x0000, bytes(4), 2, 0, i == 8), (BRD, i, 0x0000, 0x0130, bytes(2), 2)], src=RETURN_MAC)
+        packets += [back] * {3: 0, 6: 2}.get(i, 1)
+    capture('lost_frames', packets)
+
+    # a jumbo frame of another protocol, larger than the read buffer, between two EtherCAT
+    # frames, in both file formats
+    cycle = [(LRW, 0, 0x0000, 0x0000, bytes(4), 0)]
+    packets = [ecat(cycle), ethernet(0x88B5, bytes(i % 251 for i in range(100_000))), ecat(cycle, src=RETURN_MAC)]
+    capture('jumbo_frame', packets)
+    pcap('jumbo_frame_pcap', packets)
+
+    # a recording rotated into three files of two cycles each, the last one a classic pcap file
+    for part in range(3):
+        packets = []
+        for i in range(part * 2, part * 2 + 2):
+            packets += [ecat([(LRW, i, 0x0000, 0x0000, bytes(4), 0)]),
+                        ecat([(LRW, i, 0x0000, 0x0000, bytes([i, 0, 0, 0]), 3)], src=RETURN_MAC)]
+        write = pcap if part == 2 else capture
+        write('rotated_%05d' % (part + 1), packets, ts=TS_BASE + part * 4000)
+
+    # network variables of a publisher, a mailbox message through a mailbox gateway and a
+    # frame of an unknown type among the datagrams of a cycle: EtherCAT frames of other types
+    def typed_frame(frame_type, body):
+        return ethernet(0x88A4, struct.pack('<H', len(body) | frame_type << 12) + body)
+    variables = [(1, 0x1234, bytes([1, 2, 3, 4])), (2, 0x5678, bytes([5, 6]))]
+    network_variables = MASTER_MAC + struct.pack('<HHH', len(variables), 7, 0) + b''.join(
+        struct.pack('<HHHH', index, hash_, len(data), 1) + data for index, hash_, data in variables)
+    cycle = [(LRW, 0, 0x0000, 0x0000, bytes(4), 0)]
+    capture('frame_types', [
+        ecat(cycle),
+        typed_frame(4, network_variables),
+        typed_frame(5, sdo_download(0x6040, 0, 0x000F)[0]),
+        typed_frame(7, bytes(4)),
+        ecat(cycle, src=RETURN_MAC),
+    ])
+
+    # the master reads the error counters of two slaves every cycle; the cable between them
+    # corrupts frames, which 0x1002 counts as invalid frames and RX errors on port 0 and 0x1001,
+    # getting them back marked, as forwarded errors on port 1; the master clears the counters
+    # of 0x1002 in cycle 5, and its port 0 loses the link once after
+    def error_counters(invalid, rx, forwarded, lost):
+        counters = bytearray(20)
+        for port in range(4):
+            counters[2 * port], counters[2 * port + 1] = invalid[port], rx[port]
+            counters[8 + port], counters[16 + port] = forwarded[port], lost[port]
+        return bytes(counters)
+    zero = [0] * 4
+    corrupted = [0, 0, 1, 1, 2, 0, 1, 1]
+    forwarded = [0, 0, 1, 1, 2, 2, 3, 3]
+    rx_errors = [0, 0, 2, 2, 3, 0, 1, 1]
+    lost_links = [0, 0, 0, 0, 0, 0, 1, 1]
+    packets = []
+    for i in range(8):
+        first = error_counters([0, forwarded[i], 0, 0], zero, [0, forwarded[i], 0, 0], zero)
+        second = error_counters([corrupted[i], 0, 0, 0], [rx_errors[i], 0, 0, 0], zero, [lost_links[i], 0, 0, 0])
+        datagrams = [(LRW, i, 0x0000, 0x0000, bytes(4), 0), (FPRD, i, 0x1001, 0x0300, bytes(20), 0),
+                     (FPRD, i, 0x1002, 0x0300, bytes(20), 0)]
+        packets.append(ecat(datagrams))
+        packets.append(ecat([(LRW, i, 0x0000, 0x0000, bytes(4), 3), (FPRD, i, 0x1001, 0x0300, first, 1),
+                             (FPRD, i, 0x1002, 0x0300, second, 1)], src=RETURN_MAC))
+        if i == 4:
+            packets.append(ecat([(FPWR, 0x40, 0x1002, 0x0300, bytes(1), 0)]))
+            packets.append(ecat([(FPWR, 0x40, 0x1002, 0x0300, bytes(1), 1)], src=RETURN_MAC))
+    capture('port_errors', packets)
+
+    # the setup of two slaves: the mailbox SyncManagers of the first written through its
+    # position before its process data ones through its station address, an FMMU of the second
+    # moved and another left deactivated
+    def setup(datagrams):
+        outgoing = [(cmd, index, adp, ado, data, 0) for cmd, index, adp, ado, data, _ in datagrams]
+        return [ecat(outgoing), ecat(datagrams, src=RETURN_MAC)]
+    pd_sms = sync_manager(0x1100, 2, 0x64) + sync_manager(0x1180, 2, 0x20)
+    packets = setup([(APWR, 0x01, 0x0000, 0x0010, struct.pack('<H', 0x1001), 1),
+                     (APWR, 0x02, 0xFFFF, 0x0010, struct.pack('<H', 0x1002), 1)])
+    packets += setup([(APWR, 0x03, 0x0000, 0x0800, MAILBOX_SMS, 1)])
+    packets += setup([(FPWR, 0x04, 0x1001, 0x0810, pd_sms, 1),
+                      (FPWR, 0x05, 0x1002, 0x0800, MAILBOX_SMS + sync_manager(0x1100, 4, 0x64)
+                       + sync_manager(0x1180, 4, 0x20, activate=0), 1)])
+    packets += setup([(FPWR, 0x06, 0x1001, 0x0600, fmmu(0x0000, 2, 0x1100, 2) + fmmu(0x0002, 2, 0x1180, 1)
+                       + fmmu(0x0100, 1, 0x080D, 1, end_bit=0), 1),
+                      (FPWR, 0x07, 0x1002, 0x0600, fmmu(0x0004, 4, 0x1100, 2) + fmmu(0x0008, 4, 0x1180, 1, activate=0), 1)])
+    packets += setup([(FPWR, 0x08, 0x1002, 0x0600, fmmu(0x0010, 4, 0x1100, 2), 1)])
+    capture('slave_setup', packets)
+
+    # a mailbox stall: the slave rejects an SDO request with a mailbox error, the master writes
+    # the next request twice, a reply of the slave goes missing and the master toggles the
+    # repeat request of the read mailbox to have the last one sent again
+    def sdo_request(counter):
+        return mailbox(10, 0, 0, 0, 3, counter, struct.pack('<HBHBI', 0x2000, 0x40, 0x1018, 1, 0))
+
+    def sdo_reply(counter):
+        return mailbox(10, 0, 0, 0, 3, counter, struct.pack('<HBHBI', 0x3000, 0x43, 0x1018, 1, 0x00000002))
+    mailbox_error = mailbox(4, 0, 0, 0, 0, 1, struct.pack('<HH', 0x0001, 0x0002))
+    packets = [ecat([(FPWR, 0x01, 0x1001, 0x0800, MAILBOX_SMS, 1)], src=RETURN_MAC)]
+    packets += mailbox_write(0x1001, sdo_request(1))
+    packets += mailbox_read(0x1001, mailbox_error)
+    packets += mailbox_write(0x1001, sdo_request(2))
+    packets += mailbox_write(0x1001, sdo_request(2))
+    packets += mailbox_read(0x1001, sdo_reply(2))
+    packets += mailbox_write(0x1001, sdo_request(3))
+    packets += mailbox_read(0x1001, sdo_reply(4))
+    packets += [ecat([(FPWR, 0x40, 0x1001, 0x080E, bytes([0x03]), 1)], src=RETURN_MAC)]
+    packets += mailbox_read(0x1001, sdo_reply(4))
+    capture('mailbox_errors', packets)
+
+    # notes made at capture time: comments on the section, the interface and a packet, and
+    # custom blocks of a vendor (32473 is the PEN reserved for documentation), one of them text
+    lrw_out = ecat([(LRW, 0, 0x0000, 0x0000, bytes(4), 0)])
+    lrw_back = ecat([(LRW, 0, 0x0000, 0x0000, bytes(4), 3)], src=RETURN_MAC)
+    end = option(0, b'')
+    with open('comments.pcapng', 'wb') as f:
+        f.write(shb(option(1, b'line 2, night shift') + end) + idb(options=option(1, b'tap on port X1') + end))
+        f.write(epb(lrw_out, TS_BASE) + epb(lrw_back, TS_BASE + 20))
+        f.write(block(0x00000BAD, struct.pack('<I', 32473) + b'operator pressed "stop"'))
+        f.write(epb(lrw_out, TS_BASE + 1000, options=option(1, b'drive 3 rattles here') + end))
+        f.write(epb(lrw_back, TS_BASE + 1020))
+        f.write(block(0x40000BAD, struct.pack('<I', 32473) + bytes([0x01, 0x02, 0xFF, 0x00])))
+
+    # a tap wrapping EtherCAT in a vendor tunnel (EtherType 0x88B5, 4 byte tunnel header)
+    tunnel_header = struct.pack('>HH', 0x0001, 0x0000)
+    capture('vendor_tunnel', [
+        ethernet(0x88B5, tunnel_header + ecat_payload(cyclic_tx)),
+        ethernet(0x88B5, tunnel_header + ecat_payload(cyclic_rx), src=RETURN_MAC),
+    ])
+
+    # the exchange of chained_datagrams saved as classic pcap files
+    pcap('legacy_pcap', [ecat(cyclic_tx), ecat(cyclic_rx, src=RETURN_MAC)])
+    pcap('legacy_pcap_nanoseconds', [ecat(cyclic_tx), ecat(cyclic_rx, src=RETURN_MAC)],
+         nanoseconds=True, big_endian=True, fcs=True)
+
+    # copies compressed as a capture archive keeps them, gzip without a timestamp to stay reproducible
+    with open('chained_datagrams.pcapng', 'rb') as f, open('chained_datagrams.pcapng.gz', 'wb') as out:
+        out.write(gzip.compress(f.read(), mtime=0))
+    # the same archive without its trailer: gzip writes the whole capture, then fails
+    with open('chained_datagrams.pcapng.gz', 'rb') as f, open('truncated_archive.pcapng.gz', 'wb') as out:
+        out.write(f.read()[:-8])
+    with open('legacy_pcap.pcap', 'rb') as f, open('legacy_pcap.pcap.xz', 'wb') as out:
+        out.write(lzma.compress(f.read()))
+
+    # a power loss left garbage between two packets and cut the last one short
+    good = ecat([lrw], src=RETURN_MAC)
+    with open('corrupt_block.pcapng', 'wb') as f:
+        f.write(shb() + idb() + epb(good, TS_BASE) + b'\xde\xad\xbe\xef' * 5
+                + epb(ecat(cyclic_rx, src=RETURN_MAC), TS_BASE + 1000) + epb(good, TS_BASE + 2000)[:20])
+
+    # garbage in front of a jumbo frame of another protocol, larger than the read buffer
+    jumbo = ethernet(0x88B5, bytes(i % 251 for i in range(100_000)))
+    with open('corrupt_jumbo.pcapng', 'wb') as f:
+        f.write(shb() + idb() + epb(good, TS_BASE) + b'\xde\xad\xbe\xef' * 5
+                + epb(jumbo, TS_BASE + 1000) + epb(good, TS_BASE + 2000))
+
+
+if __name__ == '__main__':
+    main()
@@ -0,0 +1,1810 @@
+//! End-to-end regression tests: every fixture capture in `tests/fixtures` is exported in every
+//! output format and compared byte for byte with its golden file in `tests/golden`.
+//!
+//! Each export is also run with several worker threads, whose output must be identical.
+//!
+//! After an intended output change, regenerate the golden files with
+//! `UPDATE_GOLDEN=1 cargo test --test golden` and review the diff.
+
+use std::fs;
+use std::path::PathBuf;
+use std::process::{Command, Output};
+
+const FIXTURES_DIR: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fixtures");
+const GOLDEN_DIR: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/golden");
+
+/// Worker threads used for the parallel run of every export.
+const PARALLEL_JOBS: &str = "4";
+
+/// Output formats and the command line arguments selecting them.
+const FORMATS: &[(&str, &[&str])] = &[("csv", &[]), ("md", &["--format", "markdown-report"])];
+
+/// Fixtures are pcapng captures, except for the classic pcap files of the tests of that format.
+fn fixture_path(fixture: &str) -> PathBuf {
+    let pcapng = PathBuf::from(FIXTURES_DIR).join(format!("{}.pcapng", fixture));
+    if pcapng.exists() {
+        pcapng
+    } else {
+        PathBuf::from(FIXTURES_DIR).join(format!("{}.pcap", fixture))
+    }
+}
+
+fn run(fixture: &str, args: &[&str]) -> Output {
+    let input = fixture_path(fixture);
+    Command::new(env!("CARGO_BIN_EXE_ecat-packet-analyzer-rs"))
+        .arg("--file")
+        .arg(&input)
+        .args(args)
+        .output()
+        .expect("failed to run the exporter")
+}
+
+fn export(fixture: &str, args: &[&str]) -> String {
+    String::from_utf8(run(fixture, args).stdout).expect("output is not UTF-8")
+}
+
+fn diagnostics(fixture: &str, args: &[&str]) -> String {
+    String::from_utf8(run(fixture, args).stderr).expect("diagnostics are not UTF-8")
+}
+
+fn assert_golden(name: &str, actual: &str) {
+    let path = PathBuf::from(GOLDEN_DIR).join(name);
+    if std::env::var_os("UPDATE_GOLDEN").is_some() {
+        fs::write(&path, actual).expect("failed to write golden file");
+        return;
+    }
+    let expected = fs::read_to_string(&path)
+        .unwrap_or_else(|e| panic!("cannot read {} ({}), run with UPDATE_GOLDEN=1 to create it", path.display(), e));
+    assert_eq!(actual, expected, "output differs from {}", path.display());
+}
+
+/// Checks one fixture in every output format, with `extra_args` appended to each run.
+fn check_fixture(fixture: &str, variant: &str, extra_args: &[&str]) {
+    for (format, format_args) in FORMATS {
+        let args: Vec<&str> = format_args.iter().chain(extra_args).copied().collect();
+        let sequential = export(fixture, &args);
+        assert_golden(&format!("{}{}.{}", fixture, variant, format), &sequential);
+
+        let parallel_args: Vec<&str> = args.iter().copied().chain(["--jobs", PARALLEL_JOBS]).collect();
+        assert_eq!(export(fixture, &parallel_args), sequential, "--jobs {} changed the output order", PARALLEL_JOBS);
+    }
+}
+
+macro_rules! golden_tests {
+    ($($test:ident: $fixture:literal $(, $variant:literal [$($arg:literal),*])?;)*) => {
+        $(
+            #[test]
+            fn $test() {
+                check_fixture($fixture, golden_tests!(@variant $($variant)?), &[$($($arg),*)?]);
+            }
+        )*
+    };
+    (@variant $variant:literal) => { $variant };
+    (@variant) => { "" };
+}
+
+golden_tests! {
+    single_datagram: "single_datagram";
+    single_datagram_annotated: "single_datagram", "_annotated" ["--annotate", "tests/fixtures/annotations.csv"];
+    chained_datagrams: "chained_datagrams";
+    chained_datagrams_wkc: "chained_datagrams", "_wkc" ["--wkc-config", "tests/fixtures/wkc.toml"];
+    chained_datagrams_derive: "chained_datagrams", "_derive" [
+        "--derive", "low=u16le(data,0)&0x0f", "--derive", "scaled=i32le(data,4)*0.001", "--derive", "missing=3-wkc"
+    ];
+    chained_datagrams_labels: "chained_datagrams", "_labels" ["--labels", "tests/fixtures/labels.toml"];
+    chained_datagrams_semicolon: "chained_datagrams", "_semicolon" ["--delimiter", "semicolon", "--labels", "tests/fixtures/labels.toml"];
+    chained_datagrams_tab: "chained_datagrams", "_tab" ["--delimiter", "tab", "--derive", "scaled=i32le(data,4)*0.001"];
+    chained_datagrams_composition: "chained_datagrams", "_composition" ["--frame-composition"];
+    chained_datagrams_frame_number: "chained_datagrams", "_frame_number" ["--frame-number"];
+    chained_datagrams_cmd: "chained_datagrams", "_cmd" ["--cmd", "lrw,FPRD", "--frame-number"];
+    chained_datagrams_address_filter: "chained_datagrams", "_address_filter" ["--adp", "0x1001,0..=0", "--ado", "0x0130..0x0140"];
+    chained_datagrams_offsets: "chained_datagrams", "_offsets" ["--frame-hex", "--datagram-offset"];
+    chained_datagrams_oui: "chained_datagrams", "_oui" ["--oui", "tests/fixtures/manuf"];
+    chained_datagrams_register_names: "chained_datagrams", "_register_names" ["--register-names"];
+    chained_datagrams_hyphen_mac: "chained_datagrams", "_hyphen_mac" ["--mac-format", "hyphen"];
+    cyclic: "cyclic";
+    cyclic_only_changes: "cyclic", "_only_changes" ["--only-changes"];
+    cyclic_rate_limit: "cyclic", "_rate_limit" ["--rate-limit", "10/20ms"];
+    cyclic_timestamp: "cyclic", "_timestamp" ["--timestamp"];
+    cyclic_timestamp_iso8601: "cyclic", "_timestamp_iso8601" ["--timestamp", "--time-format", "iso8601"];
+    cyclic_timestamp_relative: "cyclic", "_timestamp_relative" ["--timestamp", "--time-format", "relative"];
+    cyclic_window: "cyclic", "_window" ["--from", "5ms", "--to", "1700000000.012", "--timestamp", "--time-format", "relative"];
+    foe_update_ids: "foe_update", "_ids" ["--ids"];
+    eoe_ids: "eoe", "_ids" ["--ids"];
+    mailbox_types: "mailbox", "_mailbox" ["--mailbox"];
+    foe_update_ids_mailbox: "foe_update", "_ids_mailbox" ["--ids", "--mailbox"];
+    sdo_abort_sdo: "sdo_abort", "_sdo" ["--sdo"];
+    mailbox_sdo: "mailbox", "_sdo" ["--mailbox", "--sdo"];
+    mailbox_register_names: "mailbox", "_register_names" ["--register-names", "--registers", "tests/fixtures/registers.toml"];
+    emergency: "emergency";
+    emergency_events: "emergency", "_events" ["--events"];
+    mailbox_errors_events: "mailbox_errors", "_events" ["--events", "--mailbox"];
+    mailbox: "mailbox";
+    truncated: "truncated";
+    vlan_tagged: "vlan_tagged";
+    vlan_tagged_vlan: "vlan_tagged", "_vlan" ["--vlan", "--datagram-offset"];
+    esi_scan_position: "esi_scan", "_position" ["--position"];
+    udp_encapsulated: "udp_encapsulated";
+    mixed_traffic: "mixed_traffic";
+    lost_frames: "lost_frames";
+    jumbo_frame: "jumbo_frame";
+    jumbo_frame_pcap: "jumbo_frame_pcap";
+    frame_types: "frame_types";
+    port_errors: "port_errors";
+    slave_setup: "slave_setup";
+    frame_types_network_variables: "frame_types", "_network_variables" ["--network-variables", "--frame-number"];
+    chained_frames: "chained_frames";
+    chained_frames_offset: "chained_frames", "_offset" ["--datagram-offset", "--frame-number"];
+    legacy_pcap: "legacy_pcap";
+    legacy_pcap_nanoseconds: "legacy_pcap_nanoseconds";
+    udp_encapsulated_offset: "udp_encapsulated", "_offset" ["--assume-ecat-offset", "42"];
+    udp_encapsulated_scan: "udp_encapsulated", "_scan" ["--scan-ecat"];
+    vendor_tunnel_scan: "vendor_tunnel", "_scan" ["--scan-ecat"];
+    vendor_tunnel_offset: "vendor_tunnel", "_offset" ["--assume-ecat-offset", "18"];
+    corrupt_block_recover: "corrupt_block", "_recover" ["--recover"];
+    corrupt_jumbo_recover: "corrupt_jumbo", "_recover" ["--recover"];
+    clock_events: "clock_events";
+    process_image: "process_image";
+    foe_update: "foe_update";
+    eoe: "eoe";
+    dc_sync_dc_time: "dc_sync", "_dc_time" ["--dc-time"];
+    link_errors: "link_errors";
+    pdo_mapping: "pdo_mapping";
+    pdo_mapping_pdo_map: "pdo_mapping", "_pdo_map" ["--pdo-map", "tests/fixtures/pdo_map.toml"];
+    irq_storm: "irq_storm";
+    irq_storm_irq_flags: "irq_storm", "_irq_flags" ["--irq-flags"];
+    circulating: "circulating";
+    comments: "comments";
+    comments_capture_comments: "comments", "_capture_comments" ["--capture-comments"];
+    al_error: "al_error";
+    digital_io: "digital_io";
+    dc_latency: "dc_latency";
+    dc_difference: "dc_difference";
+    lint: "lint";
+    lint_register_names: "lint", "_register_names" ["--register-names", "--registers", "tests/fixtures/registers.toml"];
+    long_run: "long_run";
+    slave_reboot: "slave_reboot";
+    startup: "startup";
+    eni_cycles: "eni_cycles";
+    eni_cycles_eni: "eni_cycles", "_eni" ["--eni", "tests/fixtures/network.xml"];
+    esi_scan: "esi_scan";
+    esi_scan_esi: "esi_scan", "_esi" ["--esi-dir", "tests/fixtures/esi"];
+}
+
+#[test]
+fn recover_diagnostics() {
+    assert_golden("corrupt_block_recover.stderr", &diagnostics("corrupt_block", &["--recover"]));
+}
+
+/// The block found after a corrupt region may be longer than the read buffer.
+#[test]
+fn recover_jumbo_diagnostics() {
+    assert_golden("corrupt_jumbo_recover.stderr", &diagnostics("corrupt_jumbo", &["--recover"]));
+}
+
+#[test]
+fn truncated_diagnostics() {
+    assert_golden("truncated.stderr", &diagnostics("truncated", &[]));
+}
+
+#[test]
+fn clock_event_diagnostics() {
+    assert_golden("clock_events.stderr", &diagnostics("clock_events", &[]));
+}
+
+#[test]
+fn circulating_diagnostics() {
+    assert_golden("circulating.stderr", &diagnostics("circulating", &[]));
+}
+
+#[test]
+fn frame_type_diagnostics() {
+    assert_golden("frame_types.stderr", &diagnostics("frame_types", &[]));
+}
+
+#[test]
+fn emergency_diagnostics() {
+    assert_golden("emergency.stderr", &diagnostics("emergency", &[]));
+}
+
+#[test]
+fn mailbox_error_diagnostics() {
+    assert_golden("mailbox_errors.stderr", &diagnostics("mailbox_errors", &[]));
+}
+
+#[test]
+fn irq_storm_diagnostics() {
+    assert_golden("irq_storm.stderr", &diagnostics("irq_storm", &[]));
+    assert_eq!(diagnostics("irq_storm", &["--irq-storm", "0"]), "", "--irq-storm 0 still reports storms");
+}
+
+#[test]
+fn slave_reboot_diagnostics() {
+    assert_golden("slave_reboot.stderr", &diagnostics("slave_reboot", &[]));
+}
+
+#[test]
+fn other_frame_diagnostics() {
+    assert_golden("mixed_traffic.stderr", &diagnostics("mixed_traffic", &[]));
+    assert_golden("mixed_traffic_include_other.stderr", &diagnostics("mixed_traffic", &["--include-other", "summary"]));
+}
+
+#[test]
+fn influx_line_protocol() {
+    let args = ["--labels", "tests/fixtures/labels.toml", "--format", "influx"];
+    assert_golden("chained_datagrams_influx.txt", &export("chained_datagrams", &args));
+    let filtered = export("chained_datagrams", &["--format", "influx", "--cmd", "LRW"]);
+    assert_golden("chained_datagrams_influx_lrw.txt", &filtered);
+    let window = export("chained_datagrams", &["--format", "influx", "--adp", "0x1001", "--from", "1ms"]);
+    assert_golden("chained_datagrams_influx_window.txt", &window);
+}
+
+#[test]
+fn process_image_snapshot() {
+    assert_golden("process_image_snapshot.txt", &export("process_image", &["snapshot", "--at", "4.5ms"]));
+}
+
+#[test]
+fn pdo_mapping_variables() {
+    assert_golden("pdo_mapping_pdos.csv", &export("pdo_mapping", &["pdo"]));
+    assert_golden("pdo_mapping_snapshot.txt", &export("pdo_mapping", &["snapshot", "--at", "1s"]));
+    let labels = ["--labels", "tests/fixtures/labels.toml"];
+    assert_golden("pdo_mapping_pdos_labels.csv", &export("pdo_mapping", &[&labels[..], &["pdo"]].concat()));
+}
+
+#[test]
+fn digital_io_signals() {
+    let signals = ["0x1002@0x1000.0:bool", "0x1002@0x1000.1:bool", "lrd@0x0010:u8"];
+    let args: Vec<&str> = ["signals", "--changes"].into_iter().chain(signals.iter().flat_map(|s| ["--extract-signal", s])).collect();
+    assert_golden("digital_io_signals.csv", &export("digital_io", &args));
+    assert_golden("pdo_mapping_signals.csv", &export("pdo_mapping", &["signals", "--extract-signal", "0x1001@0x1182:i32"]));
+}
+
+#[test]
+fn foe_sessions() {
+    assert_golden("foe_update_sessions.csv", &export("foe_update", &["foe"]));
+}
+
+#[test]
+fn eoe_statistics() {
+    assert_golden("eoe_statistics.csv", &export("eoe", &["eoe"]));
+}
+
+#[test]
+fn dc_correlation() {
+    assert_golden("dc_sync_correlation.txt", &export("dc_sync", &["dc"]));
+}
+
+#[test]
+fn dc_per_slave() {
+    assert_golden("dc_difference_per_slave.csv", &export("dc_difference", &["dc", "--per-slave"]));
+    assert_golden("dc_latency_per_slave.csv", &export("dc_latency", &["--labels", "tests/fixtures/labels.toml", "dc", "--per-slave"]));
+}
+
+#[test]
+fn dual_tap_alignment() {
+    let other = PathBuf::from(FIXTURES_DIR).join("align_b.pcapng");
+    let output = run("align_a", &["align", other.to_str().unwrap()]);
+    assert_golden("align_propagation.csv", &String::from_utf8(output.stdout).unwrap());
+    assert_golden("align_propagation.stderr", &String::from_utf8(output.stderr).unwrap());
+}
+
+#[test]
+fn ring_dump_on_al_error() {
+    let dir = PathBuf::from(env!("CARGO_TARGET_TMPDIR")).join("ring_al_error");
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(&dir).unwrap();
+    let args = ["ring", "--frames", "6", "--after", "3", "--dump-on", "al-error", "--output-dir", dir.to_str().unwrap()];
+    assert!(run("al_error", &args).status.success());
+    // the slave stays in error, which triggers a single dump at the frame reporting it.
+    let dumps: Vec<_> = fs::read_dir(&dir).unwrap().map(|entry| entry.unwrap().file_name()).collect();
+    assert_eq!(dumps, ["ring_52.pcapng"]);
+    let dump = Command::new(env!("CARGO_BIN_EXE_ecat-packet-analyzer-rs"))
+        .arg("--file")
+        .arg(dir.join("ring_52.pcapng"))
+        .output()
+        .expect("failed to run the exporter");
+    assert_golden("al_error_ring.csv", &String::from_utf8(dump.stdout).unwrap());
+}
+
+#[test]
+fn foe_extraction() {
+    let dir = PathBuf::from(env!("CARGO_TARGET_TMPDIR")).join("foe_extract");
+    let _ = fs::remove_dir_all(&dir);
+    let output = run("foe_update", &["extract-foe", "--output-dir", dir.to_str().unwrap()]);
+    assert!(output.status.success());
+    let listing = String::from_utf8(output.stdout).unwrap().replace(dir.to_str().unwrap(), "<dir>");
+    assert_golden("foe_update_extract.csv", &listing);
+    // the repeated second packet is written once.
+    let firmware: Vec<u8> = [116, 116, 10].into_iter().flat_map(|length| 0..length).collect();
+    assert_eq!(fs::read(dir.join("3.0_firmware.efw")).unwrap(), firmware);
+    assert_eq!(fs::read(dir.join("23.0_missing.txt")).unwrap(), b"");
+}
+
+#[test]
+fn signal_extraction() {
+    let pdo_map = ["--pdo-map", "tests/fixtures/pdo_map.toml", "extract-signals"];
+    let wide = export("pdo_mapping", &pdo_map);
+    assert_golden("pdo_mapping_extract_signals.csv", &wide);
+
+    let dir = PathBuf::from(env!("CARGO_TARGET_TMPDIR")).join("signals");
+    let _ = fs::remove_dir_all(&dir);
+    let args = [&pdo_map[..], &["--signal", "position_mm", "--signal", "controlword", "--output-dir", dir.to_str().unwrap()]].concat();
+    let output = run("pdo_mapping", &args);
+    assert!(output.status.success());
+    let listing = String::from_utf8(output.stdout).unwrap().replace(dir.to_str().unwrap(), "<dir>");
+    assert_golden("pdo_mapping_extract_signals_files.csv", &listing);
+    // every file holds the column of its signal in the wide CSV.
+    let rows: Vec<Vec<&str>> = wide.lines().map(|line| line.split(',').collect()).collect();
+    for signal in ["position_mm", "controlword"] {
+        let column = rows[0].iter().position(|name| *name == signal).unwrap();
+        let expected: Vec<String> = rows[1..].iter().map(|row| format!("{},{}", row[..4].join(","), row[column])).collect();
+        let series = fs::read_to_string(dir.join(format!("{}.csv", signal))).unwrap();
+        assert_eq!(series.lines().skip(1).collect::<Vec<_>>(), expected);
+    }
+}
+
+#[test]
+fn eoe_frames_to_pcapng() {
+    let path = PathBuf::from(env!("CARGO_TARGET_TMPDIR")).join("eoe_tunnel.pcapng");
+    let output = run("eoe", &["eoe", "--eoe-out", path.to_str().unwrap()]);
+    assert!(output.status.success());
+    assert_golden("eoe_statistics.csv", &String::from_utf8(output.stdout).unwrap());
+    // EtherType and length of the frame of every enhanced packet block, in the order written.
+    let dump = fs::read(&path).unwrap();
+    let mut frames = Vec::new();
+    let mut offset = 0;
+    while offset < dump.len() {
+        let word = |at: usize| u32::from_le_bytes(dump[at..at + 4].try_into().unwrap());
+        if word(offset) == 6 {
+            let frame = &dump[offset + 28..offset + 28 + word(offset + 20) as usize];
+            frames.push((u16::from_be_bytes([frame[12], frame[13]]), frame.len()));
+        }
+        offset += word(offset + 4) as usize;
+    }
+    // the frame missing its second fragment is not written.
+    assert_eq!(frames, [(0x0806, 42), (0x0800, 200), (0x0806, 42)]);
+}
+
+#[test]
+fn al_state_timeline() {
+    assert_golden("startup_states.csv", &export("startup", &["states"]));
+    assert_golden("startup_states_labels.csv", &export("startup", &["--labels", "tests/fixtures/labels.toml", "states"]));
+    assert_golden("slave_reboot_states.csv", &export("slave_reboot", &["states"]));
+}
+
+#[test]
+fn paired_transactions() {
+    assert_golden("dc_latency_pairs.csv", &export("dc_latency", &["--pair"]));
+    assert_golden("slave_reboot_pairs.csv", &export("slave_reboot", &["--pair"]));
+}
+
+#[test]
+fn cycle_stats() {
+    assert_golden("long_run_stats.csv", &export("long_run", &["stats"]));
+    assert_golden("cyclic_stats.csv", &export("cyclic", &["stats"]));
+}
+
+#[test]
+fn latency_attribution() {
+    assert_golden("dc_latency_attribution.csv", &export("dc_latency", &["latency"]));
+    let labelled = export("dc_latency", &["--labels", "tests/fixtures/labels.toml", "latency"]);
+    assert_golden("dc_latency_attribution_labels.csv", &labelled);
+}
+
+#[test]
+fn port_error_counters() {
+    assert_golden("port_errors_errors.csv", &export("port_errors", &["errors"]));
+    assert_golden("port_errors_changes.csv", &export("port_errors", &["errors", "--changes"]));
+    let labelled = export("port_errors", &["--labels", "tests/fixtures/labels.toml", "errors"]);
+    assert_golden("port_errors_errors_labels.csv", &labelled);
+}
+
+#[test]
+fn slave_setup_config() {
+    assert_golden("slave_setup_config.csv", &export("slave_setup", &["config"]));
+    let labelled = export("slave_setup", &["--labels", "tests/fixtures/labels.toml", "config"]);
+    assert_golden("slave_setup_config_labels.csv", &labelled);
+}
+
+#[test]
+fn state_query() {
+    assert_golden("slave_reboot_state_10ms.csv", &export("slave_reboot", &["state", "--at", "10ms"]));
+    let labelled = export("slave_reboot", &["--labels", "tests/fixtures/labels.toml", "state", "--at", "1000s"]);
+    assert_golden("slave_reboot_state_labels.csv", &labelled);
+}
+
+#[test]
+fn group_by_cycle() {
+    assert_golden("long_run_cycles.csv", &export("long_run", &["--group-by", "cycle"]));
+    let checked = export("chained_datagrams", &["--group-by", "cycle", "--wkc-config", "tests/fixtures/wkc.toml"]);
+    assert_golden("chained_datagrams_cycles.csv", &checked);
+}
+
+#[test]
+fn diff_windows() {
+    let diff = export("long_run", &["diff-windows", "--a", "0..50ms", "--b", "50..100ms"]);
+    assert_golden("long_run_diff_windows.csv", &diff);
+}
+
+#[test]
+fn lint_rules() {
+    assert_golden("lint_violations.csv", &export("lint", &["lint"]));
+    assert_golden("lint_violations_labels.csv", &export("lint", &["--labels", "tests/fixtures/labels.toml", "lint"]));
+}
+
+#[test]
+fn slave_summary() {
+    assert_golden("startup_slaves.csv", &export("startup", &["--labels", "tests/fixtures/labels.toml", "slaves"]));
+    assert_golden("mailbox_slaves.csv", &export("mailbox", &["slaves"]));
+    assert_golden("esi_scan_slaves.csv", &export("esi_scan", &["slaves"]));
+    assert_golden("slave_reboot_slaves.csv", &export("slave_reboot", &["slaves"]));
+}
+
+#[test]
+fn topology() {
+    assert_golden("esi_scan_topology.csv", &export("esi_scan", &["--labels", "tests/fixtures/labels.toml", "topology"]));
+    assert_golden("dc_latency_topology.csv", &export("dc_latency", &["topology"]));
+    assert_golden("chained_datagrams_topology.csv", &export("chained_datagrams", &["topology"]));
+}
+
+#[test]
+fn frame_losses() {
+    assert_golden("lost_frames_losses.csv", &export("lost_frames", &["losses"]));
+    assert_golden("lost_frames_losses.stderr", &diagnostics("lost_frames", &["losses"]));
+    assert_golden("circulating_losses.csv", &export("circulating", &["losses"]));
+}
+
+#[test]
+fn wkc_mismatches() {
+    assert_golden("chained_datagrams_wkc_mismatches.csv", &export("chained_datagrams", &["--wkc-config", "tests/fixtures/wkc.toml", "wkc"]));
+    let args = ["--wkc-config", "tests/fixtures/wkc.toml", "--expected-wkc", "brd=1", "--labels", "tests/fixtures/labels.toml", "wkc"];
+    assert_golden("chained_datagrams_expected_wkc.csv", &export("chained_datagrams", &args));
+
+    // a write adds 1 per slave, and the returned auto-increment commands are not checked.
+    for (fixture, checked) in [("slave_setup", 5), ("mailbox", 2), ("esi_scan", 6)] {
+        let args = ["--wkc-config", "tests/fixtures/wkc_setup.toml", "wkc"];
+        assert_eq!(export(fixture, &args), "frame,time,cmd,index,adp,ado,wkc,expected\n", "mismatches of {}", fixture);
+        let summary = format!("0 of {} datagrams with an expected working counter differ from it", checked);
+        assert!(diagnostics(fixture, &args).starts_with(&summary), "diagnostics of {}", fixture);
+    }
+}
+
+#[test]
+fn eni_check() {
+    let args = ["--eni", "tests/fixtures/network.xml", "check-eni"];
+    assert_golden("eni_cycles_check.csv", &export("eni_cycles", &args));
+    assert_golden("eni_cycles_check.stderr", &diagnostics("eni_cycles", &args));
+    assert_golden("eni_cycles_eni_mismatches.csv", &export("eni_cycles", &["--eni", "tests/fixtures/network.xml", "wkc"]));
+}
+
+#[test]
+fn warnings_out_sidecar() {
+    let path = PathBuf::from(env!("CARGO_TARGET_TMPDIR")).join("warnings.csv");
+    run("corrupt_block", &["--recover", "--warnings-out", path.to_str().unwrap()]);
+    assert_golden("corrupt_block_warnings.csv", &fs::read_to_string(&path).expect("no warnings file"));
+    run("chained_datagrams", &["--wkc-config", "tests/fixtures/wkc.toml", "--warnings-out", path.to_str().unwrap()]);
+    assert_golden("chained_datagrams_wkc_warnings.csv", &fs::read_to_string(&path).expect("no warnings file"));
+
+    // the exports without a warnings column record the same findings.
+    for format in ["influx", "parquet", "arrow"] {
+        let tmp = PathBuf::from(env!("CARGO_TARGET_TMPDIR"));
+        let output = tmp.join(format!("warnings.{}", format));
+        let output_args: Vec<&str> = if format == "influx" { vec![] } else { vec!["--output", output.to_str().unwrap()] };
+        for (fixture, golden, args) in [("corrupt_block", "corrupt_block_warnings.csv", &["--recover"][..]), ("chained_datagrams", "chained_datagrams_wkc_warnings.csv", &["--wkc-config", "tests/fixtures/wkc.toml"])] {
+            let args: Vec<&str> = args.iter().copied().chain(["--format", format, "--warnings-out", path.to_str().unwrap()]).chain(output_args.iter().copied()).collect();
+            run(fixture, &args);
+            assert_eq!(fs::read_to_string(&path).expect("no warnings file"), fs::read_to_string(PathBuf::from(GOLDEN_DIR).join(golden)).unwrap(), "{} --format {}", fixture, format);
+        }
+    }
+
+    assert_golden("corrupt_block_inline_warnings.jsonl", &export("corrupt_block", &["--recover", "--format", "jsonl", "--inline-warnings"]));
+    assert_golden("chained_datagrams_inline_warnings.jsonl", &export("chained_datagrams", &["--wkc-config", "tests/fixtures/wkc.toml", "--format", "jsonl", "--inline-warnings"]));
+    for (args, error) in [
+        (&["--warnings-out", path.to_str().unwrap(), "--format", "markdown-report"][..], "--warnings-out applies to the csv, jsonl, influx, parquet and arrow exports only\n"),
+        (&["--warnings-out", path.to_str().unwrap(), "states"], "--warnings-out applies to the csv, jsonl, influx, parquet and arrow exports only\n"),
+        (&["--inline-warnings"], "--inline-warnings applies to the jsonl export only, the others take --warnings-out\n"),
+    ] {
+        let output = run("chained_datagrams", args);
+        assert!(!output.status.success());
+        assert_eq!(String::from_utf8(output.stderr).unwrap(), error);
+    }
+}
+
+#[test]
+fn batch_jobs() {
+    let dir = PathBuf::from(env!("CARGO_TARGET_TMPDIR"));
+    let (export_path, foe_path) = (dir.join("batch_export.csv"), dir.join("batch_foe.csv"));
+    let jobs = format!(
+        "[[job]]\ninput = '{}/chained_datagrams.pcapng'\noutput = '{}'\nargs = [\"--wkc-config\", \"tests/fixtures/wkc.toml\"]\n\n\
+         [[job]]\ninput = '{}/foe_update.pcapng'\noutput = '{}'\nargs = [\"foe\"]\n",
+        FIXTURES_DIR, export_path.display(), FIXTURES_DIR, foe_path.display());
+    let jobs_path = dir.join("jobs.toml");
+    fs::write(&jobs_path, jobs).unwrap();
+    let output = Command::new(env!("CARGO_BIN_EXE_ecat-packet-analyzer-rs"))
+        .arg("batch")
+        .arg(&jobs_path)
+        .output()
+        .expect("failed to run the exporter");
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+    // each job writes what the same single run writes to stdout.
+    assert_golden("chained_datagrams_wkc.csv", &fs::read_to_string(&export_path).expect("no export output"));
+    assert_golden("foe_update_sessions.csv", &fs::read_to_string(&foe_path).expect("no foe output"));
+}
+
+#[test]
+fn backpressure_spill_keeps_order() {
+    // a one byte buffer spills nearly everything, which must come back in order.
+    for jobs in ["1", "4"] {
+        let output = run("cyclic", &["--backpressure", "spill", "--output-buffer", "1", "--jobs", jobs]);
+        assert_golden("cyclic.csv", &String::from_utf8(output.stdout).unwrap());
+        assert!(String::from_utf8_lossy(&output.stderr).contains("Spilled"));
+    }
+}
+
+#[test]
+fn mailbox_jsonl() {
+    let output = export("foe_update", &["--format", "jsonl", "--ids", "--mailbox"]);
+    assert_golden("foe_update_mailbox.jsonl", &output);
+    let output = export("sdo_abort", &["--format", "jsonl", "--sdo"]);
+    assert_golden("sdo_abort_sdo.jsonl", &output);
+    let output = export("pdo_mapping", &["--format", "jsonl", "--pdo-map", "tests/fixtures/pdo_map.toml"]);
+    assert_golden("pdo_mapping_pdo_map.jsonl", &output);
+}
+
+#[test]
+fn jsonl_export() {
+    let args = [
+        "--format", "jsonl", "--wkc-config", "tests/fixtures/wkc.toml", "--labels", "tests/fixtures/labels.toml",
+        "--derive", "low=u16le(data,0)&0x0f", "--frame-composition",
+    ];
+    let output = export("chained_datagrams", &args);
+    for line in output.lines() {
+        serde_json::from_str::<serde_json::Value>(line).unwrap_or_else(|e| panic!("{} is not JSON ({})", line, e));
+    }
+    assert_golden("chained_datagrams.jsonl", &output);
+
+    let parallel_args: Vec<&str> = args.iter().copied().chain(["--jobs", PARALLEL_JOBS]).collect();
+    assert_eq!(export("chained_datagrams", &parallel_args), output, "--jobs {} changed the output order", PARALLEL_JOBS);
+    let auto_args: Vec<&str> = args.iter().copied().chain(["--jobs", "auto"]).collect();
+    assert_eq!(export("chained_datagrams", &auto_args), output, "--jobs auto changed the output order");
+    assert!(!run("chained_datagrams", &["--jobs", "0"]).status.success());
+
+    // times are nanoseconds in JSON, as time_ns is.
+    assert_golden("dc_sync_dc_time.jsonl", &export("dc_sync", &["--format", "jsonl", "--dc-time"]));
+}
+
+#[test]
+fn column_selection() {
+    let args = ["--columns", "cmd,adp,ado,wkc,ts,label", "--labels", "tests/fixtures/labels.toml"];
+    assert_golden("chained_datagrams_columns.csv", &export("chained_datagrams", &args));
+    let jsonl_args: Vec<&str> = args.iter().copied().chain(["--format", "jsonl"]).collect();
+    assert_golden("chained_datagrams_columns.jsonl", &export("chained_datagrams", &jsonl_args));
+    // the CSV columns of the SDO and its JSON object can be picked in either export.
+    let args = ["--sdo", "--columns", "frame,sdo_index,sdo,cmd", "--delimiter", "semicolon"];
+    assert_golden("sdo_abort_columns.csv", &export("sdo_abort", &args));
+    let args = ["--sdo", "--columns", "frame,sdo_index,sdo,cmd", "--format", "jsonl"];
+    assert_golden("sdo_abort_columns.jsonl", &export("sdo_abort", &args));
+
+    let output = run("chained_datagrams", &["--columns", "cmd,timestamp"]);
+    assert!(!output.status.success());
+    assert_golden("chained_datagrams_columns_unknown.stderr", &String::from_utf8(output.stderr).unwrap());
+    assert!(!run("chained_datagrams", &["--columns", "cmd", "stats"]).status.success());
+}
+
+#[test]
+fn output_file() {
+    let golden = fs::read_to_string(PathBuf::from(GOLDEN_DIR).join("comments.csv")).unwrap();
+    let (diagnostics, rows): (Vec<&str>, Vec<&str>) =
+        golden.lines().partition(|line| *line == "unknown block" || line.starts_with("num_blocks: "));
+    let path = PathBuf::from(env!("CARGO_TARGET_TMPDIR")).join("output_file.csv");
+    for jobs in ["1", "4"] {
+        let output = run("comments", &["--output", path.to_str().unwrap(), "--jobs", jobs]);
+        assert_eq!(fs::read_to_string(&path).unwrap(), rows.iter().map(|row| format!("{}\n", row)).collect::<String>());
+        assert_eq!(String::from_utf8(output.stdout).unwrap(), diagnostics.iter().map(|line| format!("{}\n", line)).collect::<String>());
+    }
+}
+
+/// The Parquet export is compared as a hex dump, with its footer checked: the metadata length
+/// in front of the closing magic.
+#[test]
+fn parquet_export() {
+    for (suffix, filter) in [("", &[][..]), ("_lrw", &["--cmd", "LRW"][..])] {
+        let path = PathBuf::from(env!("CARGO_TARGET_TMPDIR")).join(format!("chained_datagrams{}.parquet", suffix));
+        let output = run("chained_datagrams", &[&["--format", "parquet", "--output", path.to_str().unwrap()], filter].concat());
+        assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+        let bytes = fs::read(&path).unwrap();
+        assert!(bytes.starts_with(b"PAR1") && bytes.ends_with(b"PAR1"));
+        let footer = u32::from_le_bytes(bytes[bytes.len() - 8..bytes.len() - 4].try_into().unwrap()) as usize;
+        assert!(footer + 12 < bytes.len());
+        let dump: String = bytes.chunks(16).map(|line| line.iter().map(|byte| format!("{:02x}", byte)).collect::<String>() + "\n").collect();
+        assert_golden(&format!("chained_datagrams_parquet{}.hex", suffix), &dump);
+    }
+}
+
+/// A value of the Thrift compact protocol that the Parquet metadata is encoded with.
+#[derive(Debug)]
+enum Thrift {
+    Bool(bool),
+    Int(i64),
+    Binary(Vec<u8>),
+    List(Vec<Thrift>),
+    Struct(Vec<(i16, Thrift)>),
+}
+
+impl Thrift {
+    fn field(&self, id: i16) -> Option<&Thrift> {
+        let Thrift::Struct(fields) = self else { panic!("not a struct: {:?}", self) };
+        fields.iter().find(|(field, _)| *field == id).map(|(_, value)| value)
+    }
+
+    /// The integer of a field the struct must have.
+    fn int(&self, id: i16) -> i64 {
+        match self.field(id) {
+            Some(Thrift::Int(value)) => *value,
+            value => panic!("field {} is not an integer: {:?}", id, value),
+        }
+    }
+
+    fn binary(&self, id: i16) -> &[u8] {
+        match self.field(id) {
+            Some(Thrift::Binary(value)) => value,
+            value => panic!("field {} is not binary: {:?}", id, value),
+        }
+    }
+
+    fn list(&self, id: i16) -> &[Thrift] {
+        match self.field(id) {
+            Some(Thrift::List(values)) => values,
+            value => panic!("field {} is not a list: {:?}", id, value),
+        }
+    }
+}
+
+/// Reads the Thrift compact protocol, written by the exporter with an encoder of its own.
+struct ThriftReader<'a> {
+    bytes: &'a [u8],
+    pos: usize,
+}
+
+impl ThriftReader<'_> {
+    fn byte(&mut self) -> u8 {
+        self.pos += 1;
+        self.bytes[self.pos - 1]
+    }
+
+    fn varint(&mut self) -> u64 {
+        let (mut value, mut shift) = (0, 0);
+        loop {
+            let byte = self.byte();
+            value |= ((byte & 0x7F) as u64) << shift;
+            if byte & 0x80 == 0 {
+                return value;
+            }
+            shift += 7;
+        }
+    }
+
+    fn zigzag(&mut self) -> i64 {
+        let value = self.varint();
+        (value >> 1) as i64 ^ -((value & 1) as i64)
+    }
+
+    fn value(&mut self, value_type: u8) -> Thrift {
+        match value_type {
+            1 | 2 => Thrift::Bool(value_type == 1),
+            3 => Thrift::Int(self.byte() as i8 as i64),
+            4..=6 => Thrift::Int(self.zigzag()),
+            8 => {
+                let len = self.varint() as usize;
+                self.pos += len;
+                Thrift::Binary(self.bytes[self.pos - len..self.pos].to_vec())
+            }
+            9 | 10 => {
+                let header = self.byte();
+                let len = if header >> 4 == 15 { self.varint() as usize } else { (header >> 4) as usize };
+                // the booleans of a list are a byte each, not field types
+                Thrift::List((0..len).map(|_| match header & 0x0F {
+                    1 | 2 => Thrift::Bool(self.byte() == 1),
+                    element_type => self.value(element_type),
+                }).collect())
+            }
+            12 => self.structure(),
+            _ => panic!("unexpected Thrift type {} at {}", value_type, self.pos),
+        }
+    }
+
+    fn structure(&mut self) -> Thrift {
+        let (mut fields, mut last) = (Vec::new(), 0);
+        loop {
+            let header = self.byte();
+            if header == 0 {
+                return Thrift::Struct(fields);
+            }
+            let id = match header >> 4 {
+                0 => self.zigzag() as i16,
+                delta => last + delta as i16,
+            };
+            last = id;
+            fields.push((id, self.value(header & 0x0F)));
+        }
+    }
+}
+
+/// Decompresses a block of the Snappy format: literals, and copies of the output so far.
+fn unsnappy(input: &[u8]) -> Vec<u8> {
+    let mut reader = ThriftReader { bytes: input, pos: 0 };
+    let len = reader.varint() as usize;
+    let mut pos = reader.pos;
+    let mut out = Vec::with_capacity(len);
+    while pos < input.len() {
+        let tag = input[pos];
+        pos += 1;
+        let (copy_len, offset) = match tag & 3 {
+            0 => {
+                let mut literal = (tag >> 2) as usize;
+                if literal >= 60 {
+                    let bytes = literal - 59;
+                    literal = le(&input[pos..pos + bytes]) as usize;
+                    pos += bytes;
+                }
+                out.extend_from_slice(&input[pos..pos + literal + 1]);
+                pos += literal + 1;
+                continue;
+            }
+            1 => {
+                pos += 1;
+                (4 + (tag >> 2 & 7) as usize, ((tag >> 5) as usize) << 8 | input[pos - 1] as usize)
+            }
+            2 => {
+                pos += 2;
+                ((tag >> 2) as usize + 1, le(&input[pos - 2..pos]) as usize)
+            }
+            _ => {
+                pos += 4;
+                ((tag >> 2) as usize + 1, le(&input[pos - 4..pos]) as usize)
+            }
+        };
+        assert!((1..=out.len()).contains(&offset), "copy from before the start of the block");
+        for _ in 0..copy_len {
+            out.push(out[out.len() - offset]);
+        }
+    }
+    assert_eq!(out.len(), len, "decompressed length differs from the preamble");
+    out
+}
+
+/// Reads a Parquet export back: the metadata of the footer, then the data page of every column
+/// chunk, decompressed and its PLAIN values decoded. Returns the column names and the rows,
+/// with the integers in decimal, the UTF-8 columns as text and the other byte arrays in hex.
+fn parquet_rows(bytes: &[u8]) -> (Vec<String>, Vec<Vec<String>>) {
+    assert!(bytes.starts_with(b"PAR1") && bytes.ends_with(b"PAR1"));
+    let footer_len = u32::from_le_bytes(bytes[bytes.len() - 8..bytes.len() - 4].try_into().unwrap()) as usize;
+    let footer_start = bytes.len() - 8 - footer_len;
+    let mut reader = ThriftReader { bytes: &bytes[footer_start..bytes.len() - 8], pos: 0 };
+    let meta = reader.structure();
+    assert_eq!(reader.pos, footer_len, "metadata shorter than its length");
+
+    let schema = meta.list(2);
+    assert_eq!(schema[0].int(5) as usize, schema.len() - 1, "root of the schema without every column");
+    let columns = &schema[1..];
+    let names: Vec<String> = columns.iter().map(|column| String::from_utf8(column.binary(4).to_vec()).unwrap()).collect();
+    for timestamp in columns.iter().filter_map(|column| column.field(10)) {
+        let adjusted = timestamp.field(8).and_then(|timestamp| timestamp.field(1));
+        assert!(matches!(adjusted, Some(Thrift::Bool(true))), "timestamp not adjusted to UTC: {:?}", timestamp);
+    }
+    let mut rows = Vec::new();
+    for group in meta.list(4) {
+        let group_rows = group.int(3) as usize;
+        let mut cells = vec![Vec::new(); group_rows];
+        let chunks = group.list(1);
+        assert_eq!(chunks.len(), columns.len());
+        for (chunk, column) in chunks.iter().zip(columns) {
+            let chunk_meta = chunk.field(3).unwrap();
+            assert_eq!(chunk_meta.int(1), column.int(1), "chunk of another type than its column");
+            assert_eq!(chunk_meta.int(4), 1, "column not compressed with Snappy");
+            assert_eq!(chunk_meta.int(5) as usize, group_rows);
+            let offset = chunk_meta.int(9) as usize;
+            let mut reader = ThriftReader { bytes: &bytes[offset..footer_start], pos: 0 };
+            let header = reader.structure();
+            assert_eq!(header.int(1), 0, "not a data page");
+            let page_start = offset + reader.pos;
+            let page = unsnappy(&bytes[page_start..page_start + header.int(3) as usize]);
+            assert_eq!(page.len(), header.int(2) as usize, "uncompressed page size differs");
+            assert_eq!(reader.pos + header.int(3) as usize, chunk_meta.int(7) as usize, "compressed chunk size differs");
+            let data_page = header.field(5).unwrap();
+            assert_eq!((data_page.int(1) as usize, data_page.int(2)), (group_rows, 0), "page not of PLAIN values of every row");
+
+            let mut pos = 0;
+            for row in &mut cells {
+                let mut take = |len: usize| {
+                    pos += len;
+                    &page[pos - len..pos]
+                };
+                row.push(match column.int(1) {
+                    1 => i32::from_le_bytes(take(4).try_into().unwrap()).to_string(),
+                    2 => i64::from_le_bytes(take(8).try_into().unwrap()).to_string(),
+                    6 => {
+                        let len = u32::from_le_bytes(take(4).try_into().unwrap()) as usize;
+                        let value = take(len);
+                        match column.field(6) {
+                            Some(Thrift::Int(0)) => String::from_utf8(value.to_vec()).unwrap(),
+                            _ => value.iter().map(|byte| format!("{:02x}", byte)).collect(),
+                        }
+                    }
+                    physical_type => panic!("unexpected physical type {}", physical_type),
+                });
+            }
+            assert_eq!(pos, page.len(), "page longer than its values");
+        }
+        rows.extend(cells);
+    }
+    assert_eq!(meta.int(3) as usize, rows.len(), "row count of the metadata differs");
+    (names, rows)
+}
+
+/// The columns of the Parquet and Arrow exports.
+const COLUMNAR_COLUMNS: &[&str] = &["frame_no", "ts", "src_mac", "dst_mac", "cmd", "index", "adp", "ado", "length", "wkc", "data"];
+
+/// The captures and selections that the Parquet and Arrow exports are read back from.
+const READ_BACK_EXPORTS: &[(&str, &[&str])] = &[("chained_datagrams", &[]), ("chained_datagrams", &["--cmd", "LRW"]), ("cyclic", &[])];
+
+/// The cells of the columns of the Parquet and Arrow exports, taken from the CSV export of a
+/// capture and selection.
+fn datagram_cells(fixture: &str, filter: &[&str]) -> Vec<Vec<String>> {
+    let csv = export(fixture, &[&["--columns", "frame,ts,src_mac,dst_mac,cmd,index,adp,ado,length,wkc,data"], filter].concat());
+    let cells: Vec<Vec<String>> = csv.lines().skip(1).filter(|line| line.contains(',')).map(|line| {
+        let mut cells: Vec<String> = line.split(',').map(str::to_string).collect();
+        // the CSV export writes the ADO in hex
+        cells[7] = i64::from_str_radix(&cells[7], 16).unwrap().to_string();
+        cells
+    }).collect();
+    assert!(!cells.is_empty());
+    cells
+}
+
+/// The Parquet export read back holds the datagrams of the CSV export of the same capture and
+/// selection, row for row.
+#[test]
+fn parquet_read_back() {
+    for &(fixture, filter) in READ_BACK_EXPORTS {
+        let path = PathBuf::from(env!("CARGO_TARGET_TMPDIR")).join(format!("read_back_{}{}.parquet", fixture, filter.concat()));
+        let output = run(fixture, &[&["--format", "parquet", "--output", path.to_str().unwrap()], filter].concat());
+        assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+        let (names, rows) = parquet_rows(&fs::read(&path).unwrap());
+        assert_eq!(names, COLUMNAR_COLUMNS);
+
+        assert_eq!(rows, datagram_cells(fixture, filter), "{} {:?}", fixture, filter);
+    }
+}
+
+/// A FlatBuffers table of the Arrow metadata, at its position in the buffer of its message.
+#[derive(Clone, Copy)]
+struct Flat<'a> {
+    buf: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Flat<'a> {
+    fn root(buf: &'a [u8]) -> Self {
+        Flat { buf, pos: le(&buf[..4]) as usize }
+    }
+
+    /// Where a field is in the table, if the vtable has it.
+    fn field(&self, id: usize) -> Option<usize> {
+        let vtable = (self.pos as i64 - le(&self.buf[self.pos..self.pos + 4]) as i32 as i64) as usize;
+        let vtable_len = le(&self.buf[vtable..vtable + 2]) as usize;
+        let entry = vtable + 4 + 2 * id;
+        let offset = if entry < vtable + vtable_len { le(&self.buf[entry..entry + 2]) as usize } else { 0 };
+        (offset != 0).then_some(self.pos + offset)
+    }
+
+    /// A scalar field of `size` bytes, 0 if absent.
+    fn int(&self, id: usize, size: usize) -> u64 {
+        self.field(id).map_or(0, |at| le(&self.buf[at..at + size]))
+    }
+
+    fn target(&self, at: usize) -> usize {
+        at + le(&self.buf[at..at + 4]) as usize
+    }
+
+    fn table(&self, id: usize) -> Flat<'a> {
+        Flat { buf: self.buf, pos: self.target(self.field(id).expect("table field absent")) }
+    }
+
+    /// The start and length of a vector field.
+    fn vector(&self, id: usize) -> (usize, usize) {
+        let target = self.target(self.field(id).expect("vector field absent"));
+        (target + 4, le(&self.buf[target..target + 4]) as usize)
+    }
+
+    fn string(&self, id: usize) -> &'a str {
+        let (start, len) = self.vector(id);
+        assert_eq!(self.buf[start + len], 0, "string without its terminator");
+        std::str::from_utf8(&self.buf[start..start + len]).unwrap()
+    }
+
+    fn tables(&self, id: usize) -> Vec<Flat<'a>> {
+        let (start, len) = self.vector(id);
+        (0..len).map(|i| Flat { buf: self.buf, pos: self.target(start + 4 * i) }).collect()
+    }
+
+    /// A vector of structs of `fields` 8-byte fields, which must be 8-byte aligned.
+    fn structs(&self, id: usize, fields: usize) -> Vec<Vec<u64>> {
+        let (start, len) = self.vector(id);
+        assert_eq!(start % 8, 0, "structs not aligned");
+        (0..len * fields).map(|i| le(&self.buf[start + 8 * i..start + 8 * i + 8])).collect::<Vec<_>>().chunks(fields).map(<[u64]>::to_vec).collect()
+    }
+}
+
+/// A little-endian unsigned integer of up to 8 bytes.
+fn le(bytes: &[u8]) -> u64 {
+    bytes.iter().rev().fold(0, |value, &byte| value << 8 | byte as u64)
+}
+
+/// Reads an encapsulated message of an Arrow IPC file at `offset`: the continuation marker and
+/// the metadata length, then the `Message` table of the given header type. Returns the message,
+/// its header and where its body starts.
+fn arrow_message(bytes: &[u8], offset: usize, header_type: u64) -> (Flat<'_>, Flat<'_>, usize) {
+    assert_eq!(offset % 8, 0, "message not aligned");
+    assert_eq!(le(&bytes[offset..offset + 4]), 0xFFFF_FFFF, "message without its continuation marker");
+    let metadata_len = le(&bytes[offset + 4..offset + 8]) as usize;
+    assert_eq!(metadata_len % 8, 0, "metadata not padded");
+    let message = Flat::root(&bytes[offset + 8..offset + 8 + metadata_len]);
+    assert_eq!(message.int(0, 2), 4, "metadata not of version V5");
+    assert_eq!(message.int(1, 1), header_type);
+    (message, message.table(2), offset + 8 + metadata_len)
+}
+
+/// The names of the fields of an Arrow schema, with their `Type` union member and the width in
+/// bytes of their values, 0 for the variable length ones.
+fn arrow_fields(schema: Flat) -> Vec<(String, u64, usize)> {
+    schema.tables(1).iter().map(|field| {
+        let name = field.string(0);
+        assert_eq!(field.int(1, 1), 0, "{} nullable", name);
+        assert!(field.tables(5).is_empty(), "{} with children", name);
+        let (type_type, field_type) = (field.int(2, 1), field.table(3));
+        let width = match type_type {
+            2 => {
+                assert_eq!(field_type.int(1, 1), 1, "{} not signed", name);
+                field_type.int(0, 4) as usize / 8
+            }
+            4 | 5 => 0,
+            10 => {
+                assert_eq!((field_type.int(0, 2), field_type.string(1)), (3, "UTC"), "{} not in nanoseconds in UTC", name);
+                8
+            }
+            _ => panic!("{} of unexpected type {}", name, type_type),
+        };
+        (name.to_string(), type_type, width)
+    }).collect()
+}
+
+/// Reads an Arrow export back: the schema message, then the record batches the footer indexes,
+/// every buffer checked to be aligned within the body of its batch and their values decoded.
+/// Returns the column names and the rows, with the integers and timestamps in decimal, the UTF-8
+/// columns as text and the binary column in hex.
+fn arrow_rows(bytes: &[u8]) -> (Vec<String>, Vec<Vec<String>>) {
+    assert!(bytes.starts_with(b"ARROW1\0\0") && bytes.ends_with(b"ARROW1"));
+    let footer_len = le(&bytes[bytes.len() - 10..bytes.len() - 6]) as usize;
+    let footer = Flat::root(&bytes[bytes.len() - 10 - footer_len..bytes.len() - 10]);
+    let (message, schema, body) = arrow_message(bytes, 8, 1);
+    assert_eq!(message.int(3, 8), 0, "schema message with a body");
+    let fields = arrow_fields(schema);
+    assert_eq!(arrow_fields(footer.table(1)), fields, "footer schema differs");
+
+    let mut rows = Vec::new();
+    let mut next = body;
+    for block in footer.structs(3, 3) {
+        let (offset, metadata_len, body_len) = (block[0] as usize, block[1] as u32 as usize, block[2] as usize);
+        assert_eq!(offset, next, "record batch not right after the previous message");
+        let (message, batch, body) = arrow_message(bytes, offset, 3);
+        assert_eq!(body - offset, metadata_len, "metadata length of the block differs");
+        assert_eq!(message.int(3, 8) as usize, body_len, "body length of the block differs");
+        let body = &bytes[body..body + body_len];
+        next = offset + metadata_len + body_len;
+
+        let length = batch.int(0, 8) as usize;
+        let nodes = batch.structs(1, 2);
+        assert_eq!(nodes.len(), fields.len());
+        assert!(nodes.iter().all(|node| node[..] == [length as u64, 0]), "column of another length or with nulls");
+        let mut buffers = batch.structs(2, 2).into_iter().map(|buffer| {
+            let (start, len) = (buffer[0] as usize, buffer[1] as usize);
+            assert_eq!(start % 8, 0, "buffer not aligned");
+            assert!(start + len <= body.len(), "buffer past the body");
+            &body[start..start + len]
+        });
+        let mut cells = vec![Vec::new(); length];
+        for (name, type_type, width) in &fields {
+            assert!(buffers.next().unwrap().is_empty(), "{} with a validity buffer", name);
+            let column: Vec<String> = match width {
+                0 => {
+                    let offsets: Vec<usize> = buffers.next().unwrap().chunks(4).map(|offset| le(offset) as usize).collect();
+                    let data = buffers.next().unwrap();
+                    assert_eq!((offsets.len(), offsets[0], offsets[length]), (length + 1, 0, data.len()), "{} offsets differ", name);
+                    offsets.windows(2).map(|range| {
+                        let value = &data[range[0]..range[1]];
+                        if *type_type == 5 {
+                            String::from_utf8(value.to_vec()).unwrap()
+                        } else {
+                            value.iter().map(|byte| format!("{:02x}", byte)).collect()
+                        }
+                    }).collect()
+                }
+                &width => {
+                    let data = buffers.next().unwrap();
+                    assert_eq!(data.len(), length * width, "{} of another length", name);
+                    // sign extended from the width of the values
+                    let shift = 64 - 8 * width;
+                    data.chunks(width).map(|value| (((le(value) << shift) as i64) >> shift).to_string()).collect()
+                }
+            };
+            for (row, cell) in cells.iter_mut().zip(column) {
+                row.push(cell);
+            }
+        }
+        assert!(buffers.next().is_none(), "buffers left over");
+        rows.extend(cells);
+    }
+    assert_eq!(next, bytes.len() - 10 - footer_len, "footer not right after the last record batch");
+    (fields.into_iter().map(|(name, _, _)| name).collect(), rows)
+}
+
+/// The Arrow export read back holds the datagrams of the CSV export of the same capture and
+/// selection, row for row.
+#[test]
+fn arrow_read_back() {
+    for &(fixture, filter) in READ_BACK_EXPORTS {
+        let path = PathBuf::from(env!("CARGO_TARGET_TMPDIR")).join(format!("read_back_{}{}.arrow", fixture, filter.concat()));
+        let output = run(fixture, &[&["--format", "arrow", "--output", path.to_str().unwrap()], filter].concat());
+        assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+        let (names, rows) = arrow_rows(&fs::read(&path).unwrap());
+        assert_eq!(names, COLUMNAR_COLUMNS);
+        assert_eq!(rows, datagram_cells(fixture, filter), "{} {:?}", fixture, filter);
+    }
+}
+
+/// The Arrow export is compared as a hex dump too, with its magic at both ends.
+#[test]
+fn arrow_export() {
+    for (suffix, filter) in [("", &[][..]), ("_window", &["--adp", "0x1001", "--from", "1ms"][..])] {
+        let path = PathBuf::from(env!("CARGO_TARGET_TMPDIR")).join(format!("chained_datagrams{}.arrow", suffix));
+        let output = run("chained_datagrams", &[&["--format", "arrow", "--output", path.to_str().unwrap()], filter].concat());
+        assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+        let bytes = fs::read(&path).unwrap();
+        assert!(bytes.starts_with(b"ARROW1\0\0") && bytes.ends_with(b"ARROW1"));
+        let dump: String = bytes.chunks(16).map(|line| line.iter().map(|byte| format!("{:02x}", byte)).collect::<String>() + "\n").collect();
+        assert_golden(&format!("chained_datagrams_arrow{}.hex", suffix), &dump);
+    }
+}
+
+#[test]
+fn parse_frame_api() {
+    use ecat_packet_analyzer_rs::parser::ecat::{EtherCATCommand, FrameContent, FrameType};
+    use ecat_packet_analyzer_rs::ParseError;
+
+    let mut frame = vec![0xff; 6];
+    frame.extend([0x00, 0x1b, 0x21, 0xaa, 0xbb, 0x01, 0x88, 0xa4]);
+    // EtherCAT header, then a last BRD of 2 bytes to register 0x0130 answered by 3 slaves.
+    frame.extend([0x0e, 0x10, 0x07, 0x05, 0x00, 0x00, 0x30, 0x01, 0x02, 0x00, 0x00, 0x00, 0x08, 0x00, 0x03, 0x00]);
+    let ecat_frame = ecat_packet_analyzer_rs::parse_frame(&frame).unwrap();
+    assert_eq!(ecat_frame.datagrams.len(), 1);
+    let datagram = &ecat_frame.datagrams[0];
+    assert_eq!((datagram.header.cmd, datagram.header.index, datagram.header.offset_addr), (EtherCATCommand::BRD, 5, 0x0130));
+    assert_eq!((datagram.data, datagram.wkc), (&[0x08, 0x00][..], 3));
+
+    // network variables instead of datagrams: the publisher header, then a variable of 2 bytes.
+    let mut network_variables = frame[..14].to_vec();
+    network_variables.extend([0x16, 0x40, 0x00, 0x1b, 0x21, 0xaa, 0xbb, 0x01, 0x01, 0x00, 0x07, 0x00, 0x00, 0x00]);
+    network_variables.extend([0x03, 0x00, 0x34, 0x12, 0x02, 0x00, 0x01, 0x00, 0xaa, 0xbb]);
+    let ecat_frame = ecat_packet_analyzer_rs::parse_frame(&network_variables).unwrap();
+    assert_eq!(ecat_frame.header.frame_type(), FrameType::NetworkVariables);
+    assert!(ecat_frame.datagrams.is_empty());
+    let Some(FrameContent::NetworkVariables(content)) = &ecat_frame.content else {
+        panic!("no network variables");
+    };
+    assert_eq!((content.publisher_id, content.cycle, content.variables.len()), ([0x00, 0x1b, 0x21, 0xaa, 0xbb, 0x01], 7, 1));
+    let variable = &content.variables[0];
+    assert_eq!((variable.index, variable.hash, variable.quality, variable.data), (3, 0x1234, 1, &[0xaa, 0xbb][..]));
+    let truncated = ecat_packet_analyzer_rs::parse_frame(&network_variables[..network_variables.len() - 1]);
+    assert!(matches!(truncated, Err(ParseError::TruncatedNetworkVariables { needed: 22, available: 21 })));
+
+    let truncated = ecat_packet_analyzer_rs::parse_frame(&frame[..20]);
+    assert!(matches!(truncated, Err(ParseError::TruncatedDatagram { datagram: 0, needed: 10, available: 4 })));
+
+    frame[12..14].copy_from_slice(&[0x08, 0x00]);
+    assert!(ecat_packet_analyzer_rs::parse_frame(&frame).is_err());
+    assert!(ecat_packet_analyzer_rs::parse_frame(&frame[..10]).is_err());
+}
+
+#[test]
+fn mailbox_error_api() {
+    use ecat_packet_analyzer_rs::parser::mailbox::{self, MailboxError};
+    use ecat_packet_analyzer_rs::ParseError;
+
+    let error = MailboxError::parse(&[0x01, 0x00, 0x02, 0x00]).unwrap();
+    assert_eq!((error.code, mailbox::error_message(error.code)), (0x0002, Some("Mailbox protocol not supported")));
+    assert!(matches!(MailboxError::parse(&[0x01, 0x00, 0x02]), Err(ParseError::TruncatedMailboxError { len: 3 })));
+    assert!(matches!(MailboxError::parse(&[0x02, 0x00, 0x02, 0x00]), Err(ParseError::NotMailboxError(2))));
+}
+
+#[test]
+fn datagram_reader_api() {
+    use ecat_packet_analyzer_rs::{EcatPcapReader, ParseError, ReadError};
+
+    // the reader yields the datagrams the CSV export has rows for.
+    let columns = ["--columns", "frame,ts,cmd,index,adp,length,wkc,data"];
+    for fixture in ["chained_datagrams", "legacy_pcap_nanoseconds", "udp_encapsulated", "vlan_tagged", "mixed_traffic", "jumbo_frame", "jumbo_frame_pcap", "frame_types"] {
+        let file = fs::File::open(fixture_path(fixture)).unwrap();
+        let rows: Vec<String> = EcatPcapReader::new(file).map(|record| {
+            let record = record.unwrap();
+            let data: String = record.data().iter().map(|byte| format!("{:02x}", byte)).collect();
+            let header = &record.header;
+            format!("{},{},{},{},{},{},{},{}", record.frame_number, record.ts_ns, header.cmd, header.index, header.slave_addr,
+                header.length, record.wkc, data)
+        }).collect();
+        let output = export(fixture, &columns);
+        let expected: Vec<&str> = output.lines().skip(1)
+            .filter(|line| *line != "unknown block" && !line.starts_with("num_blocks: "))
+            .collect();
+        assert_eq!(rows, expected, "datagrams of {}", fixture);
+    }
+
+    let file = fs::File::open(fixture_path("truncated")).unwrap();
+    let records: Vec<_> = EcatPcapReader::new(file).collect();
+    assert_eq!(records.len(), 4);
+    let error = ParseError::TruncatedDatagram { datagram: 1, needed: 10, available: 4 };
+    assert!(records[..3].iter().all(|record| record.as_ref().is_ok_and(|record| record.frame_number == 1)));
+    assert_eq!(records[3].as_ref().unwrap_err(), &ReadError::Frame { frame_number: 2, error });
+
+    assert!(matches!(EcatPcapReader::new(&b"not a capture"[..]).next(), Some(Err(ReadError::Capture(_)))));
+    assert!(EcatPcapReader::new(&b""[..]).next().is_none());
+}
+
+#[test]
+fn datagram_stream_api() {
+    use ecat_packet_analyzer_rs::stream::{AsyncRead, Stream};
+    use ecat_packet_analyzer_rs::{EcatPcapReader, EcatStream, ReadError};
+    use std::pin::Pin;
+    use std::task::{Context, Poll, Waker};
+
+    /// An input handing out a few bytes at a time, pending before every read.
+    struct Trickle<'a> {
+        data: &'a [u8],
+        ready: bool,
+    }
+
+    impl AsyncRead for Trickle<'_> {
+        fn poll_read(mut self: Pin<&mut Self>, cx: &mut Context<'_>, buf: &mut [u8]) -> Poll<std::io::Result<usize>> {
+            self.ready = !self.ready;
+            if !self.ready {
+                cx.waker().wake_by_ref();
+                return Poll::Pending;
+            }
+            let len = buf.len().min(self.data.len()).min(7);
+            buf[..len].copy_from_slice(&self.data[..len]);
+            self.data = &self.data[len..];
+            Poll::Ready(Ok(len))
+        }
+    }
+
+    fn collect(input: impl AsyncRead + Unpin) -> Vec<Result<String, ReadError>> {
+        let mut stream = EcatStream::new(input);
+        let mut cx = Context::from_waker(Waker::noop());
+        let mut records = Vec::new();
+        loop {
+            match Pin::new(&mut stream).poll_next(&mut cx) {
+                Poll::Ready(Some(record)) => records.push(record.map(|record| format!("{:?}", record))),
+                Poll::Ready(None) => return records,
+                Poll::Pending => {}
+            }
+        }
+    }
+
+    // the stream yields the records and errors of the reader, however the input is split.
+    for fixture in ["chained_datagrams", "legacy_pcap_nanoseconds", "jumbo_frame", "jumbo_frame_pcap", "frame_types", "truncated"] {
+        let bytes = fs::read(fixture_path(fixture)).unwrap();
+        let expected: Vec<_> = EcatPcapReader::new(&bytes[..]).map(|record| record.map(|record| format!("{:?}", record))).collect();
+        assert_eq!(collect(&bytes[..]), expected, "datagrams of {}", fixture);
+        assert_eq!(collect(Trickle { data: &bytes, ready: false }), expected, "datagrams of {} in pieces", fixture);
+    }
+
+    assert!(matches!(collect(&b"not a capture"[..])[..], [Err(ReadError::Capture(_))]));
+    assert!(collect(&b""[..]).is_empty());
+    let bytes = fs::read(fixture_path("chained_datagrams")).unwrap();
+    let cut = collect(&bytes[..bytes.len() - 5]);
+    assert!(matches!(cut.last(), Some(Err(ReadError::Capture(_)))));
+
+    let mut stream = EcatStream::new(&bytes[..]);
+    let next = std::pin::pin!(stream.next_record());
+    let record = std::future::Future::poll(next, &mut Context::from_waker(Waker::noop()));
+    assert!(matches!(record, Poll::Ready(Some(Ok(record))) if record.frame_number == 1));
+}
+
+#[test]
+fn datagram_stream_adapter() {
+    use ecat_packet_analyzer_rs::stream::{self, EcatStream};
+    use ecat_packet_analyzer_rs::{DatagramRecord, EcatPcapReader, ReadError};
+    use std::pin::Pin;
+    use std::task::{Context, Poll, Waker};
+
+    /// The traits of the `futures` crate, with their signatures.
+    mod futures {
+        use std::pin::Pin;
+        use std::task::{Context, Poll};
+
+        pub mod io {
+            use std::pin::Pin;
+            use std::task::{Context, Poll};
+
+            pub trait AsyncRead {
+                fn poll_read(self: Pin<&mut Self>, cx: &mut Context<'_>, buf: &mut [u8]) -> Poll<std::io::Result<usize>>;
+            }
+        }
+
+        pub trait Stream {
+            type Item;
+
+            fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>>;
+        }
+    }
+
+    struct Source<'a>(&'a [u8]);
+
+    impl futures::io::AsyncRead for Source<'_> {
+        fn poll_read(mut self: Pin<&mut Self>, _cx: &mut Context<'_>, buf: &mut [u8]) -> Poll<std::io::Result<usize>> {
+            let len = buf.len().min(self.0.len());
+            buf[..len].copy_from_slice(&self.0[..len]);
+            self.0 = &self.0[len..];
+            Poll::Ready(Ok(len))
+        }
+    }
+
+    // the adapters of the documentation of the stream module.
+    struct Input<R>(R);
+
+    impl<R: futures::io::AsyncRead + Unpin> stream::AsyncRead for Input<R> {
+        fn poll_read(mut self: Pin<&mut Self>, cx: &mut Context<'_>, buf: &mut [u8]) -> Poll<std::io::Result<usize>> {
+            Pin::new(&mut self.0).poll_read(cx, buf)
+        }
+    }
+
+    struct Records<R>(EcatStream<R>);
+
+    impl<R: stream::AsyncRead + Unpin> futures::Stream for Records<R> {
+        type Item = Result<DatagramRecord, ReadError>;
+
+        fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+            stream::Stream::poll_next(Pin::new(&mut self.0), cx)
+        }
+    }
+
+    let bytes = fs::read(fixture_path("chained_datagrams")).unwrap();
+    let mut records = Records(EcatStream::new(Input(Source(&bytes))));
+    let mut cx = Context::from_waker(Waker::noop());
+    let mut frames = Vec::new();
+    while let Poll::Ready(Some(record)) = futures::Stream::poll_next(Pin::new(&mut records), &mut cx) {
+        frames.push(record.unwrap().frame_number);
+    }
+    let expected: Vec<u64> = EcatPcapReader::new(&bytes[..]).map(|record| record.unwrap().frame_number).collect();
+    assert_eq!(frames, expected);
+}
+
+#[test]
+fn capabilities() {
+    let output = Command::new(env!("CARGO_BIN_EXE_ecat-packet-analyzer-rs"))
+        .arg("--capabilities")
+        .output()
+        .expect("failed to run the exporter");
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+    let capabilities: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    assert_eq!(capabilities["schema"], 1);
+    assert_eq!(capabilities["input_formats"], serde_json::json!(["pcapng", "pcap"]));
+    assert_eq!(capabilities["input_compressions"], serde_json::json!(["gzip", "zstd", "xz"]));
+    assert_eq!(capabilities["output_formats"], serde_json::json!(["csv", "jsonl", "markdown-report", "influx", "parquet", "arrow"]));
+    let listed = |key: &str, value: &str| capabilities[key].as_array().unwrap().iter().any(|item| item == value);
+    assert!(listed("subcommands", "foe") && listed("subcommands", "batch"));
+    assert!(listed("decoders", "coe"));
+    assert!(listed("filter_keys", "wkc"));
+    assert!(listed("options", "file") && listed("options", "group-by"));
+}
+
+#[test]
+fn examples() {
+    let examples = |args: &[&str]| {
+        let output = Command::new(env!("CARGO_BIN_EXE_ecat-packet-analyzer-rs"))
+            .arg("examples")
+            .args(args)
+            .output()
+            .expect("failed to run the exporter");
+        assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+        String::from_utf8(output.stdout).unwrap()
+    };
+    assert_golden("examples.txt", &examples(&[]));
+    let dir = PathBuf::from(env!("CARGO_TARGET_TMPDIR")).join("examples");
+    // each demonstration runs on a copy of a fixture, as its test does.
+    for (name, golden) in [("wkc-fault", "chained_datagrams_wkc.csv"), ("coe-startup", "pdo_mapping_pdos.csv"),
+                           ("firmware-update", "foe_update_sessions.csv"), ("slave-reboot", "slave_reboot.md")] {
+        assert_golden(golden, &examples(&[name, "--output-dir", dir.to_str().unwrap()]));
+    }
+}
+
+#[test]
+fn stats_out_final_snapshot() {
+    let path = PathBuf::from(env!("CARGO_TARGET_TMPDIR")).join("clock_events_health.json");
+    run("clock_events", &["--stats-out", path.to_str().unwrap()]);
+    let health: serde_json::Value = serde_json::from_str(&fs::read_to_string(&path).expect("no health file")).unwrap();
+    assert_eq!(health["finished"], true);
+    assert_eq!(health["packets"], 5);
+    assert_eq!(health["capture_drops"], 5);
+    assert_eq!(health["events"], 4);
+    assert_eq!(health["last_capture_time"], "1700000005.001500000s");
+    assert_eq!(health["last_capture_time_ns"], 1_700_000_005_001_500_000u64);
+
+    for args in [&["--format", "parquet", "--output", "/dev/null"][..], &["--format", "markdown-report"], &["states"]] {
+        let output = run("clock_events", &[&["--stats-out", path.to_str().unwrap()], args].concat());
+        assert!(!output.status.success());
+        assert_eq!(String::from_utf8(output.stderr).unwrap(), "--stats-out applies to the csv and jsonl exports only\n");
+    }
+}
+
+#[test]
+fn dc_correlation_skips_link_errors() {
+    assert_golden("link_errors_correlation.txt", &export("link_errors", &["dc"]));
+}
+
+/// Exports the first `blocks` blocks of a fixture, leaving a final checkpoint, then resumes on
+/// the whole fixture: the output, the events reported and the packets of `--write-pcap` must be
+/// those of a single export.
+fn check_resume(fixture: &str, blocks: usize, args: &[&str]) {
+    let input = fs::read(PathBuf::from(FIXTURES_DIR).join(format!("{}.pcapng", fixture))).unwrap();
+    let mut cut = 0;
+    for _ in 0..blocks {
+        cut += u32::from_le_bytes(input[cut + 4..cut + 8].try_into().unwrap()) as usize;
+    }
+    let tmp = PathBuf::from(env!("CARGO_TARGET_TMPDIR")).join(format!("resume_{}{}", fixture, args.concat().replace('/', "_")));
+    fs::create_dir_all(&tmp).unwrap();
+    let (partial, checkpoint, output) = (tmp.join("partial.pcapng"), tmp.join("checkpoint.json"), tmp.join("output.csv"));
+    fs::write(&partial, &input[..cut]).unwrap();
+    let _ = fs::remove_file(&output);
+    let event_lines = |stderr: &[u8]| -> Vec<String> {
+        String::from_utf8_lossy(stderr).lines().filter(|line| line.starts_with("Event ")).map(str::to_string).collect()
+    };
+
+    let mut events = Vec::new();
+    for (capture, resume) in [(&partial, false), (&PathBuf::from(FIXTURES_DIR).join(format!("{}.pcapng", fixture)), true)] {
+        let out = fs::OpenOptions::new().create(true).append(true).open(&output).unwrap();
+        let mut command = Command::new(env!("CARGO_BIN_EXE_ecat-packet-analyzer-rs"));
+        command.arg("--file").arg(capture).args(args).arg("--checkpoint").arg(&checkpoint);
+        command.args(["--checkpoint-interval", "0s"]);
+        if resume {
+            command.arg("--resume").arg(&checkpoint);
+        }
+        let run = command.stdout(out).output().expect("failed to run the exporter");
+        assert!(run.status.success());
+        events.extend(event_lines(&run.stderr));
+    }
+    let carved = args.iter().position(|arg| *arg == "--write-pcap").map(|i| PathBuf::from(args[i + 1]));
+    let resumed_carve = carved.as_ref().map(|carved| fs::read(carved).unwrap());
+    let expected = run(fixture, args);
+    assert_eq!(fs::read_to_string(&output).unwrap(), String::from_utf8(expected.stdout).unwrap(), "resumed export differs");
+    assert_eq!(events, event_lines(&expected.stderr), "resumed export reports other events");
+    assert_eq!(resumed_carve, carved.map(|carved| fs::read(carved).unwrap()), "resumed export writes other packets");
+}
+
+#[test]
+fn checkpoint_resume() {
+    check_resume("cyclic", 152, &[]);
+}
+
+#[test]
+fn checkpoint_resume_parallel() {
+    check_resume("cyclic", 152, &["--jobs", PARALLEL_JOBS]);
+}
+
+/// The mailboxes of the resumed export are located with the SyncManagers written before the
+/// checkpoint.
+#[test]
+fn checkpoint_resume_mailbox() {
+    check_resume("process_data_mailbox", 5, &["--mailbox"]);
+}
+
+/// The emergencies after the checkpoint are only looked for in the mailboxes of the SyncManagers
+/// written before it.
+#[test]
+fn checkpoint_resume_emergency() {
+    check_resume("process_data_emergency", 4, &[]);
+}
+
+/// A transaction open at the checkpoint, an FoE transfer or a fragmented EoE frame, keeps the
+/// ID of its first message.
+#[test]
+fn checkpoint_resume_ids() {
+    check_resume("foe_update", 5, &["--ids"]);
+    check_resume("eoe", 7, &["--ids"]);
+}
+
+/// A message repeated after the checkpoint is told from the one before it.
+#[test]
+fn checkpoint_resume_mailbox_sequences() {
+    check_resume("mailbox_errors", 9, &["--events"]);
+}
+
+/// An export of a window resumed with a wider one goes on at the first packet the window left
+/// out.
+#[test]
+fn checkpoint_resume_wider_window() {
+    let tmp = PathBuf::from(env!("CARGO_TARGET_TMPDIR")).join("resume_wider_window");
+    fs::create_dir_all(&tmp).unwrap();
+    let (checkpoint, output) = (tmp.join("checkpoint.json"), tmp.join("output.csv"));
+    for (option, narrow, wide) in [("--frames", "10..=50", "10..=100"), ("--to", "50ms", "100ms")] {
+        let _ = fs::remove_file(&output);
+        for (window, resume) in [(narrow, false), (wide, true)] {
+            let out = fs::OpenOptions::new().create(true).append(true).open(&output).unwrap();
+            let mut command = Command::new(env!("CARGO_BIN_EXE_ecat-packet-analyzer-rs"));
+            command.arg("--file").arg(fixture_path("cyclic")).args([option, window, "--frame-number", "--checkpoint"]).arg(&checkpoint);
+            if resume {
+                command.arg("--resume").arg(&checkpoint);
+            }
+            assert!(command.stdout(out).status().expect("failed to run the exporter").success());
+        }
+        let expected = String::from_utf8(run("cyclic", &[option, wide, "--frame-number"]).stdout).unwrap();
+        assert_eq!(fs::read_to_string(&output).unwrap(), expected, "resumed {} export differs", option);
+    }
+}
+
+/// The packets carved after the checkpoint follow those before it in the same section.
+#[test]
+fn checkpoint_resume_write_pcap() {
+    let tmp = PathBuf::from(env!("CARGO_TARGET_TMPDIR")).join("resume_write_pcap");
+    fs::create_dir_all(&tmp).unwrap();
+    let carved = tmp.join("carved.pcapng");
+    check_resume("cyclic", 145, &["--frames", "100..=200", "--write-pcap", carved.to_str().unwrap()]);
+}
+
+/// The rate limit window open at the checkpoint and the last frames seen go on after it.
+#[test]
+fn checkpoint_resume_throttle() {
+    check_resume("cyclic", 145, &["--rate-limit", "10/20ms"]);
+    check_resume("cyclic", 145, &["--only-changes"]);
+}
+
+#[test]
+fn rotated_files() {
+    let fixture = |name: &str| format!("{}/{}", FIXTURES_DIR, name);
+    let files = ["rotated_00001.pcapng", "rotated_00002.pcapng", "rotated_00003.pcap"].map(fixture);
+    let run_files = |files: &[String], args: &[&str]| {
+        let mut command = Command::new(env!("CARGO_BIN_EXE_ecat-packet-analyzer-rs"));
+        for file in files {
+            command.arg("--file").arg(file);
+        }
+        command.args(args).output().expect("failed to run the exporter")
+    };
+    let stdout = |output: Output| String::from_utf8(output.stdout).unwrap();
+    let pattern = [fixture("rotated_*")];
+    let exported = stdout(run_files(&pattern, &["--frame-number", "--file-column"]));
+    assert_golden("rotated_files.csv", &exported);
+    assert_eq!(stdout(run_files(&files, &["--frame-number", "--file-column"])), exported);
+    assert_golden("rotated_files.md", &stdout(run_files(&pattern, &["--format", "markdown-report"])));
+    assert_eq!(stdout(run_files(&pattern, &["--frames", "5..=8", "--file-column", "--columns", "frame,file"])),
+        "frame,file\n5,rotated_00002.pcapng\n6,rotated_00002.pcapng\n7,rotated_00002.pcapng\n8,rotated_00002.pcapng\nnum_blocks: 13\n");
+
+    // an export of the first two files, resumed once the third one is there, is cut back to
+    // its checkpoint.
+    let tmp = PathBuf::from(env!("CARGO_TARGET_TMPDIR")).join("rotated_files");
+    fs::create_dir_all(&tmp).unwrap();
+    let (checkpoint, output) = (tmp.join("checkpoint.json"), tmp.join("output.csv"));
+    let _ = fs::remove_file(&output);
+    for (count, resume) in [(2, false), (3, true)] {
+        let out = fs::OpenOptions::new().create(true).append(true).open(&output).unwrap();
+        let mut command = Command::new(env!("CARGO_BIN_EXE_ecat-packet-analyzer-rs"));
+        for file in &files[..count] {
+            command.arg("--file").arg(file);
+        }
+        command.args(["--frame-number", "--file-column", "--checkpoint"]).arg(&checkpoint);
+        if resume {
+            command.arg("--resume").arg(&checkpoint);
+        }
+        assert!(command.stdout(out).status().expect("failed to run the exporter").success());
+    }
+    assert_eq!(fs::read_to_string(&output).unwrap(), exported, "resumed export differs");
+
+    let index = tmp.join("rotated.idx");
+    assert!(run_files(&pattern, &["--index", index.to_str().unwrap(), "index", "--every", "4"]).status.success());
+    for args in [&["--frames", "10..=12"][..], &["--frames", "6"]] {
+        let with_index: Vec<&str> = args.iter().copied().chain(["--file-column", "--index", index.to_str().unwrap()]).collect();
+        let output = run_files(&pattern, &with_index);
+        assert!(String::from_utf8_lossy(&output.stderr).starts_with("Starting after frame"), "{:?}", args);
+        assert_eq!(stdout(output), stdout(run_files(&pattern, &with_index[..with_index.len() - 2])));
+    }
+
+    let missing = run_files(&[fixture("rotated_*.pcapng.gz")], &[]);
+    assert!(!missing.status.success());
+    assert!(String::from_utf8_lossy(&missing.stderr).contains("No file matches"));
+}
+
+#[test]
+fn merge_captures() {
+    let tmp = PathBuf::from(env!("CARGO_TARGET_TMPDIR")).join("merge_captures");
+    fs::create_dir_all(&tmp).unwrap();
+    let merged = tmp.join("merged.pcapng");
+    let written = export("align_a", &["merge", fixture_path("align_b").to_str().unwrap(), "--out", merged.to_str().unwrap()]);
+    assert_eq!(written, format!("77 packets of 2 captures merged into {}\n", merged.display()));
+    // an interface for each capture, named after it.
+    let bytes = fs::read(&merged).unwrap();
+    assert!(bytes.windows(14).any(|name| name == b"align_a.pcapng") && bytes.windows(14).any(|name| name == b"align_b.pcapng"));
+    let rows = |path: &PathBuf| {
+        let output = Command::new(env!("CARGO_BIN_EXE_ecat-packet-analyzer-rs"))
+            .arg("--file")
+            .arg(path)
+            .args(["--columns", "frame,time,cmd,index,src_mac,wkc"])
+            .output()
+            .expect("failed to run the exporter");
+        String::from_utf8(output.stdout).unwrap()
+    };
+    assert_golden("align_merged.csv", &rows(&merged));
+
+    // packets of the same time keep the order of the captures: merged with itself, a capture
+    // has each of its packets twice in a row, the frame numbers aside.
+    export("chained_datagrams", &["merge", fixture_path("chained_datagrams").to_str().unwrap(), "--out", merged.to_str().unwrap()]);
+    let datagrams = |path: &PathBuf| -> Vec<String> {
+        rows(path).lines().skip(1).filter_map(|row| row.split_once(',')).map(|(_, datagram)| datagram.to_string()).collect()
+    };
+    let original = datagrams(&fixture_path("chained_datagrams"));
+    // the datagrams of a packet share its time, the first field left.
+    let packets: Vec<&[String]> = original.chunk_by(|a, b| a.split(',').next() == b.split(',').next()).collect();
+    let expected: Vec<String> = packets.iter().flat_map(|packet| packet.iter().chain(packet.iter()).cloned()).collect();
+    let merged_rows = datagrams(&merged);
+    assert_eq!(merged_rows, expected);
+}
+
+#[test]
+fn write_pcap() {
+    let tmp = PathBuf::from(env!("CARGO_TARGET_TMPDIR")).join("write_pcap");
+    fs::create_dir_all(&tmp).unwrap();
+    let carved = tmp.join("carved.pcapng");
+    let carve = |fixture: &str, args: &[&str]| {
+        let output = run(fixture, &[args, &["--write-pcap", carved.to_str().unwrap()]].concat());
+        assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+        (String::from_utf8(output.stdout).unwrap(), String::from_utf8(output.stderr).unwrap())
+    };
+    let reexport = |args: &[&str]| {
+        let output = Command::new(env!("CARGO_BIN_EXE_ecat-packet-analyzer-rs"))
+            .arg("--file")
+            .arg(&carved)
+            .args(args)
+            .output()
+            .expect("failed to run the exporter");
+        String::from_utf8(output.stdout).unwrap()
+    };
+
+    // the packets of the window, with their capture times.
+    let columns = ["--columns", "time,cmd,index,src_mac,wkc,data"];
+    let (exported, written) = carve("cyclic", &[&["--frames", "342..=357"][..], &columns].concat());
+    assert_eq!(written, format!("16 packets written to {}\n", carved.display()));
+    // the blocks read differ, the rows do not.
+    let rows = |export: &str| export.lines().filter(|row| !row.starts_with("num_blocks")).map(String::from).collect::<Vec<_>>();
+    assert_eq!(rows(&reexport(&columns)), rows(&exported));
+
+    // the link-layer errors and the FCS of the packets are kept.
+    let (exported, _) = carve("link_errors", &[]);
+    assert_eq!(reexport(&[]), exported);
+
+    // the frames with a datagram of the filter, whole.
+    let (_, written) = carve("startup", &["--cmd", "FPWR"]);
+    assert_eq!(written, format!("6 packets written to {}\n", carved.display()));
+    assert_eq!(reexport(&["--columns", "cmd"]).lines().filter(|row| *row == "FPWR").count(), 6);
+    carve("chained_datagrams", &["--cmd", "BRD"]);
+    assert_eq!(reexport(&[]), export("chained_datagrams", &[]));
+
+    let output = run("cyclic", &["--format", "markdown-report", "--write-pcap", carved.to_str().unwrap()]);
+    assert!(!output.status.success());
+    assert_eq!(String::from_utf8(output.stderr).unwrap(), "--write-pcap applies to the csv and jsonl exports only\n");
+}
+
+#[test]
+fn compressed_input() {
+    let run_compressed = |compressed: &str, args: &[&str]| {
+        Command::new(env!("CARGO_BIN_EXE_ecat-packet-analyzer-rs"))
+            .arg("--file")
+            .arg(PathBuf::from(FIXTURES_DIR).join(compressed))
+            .args(args)
+            .output()
+            .expect("failed to run the exporter")
+    };
+    for (fixture, compressed) in [("chained_datagrams", "chained_datagrams.pcapng.gz"), ("legacy_pcap", "legacy_pcap.pcap.xz")] {
+        let expected = export(fixture, &[]);
+        assert_eq!(String::from_utf8(run_compressed(compressed, &[]).stdout).unwrap(), expected, "{} differs from {}", compressed, fixture);
+    }
+    let output = run_compressed("chained_datagrams.pcapng.gz", &["index"]);
+    assert!(!output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr).contains("needs uncompressed capture files"));
+
+    // the capture of a failing decompressor is an error, even when it ends with a whole block.
+    let output = run_compressed("truncated_archive.pcapng.gz", &[]);
+    assert!(!output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr).contains("gzip failed to decompress the capture (exit status: 1)"));
+}
+
+#[test]
+fn piped_input() {
+    use std::io::Write;
+    use std::process::Stdio;
+
+    // a pipe cannot be mapped, so it is read through the buffered readers.
+    for (fixture, args) in [("jumbo_frame", &[][..]), ("jumbo_frame_pcap", &[]), ("corrupt_block", &["--recover"]), ("truncated", &[])] {
+        let mut child = Command::new(env!("CARGO_BIN_EXE_ecat-packet-analyzer-rs"))
+            .args(["--file", "/dev/stdin"])
+            .args(args)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()
+            .expect("failed to run the exporter");
+        let capture = fs::read(fixture_path(fixture)).unwrap();
+        let mut stdin = child.stdin.take().unwrap();
+        let writer = std::thread::spawn(move || stdin.write_all(&capture));
+        let piped = child.wait_with_output().unwrap();
+        writer.join().unwrap().unwrap();
+        let mapped = run(fixture, args);
+        assert_eq!(String::from_utf8(piped.stdout).unwrap(), String::from_utf8(mapped.stdout).unwrap(), "output of {}", fixture);
+        assert_eq!(String::from_utf8(piped.stderr).unwrap(), String::from_utf8(mapped.stderr).unwrap(), "diagnostics of {}", fixture);
+    }
+}
+
+#[test]
+fn closed_output_pipe() {
+    use std::process::Stdio;
+
+    // the reader of the output is gone before the first line: the exporter stops quietly.
+    for args in [&[][..], &["--pair"], &["states"], &["stats"], &["top"]] {
+        let mut child = Command::new(env!("CARGO_BIN_EXE_ecat-packet-analyzer-rs"))
+            .arg("--file")
+            .arg(fixture_path("slave_reboot"))
+            .args(args)
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()
+            .expect("failed to run the exporter");
+        drop(child.stdout.take());
+        let output = child.wait_with_output().unwrap();
+        assert!(output.status.success(), "status of {:?}", args);
+        assert_eq!(String::from_utf8(output.stderr).unwrap(), "", "diagnostics of {:?}", args);
+    }
+}
+
+#[test]
+fn capture_index() {
+    let tmp = PathBuf::from(env!("CARGO_TARGET_TMPDIR")).join("capture_index");
+    fs::create_dir_all(&tmp).unwrap();
+    let (index, stale) = (tmp.join("cyclic.idx"), tmp.join("chained_datagrams.idx"));
+    let index_args = |path: &PathBuf| vec!["--index".to_string(), path.to_str().unwrap().to_string()];
+    let written = export("cyclic", &["--index", index.to_str().unwrap(), "index", "--every", "50"]);
+    assert_eq!(written, format!("400 frames, 8 entries written to {}\n", index.display()));
+    export("chained_datagrams", &["--index", stale.to_str().unwrap(), "index"]);
+
+    assert_golden("cyclic_frames.csv", &export("cyclic", &["--frames", "342..=357", "--frame-number"]));
+    for args in [&["--frames", "342..=357", "--frame-number"][..], &["--frames", "395"], &["--from", "0.05s", "--to", "0.06s"], &["--frames", "5..8"]] {
+        let sequential = export("cyclic", args);
+        let indexed: Vec<String> = args.iter().map(|arg| arg.to_string()).chain(index_args(&index)).collect();
+        let indexed: Vec<&str> = indexed.iter().map(String::as_str).collect();
+        assert_eq!(export("cyclic", &indexed), sequential, "the index changed the export of {:?}", args);
+        let parallel: Vec<&str> = indexed.iter().copied().chain(["--jobs", PARALLEL_JOBS]).collect();
+        assert_eq!(export("cyclic", &parallel), sequential, "the index changed the export of {:?}", args);
+    }
+    assert!(diagnostics("cyclic", &["--frames", "395", "--index", index.to_str().unwrap()]).starts_with("Starting after frame 350 with the index\n"));
+    // the first entry is past the start of the window.
+    assert!(!diagnostics("cyclic", &["--frames", "5..8", "--index", index.to_str().unwrap()]).contains("Starting after"));
+    let ignored = diagnostics("cyclic", &["--frames", "395", "--index", stale.to_str().unwrap()]);
+    assert!(ignored.starts_with("Ignoring the index, written before the capture changed"), "{}", ignored);
+
+    for (range, error) in [("0..5", "frames are numbered from 1"), ("7..7", "the range is empty"), ("x", "frame numbers are positive numbers")] {
+        let output = run("cyclic", &["--frames", range]);
+        assert!(!output.status.success());
+        assert!(String::from_utf8_lossy(&output.stderr).contains(error), "--frames {}", range);
+    }
+}
+
+#[test]
+fn top_talkers() {
+    assert_golden("chained_datagrams_top.txt", &export("chained_datagrams", &["top"]));
+    assert_golden("cyclic_top.txt", &export("cyclic", &["top", "--window", "50ms", "--count", "3"]));
+}
+
+/// Replays a fixture through an extcap interface and returns the frames written to the fifo.
+fn extcap_capture(fixture: &str, interface: &str) -> Vec<Vec<u8>> {
+    let input = PathBuf::from(FIXTURES_DIR).join(format!("{}.pcapng", fixture));
+    let fifo = PathBuf::from(env!("CARGO_TARGET_TMPDIR")).join(format!("extcap_{}_{}.pcap", fixture, interface));
+    let output = Command::new(env!("CARGO_BIN_EXE_ecat-packet-analyzer-rs"))
+        .args(["--extcap-interface", interface, "--capture", "--fifo"])
+        .arg(&fifo)
+        .arg("--file")
+        .arg(&input)
+        .output()
+        .expect("failed to run the exporter");
+    assert!(output.status.success(), "extcap capture failed");
+    let pcap = fs::read(&fifo).expect("no pcap written");
+    assert_eq!(pcap[..4], 0xA1B2_3C4Du32.to_le_bytes(), "not a nanosecond pcap");
+    assert_eq!(pcap[20..24], 1u32.to_le_bytes(), "not an Ethernet pcap");
+    let mut frames = Vec::new();
+    let mut rest = &pcap[24..];
+    while !rest.is_empty() {
+        let length = u32::from_le_bytes(rest[8..12].try_into().unwrap()) as usize;
+        frames.push(rest[16..16 + length].to_vec());
+        rest = &rest[16 + length..];
+    }
+    frames
+}
+
+#[test]
+fn extcap_interfaces() {
+    let output = Command::new(env!("CARGO_BIN_EXE_ecat-packet-analyzer-rs"))
+        .arg("--extcap-interfaces")
+        .output()
+        .expect("failed to run the exporter");
+    assert_golden("extcap_interfaces.txt", &String::from_utf8(output.stdout).unwrap());
+}
+
+#[test]
+fn extcap_replay() {
+    // the EoE interface writes the tunnelled frames counted by the eoe statistics.
+    let tunnelled = extcap_capture("eoe", "ecat-eoe");
+    let lengths: Vec<usize> = tunnelled.iter().map(Vec::len).collect();
+    assert_eq!(lengths.len(), 3);
+    assert_eq!(lengths.iter().sum::<usize>(), 42 + 242);
+    // the mailbox interface writes the EtherCAT frames unchanged.
+    let mailbox = extcap_capture("eoe", "ecat-mailbox");
+    assert!(!mailbox.is_empty());
+    assert!(mailbox.iter().all(|frame| frame[12..14] == [0x88, 0xA4]));
+    assert!(extcap_capture("cyclic", "ecat-mailbox").is_empty());
+}
+
+/// The body of an HTTP GET of `path` from `address`.
+fn http_get(address: &str, path: &str) -> String {
+    use std::io::{Read, Write};
+
+    let mut stream = std::net::TcpStream::connect(address).expect("cannot connect to the metrics endpoint");
+    write!(stream, "GET {} HTTP/1.1\r\nHost: {}\r\n\r\n", path, address).unwrap();
+    let mut response = String::new();
+    stream.read_to_string(&mut response).unwrap();
+    let (_, body) = response.split_once("\r\n\r\n").expect("no end of the HTTP headers");
+    body.to_string()
+}
+
+#[test]
+fn metrics_endpoint() {
+    use std::io::{BufRead, BufReader, Write};
+    use std::process::Stdio;
+
+    // the capture comes through a pipe kept open, as from a running capture tool.
+    let mut child = Command::new(env!("CARGO_BIN_EXE_ecat-packet-analyzer-rs"))
+        .args(["--file", "/dev/stdin", "--metrics-listen", "127.0.0.1:0", "--eni", "tests/fixtures/network.xml"])
+        .stdin(Stdio::piped())
+        .stdout(Stdio::null())
+        .stderr(Stdio::piped())
+        .spawn()
+        .expect("failed to run the exporter");
+    let mut stdin = child.stdin.take().unwrap();
+    stdin.write_all(&fs::read(fixture_path("eni_cycles")).unwrap()).unwrap();
+    let mut serving = String::new();
+    BufReader::new(child.stderr.take().unwrap()).read_line(&mut serving).unwrap();
+    let address = serving.trim().strip_prefix("Serving metrics on http://").and_then(|url| url.strip_suffix("/metrics")).unwrap().to_string();
+
+    let mut metrics = String::new();
+    for _ in 0..100 {
+        metrics = http_get(&address, "/metrics");
+        // every frame of the capture is counted.
+        if metrics.contains("ecat_frames_total{direction=\"out\"} 12\n") && metrics.contains("ecat_frames_total{direction=\"returned\"} 11\n") {
+            break;
+        }
+        std::thread::sleep(std::time::Duration::from_millis(20));
+    }
+    assert!(http_get(&address, "/").starts_with("Not found"));
+    drop(stdin);
+    assert!(child.wait().unwrap().success());
+    assert_golden("eni_cycles_metrics.txt", &metrics);
+
+    let output = run("eni_cycles", &["--metrics-listen", "127.0.0.1:0", "--format", "markdown-report"]);
+    assert!(!output.status.success());
+    assert_eq!(String::from_utf8(output.stderr).unwrap(), "--metrics-listen applies to the csv and jsonl exports only\n");
+}